@@ -0,0 +1,68 @@
+/// How the server compares nicks and channel names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseMapping {
+    Ascii,
+    /// ascii, plus []\~ being the uppercase of {}|^
+    Rfc1459,
+    /// like rfc1459 without ~ and ^
+    StrictRfc1459,
+}
+
+/// What servers use without saying anything
+impl Default for CaseMapping {
+    fn default() -> Self {
+        CaseMapping::Rfc1459
+    }
+}
+
+impl CaseMapping {
+    /// The value of the CASEMAPPING token of RPL_ISUPPORT
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "ascii" => Some(CaseMapping::Ascii),
+            "rfc1459" => Some(CaseMapping::Rfc1459),
+            "strict-rfc1459" => Some(CaseMapping::StrictRfc1459),
+            _ => None,
+        }
+    }
+
+    fn lower(&self, c: char) -> char {
+        match (self, c) {
+            (_, 'A'..='Z') => c.to_ascii_lowercase(),
+            (CaseMapping::Rfc1459 | CaseMapping::StrictRfc1459, '[') => '{',
+            (CaseMapping::Rfc1459 | CaseMapping::StrictRfc1459, ']') => '}',
+            (CaseMapping::Rfc1459 | CaseMapping::StrictRfc1459, '\\') => '|',
+            (CaseMapping::Rfc1459, '~') => '^',
+            _ => c,
+        }
+    }
+
+    pub fn normalize(&self, name: &str) -> String {
+        name.chars().map(|c| self.lower(c)).collect()
+    }
+
+    pub fn eq_ignore_case(&self, a: &str, b: &str) -> bool {
+        a.chars().count() == b.chars().count()
+            && a.chars()
+                .zip(b.chars())
+                .all(|(x, y)| self.lower(x) == self.lower(y))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_casemapping() {
+        assert!(CaseMapping::Rfc1459.eq_ignore_case("[Golem]", "{golem}"));
+        assert!(CaseMapping::Rfc1459.eq_ignore_case("a\\b~", "A|B^"));
+        assert!(!CaseMapping::Ascii.eq_ignore_case("[Golem]", "{golem}"));
+        assert!(CaseMapping::Ascii.eq_ignore_case("Golem", "gOLEM"));
+        assert!(CaseMapping::StrictRfc1459.eq_ignore_case("[x]\\", "{X}|"));
+        assert!(!CaseMapping::StrictRfc1459.eq_ignore_case("x~", "x^"));
+        assert!(!CaseMapping::Rfc1459.eq_ignore_case("golem", "golem_"));
+        assert_eq!(CaseMapping::Rfc1459.normalize("Coucou[m]"), "coucou{m}");
+    }
+}
@@ -0,0 +1,275 @@
+#[cfg(feature = "database")]
+use crate::Database;
+use crate::{Error, HttpConfig, Members, Metrics, MetricsHandle, Result};
+use once_cell::sync::OnceCell;
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+
+pub struct Config {
+    pub config_path: String,
+    /// the whole golem config, parsed on first use
+    parsed: OnceCell<serde_json::Value>,
+    /// built with the default settings on first use when not given
+    http_client: OnceCell<reqwest::Client>,
+    /// rendered by the golem under /metrics
+    metrics: Arc<Metrics>,
+    /// kept up to date by the golem
+    members: Arc<Members>,
+    #[cfg(feature = "database")]
+    database: Option<Database>,
+}
+
+impl Config {
+    pub fn new<S: Into<String>>(config_path: S) -> Self {
+        Config {
+            config_path: config_path.into(),
+            parsed: OnceCell::new(),
+            http_client: OnceCell::new(),
+            metrics: Arc::default(),
+            members: Arc::default(),
+            #[cfg(feature = "database")]
+            database: None,
+        }
+    }
+
+    /// Config given directly as dhall source instead of a file, for tests
+    pub fn from_dhall_str(source: &str) -> Result<Self> {
+        let parsed = serde_dhall::from_str(source)
+            .parse()
+            .map_err(|err| Error::Internal {
+                source: Box::new(err),
+                ctx: "Failed to parse inline config".to_string(),
+            })?;
+        let config = Config::new("<inline config>");
+        config.parsed.set(parsed).expect("config not parsed yet");
+        Ok(config)
+    }
+
+    pub fn with_http_client(self, client: reqwest::Client) -> Self {
+        self.http_client
+            .set(client)
+            .expect("http client already set");
+        self
+    }
+
+    /// Shared by all the plugins, cheap to clone. Plugins needing
+    /// special settings can still build their own client.
+    pub fn http_client(&self) -> reqwest::Client {
+        self.http_client
+            .get_or_init(|| {
+                HttpConfig::default()
+                    .build_client()
+                    .expect("http client with default settings")
+            })
+            .clone()
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Where the given plugin records its metrics, prefixed with its name
+    pub fn metrics(&self, plugin: &str) -> MetricsHandle {
+        MetricsHandle::new(Arc::clone(&self.metrics), plugin)
+    }
+
+    pub fn with_members(mut self, members: Arc<Members>) -> Self {
+        self.members = members;
+        self
+    }
+
+    /// Who is in the channels of the golem, empty until it joins some
+    pub fn members(&self) -> Arc<Members> {
+        Arc::clone(&self.members)
+    }
+
+    #[cfg(feature = "database")]
+    pub fn with_database(mut self, database: Database) -> Self {
+        self.database = Some(database);
+        self
+    }
+
+    /// None when the golem config has no database_path
+    #[cfg(feature = "database")]
+    pub fn database(&self) -> Option<&Database> {
+        self.database.as_ref()
+    }
+
+    /// The database of the plugins which cannot work without one,
+    /// an error naming the plugin when the golem config has no database_path
+    #[cfg(feature = "database")]
+    pub fn require_database(&self, plugin: &str) -> Result<Database> {
+        match &self.database {
+            Some(database) => Ok(database.clone()),
+            None => Err(anyhow::anyhow!("plugin {plugin} requires database_path").into()),
+        }
+    }
+
+    /// Same as `require_database` for the `check_config` of those plugins,
+    /// where the database isn't opened
+    #[cfg(feature = "database")]
+    pub fn check_database(&self, plugin: &str) -> Result<()> {
+        if self.database.is_some() {
+            return Ok(());
+        }
+        match self.parsed()?.get("database_path") {
+            Some(path) if !path.is_null() => Ok(()),
+            _ => Err(anyhow::anyhow!("plugin {plugin} requires database_path").into()),
+        }
+    }
+
+    /// Deserialize the record named after the plugin in the golem config,
+    /// for example `url = { youtube_api_key = None Text }` for the plugin url.
+    /// None when there is no such record.
+    pub fn plugin_section<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>> {
+        let section = match self.parsed()?.get(name) {
+            Some(section) => section.clone(),
+            None => return Ok(None),
+        };
+        serde_json::from_value(section)
+            .map(Some)
+            .map_err(|err| Error::Internal {
+                source: Box::new(err),
+                ctx: format!(
+                    "Invalid config section for plugin {name} in {}",
+                    self.config_path
+                ),
+            })
+    }
+
+    /// Whether the plugin's section has the given key, set to something else than None
+    pub fn has_plugin_key(&self, name: &str, key: &str) -> Result<bool> {
+        Ok(self
+            .parsed()?
+            .get(name)
+            .and_then(|section| section.get(key))
+            .map_or(false, |value| !value.is_null()))
+    }
+
+    /// The nicks allowed to use the admin commands, from the top-level
+    /// `admins` of the golem config, see `utils::account::is_admin`. Empty
+    /// when unset.
+    pub fn admins(&self) -> Result<Vec<String>> {
+        Ok(self.plugin_section("admins")?.unwrap_or_default())
+    }
+
+    fn parsed(&self) -> Result<&serde_json::Value> {
+        self.parsed.get_or_try_init(|| {
+            serde_dhall::from_file(&self.config_path)
+                .parse()
+                .map_err(|err| Error::Internal {
+                    source: Box::new(err),
+                    ctx: format!("Failed to read config at {}", self.config_path),
+                })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct UrlSection {
+        youtube_api_key: Option<String>,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct TwitchSection {
+        client_id: String,
+        watched_streams: Vec<String>,
+    }
+
+    fn fixture() -> Config {
+        Config::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/fixtures/plugin_sections.dhall"
+        ))
+    }
+
+    #[test]
+    fn test_plugin_sections() {
+        let config = fixture();
+        assert_eq!(
+            config.plugin_section::<UrlSection>("url").unwrap(),
+            Some(UrlSection {
+                youtube_api_key: Some("yt-key".to_string())
+            })
+        );
+        assert_eq!(
+            config.plugin_section::<TwitchSection>("twitch").unwrap(),
+            Some(TwitchSection {
+                client_id: "twitch-id".to_string(),
+                watched_streams: vec!["coucou".to_string(), "geekingfrog".to_string()],
+            })
+        );
+        assert_eq!(config.plugin_section::<UrlSection>("joke").unwrap(), None);
+    }
+
+    #[test]
+    fn test_errors_name_plugin_and_path() {
+        let config = fixture();
+        let err = config
+            .plugin_section::<TwitchSection>("url")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("plugin url"), "{err}");
+        assert!(err.contains("plugin_sections.dhall"), "{err}");
+
+        let config = Config::new("/nope/golem_config.dhall");
+        let err = config
+            .plugin_section::<UrlSection>("url")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("/nope/golem_config.dhall"), "{err}");
+    }
+
+    #[test]
+    fn test_inline_config() {
+        let config =
+            Config::from_dhall_str(r#"{ url = { youtube_api_key = None Text } }"#).unwrap();
+        assert_eq!(
+            config.plugin_section::<UrlSection>("url").unwrap(),
+            Some(UrlSection {
+                youtube_api_key: None
+            })
+        );
+        assert!(Config::from_dhall_str("{ url = ").is_err());
+    }
+
+    #[test]
+    fn test_has_plugin_key() {
+        let config = Config::from_dhall_str(
+            r#"{ url = { youtube_api_key = None Text }, twitch = { client_id = "twitch-id" } }"#,
+        )
+        .unwrap();
+        assert!(!config.has_plugin_key("url", "youtube_api_key").unwrap());
+        assert!(config.has_plugin_key("twitch", "client_id").unwrap());
+        assert!(!config.has_plugin_key("twitch", "client_secret").unwrap());
+        assert!(!config.has_plugin_key("joke", "anything").unwrap());
+    }
+
+    #[cfg(feature = "database")]
+    #[test]
+    fn test_check_database() {
+        let config = Config::from_dhall_str(r#"{ database_path = Some "golem.sqlite" }"#).unwrap();
+        assert!(config.check_database("seen").is_ok());
+        for source in ["{ database_path = None Text }", "{ url = {=} }"] {
+            let config = Config::from_dhall_str(source).unwrap();
+            let err = config.check_database("seen").unwrap_err().to_string();
+            assert!(err.contains("plugin seen"), "{source}: {err}");
+            assert!(config.require_database("seen").is_err(), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_admins() {
+        let config = Config::from_dhall_str(r#"{ admins = [ "Geekingfrog" ] }"#).unwrap();
+        assert_eq!(config.admins().unwrap(), vec!["Geekingfrog"]);
+        let config = Config::from_dhall_str("{ url = {=} }").unwrap();
+        assert_eq!(config.admins().unwrap(), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,157 @@
+use crate::utils::network::network;
+use crate::utils::private::is_private;
+use crate::{CaseMapping, Lang};
+use irc::proto::{ChannelExt, Command, Message};
+
+/// What plugins usually need to know about an inbound message,
+/// computed once by the golem for all the plugins.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MsgCtx {
+    /// None for private messages and for messages not sent to a channel
+    pub channel: Option<String>,
+    /// of the sender
+    pub nick: Option<String>,
+    pub is_private: bool,
+    /// `/me` messages, `text` is then the action itself
+    pub is_action: bool,
+    /// of PRIVMSG only, plugins shouldn't react to notices
+    pub text: Option<String>,
+    pub network: Option<String>,
+    /// to compare the nicks and the channels, set by the golem from what the
+    /// network advertised
+    pub casemapping: CaseMapping,
+    /// to reply in, set by the golem from the channel
+    pub lang: Lang,
+}
+
+impl MsgCtx {
+    /// Relies on the tags set by the golem for the network and private messages
+    pub fn from_message(msg: &Message) -> Self {
+        let is_private = is_private(msg);
+        let mut ctx = MsgCtx {
+            nick: msg.source_nickname().map(String::from),
+            is_private,
+            network: network(msg).map(String::from),
+            ..Default::default()
+        };
+        if let Command::PRIVMSG(target, text) = &msg.command {
+            if !is_private && target.is_channel_name() {
+                ctx.channel = Some(target.clone());
+            }
+            let action = text
+                .strip_prefix("\x01ACTION ")
+                .map(|t| t.strip_suffix('\x01').unwrap_or(t));
+            ctx.is_action = action.is_some();
+            ctx.text = Some(action.unwrap_or(text).to_string());
+        }
+        ctx
+    }
+
+    /// Where replies should go: the channel, or the sender in private
+    pub fn response_target(&self) -> Option<&str> {
+        self.channel.as_deref().or(if self.is_private {
+            self.nick.as_deref()
+        } else {
+            None
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::network::set_network;
+    use crate::utils::private::set_private;
+    use pretty_assertions::assert_eq;
+
+    fn privmsg(target: &str, text: &str) -> Message {
+        let mut msg = Message::new(
+            Some("alice!~alice@localhost"),
+            "PRIVMSG",
+            vec![target, text],
+        )
+        .unwrap();
+        set_network(&mut msg, "libera");
+        msg
+    }
+
+    #[test]
+    fn test_channel_message() {
+        let ctx = MsgCtx::from_message(&privmsg("#rust", "coucou toi"));
+        assert_eq!(
+            ctx,
+            MsgCtx {
+                channel: Some("#rust".to_string()),
+                nick: Some("alice".to_string()),
+                is_private: false,
+                is_action: false,
+                text: Some("coucou toi".to_string()),
+                network: Some("libera".to_string()),
+                casemapping: CaseMapping::Rfc1459,
+                lang: Lang::En,
+            }
+        );
+        assert_eq!(ctx.response_target(), Some("#rust"));
+    }
+
+    #[test]
+    fn test_private_message() {
+        let mut msg = privmsg("rustygolem", "coucou toi");
+        set_private(&mut msg);
+        let ctx = MsgCtx::from_message(&msg);
+        assert_eq!(
+            ctx,
+            MsgCtx {
+                channel: None,
+                nick: Some("alice".to_string()),
+                is_private: true,
+                is_action: false,
+                text: Some("coucou toi".to_string()),
+                network: Some("libera".to_string()),
+                casemapping: CaseMapping::Rfc1459,
+                lang: Lang::En,
+            }
+        );
+        assert_eq!(ctx.response_target(), Some("alice"));
+    }
+
+    #[test]
+    fn test_action() {
+        let ctx = MsgCtx::from_message(&privmsg("#rust", "\x01ACTION waves at everyone\x01"));
+        assert_eq!(
+            ctx,
+            MsgCtx {
+                channel: Some("#rust".to_string()),
+                nick: Some("alice".to_string()),
+                is_private: false,
+                is_action: true,
+                text: Some("waves at everyone".to_string()),
+                network: Some("libera".to_string()),
+                casemapping: CaseMapping::Rfc1459,
+                lang: Lang::En,
+            }
+        );
+    }
+
+    #[test]
+    fn test_other_commands() {
+        let msg = Message::new(
+            Some("alice!~alice@localhost"),
+            "NOTICE",
+            vec!["#rust", "coucou toi"],
+        )
+        .unwrap();
+        let ctx = MsgCtx::from_message(&msg);
+        assert_eq!(
+            ctx,
+            MsgCtx {
+                nick: Some("alice".to_string()),
+                ..Default::default()
+            }
+        );
+        assert_eq!(ctx.response_target(), None);
+
+        let join: Message = Command::JOIN("#rust".to_string(), None, None).into();
+        assert_eq!(MsgCtx::from_message(&join), MsgCtx::default());
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Allow an action at most once per `duration` for each key, typically
+/// the channel where a command was triggered.
+/// Only allowed attempts start a new cooldown, so spamming a command
+/// doesn't keep it blocked forever.
+pub struct Cooldown {
+    duration: Duration,
+    state: Mutex<CooldownState>,
+}
+
+struct CooldownState {
+    last_allowed: HashMap<String, Instant>,
+    last_prune: Option<Instant>,
+}
+
+impl Cooldown {
+    pub fn new(duration: Duration) -> Self {
+        Cooldown {
+            duration,
+            state: Mutex::new(CooldownState {
+                last_allowed: HashMap::new(),
+                last_prune: None,
+            }),
+        }
+    }
+
+    pub fn check(&self, key: &str) -> bool {
+        self.check_at(key, Instant::now())
+    }
+
+    /// Same as `check`, with the current time given by the caller.
+    /// A `now` earlier than a previous call counts as no time elapsed.
+    pub fn check_at(&self, key: &str, now: Instant) -> bool {
+        let mut state = self.state.lock().expect("cooldown lock");
+        let duration = self.duration;
+        // keys past their cooldown behave like unknown keys, drop them
+        let should_prune = match state.last_prune {
+            None => true,
+            Some(last) => now.saturating_duration_since(last) >= duration,
+        };
+        if should_prune {
+            state
+                .last_allowed
+                .retain(|_, last| now.saturating_duration_since(*last) < duration);
+            state.last_prune = Some(now);
+        }
+
+        match state.last_allowed.get(key) {
+            Some(last) if now.saturating_duration_since(*last) < duration => false,
+            _ => {
+                state.last_allowed.insert(key.to_string(), now);
+                true
+            }
+        }
+    }
+}
+
+/// Allow bursts of up to `capacity` actions for each key, refilled
+/// at `capacity` actions per `period`.
+pub struct TokenBucket {
+    period: Duration,
+    /// time for one token to come back
+    interval: Duration,
+    /// when each bucket is full again, kept as instants rather than a
+    /// fractional token count so that edges are exact
+    full_at: Mutex<HashMap<String, Instant>>,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: u32, period: Duration) -> Self {
+        assert!(capacity > 0, "a token bucket needs some capacity");
+        TokenBucket {
+            period,
+            interval: period / capacity,
+            full_at: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn per_minute(capacity: u32) -> Self {
+        TokenBucket::new(capacity, Duration::from_secs(60))
+    }
+
+    pub fn check(&self, key: &str) -> bool {
+        self.check_at(key, Instant::now())
+    }
+
+    /// Same as `check`, with the current time given by the caller.
+    /// A `now` earlier than a previous call counts as no time elapsed.
+    pub fn check_at(&self, key: &str, now: Instant) -> bool {
+        let mut full_at = self.full_at.lock().expect("token bucket lock");
+        // full buckets behave like unknown keys, drop them
+        full_at.retain(|_, full| *full > now);
+
+        let full = full_at.get(key).copied().unwrap_or(now).max(now);
+        if full.saturating_duration_since(now) + self.interval <= self.period {
+            full_at.insert(key.to_string(), full + self.interval);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn test_cooldown_edge() {
+        let cooldown = Cooldown::new(secs(10));
+        let t0 = Instant::now();
+        assert!(cooldown.check_at("#chan", t0));
+        assert!(cooldown.check_at("#other", t0), "keys are independent");
+        for ms in [1, 5_000, 9_999] {
+            assert!(
+                !cooldown.check_at("#chan", t0 + Duration::from_millis(ms)),
+                "{ms}ms after"
+            );
+        }
+        // denied attempts don't extend the cooldown
+        assert!(
+            cooldown.check_at("#chan", t0 + secs(10)),
+            "exactly at the edge"
+        );
+        assert!(!cooldown.check_at("#chan", t0 + secs(10)));
+        assert!(cooldown.check_at("#chan", t0 + secs(20)));
+    }
+
+    #[test]
+    fn test_cooldown_every_offset() {
+        let t0 = Instant::now();
+        for cooldown_ms in [1, 7, 100] {
+            for offset in 0..(3 * cooldown_ms) {
+                let cooldown = Cooldown::new(Duration::from_millis(cooldown_ms));
+                assert!(cooldown.check_at("k", t0));
+                let allowed = cooldown.check_at("k", t0 + Duration::from_millis(offset));
+                assert_eq!(allowed, offset >= cooldown_ms, "{offset}/{cooldown_ms}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_cooldown_clock_backwards() {
+        let cooldown = Cooldown::new(secs(10));
+        let t0 = Instant::now();
+        let later = t0 + secs(100);
+        assert!(cooldown.check_at("#chan", later));
+        assert!(!cooldown.check_at("#chan", t0));
+        assert!(!cooldown.check_at("#chan", later + secs(9)));
+        assert!(cooldown.check_at("#chan", later + secs(10)));
+        // an unknown key is allowed whatever the clock says
+        assert!(cooldown.check_at("#other", t0));
+    }
+
+    #[test]
+    fn test_cooldown_prunes_stale_keys() {
+        let cooldown = Cooldown::new(secs(10));
+        let t0 = Instant::now();
+        for i in 0..100 {
+            cooldown.check_at(&format!("#chan{i}"), t0);
+        }
+        assert_eq!(cooldown.state.lock().unwrap().last_allowed.len(), 100);
+        cooldown.check_at("#chan", t0 + secs(10));
+        assert_eq!(cooldown.state.lock().unwrap().last_allowed.len(), 1);
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let bucket = TokenBucket::per_minute(3);
+        let t0 = Instant::now();
+        assert!(bucket.check_at("#chan", t0));
+        assert!(bucket.check_at("#chan", t0));
+        assert!(bucket.check_at("#chan", t0));
+        assert!(!bucket.check_at("#chan", t0), "burst exhausted");
+        assert!(bucket.check_at("#other", t0));
+
+        // one token every 20s
+        assert!(!bucket.check_at("#chan", t0 + Duration::from_millis(19_999)));
+        assert!(bucket.check_at("#chan", t0 + secs(20)), "exactly one token");
+        assert!(!bucket.check_at("#chan", t0 + secs(20)));
+
+        // never more than the capacity
+        let later = t0 + secs(3600);
+        let allowed = (0..10).filter(|_| bucket.check_at("#chan", later)).count();
+        assert_eq!(allowed, 3);
+    }
+
+    #[test]
+    fn test_token_bucket_clock_backwards() {
+        let bucket = TokenBucket::new(1, secs(10));
+        let t0 = Instant::now();
+        let later = t0 + secs(100);
+        assert!(bucket.check_at("#chan", later));
+        assert!(!bucket.check_at("#chan", t0), "no refill going backwards");
+        assert!(!bucket.check_at("#chan", later + secs(5)));
+        assert!(bucket.check_at("#chan", later + secs(10)));
+    }
+}
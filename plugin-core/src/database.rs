@@ -0,0 +1,178 @@
+use crate::{Error, Result};
+use diesel::connection::SimpleConnection;
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Text};
+use std::sync::{Arc, Mutex};
+
+/// SQLite database shared by all the plugins. Each plugin prefixes its tables
+/// with its name (`url_history`, `karma_scores`…) and creates them with
+/// `ensure_schema`.
+#[derive(Clone)]
+pub struct Database {
+    path: String,
+    conn: Arc<Mutex<SqliteConnection>>,
+}
+
+impl Database {
+    pub fn open(path: &str) -> Result<Self> {
+        let conn = SqliteConnection::establish(path).map_err(|err| Error::Internal {
+            source: Box::new(err),
+            ctx: format!("Cannot open database at {path}"),
+        })?;
+        // wait a bit instead of failing when another connection holds a lock
+        conn.batch_execute("PRAGMA busy_timeout = 5000;")
+            .map_err(|err| wrap(err, path))?;
+        Ok(Database {
+            path: path.to_string(),
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Nothing is persisted, useful for tests
+    pub fn in_memory() -> Result<Self> {
+        Database::open(":memory:")
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Blocks the calling thread on the connection lock and the queries, see
+    /// `run` for the async code of the plugins
+    pub fn with_connection<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&SqliteConnection) -> QueryResult<T>,
+    {
+        let conn = self.conn.lock().expect("database lock");
+        f(&conn).map_err(|err| wrap(err, &self.path))
+    }
+
+    /// Like `with_connection`, on the blocking threads of tokio so that
+    /// waiting for the lock or the disk doesn't stall the other tasks
+    pub async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&SqliteConnection) -> QueryResult<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let db = self.clone();
+        tokio::task::spawn_blocking(move || db.with_connection(f))
+            .await
+            .map_err(|err| Error::Internal {
+                source: Box::new(err),
+                ctx: format!("Database task failed with {}", self.path),
+            })?
+    }
+}
+
+fn wrap(err: diesel::result::Error, path: &str) -> Error {
+    Error::Internal {
+        source: Box::new(err),
+        ctx: format!("Database error with {path}"),
+    }
+}
+
+/// Apply the migrations of a plugin not applied yet, in order. Migrations are
+/// identified by their position, so new ones must be appended, and existing
+/// ones never modified.
+pub fn ensure_schema(db: &Database, plugin: &str, migrations: &[&str]) -> Result<()> {
+    db.with_connection(|conn| {
+        conn.batch_execute(
+            "CREATE TABLE IF NOT EXISTS plugin_migrations (
+                plugin TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                applied_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (plugin, version)
+            );",
+        )?;
+        for (i, migration) in migrations.iter().enumerate() {
+            let version = i as i32 + 1;
+            conn.transaction::<_, diesel::result::Error, _>(|| {
+                // rolled back with the migration if it fails
+                let inserted = diesel::sql_query(
+                    "INSERT OR IGNORE INTO plugin_migrations (plugin, version) VALUES (?, ?)",
+                )
+                .bind::<Text, _>(plugin)
+                .bind::<Integer, _>(version)
+                .execute(conn)?;
+                if inserted == 1 {
+                    log::info!("Applying migration {version} for plugin {plugin}");
+                    conn.batch_execute(migration)?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    })
+    .map_err(|err| match err {
+        Error::Internal { source, ctx } => Error::Internal {
+            source,
+            ctx: format!("Cannot migrate the tables of plugin {plugin}. {ctx}"),
+        },
+        err => err,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const KARMA_V1: &str =
+        "CREATE TABLE karma_scores (nick TEXT PRIMARY KEY, score INTEGER NOT NULL);";
+    const KARMA_V2: &str = "ALTER TABLE karma_scores ADD COLUMN updated_at DATETIME;";
+
+    fn count(db: &Database, table: &str) -> i64 {
+        #[derive(QueryableByName)]
+        struct Count {
+            #[sql_type = "diesel::sql_types::BigInt"]
+            n: i64,
+        }
+        db.with_connection(|conn| {
+            diesel::sql_query(format!("SELECT count(*) AS n FROM {table}"))
+                .get_result::<Count>(conn)
+        })
+        .unwrap()
+        .n
+    }
+
+    #[test]
+    fn test_migrations_applied_once() {
+        let db = Database::in_memory().unwrap();
+        ensure_schema(&db, "karma", &[KARMA_V1]).unwrap();
+        // would fail if the table was created again
+        ensure_schema(&db, "karma", &[KARMA_V1]).unwrap();
+        ensure_schema(&db, "karma", &[KARMA_V1, KARMA_V2]).unwrap();
+        db.with_connection(|conn| {
+            conn.batch_execute(
+                "INSERT INTO karma_scores (nick, score, updated_at) VALUES ('coucou', 1, NULL);",
+            )
+        })
+        .unwrap();
+        assert_eq!(count(&db, "plugin_migrations"), 2);
+    }
+
+    #[test]
+    fn test_migrations_per_plugin() {
+        let db = Database::in_memory().unwrap();
+        ensure_schema(&db, "karma", &[KARMA_V1]).unwrap();
+        ensure_schema(
+            &db,
+            "quote",
+            &["CREATE TABLE quote_quotes (id INTEGER PRIMARY KEY, text TEXT NOT NULL);"],
+        )
+        .unwrap();
+        assert_eq!(count(&db, "plugin_migrations"), 2);
+        assert_eq!(count(&db, "quote_quotes"), 0);
+    }
+
+    #[test]
+    fn test_failed_migration_rolled_back() {
+        let db = Database::in_memory().unwrap();
+        let err = ensure_schema(&db, "karma", &[KARMA_V1, "NOT SQL AT ALL;"]).unwrap_err();
+        assert!(err.to_string().contains("plugin karma"), "{err}");
+        assert_eq!(count(&db, "plugin_migrations"), 1, "only the first one");
+
+        ensure_schema(&db, "karma", &[KARMA_V1, KARMA_V2]).unwrap();
+        assert_eq!(count(&db, "plugin_migrations"), 2);
+    }
+}
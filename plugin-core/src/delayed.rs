@@ -0,0 +1,163 @@
+use std::sync::Mutex;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// The things a plugin posts later, like its reminders, taken by its `run`
+/// once their time comes.
+/// What's still pending when the golem shuts down is dropped along with it,
+/// the plugins keep what must survive a restart in the database.
+pub struct Delayed<T> {
+    pending: Mutex<Vec<(Instant, T)>>,
+    /// to wake up `due` with the new deadlines
+    added: Notify,
+}
+
+impl<T> Default for Delayed<T> {
+    fn default() -> Self {
+        Delayed {
+            pending: Mutex::new(vec![]),
+            added: Notify::new(),
+        }
+    }
+}
+
+impl<T> Delayed<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self, at: Instant, item: T) {
+        self.pending.lock().expect("delayed lock").push((at, item));
+        self.added.notify_one();
+    }
+
+    /// Drops the pending items not kept, and tells how many
+    pub fn retain(&self, mut keep: impl FnMut(&T) -> bool) -> usize {
+        let mut pending = self.pending.lock().expect("delayed lock");
+        let before = pending.len();
+        pending.retain(|(_, item)| keep(item));
+        before - pending.len()
+    }
+
+    /// The pending items matching, the earliest first
+    pub fn filter(&self, matching: impl Fn(&T) -> bool) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let pending = self.pending.lock().expect("delayed lock");
+        let mut found = pending
+            .iter()
+            .filter(|(_, item)| matching(item))
+            .collect::<Vec<_>>();
+        found.sort_by_key(|(at, _)| *at);
+        found.into_iter().map(|(_, item)| item.clone()).collect()
+    }
+
+    pub fn count(&self, matching: impl Fn(&T) -> bool) -> usize {
+        let pending = self.pending.lock().expect("delayed lock");
+        pending.iter().filter(|(_, item)| matching(item)).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.lock().expect("delayed lock").is_empty()
+    }
+
+    /// Waits for the next items due, the earliest first.
+    /// Nothing is lost when cancelled.
+    pub async fn due(&self) -> Vec<T> {
+        loop {
+            let due = self.take_due(Instant::now());
+            if !due.is_empty() {
+                return due;
+            }
+            let next = self
+                .pending
+                .lock()
+                .expect("delayed lock")
+                .iter()
+                .map(|(at, _)| *at)
+                .min();
+            match next {
+                Some(at) => tokio::select! {
+                    _ = tokio::time::sleep_until(at) => (),
+                    _ = self.added.notified() => (),
+                },
+                None => self.added.notified().await,
+            }
+        }
+    }
+
+    fn take_due(&self, now: Instant) -> Vec<T> {
+        let mut pending = self.pending.lock().expect("delayed lock");
+        let (mut due, later): (Vec<_>, Vec<_>) = pending.drain(..).partition(|(at, _)| *at <= now);
+        *pending = later;
+        due.sort_by_key(|(at, _)| *at);
+        due.into_iter().map(|(_, item)| item).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_due() {
+        let delayed = Delayed::new();
+        let start = Instant::now();
+        delayed.push(start + secs(8), "at 8s");
+        delayed.push(start + secs(4), "at 4s");
+        delayed.push(start + secs(4), "also at 4s");
+
+        assert_eq!(delayed.due().await, vec!["at 4s", "also at 4s"]);
+        assert_eq!(start.elapsed(), secs(4));
+        assert!(
+            tokio::time::timeout(secs(2), delayed.due()).await.is_err(),
+            "not yet"
+        );
+        delayed.push(start + secs(7), "at 7s");
+        assert_eq!(delayed.due().await, vec!["at 7s"], "pushed while waiting");
+        assert_eq!(delayed.due().await, vec!["at 8s"]);
+        assert!(delayed.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_overdue() {
+        let delayed = Delayed::new();
+        let start = Instant::now();
+        tokio::time::sleep(secs(10)).await;
+        delayed.push(start + secs(5), "due at 5s");
+        delayed.push(start, "due at 0s");
+        assert_eq!(
+            delayed.due().await,
+            vec!["due at 0s", "due at 5s"],
+            "right away"
+        );
+        assert_eq!(start.elapsed(), secs(10));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retain() {
+        let delayed = Delayed::new();
+        let start = Instant::now();
+        delayed.push(start + secs(3), ("#rust", "pizza"));
+        delayed.push(start + secs(1), ("#rust", "oven"));
+        delayed.push(start + secs(2), ("#ocaml", "pasta"));
+        assert_eq!(delayed.count(|(channel, _)| *channel == "#rust"), 2);
+        assert_eq!(
+            delayed.filter(|(channel, _)| *channel == "#rust"),
+            vec![("#rust", "oven"), ("#rust", "pizza")]
+        );
+        assert_eq!(delayed.retain(|(channel, _)| *channel != "#rust"), 2);
+        assert_eq!(delayed.due().await, vec![("#ocaml", "pasta")]);
+        assert!(
+            tokio::time::timeout(secs(10), delayed.due()).await.is_err(),
+            "forgotten"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+/// User facing description of a command handled by a plugin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandHelp {
+    pub name: &'static str,
+    /// without the command prefix, like `url [idx] [> nick]`
+    pub usage: &'static str,
+    pub description: &'static str,
+    /// other names of the command, listed with it rather than on their own
+    pub aliases: &'static [&'static str],
+}
+
+impl CommandHelp {
+    /// The usage defaults to the bare command name
+    pub fn new(name: &'static str) -> Self {
+        CommandHelp {
+            name,
+            usage: name,
+            description: "",
+            aliases: &[],
+        }
+    }
+
+    pub fn usage(mut self, usage: &'static str) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    pub fn description(mut self, description: &'static str) -> Self {
+        self.description = description;
+        self
+    }
+
+    pub fn aliases(mut self, aliases: &'static [&'static str]) -> Self {
+        self.aliases = aliases;
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Config, Initialised, Plugin, Result};
+    use async_trait::async_trait;
+    use pretty_assertions::assert_eq;
+
+    struct Coucou;
+
+    #[async_trait]
+    impl Plugin for Coucou {
+        async fn init(_config: &Config) -> Result<Initialised> {
+            Ok(Initialised::from(Coucou))
+        }
+
+        fn get_name(&self) -> &'static str {
+            "coucou"
+        }
+
+        fn commands(&self) -> Vec<CommandHelp> {
+            vec![CommandHelp::new("coucou")
+                .usage("coucou [> nick]")
+                .description("Say coucou")
+                .aliases(&["cc"])]
+        }
+    }
+
+    struct Silent;
+
+    #[async_trait]
+    impl Plugin for Silent {
+        async fn init(_config: &Config) -> Result<Initialised> {
+            Ok(Initialised::from(Silent))
+        }
+
+        fn get_name(&self) -> &'static str {
+            "silent"
+        }
+    }
+
+    #[test]
+    fn test_commands_through_dyn_plugin() {
+        let plugins: Vec<Box<dyn Plugin>> = vec![Box::new(Coucou), Box::new(Silent)];
+        assert_eq!(
+            plugins[0].commands(),
+            vec![CommandHelp {
+                name: "coucou",
+                usage: "coucou [> nick]",
+                description: "Say coucou",
+                aliases: &["cc"],
+            }]
+        );
+        assert_eq!(plugins[1].commands(), vec![]);
+        assert_eq!(CommandHelp::new("ping").usage, "ping");
+        assert!(CommandHelp::new("ping").aliases.is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+//! A tiny message catalog, so that the replies of the bot are consistently
+//! in the language of the channel.
+
+use crate::CaseMapping;
+use serde::Deserialize;
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Lang {
+    Fr,
+    #[default]
+    En,
+}
+
+impl Lang {
+    /// Where to look for the keys missing in this language
+    fn fallback(self) -> Lang {
+        match self {
+            Lang::Fr => Lang::En,
+            Lang::En => Lang::Fr,
+        }
+    }
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fr" => Ok(Lang::Fr),
+            "en" => Ok(Lang::En),
+            _ => Err(format!("Unknown language {s}, expected fr or en")),
+        }
+    }
+}
+
+impl TryFrom<String> for Lang {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Which language to reply in, globally and per channel
+#[derive(Debug, Clone, Default)]
+pub struct Languages {
+    default: Lang,
+    per_channel: Vec<(String, Lang)>,
+}
+
+impl Languages {
+    pub fn new(default: Lang) -> Self {
+        Languages {
+            default,
+            per_channel: vec![],
+        }
+    }
+
+    pub fn with_channel(mut self, channel: &str, lang: Lang) -> Self {
+        self.per_channel.push((channel.to_string(), lang));
+        self
+    }
+
+    /// Channels are compared with the casemapping of their network
+    pub fn for_channel(&self, channel: Option<&str>, casemapping: CaseMapping) -> Lang {
+        channel
+            .and_then(|c| {
+                self.per_channel
+                    .iter()
+                    .rev()
+                    .find(|(configured, _)| casemapping.eq_ignore_case(configured, c))
+            })
+            .map_or(self.default, |(_, lang)| *lang)
+    }
+}
+
+/// Templates by key, with `{name}` placeholders for the arguments
+pub struct Catalog {
+    pub fr: &'static [(&'static str, &'static str)],
+    pub en: &'static [(&'static str, &'static str)],
+}
+
+impl Catalog {
+    /// The template for `key` in `lang`, or in the other language when missing,
+    /// with the placeholders replaced by the given arguments.
+    /// Falls back to the key itself when no language has it.
+    pub fn tr(&self, lang: Lang, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        let template = match self.lookup(lang, key) {
+            Some(t) => t,
+            None => match self.lookup(lang.fallback(), key) {
+                Some(t) => {
+                    log::warn!("Missing translation of {key} in {lang:?}");
+                    t
+                }
+                None => {
+                    log::error!("Unknown message key {key}");
+                    key
+                }
+            },
+        };
+        interpolate(template, args)
+    }
+
+    fn lookup(&self, lang: Lang, key: &str) -> Option<&'static str> {
+        let entries = match lang {
+            Lang::Fr => self.fr,
+            Lang::En => self.en,
+        };
+        entries.iter().find(|(k, _)| *k == key).map(|(_, t)| *t)
+    }
+}
+
+/// Messages shared by all the plugins, through `Error::user_message`
+const CORE: Catalog = Catalog {
+    fr: &[
+        ("not-found", "{what} introuvable"),
+        ("rate-limited", "Trop de requêtes, réessayez dans {secs}s"),
+        (
+            "rate-limited-later",
+            "Trop de requêtes, réessayez plus tard",
+        ),
+    ],
+    en: &[
+        ("not-found", "{what} not found"),
+        ("rate-limited", "Too many requests, try again in {secs}s"),
+        ("rate-limited-later", "Too many requests, try again later"),
+    ],
+};
+
+/// Translate one of the messages shared by all the plugins
+pub fn tr(lang: Lang, key: &str, args: &[(&str, &dyn Display)]) -> String {
+    CORE.tr(lang, key, args)
+}
+
+/// Unknown placeholders are left as is
+fn interpolate(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let end = match placeholder.find('}') {
+            Some(end) => end,
+            None => {
+                rest = placeholder;
+                break;
+            }
+        };
+        let name = &placeholder[1..end];
+        match args.iter().find(|(n, _)| *n == name) {
+            Some((_, value)) => result.push_str(&value.to_string()),
+            None => result.push_str(&placeholder[..=end]),
+        }
+        rest = &placeholder[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const CATALOG: Catalog = Catalog {
+        fr: &[("hello", "Coucou {nick} !"), ("fr-only", "Rien trouvé")],
+        en: &[("hello", "Hello {nick}!"), ("count", "{n} urls for {nick}")],
+    };
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(
+            CATALOG.tr(Lang::Fr, "hello", &[("nick", &"alice")]),
+            "Coucou alice !"
+        );
+        assert_eq!(
+            CATALOG.tr(Lang::En, "hello", &[("nick", &"alice")]),
+            "Hello alice!"
+        );
+        assert_eq!(
+            tr(Lang::En, "not-found", &[("what", &"Url at index 3")]),
+            "Url at index 3 not found"
+        );
+    }
+
+    #[test]
+    fn test_fallback() {
+        assert_eq!(CATALOG.tr(Lang::En, "fr-only", &[]), "Rien trouvé");
+        assert_eq!(
+            CATALOG.tr(Lang::Fr, "count", &[("n", &3), ("nick", &"bob")]),
+            "3 urls for bob"
+        );
+        assert_eq!(CATALOG.tr(Lang::Fr, "nope", &[]), "nope");
+    }
+
+    #[test]
+    fn test_interpolate() {
+        assert_eq!(
+            interpolate("{n} urls for {nick}, {n}!", &[("n", &2), ("nick", &"bob")]),
+            "2 urls for bob, 2!"
+        );
+        assert_eq!(
+            interpolate("{missing} and {n}", &[("n", &1)]),
+            "{missing} and 1"
+        );
+        assert_eq!(interpolate("unclosed {n", &[("n", &1)]), "unclosed {n");
+        assert_eq!(interpolate("no args /o\\", &[]), "no args /o\\");
+        assert_eq!(
+            interpolate("{n}", &[("n", &"{nick}"), ("nick", &"bob")]),
+            "{nick}"
+        );
+    }
+
+    #[test]
+    fn test_languages() {
+        let rfc1459 = CaseMapping::Rfc1459;
+        let languages = Languages::new(Lang::En).with_channel("##Arch-fr-[free]", Lang::Fr);
+        assert_eq!(
+            languages.for_channel(Some("##arch-fr-{free}"), rfc1459),
+            Lang::Fr
+        );
+        assert_eq!(
+            languages.for_channel(Some("##arch-fr-{free}"), CaseMapping::Ascii),
+            Lang::En
+        );
+        assert_eq!(languages.for_channel(Some("#rust"), rfc1459), Lang::En);
+        assert_eq!(languages.for_channel(None, rfc1459), Lang::En);
+        assert_eq!("FR".parse(), Ok(Lang::Fr));
+        assert!("de".parse::<Lang>().is_err());
+    }
+}
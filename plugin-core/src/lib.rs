@@ -1,4 +1,42 @@
+#[cfg(feature = "database")]
+#[macro_use]
+extern crate diesel;
+
+mod caps;
+mod config;
+mod context;
+mod cooldown;
+#[cfg(feature = "database")]
+mod database;
+mod delayed;
+mod help;
+mod http;
+mod members;
+pub mod i18n;
+pub mod metrics;
+mod outbound;
+mod requirement;
+mod task;
+#[cfg(feature = "testkit")]
+pub mod testkit;
 mod types;
 pub mod utils;
 
-pub use types::{Error, Result, WrapError, Plugin, Config, Initialised};
+pub use caps::CaseMapping;
+pub use config::Config;
+pub use context::MsgCtx;
+pub use cooldown::{Cooldown, TokenBucket};
+#[cfg(feature = "database")]
+pub use database::{ensure_schema, Database};
+pub use delayed::Delayed;
+pub use help::CommandHelp;
+pub use http::HttpConfig;
+pub use i18n::Lang;
+pub use members::Members;
+pub use metrics::{Metrics, MetricsHandle};
+pub use outbound::Outbound;
+pub use requirement::Requirement;
+pub use task::{BackgroundTask, Restart, TaskFuture};
+/// The command grammar shared by all the plugins
+pub use utils::parser as parse;
+pub use types::{Error, FilterDecision, Result, WrapError, Plugin, Initialised};
@@ -0,0 +1,277 @@
+use crate::CaseMapping;
+use irc::proto::{Command, Message, Response};
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+
+/// Who is in the channels the golem is in, for each network. Kept up to
+/// date by the golem from the NAMES replies and the JOIN, PART, KICK, QUIT
+/// and NICK messages. Channels and nicks are compared with the casemapping
+/// of their network.
+#[derive(Debug, Default)]
+pub struct Members {
+    /// (network, normalized channel) to the normalized nicks in there, the
+    /// golem included
+    channels: RwLock<HashMap<(String, String), HashSet<String>>>,
+    /// of each network, the default one until the golem tells otherwise
+    casemappings: RwLock<HashMap<String, CaseMapping>>,
+}
+
+/// The prefixes of the channel modes in the NAMES replies, like `@` for the ops
+const MODE_PREFIXES: &[char] = &['~', '&', '@', '%', '+'];
+
+impl Members {
+    /// From the RPL_ISUPPORT of the network, before joining any channel
+    pub fn set_casemapping(&self, network: &str, casemapping: CaseMapping) {
+        self.casemappings
+            .write()
+            .expect("members casemappings lock")
+            .insert(network.to_string(), casemapping);
+    }
+
+    fn casemapping(&self, network: &str) -> CaseMapping {
+        self.casemappings
+            .read()
+            .expect("members casemappings lock")
+            .get(network)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn key(&self, network: &str, channel: &str) -> (String, String) {
+        let casemapping = self.casemapping(network);
+        (network.to_string(), casemapping.normalize(channel))
+    }
+
+    /// Whether the golem is in the channel
+    pub fn has_channel(&self, network: &str, channel: &str) -> bool {
+        let key = self.key(network, channel);
+        self.channels
+            .read()
+            .expect("members lock")
+            .contains_key(&key)
+    }
+
+    /// Whether the nick is in the channel, false when the golem isn't in there
+    pub fn is_member(&self, network: &str, channel: &str, nick: &str) -> bool {
+        let key = self.key(network, channel);
+        let nick = self.casemapping(network).normalize(nick);
+        self.channels
+            .read()
+            .expect("members lock")
+            .get(&key)
+            .map_or(false, |nicks| nicks.contains(&nick))
+    }
+
+    /// Follows the channels of an inbound message, `own_nick` being the
+    /// current nick of the golem on that network
+    pub fn on_message(&self, network: &str, own_nick: &str, msg: &Message) {
+        let casemapping = self.casemapping(network);
+        let key = |channel: &str| (network.to_string(), casemapping.normalize(channel));
+        let source = msg
+            .source_nickname()
+            .map(|nick| casemapping.normalize(nick));
+        let is_own = |nick: &str| casemapping.eq_ignore_case(nick, own_nick);
+        let mut channels = self.channels.write().expect("members lock");
+        match (&msg.command, source) {
+            (Command::JOIN(joined, _, _), Some(nick)) => {
+                for channel in joined.split(',') {
+                    if is_own(&nick) {
+                        // joining again, the NAMES replies follow
+                        channels.insert(key(channel), HashSet::from([nick.clone()]));
+                    } else if let Some(nicks) = channels.get_mut(&key(channel)) {
+                        nicks.insert(nick.clone());
+                    }
+                }
+            }
+            (Command::Response(Response::RPL_NAMREPLY, args), _) => {
+                // <own nick> <symbol> <channel> :<names>
+                let (channel, names) = match args.as_slice() {
+                    [.., channel, names] => (channel, names),
+                    _ => return,
+                };
+                if let Some(nicks) = channels.get_mut(&key(channel)) {
+                    for name in names.split_whitespace() {
+                        let name = name.trim_start_matches(MODE_PREFIXES);
+                        // with userhost-in-names, nick!user@host
+                        let nick = name.split('!').next().unwrap_or(name);
+                        nicks.insert(casemapping.normalize(nick));
+                    }
+                }
+            }
+            (Command::PART(parted, _), Some(nick)) => {
+                for channel in parted.split(',') {
+                    left(&mut channels, key(channel), &nick, is_own(&nick));
+                }
+            }
+            (Command::KICK(kicked_from, nick, _), _) => {
+                let nick = casemapping.normalize(nick);
+                for channel in kicked_from.split(',') {
+                    left(&mut channels, key(channel), &nick, is_own(&nick));
+                }
+            }
+            (Command::QUIT(_), Some(nick)) => {
+                if is_own(&nick) {
+                    channels.retain(|(net, _), _| net != network);
+                } else {
+                    for (_, nicks) in channels.iter_mut().filter(|((net, _), _)| net == network) {
+                        nicks.remove(&nick);
+                    }
+                }
+            }
+            (Command::NICK(new_nick), Some(nick)) => {
+                for (_, nicks) in channels.iter_mut().filter(|((net, _), _)| net == network) {
+                    if nicks.remove(&nick) {
+                        nicks.insert(casemapping.normalize(new_nick));
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The normalized nick left the channel, the golem forgets about the whole
+/// channel when it's its own
+fn left(
+    channels: &mut HashMap<(String, String), HashSet<String>>,
+    key: (String, String),
+    nick: &str,
+    is_own: bool,
+) {
+    if is_own {
+        channels.remove(&key);
+    } else if let Some(nicks) = channels.get_mut(&key) {
+        nicks.remove(nick);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn from(nick: &str, command: &str, args: Vec<&str>) -> Message {
+        let source = format!("{nick}!~{nick}@localhost");
+        Message::new(Some(&source), command, args).unwrap()
+    }
+
+    fn names(channel: &str, names: &str) -> Message {
+        Message::new(
+            Some("irc.libera.chat"),
+            "353",
+            vec!["golem", "=", channel, names],
+        )
+        .unwrap()
+    }
+
+    fn joined(members: &Members) {
+        for msg in [
+            from("golem", "JOIN", vec!["#rust"]),
+            names("#rust", "golem @alice +Bob carol!~carol@localhost"),
+            from("golem", "JOIN", vec!["#ocaml"]),
+            names("#ocaml", "golem alice"),
+        ] {
+            members.on_message("libera", "golem", &msg);
+        }
+    }
+
+    #[test]
+    fn test_names() {
+        let members = Members::default();
+        joined(&members);
+        assert!(members.has_channel("libera", "#RUST"));
+        assert!(!members.has_channel("libera", "#haskell"));
+        assert!(!members.has_channel("oftc", "#rust"), "per network");
+        for nick in ["golem", "alice", "bob", "BOB", "carol"] {
+            assert!(members.is_member("libera", "#rust", nick), "{nick}");
+        }
+        assert!(!members.is_member("libera", "#ocaml", "bob"));
+        assert!(!members.is_member("libera", "#haskell", "alice"));
+
+        members.on_message("libera", "golem", &names("#haskell", "alice"));
+        assert!(
+            !members.has_channel("libera", "#haskell"),
+            "not joined, a NAMES for another reason"
+        );
+    }
+
+    #[test]
+    fn test_comings_and_goings() {
+        let members = Members::default();
+        joined(&members);
+        let on = |msg: Message| members.on_message("libera", "golem", &msg);
+
+        on(from("dave", "JOIN", vec!["#rust"]));
+        assert!(members.is_member("libera", "#rust", "dave"));
+        on(from("dave", "JOIN", vec!["#haskell"]));
+        assert!(!members.has_channel("libera", "#haskell"), "not ours");
+
+        on(from("alice", "PART", vec!["#rust"]));
+        assert!(!members.is_member("libera", "#rust", "alice"));
+        assert!(members.is_member("libera", "#ocaml", "alice"));
+
+        on(from("alice", "KICK", vec!["#rust", "bob", "out"]));
+        assert!(!members.is_member("libera", "#rust", "bob"));
+
+        on(from("carol", "NICK", vec!["caroline"]));
+        assert!(!members.is_member("libera", "#rust", "carol"));
+        assert!(members.is_member("libera", "#rust", "caroline"));
+
+        on(from("alice", "QUIT", vec!["bye"]));
+        assert!(!members.is_member("libera", "#ocaml", "alice"));
+        assert!(members.is_member("libera", "#rust", "dave"));
+    }
+
+    #[test]
+    fn test_golem_leaving() {
+        let members = Members::default();
+        joined(&members);
+        let on = |msg: Message| members.on_message("libera", "golem", &msg);
+
+        on(from("golem", "PART", vec!["#ocaml"]));
+        assert!(!members.has_channel("libera", "#ocaml"));
+        assert!(!members.is_member("libera", "#ocaml", "alice"));
+
+        on(from("alice", "KICK", vec!["#rust", "GOLEM"]));
+        assert!(!members.has_channel("libera", "#rust"));
+
+        on(from("golem", "JOIN", vec!["#rust"]));
+        assert!(!members.is_member("libera", "#rust", "bob"), "fresh join");
+        assert!(members.is_member("libera", "#rust", "golem"));
+
+        on(from("golem", "QUIT", vec!["bye"]));
+        assert!(!members.has_channel("libera", "#rust"));
+    }
+
+    #[test]
+    fn test_casemapping() {
+        let members = Members::default();
+        let on = |msg: Message| members.on_message("libera", "golem[m]", &msg);
+        on(from("golem[m]", "JOIN", vec!["#rust[fr]"]));
+        on(names("#RUST{FR}", "golem[m] alice[m]"));
+        assert!(
+            members.has_channel("libera", "#Rust{fr}"),
+            "rfc1459 by default"
+        );
+        assert!(members.is_member("libera", "#rust[fr]", "ALICE{M}"));
+
+        on(from("alice", "KICK", vec!["#rust{fr}", "Golem{M}"]));
+        assert!(
+            !members.has_channel("libera", "#rust[fr]"),
+            "the golem kicked"
+        );
+
+        members.set_casemapping("oftc", CaseMapping::Ascii);
+        let on = |msg: Message| members.on_message("oftc", "golem[m]", &msg);
+        on(from("golem[m]", "JOIN", vec!["#rust[fr]"]));
+        on(names("#rust[fr]", "golem[m] alice[m]"));
+        assert!(members.is_member("oftc", "#RUST[FR]", "Alice[M]"));
+        assert!(!members.is_member("oftc", "#rust[fr]", "alice{m}"));
+        on(from("golem{m}", "PART", vec!["#rust[fr]"]));
+        assert!(
+            members.has_channel("oftc", "#rust[fr]"),
+            "someone else under ascii"
+        );
+        on(from("golem[m]", "PART", vec!["#rust[fr]"]));
+        assert!(!members.has_channel("oftc", "#rust[fr]"));
+    }
+}
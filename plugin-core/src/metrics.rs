@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Counter(u64),
+    Gauge(f64),
+    Histogram {
+        /// upper bounds, sorted
+        buckets: Vec<f64>,
+        /// observations per bucket, not cumulative
+        counts: Vec<u64>,
+        sum: f64,
+        count: u64,
+    },
+}
+
+/// Minimal registry of counters, gauges and histograms, shared by the
+/// golem and the plugins, rendered with the prometheus text format
+#[derive(Debug, Default)]
+pub struct Metrics {
+    // (metric name, rendered labels) -> value
+    series: Mutex<BTreeMap<(String, String), Value>>,
+}
+
+impl Metrics {
+    pub fn set_gauge(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.series.lock().expect("metrics lock").insert(
+            (name.to_string(), render_labels(labels)),
+            Value::Gauge(value),
+        );
+    }
+
+    pub fn inc_counter(&self, name: &str, labels: &[(&str, &str)]) {
+        self.add_counter(name, labels, 1)
+    }
+
+    pub fn add_counter(&self, name: &str, labels: &[(&str, &str)], n: u64) {
+        let mut series = self.series.lock().expect("metrics lock");
+        let value = series
+            .entry((name.to_string(), render_labels(labels)))
+            .or_insert(Value::Counter(0));
+        if let Value::Counter(c) = value {
+            *c += n;
+        }
+    }
+
+    /// The buckets are only taken into account for the first observation
+    pub fn observe(&self, name: &str, buckets: &[f64], value: f64) {
+        let mut series = self.series.lock().expect("metrics lock");
+        let histogram = series
+            .entry((name.to_string(), String::new()))
+            .or_insert_with(|| {
+                let mut buckets = buckets.to_vec();
+                buckets.sort_by(|a, b| a.total_cmp(b));
+                Value::Histogram {
+                    counts: vec![0; buckets.len()],
+                    buckets,
+                    sum: 0.0,
+                    count: 0,
+                }
+            });
+        if let Value::Histogram {
+            buckets,
+            counts,
+            sum,
+            count,
+        } = histogram
+        {
+            if let Some(idx) = buckets.iter().position(|b| value <= *b) {
+                counts[idx] += 1;
+            }
+            *sum += value;
+            *count += 1;
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let series = self.series.lock().expect("metrics lock");
+        let mut out = String::new();
+        let mut current_name: Option<&str> = None;
+        for ((name, labels), value) in series.iter() {
+            if current_name != Some(name) {
+                let typ = match value {
+                    Value::Counter(_) => "counter",
+                    Value::Gauge(_) => "gauge",
+                    Value::Histogram { .. } => "histogram",
+                };
+                out.push_str(&format!("# TYPE {name} {typ}\n"));
+                current_name = Some(name);
+            }
+            match value {
+                Value::Counter(c) => out.push_str(&format!("{name}{labels} {c}\n")),
+                Value::Gauge(g) => out.push_str(&format!("{name}{labels} {g}\n")),
+                Value::Histogram {
+                    buckets,
+                    counts,
+                    sum,
+                    count,
+                } => {
+                    let mut cumulative = 0;
+                    for (bound, n) in buckets.iter().zip(counts) {
+                        cumulative += n;
+                        out.push_str(&format!("{name}_bucket{{le=\"{bound}\"}} {cumulative}\n"));
+                    }
+                    out.push_str(&format!("{name}_bucket{{le=\"+Inf\"}} {count}\n"));
+                    out.push_str(&format!("{name}_sum {sum}\n"));
+                    out.push_str(&format!("{name}_count {count}\n"));
+                }
+            }
+        }
+        out
+    }
+}
+
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let labels = labels
+        .iter()
+        .map(|(k, v)| format!("{k}=\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("{{{labels}}}")
+}
+
+/// What a plugin gets to record its own metrics, all prefixed
+/// with the name of the plugin
+#[derive(Debug, Clone)]
+pub struct MetricsHandle {
+    registry: Arc<Metrics>,
+    prefix: String,
+}
+
+impl MetricsHandle {
+    pub fn new(registry: Arc<Metrics>, plugin: &str) -> Self {
+        MetricsHandle {
+            registry,
+            prefix: format!("{plugin}_"),
+        }
+    }
+
+    pub fn counter(&self, name: &str, labels: &[(&str, &str)]) -> Counter {
+        Counter {
+            registry: Arc::clone(&self.registry),
+            name: format!("{}{name}", self.prefix),
+            labels: labels
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    pub fn histogram(&self, name: &str, buckets: &[f64]) -> Histogram {
+        Histogram {
+            registry: Arc::clone(&self.registry),
+            name: format!("{}{name}", self.prefix),
+            buckets: buckets.to_vec(),
+        }
+    }
+}
+
+pub struct Counter {
+    registry: Arc<Metrics>,
+    name: String,
+    labels: Vec<(String, String)>,
+}
+
+impl Counter {
+    pub fn inc(&self) {
+        self.inc_by(1)
+    }
+
+    pub fn inc_by(&self, n: u64) {
+        let labels = self
+            .labels
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect::<Vec<_>>();
+        self.registry.add_counter(&self.name, &labels, n)
+    }
+}
+
+pub struct Histogram {
+    registry: Arc<Metrics>,
+    name: String,
+    buckets: Vec<f64>,
+}
+
+impl Histogram {
+    pub fn observe(&self, value: f64) {
+        self.registry.observe(&self.name, &self.buckets, value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        metrics.set_gauge("golem_lag_seconds", &[("network", "libera")], 0.25);
+        metrics.inc_counter("golem_timeouts_total", &[("plugin", "url")]);
+        metrics.inc_counter("golem_timeouts_total", &[("plugin", "url")]);
+        metrics.inc_counter("golem_timeouts_total", &[("plugin", "crypto")]);
+
+        assert_eq!(
+            metrics.render(),
+            "# TYPE golem_lag_seconds gauge\n\
+             golem_lag_seconds{network=\"libera\"} 0.25\n\
+             # TYPE golem_timeouts_total counter\n\
+             golem_timeouts_total{plugin=\"crypto\"} 1\n\
+             golem_timeouts_total{plugin=\"url\"} 2\n"
+        );
+    }
+
+    #[test]
+    fn test_plugin_handle() {
+        let metrics = Arc::new(Metrics::default());
+        let handle = MetricsHandle::new(Arc::clone(&metrics), "url");
+        let hits = handle.counter("cache_total", &[("result", "hit")]);
+        hits.inc();
+        hits.inc_by(2);
+        let duration = handle.histogram("fetch_duration_seconds", &[1.0, 0.125]);
+        for value in [0.0625, 0.125, 0.5, 3.0] {
+            duration.observe(value);
+        }
+
+        assert_eq!(
+            metrics.render(),
+            "# TYPE url_cache_total counter\n\
+             url_cache_total{result=\"hit\"} 3\n\
+             # TYPE url_fetch_duration_seconds histogram\n\
+             url_fetch_duration_seconds_bucket{le=\"0.125\"} 2\n\
+             url_fetch_duration_seconds_bucket{le=\"1\"} 3\n\
+             url_fetch_duration_seconds_bucket{le=\"+Inf\"} 4\n\
+             url_fetch_duration_seconds_sum 3.6875\n\
+             url_fetch_duration_seconds_count 4\n"
+        );
+    }
+}
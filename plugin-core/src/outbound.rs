@@ -0,0 +1,100 @@
+use irc::proto::{Command, Message};
+
+/// What a plugin wants to send. The golem turns it into IRC messages,
+/// so that things like length limits are handled in a single place.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outbound {
+    /// A regular message to a channel or a nick
+    Reply {
+        target: String,
+        text: String,
+    },
+    Notice {
+        target: String,
+        text: String,
+    },
+    /// Like /me
+    Action {
+        target: String,
+        text: String,
+    },
+    /// Sent as is, for anything not covered above
+    Raw(Message),
+}
+
+impl Outbound {
+    pub fn reply<T: Into<String>, S: Into<String>>(target: T, text: S) -> Self {
+        Outbound::Reply {
+            target: target.into(),
+            text: text.into(),
+        }
+    }
+
+    pub fn notice<T: Into<String>, S: Into<String>>(target: T, text: S) -> Self {
+        Outbound::Notice {
+            target: target.into(),
+            text: text.into(),
+        }
+    }
+
+    pub fn action<T: Into<String>, S: Into<String>>(target: T, text: S) -> Self {
+        Outbound::Action {
+            target: target.into(),
+            text: text.into(),
+        }
+    }
+}
+
+impl From<Message> for Outbound {
+    fn from(msg: Message) -> Self {
+        Outbound::Raw(msg)
+    }
+}
+
+impl From<Command> for Outbound {
+    fn from(cmd: Command) -> Self {
+        Outbound::Raw(cmd.into())
+    }
+}
+
+/// What the golem sends, before splitting the lines too long for the server
+impl From<Outbound> for Message {
+    fn from(outbound: Outbound) -> Self {
+        match outbound {
+            Outbound::Reply { target, text } => Command::PRIVMSG(target, text).into(),
+            Outbound::Notice { target, text } => Command::NOTICE(target, text).into(),
+            Outbound::Action { target, text } => {
+                Command::PRIVMSG(target, format!("\x01ACTION {text}\x01")).into()
+            }
+            Outbound::Raw(msg) => msg,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_to_message() {
+        let to_string = |o: Outbound| Message::from(o).to_string();
+        assert_eq!(
+            to_string(Outbound::reply("#chan", "hello there")),
+            "PRIVMSG #chan :hello there\r\n"
+        );
+        assert_eq!(
+            to_string(Outbound::notice("someone", "hello there")),
+            "NOTICE someone :hello there\r\n"
+        );
+        assert_eq!(
+            to_string(Outbound::action("#chan", "waves at everyone")),
+            "PRIVMSG #chan :\x01ACTION waves at everyone\x01\r\n"
+        );
+        assert_eq!(
+            to_string(Command::PRIVMSG("#chan".to_string(), "hello there".to_string()).into()),
+            "PRIVMSG #chan :hello there\r\n",
+            "raw messages are untouched"
+        );
+    }
+}
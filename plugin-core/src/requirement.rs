@@ -0,0 +1,25 @@
+/// What a plugin needs from the golem to work properly,
+/// checked by the golem once all the plugins are initialised.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Requirement {
+    /// A key set in the plugin's own section of the golem config, like
+    /// `youtube_api_key` for `url = { youtube_api_key = Some "…" }`
+    ConfigKey(&'static str),
+    /// At least one IRC network
+    Network,
+    /// `database_path` set in the golem config
+    Database,
+    /// The web server, to mount the routes of the plugin
+    WebRouter,
+}
+
+impl std::fmt::Display for Requirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Requirement::ConfigKey(key) => write!(f, "config key {key}"),
+            Requirement::Network => f.write_str("an IRC network"),
+            Requirement::Database => f.write_str("a database"),
+            Requirement::WebRouter => f.write_str("the web server"),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::Result;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+pub type TaskFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+/// What the golem does once a background task completes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Restart {
+    /// the task is done, whether it succeeded or not
+    Never,
+    /// start it again after `delay` when it failed
+    OnFailure { delay: Duration },
+    /// start it again after `delay` whatever the outcome,
+    /// for periodic work done one iteration at a time
+    Always { delay: Duration },
+}
+
+impl Default for Restart {
+    fn default() -> Self {
+        Restart::OnFailure {
+            delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl Restart {
+    /// The delay before starting again, None when the task is done
+    pub fn after(&self, outcome: &Result<()>) -> Option<Duration> {
+        match (self, outcome) {
+            (Restart::Never, _) => None,
+            (Restart::OnFailure { .. }, Ok(())) => None,
+            (Restart::OnFailure { delay }, Err(_)) => Some(*delay),
+            (Restart::Always { delay }, _) => Some(*delay),
+        }
+    }
+}
+
+/// A named task spawned and supervised by the golem alongside `Plugin::run`,
+/// and cancelled when the golem shuts down.
+/// It's given as a function building the future, so that it can be restarted.
+pub struct BackgroundTask {
+    pub name: String,
+    pub restart: Restart,
+    start: Box<dyn Fn() -> TaskFuture + Send + Sync>,
+}
+
+impl BackgroundTask {
+    pub fn new<F, Fut>(name: &str, start: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        BackgroundTask {
+            name: name.to_string(),
+            restart: Restart::default(),
+            start: Box::new(move || Box::pin(start())),
+        }
+    }
+
+    pub fn restart(mut self, restart: Restart) -> Self {
+        self.restart = restart;
+        self
+    }
+
+    pub fn start(&self) -> TaskFuture {
+        (self.start)()
+    }
+}
+
+impl std::fmt::Debug for BackgroundTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BackgroundTask")
+            .field("name", &self.name)
+            .field("restart", &self.restart)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Error;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_restart_policy() {
+        let failed: Result<()> = Err(Error::Synthetic("boom".to_string()));
+        let delay = Duration::from_secs(5);
+        assert_eq!(Restart::Never.after(&Ok(())), None);
+        assert_eq!(Restart::Never.after(&failed), None);
+        assert_eq!(Restart::OnFailure { delay }.after(&Ok(())), None);
+        assert_eq!(Restart::OnFailure { delay }.after(&failed), Some(delay));
+        assert_eq!(Restart::Always { delay }.after(&Ok(())), Some(delay));
+        assert_eq!(Restart::Always { delay }.after(&failed), Some(delay));
+    }
+}
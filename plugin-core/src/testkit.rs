@@ -0,0 +1,295 @@
+//! Drive a plugin through scripted IRC conversations, the way the golem would,
+//! and check everything it sends back.
+
+use crate::i18n::Languages;
+use crate::utils::network::set_network;
+use crate::utils::parser::DEFAULT_PREFIXES;
+use crate::utils::private::set_private;
+use crate::{Config, Error, Metrics, MsgCtx, Outbound, Plugin, Result};
+use irc::proto::{ChannelExt, Command, Message};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// Network tagged on every received line
+pub const NETWORK: &str = "testnet";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    /// reply to a received line
+    InMessage,
+    /// sent out of band, through the channel given to [`Plugin::run`]
+    Run,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Emitted {
+    /// since the creation of the harness
+    pub at: Duration,
+    pub origin: Origin,
+    pub outbound: Outbound,
+}
+
+pub struct Harness {
+    plugin: Arc<dyn Plugin>,
+    started_at: Instant,
+    emitted: Arc<Mutex<Vec<Emitted>>>,
+    languages: Languages,
+    /// given to the plugin through its config
+    metrics: Arc<Metrics>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Harness {
+    /// Initialise the plugin with the given golem config, and start [`Plugin::run`]
+    pub async fn new<P: Plugin + 'static>(dhall_config: &str) -> Result<Self> {
+        let metrics = Arc::new(Metrics::default());
+        let config = Config::from_dhall_str(dhall_config)?.with_metrics(Arc::clone(&metrics));
+        let initialised = P::init(&config).await?;
+        let mut harness = Harness::with_plugin(initialised.plugin);
+        harness.metrics = metrics;
+        Ok(harness)
+    }
+
+    pub fn with_plugin(plugin: Box<dyn Plugin>) -> Self {
+        let plugin: Arc<dyn Plugin> = Arc::from(plugin);
+        let started_at = Instant::now();
+        let emitted = Arc::new(Mutex::new(vec![]));
+        let (tx, mut rx) = mpsc::channel::<Outbound>(100);
+
+        let run = {
+            let plugin = Arc::clone(&plugin);
+            tokio::spawn(async move {
+                if let Err(err) = plugin.run(tx).await {
+                    log::error!("Plugin {} exited: {err}", plugin.get_name());
+                }
+            })
+        };
+        let collect = {
+            let plugin = Arc::clone(&plugin);
+            let emitted = Arc::clone(&emitted);
+            tokio::spawn(async move {
+                while let Some(outbound) = rx.recv().await {
+                    record(&emitted, started_at, Origin::Run, outbound.clone());
+                    if let Err(err) = plugin.out_message(&outbound.into()).await {
+                        log::error!("out_message error from {}: {err}", plugin.get_name());
+                    }
+                }
+            })
+        };
+
+        Harness {
+            plugin,
+            started_at,
+            emitted,
+            languages: Languages::default(),
+            metrics: Arc::default(),
+            tasks: vec![run, collect],
+        }
+    }
+
+    /// Reply in these languages instead of english everywhere
+    pub fn with_languages(mut self, languages: Languages) -> Self {
+        self.languages = languages;
+        self
+    }
+
+    /// Each step waits for the given delay, then sends the raw IRC line
+    /// to the plugin, for example
+    /// `(Duration::ZERO, ":alice!~alice@localhost PRIVMSG #chan :λurl")`
+    pub async fn play(&self, script: &[(Duration, &str)]) -> Result<()> {
+        for (delay, line) in script {
+            tokio::time::sleep(*delay).await;
+            self.receive(line).await?;
+        }
+        // give a chance to the output of `Plugin::run` to be collected
+        tokio::task::yield_now().await;
+        Ok(())
+    }
+
+    /// Send a single raw IRC line, tagged like the golem does
+    pub async fn receive(&self, line: &str) -> Result<Option<Outbound>> {
+        let mut msg: Message = line.parse().map_err(|err| Error::Internal {
+            source: Box::new(err),
+            ctx: format!("Invalid IRC line in script: {line}"),
+        })?;
+        set_network(&mut msg, NETWORK);
+        if let Command::PRIVMSG(target, _) | Command::NOTICE(target, _) = &msg.command {
+            if !target.is_channel_name() {
+                set_private(&mut msg);
+            }
+        }
+
+        let mut ctx = MsgCtx::from_message(&msg);
+        ctx.lang = self
+            .languages
+            .for_channel(ctx.channel.as_deref(), ctx.casemapping);
+        let reply = match self.plugin.in_message_ctx(&ctx, &msg).await {
+            Ok(reply) => reply,
+            // replied to the users by the golem, like a regular reply
+            Err(err) => match err.reply_to(&msg, ctx.lang, &DEFAULT_PREFIXES[..]) {
+                Some(reply) => Some(reply),
+                None => return Err(err),
+            },
+        };
+        if let Some(outbound) = &reply {
+            record(
+                &self.emitted,
+                self.started_at,
+                Origin::InMessage,
+                outbound.clone(),
+            );
+            self.plugin.out_message(&outbound.clone().into()).await?;
+        }
+        Ok(reply)
+    }
+
+    /// Everything sent by the plugin so far, in order
+    pub fn emitted(&self) -> Vec<Emitted> {
+        self.emitted.lock().expect("emitted lock").clone()
+    }
+
+    /// What the golem would serve under /metrics
+    pub fn metrics(&self) -> String {
+        self.metrics.render()
+    }
+
+    pub fn clear(&self) {
+        self.emitted.lock().expect("emitted lock").clear();
+    }
+
+    /// Panics unless a message sent to `target` contains `text`
+    #[track_caller]
+    pub fn assert_replied_containing(&self, target: &str, text: &str) {
+        let emitted = self.emitted();
+        let found = emitted
+            .iter()
+            .any(|e| match Message::from(e.outbound.clone()).command {
+                Command::PRIVMSG(t, content) | Command::NOTICE(t, content) => {
+                    t == target && content.contains(text)
+                }
+                _ => false,
+            });
+        assert!(
+            found,
+            "Nothing sent to {target} containing {text:?}, got {emitted:#?}"
+        );
+    }
+
+    /// Panics if the plugin sent anything
+    #[track_caller]
+    pub fn assert_silent(&self) {
+        let emitted = self.emitted();
+        assert!(emitted.is_empty(), "Expected no output, got {emitted:#?}");
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+fn record(emitted: &Mutex<Vec<Emitted>>, started_at: Instant, origin: Origin, outbound: Outbound) {
+    emitted.lock().expect("emitted lock").push(Emitted {
+        at: started_at.elapsed(),
+        origin,
+        outbound,
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use async_trait::async_trait;
+    use pretty_assertions::assert_eq;
+
+    /// Says hello to whoever says coucou, and ticks every minute
+    struct Coucou;
+
+    #[async_trait]
+    impl Plugin for Coucou {
+        async fn init(_config: &Config) -> Result<crate::Initialised> {
+            Ok(crate::Initialised::from(Coucou))
+        }
+
+        fn get_name(&self) -> &'static str {
+            "coucou"
+        }
+
+        async fn in_message_ctx(&self, ctx: &MsgCtx, _msg: &Message) -> Result<Option<Outbound>> {
+            match (ctx.text.as_deref(), ctx.response_target(), &ctx.nick) {
+                (Some("coucou"), Some(target), Some(nick)) => {
+                    Ok(Some(Outbound::reply(target, format!("hello {nick}"))))
+                }
+                _ => Ok(None),
+            }
+        }
+
+        async fn run(&self, bot_chan: mpsc::Sender<Outbound>) -> Result<()> {
+            loop {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                bot_chan
+                    .send(Outbound::notice("#chan", "tick"))
+                    .await
+                    .map_err(|err| Error::Synthetic(err.to_string()))?;
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_conversation() {
+        let harness = Harness::new::<Coucou>("{=}").await.unwrap();
+        harness
+            .play(&[
+                (
+                    Duration::ZERO,
+                    ":alice!~alice@localhost PRIVMSG #chan :coucou",
+                ),
+                (
+                    Duration::from_secs(30),
+                    ":bob!~bob@localhost PRIVMSG #chan :hi",
+                ),
+                (
+                    Duration::from_secs(40),
+                    ":bob!~bob@localhost PRIVMSG rustygolem :coucou",
+                ),
+            ])
+            .await
+            .unwrap();
+
+        harness.assert_replied_containing("#chan", "hello alice");
+        harness.assert_replied_containing("#chan", "tick");
+        // in private, the reply goes to the sender, not to the bot
+        harness.assert_replied_containing("bob", "hello bob");
+        let emitted = harness.emitted();
+        assert_eq!(
+            emitted
+                .iter()
+                .map(|e| (e.at.as_secs(), e.origin))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, Origin::InMessage),
+                (60, Origin::Run),
+                (70, Origin::InMessage)
+            ]
+        );
+
+        harness.clear();
+        harness
+            .play(&[(Duration::ZERO, ":bob!~bob@localhost PRIVMSG #chan :hi")])
+            .await
+            .unwrap();
+        harness.assert_silent();
+    }
+
+    #[tokio::test]
+    async fn test_invalid_line() {
+        let harness = Harness::with_plugin(Box::new(Coucou));
+        assert!(harness.receive("").await.is_err());
+    }
+}
@@ -1,18 +1,44 @@
 #![allow(unused_variables)]
 
 use async_trait::async_trait;
-use irc::proto::Message;
+use crate::i18n::{self, Lang};
+use crate::{BackgroundTask, CommandHelp, Config, MsgCtx, Outbound, Requirement};
+use crate::utils::parser;
+use irc::proto::{Command, Message};
 use tokio::sync::mpsc;
 use axum::Router;
+use std::time::Duration;
 
 #[derive(Debug, thiserror::Error)]
 #[allow(dead_code)]
 pub enum Error {
-    /// useful when constructing an error from scratch
+    /// Something the users should be told about, as a reply where
+    /// the message came from. Not counted as a plugin failure.
+    #[error("{message}")]
+    UserVisible { message: String },
+
+    /// An upstream API refused to serve more requests for now
+    #[error("Rate limited{}", retry_hint(.retry_after))]
+    RateLimited { retry_after: Option<Duration> },
+
+    /// What the users asked for doesn't exist
+    #[error("{what} not found")]
+    NotFound { what: String },
+
+    /// A bug or an unexpected failure, only logged by the golem
+    #[error("{ctx}, plugin error from {source:?}")]
+    Internal {
+        source: Box<dyn std::error::Error + Send + Sync>,
+        ctx: String,
+    },
+
+    /// Deprecated, use `Internal`, or `UserVisible` when the users should know.
+    /// Useful when constructing an error from scratch.
     #[error("Generic plugin error {0}")]
     Synthetic(String),
 
-    #[error("Plugin error from {source:?}")]
+    /// Deprecated, same as `Internal`
+    #[error("{ctx}, plugin error from {source:?}")]
     Wrapped {
         source: Box<dyn std::error::Error + Send + Sync>,
         ctx: String,
@@ -22,6 +48,65 @@ pub enum Error {
     Generic(#[from] anyhow::Error),
 }
 
+impl Error {
+    pub fn user_visible<S: Into<String>>(message: S) -> Self {
+        Error::UserVisible {
+            message: message.into(),
+        }
+    }
+
+    pub fn not_found<S: Into<String>>(what: S) -> Self {
+        Error::NotFound { what: what.into() }
+    }
+
+    /// What the golem replies to the users, in their language,
+    /// None for the errors only worth logging
+    pub fn user_message(&self, lang: Lang) -> Option<String> {
+        match self {
+            Error::UserVisible { message } => Some(message.clone()),
+            Error::RateLimited {
+                retry_after: Some(delay),
+            } => Some(i18n::tr(
+                lang,
+                "rate-limited",
+                &[("secs", &delay.as_secs().max(1))],
+            )),
+            Error::RateLimited { retry_after: None } => {
+                Some(i18n::tr(lang, "rate-limited-later", &[]))
+            }
+            Error::NotFound { what } => Some(i18n::tr(lang, "not-found", &[("what", what)])),
+            _ => None,
+        }
+    }
+
+    /// `user_message` as a reply where the message came from, to the
+    /// `> nick` of the command too. None for the errors only worth logging.
+    pub fn reply_to<S: AsRef<str>>(
+        &self,
+        msg: &Message,
+        lang: Lang,
+        prefixes: &[S],
+    ) -> Option<Outbound> {
+        let text = self.user_message(lang)?;
+        let target = msg.response_target()?;
+        let nick = match &msg.command {
+            Command::PRIVMSG(_, privmsg) => parser::command_target(prefixes, privmsg),
+            _ => None,
+        };
+        let text = match nick {
+            Some(nick) => format!("{nick}: {text}"),
+            None => text,
+        };
+        Some(Outbound::reply(target, text))
+    }
+}
+
+fn retry_hint(retry_after: &Option<Duration>) -> String {
+    retry_after
+        .map(|delay| format!(", retry after {delay:?}"))
+        .unwrap_or_default()
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 // Can't figure out how to automatically convert an Error (+ other bounds)
@@ -30,13 +115,16 @@ pub trait WrapError<T> {
     fn wrap(self) -> Result<T>;
 }
 
-pub struct Config {
-    pub config_path: String,
-}
-
 pub struct Initialised {
     pub plugin: Box<dyn Plugin>,
+    /// Routes are mounted under /{plugin_name}/ and require the plugin's
+    /// web secret if one is configured.
     pub router: Option<Router>,
+    /// Also mounted under /{plugin_name}/, but never require any secret.
+    /// For example for webhooks called by third parties.
+    pub public_router: Option<Router>,
+    /// Spawned by the golem alongside `run`, restarted according to their policy
+    pub tasks: Vec<BackgroundTask>,
 }
 
 impl<T: Plugin + 'static> std::convert::From<T> for Initialised {
@@ -44,21 +132,43 @@ impl<T: Plugin + 'static> std::convert::From<T> for Initialised {
         Initialised {
             plugin: Box::new(value),
             router: None,
+            public_router: None,
+            tasks: vec![],
         }
     }
 }
 
+/// What a plugin decides about a message the golem is about to send
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterDecision {
+    /// Leave the message as it is
+    Pass,
+    /// Don't send the message at all, the reason is only logged
+    Drop { reason: String },
+    /// Send this message instead, subject to the filters of the next plugins
+    Replace(Message),
+}
+
 #[async_trait]
 pub trait Plugin: Sync + Send {
     async fn init(config: &Config) -> Result<Initialised>
     where
         Self: Sized;
 
+    /// Checks the plugin's settings in the golem config without initialising
+    /// anything, for `--check-config` and before connecting to IRC.
+    fn check_config(config: &Config) -> Result<()>
+    where
+        Self: Sized,
+    {
+        Ok(())
+    }
+
     /// This method is polled (through .await) after initialisation once the bot is running.
     /// The given bot_chan can be used to send message to IRC out of band,
     /// that is, not as a response to an incoming event.
     /// This method can also be used to start an async process.
-    async fn run(&self, bot_chan: mpsc::Sender<Message>) -> Result<()> {
+    async fn run(&self, bot_chan: mpsc::Sender<Outbound>) -> Result<()> {
         Ok(())
     }
 
@@ -66,16 +176,42 @@ pub trait Plugin: Sync + Send {
     fn get_name(&self) -> &'static str;
 
     /// Method invoked whenever a message is received from IRC
-    /// Returns Some(Message) if a response message should be sent, None otherwise
-    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+    /// Returns Some(Outbound) if a response should be sent, None otherwise
+    async fn in_message(&self, msg: &Message) -> Result<Option<Outbound>> {
         Ok(None)
     }
 
+    /// What the golem actually invokes for each received message, override it
+    /// instead of `in_message` to get the context already extracted from the message.
+    async fn in_message_ctx(&self, ctx: &MsgCtx, msg: &Message) -> Result<Option<Outbound>> {
+        self.in_message(msg).await
+    }
+
+    /// Invoked once the bot has actually joined a channel of that network, including
+    /// when joining again after a reconnection. Returns the messages to send right
+    /// away, tagged with the network like the ones sent by `run`.
+    async fn on_self_join(&self, network: &str, channel: &str) -> Result<Vec<Outbound>> {
+        Ok(vec![])
+    }
+
+    /// Invoked once the bot left a channel, parted or kicked out of it,
+    /// to forget about what was pending there.
+    async fn on_self_part(&self, channel: &str) -> Result<()> {
+        Ok(())
+    }
+
     /// Method invoked whenever the bot sends a message to IRC.
     async fn out_message(&self, msg: &Message) -> Result<()> {
         Ok(())
     }
 
+    /// Invoked before `out_message` for every message the bot is about to send,
+    /// including the ones of this plugin. The filters run one after the other
+    /// in the order the plugins are declared in the config.
+    async fn filter_out_message(&self, msg: &Message) -> Result<FilterDecision> {
+        Ok(FilterDecision::Pass)
+    }
+
     /// if the plugin should have a special handling for usually ignored users
     /// (typically, other bots), override this to return false.
     /// In this case `in_message` will also be invoked for messages coming from
@@ -83,4 +219,62 @@ pub trait Plugin: Sync + Send {
     fn ignore_blacklisted_users(&self) -> bool {
         true
     }
+
+    /// The commands handled by this plugin, as shown to the users
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![]
+    }
+
+    /// What this plugin needs to work properly. The golem warns about,
+    /// or refuses to start with, the ones it cannot provide.
+    fn requirements(&self) -> Vec<Requirement> {
+        vec![]
+    }
+
+    /// When several plugins reply the same text to the same message, only
+    /// the first reply is sent. Override this to return true so that the
+    /// replies of this plugin are never suppressed.
+    fn allow_duplicate_output(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_user_message() {
+        assert_eq!(
+            Error::user_visible("Cette vidéo n'existe pas").user_message(Lang::En),
+            Some("Cette vidéo n'existe pas".to_string())
+        );
+        assert_eq!(
+            Error::not_found("Url at index 3").user_message(Lang::En),
+            Some("Url at index 3 not found".to_string())
+        );
+        let limited = Error::RateLimited {
+            retry_after: Some(Duration::from_millis(30_500)),
+        };
+        assert_eq!(
+            limited.user_message(Lang::En),
+            Some("Too many requests, try again in 30s".to_string())
+        );
+        assert_eq!(
+            limited.user_message(Lang::Fr),
+            Some("Trop de requêtes, réessayez dans 30s".to_string())
+        );
+        assert_eq!(
+            Error::not_found("Url à l'index 3").user_message(Lang::Fr),
+            Some("Url à l'index 3 introuvable".to_string())
+        );
+        assert_eq!(limited.to_string(), "Rate limited, retry after 30.5s");
+        let internal = Error::Internal {
+            source: "connection reset".into(),
+            ctx: "Cannot fetch the title".to_string(),
+        };
+        assert_eq!(internal.user_message(Lang::En), None);
+        assert_eq!(Error::Synthetic("oops".to_string()).user_message(Lang::En), None);
+    }
 }
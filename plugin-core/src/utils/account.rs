@@ -0,0 +1,75 @@
+use crate::CaseMapping;
+use irc::proto::message::Tag;
+use irc::proto::Message;
+
+/// IRCv3 tag with the services account of the sender, only sent by servers
+/// supporting account-tag, and only for senders logged in.
+/// https://ircv3.net/specs/extensions/account-tag
+pub const ACCOUNT_TAG: &str = "account";
+
+/// Services account of the sender, steadier than its nick
+pub fn account(msg: &Message) -> Option<&str> {
+    msg.tags
+        .as_ref()?
+        .iter()
+        .find(|Tag(key, _)| key == ACCOUNT_TAG)
+        .and_then(|Tag(_, value)| value.as_deref())
+        .filter(|account| !account.is_empty())
+}
+
+/// Whether the sender is one of the admins of the golem config. By services
+/// account when logged in, so that taking the nick of an admin isn't enough,
+/// by nick otherwise.
+pub fn is_admin<S: AsRef<str>>(admins: &[S], msg: &Message, casemapping: CaseMapping) -> bool {
+    let who = match account(msg).or_else(|| msg.source_nickname()) {
+        Some(who) => who,
+        None => return false,
+    };
+    admins
+        .iter()
+        .any(|admin| casemapping.eq_ignore_case(admin.as_ref(), who))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_account_tag() {
+        let msg: Message = "@account=geekingfrog :Geek!~g@host PRIVMSG #chan :coucou\r\n"
+            .parse()
+            .unwrap();
+        assert_eq!(account(&msg), Some("geekingfrog"));
+
+        let msg: Message = ":Geek!~g@host PRIVMSG #chan :coucou\r\n".parse().unwrap();
+        assert_eq!(account(&msg), None, "not logged in");
+
+        let msg: Message = "@time=2024-05-08T12:00:00.000Z :Geek!~g@host PRIVMSG #chan :coucou\r\n"
+            .parse()
+            .unwrap();
+        assert_eq!(account(&msg), None, "other tags");
+    }
+
+    #[test]
+    fn test_is_admin() {
+        let admins = ["Geek[ing]frog"];
+        let is = |line: &str| is_admin(&admins, &line.parse().unwrap(), CaseMapping::Rfc1459);
+        assert!(is(":geek{ing}frog!~g@host PRIVMSG #chan :coucou\r\n"));
+        assert!(is(
+            "@account=GEEK[ING]FROG :frog_away!~g@host PRIVMSG #chan :coucou\r\n"
+        ));
+        assert!(
+            !is("@account=someone :Geek[ing]frog!~g@host PRIVMSG #chan :coucou\r\n"),
+            "someone else with the nick"
+        );
+        assert!(!is(":alice!~a@host PRIVMSG #chan :coucou\r\n"));
+        assert!(!is_admin(
+            &admins,
+            &":geek{ing}frog!~g@host PRIVMSG #chan :coucou\r\n"
+                .parse()
+                .unwrap(),
+            CaseMapping::Ascii
+        ));
+    }
+}
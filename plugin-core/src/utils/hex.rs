@@ -0,0 +1,28 @@
+/// None for an odd length or anything not hexadecimal,
+/// like the signatures of the webhooks
+pub fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("00ff7Fa0"), Some(vec![0, 255, 127, 160]));
+        assert_eq!(decode_hex(""), Some(vec![]));
+        assert_eq!(decode_hex("abc"), None, "odd length");
+        assert_eq!(decode_hex("zz"), None);
+        assert_eq!(decode_hex("+f"), None, "a sign isn't a digit");
+        assert_eq!(decode_hex("-f"), None);
+        assert_eq!(decode_hex("é0"), None);
+    }
+}
@@ -1 +1,6 @@
+pub mod account;
+pub mod hex;
+pub mod network;
+pub mod numbers;
 pub mod parser;
+pub mod private;
@@ -0,0 +1,56 @@
+use irc::proto::message::Tag;
+use irc::proto::Message;
+
+/// IRCv3 tag added by the golem on every inbound message, with the name
+/// of the network the message comes from.
+pub const NETWORK_TAG: &str = "rustygolem/network";
+
+/// Name of the network this message comes from, or should be sent to
+pub fn network(msg: &Message) -> Option<&str> {
+    msg.tags
+        .as_ref()?
+        .iter()
+        .find(|Tag(key, _)| key == NETWORK_TAG)
+        .and_then(|Tag(_, value)| value.as_deref())
+}
+
+/// Tag the message with the given network, replacing any existing one.
+/// The tag is removed by the golem before sending the message.
+pub fn set_network(msg: &mut Message, name: &str) {
+    strip_network(msg);
+    msg.tags
+        .get_or_insert_with(Vec::new)
+        .push(Tag(NETWORK_TAG.to_string(), Some(name.to_string())));
+}
+
+pub fn strip_network(msg: &mut Message) {
+    if let Some(tags) = msg.tags.as_mut() {
+        tags.retain(|Tag(key, _)| key != NETWORK_TAG);
+        if tags.is_empty() {
+            msg.tags = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use irc::proto::Command;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_network_tag() {
+        let mut msg: Message =
+            Command::PRIVMSG("#chan".to_string(), "coucou toi".to_string()).into();
+        assert_eq!(network(&msg), None);
+
+        set_network(&mut msg, "libera");
+        set_network(&mut msg, "private");
+        assert_eq!(network(&msg), Some("private"));
+        assert_eq!(msg.tags.as_ref().map(|t| t.len()), Some(1));
+
+        strip_network(&mut msg);
+        assert_eq!(network(&msg), None);
+        assert_eq!(msg.to_string(), "PRIVMSG #chan :coucou toi\r\n");
+    }
+}
@@ -0,0 +1,33 @@
+/// Groups the thousands, like the typographers do
+pub const THOUSANDS_SEPARATOR: char = '\u{2009}';
+
+/// The integer part of a number, like `1234567` into `1 234 567`
+pub fn group_thousands(digits: &str) -> String {
+    let mut grouped = String::with_capacity(digits.len() * 2);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(THOUSANDS_SEPARATOR);
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// A count of things, like viewers
+pub fn format_count(count: u64) -> String {
+    group_thousands(&count.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1000), "1\u{2009}000");
+        assert_eq!(format_count(1234567), "1\u{2009}234\u{2009}567");
+    }
+}
@@ -1,12 +1,72 @@
 use nom::{
-    bytes::complete::tag,
-    character::complete::{alphanumeric1, char, multispace0, multispace1},
-    combinator::{all_consuming, map, opt, recognize},
+    bytes::complete::{tag, take_till1},
+    character::complete::{char, multispace0, multispace1},
+    combinator::{all_consuming, map, opt},
     error::ParseError,
-    multi::many1,
     sequence::{delimited, pair, preceded, terminated, tuple},
     Finish, IResult,
 };
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+/// Prefixes used when nothing else has been configured
+pub const DEFAULT_PREFIXES: [&str; 2] = ["λ", "&"];
+
+tokio::task_local! {
+    // Prefixes in effect for the message being processed. Set by the golem
+    // around every plugin invocation, according to the channel of the message.
+    static PREFIXES: Arc<Vec<String>>;
+}
+
+/// Which command prefixes are accepted, globally and per channel.
+#[derive(Debug, Clone)]
+pub struct CommandPrefixes {
+    global: Arc<Vec<String>>,
+    per_channel: HashMap<String, Arc<Vec<String>>>,
+}
+
+impl Default for CommandPrefixes {
+    fn default() -> Self {
+        CommandPrefixes::new(DEFAULT_PREFIXES.iter().map(|p| p.to_string()).collect())
+    }
+}
+
+impl CommandPrefixes {
+    pub fn new(global: Vec<String>) -> Self {
+        CommandPrefixes {
+            global: Arc::new(longest_first(global)),
+            per_channel: HashMap::new(),
+        }
+    }
+
+    /// Use these prefixes instead of the global ones, but only on the given channel.
+    pub fn with_channel(mut self, channel: &str, prefixes: Vec<String>) -> Self {
+        self.per_channel
+            .insert(channel.to_lowercase(), Arc::new(longest_first(prefixes)));
+        self
+    }
+
+    pub fn for_channel(&self, channel: Option<&str>) -> Arc<Vec<String>> {
+        channel
+            .and_then(|c| self.per_channel.get(&c.to_lowercase()))
+            .unwrap_or(&self.global)
+            .clone()
+    }
+}
+
+// so that "!!" gets a chance to match before "!"
+fn longest_first(mut prefixes: Vec<String>) -> Vec<String> {
+    prefixes.retain(|p| !p.is_empty());
+    prefixes.sort_by(|a, b| b.len().cmp(&a.len()));
+    prefixes
+}
+
+/// Run the given future with `prefixes` being the accepted command prefixes
+/// for all the parsers using `command_prefix`.
+pub async fn with_prefixes<F: Future>(prefixes: Arc<Vec<String>>, fut: F) -> F::Output {
+    PREFIXES.scope(prefixes, fut).await
+}
 
 pub fn with_target<'a, O, F: 'a, E: ParseError<&'a str>>(
     inner: F,
@@ -22,16 +82,88 @@ pub fn target<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'
     map(tuple((target_sep, word, multispace0)), |(_, n, _)| n)(input)
 }
 
+/// Anything up to the next whitespace, so that nicks like `[m]atrix` or
+/// `héloïse` can be targets
 pub fn word<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a str, E> {
-    recognize(many1(alphanumeric1))(input)
+    take_till1(char::is_whitespace)(input)
 }
 
-/// Utility to parse common command prefix
+/// Utility to parse common command prefix.
+/// The accepted prefixes are the ones configured for the current message
+/// (see `with_prefixes`), defaulting to `DEFAULT_PREFIXES`.
 pub fn command_prefix(input: &str) -> nom::IResult<&str, &str> {
-    nom::branch::alt((
-        nom::bytes::complete::is_a("&"),
-        nom::bytes::complete::is_a("λ"),
-    ))(input)
+    PREFIXES
+        .try_with(|prefixes| prefix_in(prefixes.as_slice(), input))
+        .unwrap_or_else(|_| prefix_in(&DEFAULT_PREFIXES[..], input))
+}
+
+/// Parse one of the given prefixes
+pub fn prefix_in<'a, S: AsRef<str>>(prefixes: &[S], input: &'a str) -> IResult<&'a str, &'a str> {
+    prefixes
+        .iter()
+        .map(|p| p.as_ref())
+        .find(|p| !p.is_empty() && input.starts_with(p))
+        .map(|p| (&input[p.len()..], &input[..p.len()]))
+        .ok_or_else(|| nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Tag)))
+}
+
+/// `<prefix><name> [args] [> nick]`, the shape every command should follow.
+/// Consumes the whole input and gives the trimmed args, empty when there
+/// are none, and the target nick.
+pub fn command<'a>(
+    name: &'a str,
+) -> impl FnMut(&'a str) -> IResult<&'a str, (&'a str, Option<&'a str>)> {
+    move |input| {
+        let (rest, _) = preceded(command_prefix, tag(name))(input)?;
+        if rest.chars().next().map_or(false, |c| !c.is_whitespace()) {
+            // λurlfoo isn't λurl
+            return Err(nom::Err::Error(nom::error::Error::new(
+                rest,
+                nom::error::ErrorKind::Space,
+            )));
+        }
+        let (args, target) = split_target(rest.trim());
+        Ok(("", (args.trim(), target)))
+    }
+}
+
+/// The args of a command and its trailing `> nick`
+fn split_target(rest: &str) -> (&str, Option<&str>) {
+    match rest.rsplit_once('>') {
+        Some((args, nick))
+            if (args.is_empty() || args.ends_with(char::is_whitespace))
+                && nick.starts_with(char::is_whitespace)
+                && all_consuming(word::<nom::error::Error<&str>>)(nick.trim()).is_ok() =>
+        {
+            (args, Some(nick.trim()))
+        }
+        _ => (rest, None),
+    }
+}
+
+/// The `> nick` of any command, like charlie for `λurl > charlie`, for the
+/// replies made by the golem on behalf of the plugins
+pub fn command_target<'a, S: AsRef<str>>(prefixes: &[S], input: &'a str) -> Option<&'a str> {
+    let (rest, _) = prefix_in(prefixes, input).ok()?;
+    let (rest, _) = word::<nom::error::Error<&str>>(rest).ok()?;
+    split_target(rest.trim()).1
+}
+
+/// Like `command`, for a command with aliases, the first name matching
+pub fn command_in<'a>(
+    names: &'a [&'a str],
+) -> impl FnMut(&'a str) -> IResult<&'a str, (&'a str, Option<&'a str>)> {
+    move |input| {
+        let mut last_err =
+            nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Tag));
+        for name in names {
+            match command(name)(input) {
+                Ok(parsed) => return Ok(parsed),
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err)
+    }
 }
 
 /// Parse a single command with an optional target
@@ -56,6 +188,12 @@ mod test {
     use super::*;
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn test_word() {
+        let r: Result<_, nom::error::VerboseError<_>> = word("coucou").finish();
+        assert_eq!(r, Ok(("", "coucou")));
+    }
+
     #[test]
     fn test_parse_single_command() {
         assert_eq!(
@@ -88,4 +226,142 @@ mod test {
             "also parses with target"
         );
     }
+
+    #[test]
+    fn test_target() {
+        assert_eq!(
+            single_command("coucou", "&coucou > charlie   "),
+            Some(Some("charlie")),
+            "trailing whitespace"
+        );
+        assert_eq!(
+            single_command("coucou", "&coucou > héloïse"),
+            Some(Some("héloïse")),
+            "unicode nick"
+        );
+        assert_eq!(
+            single_command("coucou", "&coucou > [m]atrix_"),
+            Some(Some("[m]atrix_"))
+        );
+        assert_eq!(single_command("coucou", "&coucou >"), None, "missing nick");
+        assert_eq!(single_command("coucou", "&coucou > a b"), None);
+    }
+
+    #[test]
+    fn test_command() {
+        let parse = |name, input| command(name)(input).finish().ok().map(|(_, r)| r);
+        assert_eq!(parse("url", "λurl"), Some(("", None)), "missing args");
+        assert_eq!(parse("url", "λurl  3 "), Some(("3", None)));
+        assert_eq!(
+            parse("yt_search", "&yt_search cats and dogs > charlie "),
+            Some(("cats and dogs", Some("charlie")))
+        );
+        assert_eq!(
+            parse("url", "λurl > héloïse"),
+            Some(("", Some("héloïse"))),
+            "unicode nick without args"
+        );
+        assert_eq!(
+            parse("calc", "λcalc 3>2"),
+            Some(("3>2", None)),
+            "only a spaced > introduces a target"
+        );
+        assert_eq!(
+            parse("calc", "λcalc 1 > 2 > charlie"),
+            Some(("1 > 2", Some("charlie")))
+        );
+        assert_eq!(
+            parse("url", "λurl > two words"),
+            Some(("> two words", None))
+        );
+        assert_eq!(parse("url", "λurlfoo"), None, "whole command name");
+        assert_eq!(parse("url", "url 3"), None, "need the command prefix");
+    }
+
+    #[test]
+    fn test_command_target() {
+        let prefixes = &DEFAULT_PREFIXES[..];
+        assert_eq!(command_target(prefixes, "λurl > charlie"), Some("charlie"));
+        assert_eq!(
+            command_target(prefixes, "&yt_search cats > charlie "),
+            Some("charlie")
+        );
+        assert_eq!(command_target(prefixes, "λurl 3"), None);
+        assert_eq!(command_target(prefixes, "λcalc 3>2"), None);
+        assert_eq!(
+            command_target(prefixes, "look > charlie"),
+            None,
+            "only for commands"
+        );
+        assert_eq!(command_target(&["!"], "!url > charlie"), Some("charlie"));
+    }
+
+    #[test]
+    fn test_command_in() {
+        let names = ["calendrier", "cal", "jourrep"];
+        let parse = |input| command_in(&names)(input).finish().ok().map(|(_, r)| r);
+        assert_eq!(parse("λcalendrier demain"), Some(("demain", None)));
+        assert_eq!(
+            parse("λcal demain > charlie"),
+            Some(("demain", Some("charlie")))
+        );
+        assert_eq!(parse("&jourrep"), Some(("", None)));
+        assert_eq!(parse("λcalc 3"), None, "whole command name");
+        assert_eq!(parse("λcalendar"), None);
+        assert_eq!(parse("cal demain"), None, "need the command prefix");
+    }
+
+    #[test]
+    fn test_multi_char_prefix() {
+        let prefixes = CommandPrefixes::new(vec!["!!".to_string()]);
+        PREFIXES.sync_scope(prefixes.for_channel(Some("#chan")), || {
+            assert_eq!(single_command("coucou", "!!coucou"), Some(None));
+            assert_eq!(
+                single_command("coucou", "!!coucou > charlie"),
+                Some(Some("charlie"))
+            );
+            assert_eq!(single_command("coucou", "!coucou"), None);
+            assert_eq!(
+                single_command("coucou", "λcoucou"),
+                None,
+                "configured prefix replaces the default ones"
+            );
+        });
+    }
+
+    #[test]
+    fn test_overlapping_prefixes() {
+        let prefixes = CommandPrefixes::new(vec!["!".to_string(), "!!".to_string()]);
+        PREFIXES.sync_scope(prefixes.for_channel(None), || {
+            assert_eq!(command_prefix("!!coucou"), Ok(("coucou", "!!")));
+            assert_eq!(command_prefix("!coucou"), Ok(("coucou", "!")));
+        });
+    }
+
+    #[test]
+    fn test_channel_override() {
+        let prefixes = CommandPrefixes::default().with_channel("#Bots", vec!["!!".to_string()]);
+
+        PREFIXES.sync_scope(prefixes.for_channel(Some("#bots")), || {
+            assert_eq!(single_command("coucou", "!!coucou"), Some(None));
+            assert_eq!(single_command("coucou", "λcoucou"), None);
+        });
+
+        PREFIXES.sync_scope(prefixes.for_channel(Some("#other")), || {
+            assert_eq!(
+                single_command("coucou", "!!coucou"),
+                None,
+                "override only applies to its own channel"
+            );
+            assert_eq!(single_command("coucou", "λcoucou"), Some(None));
+            assert_eq!(single_command("coucou", "&coucou"), Some(None));
+        });
+    }
+
+    #[test]
+    fn test_default_prefixes_outside_of_golem() {
+        assert_eq!(single_command("coucou", "λcoucou"), Some(None));
+        assert_eq!(single_command("coucou", "&coucou"), Some(None));
+        assert_eq!(single_command("coucou", "!!coucou"), None);
+    }
 }
@@ -0,0 +1,41 @@
+use irc::proto::message::Tag;
+use irc::proto::Message;
+
+/// IRCv3 tag added by the golem on inbound messages sent directly to the
+/// bot instead of a channel.
+pub const PRIVATE_TAG: &str = "rustygolem/private";
+
+/// Whether this message was sent to the bot in private. The response target
+/// is then the nick of the sender, which shouldn't be mistaken for a channel.
+pub fn is_private(msg: &Message) -> bool {
+    msg.tags.as_ref().map_or(false, |tags| {
+        tags.iter().any(|Tag(key, _)| key == PRIVATE_TAG)
+    })
+}
+
+pub fn set_private(msg: &mut Message) {
+    if !is_private(msg) {
+        msg.tags
+            .get_or_insert_with(Vec::new)
+            .push(Tag(PRIVATE_TAG.to_string(), None));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use irc::proto::Command;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_private_tag() {
+        let mut msg: Message =
+            Command::PRIVMSG("rustygolem".to_string(), "coucou toi".to_string()).into();
+        assert!(!is_private(&msg));
+
+        set_private(&mut msg);
+        set_private(&mut msg);
+        assert!(is_private(&msg));
+        assert_eq!(msg.tags.as_ref().map(|t| t.len()), Some(1));
+    }
+}
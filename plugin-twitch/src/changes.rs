@@ -0,0 +1,188 @@
+use std::{collections::HashMap, time::Duration};
+use tokio::time::Instant;
+
+/// Successive edits of a stream are announced at most once in that time
+pub const DEBOUNCE: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelInfo {
+    pub title: String,
+    pub category: String,
+}
+
+/// Like `Geekingfrog passe à Science & Technology: Rust & chill`
+pub fn change_message(name: &str, info: &ChannelInfo) -> String {
+    if info.category.is_empty() {
+        format!("{name} change de titre : {}", info.title.trim())
+    } else {
+        format!("{name} passe à {}: {}", info.category, info.title.trim())
+    }
+}
+
+#[derive(Debug)]
+struct Channel {
+    /// when going live, then the last one announced
+    announced: ChannelInfo,
+    last_sent: Option<Instant>,
+    /// held until `DEBOUNCE` after the last announcement
+    pending: Option<ChannelInfo>,
+}
+
+/// The title and category of the live streams, so that a change is announced
+/// right away, unless another one was announced less than `DEBOUNCE` ago. It is
+/// then held, and replaced by the next ones until it can be announced.
+#[derive(Debug, Default)]
+pub struct Changes {
+    channels: HashMap<String, Channel>,
+}
+
+impl Changes {
+    /// Changes get announced against that info
+    pub fn live(&mut self, login: &str, info: ChannelInfo) {
+        let channel = Channel {
+            announced: info,
+            last_sent: None,
+            pending: None,
+        };
+        self.channels.insert(login.to_string(), channel);
+    }
+
+    /// Forgets the held changes too
+    pub fn offline(&mut self, login: &str) {
+        self.channels.remove(login);
+    }
+
+    /// Some when the change can be announced right away.
+    /// Changes of streams not live are ignored.
+    pub fn update(&mut self, login: &str, info: ChannelInfo, now: Instant) -> Option<ChannelInfo> {
+        let channel = self.channels.get_mut(login)?;
+        if info == channel.announced {
+            // like the language, or edited back
+            channel.pending = None;
+            return None;
+        }
+        match channel.last_sent {
+            Some(sent) if now < sent + DEBOUNCE => {
+                channel.pending = Some(info);
+                None
+            }
+            _ => {
+                channel.announced = info.clone();
+                channel.last_sent = Some(now);
+                channel.pending = None;
+                Some(info)
+            }
+        }
+    }
+
+    /// The held changes which can now be announced, by login
+    pub fn due(&mut self, now: Instant) -> Vec<(String, ChannelInfo)> {
+        let mut due = vec![];
+        for (login, channel) in self.channels.iter_mut() {
+            let ready = channel
+                .last_sent
+                .map_or(true, |sent| sent + DEBOUNCE <= now);
+            if !ready {
+                continue;
+            }
+            if let Some(info) = channel.pending.take() {
+                channel.announced = info.clone();
+                channel.last_sent = Some(now);
+                due.push((login.clone(), info));
+            }
+        }
+        due.sort_by(|a, b| a.0.cmp(&b.0));
+        due
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn info(title: &str, category: &str) -> ChannelInfo {
+        ChannelInfo {
+            title: title.to_string(),
+            category: category.to_string(),
+        }
+    }
+
+    fn minutes(n: u64) -> Duration {
+        Duration::from_secs(n * 60)
+    }
+
+    #[test]
+    fn test_debounce() {
+        let start = Instant::now();
+        let mut changes = Changes::default();
+        changes.live("geekingfrog", info("Rust & chill", "Science & Technology"));
+
+        assert_eq!(
+            changes.update("geekingfrog", info("Rust & chill", "Just Chatting"), start),
+            Some(info("Rust & chill", "Just Chatting")),
+            "right away"
+        );
+        let at = |m| start + minutes(m);
+        assert_eq!(
+            changes.update("geekingfrog", info("Rust & chat", "Just Chatting"), at(1)),
+            None
+        );
+        assert_eq!(
+            changes.update("geekingfrog", info("Rust & chats", "Just Chatting"), at(2)),
+            None
+        );
+        assert_eq!(changes.due(at(4)), vec![]);
+        assert_eq!(
+            changes.due(at(5)),
+            vec![(
+                "geekingfrog".to_string(),
+                info("Rust & chats", "Just Chatting")
+            )],
+            "only the last edit"
+        );
+        assert_eq!(changes.due(at(20)), vec![], "sent once");
+
+        // edited back before it could be announced
+        assert_eq!(
+            changes.update("geekingfrog", info("oops", "Just Chatting"), at(6)),
+            None
+        );
+        assert_eq!(
+            changes.update("geekingfrog", info("Rust & chats", "Just Chatting"), at(7)),
+            None
+        );
+        assert_eq!(changes.due(at(10)), vec![]);
+
+        assert_eq!(
+            changes.update("geekingfrog", info("Rust & sleep", "Just Chatting"), at(10)),
+            Some(info("Rust & sleep", "Just Chatting")),
+            "a while after the last one"
+        );
+    }
+
+    #[test]
+    fn test_not_live() {
+        let now = Instant::now();
+        let mut changes = Changes::default();
+        assert_eq!(changes.update("geekingfrog", info("a", "b"), now), None);
+
+        changes.live("geekingfrog", info("a", "b"));
+        changes.update("geekingfrog", info("a", "c"), now);
+        changes.update("geekingfrog", info("a", "d"), now);
+        changes.offline("geekingfrog");
+        assert_eq!(changes.due(now + DEBOUNCE), vec![], "dropped when offline");
+    }
+
+    #[test]
+    fn test_change_message() {
+        assert_eq!(
+            change_message("Geekingfrog", &info("Rust & chill ", "Just Chatting")),
+            "Geekingfrog passe à Just Chatting: Rust & chill"
+        );
+        assert_eq!(
+            change_message("Geekingfrog", &info("Rust & chill", "")),
+            "Geekingfrog change de titre : Rust & chill"
+        );
+    }
+}
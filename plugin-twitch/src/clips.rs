@@ -0,0 +1,252 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use plugin_core::Result;
+use serde::Deserialize;
+use std::time::Duration;
+use twitch_api2::{twitch_oauth2::ClientId, types::UserId};
+
+use crate::{
+    config::Obfuscated,
+    helix::{check, HelixError},
+    token::{Fetched, TokenManager, TokenSource},
+};
+
+/// Between the creation of a clip and each check whether it's ready,
+/// twitch takes a few seconds to process it
+pub const CHECKS: [Duration; 3] = [
+    Duration::from_secs(3),
+    Duration::from_secs(3),
+    Duration::from_secs(5),
+];
+
+const CLIPS_URL: &str = "https://api.twitch.tv/helix/clips";
+
+#[derive(Debug, Deserialize)]
+pub struct Created {
+    pub id: String,
+    pub edit_url: String,
+}
+
+/// Creating clips takes a user access token with the clips:edit scope
+#[async_trait]
+pub trait ClipApi: Send + Sync {
+    /// None when the stream isn't live
+    async fn create_clip(&self, broadcaster_id: &UserId) -> Result<Option<Created>>;
+
+    /// The url of the clip, None while it's being processed
+    async fn clip_url(&self, id: &str) -> Result<Option<String>>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Clipped {
+    Ready(String),
+    /// still not ready after the last check, with its edit url
+    Processing(String),
+    NotLive,
+}
+
+/// Creates the clip, and waits for it to be ready
+pub async fn clip(
+    api: &impl ClipApi,
+    broadcaster_id: &UserId,
+    checks: &[Duration],
+) -> Result<Clipped> {
+    let created = match api.create_clip(broadcaster_id).await? {
+        Some(created) => created,
+        None => return Ok(Clipped::NotLive),
+    };
+    for delay in checks {
+        tokio::time::sleep(*delay).await;
+        if let Some(url) = api.clip_url(&created.id).await? {
+            return Ok(Clipped::Ready(url));
+        }
+    }
+    Ok(Clipped::Processing(created.edit_url))
+}
+
+pub fn clip_message(login: &str, clipped: &Clipped) -> String {
+    match clipped {
+        Clipped::Ready(url) => url.clone(),
+        Clipped::Processing(edit_url) => {
+            format!("clip en cours de traitement, réessaie dans une minute: {edit_url}")
+        }
+        Clipped::NotLive => format!("{login} n'est pas en live"),
+    }
+}
+
+#[derive(Deserialize)]
+struct Page<T> {
+    data: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct Clip {
+    url: String,
+}
+
+/// The user access token of the config, which can't be refreshed: a new
+/// one is the same one
+pub struct UserToken(pub Obfuscated);
+
+#[async_trait]
+impl TokenSource for UserToken {
+    type Token = Obfuscated;
+
+    async fn fetch(&self) -> Result<Fetched<Obfuscated>> {
+        Ok(Fetched {
+            token: self.0.clone(),
+            expires_in: Duration::from_secs(365 * 24 * 60 * 60),
+        })
+    }
+}
+
+/// With the user access token of the config
+pub struct HelixClips {
+    pub http: reqwest::Client,
+    pub client_id: ClientId,
+    pub token: TokenManager<UserToken>,
+}
+
+impl HelixClips {
+    fn request(&self, method: reqwest::Method, token: &Obfuscated) -> reqwest::RequestBuilder {
+        self.http
+            .request(method, CLIPS_URL)
+            .header("Client-Id", self.client_id.as_str())
+            .bearer_auth(&token.0)
+    }
+}
+
+#[async_trait]
+impl ClipApi for HelixClips {
+    async fn create_clip(&self, broadcaster_id: &UserId) -> Result<Option<Created>> {
+        let body = self
+            .token
+            .with_token_once("Cannot create a clip", |token| {
+                let request = self
+                    .request(reqwest::Method::POST, &token)
+                    .query(&[("broadcaster_id", broadcaster_id.as_str())]);
+                async move {
+                    let resp = request.send().await?;
+                    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+                        return Ok(None);
+                    }
+                    Ok::<_, HelixError>(Some(check(resp).await?.bytes().await?))
+                }
+            })
+            .await?;
+        let body = match body {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+        let page: Page<Created> =
+            serde_json::from_slice(&body).context("Unexpected helix POST /clips response")?;
+        Ok(page.data.into_iter().next())
+    }
+
+    async fn clip_url(&self, id: &str) -> Result<Option<String>> {
+        let body =
+            self.token
+                .with_token("Cannot get a clip", |token| {
+                    let request = self
+                        .request(reqwest::Method::GET, &token)
+                        .query(&[("id", id)]);
+                    async move {
+                        Ok::<_, HelixError>(check(request.send().await?).await?.bytes().await?)
+                    }
+                })
+                .await?;
+        let page: Page<Clip> =
+            serde_json::from_slice(&body).context("Unexpected helix GET /clips response")?;
+        Ok(page.data.into_iter().next().map(|clip| clip.url))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::sync::Mutex;
+    use tokio::time::Instant;
+
+    /// Ready after that many checks, None for never
+    struct Fake {
+        ready_after: Option<usize>,
+        live: bool,
+        checks: Mutex<usize>,
+    }
+
+    impl Fake {
+        fn new(ready_after: Option<usize>) -> Self {
+            Fake {
+                ready_after,
+                live: true,
+                checks: Mutex::new(0),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ClipApi for Fake {
+        async fn create_clip(&self, broadcaster_id: &UserId) -> Result<Option<Created>> {
+            Ok(self.live.then(|| Created {
+                id: format!("clip-of-{broadcaster_id}"),
+                edit_url: format!("https://clips.twitch.tv/clip-of-{broadcaster_id}/edit"),
+            }))
+        }
+
+        async fn clip_url(&self, id: &str) -> Result<Option<String>> {
+            let mut checks = self.checks.lock().unwrap();
+            *checks += 1;
+            let ready = self.ready_after.map_or(false, |n| *checks >= n);
+            Ok(ready.then(|| format!("https://clips.twitch.tv/{id}")))
+        }
+    }
+
+    fn broadcaster() -> UserId {
+        UserId::new("101051819".to_string())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_created_then_ready() {
+        let api = Fake::new(Some(2));
+        let start = Instant::now();
+        let clipped = clip(&api, &broadcaster(), &CHECKS).await.unwrap();
+        assert_eq!(
+            clipped,
+            Clipped::Ready("https://clips.twitch.tv/clip-of-101051819".to_string())
+        );
+        assert_eq!(start.elapsed(), Duration::from_secs(6));
+        assert_eq!(
+            clip_message("geekingfrog", &clipped),
+            "https://clips.twitch.tv/clip-of-101051819"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_created_then_timeout() {
+        let api = Fake::new(None);
+        let start = Instant::now();
+        let clipped = clip(&api, &broadcaster(), &CHECKS).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(11));
+        assert_eq!(*api.checks.lock().unwrap(), 3);
+        assert_eq!(
+            clip_message("geekingfrog", &clipped),
+            "clip en cours de traitement, réessaie dans une minute: https://clips.twitch.tv/clip-of-101051819/edit"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_not_live() {
+        let api = Fake {
+            live: false,
+            ..Fake::new(Some(1))
+        };
+        let clipped = clip(&api, &broadcaster(), &CHECKS).await.unwrap();
+        assert_eq!(clipped, Clipped::NotLive);
+        assert_eq!(*api.checks.lock().unwrap(), 0);
+        assert_eq!(
+            clip_message("geekingfrog", &clipped),
+            "geekingfrog n'est pas en live"
+        );
+    }
+}
@@ -1,12 +1,20 @@
 use std::path::Path;
+use std::time::Duration;
+
+use anyhow::anyhow;
 
 use serde::Deserialize;
 use twitch_api2::{
-    eventsub::stream::{StreamOfflineV1Payload, StreamOnlineV1Payload},
+    eventsub::{
+        channel::ChannelUpdateV1Payload,
+        stream::{StreamOfflineV1Payload, StreamOnlineV1Payload},
+    },
     twitch_oauth2::{ClientId, ClientSecret},
     types::Nickname,
 };
 
+use crate::{flaps::Flapping, routes::Route};
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct StreamSpec {
     /// nickname is the user_login, shown in the URL
@@ -16,6 +24,48 @@ pub struct StreamSpec {
     pub irc_nick: String,
     /// Which channels to notify?
     pub irc_channels: Vec<String>,
+    /// when the stream ends, with how long it lasted
+    #[serde(default = "yes")]
+    pub announce_offline: bool,
+    /// when the title or the category changes during the stream
+    #[serde(default)]
+    pub announce_changes: bool,
+}
+
+fn yes() -> bool {
+    true
+}
+
+fn default_poll_interval() -> u64 {
+    120
+}
+
+/// Below that, helix would be asked for the live streams nonstop
+pub const MIN_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Above that, the streams would be announced long after going live, and
+/// the polls failing retried that much later
+pub const MAX_POLL_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// How the plugin learns that a stream went live
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(try_from = "String")]
+pub enum Mode {
+    /// needs the golem's web server to be reachable by twitch
+    #[default]
+    Webhook,
+    Poll,
+}
+
+impl TryFrom<String> for Mode {
+    type Error = String;
+
+    fn try_from(mode: String) -> Result<Self, Self::Error> {
+        match mode.as_str() {
+            "webhook" => Ok(Mode::Webhook),
+            "poll" => Ok(Mode::Poll),
+            _ => Err(format!("unknown twitch mode {mode:?}, webhook or poll")),
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -41,6 +91,24 @@ pub struct Config {
     pub client_secret: ClientSecret,
     pub app_secret: String,
     pub watched_streams: Vec<StreamSpec>,
+    /// More channels where streams get announced, on top of their irc_channels
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    /// for the streams going offline and back online right away
+    #[serde(default)]
+    pub flapping: Flapping,
+    /// where the streams for mature audiences aren't announced
+    #[serde(default)]
+    pub family_friendly_channels: Vec<String>,
+    /// "webhook" for the eventsub notifications, "poll" to ask helix
+    /// every poll_interval_secs instead
+    #[serde(default)]
+    pub mode: Mode,
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval_secs: u64,
+    /// a user access token with the clips:edit scope, for λclip
+    #[serde(default)]
+    pub clip_token: Option<Obfuscated>,
     pub callback_uri: Obfuscated,
 }
 
@@ -67,10 +135,69 @@ impl Config {
         let tmp: TC = serde_dhall::from_file(p).parse()?;
         Ok(tmp.twitch)
     }
+
+    /// Between two polls of helix, and after a failed one
+    pub fn poll_interval(&self) -> anyhow::Result<Duration> {
+        let interval = Duration::from_secs(self.poll_interval_secs);
+        if (MIN_POLL_INTERVAL..=MAX_POLL_INTERVAL).contains(&interval) {
+            Ok(interval)
+        } else {
+            Err(anyhow!(
+                "twitch.poll_interval_secs must be between {} and {}",
+                MIN_POLL_INTERVAL.as_secs(),
+                MAX_POLL_INTERVAL.as_secs()
+            ))
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum Message {
     StreamOnline(StreamOnlineV1Payload),
     StreamOffline(StreamOfflineV1Payload),
+    /// new title or category, or anything else about the channel
+    ChannelUpdate(ChannelUpdateV1Payload),
+    /// twitch stopped sending the events of that subscription
+    Revoked(RevokedSubscription),
+}
+
+/// From the body of a revocation message
+#[derive(Debug, Deserialize)]
+pub struct RevokedSubscription {
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// the reason, like user_removed or authorization_revoked
+    pub status: String,
+    pub condition: serde_json::Value,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_poll_interval() {
+        let config = |poll_interval_secs| Config {
+            client_id: ClientId::new("id".to_string()),
+            client_secret: ClientSecret::new("secret".to_string()),
+            app_secret: "s3cr3t".to_string(),
+            watched_streams: vec![],
+            routes: vec![],
+            flapping: Default::default(),
+            family_friendly_channels: vec![],
+            mode: Mode::Poll,
+            poll_interval_secs,
+            clip_token: None,
+            callback_uri: Obfuscated("https://example.com".to_string()),
+        };
+        assert_eq!(
+            config(120).poll_interval().unwrap(),
+            Duration::from_secs(120)
+        );
+        assert_eq!(config(30).poll_interval().unwrap(), MIN_POLL_INTERVAL);
+        for invalid in [0, 29, 3601] {
+            assert!(config(invalid).poll_interval().is_err(), "{invalid}");
+        }
+    }
 }
@@ -0,0 +1,40 @@
+use plugin_core::Database;
+
+/// The tables of the twitch plugin in the shared database, see
+/// `plugin_core::ensure_schema`
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE twitch_followed (
+        login TEXT PRIMARY KEY,
+        irc_channel TEXT NOT NULL,
+        added_by TEXT NOT NULL,
+        added_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );",
+    // started_at in RFC3339
+    "CREATE TABLE twitch_sessions (
+        login TEXT PRIMARY KEY,
+        started_at TEXT NOT NULL
+    );",
+    // the channels of the streams added with λtwitch add, from then on
+    // twitch_followed.irc_channel is the one where it was added
+    "CREATE TABLE twitch_routes (
+        irc_channel TEXT NOT NULL,
+        login TEXT NOT NULL,
+        added_by TEXT NOT NULL,
+        added_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (irc_channel, login)
+    );
+    INSERT INTO twitch_routes (irc_channel, login, added_by, added_at)
+        SELECT irc_channel, login, added_by, added_at FROM twitch_followed;",
+    // the last announced stream id and when it went offline, in RFC3339
+    "CREATE TABLE twitch_flaps (
+        login TEXT PRIMARY KEY,
+        stream_id TEXT,
+        offline_at TEXT
+    );",
+    // when the last announced stream started, in RFC3339
+    "ALTER TABLE twitch_flaps ADD COLUMN started_at TEXT;",
+];
+
+pub fn ensure_schema(db: &Database) -> plugin_core::Result<()> {
+    plugin_core::ensure_schema(db, "twitch", MIGRATIONS)
+}
@@ -10,6 +10,8 @@ pub enum TwitchSigError {
     Missing(&'static str),
     #[error("Invalid signature")]
     Invalid,
+    #[error("Message too old, or replayed")]
+    Stale,
     #[error("Invalid header value")]
     InvalidHeader(#[from] axum::http::header::ToStrError),
     #[error("Missing env var for app secret")]
@@ -39,7 +41,10 @@ impl IntoResponse for TwitchSigError {
                 (StatusCode::BAD_REQUEST, format!("{e}")).into_response()
             }
             TwitchSigError::Invalid => {
-                (StatusCode::BAD_REQUEST, "invalid signature").into_response()
+                (StatusCode::FORBIDDEN, "invalid signature").into_response()
+            }
+            e@TwitchSigError::Stale => {
+                (StatusCode::FORBIDDEN, format!("{e}")).into_response()
             }
             e@TwitchSigError::InvalidHeader(_) => {
                 (StatusCode::BAD_REQUEST, format!("{e}")).into_response()
@@ -55,9 +60,7 @@ impl IntoResponse for TwitchSigError {
 impl IntoResponse for TwitchError {
     fn into_response(self) -> Response {
         match self {
-            e@TwitchError::InvalidSig(_) => {
-                (StatusCode::BAD_REQUEST, format!("{e}")).into_response()
-            }
+            TwitchError::InvalidSig(e) => e.into_response(),
             TwitchError::HttpError(code) => code.into_response(),
         }
     }
@@ -0,0 +1,352 @@
+use diesel::prelude::*;
+use diesel::sql_types::{Nullable, Text};
+use plugin_core::{Database, Result};
+use serde::Deserialize;
+use std::time::Duration;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::db;
+
+/// What to say when a stream comes back soon after going offline
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub enum OnReturn {
+    /// nothing at all
+    Silent,
+    /// `{name} est de retour`
+    Back,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Flapping {
+    /// a stream back online within that many minutes isn't announced again
+    #[serde(default = "default_window")]
+    pub window_minutes: u64,
+    #[serde(default = "default_on_return")]
+    pub on_return: OnReturn,
+}
+
+fn default_window() -> u64 {
+    15
+}
+
+fn default_on_return() -> OnReturn {
+    OnReturn::Back
+}
+
+impl Default for Flapping {
+    fn default() -> Self {
+        Flapping {
+            window_minutes: default_window(),
+            on_return: default_on_return(),
+        }
+    }
+}
+
+impl Flapping {
+    fn window(&self) -> Duration {
+        Duration::from_secs(self.window_minutes * 60)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Announce {
+    Live,
+    /// `{name} est de retour`, instead of announcing it again
+    Back,
+    Nothing,
+}
+
+/// Of a single stream
+#[derive(Debug, Default, PartialEq)]
+pub struct FlapState {
+    /// the last one announced, or live when the golem started
+    pub stream_id: Option<String>,
+    /// when it went offline last, None once back online
+    pub offline_at: Option<OffsetDateTime>,
+}
+
+/// How a stream going live gets announced
+pub fn decide(
+    state: &FlapState,
+    stream_id: &str,
+    now: OffsetDateTime,
+    flapping: &Flapping,
+) -> Announce {
+    if state.stream_id.as_deref() == Some(stream_id) {
+        return Announce::Nothing;
+    }
+    let offline_for = state
+        .offline_at
+        .and_then(|offline_at| Duration::try_from(now - offline_at).ok());
+    match offline_for {
+        Some(offline_for) if offline_for < flapping.window() => match flapping.on_return {
+            OnReturn::Silent => Announce::Nothing,
+            OnReturn::Back => Announce::Back,
+        },
+        _ => Announce::Live,
+    }
+}
+
+#[derive(QueryableByName)]
+struct Row {
+    #[sql_type = "Nullable<Text>"]
+    stream_id: Option<String>,
+    #[sql_type = "Nullable<Text>"]
+    offline_at: Option<String>,
+}
+
+/// Forgotten that long after going offline, or going live for the ones
+/// whose end was missed
+const EXPIRY: time::Duration = time::Duration::hours(48);
+
+/// The `FlapState` of the streams, in the database so that a restart
+/// doesn't announce them again
+pub struct Flaps {
+    db: Database,
+}
+
+impl Flaps {
+    pub fn new(db: Database) -> Result<Self> {
+        db::ensure_schema(&db)?;
+        Ok(Flaps { db })
+    }
+
+    pub fn state(&self, login: &str) -> Result<FlapState> {
+        let rows = self.db.with_connection(|conn| {
+            diesel::sql_query("SELECT stream_id, offline_at FROM twitch_flaps WHERE login = ?")
+                .bind::<Text, _>(login)
+                .load::<Row>(conn)
+        })?;
+        Ok(rows
+            .into_iter()
+            .next()
+            .map(|row| FlapState {
+                stream_id: row.stream_id,
+                offline_at: row
+                    .offline_at
+                    .and_then(|at| OffsetDateTime::parse(&at, &Rfc3339).ok()),
+            })
+            .unwrap_or_default())
+    }
+
+    /// How to announce that stream going live, see `decide`. Whatever noticed
+    /// it: the notifications, the polls or the catch-up after a restart.
+    pub fn check(
+        &self,
+        login: &str,
+        stream_id: &str,
+        now: OffsetDateTime,
+        flapping: &Flapping,
+    ) -> Result<Announce> {
+        Ok(decide(&self.state(login)?, stream_id, now, flapping))
+    }
+
+    /// Once sent, or deliberately not, so that it never is again. Forgets the
+    /// streams gone for `EXPIRY` at the same time.
+    pub fn announced(
+        &self,
+        login: &str,
+        stream_id: &str,
+        started_at: OffsetDateTime,
+        now: OffsetDateTime,
+    ) -> Result<()> {
+        let started_at = started_at.format(&Rfc3339).expect("RFC3339 date");
+        let expired = (now - EXPIRY).format(&Rfc3339).expect("RFC3339 date");
+        self.db.with_connection(|conn| {
+            conn.transaction(|| {
+                diesel::sql_query(
+                    "INSERT OR REPLACE INTO twitch_flaps (login, stream_id, started_at, offline_at)
+                     VALUES (?, ?, ?, NULL)",
+                )
+                .bind::<Text, _>(login)
+                .bind::<Text, _>(stream_id)
+                .bind::<Text, _>(started_at)
+                .execute(conn)?;
+                diesel::sql_query(
+                    "DELETE FROM twitch_flaps WHERE COALESCE(offline_at, started_at) < ?",
+                )
+                .bind::<Text, _>(expired)
+                .execute(conn)
+            })
+        })?;
+        Ok(())
+    }
+
+    pub fn offline(&self, login: &str, now: OffsetDateTime) -> Result<()> {
+        let offline_at = now.format(&Rfc3339).expect("RFC3339 date");
+        self.db.with_connection(|conn| {
+            diesel::sql_query("INSERT OR IGNORE INTO twitch_flaps (login) VALUES (?)")
+                .bind::<Text, _>(login)
+                .execute(conn)?;
+            diesel::sql_query("UPDATE twitch_flaps SET offline_at = ? WHERE login = ?")
+                .bind::<Text, _>(offline_at)
+                .bind::<Text, _>(login)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use time::macros::datetime;
+
+    fn flapping(on_return: OnReturn) -> Flapping {
+        Flapping {
+            window_minutes: 15,
+            on_return,
+        }
+    }
+
+    #[test]
+    fn test_back_within_window() {
+        let state = FlapState {
+            stream_id: Some("1".to_string()),
+            offline_at: Some(datetime!(2024-05-01 20:00 UTC)),
+        };
+        let now = datetime!(2024-05-01 20:14 UTC);
+        assert_eq!(
+            decide(&state, "2", now, &flapping(OnReturn::Back)),
+            Announce::Back
+        );
+        assert_eq!(
+            decide(&state, "2", now, &flapping(OnReturn::Silent)),
+            Announce::Nothing
+        );
+    }
+
+    #[test]
+    fn test_back_after_window() {
+        let state = FlapState {
+            stream_id: Some("1".to_string()),
+            offline_at: Some(datetime!(2024-05-01 20:00 UTC)),
+        };
+        for on_return in [OnReturn::Back, OnReturn::Silent] {
+            assert_eq!(
+                decide(
+                    &state,
+                    "2",
+                    datetime!(2024-05-01 20:15 UTC),
+                    &flapping(on_return)
+                ),
+                Announce::Live
+            );
+        }
+        assert_eq!(
+            decide(
+                &FlapState::default(),
+                "2",
+                datetime!(2024-05-01 20:15 UTC),
+                &flapping(OnReturn::Back)
+            ),
+            Announce::Live,
+            "never seen"
+        );
+    }
+
+    #[test]
+    fn test_same_stream() {
+        let state = FlapState {
+            stream_id: Some("1".to_string()),
+            offline_at: Some(datetime!(2024-05-01 20:00 UTC)),
+        };
+        assert_eq!(
+            decide(
+                &state,
+                "1",
+                datetime!(2024-05-02 20:00 UTC),
+                &flapping(OnReturn::Back)
+            ),
+            Announce::Nothing
+        );
+    }
+
+    /// Like the plugin does, recording it once announced
+    fn announce(flaps: &Flaps, stream_id: &str, now: OffsetDateTime) -> Announce {
+        let back = flapping(OnReturn::Back);
+        let announce = flaps.check("geekingfrog", stream_id, now, &back).unwrap();
+        flaps.announced("geekingfrog", stream_id, now, now).unwrap();
+        announce
+    }
+
+    #[test]
+    fn test_restart_within_window() {
+        let db = Database::in_memory().unwrap();
+        let flaps = Flaps::new(db.clone()).unwrap();
+        assert_eq!(
+            announce(&flaps, "1", datetime!(2024-05-01 18:00 UTC)),
+            Announce::Live
+        );
+        flaps
+            .offline("geekingfrog", datetime!(2024-05-01 20:00 UTC))
+            .unwrap();
+        drop(flaps);
+
+        let restarted = Flaps::new(db).unwrap();
+        assert_eq!(
+            announce(&restarted, "2", datetime!(2024-05-01 20:05 UTC)),
+            Announce::Back
+        );
+        assert_eq!(
+            restarted.state("geekingfrog").unwrap(),
+            FlapState {
+                stream_id: Some("2".to_string()),
+                offline_at: None
+            }
+        );
+        restarted
+            .offline("geekingfrog", datetime!(2024-05-01 21:00 UTC))
+            .unwrap();
+        assert_eq!(
+            announce(&restarted, "3", datetime!(2024-05-01 22:00 UTC)),
+            Announce::Live
+        );
+    }
+
+    #[test]
+    fn test_restart_catch_up() {
+        let db = Database::in_memory().unwrap();
+        let flaps = Flaps::new(db.clone()).unwrap();
+        assert_eq!(
+            announce(&flaps, "1", datetime!(2024-05-01 18:00 UTC)),
+            Announce::Live
+        );
+        drop(flaps);
+
+        // still live after the restart, seen by the catch-up
+        let restarted = Flaps::new(db).unwrap();
+        let back = flapping(OnReturn::Back);
+        assert_eq!(
+            restarted
+                .check("geekingfrog", "1", datetime!(2024-05-01 18:30 UTC), &back)
+                .unwrap(),
+            Announce::Nothing
+        );
+    }
+
+    #[test]
+    fn test_expiry() {
+        let flaps = Flaps::new(Database::in_memory().unwrap()).unwrap();
+        flaps
+            .announced(
+                "chouhartem",
+                "1",
+                datetime!(2024-05-01 18:00 UTC),
+                datetime!(2024-05-01 18:00 UTC),
+            )
+            .unwrap();
+        flaps
+            .offline("chouhartem", datetime!(2024-05-01 20:00 UTC))
+            .unwrap();
+        announce(&flaps, "2", datetime!(2024-05-03 19:59 UTC));
+        assert_eq!(
+            flaps.state("chouhartem").unwrap().stream_id,
+            Some("1".to_string()),
+            "offline for less than 48h"
+        );
+        announce(&flaps, "3", datetime!(2024-05-03 20:01 UTC));
+        assert_eq!(flaps.state("chouhartem").unwrap(), FlapState::default());
+    }
+}
@@ -0,0 +1,661 @@
+use async_trait::async_trait;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use irc::proto::ChannelExt;
+use plugin_core::{parse, Database, Result};
+use std::result::Result as StdResult;
+use std::sync::Mutex;
+use twitch_api2::types::{Nickname, UserId};
+
+use crate::{
+    config::StreamSpec,
+    db,
+    routes::{self, Route},
+};
+
+pub const USAGE: &str = "Usage: λtwitch add <login> [#channel], λtwitch remove <login> [#channel], λtwitch list, λtwitch status [login] or λtwitch clip <login>";
+
+#[derive(Debug, PartialEq)]
+pub enum TwitchCommand {
+    /// in the given channel, or the one of the command
+    Add(String, Option<String>),
+    Remove(String, Option<String>),
+    List,
+    /// of all the followed streams without a login
+    Status(Option<String>),
+    /// of the only followed stream live in the channel without a login,
+    /// for λclip
+    Clip(Option<String>),
+}
+
+/// None when this isn't a twitch command, an error with the usage
+/// when it is one, but malformed. Along with the `> nick` target.
+pub fn parse_command(input: &str) -> Option<(StdResult<TwitchCommand, String>, Option<&str>)> {
+    let (_, (args, target)) = parse::command("twitch")(input).ok()?;
+    let mut words = args.split_whitespace();
+    let owned = |word: Option<&str>| word.map(str::to_string);
+    let command = match (words.next(), words.next(), words.next(), words.next()) {
+        (Some("add"), Some(login), chan, None) => {
+            channel(chan).map(|c| TwitchCommand::Add(login.to_string(), owned(c)))
+        }
+        (Some("remove" | "rm"), Some(login), chan, None) => {
+            channel(chan).map(|c| TwitchCommand::Remove(login.to_string(), owned(c)))
+        }
+        (Some("list"), None, None, None) => Some(TwitchCommand::List),
+        (Some("status"), login, None, None) => Some(TwitchCommand::Status(owned(login))),
+        (Some("clip"), Some(login), None, None) => {
+            Some(TwitchCommand::Clip(Some(login.to_string())))
+        }
+        _ => None,
+    };
+    Some((command.ok_or_else(|| USAGE.to_string()), target))
+}
+
+/// None when the optional channel argument isn't a channel
+fn channel(word: Option<&str>) -> Option<Option<&str>> {
+    match word {
+        Some(word) if word.is_channel_name() => Some(Some(word)),
+        Some(_) => None,
+        None => Some(None),
+    }
+}
+
+/// Like in twitch.tv/<login>: 4 to 25 letters, digits or underscores,
+/// in lowercase. None for anything else.
+pub fn login(input: &str) -> Option<String> {
+    let login = input.trim_start_matches('@').to_lowercase();
+    let valid = (4..=25).contains(&login.len())
+        && login.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then(|| login)
+}
+
+/// What following a stream takes on the twitch side
+#[async_trait]
+pub trait FollowApi: Send + Sync {
+    /// None when there is no such user
+    async fn user_id(&self, login: &str) -> Result<Option<UserId>>;
+
+    /// To both stream.online and stream.offline
+    async fn subscribe_stream(&self, user_id: &UserId) -> Result<()>;
+
+    async fn unsubscribe_stream(&self, user_id: &UserId) -> Result<()>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Added {
+    Followed,
+    /// already followed, now announced in that channel too
+    Routed,
+    /// already announced in that channel
+    AlreadyFollowed,
+    NoSuchUser,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Removed {
+    Unfollowed,
+    /// no longer announced in that channel, still in others
+    Unrouted,
+    /// followed, but not announced in that channel
+    NotRouted,
+    NotFollowed,
+    /// only removed by editing the config
+    Configured,
+}
+
+#[derive(QueryableByName)]
+struct Row {
+    #[sql_type = "Text"]
+    login: String,
+}
+
+#[derive(QueryableByName)]
+struct RouteRow {
+    #[sql_type = "Text"]
+    irc_channel: String,
+    #[sql_type = "Text"]
+    login: String,
+}
+
+/// Followed with λtwitch add, announced where λtwitch add was issued
+struct Runtime {
+    logins: Vec<String>,
+    routes: Vec<Route>,
+}
+
+/// The watched streams: the ones of the config, then the ones followed
+/// with λtwitch add, which survive a restart. Each of them announced in
+/// the channels routed to it, see `routes::channels_for`.
+pub struct Followed {
+    db: Database,
+    configured: Vec<StreamSpec>,
+    /// the irc_channels of the configured streams, then the routes of the config
+    routes: Vec<Route>,
+    added: Mutex<Runtime>,
+}
+
+impl Followed {
+    /// Create the tables if needed, and load the streams followed before the
+    /// last restart. The logins of the routes are followed too.
+    pub fn load(db: Database, configured: Vec<StreamSpec>, routes: Vec<Route>) -> Result<Self> {
+        db::ensure_schema(&db)?;
+        let (logins, added_routes) = db.with_connection(|conn| {
+            let logins = diesel::sql_query("SELECT login FROM twitch_followed ORDER BY rowid")
+                .load::<Row>(conn)?;
+            let routes =
+                diesel::sql_query("SELECT irc_channel, login FROM twitch_routes ORDER BY rowid")
+                    .load::<RouteRow>(conn)?;
+            Ok((logins, routes))
+        })?;
+
+        let mut all_routes: Vec<Route> = configured
+            .iter()
+            .flat_map(|s| {
+                s.irc_channels
+                    .iter()
+                    .map(|c| Route::new(c, s.nickname.as_str()))
+            })
+            .collect();
+        all_routes.extend(routes.iter().cloned());
+        let mut configured = configured;
+        for login in routes::logins(&routes) {
+            let login = login.to_lowercase();
+            if !configured.iter().any(|s| s.nickname.as_str() == login) {
+                configured.push(spec(&login));
+            }
+        }
+        Ok(Followed {
+            db,
+            configured,
+            routes: all_routes,
+            added: Mutex::new(Runtime {
+                logins: logins.into_iter().map(|row| row.login).collect(),
+                routes: added_routes
+                    .into_iter()
+                    .map(|row| Route::new(&row.irc_channel, &row.login))
+                    .collect(),
+            }),
+        })
+    }
+
+    pub fn streams(&self) -> Vec<StreamSpec> {
+        let added = self.added.lock().expect("followed lock");
+        let routes = self
+            .routes
+            .iter()
+            .chain(added.routes.iter())
+            .cloned()
+            .collect::<Vec<_>>();
+        self.configured
+            .iter()
+            .cloned()
+            .chain(added.logins.iter().map(|login| spec(login)))
+            .map(|s| StreamSpec {
+                irc_channels: routes::channels_for(s.nickname.as_str(), &routes),
+                ..s
+            })
+            .collect()
+    }
+
+    fn channels(&self, login: &str) -> Option<Vec<String>> {
+        self.streams()
+            .into_iter()
+            .find(|s| s.nickname.as_str() == login)
+            .map(|s| s.irc_channels)
+    }
+
+    fn insert_route(&self, login: &str, irc_channel: &str, added_by: &str) -> Result<()> {
+        self.db.with_connection(|conn| {
+            diesel::sql_query(
+                "INSERT OR IGNORE INTO twitch_routes (irc_channel, login, added_by) VALUES (?, ?, ?)",
+            )
+            .bind::<Text, _>(irc_channel)
+            .bind::<Text, _>(login)
+            .bind::<Text, _>(added_by)
+            .execute(conn)
+        })?;
+        self.added
+            .lock()
+            .expect("followed lock")
+            .routes
+            .push(Route::new(irc_channel, login));
+        Ok(())
+    }
+
+    /// Subscribes to the stream if needed, announced in the given channel.
+    /// The login must be valid, see `login`.
+    pub async fn add(
+        &self,
+        api: &impl FollowApi,
+        login: &str,
+        irc_channel: &str,
+        added_by: &str,
+    ) -> Result<Added> {
+        let announced_in =
+            |channels: Vec<String>| channels.iter().any(|c| c.eq_ignore_ascii_case(irc_channel));
+        match self.channels(login) {
+            Some(channels) if announced_in(channels) => return Ok(Added::AlreadyFollowed),
+            Some(_) => {
+                self.insert_route(login, irc_channel, added_by)?;
+                return Ok(Added::Routed);
+            }
+            None => (),
+        }
+        let user_id = match api.user_id(login).await? {
+            Some(user_id) => user_id,
+            None => return Ok(Added::NoSuchUser),
+        };
+        api.subscribe_stream(&user_id).await?;
+        // twice at the same time, the second one was subscribed again for nothing
+        if self.channels(login).is_some() {
+            return Ok(Added::AlreadyFollowed);
+        }
+        self.db.with_connection(|conn| {
+            diesel::sql_query(
+                "INSERT INTO twitch_followed (login, irc_channel, added_by) VALUES (?, ?, ?)",
+            )
+            .bind::<Text, _>(login)
+            .bind::<Text, _>(irc_channel)
+            .bind::<Text, _>(added_by)
+            .execute(conn)
+        })?;
+        self.added
+            .lock()
+            .expect("followed lock")
+            .logins
+            .push(login.to_string());
+        self.insert_route(login, irc_channel, added_by)?;
+        Ok(Added::Followed)
+    }
+
+    /// No longer announced in the given channel, unfollowed when that was the
+    /// last one. Unfollowed from everywhere without a channel.
+    pub async fn remove(
+        &self,
+        api: &impl FollowApi,
+        login: &str,
+        irc_channel: Option<&str>,
+    ) -> Result<Removed> {
+        let is_configured = self.configured.iter().any(|s| s.nickname.as_str() == login);
+        let channels = match self.channels(login) {
+            Some(channels) => channels,
+            None => return Ok(Removed::NotFollowed),
+        };
+        if let Some(irc_channel) = irc_channel {
+            let is_route =
+                |r: &Route| r.irc_channel.eq_ignore_ascii_case(irc_channel) && r.logins == [login];
+            let routed = self
+                .added
+                .lock()
+                .expect("followed lock")
+                .routes
+                .iter()
+                .any(is_route);
+            if !routed {
+                let announced = channels.iter().any(|c| c.eq_ignore_ascii_case(irc_channel));
+                return Ok(match announced {
+                    true => Removed::Configured,
+                    false => Removed::NotRouted,
+                });
+            }
+            self.db.with_connection(|conn| {
+                diesel::sql_query(
+                    "DELETE FROM twitch_routes WHERE irc_channel = ? COLLATE NOCASE AND login = ?",
+                )
+                .bind::<Text, _>(irc_channel)
+                .bind::<Text, _>(login)
+                .execute(conn)
+            })?;
+            let mut added = self.added.lock().expect("followed lock");
+            added.routes.retain(|r| !is_route(r));
+            let last = !added.routes.iter().any(|r| r.logins == [login]);
+            if is_configured || !last {
+                return Ok(Removed::Unrouted);
+            }
+        } else if is_configured {
+            return Ok(Removed::Configured);
+        }
+
+        // gone from twitch since, nothing left to unsubscribe from
+        if let Some(user_id) = api.user_id(login).await? {
+            api.unsubscribe_stream(&user_id).await?;
+        }
+        self.db.with_connection(|conn| {
+            diesel::sql_query("DELETE FROM twitch_followed WHERE login = ?")
+                .bind::<Text, _>(login)
+                .execute(conn)?;
+            diesel::sql_query("DELETE FROM twitch_routes WHERE login = ?")
+                .bind::<Text, _>(login)
+                .execute(conn)
+        })?;
+        let mut added = self.added.lock().expect("followed lock");
+        added.logins.retain(|l| l != login);
+        added.routes.retain(|r| r.logins != [login]);
+        Ok(Removed::Unfollowed)
+    }
+}
+
+/// Announced wherever the routes say, the irc nick is the login
+fn spec(login: &str) -> StreamSpec {
+    StreamSpec {
+        nickname: Nickname::new(login.to_string()),
+        irc_nick: login.to_string(),
+        irc_channels: vec![],
+        announce_offline: true,
+        announce_changes: false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    /// Knows a few users, and records the subscriptions
+    struct Fake {
+        users: HashMap<&'static str, &'static str>,
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl Fake {
+        fn new() -> Self {
+            Fake {
+                users: HashMap::from([("geekingfrog", "101051819"), ("coucou", "42")]),
+                calls: Mutex::new(vec![]),
+            }
+        }
+
+        fn calls(&self) -> Vec<String> {
+            std::mem::take(&mut *self.calls.lock().unwrap())
+        }
+    }
+
+    #[async_trait]
+    impl FollowApi for Fake {
+        async fn user_id(&self, login: &str) -> Result<Option<UserId>> {
+            Ok(self.users.get(login).map(|id| UserId::new(id.to_string())))
+        }
+
+        async fn subscribe_stream(&self, user_id: &UserId) -> Result<()> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("subscribe {user_id}"));
+            Ok(())
+        }
+
+        async fn unsubscribe_stream(&self, user_id: &UserId) -> Result<()> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("unsubscribe {user_id}"));
+            Ok(())
+        }
+    }
+
+    fn logins(followed: &Followed) -> Vec<String> {
+        followed
+            .streams()
+            .iter()
+            .map(|s| s.nickname.to_string())
+            .collect()
+    }
+
+    fn stream(login: &str, irc_channel: &str) -> StreamSpec {
+        StreamSpec {
+            irc_channels: vec![irc_channel.to_string()],
+            ..spec(login)
+        }
+    }
+
+    fn channels(followed: &Followed, login: &str) -> Vec<String> {
+        followed.channels(login).unwrap_or_default()
+    }
+
+    fn owned(word: &str) -> String {
+        word.to_string()
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            parse_command("λtwitch add Geekingfrog"),
+            Some((Ok(TwitchCommand::Add(owned("Geekingfrog"), None)), None))
+        );
+        assert_eq!(
+            parse_command("λtwitch add Geekingfrog #rust"),
+            Some((
+                Ok(TwitchCommand::Add(
+                    owned("Geekingfrog"),
+                    Some(owned("#rust"))
+                )),
+                None
+            ))
+        );
+        assert_eq!(
+            parse_command("λtwitch remove coucou"),
+            Some((Ok(TwitchCommand::Remove(owned("coucou"), None)), None))
+        );
+        assert_eq!(
+            parse_command("λtwitch rm coucou ##arch-fr-free"),
+            Some((
+                Ok(TwitchCommand::Remove(
+                    owned("coucou"),
+                    Some(owned("##arch-fr-free"))
+                )),
+                None
+            ))
+        );
+        assert_eq!(
+            parse_command("λtwitch list"),
+            Some((Ok(TwitchCommand::List), None))
+        );
+        assert_eq!(
+            parse_command("λtwitch status shroud > Armael"),
+            Some((
+                Ok(TwitchCommand::Status(Some(owned("shroud")))),
+                Some("Armael")
+            ))
+        );
+        assert_eq!(
+            parse_command("λtwitch status"),
+            Some((Ok(TwitchCommand::Status(None)), None))
+        );
+        assert_eq!(
+            parse_command("λtwitch clip geekingfrog"),
+            Some((Ok(TwitchCommand::Clip(Some(owned("geekingfrog")))), None))
+        );
+
+        for malformed in [
+            "λtwitch",
+            "λtwitch add",
+            "λtwitch add a b",
+            "λtwitch add a #b c",
+            "λtwitch follow a",
+            "λtwitch status a b",
+            "λtwitch clip",
+        ] {
+            assert_eq!(
+                parse_command(malformed),
+                Some((Err(USAGE.to_string()), None)),
+                "{malformed}"
+            );
+        }
+        assert_eq!(parse_command("λtwitchy add a"), None);
+        assert_eq!(parse_command("λstreams"), None);
+    }
+
+    #[test]
+    fn test_login() {
+        assert_eq!(login("Geekingfrog"), Some("geekingfrog".to_string()));
+        assert_eq!(login("@coucou_42"), Some("coucou_42".to_string()));
+        assert_eq!(login("abc"), None, "too short");
+        assert_eq!(login("twitch.tv/coucou"), None);
+    }
+
+    #[tokio::test]
+    async fn test_add_and_remove() {
+        let db = Database::in_memory().unwrap();
+        let api = Fake::new();
+        let configured = vec![stream("coucou", "#golem")];
+        let followed = Followed::load(db.clone(), configured.clone(), vec![]).unwrap();
+
+        assert_eq!(
+            followed
+                .add(&api, "geekingfrog", "#rust", "admin")
+                .await
+                .unwrap(),
+            Added::Followed
+        );
+        assert_eq!(api.calls(), vec!["subscribe 101051819"]);
+        assert_eq!(
+            followed
+                .add(&api, "geekingfrog", "#Rust", "admin")
+                .await
+                .unwrap(),
+            Added::AlreadyFollowed
+        );
+        assert_eq!(
+            followed
+                .add(&api, "coucou", "#golem", "admin")
+                .await
+                .unwrap(),
+            Added::AlreadyFollowed,
+            "in the config"
+        );
+        assert_eq!(
+            followed
+                .add(&api, "nobody", "#rust", "admin")
+                .await
+                .unwrap(),
+            Added::NoSuchUser
+        );
+        assert_eq!(api.calls(), Vec::<String>::new());
+
+        let reloaded = Followed::load(db.clone(), configured.clone(), vec![]).unwrap();
+        assert_eq!(logins(&reloaded), vec!["coucou", "geekingfrog"]);
+        assert_eq!(reloaded.streams()[1].irc_channels, vec!["#rust"]);
+
+        assert_eq!(
+            followed.remove(&api, "coucou", None).await.unwrap(),
+            Removed::Configured
+        );
+        assert_eq!(
+            followed.remove(&api, "nobody", None).await.unwrap(),
+            Removed::NotFollowed
+        );
+        assert_eq!(api.calls(), Vec::<String>::new());
+        assert_eq!(
+            followed.remove(&api, "geekingfrog", None).await.unwrap(),
+            Removed::Unfollowed
+        );
+        assert_eq!(api.calls(), vec!["unsubscribe 101051819"]);
+        assert_eq!(logins(&followed), vec!["coucou"]);
+
+        let reloaded = Followed::load(db, configured, vec![]).unwrap();
+        assert_eq!(logins(&reloaded), vec!["coucou"], "removed from the db too");
+    }
+
+    #[tokio::test]
+    async fn test_routes() {
+        let db = Database::in_memory().unwrap();
+        let api = Fake::new();
+        let configured = vec![stream("coucou", "#golem")];
+        let routes = vec![Route::new("#twitch", "*"), Route::new("#ocaml", "Shroud")];
+        let followed = Followed::load(db.clone(), configured.clone(), routes.clone()).unwrap();
+        assert_eq!(logins(&followed), vec!["coucou", "shroud"]);
+        assert_eq!(channels(&followed, "coucou"), vec!["#golem", "#twitch"]);
+        assert_eq!(channels(&followed, "shroud"), vec!["#twitch", "#ocaml"]);
+
+        assert_eq!(
+            followed
+                .add(&api, "geekingfrog", "#rust", "admin")
+                .await
+                .unwrap(),
+            Added::Followed
+        );
+        assert_eq!(
+            followed
+                .add(&api, "geekingfrog", "#haskell", "admin")
+                .await
+                .unwrap(),
+            Added::Routed
+        );
+        assert_eq!(
+            followed
+                .add(&api, "coucou", "#rust", "admin")
+                .await
+                .unwrap(),
+            Added::Routed,
+            "configured elsewhere"
+        );
+        assert_eq!(
+            followed
+                .add(&api, "geekingfrog", "#twitch", "admin")
+                .await
+                .unwrap(),
+            Added::AlreadyFollowed,
+            "everything is announced there"
+        );
+        assert_eq!(api.calls(), vec!["subscribe 101051819"]);
+        assert_eq!(
+            channels(&followed, "geekingfrog"),
+            vec!["#twitch", "#rust", "#haskell"]
+        );
+
+        let reloaded = Followed::load(db.clone(), configured.clone(), routes.clone()).unwrap();
+        assert_eq!(
+            channels(&reloaded, "geekingfrog"),
+            vec!["#twitch", "#rust", "#haskell"]
+        );
+        assert_eq!(
+            channels(&reloaded, "coucou"),
+            vec!["#golem", "#twitch", "#rust"]
+        );
+
+        assert_eq!(
+            followed
+                .remove(&api, "coucou", Some("#golem"))
+                .await
+                .unwrap(),
+            Removed::Configured
+        );
+        assert_eq!(
+            followed
+                .remove(&api, "coucou", Some("#rust"))
+                .await
+                .unwrap(),
+            Removed::Unrouted
+        );
+        assert_eq!(
+            followed
+                .remove(&api, "geekingfrog", Some("#ocaml"))
+                .await
+                .unwrap(),
+            Removed::NotRouted
+        );
+        assert_eq!(
+            followed
+                .remove(&api, "geekingfrog", Some("#Rust"))
+                .await
+                .unwrap(),
+            Removed::Unrouted
+        );
+        assert_eq!(api.calls(), Vec::<String>::new());
+        assert_eq!(
+            followed
+                .remove(&api, "geekingfrog", Some("#haskell"))
+                .await
+                .unwrap(),
+            Removed::Unfollowed,
+            "the last channel asking for it"
+        );
+        assert_eq!(api.calls(), vec!["unsubscribe 101051819"]);
+        assert_eq!(logins(&followed), vec!["coucou", "shroud"]);
+
+        let reloaded = Followed::load(db, configured, routes).unwrap();
+        assert_eq!(logins(&reloaded), vec!["coucou", "shroud"]);
+        assert_eq!(channels(&reloaded, "coucou"), vec!["#golem", "#twitch"]);
+    }
+}
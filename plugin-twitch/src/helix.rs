@@ -0,0 +1,412 @@
+use plugin_core::Error;
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use std::time::Duration;
+use twitch_api2::helix::{
+    ClientRequestError, HelixRequestDeleteError, HelixRequestGetError, HelixRequestPostError,
+};
+
+/// Tries of a request failing with a 5xx or a 429, before giving up
+pub const MAX_ATTEMPTS: u32 = 4;
+
+/// After the first 5xx, doubled after each next one
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+
+/// On a 429 or with few points left, waiting for Ratelimit-Reset, but not
+/// longer than that
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(30);
+
+/// On a 429 without Ratelimit-Reset
+const DEFAULT_RATE_LIMIT_WAIT: Duration = Duration::from_secs(5);
+
+/// Below that many points left, wait for the bucket to be refilled
+const LOW_REMAINING: u64 = 10;
+
+/// A failed helix request
+pub trait Failure: std::error::Error + Send + Sync + 'static {
+    /// Of the error response, None when there was no response
+    fn status(&self) -> Option<u16>;
+
+    /// What twitch said about it in the body of the response
+    fn message(&self) -> Option<&str>;
+
+    /// The Ratelimit-Reset of the response
+    fn reset(&self) -> Option<i64> {
+        None
+    }
+}
+
+impl Failure for ClientRequestError<reqwest::Error> {
+    fn status(&self) -> Option<u16> {
+        match self {
+            ClientRequestError::HelixRequestGetError(HelixRequestGetError::Error {
+                status,
+                ..
+            })
+            | ClientRequestError::HelixRequestPostError(HelixRequestPostError::Error {
+                status,
+                ..
+            })
+            | ClientRequestError::HelixRequestDeleteError(HelixRequestDeleteError::Error {
+                status,
+                ..
+            }) => Some(status.as_u16()),
+            _ => None,
+        }
+    }
+
+    fn message(&self) -> Option<&str> {
+        match self {
+            ClientRequestError::HelixRequestGetError(HelixRequestGetError::Error {
+                message,
+                ..
+            })
+            | ClientRequestError::HelixRequestPostError(HelixRequestPostError::Error {
+                message,
+                ..
+            })
+            | ClientRequestError::HelixRequestDeleteError(HelixRequestDeleteError::Error {
+                message,
+                ..
+            }) => Some(message),
+            _ => None,
+        }
+    }
+}
+
+/// Of the requests done without twitch_api2
+#[derive(Debug, thiserror::Error)]
+pub enum HelixError {
+    #[error("helix answered {status}: {message}")]
+    Response {
+        status: u16,
+        message: String,
+        reset: Option<i64>,
+    },
+    #[error("helix request failed: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+impl Failure for HelixError {
+    fn status(&self) -> Option<u16> {
+        match self {
+            HelixError::Response { status, .. } => Some(*status),
+            HelixError::Request(err) => err.status().map(|s| s.as_u16()),
+        }
+    }
+
+    fn message(&self) -> Option<&str> {
+        match self {
+            HelixError::Response { message, .. } => Some(message),
+            HelixError::Request(_) => None,
+        }
+    }
+
+    fn reset(&self) -> Option<i64> {
+        match self {
+            HelixError::Response { reset, .. } => *reset,
+            HelixError::Request(_) => None,
+        }
+    }
+}
+
+/// The response when successful, else an error with what twitch said about it
+pub async fn check(resp: reqwest::Response) -> Result<reqwest::Response, HelixError> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp);
+    }
+    #[derive(Deserialize)]
+    struct Body {
+        message: String,
+    }
+    let reset = RateLimit::from_headers(resp.headers()).map(|r| r.reset);
+    let body = resp.bytes().await?;
+    let message = match serde_json::from_slice::<Body>(&body) {
+        Ok(body) => body.message,
+        Err(_) => String::from_utf8_lossy(&body).into_owned(),
+    };
+    Err(HelixError::Response {
+        status: status.as_u16(),
+        message,
+        reset,
+    })
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Retry {
+    /// a 401, the token was revoked or expired early
+    NewToken,
+    /// a 429 or a 5xx
+    After(Duration),
+    Fail,
+}
+
+/// What to do after that failed attempt, counted from 1
+pub fn retry(err: &impl Failure, attempt: u32, now: time::OffsetDateTime) -> Retry {
+    match err.status() {
+        Some(401) => Retry::NewToken,
+        Some(429) => Retry::After(rate_limit_wait(err.reset(), now)),
+        Some(status) if status >= 500 => Retry::After(FIRST_BACKOFF * 2u32.pow(attempt - 1)),
+        _ => Retry::Fail,
+    }
+}
+
+fn rate_limit_wait(reset: Option<i64>, now: time::OffsetDateTime) -> Duration {
+    let wait = match reset {
+        Some(reset) => Duration::from_secs((reset - now.unix_timestamp()).max(1) as u64),
+        None => DEFAULT_RATE_LIMIT_WAIT,
+    };
+    wait.min(MAX_RATE_LIMIT_WAIT)
+}
+
+/// Once retrying is pointless. Rate limited, the users are told to try later.
+pub fn into_error(err: impl Failure, ctx: &str, now: time::OffsetDateTime) -> Error {
+    if err.status() == Some(429) {
+        return Error::RateLimited {
+            retry_after: Some(rate_limit_wait(err.reset(), now)),
+        };
+    }
+    let ctx = match err.message() {
+        Some(message) => format!("{ctx}: {message}"),
+        None => ctx.to_string(),
+    };
+    Error::Internal {
+        source: Box::new(err),
+        ctx,
+    }
+}
+
+/// From the Ratelimit-* headers of a helix response
+#[derive(Debug, PartialEq)]
+pub struct RateLimit {
+    pub remaining: u64,
+    /// unix timestamp of when the bucket is full again
+    pub reset: i64,
+}
+
+impl RateLimit {
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| -> Option<i64> { headers.get(name)?.to_str().ok()?.parse().ok() };
+        Some(RateLimit {
+            remaining: header("ratelimit-remaining")?.try_into().ok()?,
+            reset: header("ratelimit-reset")?,
+        })
+    }
+
+    /// How long to wait before the next request, None when there are
+    /// enough points left
+    pub fn wait(&self, now: time::OffsetDateTime) -> Option<Duration> {
+        if self.remaining >= LOW_REMAINING {
+            return None;
+        }
+        let wait = self.reset - now.unix_timestamp();
+        Some(Duration::from_secs(wait.max(1) as u64).min(MAX_RATE_LIMIT_WAIT))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::token::{Fetched, TokenManager, TokenSource};
+    use async_trait::async_trait;
+    use axum::{extract::State, http::HeaderMap as Headers, response::IntoResponse, Router};
+    use pretty_assertions::assert_eq;
+    use std::{
+        collections::VecDeque,
+        net::{SocketAddr, TcpListener},
+        sync::{Arc, Mutex},
+    };
+    use time::macros::datetime;
+
+    /// Tokens "token 1", "token 2"…
+    #[derive(Default)]
+    struct Tokens(Mutex<u64>);
+
+    #[async_trait]
+    impl TokenSource for Tokens {
+        type Token = String;
+
+        async fn fetch(&self) -> plugin_core::Result<Fetched<String>> {
+            let mut n = self.0.lock().unwrap();
+            *n += 1;
+            Ok(Fetched {
+                token: format!("token {n}"),
+                expires_in: Duration::from_secs(3600 * 4),
+            })
+        }
+    }
+
+    /// Answers with the scripted statuses, then 200, and records the
+    /// Authorization header of each request
+    #[derive(Clone, Default)]
+    struct Script {
+        statuses: Arc<Mutex<VecDeque<u16>>>,
+        seen: Arc<Mutex<Vec<String>>>,
+    }
+
+    async fn scripted(State(script): State<Script>, headers: Headers) -> impl IntoResponse {
+        let auth = headers
+            .get("authorization")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        script.seen.lock().unwrap().push(auth);
+        let status = script.statuses.lock().unwrap().pop_front().unwrap_or(200);
+        let status = axum::http::StatusCode::from_u16(status).unwrap();
+        let reset = (time::OffsetDateTime::now_utc().unix_timestamp() + 2).to_string();
+        let body = match status.as_u16() {
+            200 => r#"{"data":[]}"#.to_string(),
+            code => format!(
+                r#"{{"error":"{}","status":{code},"message":"scripted {code}"}}"#,
+                status.canonical_reason().unwrap_or_default()
+            ),
+        };
+        (
+            status,
+            [
+                ("ratelimit-remaining", "0".to_string()),
+                ("ratelimit-reset", reset),
+            ],
+            body,
+        )
+    }
+
+    /// A mock helix on a random port
+    fn serve(statuses: &[u16]) -> (SocketAddr, Script) {
+        let script = Script::default();
+        script.statuses.lock().unwrap().extend(statuses);
+        let app = Router::new()
+            .route("/helix/streams", axum::routing::get(scripted))
+            .with_state(script.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(app.into_make_service());
+        tokio::spawn(server);
+        (addr, script)
+    }
+
+    async fn get_streams(addr: SocketAddr) -> plugin_core::Result<String> {
+        let tokens = TokenManager::new(Tokens::default()).await.unwrap();
+        let http = reqwest::Client::new();
+        let url = format!("http://{addr}/helix/streams");
+        tokens
+            .with_token("Can't get live streams", |token| {
+                let request = http.get(&url).bearer_auth(token);
+                async move { Ok(check(request.send().await?).await?.text().await?) }
+            })
+            .await
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unauthorized_then_ok() {
+        let (addr, script) = serve(&[401]);
+        assert_eq!(get_streams(addr).await.unwrap(), r#"{"data":[]}"#);
+        assert_eq!(
+            *script.seen.lock().unwrap(),
+            vec!["Bearer token 1", "Bearer token 2"]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limited_then_ok() {
+        let (addr, script) = serve(&[429]);
+        assert_eq!(get_streams(addr).await.unwrap(), r#"{"data":[]}"#);
+        assert_eq!(
+            *script.seen.lock().unwrap(),
+            vec!["Bearer token 1", "Bearer token 1"]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_persistent_server_error() {
+        let (addr, script) = serve(&[500; 10]);
+        let err = get_streams(addr).await.unwrap_err();
+        assert_eq!(script.seen.lock().unwrap().len(), MAX_ATTEMPTS as usize);
+        match err {
+            Error::Internal { ctx, .. } => {
+                assert_eq!(ctx, "Can't get live streams: scripted 500")
+            }
+            err => panic!("unexpected {err:?}"),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_not_retried() {
+        let (addr, script) = serve(&[400, 400]);
+        assert!(get_streams(addr).await.is_err());
+        assert_eq!(script.seen.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_retry() {
+        let now = datetime!(2024-05-01 20:00 UTC); // 1714593600
+        let response = |status: u16, reset: Option<i64>| HelixError::Response {
+            status,
+            message: "".to_string(),
+            reset,
+        };
+        assert_eq!(retry(&response(401, None), 1, now), Retry::NewToken);
+        assert_eq!(
+            retry(&response(429, Some(1714593612)), 1, now),
+            Retry::After(Duration::from_secs(12))
+        );
+        assert_eq!(
+            retry(&response(429, Some(1714597200)), 1, now),
+            Retry::After(MAX_RATE_LIMIT_WAIT),
+            "capped"
+        );
+        assert_eq!(
+            retry(&response(429, None), 1, now),
+            Retry::After(DEFAULT_RATE_LIMIT_WAIT)
+        );
+        let backoffs = (1..=3)
+            .map(|attempt| retry(&response(503, None), attempt, now))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            backoffs,
+            vec![
+                Retry::After(Duration::from_secs(1)),
+                Retry::After(Duration::from_secs(2)),
+                Retry::After(Duration::from_secs(4))
+            ]
+        );
+        assert_eq!(retry(&response(404, None), 1, now), Retry::Fail);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut headers = HeaderMap::new();
+        headers.insert("ratelimit-limit", "800".parse().unwrap());
+        headers.insert("ratelimit-remaining", "3".parse().unwrap());
+        headers.insert("ratelimit-reset", "1714593630".parse().unwrap());
+        let rate = RateLimit::from_headers(&headers).unwrap();
+        assert_eq!(
+            rate,
+            RateLimit {
+                remaining: 3,
+                reset: 1714593630
+            }
+        );
+        let now = datetime!(2024-05-01 20:00 UTC); // 1714593600
+        assert_eq!(rate.wait(now), Some(Duration::from_secs(30)));
+        assert_eq!(
+            rate.wait(datetime!(2024-05-01 21:00 UTC)),
+            Some(Duration::from_secs(1)),
+            "already reset"
+        );
+        assert_eq!(
+            rate.wait(datetime!(2024-05-01 19:00 UTC)),
+            Some(MAX_RATE_LIMIT_WAIT),
+            "a reset far away"
+        );
+        let plenty = RateLimit {
+            remaining: 799,
+            reset: 1714593630,
+        };
+        assert_eq!(plenty.wait(now), None);
+        assert_eq!(RateLimit::from_headers(&HeaderMap::new()), None);
+    }
+}
@@ -1,5 +1,21 @@
+#[macro_use]
+extern crate diesel;
+
 mod plugin;
+mod changes;
+mod clips;
 mod config;
+mod db;
+mod flaps;
+mod followed;
+mod helix;
+mod live_server;
+mod poll;
+mod routes;
+mod sessions;
+mod status;
+mod subscriptions;
+mod token;
 mod webhook_server;
 mod errors;
 
@@ -0,0 +1,181 @@
+use axum::{extract::State, routing, Json, Router};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use twitch_api2::{helix::streams::Stream, types::Nickname};
+
+use crate::followed::Followed;
+
+/// The live streams, kept up to date by the notifications and the
+/// reconciliations
+pub type OnlineStreams = Arc<Mutex<HashMap<Nickname, Stream>>>;
+
+#[derive(Clone)]
+struct LiveState {
+    online: OnlineStreams,
+    followed: Arc<Followed>,
+}
+
+#[derive(Debug, Serialize)]
+struct Live {
+    login: String,
+    title: String,
+    /// empty when none was set
+    category: String,
+    viewer_count: usize,
+    /// RFC3339, as given by twitch
+    started_at: String,
+}
+
+#[derive(Debug, Serialize)]
+struct FollowedStream {
+    login: String,
+    irc_nick: String,
+    /// where its go-live gets announced
+    irc_channels: Vec<String>,
+}
+
+/// GET /live and /followed for status dashboards, out of what the plugin
+/// already knows, without asking twitch
+pub fn router(online: OnlineStreams, followed: Arc<Followed>) -> Router<()> {
+    Router::new()
+        .route("/live", routing::get(get_live))
+        .route("/followed", routing::get(get_followed))
+        .with_state(LiveState { online, followed })
+}
+
+async fn get_live(State(state): State<LiveState>) -> Json<Vec<Live>> {
+    let mut live = state
+        .online
+        .lock()
+        .expect("twitch state lock")
+        .values()
+        .map(|stream| Live {
+            login: stream.user_login.to_string(),
+            title: stream.title.clone(),
+            category: stream.game_name.to_string(),
+            viewer_count: stream.viewer_count,
+            started_at: stream.started_at.to_string(),
+        })
+        .collect::<Vec<_>>();
+    live.sort_by(|a, b| a.login.cmp(&b.login));
+    Json(live)
+}
+
+async fn get_followed(State(state): State<LiveState>) -> Json<Vec<FollowedStream>> {
+    let followed = state
+        .followed
+        .streams()
+        .into_iter()
+        .map(|s| FollowedStream {
+            login: s.nickname.to_string(),
+            irc_nick: s.irc_nick,
+            irc_channels: s.irc_channels,
+        })
+        .collect();
+    Json(followed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{config::StreamSpec, routes::Route};
+    use axum::body::{Body, HttpBody};
+    use axum::http::{Request, StatusCode};
+    use plugin_core::Database;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    /// An entry of the data of a helix GET /streams response
+    const STREAM: &str = r#"{
+        "id": "40952121085",
+        "user_id": "101051819",
+        "user_login": "geekingfrog",
+        "user_name": "Geekingfrog",
+        "game_id": "509670",
+        "game_name": "Science & Technology",
+        "type": "live",
+        "title": "Rust & chill",
+        "viewer_count": 42,
+        "started_at": "2024-05-01T20:00:00Z",
+        "language": "fr",
+        "thumbnail_url": "https://static-cdn.jtvnw.net/previews-ttv/live_user_geekingfrog-{width}x{height}.jpg",
+        "tag_ids": [],
+        "is_mature": false
+    }"#;
+
+    fn app() -> Router<()> {
+        let stream: Stream = serde_json::from_str(STREAM).unwrap();
+        let online = OnlineStreams::default();
+        online
+            .lock()
+            .unwrap()
+            .insert(stream.user_login.clone(), stream);
+        let configured = ["geekingfrog", "chouhartem"]
+            .into_iter()
+            .map(|login| StreamSpec {
+                nickname: Nickname::new(login.to_string()),
+                irc_nick: login.to_uppercase(),
+                irc_channels: vec!["##arch-fr-free".to_string()],
+                announce_offline: true,
+                announce_changes: false,
+            })
+            .collect();
+        let routes = vec![Route::new("#rust", "geekingfrog")];
+        let db = Database::in_memory().unwrap();
+        let followed = Followed::load(db, configured, routes).unwrap();
+        router(online, Arc::new(followed))
+    }
+
+    async fn get(app: Router<()>, uri: &str) -> (StatusCode, serde_json::Value) {
+        let req = Request::get(uri).body(Body::empty()).unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        let status = resp.status();
+        let mut body = resp.into_body();
+        let mut bytes = vec![];
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_live() {
+        let (status, body) = get(app(), "/live").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!([{
+                "login": "geekingfrog",
+                "title": "Rust & chill",
+                "category": "Science & Technology",
+                "viewer_count": 42,
+                "started_at": "2024-05-01T20:00:00Z",
+            }])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_followed() {
+        let (status, body) = get(app(), "/followed").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!([
+                {
+                    "login": "geekingfrog",
+                    "irc_nick": "GEEKINGFROG",
+                    "irc_channels": ["##arch-fr-free", "#rust"],
+                },
+                {
+                    "login": "chouhartem",
+                    "irc_nick": "CHOUHARTEM",
+                    "irc_channels": ["##arch-fr-free"],
+                },
+            ])
+        );
+    }
+}
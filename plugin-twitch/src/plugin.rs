@@ -1,64 +1,108 @@
 use async_trait::async_trait;
 // use irc::client::prelude::Message;
-use plugin_core::{Initialised, Plugin, Result};
+use plugin_core::utils::account::is_admin;
+use plugin_core::{
+    CaseMapping, CommandHelp, Cooldown, Initialised, Lang, MsgCtx, Outbound, Plugin, Requirement,
+    Result,
+};
 
 use std::{
     collections::HashMap,
     sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc, Mutex as TokioMutex},
+    time::Instant,
 };
-use tokio::sync::{mpsc, Mutex as TokioMutex};
 
 use anyhow::Context;
 use irc::client::prelude::Command;
-use irc::proto::Message as IrcMessage;
+use irc::proto::{ChannelExt, Message as IrcMessage};
 use twitch_api2::{
     eventsub::{
         self,
-        stream::{StreamOfflineV1, StreamOfflineV1Payload, StreamOnlineV1, StreamOnlineV1Payload},
-        EventSubscription, EventType,
+        channel::ChannelUpdateV1,
+        stream::{StreamOfflineV1, StreamOnlineV1, StreamOnlineV1Payload},
+        EventSubscription,
     },
     helix::{
         self,
         streams::{self, Stream},
         users::{get_users, User},
+        videos::{self, Video},
     },
-    twitch_oauth2::AppAccessToken,
-    types::{EventSubId, Nickname, UserId},
+    twitch_oauth2::{AppAccessToken, ClientId, ClientSecret, TwitchToken},
+    types::{Nickname, UserId},
     HelixClient,
 };
 
 use crate::{
-    config::{Config, Message},
+    changes::{self, Changes, ChannelInfo},
+    clips::{self, HelixClips, UserToken},
+    config::{Config, Message, Mode, StreamSpec},
+    flaps::{Announce, Flaps},
+    followed::{self, Added, FollowApi, Followed, Removed, TwitchCommand},
+    helix::{check, HelixError, RateLimit},
+    live_server::{self, OnlineStreams},
+    poll::{self, PollEvent},
+    sessions::{self, Sessions},
+    status::{self, Status},
+    subscriptions::{self, Backoff, Change, Event, Subscription, Wanted},
+    token::{Fetched, TokenManager, TokenSource},
     webhook_server,
 };
 
 use futures::{StreamExt, TryStreamExt};
-use plugin_core::utils::parser;
-
-#[derive(Debug)]
-pub struct Subscription {
-    pub id: EventSubId,
-    pub user_id: UserId,
-    pub type_: EventType,
-    pub status: eventsub::Status,
+use plugin_core::utils::{numbers, parser};
+
+/// A go-live announced later than that, like after a reconnection, tells
+/// for how long the stream has been live
+const LATE_ANNOUNCEMENT: Duration = Duration::from_secs(5 * 60);
+
+/// Between two commands asking twitch of the same nick
+const COMMAND_COOLDOWN: Duration = Duration::from_secs(10);
+
+/// The commands waiting for twitch at most, the next ones are refused
+const MAX_PENDING_COMMANDS: usize = 5;
+
+/// A λtwitch command or λclip, answered by `answer_commands` once twitch
+/// answered, after the retries of the helix requests and the processing of
+/// the clips
+struct Pending {
+    command: TwitchCommand,
+    /// the nick who asked
+    source: String,
+    /// where the command was said
+    response_target: String,
+    /// `nick: ` for the `> nick` of the command
+    prefix: String,
 }
 
-impl Subscription {
-    fn is_valid(&self) -> bool {
-        match self.status {
-            eventsub::Status::Enabled | eventsub::Status::WebhookCallbackVerificationPending => {
-                true
-            }
-            _ => false,
-        }
-    }
+/// The app access tokens, from the client credentials flow
+struct ClientCredentials {
+    http: reqwest::Client,
+    client_id: ClientId,
+    client_secret: ClientSecret,
 }
 
-struct WrappedToken(AppAccessToken);
+#[async_trait]
+impl TokenSource for ClientCredentials {
+    type Token = AppAccessToken;
 
-impl WrappedToken {
-    fn get(&self) -> &AppAccessToken {
-        &self.0
+    async fn fetch(&self) -> Result<Fetched<AppAccessToken>> {
+        let token = AppAccessToken::get_app_access_token(
+            &self.http,
+            self.client_id.clone(),
+            self.client_secret.clone(),
+            vec![], // scopes
+        )
+        .await
+        .context("Cannot get app access token")?;
+        Ok(Fetched {
+            expires_in: token.expires_in(),
+            token,
+        })
     }
 }
 
@@ -86,36 +130,59 @@ pub struct Twitch {
     // The whole thing seems to go away if the twitch client and the oauth client are kept
     // separate. Not the most elegant solution, but at least it works.
     client: HelixClient<'static, reqwest::Client>,
+    /// for the few requests done without twitch_api2
+    http: reqwest::Client,
+    /// None without a clip_token in the config
+    clips: Option<HelixClips>,
 
-    // TODO wrap the uses of the token to automatically refresh it if expired
-    token: WrappedToken,
+    token: Arc<TokenManager<ClientCredentials>>,
     state: State,
+    /// the config's watched streams, and the ones added with λtwitch add
+    followed: Arc<Followed>,
+    /// allowed to change the followed streams
+    admins: Vec<String>,
+    /// of the commands asking twitch, by nick
+    cooldown: Cooldown,
+    sessions: Sessions,
+    /// to not announce the streams coming back right away again
+    flaps: Flaps,
+    changes: Mutex<Changes>,
+    /// held while the subscriptions change, so that syncing them doesn't undo
+    /// what λtwitch add/remove is doing, or the other way around
+    subscribing: TokioMutex<()>,
 
     // messages coming in as responses to twitch webhook, and that need to be sent
     // to the irc network
     twitch_rx: TokioMutex<mpsc::Receiver<Message>>,
+
+    // the commands asking twitch, answered by run
+    commands_tx: mpsc::Sender<Pending>,
+    commands_rx: TokioMutex<mpsc::Receiver<Pending>>,
 }
 
 #[derive(Debug, Default)]
 pub struct State {
     // keys corresponding to Config.watched_streams
     // to identify which watched streams are currently online.
-    online_streams: Arc<Mutex<HashMap<Nickname, Stream>>>,
+    online_streams: OnlineStreams,
 }
 
 impl State {
-    fn add_streams(&self, streams: HashMap<Nickname, Stream>) {
+    fn add_stream(&self, nick: Nickname, stream: Stream) {
         self.online_streams
             .lock()
             .expect("twitch state lock")
-            .extend(streams)
+            .insert(nick, stream);
     }
 
-    fn add_stream(&self, nick: Nickname, stream: Stream) {
+    /// Unknown when not live
+    fn is_mature(&self, nick: &Nickname) -> bool {
         self.online_streams
             .lock()
             .expect("twitch state lock")
-            .insert(nick, stream);
+            .get(nick)
+            .map(|s| s.is_mature)
+            .unwrap_or(false)
     }
 
     fn remove_stream(&self, nick: &Nickname) -> Option<Stream> {
@@ -128,66 +195,172 @@ impl State {
 
 #[async_trait]
 impl Plugin for Twitch {
+    fn check_config(core_config: &plugin_core::Config) -> Result<()> {
+        let config_path = core_config.config_path.as_str();
+        let config =
+            Config::from_file_keyed(config_path).context(format!("Cannot read {config_path}"))?;
+        config.poll_interval()?;
+        core_config.check_database("twitch")?;
+        Ok(())
+    }
+
     async fn init(core_config: &plugin_core::Config) -> Result<Initialised> {
         let config_path = core_config.config_path.as_str();
         let config =
             Config::from_file_keyed(config_path).context(format!("Cannot read {config_path}"))?;
 
-        let auth_client = reqwest::Client::default();
-        let client = HelixClient::new();
+        let client = HelixClient::with_client(core_config.http_client());
 
-        let token = AppAccessToken::get_app_access_token(
-            &auth_client,
-            config.client_id.clone(),
-            config.client_secret.clone(),
-            vec![], // scopes
-        )
-        .await
-        .context("Cannot get app access token")?;
+        let token = TokenManager::new(ClientCredentials {
+            http: core_config.http_client(),
+            client_id: config.client_id.clone(),
+            client_secret: config.client_secret.clone(),
+        })
+        .await?;
 
         let (twitch_tx, twitch_rx) = mpsc::channel(5);
+        let (commands_tx, commands_rx) = mpsc::channel(MAX_PENDING_COMMANDS);
+
+        let db = core_config.require_database("twitch")?;
+        let followed = Arc::new(Followed::load(
+            db.clone(),
+            config.watched_streams.clone(),
+            config.routes.clone(),
+        )?);
+        let sessions = Sessions::new(db.clone())?;
+        let flaps = Flaps::new(db)?;
 
         let router = webhook_server::init_router(&config, twitch_tx);
+        let state = State::default();
+        let live_router =
+            live_server::router(Arc::clone(&state.online_streams), Arc::clone(&followed));
+        let token = Arc::new(token);
+        let refresh_task = token.refresh_task();
+        let mode = config.mode;
+        let clips = match config.clip_token.clone() {
+            Some(token) => Some(HelixClips {
+                http: core_config.http_client(),
+                client_id: config.client_id.clone(),
+                token: TokenManager::new(UserToken(token)).await?,
+            }),
+            None => None,
+        };
         let plugin = Twitch {
             config,
-            token: WrappedToken(token),
+            token,
             client,
-            state: Default::default(),
+            http: core_config.http_client(),
+            clips,
+            state,
+            followed,
+            admins: core_config.admins()?,
+            cooldown: Cooldown::new(COMMAND_COOLDOWN),
+            sessions,
+            flaps,
+            changes: Default::default(),
+            subscribing: TokioMutex::new(()),
             twitch_rx: TokioMutex::new(twitch_rx),
+            commands_tx,
+            commands_rx: TokioMutex::new(commands_rx),
         };
 
         Ok(Initialised {
             plugin: Box::new(plugin),
-            router: Some(router),
+            router: Some(live_router),
+            // twitch cannot know our secret, webhook_post2 checks the signature instead
+            public_router: Some(router).filter(|_| mode == Mode::Webhook),
+            tasks: vec![refresh_task],
         })
     }
 
-    async fn run(&self, tx: mpsc::Sender<irc::proto::Message>) -> Result<()> {
-        self.sync_subscriptions().await?;
-        self.state.add_streams(self.get_live_streams().await?);
+    async fn run(&self, tx: mpsc::Sender<Outbound>) -> Result<()> {
+        if self.config.mode == Mode::Webhook {
+            self.sync_subscriptions().await?;
+        }
+        // the streams gone live while the golem was away, announced unless
+        // they were before it left
+        let live_streams = self.get_live_streams().await?;
+        for (nick, stream) in live_streams {
+            let target = self
+                .followed
+                .streams()
+                .into_iter()
+                .find(|s| s.nickname == nick);
+            match target {
+                Some(target) => self.stream_online(&tx, &target, stream).await?,
+                None => self.state.add_stream(nick, stream),
+            }
+        }
 
         // hold that lock forever
         let mut twitch_rx = self.twitch_rx.lock().await;
 
-        while let Some(twitch_msg) = twitch_rx.recv().await {
-            self.process_twitch_message(&tx, twitch_msg).await?;
+        let messages = async {
+            while let Some(twitch_msg) = twitch_rx.recv().await {
+                self.process_twitch_message(&tx, twitch_msg).await?;
+            }
+            Ok(())
+        };
+        let follow = async {
+            match self.config.mode {
+                Mode::Webhook => {
+                    self.reconcile_periodically().await;
+                    Ok(())
+                }
+                Mode::Poll => self.poll_periodically(&tx).await,
+            }
+        };
+        tokio::select! {
+            result = messages => result,
+            result = self.answer_commands(&tx) => result,
+            result = self.announce_held_changes(&tx) => result,
+            result = follow => result,
         }
-        Ok(())
     }
 
     fn get_name(&self) -> &'static str {
         "twitch"
     }
 
-    async fn in_message(&self, msg: &IrcMessage) -> Result<Option<IrcMessage>> {
-        self.in_message(msg).await
+    async fn in_message_ctx(&self, ctx: &MsgCtx, msg: &IrcMessage) -> Result<Option<Outbound>> {
+        self.in_message(msg, ctx.casemapping).await
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("streams")
+                .usage("streams [> nick]")
+                .description("The watched streams currently live"),
+            CommandHelp::new("clip")
+                .usage("clip [> nick]")
+                .description("Clips the only watched stream live and announced in the channel"),
+            CommandHelp::new("twitch")
+                .usage("twitch status [login] [> nick] | twitch clip <login> | twitch add <login> [#channel] | twitch remove <login> [#channel] | twitch list")
+                .description("Whether a stream is live, or clip it. Changing the watched streams is for the admins"),
+        ]
+    }
+
+    fn requirements(&self) -> Vec<Requirement> {
+        let mut requirements = vec![
+            Requirement::ConfigKey("client_id"),
+            Requirement::ConfigKey("client_secret"),
+            Requirement::ConfigKey("app_secret"),
+            Requirement::Network,
+            // to keep the streams added with λtwitch add, and when the live ones started
+            Requirement::Database,
+        ];
+        // to receive the webhook notifications
+        if self.config.mode == Mode::Webhook {
+            requirements.push(Requirement::WebRouter);
+        }
+        requirements
     }
 }
 
 impl Twitch {
     async fn process_twitch_message(
         &self,
-        tx: &mpsc::Sender<irc::proto::Message>,
+        tx: &mpsc::Sender<Outbound>,
         msg: Message,
     ) -> Result<()> {
         log::debug!("Got a twitch message! {:?}", msg);
@@ -197,7 +370,24 @@ impl Twitch {
             }
 
             Message::StreamOffline(offline) => {
-                self.on_stream_offline(tx, offline).await?;
+                self.on_stream_offline(tx, &offline.broadcaster_user_login)
+                    .await?;
+            }
+
+            Message::ChannelUpdate(update) => {
+                let info = ChannelInfo {
+                    title: update.title.clone(),
+                    category: update.category_name.to_string(),
+                };
+                self.on_channel_update(tx, &update.broadcaster_user_login, info)
+                    .await?;
+            }
+
+            Message::Revoked(sub) => {
+                // twitch no longer returns a removed user, who gets skipped
+                if let Err(err) = self.sync_subscriptions().await {
+                    log::error!("Cannot subscribe again after the revocation of {sub:?}: {err:?}");
+                }
             }
         }
         Ok(())
@@ -205,13 +395,13 @@ impl Twitch {
 
     async fn on_stream_online(
         &self,
-        tx: &mpsc::Sender<irc::proto::Message>,
+        tx: &mpsc::Sender<Outbound>,
         online: StreamOnlineV1Payload,
     ) -> Result<()> {
         let target = self
-            .config
-            .watched_streams
-            .iter()
+            .followed
+            .streams()
+            .into_iter()
             .find(|s| s.nickname == online.broadcaster_user_login);
         log::info!("Stream online payload {online:?}");
         match target {
@@ -227,95 +417,218 @@ impl Twitch {
                     None => log::info!(
                         "Got stream live notification but twitch returned nothing. TOCTOU :shrug:"
                     ),
-                    Some(stream) => {
-                        let url = format!("https://www.twitch.tv/{}", &target.nickname);
-                        let game = &stream.game_name.to_string();
-                        let game = if game.is_empty() {
-                            "".to_string()
-                        } else {
-                            format!("({})", game)
-                        };
-
-                        let irc_nick = self.to_irc_nick(nick.as_str());
-                        let message = format!(
-                            "Le stream de {} est maintenant live at {} {}!",
-                            irc_nick, url, game
-                        );
-
-                        log::info!("Stream online: {}", &message);
-                        self.state.add_stream(nick, stream);
-                        for chan in &target.irc_channels {
-                            let cmd = Command::PRIVMSG(chan.clone(), message.clone()).into();
-                            log::info!("Stream online command to chan: {}, {:?}", &chan, &cmd);
-                            tx.send(cmd)
-                                .await
-                                .with_context(|| format!("can't send message to {}", &chan))?;
-                        }
-                    }
+                    // the title and category from helix, the notification may be stale
+                    Some(stream) => self.stream_online(tx, &target, stream).await?,
                 }
             }
         };
         Ok(())
     }
 
-    async fn on_stream_offline(
+    /// Announces it, unless it already was, even before a restart, or it is
+    /// flapping, see `Flaps`
+    async fn stream_online(
         &self,
-        tx: &mpsc::Sender<irc::proto::Message>,
-        offline: StreamOfflineV1Payload,
+        tx: &mpsc::Sender<Outbound>,
+        target: &StreamSpec,
+        stream: Stream,
     ) -> Result<()> {
+        let nick = target.nickname.clone();
+        let now = time::OffsetDateTime::now_utc();
+        let announce = self.flaps.check(
+            nick.as_str(),
+            stream.id.as_str(),
+            now,
+            &self.config.flapping,
+        )?;
+        let stream_id = stream.id.to_string();
+        let started_at = stream_start(&stream).unwrap_or(now);
+        let message = match announce {
+            Announce::Live => Some(live_announcement(&stream, now)),
+            Announce::Back => Some(format!("{} est de retour", stream.user_name)),
+            Announce::Nothing => None,
+        };
+
+        self.went_live(&stream)?;
+        let channels = self.channels_of(target, stream.is_mature);
+        self.state.add_stream(nick.clone(), stream);
+        match message {
+            None => log::info!("Stream online again, not announced"),
+            Some(message) => {
+                log::info!("Stream online: {}", &message);
+                for chan in &channels {
+                    let cmd = Outbound::reply(chan.clone(), message.clone());
+                    log::info!("Stream online command to chan: {}, {:?}", &chan, &cmd);
+                    tx.send(cmd)
+                        .await
+                        .with_context(|| format!("can't send message to {}", &chan))?;
+                }
+            }
+        }
+        self.flaps
+            .announced(nick.as_str(), &stream_id, started_at, now)?;
+        Ok(())
+    }
+
+    async fn on_stream_offline(&self, tx: &mpsc::Sender<Outbound>, login: &Nickname) -> Result<()> {
         let target = self
-            .config
-            .watched_streams
-            .iter()
-            .find(|s| s.nickname == offline.broadcaster_user_login);
+            .followed
+            .streams()
+            .into_iter()
+            .find(|s| &s.nickname == login);
         match target {
-            None => log::warn!(
-                "Got a notification for {} but not found in config",
-                offline.broadcaster_user_login
-            ),
+            None => log::warn!("Got a notification for {login} but not found in config"),
             Some(target) => {
-                match self.state.remove_stream(&target.nickname) {
-                    None => {
-                        // this can happen when a streams goes online/offline rapidly,
-                        // twitch only sends the offline event.
-                        log::warn!("Got an offline notification for a stream not marked live");
-                    }
-                    Some(_s) => {
-                        let nick = self.to_irc_nick(target.nickname.as_str());
-                        let message =
-                                    format!("{} a arreté de streamer pour le moment. N'oubliez pas de like&subscribe.", nick);
-                        log::info!("Stream offline: {}", &message);
-                        for chan in &target.irc_channels {
-                            tx.send(Command::PRIVMSG(chan.clone(), message.clone()).into())
-                                .await
-                                .with_context(|| format!("can't send message to {}", &chan))?;
-                        }
-                    }
+                let login = target.nickname.as_str();
+                // persisted, unlike the live streams, to know it even after a restart
+                let started_at = self.sessions.end(login)?;
+                self.flaps.offline(login, time::OffsetDateTime::now_utc())?;
+                let stream = self.state.remove_stream(&target.nickname);
+                let was_live = stream.is_some();
+                let is_mature = stream.map(|s| s.is_mature).unwrap_or(false);
+                self.changes.lock().expect("changes lock").offline(login);
+                if !was_live && started_at.is_none() {
+                    // this can happen when a streams goes online/offline rapidly,
+                    // twitch only sends the offline event.
+                    log::warn!("Got an offline notification for a stream not marked live");
+                } else if target.announce_offline {
+                    let nick = self.to_irc_nick(login);
+                    let length = started_at.and_then(|started_at| {
+                        sessions::session_length(started_at, time::OffsetDateTime::now_utc())
+                    });
+                    let message = match length {
+                        Some(length) => format!(
+                            "{nick} a fini son live après {}",
+                            sessions::format_length(length)
+                        ),
+                        None => format!("{nick} a fini son live"),
+                    };
+                    log::info!("Stream offline: {}", &message);
+                    let channels = self.channels_of(&target, is_mature);
+                    self.announce(tx, &channels, &message).await?;
                 }
             }
         };
         Ok(())
     }
 
+    /// Remembers when the stream started, and its title and category
+    /// to announce their changes
+    fn went_live(&self, stream: &Stream) -> Result<()> {
+        let login = stream.user_login.as_str();
+        let started_at = stream_start(stream).unwrap_or_else(time::OffsetDateTime::now_utc);
+        self.sessions.start(login, started_at)?;
+        let info = ChannelInfo {
+            title: stream.title.clone(),
+            category: stream.game_name.to_string(),
+        };
+        self.changes.lock().expect("changes lock").live(login, info);
+        Ok(())
+    }
+
+    async fn on_channel_update(
+        &self,
+        tx: &mpsc::Sender<Outbound>,
+        login: &Nickname,
+        info: ChannelInfo,
+    ) -> Result<()> {
+        let target = self
+            .followed
+            .streams()
+            .into_iter()
+            .find(|s| &s.nickname == login);
+        let target = match target {
+            Some(target) if target.announce_changes => target,
+            _ => return Ok(()),
+        };
+        let login = target.nickname.as_str();
+        let change = self
+            .changes
+            .lock()
+            .expect("changes lock")
+            .update(login, info, Instant::now());
+        match change {
+            Some(info) => {
+                let message = changes::change_message(&self.to_irc_nick(login), &info);
+                log::info!("Channel update: {}", &message);
+                let channels = self.channels_of(&target, self.state.is_mature(&target.nickname));
+                self.announce(tx, &channels, &message).await?;
+            }
+            None => log::debug!("Channel update of {login} not announced (yet)"),
+        }
+        Ok(())
+    }
+
+    /// Never returns unless the messages can't be sent anymore. The changes held
+    /// back because another one was just announced are sent once they are due.
+    async fn announce_held_changes(&self, tx: &mpsc::Sender<Outbound>) -> Result<()> {
+        let mut interval = tokio::time::interval(Duration::from_secs(30));
+        loop {
+            interval.tick().await;
+            let due = self
+                .changes
+                .lock()
+                .expect("changes lock")
+                .due(Instant::now());
+            for (login, info) in due {
+                let target = self
+                    .followed
+                    .streams()
+                    .into_iter()
+                    .find(|s| s.nickname.as_str() == login);
+                if let Some(target) = target {
+                    let message = changes::change_message(&self.to_irc_nick(&login), &info);
+                    log::info!("Channel update: {}", &message);
+                    let mature = self.state.is_mature(&target.nickname);
+                    self.announce(tx, &self.channels_of(&target, mature), &message)
+                        .await?;
+                }
+            }
+        }
+    }
+
+    /// See `announced_in`
+    fn channels_of(&self, target: &StreamSpec, is_mature: bool) -> Vec<String> {
+        announced_in(
+            &target.irc_channels,
+            is_mature,
+            &self.config.family_friendly_channels,
+        )
+    }
+
+    async fn announce(
+        &self,
+        tx: &mpsc::Sender<Outbound>,
+        channels: &[String],
+        message: &str,
+    ) -> Result<()> {
+        for chan in channels {
+            tx.send(Outbound::reply(chan.clone(), message))
+                .await
+                .with_context(|| format!("can't send message to {}", &chan))?;
+        }
+        Ok(())
+    }
+
     /// Returns a hashmap indexed by nickname and live stream information
     /// Abscence of a key indicates the stream is not live.
     async fn get_live_streams(&self) -> Result<HashMap<Nickname, Stream>> {
         let user_logins = self
-            .config
-            .watched_streams
-            .iter()
-            .map(|s| s.nickname.clone())
+            .followed
+            .streams()
+            .into_iter()
+            .map(|s| s.nickname)
             .collect();
+        let req = streams::GetStreamsRequest::builder()
+            .user_login(user_logins)
+            .build();
         let resp = self
-            .client
-            .req_get(
-                streams::GetStreamsRequest::builder()
-                    .user_login(user_logins)
-                    .build(),
-                self.token.get(),
-            )
-            .await
-            .context("Can't get live stream")?;
+            .token
+            .with_token("Can't get live stream", |token| {
+                let req = req.clone();
+                async move { self.client.req_get(req, &token).await }
+            })
+            .await?;
 
         Ok(resp
             .data
@@ -326,21 +639,26 @@ impl Twitch {
 
     /// returning Ok(None) means the given nick isn't live atm
     pub async fn get_live_stream(&self, nick: Nickname) -> Result<Option<Stream>> {
+        let req = streams::GetStreamsRequest::builder()
+            .user_login(vec![nick.clone()])
+            .build();
+        let ctx = format!("Can't get live stream for {}", &nick);
         let mut resp = self
-            .client
-            .req_get(
-                streams::GetStreamsRequest::builder()
-                    .user_login(vec![nick.clone()])
-                    .build(),
-                self.token.get(),
-            )
-            .await
-            .with_context(|| format!("Can't get live stream for {}", &nick))?;
+            .token
+            .with_token(&ctx, |token| {
+                let req = req.clone();
+                async move { self.client.req_get(req, &token).await }
+            })
+            .await?;
 
         Ok(resp.data.pop())
     }
 
-    async fn in_message(&self, msg: &IrcMessage) -> Result<Option<IrcMessage>> {
+    async fn in_message(
+        &self,
+        msg: &IrcMessage,
+        casemapping: CaseMapping,
+    ) -> Result<Option<Outbound>> {
         let response_target = match msg.response_target() {
             None => return Ok(None),
             Some(target) => target,
@@ -355,69 +673,487 @@ impl Twitch {
                 } else {
                     self.format_streams(live_streams.values())
                 };
-                return Ok(Some(
-                    Command::PRIVMSG(response_target.to_string(), message).into(),
-                ));
+                return Ok(Some(Outbound::reply(response_target, message)));
+            }
+
+            let twitch_command = match parser::single_command("clip", privmsg) {
+                Some(target) => Some((Ok(TwitchCommand::Clip(None)), target)),
+                None => followed::parse_command(privmsg),
+            };
+            if let Some((command, target)) = twitch_command {
+                let source = msg.source_nickname().unwrap_or_default();
+                let public = matches!(
+                    command,
+                    Ok(TwitchCommand::Status(_) | TwitchCommand::Clip(_))
+                );
+                if !public && !is_admin(&self.admins, msg, casemapping) {
+                    log::warn!("{source} isn't an admin, ignoring {privmsg:?}");
+                    return Ok(None);
+                }
+                if public && !self.cooled_down(msg, casemapping) {
+                    return Ok(None);
+                }
+                let prefix = target.map(|t| format!("{}: ", t)).unwrap_or_default();
+                let command = match command {
+                    Ok(command) => command,
+                    Err(usage) => {
+                        return Ok(Some(Outbound::reply(
+                            response_target,
+                            format!("{prefix}{usage}"),
+                        )))
+                    }
+                };
+                return Ok(self.queue_command(Pending {
+                    command,
+                    source: source.to_string(),
+                    response_target: response_target.to_string(),
+                    prefix,
+                }));
             }
         }
         Ok(None)
     }
 
-    /// Make sure the bot is subscribed to stream.online and stream.offline
-    /// for all the given user names (should not be capitalized)
-    /// Also unsubscribe from existing subscriptions for user not listed in `user_names`
-    async fn sync_subscriptions(&self) -> Result<()> {
-        let subs = self.list_subscriptions().await?;
+    /// Whether the cooldown of the sender of the message is over, nicks
+    /// compared with the casemapping of their network
+    fn cooled_down(&self, msg: &IrcMessage, casemapping: CaseMapping) -> bool {
+        let nick = msg.source_nickname().unwrap_or_default();
+        self.cooldown.check(&casemapping.normalize(nick))
+    }
 
-        let users = self
-            .config
-            .watched_streams
-            .iter()
-            .map(|u| &u.nickname)
-            .collect::<Vec<_>>();
-        log::info!("Syncing subscription for users {:?}", users);
+    /// The reply to λtwitch add|remove|list|status|clip and λclip. Streams are
+    /// added to and removed from the channel given, or else the one of the command.
+    async fn twitch_command(
+        &self,
+        command: TwitchCommand,
+        channel: Option<&str>,
+        source: &str,
+    ) -> Result<String> {
+        let message = match command {
+            TwitchCommand::Add(login, _) | TwitchCommand::Remove(login, _)
+                if followed::login(&login).is_none() =>
+            {
+                format!("{login} isn't a twitch login")
+            }
+            TwitchCommand::Add(login, irc_channel) => {
+                let login = followed::login(&login).expect("valid login");
+                match irc_channel.as_deref().or(channel) {
+                    None => format!(
+                        "Add {login} from the channel where it should be announced, or give that channel"
+                    ),
+                    Some(channel) => {
+                        let _subscribing = self.subscribing.lock().await;
+                        match self.followed.add(self, &login, channel, source).await? {
+                            Added::Followed => format!("Following {login}, announced in {channel}"),
+                            Added::Routed => format!("{login} now announced in {channel} too"),
+                            Added::AlreadyFollowed => {
+                                format!("{login} is already announced in {channel}")
+                            }
+                            Added::NoSuchUser => format!("No twitch user {login}"),
+                        }
+                    }
+                }
+            }
+            TwitchCommand::Remove(login, irc_channel) => {
+                let login = followed::login(&login).expect("valid login");
+                let channel = irc_channel.as_deref().or(channel);
+                let _subscribing = self.subscribing.lock().await;
+                match self.followed.remove(self, &login, channel).await? {
+                    Removed::Unfollowed => {
+                        self.state.remove_stream(&Nickname::new(login.clone()));
+                        format!("Unfollowed {login}")
+                    }
+                    Removed::Unrouted => format!(
+                        "{login} no longer announced in {}",
+                        channel.unwrap_or_default()
+                    ),
+                    Removed::NotRouted => {
+                        format!("{login} isn't announced in {}", channel.unwrap_or_default())
+                    }
+                    Removed::NotFollowed => format!("Not following {login}"),
+                    Removed::Configured => format!("{login} is in the config, remove it there"),
+                }
+            }
+            TwitchCommand::List => {
+                let online = self.state.online_streams.lock().expect("twitch state lock");
+                let streams = self
+                    .followed
+                    .streams()
+                    .into_iter()
+                    .map(|s| match online.contains_key(&s.nickname) {
+                        true => format!("{} (live)", s.nickname),
+                        false => format!("{} (offline)", s.nickname),
+                    })
+                    .collect::<Vec<_>>();
+                if streams.is_empty() {
+                    "Not following any stream".to_string()
+                } else {
+                    streams.join(", ")
+                }
+            }
+            TwitchCommand::Status(login) => self.status(login.as_deref()).await?,
+            TwitchCommand::Clip(login) => self.clip(login.as_deref(), channel).await?,
+        };
+        Ok(message)
+    }
 
-        let users = self
-            .get_users(
-                self.config
-                    .watched_streams
+    /// Leaves the command to `answer_commands`, so that in_message doesn't
+    /// wait for twitch. The reply when too many commands are waiting already.
+    fn queue_command(&self, pending: Pending) -> Option<Outbound> {
+        let response_target = pending.response_target.clone();
+        let prefix = pending.prefix.clone();
+        match self.commands_tx.try_send(pending) {
+            Ok(()) => None,
+            Err(_) => Some(Outbound::reply(
+                response_target,
+                format!("{prefix}Too many twitch commands waiting, try again in a minute"),
+            )),
+        }
+    }
+
+    /// Never returns unless the messages can't be sent anymore. Runs the
+    /// commands queued by `queue_command`, a few at a time, and replies.
+    async fn answer_commands(&self, tx: &mpsc::Sender<Outbound>) -> Result<()> {
+        // hold that lock forever
+        let mut commands_rx = self.commands_rx.lock().await;
+        futures::stream::poll_fn(|cx| commands_rx.poll_recv(cx))
+            .map(Ok::<_, plugin_core::Error>)
+            .try_for_each_concurrent(MAX_PENDING_COMMANDS, |request| async move {
+                let channel =
+                    Some(request.response_target.as_str()).filter(|t| t.is_channel_name());
+                let command = format!("{:?}", request.command);
+                let message = match self
+                    .twitch_command(request.command, channel, &request.source)
+                    .await
+                {
+                    Ok(message) => message,
+                    Err(err) => match err.user_message(Lang::En) {
+                        Some(message) => message,
+                        None => {
+                            log::error!("Cannot answer {command}: {err:?}");
+                            "Twitch is unavailable for now".to_string()
+                        }
+                    },
+                };
+                let reply = Outbound::reply(
+                    &request.response_target,
+                    format!("{}{message}", request.prefix),
+                );
+                tx.send(reply).await.with_context(|| {
+                    format!("can't send message to {}", request.response_target)
+                })?;
+                Ok(())
+            })
+            .await
+    }
+
+    /// The reply to λtwitch clip <login>, or to λclip for the only followed
+    /// stream live among the ones announced in the channel
+    async fn clip(&self, login: Option<&str>, channel: Option<&str>) -> Result<String> {
+        let api = match &self.clips {
+            Some(api) => api,
+            None => return Ok("Les clips ne sont pas configurés".to_string()),
+        };
+        let login = match login {
+            Some(login) => match followed::login(login) {
+                Some(login) => login,
+                None => return Ok(format!("{login} isn't a twitch login")),
+            },
+            None => match self.live_in(channel).as_slice() {
+                [login] => login.clone(),
+                [] => return Ok("Personne n'est en live ici".to_string()),
+                _ => {
+                    return Ok(
+                        "Plusieurs streams en live, précise : λtwitch clip <login>".to_string()
+                    )
+                }
+            },
+        };
+        let live_id = self
+            .state
+            .online_streams
+            .lock()
+            .expect("twitch state lock")
+            .get(&Nickname::new(login.clone()))
+            .map(|s| s.user_id.clone());
+        let broadcaster_id = match live_id {
+            Some(user_id) => user_id,
+            None => match self.user_id(&login).await? {
+                Some(user_id) => user_id,
+                None => return Ok(format!("No twitch user {login}")),
+            },
+        };
+        let clipped = clips::clip(api, &broadcaster_id, &clips::CHECKS).await?;
+        Ok(clips::clip_message(&login, &clipped))
+    }
+
+    /// The followed streams live right now, and announced in that channel
+    fn live_in(&self, channel: Option<&str>) -> Vec<String> {
+        let channel = match channel {
+            Some(channel) => channel,
+            None => return vec![],
+        };
+        let online = self.state.online_streams.lock().expect("twitch state lock");
+        self.followed
+            .streams()
+            .into_iter()
+            .filter(|s| online.contains_key(&s.nickname))
+            .filter(|s| {
+                s.irc_channels
                     .iter()
-                    .map(|u| u.nickname.clone())
-                    .collect(),
-                vec![],
-            )
-            .await?;
+                    .any(|c| c.eq_ignore_ascii_case(channel))
+            })
+            .map(|s| s.nickname.to_string())
+            .collect()
+    }
 
-        let subs_to_delete: Vec<_> = subs
-            .iter()
-            .filter(|s| !s.is_valid() || !users.iter().any(|u| s.user_id == u.id))
-            .collect();
+    /// Asks twitch whether the stream is live, or all the followed ones
+    /// without a login
+    async fn status(&self, login: Option<&str>) -> Result<String> {
+        let logins = match login {
+            Some(login) => match followed::login(login) {
+                Some(login) => vec![login],
+                None => return Ok(format!("{login} isn't a twitch login")),
+            },
+            None => self
+                .followed
+                .streams()
+                .into_iter()
+                .map(|s| s.nickname.to_string())
+                .collect(),
+        };
+        if logins.is_empty() {
+            return Ok("Not following any stream".to_string());
+        }
+
+        let nicks = logins.iter().cloned().map(Nickname::new).collect();
+        let users = self.get_users(nicks, vec![]).await?;
+        let unknown = status::unknown_logins(&logins, &users);
+        if !unknown.is_empty() {
+            if login.is_some() {
+                return Ok(format!("No twitch user {}", unknown.join(", ")));
+            }
+            log::warn!("Followed streams unknown to twitch: {unknown:?}");
+        }
+        if users.is_empty() {
+            // without any user_id, helix gives the most watched streams
+            return Ok("Not following any stream".to_string());
+        }
+
+        let req = streams::GetStreamsRequest::builder()
+            .user_id(users.iter().map(|u| u.id.clone()).collect())
+            .build();
+        let live = self
+            .token
+            .with_token("Can't get live streams", |token| {
+                let req = req.clone();
+                async move { self.client.req_get(req, &token).await }
+            })
+            .await?
+            .data;
+
+        let now = time::OffsetDateTime::now_utc();
+        let statuses = futures::future::try_join_all(users.iter().map(|user| {
+            let live = &live;
+            async move {
+                let status = match live.iter().find(|s| s.user_id == user.id) {
+                    Some(stream) => Status::Live(stream),
+                    None => Status::Offline(self.last_broadcast(&user.id).await?),
+                };
+                Ok::<_, plugin_core::Error>(status::describe(
+                    user.display_name.as_str(),
+                    &status,
+                    now,
+                ))
+            }
+        }))
+        .await?;
+        Ok(statuses.join(" − "))
+    }
 
-        futures::stream::iter(subs_to_delete)
-            .map(Ok)
-            .try_for_each_concurrent(5, |s| async move {
-                self.delete_subscription(s).await?;
-                Ok::<(), anyhow::Error>(())
+    /// The last past broadcast twitch kept
+    async fn last_broadcast(&self, user_id: &UserId) -> Result<Option<Video>> {
+        let req = videos::GetVideosRequest::builder()
+            .user_id(Some(user_id.clone()))
+            .type_(Some(videos::VideoTypeFilter::Archive))
+            .first(Some(1))
+            .build();
+        let ctx = format!("Can't get the videos of {user_id}");
+        let resp = self
+            .token
+            .with_token(&ctx, |token| {
+                let req = req.clone();
+                async move { self.client.req_get(req, &token).await }
             })
             .await?;
+        Ok(resp.data.into_iter().next())
+    }
+
+    /// Converges the subscriptions to stream.online and stream.offline with the
+    /// followed streams: the missing ones are created, the failed ones and the ones
+    /// of streams no longer followed are deleted
+    async fn sync_subscriptions(&self) -> Result<()> {
+        let _subscribing = self.subscribing.lock().await;
+        let subs = self.list_subscriptions().await?;
 
-        let subs = subs
+        let logins = self
+            .followed
+            .streams()
             .into_iter()
-            .filter(|s| s.is_valid())
+            .map(|u| u.nickname)
+            .collect::<Vec<_>>();
+        log::info!("Syncing subscription for users {:?}", logins);
+
+        let wanted = self
+            .get_users(logins, vec![])
+            .await?
+            .into_iter()
+            .map(|u| Wanted {
+                user_id: u.id,
+                login: u.login,
+            })
             .collect::<Vec<_>>();
 
-        futures::stream::iter(users)
-            .map(Ok)
-            .try_for_each_concurrent(5, |u| {
-                let subs = &subs;
+        let (deletes, creates): (Vec<_>, Vec<_>) = subscriptions::reconcile(&subs, &wanted)
+            .into_iter()
+            .partition(|change| matches!(change, Change::Delete(_)));
+        if deletes.is_empty() && creates.is_empty() {
+            log::info!("Subscriptions already in sync");
+        }
+
+        // deleted first, a failed subscription may conflict with its replacement
+        for changes in [deletes, creates] {
+            futures::stream::iter(changes)
+                .map(Ok)
+                .try_for_each_concurrent(5, |change| async move {
+                    match change {
+                        Change::Delete(sub) => self.delete_subscription(sub).await,
+                        Change::Create(wanted, event) => {
+                            self.create_subscription(wanted, event).await
+                        }
+                    }
+                })
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Never returns. Twitch drops the subscriptions when the callback misbehaves,
+    /// so they are synced again every now and then, sooner after a failure.
+    async fn reconcile_periodically(&self) {
+        let mut backoff = Backoff::default();
+        let mut delay = subscriptions::RECONCILE_EVERY;
+        loop {
+            tokio::time::sleep(delay).await;
+            delay = match self.sync_subscriptions().await {
+                Ok(()) => backoff.succeeded(),
+                Err(err) => {
+                    let delay = backoff.failed();
+                    log::error!(
+                        "Cannot sync the subscriptions, trying again in {delay:?}: {err:?}"
+                    );
+                    delay
+                }
+            };
+        }
+    }
+
+    /// Never returns unless the messages can't be sent anymore. Asks helix which
+    /// followed streams are live, and handles what changed since the last time
+    /// like the webhook notifications.
+    async fn poll_periodically(&self, tx: &mpsc::Sender<Outbound>) -> Result<()> {
+        let every = self.config.poll_interval()?;
+        loop {
+            tokio::time::sleep(every).await;
+            let current = match self.poll_live_streams().await {
+                Ok(current) => current,
+                Err(err) => {
+                    log::error!("Cannot poll the live streams, trying again in {every:?}: {err:?}");
+                    continue;
+                }
+            };
+            let previous = self
+                .state
+                .online_streams
+                .lock()
+                .expect("twitch state lock")
+                .clone();
+            for event in poll::diff(&previous, &current) {
+                match event {
+                    PollEvent::Offline(login) => self.on_stream_offline(tx, login).await?,
+                    PollEvent::Online(stream) => {
+                        let target = self
+                            .followed
+                            .streams()
+                            .into_iter()
+                            .find(|s| s.nickname == stream.user_login);
+                        if let Some(target) = target {
+                            self.stream_online(tx, &target, stream.clone()).await?;
+                        }
+                    }
+                    PollEvent::Update(login, info) => {
+                        self.on_channel_update(tx, login, info).await?
+                    }
+                }
+            }
+        }
+    }
+
+    /// The live followed streams, by batches of `poll::BATCH_SIZE`, waiting
+    /// between them when helix says few requests are left
+    async fn poll_live_streams(&self) -> Result<HashMap<Nickname, Stream>> {
+        let logins = self
+            .followed
+            .streams()
+            .into_iter()
+            .map(|s| s.nickname)
+            .collect::<Vec<_>>();
+        let mut live = HashMap::new();
+        for batch in logins.chunks(poll::BATCH_SIZE) {
+            let (streams, rate_limit) = self.poll_batch(batch).await?;
+            live.extend(streams.into_iter().map(|s| (s.user_login.clone(), s)));
+            let wait = rate_limit.and_then(|r| r.wait(time::OffsetDateTime::now_utc()));
+            if let Some(wait) = wait {
+                log::warn!("Few helix requests left, waiting {wait:?}");
+                tokio::time::sleep(wait).await;
+            }
+        }
+        Ok(live)
+    }
+
+    /// GET /helix/streams without twitch_api2, which doesn't give the
+    /// Ratelimit-* headers back
+    async fn poll_batch(&self, logins: &[Nickname]) -> Result<(Vec<Stream>, Option<RateLimit>)> {
+        #[derive(serde::Deserialize)]
+        struct Page {
+            data: Vec<Stream>,
+        }
+
+        let query = logins
+            .iter()
+            .map(|login| ("user_login", login.as_str()))
+            .chain([("first", "100")])
+            .collect::<Vec<_>>();
+        let (body, rate_limit) = self
+            .token
+            .with_token("Can't poll the live streams", |token| {
+                let request = self
+                    .http
+                    .get("https://api.twitch.tv/helix/streams")
+                    .query(&query)
+                    .header("Client-Id", self.config.client_id.as_str())
+                    .bearer_auth(token.token().secret());
                 async move {
-                    self.sync_user_subscription(subs, u).await?;
-                    Ok::<(), anyhow::Error>(())
+                    let resp = check(request.send().await?).await?;
+                    let rate_limit = RateLimit::from_headers(resp.headers());
+                    Ok::<_, HelixError>((resp.bytes().await?, rate_limit))
                 }
             })
             .await?;
-
-        Ok(())
+        let page: Page =
+            serde_json::from_slice(&body).context("Unexpected helix GET /streams response")?;
+        Ok((page.data, rate_limit))
     }
 
     pub async fn get_users(&self, nicks: Vec<Nickname>, ids: Vec<UserId>) -> Result<Vec<User>> {
@@ -429,13 +1165,12 @@ impl Twitch {
             .login(nicks)
             .build();
         let user_resp = self
-            .client
-            .req_get(req, self.token.get())
-            .await
-            .map_err(|e| plugin_core::Error::Wrapped {
-                source: Box::new(e),
-                ctx: "cannot list subscriptions".to_string(),
-            })?;
+            .token
+            .with_token("cannot get users", |token| {
+                let req = req.clone();
+                async move { self.client.req_get(req, &token).await }
+            })
+            .await?;
 
         Ok(user_resp.data)
     }
@@ -443,16 +1178,12 @@ impl Twitch {
     pub async fn list_subscriptions(&self) -> Result<Vec<Subscription>> {
         // TODO: handle pagination
         let resp = self
-            .client
-            .req_get(
-                helix::eventsub::GetEventSubSubscriptionsRequest::builder().build(),
-                self.token.get(),
-            )
-            .await
-            .map_err(|e| plugin_core::Error::Wrapped {
-                source: Box::new(e),
-                ctx: "cannot list subscriptions".to_string(),
-            })?;
+            .token
+            .with_token("cannot list subscriptions", |token| async move {
+                let req = helix::eventsub::GetEventSubSubscriptionsRequest::builder().build();
+                self.client.req_get(req, &token).await
+            })
+            .await?;
         // dbg!(&resp);
 
         let subs = resp
@@ -482,68 +1213,48 @@ impl Twitch {
 
     async fn delete_subscription(&self, sub: &Subscription) -> Result<()> {
         log::info!("Deleting subscription {:?}", sub);
-        self.client
-            .req_delete(
-                helix::eventsub::DeleteEventSubSubscriptionRequest::builder()
+        let ctx = format!("Failed to delete subscription {:?}", sub);
+        self.token
+            .with_token(&ctx, |token| async move {
+                let req = helix::eventsub::DeleteEventSubSubscriptionRequest::builder()
                     .id(sub.id.clone())
-                    .build(),
-                self.token.get(),
-            )
-            .await
-            .map_err(|e| plugin_core::Error::Wrapped {
-                source: Box::new(e),
-                ctx: format!("Failed to delete subscription {:?}", sub),
-            })?;
+                    .build();
+                self.client.req_delete(req, &token).await
+            })
+            .await?;
 
         Ok(())
     }
 
-    /// Ensure we're subscribed to the given user's stream.{online,offline} events
-    async fn sync_user_subscription(&self, subs: &[Subscription], user: User) -> Result<()> {
-        let sub_online = subs
-            .iter()
-            .find(|s| s.user_id == user.id && matches!(s.type_, EventType::StreamOnline));
-        match sub_online {
-            Some(_) => log::info!(
-                "stream online subscription already exists for user_login {}",
-                user.login
-            ),
-            None => {
+    async fn create_subscription(&self, wanted: &Wanted, event: Event) -> Result<()> {
+        let user_id = wanted.user_id.clone();
+        let subscribed = match event {
+            Event::Online => {
                 let event = StreamOnlineV1::builder()
-                    .broadcaster_user_id(user.id.clone())
+                    .broadcaster_user_id(user_id)
                     .build();
-                self.subscribe(event).await.with_context(|| {
-                    format!(
-                        "failed to create stream.online subscription for (user_id, user_name) ({}, {})",
-                        user.id, user.login
-                    )
-                })?;
-                log::info!("Subscribed stream.online for channel {}", user.login);
+                self.subscribe(event).await
             }
-        };
-
-        let sub_offline = subs
-            .iter()
-            .find(|s| s.user_id == user.id && matches!(s.type_, EventType::StreamOffline));
-        match sub_offline {
-            Some(_) => log::info!(
-                "stream offline subscription already exists for user_login {}",
-                user.login
-            ),
-            None => {
+            Event::Offline => {
                 let event = StreamOfflineV1::builder()
-                    .broadcaster_user_id(user.id.clone())
+                    .broadcaster_user_id(user_id)
                     .build();
-                self.subscribe(event).await.with_context(|| {
-                    format!(
-                        "failed to create stream.offline subscription for (user_id, user_name) ({}, {})",
-                        user.id, user.login
-                    )
-                })?;
-                log::info!("Subscribed stream.offline for channel {}", user.login);
+                self.subscribe(event).await
+            }
+            Event::Update => {
+                let event = ChannelUpdateV1::builder()
+                    .broadcaster_user_id(user_id)
+                    .build();
+                self.subscribe(event).await
             }
         };
-
+        subscribed.with_context(|| {
+            format!(
+                "failed to create {event:?} subscription for (user_id, user_name) ({}, {})",
+                wanted.user_id, wanted.login
+            )
+        })?;
+        log::info!("Subscribed {event:?} for channel {}", wanted.login);
         Ok(())
     }
 
@@ -556,29 +1267,26 @@ impl Twitch {
         &self,
         event: E,
     ) -> Result<()> {
-        let sub_body = helix::eventsub::CreateEventSubSubscriptionBody::builder()
-            .subscription(event.clone())
-            .transport(
-                eventsub::Transport::builder()
-                    .method(eventsub::TransportMethod::Webhook)
-                    .callback(self.config.callback_uri.0.clone())
-                    .secret(self.config.app_secret.clone())
-                    .build(),
-            )
-            .build();
-
-        self.client
-            .req_post(
-                helix::eventsub::CreateEventSubSubscriptionRequest::builder().build(),
-                sub_body,
-                self.token.get(),
-            )
-            // treat a conflict as a crash there
-            .await
-            .map_err(|e| plugin_core::Error::Wrapped {
-                source: Box::new(e),
-                ctx: format!("Failed to subscribe with event {event:?}"),
-            })?;
+        let ctx = format!("Failed to subscribe with event {event:?}");
+        // treat a conflict as a crash there
+        self.token
+            .with_token_once(&ctx, |token| {
+                let sub_body = helix::eventsub::CreateEventSubSubscriptionBody::builder()
+                    .subscription(event.clone())
+                    .transport(
+                        eventsub::Transport::builder()
+                            .method(eventsub::TransportMethod::Webhook)
+                            .callback(self.config.callback_uri.0.clone())
+                            .secret(self.config.app_secret.clone())
+                            .build(),
+                    )
+                    .build();
+                async move {
+                    let req = helix::eventsub::CreateEventSubSubscriptionRequest::builder().build();
+                    self.client.req_post(req, sub_body, &token).await
+                }
+            })
+            .await?;
 
         Ok(())
     }
@@ -620,8 +1328,8 @@ impl Twitch {
         // twitch nicknames as sent in the webhook events have casing
         // but the login nicknames otherwise don't
         let twitch_nick = twitch_nick.to_lowercase();
-        self.config
-            .watched_streams
+        self.followed
+            .streams()
             .iter()
             .find_map(|s| {
                 if s.nickname.as_str() == twitch_nick {
@@ -633,3 +1341,233 @@ impl Twitch {
             .unwrap_or_else(|| twitch_nick.to_string())
     }
 }
+
+#[async_trait]
+impl FollowApi for Twitch {
+    async fn user_id(&self, login: &str) -> Result<Option<UserId>> {
+        let users = self
+            .get_users(vec![Nickname::new(login.to_string())], vec![])
+            .await?;
+        Ok(users.into_iter().next().map(|u| u.id))
+    }
+
+    async fn subscribe_stream(&self, user_id: &UserId) -> Result<()> {
+        // polled along with the others
+        if self.config.mode == Mode::Poll {
+            return Ok(());
+        }
+        let online = StreamOnlineV1::builder()
+            .broadcaster_user_id(user_id.clone())
+            .build();
+        self.subscribe(online).await.with_context(|| {
+            format!("failed to create stream.online subscription for user_id {user_id}")
+        })?;
+        let offline = StreamOfflineV1::builder()
+            .broadcaster_user_id(user_id.clone())
+            .build();
+        self.subscribe(offline).await.with_context(|| {
+            format!("failed to create stream.offline subscription for user_id {user_id}")
+        })?;
+        let update = ChannelUpdateV1::builder()
+            .broadcaster_user_id(user_id.clone())
+            .build();
+        self.subscribe(update).await.with_context(|| {
+            format!("failed to create channel.update subscription for user_id {user_id}")
+        })?;
+        log::info!(
+            "Subscribed stream.online, stream.offline and channel.update for user_id {user_id}"
+        );
+        Ok(())
+    }
+
+    async fn unsubscribe_stream(&self, user_id: &UserId) -> Result<()> {
+        if self.config.mode == Mode::Poll {
+            return Ok(());
+        }
+        let subs = self.list_subscriptions().await?;
+        for sub in subs.iter().filter(|s| &s.user_id == user_id) {
+            self.delete_subscription(sub).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Like `🔴 Geek est en live : Rust & chill [Science & Technology] (1 234 viewers) — https://twitch.tv/geek`,
+/// with `[18+]` after the category for mature streams, and `depuis 25 min` in
+/// the parentheses when announced late
+fn live_announcement(stream: &Stream, now: time::OffsetDateTime) -> String {
+    let category = match stream.game_name.to_string() {
+        category if category.is_empty() => "".to_string(),
+        category => format!(" [{category}]"),
+    };
+    let uptime = match live_for(stream, now) {
+        Some(uptime) if uptime >= LATE_ANNOUNCEMENT => {
+            Some(format!("depuis {}", format_uptime(uptime)))
+        }
+        _ => None,
+    };
+    let details = match [uptime, viewers(stream)]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+    {
+        details if details.is_empty() => "".to_string(),
+        details => format!(" ({})", details.join(", ")),
+    };
+    format!(
+        "🔴 {} est en live : {}{category}{}{details} — https://twitch.tv/{}",
+        stream.user_name,
+        stream.title.trim(),
+        mature_marker(stream),
+        stream.user_login
+    )
+}
+
+/// Like `1 234 viewers`, None right after going live with nobody yet
+pub(crate) fn viewers(stream: &Stream) -> Option<String> {
+    match stream.viewer_count {
+        0 => None,
+        count => Some(format!("{} viewers", numbers::format_count(count as u64))),
+    }
+}
+
+pub(crate) fn mature_marker(stream: &Stream) -> &'static str {
+    if stream.is_mature {
+        " [18+]"
+    } else {
+        ""
+    }
+}
+
+/// The channels where the stream gets announced, without the family friendly
+/// ones when it is for mature audiences
+fn announced_in(channels: &[String], is_mature: bool, family_friendly: &[String]) -> Vec<String> {
+    channels
+        .iter()
+        .filter(|c| !is_mature || !family_friendly.iter().any(|f| f.eq_ignore_ascii_case(c)))
+        .cloned()
+        .collect()
+}
+
+/// None when twitch gives a start in the future, or not a valid one
+pub(crate) fn live_for(stream: &Stream, now: time::OffsetDateTime) -> Option<Duration> {
+    (now - stream_start(stream)?).try_into().ok()
+}
+
+fn stream_start(stream: &Stream) -> Option<time::OffsetDateTime> {
+    time::OffsetDateTime::parse(
+        stream.started_at.as_str(),
+        &time::format_description::well_known::Rfc3339,
+    )
+    .ok()
+}
+
+/// Like `25 min` or `2 h 05`
+pub(crate) fn format_uptime(uptime: Duration) -> String {
+    let minutes = uptime.as_secs() / 60;
+    match minutes {
+        m if m < 60 => format!("{m} min"),
+        m => format!("{} h {:02}", m / 60, m % 60),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use time::macros::datetime;
+
+    /// An entry of the data of a helix GET /streams response
+    fn stream(game_name: &str) -> Stream {
+        let json = format!(
+            r#"{{
+                "id": "40952121085",
+                "user_id": "101051819",
+                "user_login": "geekingfrog",
+                "user_name": "Geekingfrog",
+                "game_id": "{}",
+                "game_name": "{game_name}",
+                "type": "live",
+                "title": "Rust & chill ",
+                "viewer_count": 42,
+                "started_at": "2024-05-01T20:00:00Z",
+                "language": "fr",
+                "thumbnail_url": "https://static-cdn.jtvnw.net/previews-ttv/live_user_geekingfrog-{{width}}x{{height}}.jpg",
+                "tag_ids": [],
+                "is_mature": false
+            }}"#,
+            if game_name.is_empty() { "" } else { "509670" }
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_live_announcement() {
+        let stream = stream("Science & Technology");
+        assert_eq!(
+            live_announcement(&stream, datetime!(2024-05-01 20:01 UTC)),
+            "🔴 Geekingfrog est en live : Rust & chill [Science & Technology] (42 viewers) — https://twitch.tv/geekingfrog",
+            "right away"
+        );
+        assert_eq!(
+            live_announcement(&stream, datetime!(2024-05-01 20:25 UTC)),
+            "🔴 Geekingfrog est en live : Rust & chill [Science & Technology] (depuis 25 min, 42 viewers) — https://twitch.tv/geekingfrog",
+            "caught up later"
+        );
+        assert_eq!(
+            live_announcement(&stream, datetime!(2024-05-01 22:05 UTC)),
+            "🔴 Geekingfrog est en live : Rust & chill [Science & Technology] (depuis 2 h 05, 42 viewers) — https://twitch.tv/geekingfrog"
+        );
+    }
+
+    #[test]
+    fn test_live_announcement_without_category() {
+        assert_eq!(
+            live_announcement(&stream(""), datetime!(2024-05-01 20:25 UTC)),
+            "🔴 Geekingfrog est en live : Rust & chill (depuis 25 min, 42 viewers) — https://twitch.tv/geekingfrog"
+        );
+    }
+
+    #[test]
+    fn test_live_announcement_viewers() {
+        let mut stream = stream("Science & Technology");
+        stream.viewer_count = 0;
+        assert_eq!(
+            live_announcement(&stream, datetime!(2024-05-01 20:01 UTC)),
+            "🔴 Geekingfrog est en live : Rust & chill [Science & Technology] — https://twitch.tv/geekingfrog",
+            "nobody yet"
+        );
+        stream.viewer_count = 1234567;
+        stream.is_mature = true;
+        assert_eq!(
+            live_announcement(&stream, datetime!(2024-05-01 20:01 UTC)),
+            "🔴 Geekingfrog est en live : Rust & chill [Science & Technology] [18+] (1\u{2009}234\u{2009}567 viewers) — https://twitch.tv/geekingfrog"
+        );
+    }
+
+    #[test]
+    fn test_family_friendly() {
+        let channels = vec!["##arch-fr-free".to_string(), "#kids".to_string()];
+        let family_friendly = vec!["#Kids".to_string()];
+        assert_eq!(
+            announced_in(&channels, true, &family_friendly),
+            vec!["##arch-fr-free"]
+        );
+        assert_eq!(announced_in(&channels, false, &family_friendly), channels);
+        assert_eq!(announced_in(&channels, true, &[]), channels);
+    }
+
+    #[test]
+    fn test_live_for() {
+        let stream = stream("");
+        assert_eq!(
+            live_for(&stream, datetime!(2024-05-01 20:25 UTC)),
+            Some(Duration::from_secs(25 * 60))
+        );
+        assert_eq!(
+            live_for(&stream, datetime!(2024-05-01 19:59 UTC)),
+            None,
+            "clock skew"
+        );
+    }
+}
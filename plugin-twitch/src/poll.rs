@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use twitch_api2::{helix::streams::Stream, types::Nickname};
+
+use crate::changes::ChannelInfo;
+
+/// The most logins helix takes in a single GET /streams
+pub const BATCH_SIZE: usize = 100;
+
+/// What changed between two polls, as the webhook notifications would tell
+#[derive(Debug)]
+pub enum PollEvent<'a> {
+    Online(&'a Stream),
+    Offline(&'a Nickname),
+    Update(&'a Nickname, ChannelInfo),
+}
+
+fn info(stream: &Stream) -> ChannelInfo {
+    ChannelInfo {
+        title: stream.title.clone(),
+        category: stream.game_name.to_string(),
+    }
+}
+
+/// The streams gone offline first, then the ones gone live, then the title and
+/// category changes, by login. A new stream id means it went offline and back
+/// between the polls.
+pub fn diff<'a>(
+    previous: &'a HashMap<Nickname, Stream>,
+    current: &'a HashMap<Nickname, Stream>,
+) -> Vec<PollEvent<'a>> {
+    let mut offline = previous
+        .iter()
+        .filter(|(login, stream)| match current.get(*login) {
+            Some(now) => now.id != stream.id,
+            None => true,
+        })
+        .map(|(login, _)| login)
+        .collect::<Vec<_>>();
+    offline.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+    let mut online = vec![];
+    let mut updates = vec![];
+    for (login, stream) in current {
+        match previous.get(login) {
+            Some(before) if before.id == stream.id => {
+                if info(before) != info(stream) {
+                    updates.push((login, info(stream)));
+                }
+            }
+            _ => online.push(stream),
+        }
+    }
+    online.sort_by(|a, b| a.user_login.as_str().cmp(b.user_login.as_str()));
+    updates.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+
+    offline
+        .into_iter()
+        .map(PollEvent::Offline)
+        .chain(online.into_iter().map(PollEvent::Online))
+        .chain(
+            updates
+                .into_iter()
+                .map(|(login, info)| PollEvent::Update(login, info)),
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// An entry of the data of a helix GET /streams response
+    fn stream(login: &str, id: &str, title: &str) -> Stream {
+        let json = format!(
+            r#"{{
+                "id": "{id}",
+                "user_id": "101051819",
+                "user_login": "{login}",
+                "user_name": "{login}",
+                "game_id": "509670",
+                "game_name": "Science & Technology",
+                "type": "live",
+                "title": "{title}",
+                "viewer_count": 42,
+                "started_at": "2024-05-01T20:00:00Z",
+                "language": "fr",
+                "thumbnail_url": "https://static-cdn.jtvnw.net/previews-ttv/live_user_{login}-{{width}}x{{height}}.jpg",
+                "tag_ids": [],
+                "is_mature": false
+            }}"#
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn snapshot(streams: Vec<Stream>) -> HashMap<Nickname, Stream> {
+        streams
+            .into_iter()
+            .map(|s| (s.user_login.clone(), s))
+            .collect()
+    }
+
+    fn describe(events: Vec<PollEvent>) -> Vec<String> {
+        events
+            .into_iter()
+            .map(|event| match event {
+                PollEvent::Online(stream) => format!("online {} {}", stream.user_login, stream.id),
+                PollEvent::Offline(login) => format!("offline {login}"),
+                PollEvent::Update(login, info) => format!("update {login} {}", info.title),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let previous = snapshot(vec![
+            stream("geekingfrog", "1", "Rust & chill"),
+            stream("chouhartem", "2", "Coq"),
+            stream("artart78", "3", "Speedrun"),
+            stream("gikiam", "4", "Factorio"),
+        ]);
+        let current = snapshot(vec![
+            stream("geekingfrog", "1", "Rust & chill"),
+            stream("chouhartem", "2", "Coq, la suite"),
+            stream("gikiam", "5", "Factorio"),
+            stream("vertbrocoli", "6", "OCaml"),
+        ]);
+        assert_eq!(
+            describe(diff(&previous, &current)),
+            vec![
+                "offline artart78",
+                "offline gikiam",
+                "online gikiam 5",
+                "online vertbrocoli 6",
+                "update chouhartem Coq, la suite",
+            ]
+        );
+        assert_eq!(describe(diff(&current, &current)), Vec::<String>::new());
+        assert_eq!(
+            describe(diff(&HashMap::new(), &previous)),
+            vec![
+                "online artart78 3",
+                "online chouhartem 2",
+                "online geekingfrog 1",
+                "online gikiam 4",
+            ]
+        );
+    }
+}
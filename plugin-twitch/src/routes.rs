@@ -0,0 +1,113 @@
+use serde::Deserialize;
+
+/// The login of a route taking every followed stream
+pub const WILDCARD: &str = "*";
+
+/// Which streams get announced in an irc channel
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Route {
+    pub irc_channel: String,
+    pub logins: Vec<String>,
+}
+
+impl Route {
+    pub fn new(irc_channel: &str, login: &str) -> Self {
+        Route {
+            irc_channel: irc_channel.to_string(),
+            logins: vec![login.to_string()],
+        }
+    }
+
+    fn takes(&self, login: &str) -> bool {
+        self.logins
+            .iter()
+            .any(|l| l == WILDCARD || l.eq_ignore_ascii_case(login))
+    }
+}
+
+/// The channels where the stream is announced, in the order of the routes,
+/// each of them once
+pub fn channels_for(login: &str, routes: &[Route]) -> Vec<String> {
+    let mut channels: Vec<String> = vec![];
+    for route in routes.iter().filter(|r| r.takes(login)) {
+        if !channels
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(&route.irc_channel))
+        {
+            channels.push(route.irc_channel.clone());
+        }
+    }
+    channels
+}
+
+/// The logins named by the routes, without the wildcard
+pub fn logins(routes: &[Route]) -> impl Iterator<Item = &str> {
+    routes
+        .iter()
+        .flat_map(|r| r.logins.iter())
+        .map(|l| l.as_str())
+        .filter(|l| *l != WILDCARD)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn route(irc_channel: &str, logins: &[&str]) -> Route {
+        Route {
+            irc_channel: irc_channel.to_string(),
+            logins: logins.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_channels_for() {
+        let routes = vec![
+            route("#rust", &["geekingfrog", "coucou"]),
+            route("#haskell", &["geekingfrog"]),
+            route("#ocaml", &["shroud"]),
+        ];
+        assert_eq!(
+            channels_for("geekingfrog", &routes),
+            vec!["#rust", "#haskell"]
+        );
+        assert_eq!(channels_for("Coucou", &routes), vec!["#rust"]);
+        assert_eq!(channels_for("nobody", &routes), Vec::<String>::new());
+        assert_eq!(channels_for("nobody", &[]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_channels_for_overlapping() {
+        let routes = vec![
+            route("#rust", &["geekingfrog"]),
+            route("#haskell", &["geekingfrog"]),
+            route("#Rust", &["geekingfrog", "coucou"]),
+            route("#rust", &["geekingfrog"]),
+        ];
+        assert_eq!(
+            channels_for("geekingfrog", &routes),
+            vec!["#rust", "#haskell"],
+            "once per channel"
+        );
+        assert_eq!(channels_for("coucou", &routes), vec!["#Rust"]);
+    }
+
+    #[test]
+    fn test_channels_for_wildcard() {
+        let routes = vec![
+            route("#rust", &["geekingfrog"]),
+            route("#twitch", &["*"]),
+            route("#haskell", &["coucou", "*"]),
+        ];
+        assert_eq!(
+            channels_for("geekingfrog", &routes),
+            vec!["#rust", "#twitch", "#haskell"]
+        );
+        assert_eq!(channels_for("nobody", &routes), vec!["#twitch", "#haskell"]);
+        assert_eq!(
+            logins(&routes).collect::<Vec<_>>(),
+            vec!["geekingfrog", "coucou"]
+        );
+    }
+}
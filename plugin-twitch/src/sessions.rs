@@ -0,0 +1,126 @@
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use plugin_core::{Database, Result};
+use std::time::Duration;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::db;
+
+#[derive(QueryableByName)]
+struct Row {
+    #[sql_type = "Text"]
+    started_at: String,
+}
+
+/// When the live streams started, kept in the database to tell
+/// how long they lasted even after a restart
+pub struct Sessions {
+    db: Database,
+}
+
+impl Sessions {
+    pub fn new(db: Database) -> Result<Self> {
+        db::ensure_schema(&db)?;
+        Ok(Sessions { db })
+    }
+
+    /// Replaces the previous one, whose end was missed
+    pub fn start(&self, login: &str, started_at: OffsetDateTime) -> Result<()> {
+        let started_at = started_at.format(&Rfc3339).expect("RFC3339 date");
+        self.db.with_connection(|conn| {
+            diesel::sql_query(
+                "INSERT OR REPLACE INTO twitch_sessions (login, started_at) VALUES (?, ?)",
+            )
+            .bind::<Text, _>(login)
+            .bind::<Text, _>(started_at)
+            .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// When the session started, None when its start was missed
+    pub fn end(&self, login: &str) -> Result<Option<OffsetDateTime>> {
+        let rows = self.db.with_connection(|conn| {
+            let rows = diesel::sql_query("SELECT started_at FROM twitch_sessions WHERE login = ?")
+                .bind::<Text, _>(login)
+                .load::<Row>(conn)?;
+            diesel::sql_query("DELETE FROM twitch_sessions WHERE login = ?")
+                .bind::<Text, _>(login)
+                .execute(conn)?;
+            Ok(rows)
+        })?;
+        Ok(rows
+            .into_iter()
+            .next()
+            .and_then(|row| OffsetDateTime::parse(&row.started_at, &Rfc3339).ok()))
+    }
+}
+
+/// None when the clocks disagree, and it ended before it started
+pub fn session_length(started_at: OffsetDateTime, ended_at: OffsetDateTime) -> Option<Duration> {
+    (ended_at - started_at).try_into().ok()
+}
+
+/// Like `3h12` or `25 min`
+pub fn format_length(length: Duration) -> String {
+    let minutes = length.as_secs() / 60;
+    match minutes {
+        m if m < 60 => format!("{m} min"),
+        m => format!("{}h{:02}", m / 60, m % 60),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_length_across_restart() {
+        let db = Database::in_memory().unwrap();
+        let sessions = Sessions::new(db.clone()).unwrap();
+        sessions
+            .start("geekingfrog", datetime!(2024-05-01 20:00 UTC))
+            .unwrap();
+        drop(sessions);
+
+        let restarted = Sessions::new(db).unwrap();
+        let started_at = restarted.end("geekingfrog").unwrap().unwrap();
+        let length = session_length(started_at, datetime!(2024-05-01 23:12:30 UTC)).unwrap();
+        assert_eq!(format_length(length), "3h12");
+        assert_eq!(restarted.end("geekingfrog").unwrap(), None, "ended once");
+    }
+
+    #[test]
+    fn test_start_again() {
+        let sessions = Sessions::new(Database::in_memory().unwrap()).unwrap();
+        sessions
+            .start("geekingfrog", datetime!(2024-05-01 20:00 UTC))
+            .unwrap();
+        // the end of the first one was missed
+        sessions
+            .start("geekingfrog", datetime!(2024-05-02 20:00 UTC))
+            .unwrap();
+        assert_eq!(
+            sessions.end("geekingfrog").unwrap(),
+            Some(datetime!(2024-05-02 20:00 UTC))
+        );
+        assert_eq!(sessions.end("chouhartem").unwrap(), None);
+    }
+
+    #[test]
+    fn test_format_length() {
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        assert_eq!(format_length(minutes(25)), "25 min");
+        assert_eq!(format_length(minutes(60)), "1h00");
+        assert_eq!(format_length(minutes(192)), "3h12");
+        assert_eq!(
+            session_length(
+                datetime!(2024-05-01 20:00 UTC),
+                datetime!(2024-05-01 19:00 UTC)
+            ),
+            None
+        );
+    }
+}
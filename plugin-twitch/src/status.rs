@@ -0,0 +1,189 @@
+use twitch_api2::helix::{streams::Stream, users::User, videos::Video};
+
+use crate::plugin::{format_uptime, live_for, mature_marker, viewers};
+
+/// What λtwitch status tells about a stream
+#[derive(Debug)]
+pub enum Status<'a> {
+    Live(&'a Stream),
+    /// with the last past broadcast, if any was kept
+    Offline(Option<Video>),
+}
+
+/// Like `Geekingfrog 🔴 live: Rust & chill [Science & Technology] depuis 25 min, 1 234 viewers`,
+/// with `[18+]` after the category for mature streams
+/// or `Geekingfrog offline (dernier live il y a 3 jours : Rust & chill)`
+pub fn describe(name: &str, status: &Status, now: time::OffsetDateTime) -> String {
+    match status {
+        Status::Live(stream) => {
+            let category = match stream.game_name.to_string() {
+                category if category.is_empty() => "".to_string(),
+                category => format!(" [{category}]"),
+            };
+            let uptime = live_for(stream, now)
+                .map(|uptime| format!(" depuis {}", format_uptime(uptime)))
+                .unwrap_or_default();
+            let viewers = viewers(stream)
+                .map(|viewers| format!(", {viewers}"))
+                .unwrap_or_default();
+            format!(
+                "{name} 🔴 live: {}{category}{}{uptime}{viewers}",
+                stream.title.trim(),
+                mature_marker(stream),
+            )
+        }
+        Status::Offline(None) => format!("{name} offline"),
+        Status::Offline(Some(video)) => {
+            let when = match days_since(video, now) {
+                Some(0) => " il y a moins d'un jour".to_string(),
+                Some(1) => " il y a 1 jour".to_string(),
+                Some(days) => format!(" il y a {days} jours"),
+                None => "".to_string(),
+            };
+            format!(
+                "{name} offline (dernier live{when} : {})",
+                video.title.trim()
+            )
+        }
+    }
+}
+
+/// None when twitch gives a date in the future, or not a valid one
+fn days_since(video: &Video, now: time::OffsetDateTime) -> Option<u64> {
+    let created_at = time::OffsetDateTime::parse(
+        video.created_at.as_str(),
+        &time::format_description::well_known::Rfc3339,
+    )
+    .ok()?;
+    (now - created_at).whole_days().try_into().ok()
+}
+
+/// The requested logins twitch doesn't know about
+pub fn unknown_logins<'a>(requested: &'a [String], users: &[User]) -> Vec<&'a str> {
+    requested
+        .iter()
+        .filter(|login| !users.iter().any(|u| u.login.as_str() == login.as_str()))
+        .map(|login| login.as_str())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use time::macros::datetime;
+
+    /// An entry of the data of a helix GET /streams response
+    const STREAM: &str = r#"{
+        "id": "40952121085",
+        "user_id": "101051819",
+        "user_login": "geekingfrog",
+        "user_name": "Geekingfrog",
+        "game_id": "509670",
+        "game_name": "Science & Technology",
+        "type": "live",
+        "title": "Rust & chill",
+        "viewer_count": 42,
+        "started_at": "2024-05-01T20:00:00Z",
+        "language": "fr",
+        "thumbnail_url": "https://static-cdn.jtvnw.net/previews-ttv/live_user_geekingfrog-{width}x{height}.jpg",
+        "tag_ids": [],
+        "is_mature": false
+    }"#;
+
+    /// An entry of the data of a helix GET /videos?type=archive response
+    const VIDEO: &str = r#"{
+        "id": "335921245",
+        "stream_id": "40952121085",
+        "user_id": "101051819",
+        "user_login": "geekingfrog",
+        "user_name": "Geekingfrog",
+        "title": "Rust & chill ",
+        "description": "",
+        "created_at": "2024-05-01T20:00:00Z",
+        "published_at": "2024-05-01T20:00:00Z",
+        "url": "https://www.twitch.tv/videos/335921245",
+        "thumbnail_url": "https://static-cdn.jtvnw.net/cf_vods/d2nvs31859zcd8/twitchdev/335921245/thumb/thumb0-%{width}x%{height}.jpg",
+        "viewable": "public",
+        "view_count": 1863,
+        "language": "fr",
+        "type": "archive",
+        "duration": "3h8m33s",
+        "muted_segments": null
+    }"#;
+
+    /// An entry of the data of a helix GET /users response
+    const USER: &str = r#"{
+        "id": "101051819",
+        "login": "geekingfrog",
+        "display_name": "Geekingfrog",
+        "type": "",
+        "broadcaster_type": "",
+        "description": "",
+        "profile_image_url": "https://static-cdn.jtvnw.net/jtv_user_pictures/geekingfrog.png",
+        "offline_image_url": "",
+        "view_count": 0,
+        "created_at": "2015-09-13T12:34:56Z"
+    }"#;
+
+    #[test]
+    fn test_live() {
+        let stream: Stream = serde_json::from_str(STREAM).unwrap();
+        assert_eq!(
+            describe(
+                "Geekingfrog",
+                &Status::Live(&stream),
+                datetime!(2024-05-01 22:05 UTC)
+            ),
+            "Geekingfrog 🔴 live: Rust & chill [Science & Technology] depuis 2 h 05, 42 viewers"
+        );
+    }
+
+    #[test]
+    fn test_live_mature() {
+        let mut stream: Stream = serde_json::from_str(STREAM).unwrap();
+        stream.is_mature = true;
+        stream.viewer_count = 12345;
+        let now = datetime!(2024-05-01 22:05 UTC);
+        assert_eq!(
+            describe("Geekingfrog", &Status::Live(&stream), now),
+            "Geekingfrog 🔴 live: Rust & chill [Science & Technology] [18+] depuis 2 h 05, 12\u{2009}345 viewers"
+        );
+        stream.viewer_count = 0;
+        assert_eq!(
+            describe("Geekingfrog", &Status::Live(&stream), now),
+            "Geekingfrog 🔴 live: Rust & chill [Science & Technology] [18+] depuis 2 h 05",
+            "just went live"
+        );
+    }
+
+    #[test]
+    fn test_offline() {
+        let video: Video = serde_json::from_str(VIDEO).unwrap();
+        let offline = Status::Offline(Some(video));
+        assert_eq!(
+            describe("Geekingfrog", &offline, datetime!(2024-05-04 12:00 UTC)),
+            "Geekingfrog offline (dernier live il y a 2 jours : Rust & chill)"
+        );
+        assert_eq!(
+            describe("Geekingfrog", &offline, datetime!(2024-05-02 01:00 UTC)),
+            "Geekingfrog offline (dernier live il y a moins d'un jour : Rust & chill)"
+        );
+        assert_eq!(
+            describe(
+                "Geekingfrog",
+                &Status::Offline(None),
+                datetime!(2024-05-04 12:00 UTC)
+            ),
+            "Geekingfrog offline"
+        );
+    }
+
+    #[test]
+    fn test_unknown_logins() {
+        let users: Vec<User> = vec![serde_json::from_str(USER).unwrap()];
+        let requested = vec!["geekingfrog".to_string(), "nobody_here".to_string()];
+        assert_eq!(unknown_logins(&requested, &users), vec!["nobody_here"]);
+        assert_eq!(unknown_logins(&requested[..1], &users), Vec::<&str>::new());
+    }
+}
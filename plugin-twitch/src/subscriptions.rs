@@ -0,0 +1,215 @@
+use std::time::Duration;
+use twitch_api2::{
+    eventsub::{self, EventType},
+    types::{EventSubId, Nickname, UserId},
+};
+
+/// Between two reconciliations of the subscriptions with the followed streams
+pub const RECONCILE_EVERY: Duration = Duration::from_secs(15 * 60);
+
+/// After a failed reconciliation, doubled up to `RECONCILE_EVERY`
+const MIN_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub struct Subscription {
+    pub id: EventSubId,
+    pub user_id: UserId,
+    pub type_: EventType,
+    pub status: eventsub::Status,
+}
+
+impl Subscription {
+    pub fn is_valid(&self) -> bool {
+        match self.status {
+            eventsub::Status::Enabled | eventsub::Status::WebhookCallbackVerificationPending => {
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn event(&self) -> Option<Event> {
+        match self.type_ {
+            EventType::StreamOnline => Some(Event::Online),
+            EventType::StreamOffline => Some(Event::Offline),
+            EventType::ChannelUpdate => Some(Event::Update),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    Online,
+    Offline,
+    /// channel.update, for the title and category changes
+    Update,
+}
+
+/// A followed stream, with its twitch id
+#[derive(Debug, Clone)]
+pub struct Wanted {
+    pub user_id: UserId,
+    pub login: Nickname,
+}
+
+#[derive(Debug)]
+pub enum Change<'a> {
+    Delete(&'a Subscription),
+    Create(&'a Wanted, Event),
+}
+
+/// What to delete and create so that there is a single valid subscription to
+/// stream.online, stream.offline and channel.update for each wanted stream,
+/// and nothing else
+pub fn reconcile<'a>(subs: &'a [Subscription], wanted: &'a [Wanted]) -> Vec<Change<'a>> {
+    let mut kept: Vec<(&UserId, Event)> = vec![];
+    let mut changes = vec![];
+    for sub in subs {
+        let key = match sub.event() {
+            Some(event) if sub.is_valid() => (&sub.user_id, event),
+            _ => {
+                changes.push(Change::Delete(sub));
+                continue;
+            }
+        };
+        let is_wanted = wanted.iter().any(|w| w.user_id == sub.user_id);
+        if !is_wanted || kept.contains(&key) {
+            changes.push(Change::Delete(sub));
+        } else {
+            kept.push(key);
+        }
+    }
+    for w in wanted {
+        for event in [Event::Online, Event::Offline, Event::Update] {
+            if !kept.contains(&(&w.user_id, event)) {
+                changes.push(Change::Create(w, event));
+            }
+        }
+    }
+    changes
+}
+
+/// Delay before the next reconciliation, shorter after a failure
+/// but growing with each one
+#[derive(Debug)]
+pub struct Backoff {
+    next: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff { next: MIN_BACKOFF }
+    }
+}
+
+impl Backoff {
+    pub fn failed(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = (self.next * 2).min(RECONCILE_EVERY);
+        delay
+    }
+
+    pub fn succeeded(&mut self) -> Duration {
+        self.next = MIN_BACKOFF;
+        RECONCILE_EVERY
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn sub(id: &str, user_id: &str, type_: EventType, status: eventsub::Status) -> Subscription {
+        Subscription {
+            id: EventSubId::new(id.to_string()),
+            user_id: UserId::new(user_id.to_string()),
+            type_,
+            status,
+        }
+    }
+
+    fn wanted(user_id: &str, login: &str) -> Wanted {
+        Wanted {
+            user_id: UserId::new(user_id.to_string()),
+            login: Nickname::new(login.to_string()),
+        }
+    }
+
+    fn describe(changes: Vec<Change>) -> Vec<String> {
+        changes
+            .into_iter()
+            .map(|change| match change {
+                Change::Delete(sub) => format!("delete {}", sub.id),
+                Change::Create(w, event) => format!("create {} {event:?}", w.login),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reconcile_missing() {
+        use eventsub::Status::Enabled;
+        let subs = vec![
+            sub("a", "1", EventType::StreamOnline, Enabled),
+            sub("b", "1", EventType::StreamOffline, Enabled),
+            sub("c", "1", EventType::ChannelUpdate, Enabled),
+            sub("d", "2", EventType::StreamOnline, Enabled),
+            sub("e", "2", EventType::ChannelUpdate, Enabled),
+        ];
+        let wanted = vec![wanted("1", "geekingfrog"), wanted("2", "chouhartem")];
+        assert_eq!(
+            describe(reconcile(&subs, &wanted)),
+            vec!["create chouhartem Offline"]
+        );
+    }
+
+    #[test]
+    fn test_reconcile_extra() {
+        use eventsub::Status::{Enabled, WebhookCallbackVerificationFailed};
+        let subs = vec![
+            sub("a", "1", EventType::StreamOnline, Enabled),
+            sub("b", "1", EventType::StreamOnline, Enabled),
+            sub(
+                "c",
+                "1",
+                EventType::StreamOffline,
+                WebhookCallbackVerificationFailed,
+            ),
+            sub("d", "3", EventType::StreamOnline, Enabled),
+        ];
+        let wanted = vec![wanted("1", "geekingfrog")];
+        assert_eq!(
+            describe(reconcile(&subs, &wanted)),
+            vec![
+                "delete b",
+                "delete c",
+                "delete d",
+                "create geekingfrog Offline",
+                "create geekingfrog Update"
+            ]
+        );
+        assert_eq!(describe(reconcile(&[], &[])), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::default();
+        let delays = (0..7).map(|_| backoff.failed()).collect::<Vec<_>>();
+        let minutes = |m: f64| Duration::from_secs_f64(m * 60.0);
+        assert_eq!(
+            delays,
+            vec![
+                minutes(0.5),
+                minutes(1.0),
+                minutes(2.0),
+                minutes(4.0),
+                minutes(8.0),
+                minutes(15.0),
+                minutes(15.0)
+            ]
+        );
+        assert_eq!(backoff.succeeded(), RECONCILE_EVERY);
+        assert_eq!(backoff.failed(), MIN_BACKOFF);
+    }
+}
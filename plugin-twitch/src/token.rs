@@ -0,0 +1,314 @@
+use async_trait::async_trait;
+use plugin_core::{BackgroundTask, Restart, Result};
+use std::{future::Future, sync::Arc, time::Duration};
+use tokio::{sync::RwLock, time::Instant};
+
+use crate::helix::{self, Failure, Retry};
+
+/// Get a new token that long before the current one expires,
+/// or halfway through its lifetime when it is shorter
+const REFRESH_MARGIN: Duration = Duration::from_secs(60 * 60);
+
+pub struct Fetched<T> {
+    pub token: T,
+    pub expires_in: Duration,
+}
+
+/// Where the tokens come from, the client credentials flow for twitch
+#[async_trait]
+pub trait TokenSource: Send + Sync + 'static {
+    type Token: Clone + Send + Sync + 'static;
+
+    async fn fetch(&self) -> Result<Fetched<Self::Token>>;
+}
+
+struct Cached<T> {
+    token: T,
+    refresh_at: Instant,
+    /// bumped on every fetch, to only replace the token once when
+    /// several requests get it rejected at the same time
+    generation: u64,
+}
+
+impl<T> Cached<T> {
+    fn new(fetched: Fetched<T>, generation: u64) -> Self {
+        let margin = REFRESH_MARGIN.min(fetched.expires_in / 2);
+        Cached {
+            token: fetched.token,
+            refresh_at: Instant::now() + (fetched.expires_in - margin),
+            generation,
+        }
+    }
+}
+
+/// Caches a token, replaced before it expires and when it gets rejected
+pub struct TokenManager<S: TokenSource> {
+    source: S,
+    cached: RwLock<Cached<S::Token>>,
+}
+
+impl<S: TokenSource> TokenManager<S> {
+    pub async fn new(source: S) -> Result<Self> {
+        let fetched = source.fetch().await?;
+        Ok(TokenManager {
+            cached: RwLock::new(Cached::new(fetched, 0)),
+            source,
+        })
+    }
+
+    /// The cached token, or a new one when it's about to expire
+    pub async fn get(&self) -> Result<S::Token> {
+        let (token, generation) = self.current().await;
+        match token {
+            Some(token) => Ok(token),
+            None => self.refresh(generation).await,
+        }
+    }
+
+    /// None when it should be refreshed
+    async fn current(&self) -> (Option<S::Token>, u64) {
+        let cached = self.cached.read().await;
+        let token = (Instant::now() < cached.refresh_at).then(|| cached.token.clone());
+        (token, cached.generation)
+    }
+
+    /// Replaces the token of the given generation. Already replaced, the newer
+    /// one is given back unless it's about to expire too.
+    async fn refresh(&self, generation: u64) -> Result<S::Token> {
+        let mut cached = self.cached.write().await;
+        if cached.generation != generation && Instant::now() < cached.refresh_at {
+            return Ok(cached.token.clone());
+        }
+        let fetched = self.source.fetch().await?;
+        *cached = Cached::new(fetched, cached.generation + 1);
+        log::info!("Twitch app access token refreshed");
+        Ok(cached.token.clone())
+    }
+
+    /// Runs the request with the token, retried as `helix::retry` says: once
+    /// with a new token when it's rejected, and after a while when twitch is
+    /// rate limiting or failing. Only for the idempotent requests.
+    pub async fn with_token<R, E, F, Fut>(&self, ctx: &str, request: F) -> Result<R>
+    where
+        E: Failure,
+        F: Fn(S::Token) -> Fut,
+        Fut: Future<Output = std::result::Result<R, E>>,
+    {
+        self.retried(ctx, true, request).await
+    }
+
+    /// Runs the request with the token, only retried with a new token when
+    /// it's rejected, since twitch then did nothing. For the requests which
+    /// aren't idempotent, like creating a clip.
+    pub async fn with_token_once<R, E, F, Fut>(&self, ctx: &str, request: F) -> Result<R>
+    where
+        E: Failure,
+        F: Fn(S::Token) -> Fut,
+        Fut: Future<Output = std::result::Result<R, E>>,
+    {
+        self.retried(ctx, false, request).await
+    }
+
+    async fn retried<R, E, F, Fut>(&self, ctx: &str, idempotent: bool, request: F) -> Result<R>
+    where
+        E: Failure,
+        F: Fn(S::Token) -> Fut,
+        Fut: Future<Output = std::result::Result<R, E>>,
+    {
+        let (mut token, mut generation) = match self.current().await {
+            (Some(token), generation) => (token, generation),
+            (None, generation) => (self.refresh(generation).await?, generation + 1),
+        };
+        let mut renewed = false;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let err = match request(token.clone()).await {
+                Ok(result) => return Ok(result),
+                Err(err) => err,
+            };
+            let now = time::OffsetDateTime::now_utc();
+            match helix::retry(&err, attempt, now) {
+                Retry::NewToken if !renewed => {
+                    log::warn!("{ctx}: token rejected, trying again with a new one");
+                    token = self.refresh(generation).await?;
+                    generation += 1;
+                    renewed = true;
+                }
+                Retry::After(delay) if idempotent && attempt < helix::MAX_ATTEMPTS => {
+                    log::warn!("{ctx}: {err}, trying again in {delay:?}");
+                    tokio::time::sleep(delay).await;
+                }
+                _ => return Err(helix::into_error(err, ctx, now)),
+            }
+        }
+    }
+
+    /// Waits until the token is about to expire and replaces it.
+    /// Started again by the golem a minute later, to wait for the next one.
+    pub fn refresh_task(self: &Arc<Self>) -> BackgroundTask {
+        let manager = Arc::clone(self);
+        BackgroundTask::new("token_refresh", move || {
+            let manager = Arc::clone(&manager);
+            async move {
+                let refresh_at = manager.cached.read().await.refresh_at;
+                tokio::time::sleep_until(refresh_at).await;
+                manager.get().await?;
+                Ok(())
+            }
+        })
+        .restart(Restart::Always {
+            delay: Duration::from_secs(60),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Tokens "token 1", "token 2"… expiring after the given lifetime
+    struct Fake {
+        fetched: AtomicU64,
+        lifetime: Duration,
+    }
+
+    impl Fake {
+        fn new(lifetime: Duration) -> Self {
+            Fake {
+                fetched: AtomicU64::new(0),
+                lifetime,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl TokenSource for Fake {
+        type Token = String;
+
+        async fn fetch(&self) -> Result<Fetched<String>> {
+            let n = self.fetched.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(Fetched {
+                token: format!("token {n}"),
+                expires_in: self.lifetime,
+            })
+        }
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    enum FakeError {
+        #[error("401")]
+        Unauthorized,
+        #[error("400")]
+        BadRequest,
+        #[error("503")]
+        Unavailable,
+    }
+
+    impl Failure for FakeError {
+        fn status(&self) -> Option<u16> {
+            match self {
+                FakeError::Unauthorized => Some(401),
+                FakeError::BadRequest => Some(400),
+                FakeError::Unavailable => Some(503),
+            }
+        }
+
+        fn message(&self) -> Option<&str> {
+            None
+        }
+    }
+
+    fn minutes(n: u64) -> Duration {
+        Duration::from_secs(n * 60)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refresh_before_expiry() {
+        let manager = TokenManager::new(Fake::new(minutes(120))).await.unwrap();
+        assert_eq!(manager.get().await.unwrap(), "token 1");
+        tokio::time::advance(minutes(59)).await;
+        assert_eq!(manager.get().await.unwrap(), "token 1");
+        tokio::time::advance(minutes(1)).await;
+        assert_eq!(
+            manager.get().await.unwrap(),
+            "token 2",
+            "an hour before expiry"
+        );
+        assert_eq!(manager.get().await.unwrap(), "token 2");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refresh_short_lived() {
+        let manager = TokenManager::new(Fake::new(minutes(10))).await.unwrap();
+        tokio::time::advance(minutes(4)).await;
+        assert_eq!(manager.get().await.unwrap(), "token 1");
+        tokio::time::advance(minutes(1)).await;
+        assert_eq!(manager.get().await.unwrap(), "token 2", "halfway");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refresh_task() {
+        let manager = Arc::new(TokenManager::new(Fake::new(minutes(120))).await.unwrap());
+        let start = Instant::now();
+        manager.refresh_task().start().await.unwrap();
+        assert_eq!(start.elapsed(), minutes(60));
+        assert_eq!(manager.source.fetched.load(Ordering::SeqCst), 2);
+        assert_eq!(manager.get().await.unwrap(), "token 2");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_rejected() {
+        let manager = TokenManager::new(Fake::new(minutes(120))).await.unwrap();
+        // revoked on twitch's side, still valid for us
+        let request = |token: String| async move {
+            match token.as_str() {
+                "token 1" => Err(FakeError::Unauthorized),
+                _ => Ok(token),
+            }
+        };
+        let token = manager.with_token("test", request).await;
+        assert_eq!(token.unwrap(), "token 2");
+
+        // a request rejected with the old token doesn't replace the new one
+        assert_eq!(manager.refresh(0).await.unwrap(), "token 2");
+        assert_eq!(manager.source.fetched.load(Ordering::SeqCst), 2);
+
+        let failing = |_token: String| async { Err::<(), _>(FakeError::BadRequest) };
+        let result = manager.with_token("test", failing).await;
+        assert!(result.is_err());
+        assert_eq!(
+            manager.source.fetched.load(Ordering::SeqCst),
+            2,
+            "only retried when rejected"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_not_idempotent() {
+        let manager = TokenManager::new(Fake::new(minutes(120))).await.unwrap();
+        let tries = AtomicU64::new(0);
+        let unavailable = |_token: String| {
+            tries.fetch_add(1, Ordering::SeqCst);
+            async { Err::<(), _>(FakeError::Unavailable) }
+        };
+        assert!(manager.with_token_once("test", unavailable).await.is_err());
+        assert_eq!(tries.load(Ordering::SeqCst), 1, "not retried");
+        assert!(manager.with_token("test", unavailable).await.is_err());
+        assert_eq!(tries.load(Ordering::SeqCst), 1 + helix::MAX_ATTEMPTS as u64);
+
+        let rejected = |token: String| async move {
+            match token.as_str() {
+                "token 1" => Err(FakeError::Unauthorized),
+                _ => Ok(token),
+            }
+        };
+        assert_eq!(
+            manager.with_token_once("test", rejected).await.unwrap(),
+            "token 2",
+            "retried with a new token"
+        );
+    }
+}
@@ -6,25 +6,32 @@ use axum::{
     routing, Router,
 };
 use hmac::{Hmac, Mac, NewMac};
-use std::{num::ParseIntError, sync::Arc};
+use plugin_core::utils::hex::decode_hex;
+use serde::Deserialize;
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use tokio::sync::mpsc;
 use twitch_api2::eventsub;
 
-use crate::config::{Config, Message};
+use crate::config::{Config, Message, RevokedSubscription};
 
 type HmacSha256 = Hmac<sha2::Sha256>;
 
-fn decode_hex(s: &str) -> std::result::Result<Vec<u8>, ParseIntError> {
-    (0..s.len())
-        .step_by(2)
-        .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
-        .collect()
-}
+/// Older messages are rejected, twitch may be replaying them
+const MAX_MESSAGE_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// How many message ids are remembered to drop the duplicates
+const SEEN_CAPACITY: usize = 1000;
 
 struct SigVerifierAxum {
     expected_sig: Vec<u8>,
     msg_id: Vec<u8>,
     msg_ts: Vec<u8>,
+    /// notification, webhook_callback_verification or revocation
+    msg_type: String,
 }
 
 impl SigVerifierAxum {
@@ -40,6 +47,17 @@ impl SigVerifierAxum {
         })?;
         Ok(())
     }
+
+    fn check_fresh(&self, now: time::OffsetDateTime) -> Result<(), TwitchSigError> {
+        let ts = std::str::from_utf8(&self.msg_ts).map_err(|_| TwitchSigError::Invalid)?;
+        let ts = time::OffsetDateTime::parse(ts, &time::format_description::well_known::Rfc3339)
+            .map_err(|_| TwitchSigError::Invalid)?;
+        if now - ts > MAX_MESSAGE_AGE {
+            log::warn!("Rejecting a message sent at {ts}");
+            return Err(TwitchSigError::Stale);
+        }
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
@@ -61,9 +79,9 @@ where
             None => return Err(TwitchSigError::Missing("message signature")),
         };
 
-        let sig = match decode_hex(&sig["sha256=".len()..]) {
-            Ok(bs) => bs,
-            Err(_) => return Err(TwitchSigError::Invalid),
+        let sig = match sig.strip_prefix("sha256=").and_then(decode_hex) {
+            Some(bs) => bs,
+            None => return Err(TwitchSigError::Invalid),
         };
 
         let msg_id = match parts.headers.get("Twitch-Eventsub-Message-Id") {
@@ -76,18 +94,81 @@ where
             None => return Err(TwitchSigError::Missing("message timestamp")),
         };
 
+        let msg_type = match parts.headers.get("Twitch-Eventsub-Message-Type") {
+            Some(hdr) => hdr.to_str()?.to_string(),
+            None => return Err(TwitchSigError::Missing("message type")),
+        };
+
         Ok(SigVerifierAxum {
             expected_sig: sig,
             msg_id,
             msg_ts,
+            msg_type,
         })
     }
 }
 
+/// The ids of the last messages, twitch may send one more than once
+struct SeenIds {
+    ids: HashSet<Vec<u8>>,
+    order: VecDeque<Vec<u8>>,
+}
+
+impl SeenIds {
+    fn new() -> Self {
+        SeenIds {
+            ids: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// false when the id was already seen
+    fn insert(&mut self, id: &[u8]) -> bool {
+        if !self.ids.insert(id.to_vec()) {
+            return false;
+        }
+        self.order.push_back(id.to_vec());
+        if self.order.len() > SEEN_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+#[derive(Deserialize)]
+struct VerificationBody {
+    challenge: String,
+}
+
+#[derive(Deserialize)]
+struct RevocationBody {
+    subscription: RevokedSubscription,
+}
+
 #[derive(Clone)]
 pub struct ServerStateAxum {
     app_secret: Arc<String>,
     send_chan: mpsc::Sender<Message>,
+    seen: Arc<Mutex<SeenIds>>,
+}
+
+impl ServerStateAxum {
+    async fn send(&self, msg: Message) -> Result<(), TwitchError> {
+        self.send_chan.send(msg).await.map_err(|err| {
+            log::error!("{:?}", err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        Ok(())
+    }
+}
+
+fn parse_body<'a, T: Deserialize<'a>>(body: &'a str) -> Result<T, TwitchError> {
+    serde_json::from_str(body).map_err(|err| {
+        log::error!("Cannot parse {body:?}: {err}");
+        StatusCode::BAD_REQUEST.into()
+    })
 }
 
 async fn webhook_post2(
@@ -97,40 +178,64 @@ async fn webhook_post2(
 ) -> Result<axum::response::Response, TwitchError> {
     log::debug!("got something from twitch: {:?}", body);
     sig_verifier.verify(&state.app_secret, body.as_bytes())?;
+    sig_verifier.check_fresh(time::OffsetDateTime::now_utc())?;
 
-    let payload = twitch_api2::eventsub::Payload::parse(&body).expect("good twitch response");
-    // dbg!(&payload);
-    match payload {
-        eventsub::Payload::VerificationRequest(verif_req) => {
-            log::debug!("verification request received: {:#?}", verif_req);
+    // a challenge sent again gets answered again
+    let first_seen = sig_verifier.msg_type == "webhook_callback_verification"
+        || state
+            .seen
+            .lock()
+            .expect("seen ids lock")
+            .insert(&sig_verifier.msg_id);
+    if !first_seen {
+        // still a success, otherwise twitch sends it again
+        log::info!("Dropping a duplicate message");
+        return Ok(().into_response());
+    }
+
+    match sig_verifier.msg_type.as_str() {
+        "webhook_callback_verification" => {
+            let verif_req: VerificationBody = parse_body(&body)?;
+            log::debug!("verification request received: {:?}", verif_req.challenge);
             Ok(verif_req.challenge.into_response())
         }
-        eventsub::Payload::StreamOnlineV1(online) => {
-            log::debug!("online stream event: {:#?}", online);
+        "revocation" => {
+            let revocation: RevocationBody = parse_body(&body)?;
+            log::warn!("subscription revoked: {:?}", revocation.subscription);
             state
-                .send_chan
-                .send(Message::StreamOnline(online.event))
-                .await
-                .map_err(|err| {
-                    log::error!("{:?}", err);
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?;
+                .send(Message::Revoked(revocation.subscription))
+                .await?;
             Ok(().into_response())
         }
-        eventsub::Payload::StreamOfflineV1(offline) => {
-            log::debug!("offline stream event: {:#?}", offline);
-            state
-                .send_chan
-                .send(Message::StreamOffline(offline.event))
-                .await
-                .map_err(|err| {
-                    log::error!("{:?}", err);
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?;
-            Ok(().into_response())
+        "notification" => {
+            let payload = twitch_api2::eventsub::Payload::parse(&body).map_err(|err| {
+                log::error!("Cannot parse notification {body:?}: {err}");
+                StatusCode::BAD_REQUEST
+            })?;
+            match payload {
+                eventsub::Payload::StreamOnlineV1(online) => {
+                    log::debug!("online stream event: {:#?}", online);
+                    state.send(Message::StreamOnline(online.event)).await?;
+                    Ok(().into_response())
+                }
+                eventsub::Payload::StreamOfflineV1(offline) => {
+                    log::debug!("offline stream event: {:#?}", offline);
+                    state.send(Message::StreamOffline(offline.event)).await?;
+                    Ok(().into_response())
+                }
+                eventsub::Payload::ChannelUpdateV1(update) => {
+                    log::debug!("channel update event: {:#?}", update);
+                    state.send(Message::ChannelUpdate(update.event)).await?;
+                    Ok(().into_response())
+                }
+                _ => {
+                    log::info!("Received unsupported payload: {:#?}", payload);
+                    Err(StatusCode::NOT_IMPLEMENTED.into())
+                }
+            }
         }
-        _ => {
-            log::info!("Received unsupported payload: {:#?}", payload);
+        msg_type => {
+            log::info!("Received unsupported message type {msg_type}");
             Err(StatusCode::NOT_IMPLEMENTED.into())
         }
     }
@@ -140,9 +245,207 @@ pub(crate) fn init_router(config: &Config, tx: mpsc::Sender<Message>) -> Router<
     let server_state = ServerStateAxum {
         app_secret: Arc::new(config.app_secret.clone()),
         send_chan: tx,
+        seen: Arc::new(Mutex::new(SeenIds::new())),
     };
 
     axum::Router::new()
         .route("/touitche/coucou", routing::post(webhook_post2))
         .with_state(server_state.clone())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::{Body, HttpBody};
+    use axum::http::Request;
+    use pretty_assertions::assert_eq;
+    use tower::ServiceExt;
+    use twitch_api2::twitch_oauth2::{ClientId, ClientSecret};
+
+    const SECRET: &str = "s3cr3t";
+
+    const ONLINE: &str = r#"{"subscription":{"id":"f1c2a387-161a-49f9-a165-0f21d7a4e1c4","type":"stream.online","version":"1","status":"enabled","cost":0,"condition":{"broadcaster_user_id":"1337"},"transport":{"method":"webhook","callback":"https://example.com/webhooks/callback"},"created_at":"2019-11-16T10:11:12.123Z"},"event":{"id":"9001","broadcaster_user_id":"1337","broadcaster_user_login":"cool_user","broadcaster_user_name":"Cool_User","type":"live","started_at":"2020-10-11T10:11:12.123Z"}}"#;
+
+    const CHALLENGE: &str = r#"{"challenge":"pogchamp-kappa-360noscope-vohiyo","subscription":{"id":"f1c2a387-161a-49f9-a165-0f21d7a4e1c4","status":"webhook_callback_verification_pending","type":"stream.online","version":"1","cost":0,"condition":{"broadcaster_user_id":"1337"},"transport":{"method":"webhook","callback":"https://example.com/webhooks/callback"},"created_at":"2019-11-16T10:11:12.123Z"}}"#;
+
+    const REVOCATION: &str = r#"{"subscription":{"id":"f1c2a387-161a-49f9-a165-0f21d7a4e1c4","status":"authorization_revoked","type":"stream.online","cost":0,"version":"1","condition":{"broadcaster_user_id":"1337"},"transport":{"method":"webhook","callback":"https://example.com/webhooks/callback"},"created_at":"2019-11-16T10:11:12.123Z"}}"#;
+
+    fn app() -> (Router<()>, mpsc::Receiver<Message>) {
+        let config = Config {
+            client_id: ClientId::new("id".to_string()),
+            client_secret: ClientSecret::new("secret".to_string()),
+            app_secret: SECRET.to_string(),
+            watched_streams: vec![],
+            routes: vec![],
+            flapping: Default::default(),
+            family_friendly_channels: vec![],
+            mode: Default::default(),
+            poll_interval_secs: 120,
+            clip_token: None,
+            callback_uri: crate::config::Obfuscated("https://example.com".to_string()),
+        };
+        let (tx, rx) = mpsc::channel(5);
+        (init_router(&config, tx), rx)
+    }
+
+    fn sign(id: &str, ts: &str, body: &str) -> String {
+        let mut mac = HmacSha256::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(id.as_bytes());
+        mac.update(ts.as_bytes());
+        mac.update(body.as_bytes());
+        let sig = mac.finalize().into_bytes();
+        let hex = sig.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        format!("sha256={hex}")
+    }
+
+    fn now() -> String {
+        time::OffsetDateTime::now_utc()
+            .format(&time::format_description::well_known::Rfc3339)
+            .unwrap()
+    }
+
+    async fn post(
+        app: Router<()>,
+        msg_type: &str,
+        id: &str,
+        ts: &str,
+        sig: &str,
+        body: &str,
+    ) -> (StatusCode, String) {
+        let req = Request::builder()
+            .method("POST")
+            .uri("/touitche/coucou")
+            .header("Twitch-Eventsub-Message-Id", id)
+            .header("Twitch-Eventsub-Message-Timestamp", ts)
+            .header("Twitch-Eventsub-Message-Signature", sig)
+            .header("Twitch-Eventsub-Message-Type", msg_type)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        let status = resp.status();
+        let mut body = resp.into_body();
+        let mut bytes = vec![];
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        (status, String::from_utf8(bytes).unwrap())
+    }
+
+    #[test]
+    fn test_verify() {
+        // hmac-sha256 of "id" + "ts" + "{}" keyed with "secret"
+        let sig = "333448268c4a9a4fa93abdf5fdbeba9b8c7f0e14ff9c33732b7b89370c3f2be7";
+        let verifier = SigVerifierAxum {
+            expected_sig: decode_hex(sig).unwrap(),
+            msg_id: b"id".to_vec(),
+            msg_ts: b"ts".to_vec(),
+            msg_type: "notification".to_string(),
+        };
+        assert!(verifier.verify("secret", b"{}").is_ok());
+        assert!(verifier.verify("secret", b"{ }").is_err());
+        assert!(verifier.verify("other secret", b"{}").is_err());
+    }
+
+    #[test]
+    fn test_seen_ids() {
+        let mut seen = SeenIds::new();
+        assert!(seen.insert(b"0"));
+        assert!(!seen.insert(b"0"));
+        for i in 1..=SEEN_CAPACITY {
+            assert!(seen.insert(i.to_string().as_bytes()));
+        }
+        assert!(seen.insert(b"0"), "forgotten once the set is full");
+        assert_eq!(seen.ids.len(), SEEN_CAPACITY);
+    }
+
+    #[tokio::test]
+    async fn test_notification() {
+        let (app, mut rx) = app();
+        let ts = now();
+        let sig = sign("1", &ts, ONLINE);
+        let (status, _) = post(app, "notification", "1", &ts, &sig, ONLINE).await;
+        assert_eq!(status, StatusCode::OK);
+        match rx.try_recv() {
+            Ok(Message::StreamOnline(online)) => {
+                assert_eq!(online.broadcaster_user_login.as_str(), "cool_user")
+            }
+            other => panic!("expected a stream online, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tampered() {
+        let (app, mut rx) = app();
+        let ts = now();
+        let sig = sign("1", &ts, ONLINE);
+        let tampered = ONLINE.replace("cool_user", "evil_user");
+        let (status, _) = post(app.clone(), "notification", "1", &ts, &sig, &tampered).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _) = post(app.clone(), "notification", "2", &ts, &sig, ONLINE).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "signed for another id");
+
+        let (status, _) = post(app, "notification", "1", &ts, "sha256=zz", ONLINE).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replayed() {
+        let (app, mut rx) = app();
+        let ts = now();
+        let sig = sign("1", &ts, ONLINE);
+        for _ in 0..2 {
+            let (status, _) = post(app.clone(), "notification", "1", &ts, &sig, ONLINE).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        assert!(rx.try_recv().is_ok());
+        assert!(rx.try_recv().is_err(), "the duplicate is dropped");
+
+        let old = time::OffsetDateTime::now_utc() - time::Duration::minutes(11);
+        let old = old
+            .format(&time::format_description::well_known::Rfc3339)
+            .unwrap();
+        let sig = sign("2", &old, ONLINE);
+        let (status, _) = post(app, "notification", "2", &old, &sig, ONLINE).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "too old");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_challenge() {
+        let (app, _rx) = app();
+        let ts = now();
+        let sig = sign("1", &ts, CHALLENGE);
+        let msg_type = "webhook_callback_verification";
+        let (status, body) = post(app.clone(), msg_type, "1", &ts, &sig, CHALLENGE).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "pogchamp-kappa-360noscope-vohiyo");
+
+        let (status, body) = post(app.clone(), msg_type, "1", &ts, &sig, CHALLENGE).await;
+        assert_eq!(
+            (status, body.as_str()),
+            (StatusCode::OK, "pogchamp-kappa-360noscope-vohiyo"),
+            "answered again"
+        );
+
+        let (status, _) = post(app, msg_type, "1", &ts, &sig, "{}").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_revocation() {
+        let (app, mut rx) = app();
+        let ts = now();
+        let sig = sign("1", &ts, REVOCATION);
+        let (status, _) = post(app, "revocation", "1", &ts, &sig, REVOCATION).await;
+        assert_eq!(status, StatusCode::OK);
+        match rx.try_recv() {
+            Ok(Message::Revoked(sub)) => {
+                assert_eq!(sub.type_, "stream.online");
+                assert_eq!(sub.status, "authorization_revoked");
+            }
+            other => panic!("expected a revocation, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use plugin_core::{ensure_schema, Database, Result};
+use std::collections::{HashMap, VecDeque};
+use url::Url;
+
+/// Urls remembered for each channel
+pub const MAX_URLS: usize = 10;
+
+const MIGRATIONS: &[&str] = &["CREATE TABLE url_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        key TEXT NOT NULL,
+        url TEXT NOT NULL,
+        seen_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE INDEX url_history_key ON url_history (key, id);"];
+
+pub type History = HashMap<String, VecDeque<Url>>;
+
+#[derive(QueryableByName)]
+struct Row {
+    #[sql_type = "Text"]
+    key: String,
+    #[sql_type = "Text"]
+    url: String,
+}
+
+/// Create the table if needed, and load the urls seen before the last restart
+pub fn load(db: &Database) -> Result<History> {
+    ensure_schema(db, "url", MIGRATIONS)?;
+    let rows = db.with_connection(|conn| {
+        diesel::sql_query("SELECT key, url FROM url_history ORDER BY id").load::<Row>(conn)
+    })?;
+    let mut history = History::new();
+    for row in rows {
+        match Url::parse(&row.url) {
+            Ok(url) => push(&mut history, &row.key, url),
+            Err(err) => log::warn!("Ignoring invalid url {} in history: {err}", row.url),
+        }
+    }
+    Ok(history)
+}
+
+/// Only keep the last MAX_URLS urls of the history
+pub fn push(history: &mut History, key: &str, url: Url) {
+    let urls = history.entry(key.to_string()).or_default();
+    urls.push_back(url);
+    if urls.len() > MAX_URLS {
+        urls.pop_front();
+    }
+}
+
+pub async fn record(db: &Database, key: String, url: Url) -> Result<()> {
+    db.run(move |conn| {
+        conn.transaction(|| {
+            diesel::sql_query("INSERT INTO url_history (key, url) VALUES (?, ?)")
+                .bind::<Text, _>(&key)
+                .bind::<Text, _>(url.as_str())
+                .execute(conn)?;
+            diesel::sql_query(format!(
+                "DELETE FROM url_history WHERE key = ? AND id NOT IN \
+                 (SELECT id FROM url_history WHERE key = ? ORDER BY id DESC LIMIT {MAX_URLS})"
+            ))
+            .bind::<Text, _>(&key)
+            .bind::<Text, _>(&key)
+            .execute(conn)?;
+            Ok(())
+        })
+    })
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn url(i: usize) -> Url {
+        Url::parse(&format!("https://coucou.com/{i}")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_history_survives_restart() {
+        let db = Database::in_memory().unwrap();
+        assert_eq!(load(&db).unwrap(), History::new());
+
+        for i in 0..12 {
+            record(&db, "libera/#rust".to_string(), url(i))
+                .await
+                .unwrap();
+        }
+        record(&db, "libera/#haskell-fr".to_string(), url(42))
+            .await
+            .unwrap();
+
+        let history = load(&db).unwrap();
+        assert_eq!(
+            history["libera/#rust"],
+            (2..12).map(url).collect::<VecDeque<_>>()
+        );
+        assert_eq!(history["libera/#haskell-fr"], VecDeque::from(vec![url(42)]));
+
+        #[derive(QueryableByName)]
+        struct Count {
+            #[sql_type = "diesel::sql_types::BigInt"]
+            n: i64,
+        }
+        let count = db
+            .with_connection(|conn| {
+                diesel::sql_query("SELECT count(*) AS n FROM url_history").get_result::<Count>(conn)
+            })
+            .unwrap();
+        assert_eq!(count.n, 11, "old urls are deleted from the db too");
+    }
+}
@@ -1,3 +1,6 @@
+#[macro_use]
+extern crate diesel;
+
 use encoding_rs::{CoderResult, Encoding};
 use google_youtube3::api::{PlaylistListResponse, SearchListResponse, VideoListResponse};
 use mime::Mime;
@@ -7,105 +10,138 @@ use std::{
     borrow::Cow,
     collections::{HashMap, VecDeque},
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use async_trait::async_trait;
 use irc::proto::{Command, Message};
+use messages::tr;
 use nom::{
-    branch::alt,
-    bytes::complete::{tag, take_till1, take_while, take_while1},
-    character::complete::{digit1, multispace0, multispace1},
-    combinator::{all_consuming, map, opt},
-    multi::separated_list0,
-    sequence::{delimited, pair, preceded, terminated, tuple},
-    AsChar, Finish, IResult, InputTakeAtPosition,
+    bytes::complete::take_while, combinator::map, multi::separated_list0, AsChar, Finish, IResult,
+    InputTakeAtPosition,
 };
 use parking_lot::Mutex;
-use plugin_core::{Error, Initialised, Plugin, Result};
+use plugin_core::{
+    parse, CommandHelp, Database, Error, Initialised, Lang, MetricsHandle, MsgCtx, Outbound,
+    Plugin, Requirement, Result,
+};
 use url::Url;
 
-mod parsing_utils;
+mod history;
+mod messages;
 
-#[derive(Deserialize)]
+/// Moved to plugin_core, kept for the code still importing it from here
+pub use plugin_core::parse as parsing_utils;
+
+/// The `url` section of the golem config
+#[derive(Default, Deserialize)]
 struct YtConfig {
     youtube_api_key: Option<String>,
 }
 
 pub struct UrlPlugin {
+    /// keyed by `history_key`
     seen_urls: Arc<Mutex<HashMap<String, VecDeque<Url>>>>,
     client: reqwest::Client,
     yt_api_key: Option<String>,
+    /// persists the history when the golem has a database
+    db: Option<Database>,
+    metrics: MetricsHandle,
 }
 
+/// Upper bounds in seconds, the http client gives up after 10s by default
+const FETCH_DURATION_BUCKETS: &[f64] = &[0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
 impl UrlPlugin {
-    fn new(config_path: &str) -> Result<Self> {
-        // let path = "golem_config.dhall";
-        let yt_config: YtConfig =
-            serde_dhall::from_file(config_path)
-                .parse()
-                .map_err(|err| Error::Wrapped {
-                    source: Box::new(err),
-                    ctx: format!("Failed to read config at {config_path}"),
-                })?;
+    fn new(config: &plugin_core::Config) -> Result<Self> {
+        let yt_config: YtConfig = config.plugin_section("url")?.unwrap_or_default();
         if yt_config.youtube_api_key.is_some() {
             log::info!("Url plugin initialized with youtube api credentials.");
         } else {
             log::warn!("Url plugin is missing youtube api key.");
         }
 
+        let db = config.database().cloned();
+        let seen_urls = match &db {
+            Some(db) => history::load(db)?,
+            None => Default::default(),
+        };
+
         Ok(UrlPlugin {
-            seen_urls: Default::default(),
-            client: reqwest::Client::new(),
+            seen_urls: Arc::new(Mutex::new(seen_urls)),
+            client: config.http_client(),
             yt_api_key: yt_config.youtube_api_key,
+            db,
+            metrics: config.metrics("url"),
         })
     }
 
-    fn add_urls(&self, channel: &str, urls: Vec<Url>) {
-        let mut seen_urls = self.seen_urls.lock();
-        let e = seen_urls.entry(channel.to_string()).or_default();
-        for url in urls {
-            log::info!("Adding {url} to chan {channel}");
-            e.push_back(url);
-            if e.len() > 10 {
-                e.pop_front();
+    async fn add_urls(&self, channel: &str, urls: Vec<Url>) {
+        {
+            let mut seen_urls = self.seen_urls.lock();
+            for url in &urls {
+                log::info!("Adding {url} to chan {channel}");
+                history::push(&mut seen_urls, channel, url.clone());
+            }
+        }
+        // not holding the lock of the history while waiting for the database
+        if let Some(db) = &self.db {
+            for url in urls {
+                let url_str = url.to_string();
+                if let Err(err) = history::record(db, channel.to_string(), url).await {
+                    log::error!("Cannot persist {url_str} for {channel}: {err}");
+                }
             }
         }
     }
 
-    async fn in_msg(&self, msg: &Message) -> Result<Option<Message>> {
-        if let Command::PRIVMSG(source, privmsg) = &msg.command {
-            self.add_urls(source, parse_urls(privmsg)?);
-
-            if let Some(cmd) = parse_command(privmsg) {
-                match cmd {
-                    Cmd::Url(mb_idx, mb_target) => {
-                        let channel = match msg.response_target() {
-                            None => return Ok(None),
-                            Some(target) => target,
-                        };
-                        let message = self.get_url(channel, mb_idx.unwrap_or(0)).await?;
+    async fn in_msg(&self, ctx: &MsgCtx) -> Result<Option<Outbound>> {
+        let text = match &ctx.text {
+            None => return Ok(None),
+            Some(text) => text,
+        };
+        // the history is per channel, there is no channel in private
+        if let Some(channel) = &ctx.channel {
+            self.add_urls(&history_key(ctx, channel), parse_urls(text)?)
+                .await;
+        }
+        if ctx.is_action {
+            return Ok(None);
+        }
+        let response_target = match ctx.response_target() {
+            None => return Ok(None),
+            Some(target) => target,
+        };
 
-                        let target = mb_target.map(|t| format!("{t}: ")).unwrap_or_default();
-                        let msg = format!("{target}{message}");
-                        return Ok(Some(Command::PRIVMSG(channel.to_string(), msg).into()));
-                    }
-                    Cmd::Search(term, _mb_target) => {
-                        let channel = match msg.response_target() {
-                            None => return Ok(None),
-                            Some(target) => target,
-                        };
-                        log::info!("searching yt for term {term}");
-                        let msg = self.yt_search(term).await?;
-                        return Ok(Some(Command::PRIVMSG(channel.to_string(), msg).into()));
+        match parse_command(text) {
+            None => Ok(None),
+            Some(Cmd::Url(mb_idx, mb_target)) => {
+                let channel = match &ctx.channel {
+                    None => {
+                        return Ok(Some(Outbound::reply(
+                            response_target,
+                            tr(ctx.lang, "no-history-in-private", &[]),
+                        )))
                     }
-                }
+                    Some(channel) => channel,
+                };
+                let message = self
+                    .get_url(&history_key(ctx, channel), mb_idx.unwrap_or(0), ctx.lang)
+                    .await?;
+
+                let target = mb_target.map(|t| format!("{t}: ")).unwrap_or_default();
+                let msg = format!("{target}{message}");
+                Ok(Some(Outbound::reply(response_target, msg)))
+            }
+            Some(Cmd::Search(term, _mb_target)) => {
+                log::info!("searching yt for term {term}");
+                let msg = self.yt_search(term, ctx.lang).await?;
+                Ok(Some(Outbound::reply(response_target, msg)))
             }
         }
-        Ok(None)
     }
 
-    async fn get_url(&self, channel: &str, idx: usize) -> Result<String> {
+    async fn get_url(&self, channel: &str, idx: usize, lang: Lang) -> Result<String> {
         let mb_url = {
             let urls_guard = self.seen_urls.lock();
             urls_guard
@@ -115,34 +151,58 @@ impl UrlPlugin {
                 // This avoid holding it across await points when fetching data for the url
                 .cloned()
         };
+        let lookup = if mb_url.is_some() { "hit" } else { "miss" };
+        self.metrics
+            .counter("history_lookups_total", &[("result", lookup)])
+            .inc();
         let url = match mb_url {
             Some(u) => u,
-            None => return Ok(format!("No stored url found at index {idx}")),
+            None => return Err(Error::not_found(tr(lang, "url-at-index", &[("idx", &idx)]))),
+        };
+
+        let started_at = Instant::now();
+        let (kind, result) = match &self.yt_api_key {
+            Some(yt_key) if is_yt_url(&url) => {
+                ("youtube", self.get_yt_url(&url, yt_key, lang).await)
+            }
+            _ => ("regular", self.get_regular_url(&url, lang).await),
         };
+        self.metrics
+            .histogram("fetch_duration_seconds", FETCH_DURATION_BUCKETS)
+            .observe(started_at.elapsed().as_secs_f64());
+        let outcome = if result.is_ok() { "ok" } else { "error" };
+        self.metrics
+            .counter("fetches_total", &[("kind", kind), ("result", outcome)])
+            .inc();
+        result
+    }
 
-        match &self.yt_api_key {
-            Some(yt_key) if is_yt_url(&url) => self.get_yt_url(&url, yt_key).await,
-            _ => self.get_regular_url(&url).await,
+    /// Youtube answers 403 once the daily quota is exhausted
+    fn count_quota_error(&self, status: reqwest::StatusCode) {
+        if status == reqwest::StatusCode::FORBIDDEN
+            || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        {
+            self.metrics.counter("yt_quota_errors_total", &[]).inc();
         }
     }
 
-    async fn get_regular_url(&self, url: &Url) -> Result<String> {
+    async fn get_regular_url(&self, url: &Url, lang: Lang) -> Result<String> {
         log::info!("Querying url {}", url);
-        let resp = self
-            .client
-            .get(url.clone())
-            .timeout(Duration::from_secs(10))
-            .send()
-            .await;
+        let resp = self.client.get(url.clone()).send().await;
 
         let resp = match resp {
             Ok(r) => r,
-            Err(err) => return Ok(format!("Problème avec l'url {}: {}", url, err)),
+            Err(err) => {
+                return Err(Error::user_visible(tr(
+                    lang,
+                    "fetch-failed",
+                    &[("url", url), ("err", &err)],
+                )))
+            }
         };
 
-        let status_code = resp.status();
-        if status_code != reqwest::StatusCode::OK {
-            return Ok(format!("Oops, wrong status code, got {}", status_code));
+        if resp.status() != reqwest::StatusCode::OK {
+            return Err(status_error(&resp, lang));
         }
 
         match resp
@@ -152,29 +212,38 @@ impl UrlPlugin {
         {
             Some(ct) if ct.contains("text") || ct.contains("html") => (),
             Some(ct) => {
-                return Ok(format!(
-                    "Cannot extract title from content type {ct} for {url}"
-                ))
+                return Err(Error::user_visible(tr(
+                    lang,
+                    "unsupported-content-type",
+                    &[("ct", &ct), ("url", url)],
+                )))
+            }
+            _ => {
+                return Err(Error::user_visible(tr(
+                    lang,
+                    "no-content-type",
+                    &[("url", url)],
+                )))
             }
-            _ => return Ok(format!("No valid content type found for {url}")),
         };
 
-        self.sniff_title(resp).await
+        self.sniff_title(resp, lang).await
     }
 
     // To avoid someone pointing the bot at a gigantic file, filling up memory or disk
-    async fn sniff_title(&self, resp: reqwest::Response) -> Result<String> {
-        sniff_title(resp).await
+    async fn sniff_title(&self, resp: reqwest::Response, lang: Lang) -> Result<String> {
+        sniff_title(resp, lang).await
     }
 
-    async fn get_yt_url(&self, url: &Url, yt_api_key: &str) -> Result<String> {
+    async fn get_yt_url(&self, url: &Url, yt_api_key: &str, lang: Lang) -> Result<String> {
         let yt_id = match extract_yt_id(url) {
             Some(x) => x,
             None => {
-                return Ok(format!(
-                    "Ook Ook 🙈, pas possible de trouver quoi query pour {}",
-                    url
-                ))
+                return Err(Error::user_visible(tr(
+                    lang,
+                    "unknown-yt-url",
+                    &[("url", url)],
+                )))
             }
         };
 
@@ -198,7 +267,7 @@ impl UrlPlugin {
                             &title, &chan, &published_at, &url
                         ))
                     }
-                    None => Ok(format!("Rien trouvé pour vidéo {vid_id}")),
+                    None => Err(Error::not_found(tr(lang, "video", &[("id", &vid_id)]))),
                 }
             }
             YtId::Channel(chan_name) => {
@@ -211,21 +280,22 @@ impl UrlPlugin {
                     .query(&[("q", chan_name)])
                     .send()
                     .await
-                    .map_err(|err| Error::Wrapped {
+                    .map_err(|err| Error::Internal {
                         source: Box::new(err),
                         ctx: format!("Failed to fetch channel with id {chan_name}"),
                     })?;
 
+                self.count_quota_error(raw_resp.status());
                 if raw_resp.status() == reqwest::StatusCode::NOT_FOUND {
-                    return Ok(format!("Pas trouvé de chan pour {chan_name}"));
+                    return Err(Error::not_found(tr(lang, "channel", &[("id", &chan_name)])));
                 }
 
                 if raw_resp.status() != reqwest::StatusCode::OK {
-                    return Ok(format!("Ooops, status code: {}", raw_resp.status()));
+                    return Err(status_error(&raw_resp, lang));
                 }
 
                 let results: SearchListResponse =
-                    raw_resp.json().await.map_err(|err| Error::Wrapped {
+                    raw_resp.json().await.map_err(|err| Error::Internal {
                         source: Box::new(err),
                         ctx: format!("Cannot parse response when fetching channel {chan_name}"),
                     })?;
@@ -240,16 +310,19 @@ impl UrlPlugin {
                             .as_deref()
                             .map(|d| format!(" - {d}"))
                             .unwrap_or_else(|| "".to_string());
+                        let args: &[(&str, &dyn std::fmt::Display)] = &[
+                            ("title", &title),
+                            ("published_at", &published_at),
+                            ("description", &description),
+                            ("url", url),
+                        ];
                         if description.is_empty() {
-                            Ok(format!("Channel: {}{} [{}]", title, published_at, url))
+                            Ok(tr(lang, "channel-info", args))
                         } else {
-                            Ok(format!(
-                                "Channel: {}{} ({}) [{}]",
-                                title, published_at, description, url
-                            ))
+                            Ok(tr(lang, "channel-info-description", args))
                         }
                     }
-                    None => Ok(format!("Pas trouvé de chan pour {chan_name}")),
+                    None => Err(Error::not_found(tr(lang, "channel", &[("id", &chan_name)]))),
                 }
             }
             YtId::Playlist(playlist_id) => {
@@ -260,9 +333,17 @@ impl UrlPlugin {
                     Some(playlist) => {
                         let snip = playlist.snippet.as_ref().unwrap();
                         let title = snip.title.as_deref().unwrap_or("");
-                        Ok(format!("Playlist: {} [{}]", &title, &url))
+                        Ok(tr(
+                            lang,
+                            "playlist-info",
+                            &[("title", &title), ("url", url)],
+                        ))
                     }
-                    None => Ok(format!("Pas de playlist trouvée pour {playlist_id}")),
+                    None => Err(Error::not_found(tr(
+                        lang,
+                        "playlist",
+                        &[("id", &playlist_id)],
+                    ))),
                 }
             }
         }
@@ -276,34 +357,39 @@ impl UrlPlugin {
         let mut url = Url::parse("https://www.googleapis.com/youtube/v3").unwrap();
         url.path_segments_mut().unwrap().push(resource);
 
-        self.client
+        let resp = self
+            .client
             .get(url)
             .query(&[("id", &resource_id)])
             .query(&[("key", yt_api_key.to_owned())])
             .query(&[("part", "snippet")])
-            .timeout(Duration::from_secs(10))
             .send()
-            .await
-            .and_then(|x| x.error_for_status())
-            .map_err(|err| Error::Wrapped {
+            .await;
+        if let Ok(resp) = &resp {
+            self.count_quota_error(resp.status());
+        }
+        resp.and_then(|x| x.error_for_status())
+            .map_err(|err| Error::Internal {
                 source: Box::new(err),
                 ctx: format!("Failed to fetch {resource} with id {resource_id}"),
             })?
             .json()
             .await
-            .map_err(|err| Error::Wrapped {
+            .map_err(|err| Error::Internal {
                 source: Box::new(err),
                 ctx: format!("Failed to fetch {resource} with id {resource_id}"),
             })
     }
 
-    async fn yt_search(&self, search_term: &str) -> Result<String> {
+    async fn yt_search(&self, search_term: &str, lang: Lang) -> Result<String> {
         let key = match &self.yt_api_key {
             Some(k) => k,
             None => {
-                return Ok(format!(
-                    "No youtube api key provided, can't search: {search_term}"
-                ))
+                return Err(Error::user_visible(tr(
+                    lang,
+                    "no-yt-key",
+                    &[("term", &search_term)],
+                )))
             }
         };
 
@@ -314,15 +400,16 @@ impl UrlPlugin {
             .query(&[("part", "snippet")])
             // .query(&[("type", "channel")])
             .query(&[("q", search_term)])
-            .timeout(Duration::from_secs(10))
             .send()
             .await
-            .map_err(|err| Error::Wrapped {
+            .map_err(|err| Error::Internal {
                 source: Box::new(err),
                 ctx: format!("Failed to search yt for {search_term}"),
             })?;
 
+        self.count_quota_error(raw_resp.status());
         let jsonbody: std::result::Result<SearchListResponse, _> = raw_resp.json().await;
+        let no_channel = tr(lang, "no-channel", &[]);
 
         match jsonbody {
             Ok(search_resp) => match search_resp.items.as_ref().and_then(|v| v.first()) {
@@ -344,8 +431,12 @@ impl UrlPlugin {
                                 .snippet
                                 .as_ref()
                                 .and_then(|x| x.channel_title.as_deref())
-                                .unwrap_or("no channel found");
-                            Ok(format!("channel: [{channel_title}] https://www.youtube.com/channel/{channel_id}"))
+                                .unwrap_or(&no_channel);
+                            Ok(tr(
+                                lang,
+                                "search-channel",
+                                &[("title", &channel_title), ("id", channel_id)],
+                            ))
                         }
                         "youtube#playlist" => {
                             let title = search_result
@@ -362,7 +453,11 @@ impl UrlPlugin {
                                 .and_then(|x| x.playlist_id.as_ref())
                                 .unwrap();
 
-                            Ok(format!("playlist: {title} https://www.youtube.com/playlist?list={playlist_id}"))
+                            Ok(tr(
+                                lang,
+                                "search-playlist",
+                                &[("title", title), ("id", playlist_id)],
+                            ))
                         }
                         "youtube#video" => {
                             let title = search_result
@@ -383,18 +478,26 @@ impl UrlPlugin {
                                 .snippet
                                 .as_ref()
                                 .and_then(|x| x.channel_title.as_deref())
-                                .unwrap_or("no channel found");
+                                .unwrap_or(&no_channel);
 
                             Ok(format!("{title} [{channel_title}] https://www.youtube.com/watch?v={vid_id}"))
                         }
-                        _ => return Ok(format!("Rien trouvé pour {search_term} /o\\")),
+                        _ => Err(Error::user_visible(tr(
+                            lang,
+                            "nothing-found",
+                            &[("term", &search_term)],
+                        ))),
                     }
                 }
-                None => return Ok(format!("Rien trouvé pour {search_term} /o\\")),
+                None => Err(Error::user_visible(tr(
+                    lang,
+                    "nothing-found",
+                    &[("term", &search_term)],
+                ))),
             },
             Err(err) => {
                 log::error!("Can't parse yt response for {search_term}\n{:?}", err);
-                return Err(Error::Wrapped {
+                return Err(Error::Internal {
                     source: Box::new(err),
                     ctx: format!("Failed to parse json response for {search_term}"),
                 });
@@ -403,10 +506,31 @@ impl UrlPlugin {
     }
 }
 
+/// Too many requests, or any other unexpected status, as told to the users
+fn status_error(resp: &reqwest::Response, lang: Lang) -> Error {
+    let status = resp.status();
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = resp
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs);
+        Error::RateLimited { retry_after }
+    } else {
+        Error::user_visible(tr(lang, "wrong-status", &[("status", &status)]))
+    }
+}
+
 #[async_trait]
 impl Plugin for UrlPlugin {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        config.plugin_section::<YtConfig>("url")?;
+        Ok(())
+    }
+
     async fn init(config: &plugin_core::Config) -> Result<Initialised> {
-        let plugin = UrlPlugin::new(&config.config_path)?;
+        let plugin = UrlPlugin::new(config)?;
         Ok(Initialised::from(plugin))
     }
 
@@ -414,8 +538,27 @@ impl Plugin for UrlPlugin {
         "url"
     }
 
-    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
-        self.in_msg(msg).await
+    async fn in_message_ctx(&self, ctx: &MsgCtx, _msg: &Message) -> Result<Option<Outbound>> {
+        self.in_msg(ctx).await
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("url")
+                .usage("url [idx] [> nick]")
+                .description("Title of the last url seen on the channel, or the idx-th before it"),
+            CommandHelp::new("yt_search")
+                .usage("yt_search <terms> [> nick]")
+                .description("Search youtube and give the first video found"),
+        ]
+    }
+
+    fn requirements(&self) -> Vec<Requirement> {
+        // url titles work without a key, youtube links and searches don't
+        vec![
+            Requirement::ConfigKey("youtube_api_key"),
+            Requirement::Network,
+        ]
     }
 
     fn ignore_blacklisted_users(&self) -> bool {
@@ -423,6 +566,15 @@ impl Plugin for UrlPlugin {
     }
 }
 
+/// Urls are kept per channel, and per network when the golem is connected
+/// to several of them.
+fn history_key(ctx: &MsgCtx, channel: &str) -> String {
+    match &ctx.network {
+        Some(network) => format!("{network}/{channel}"),
+        None => channel.to_string(),
+    }
+}
+
 // all characters considered as space by the regex \s
 const SPACE_CHARS: [char; 25] = [
     '\t', '\n', '\u{b}', '\u{c}', '\r', ' ', '\u{85}', '\u{a0}', '\u{1680}', '\u{2000}',
@@ -472,47 +624,24 @@ enum Cmd<'msg> {
     Search(&'msg str, Option<&'msg str>),
 }
 
-/// returns Option<(optional_url_index, optional_target_nick)>
 fn parse_command(msg: &str) -> Option<Cmd<'_>> {
-    let cmd = preceded(
-        parsing_utils::command_prefix,
-        alt((
-            map(
-                parsing_utils::with_target(pair(tag("url"), opt(preceded(multispace1, digit1)))),
-                |((_, mb_idx), mb_target)| {
-                    let idx = mb_idx.and_then(|raw| str::parse(raw).ok());
-                    Cmd::Url(idx, mb_target)
-                },
-            ),
-            map(
-                preceded(
-                    pair(tag("yt_search"), multispace1),
-                    alt((
-                        map(
-                            tuple((
-                                take_till1(|c| c == '>'),
-                                delimited(
-                                    pair(nom::character::complete::char('>'), multispace0),
-                                    parsing_utils::word,
-                                    multispace0,
-                                ),
-                            )),
-                            |(x, t)| (x, Some(t)),
-                        ),
-                        map(
-                            terminated(take_while1(|c| c != '>'), nom::combinator::eof),
-                            |x| (x, None),
-                        ),
-                    )),
-                ),
-                |(x, t)| Cmd::Search(x, t),
-            ),
-        )),
-    );
-    all_consuming(terminated(cmd, multispace0))(msg)
-        .finish()
-        .map(|x| x.1)
-        .ok()
+    let args_of = |name| {
+        parse::command(name)(msg)
+            .finish()
+            .ok()
+            .map(|(_, args)| args)
+    };
+    if let Some((args, mb_target)) = args_of("url") {
+        let idx = match args {
+            "" => None,
+            idx => Some(idx.parse().ok()?),
+        };
+        return Some(Cmd::Url(idx, mb_target));
+    }
+    match args_of("yt_search")? {
+        ("", _) => None,
+        (term, mb_target) => Some(Cmd::Search(term, mb_target)),
+    }
 }
 
 const YT_HOSTNAMES: [&str; 5] = [
@@ -599,7 +728,7 @@ fn text_with_charset(bytes: &[u8], content_type: &Option<HeaderValue>) -> Result
     Ok(dst)
 }
 
-pub async fn sniff_title(mut resp: reqwest::Response) -> Result<String> {
+pub async fn sniff_title(mut resp: reqwest::Response, lang: Lang) -> Result<String> {
     let ct = resp.headers().get(reqwest::header::CONTENT_TYPE).cloned();
     let url = resp.url().to_string();
 
@@ -607,11 +736,19 @@ pub async fn sniff_title(mut resp: reqwest::Response) -> Result<String> {
     match ct.as_ref().and_then(|h| h.to_str().ok()) {
         Some(ct) if ct.contains("text") || ct.contains("html") => (),
         Some(ct) => {
-            return Ok(format!(
-                "Cannot extract title from content type {ct} for {url}",
-            ))
+            return Err(Error::user_visible(tr(
+                lang,
+                "unsupported-content-type",
+                &[("ct", &ct), ("url", &url)],
+            )))
+        }
+        _ => {
+            return Err(Error::user_visible(tr(
+                lang,
+                "no-content-type",
+                &[("url", &url)],
+            )))
         }
-        _ => return Ok(format!("No valid content type found for {url}")),
     };
 
     // don't download more than `capa` bytes (to avoid dos)
@@ -619,7 +756,7 @@ pub async fn sniff_title(mut resp: reqwest::Response) -> Result<String> {
     let mut read_buf = bytes::BytesMut::with_capacity(capa);
 
     while let Some(chunk) = resp.chunk().await.transpose() {
-        let chunk = chunk.map_err(|err| Error::Wrapped {
+        let chunk = chunk.map_err(|err| Error::Internal {
             source: Box::new(err),
             ctx: format!("Failed to read bytes from response for url {}", url),
         })?;
@@ -663,14 +800,24 @@ pub async fn sniff_title(mut resp: reqwest::Response) -> Result<String> {
             Ok(format!("{title} [{url}]"))
         }
     } else {
-        Ok(format!("No title found at {url}"))
+        Err(Error::not_found(tr(lang, "title-at", &[("url", &url)])))
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use nom::{
+        bytes::complete::take_while1,
+        character::complete::multispace0,
+        combinator::all_consuming,
+        sequence::{terminated, tuple},
+    };
+    use plugin_core::i18n::Languages;
+    use plugin_core::testkit::Harness;
     use pretty_assertions::assert_eq;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
 
     #[test]
     fn test_simple_url() {
@@ -768,7 +915,7 @@ mod test {
     fn test_command_search_with_target() {
         assert_eq!(
             parse_command("λyt_search coucou1 and coucou2 > charlie"),
-            Some(Cmd::Search("coucou1 and coucou2 ", Some("charlie")))
+            Some(Cmd::Search("coucou1 and coucou2", Some("charlie")))
         );
     }
 
@@ -811,6 +958,15 @@ mod test {
         assert_eq!(parse_command("λyt_search > charlie"), None);
     }
 
+    #[test]
+    fn test_command_unicode_target() {
+        assert_eq!(
+            parse_command("λurl 1 > héloïse  "),
+            Some(Cmd::Url(Some(1), Some("héloïse")))
+        );
+        assert_eq!(parse_command("λurl un"), None);
+    }
+
     #[test]
     fn test_command_search() {
         assert_eq!(
@@ -928,4 +1084,161 @@ mod test {
             "💖".to_string()
         );
     }
+
+    const NO_YT_KEY: &str = "{ url = { youtube_api_key = None Text } }";
+
+    /// nothing listens there, so that fetching the title fails right away
+    const DEAD_URL: &str = "http://127.0.0.1:1/";
+
+    fn at_once<'a>(lines: &[&'a str]) -> Vec<(Duration, &'a str)> {
+        lines.iter().map(|line| (Duration::ZERO, *line)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_url_conversation() {
+        let harness = Harness::new::<UrlPlugin>(NO_YT_KEY).await.unwrap();
+        harness
+            .play(&at_once(&[
+                &format!(":alice!~alice@localhost PRIVMSG #rust :look at {DEAD_URL}"),
+                ":bob!~bob@localhost PRIVMSG #rust :λurl > charlie",
+                ":bob!~bob@localhost PRIVMSG #other :λurl",
+            ]))
+            .await
+            .unwrap();
+        harness.assert_replied_containing("#rust", &format!("charlie: Cannot fetch {DEAD_URL}"));
+        // the history is per channel
+        harness.assert_replied_containing("#other", "Url at index 0 not found");
+    }
+
+    #[tokio::test]
+    async fn test_channel_language() {
+        let harness = Harness::new::<UrlPlugin>(NO_YT_KEY)
+            .await
+            .unwrap()
+            .with_languages(Languages::new(Lang::En).with_channel("#coucou", Lang::Fr));
+        harness
+            .play(&at_once(&[
+                &format!(":alice!~alice@localhost PRIVMSG #coucou :look at {DEAD_URL}"),
+                ":bob!~bob@localhost PRIVMSG #coucou :λurl",
+                ":bob!~bob@localhost PRIVMSG #coucou :λurl 1",
+                ":bob!~bob@localhost PRIVMSG #coucou :λyt_search cats",
+                ":bob!~bob@localhost PRIVMSG #rust :λurl",
+            ]))
+            .await
+            .unwrap();
+        harness.assert_replied_containing("#coucou", &format!("Problème avec l'url {DEAD_URL}"));
+        harness.assert_replied_containing("#coucou", "Url à l'index 1 introuvable");
+        harness.assert_replied_containing("#coucou", "Pas de clé d'api youtube");
+        harness.assert_replied_containing("#rust", "Url at index 0 not found");
+    }
+
+    #[tokio::test]
+    async fn test_no_history_in_private() {
+        let harness = Harness::new::<UrlPlugin>(NO_YT_KEY).await.unwrap();
+        harness
+            .play(&at_once(&[
+                &format!(":alice!~alice@localhost PRIVMSG rustygolem :look at {DEAD_URL}"),
+                ":alice!~alice@localhost PRIVMSG #rust :λurl",
+            ]))
+            .await
+            .unwrap();
+        harness.assert_replied_containing("#rust", "Url at index 0 not found");
+
+        harness.clear();
+        harness
+            .play(&at_once(&[
+                ":alice!~alice@localhost PRIVMSG rustygolem :λurl",
+            ]))
+            .await
+            .unwrap();
+        // to the sender, the target of the PRIVMSG is the bot itself
+        harness.assert_replied_containing("alice", "no url history in private messages");
+    }
+
+    #[tokio::test]
+    async fn test_search_conversation() {
+        let harness = Harness::new::<UrlPlugin>(NO_YT_KEY).await.unwrap();
+        harness
+            .play(&at_once(&[
+                ":alice!~alice@localhost PRIVMSG rustygolem :λyt_search cats",
+                ":alice!~alice@localhost PRIVMSG #rust :λyt_search > bob",
+                ":alice!~alice@localhost PRIVMSG #rust :\x01ACTION λyt_search dogs\x01",
+            ]))
+            .await
+            .unwrap();
+        assert_eq!(
+            harness
+                .emitted()
+                .into_iter()
+                .map(|e| e.outbound)
+                .collect::<Vec<_>>(),
+            vec![Outbound::reply(
+                "alice",
+                "No youtube api key provided, can't search: cats"
+            )],
+            "no search term, and no commands in actions"
+        );
+    }
+
+    /// Serves a page titled "Coucou" to every request
+    async fn title_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                // the request itself doesn't matter
+                let mut buf = vec![0; 4096];
+                let _ = stream.read(&mut buf).await;
+                let body = "<html><head><title>Coucou</title></head></html>";
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: text/html\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        format!("http://{addr}/")
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        let url = title_server().await;
+        let harness = Harness::new::<UrlPlugin>(NO_YT_KEY).await.unwrap();
+        harness
+            .play(&at_once(&[
+                &format!(":alice!~alice@localhost PRIVMSG #rust :look at {url} and {DEAD_URL}"),
+                ":bob!~bob@localhost PRIVMSG #rust :λurl 1",
+                ":bob!~bob@localhost PRIVMSG #rust :λurl",
+                ":bob!~bob@localhost PRIVMSG #rust :λurl 5",
+            ]))
+            .await
+            .unwrap();
+        harness.assert_replied_containing("#rust", &format!("Coucou [{url}]"));
+
+        let metrics = harness.metrics();
+        for expected in [
+            "url_fetches_total{kind=\"regular\",result=\"error\"} 1\n",
+            "url_fetches_total{kind=\"regular\",result=\"ok\"} 1\n",
+            "url_fetch_duration_seconds_count 2\n",
+            "url_history_lookups_total{result=\"hit\"} 2\n",
+            "url_history_lookups_total{result=\"miss\"} 1\n",
+        ] {
+            assert!(
+                metrics.contains(expected),
+                "{expected} missing from\n{metrics}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_history_key_per_network() {
+        let mut msg: Message = Command::PRIVMSG("#rust".to_string(), "coucou".to_string()).into();
+        assert_eq!(history_key(&MsgCtx::from_message(&msg), "#rust"), "#rust");
+        plugin_core::utils::network::set_network(&mut msg, "libera");
+        assert_eq!(
+            history_key(&MsgCtx::from_message(&msg), "#rust"),
+            "libera/#rust"
+        );
+    }
 }
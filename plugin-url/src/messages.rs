@@ -0,0 +1,101 @@
+use plugin_core::i18n::Catalog;
+use plugin_core::Lang;
+use std::fmt::Display;
+
+/// Everything the url plugin says to the users
+const CATALOG: Catalog = Catalog {
+    fr: &[
+        (
+            "no-history-in-private",
+            "pas d'historique d'url en message privé",
+        ),
+        ("url-at-index", "Url à l'index {idx}"),
+        ("fetch-failed", "Problème avec l'url {url}: {err}"),
+        (
+            "wrong-status",
+            "Oups, mauvais code de retour, reçu {status}",
+        ),
+        (
+            "unsupported-content-type",
+            "Pas de titre à extraire du type {ct} pour {url}",
+        ),
+        (
+            "no-content-type",
+            "Pas de type de contenu valide pour {url}",
+        ),
+        ("title-at", "Titre de {url}"),
+        (
+            "unknown-yt-url",
+            "Ook Ook 🙈, pas possible de trouver quoi query pour {url}",
+        ),
+        ("video", "Vidéo {id}"),
+        ("channel", "Chaîne {id}"),
+        ("playlist", "Playlist {id}"),
+        ("channel-info", "Chaîne : {title}{published_at} [{url}]"),
+        (
+            "channel-info-description",
+            "Chaîne : {title}{published_at} ({description}) [{url}]",
+        ),
+        ("playlist-info", "Playlist : {title} [{url}]"),
+        ("no-channel", "pas de chaîne trouvée"),
+        (
+            "search-channel",
+            "chaîne : [{title}] https://www.youtube.com/channel/{id}",
+        ),
+        (
+            "search-playlist",
+            "playlist : {title} https://www.youtube.com/playlist?list={id}",
+        ),
+        (
+            "no-yt-key",
+            "Pas de clé d'api youtube, impossible de chercher : {term}",
+        ),
+        ("nothing-found", "Rien trouvé pour {term} /o\\"),
+    ],
+    en: &[
+        (
+            "no-history-in-private",
+            "no url history in private messages",
+        ),
+        ("url-at-index", "Url at index {idx}"),
+        ("fetch-failed", "Cannot fetch {url}: {err}"),
+        ("wrong-status", "Oops, wrong status code, got {status}"),
+        (
+            "unsupported-content-type",
+            "Cannot extract title from content type {ct} for {url}",
+        ),
+        ("no-content-type", "No valid content type found for {url}"),
+        ("title-at", "Title at {url}"),
+        (
+            "unknown-yt-url",
+            "Ook Ook 🙈, no idea what to query for {url}",
+        ),
+        ("video", "Video {id}"),
+        ("channel", "Channel {id}"),
+        ("playlist", "Playlist {id}"),
+        ("channel-info", "Channel: {title}{published_at} [{url}]"),
+        (
+            "channel-info-description",
+            "Channel: {title}{published_at} ({description}) [{url}]",
+        ),
+        ("playlist-info", "Playlist: {title} [{url}]"),
+        ("no-channel", "no channel found"),
+        (
+            "search-channel",
+            "channel: [{title}] https://www.youtube.com/channel/{id}",
+        ),
+        (
+            "search-playlist",
+            "playlist: {title} https://www.youtube.com/playlist?list={id}",
+        ),
+        (
+            "no-yt-key",
+            "No youtube api key provided, can't search: {term}",
+        ),
+        ("nothing-found", "Nothing found for {term} /o\\"),
+    ],
+};
+
+pub fn tr(lang: Lang, key: &str, args: &[(&str, &dyn Display)]) -> String {
+    CATALOG.tr(lang, key, args)
+}
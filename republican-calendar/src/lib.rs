@@ -1,6 +1,201 @@
 use std::convert::TryFrom;
+use std::str::FromStr;
 
-use time::Date;
+use serde::Deserialize;
+use time::{Date, Duration};
+
+mod rural;
+pub use rural::{find, item};
+
+/// 1 Vendémiaire of the years 1 to 15, the 22nd, 23rd or 24th of September.
+/// The sextile years followed the autumn equinox then: 3, 7 and 11. The
+/// calendar was abolished during the year 14, the start of the year 15 is the
+/// equinox, which the rule of the following years agrees with.
+const YEAR_STARTS: [(i32, u8); 15] = [
+    (1792, 22),
+    (1793, 22),
+    (1794, 22),
+    (1795, 23),
+    (1796, 22),
+    (1797, 22),
+    (1798, 22),
+    (1799, 23),
+    (1800, 23),
+    (1801, 23),
+    (1802, 23),
+    (1803, 24),
+    (1804, 23),
+    (1805, 23),
+    (1806, 23),
+];
+
+/// The years after this one are sextile according to a `SextileRule`
+const LAST_HISTORICAL_YEAR: i32 = 14;
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Error {
+    /// before 1 Vendémiaire 1, the 22nd of September 1792
+    BeforeEpoch,
+    /// like a 31st day, or a 6th sans-culottide in a year that isn't sextile
+    InvalidDay,
+    /// too far in the future for the `time` dates
+    OutOfRange,
+    Unparseable,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let res = match self {
+            Error::BeforeEpoch => {
+                "Le calendrier républicain commence le 22 septembre 1792, le 1 Vendémiaire 1"
+            }
+            Error::InvalidDay => "Ce jour n'existe pas dans le calendrier républicain",
+            Error::OutOfRange => "C'est bien trop loin",
+            Error::Unparseable => "Date incompréhensible",
+        };
+        write!(f, "{}", res)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Which years get a 6th sans-culottide after the historical ones, the
+/// years 3, 7 and 11 being sextile whatever the rule
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default, Deserialize)]
+pub enum SextileRule {
+    /// The rule proposed by Gilbert Romme: the years divisible by 4 are
+    /// sextile, except the centuries not divisible by 400. The years 16, 20,
+    /// 24… like the gregorian leap years.
+    #[default]
+    Romme,
+    /// The historical cycle carried on, the Romme rule applied to the next
+    /// year: the years 15, 19, 23… but 99 isn't sextile while 399 is.
+    Continuous,
+}
+
+impl SextileRule {
+    /// How many years the Romme rule is applied ahead
+    fn offset(self) -> i32 {
+        match self {
+            SextileRule::Romme => 0,
+            SextileRule::Continuous => 1,
+        }
+    }
+
+    /// With a 6th sans-culottide
+    pub fn is_sextile(self, year: i32) -> Result<bool, Error> {
+        if year < 1 {
+            return Err(Error::BeforeEpoch);
+        }
+        if year <= LAST_HISTORICAL_YEAR {
+            return Ok((self.year_start(year + 1)? - self.year_start(year)?).whole_days() == 366);
+        }
+        let year = i64::from(year) + i64::from(self.offset());
+        Ok(year % 4 == 0 && (year % 100 != 0 || year % 400 == 0))
+    }
+
+    /// 1 Vendémiaire of the given year
+    pub fn year_start(self, year: i32) -> Result<Date, Error> {
+        if year < 1 {
+            return Err(Error::BeforeEpoch);
+        }
+        let first_computed = LAST_HISTORICAL_YEAR + 1;
+        if year <= first_computed {
+            let (y, day) = YEAR_STARTS[year as usize - 1];
+            return Date::from_calendar_date(y, time::Month::September, day)
+                .map_err(|_| Error::OutOfRange);
+        }
+        let offset = i64::from(self.offset());
+        let years = i64::from(year - first_computed);
+        let sextiles = sextiles_before(i64::from(year) + offset)
+            - sextiles_before(i64::from(first_computed) + offset);
+        self.year_start(first_computed)?
+            .checked_add(Duration::days(365 * years + sextiles))
+            .ok_or(Error::OutOfRange)
+    }
+
+    /// `month` from 1 for Vendémiaire to 13 for the sans-culottides
+    pub fn date(self, year: i32, month: u8, day: u8) -> Result<RepublicanDate, Error> {
+        if year < 1 {
+            return Err(Error::BeforeEpoch);
+        }
+        let month = month
+            .checked_sub(1)
+            .and_then(|m| Month::try_from(m).ok())
+            .ok_or(Error::InvalidDay)?;
+        let days = match month {
+            Month::SC if self.is_sextile(year)? => 6,
+            Month::SC => 5,
+            _ => 30,
+        };
+        if day == 0 || day > days {
+            return Err(Error::InvalidDay);
+        }
+        Ok(RepublicanDate { year, month, day })
+    }
+
+    pub fn to_republican(self, date: Date) -> Result<RepublicanDate, Error> {
+        if date < self.year_start(1)? {
+            return Err(Error::BeforeEpoch);
+        }
+        // the years start in September
+        let mut year = date.year() - 1791;
+        if date < self.year_start(year)? {
+            year -= 1;
+        }
+        let day_of_year = (date - self.year_start(year)?).whole_days();
+        RepublicanDate::from_yd(year, day_of_year)
+    }
+
+    pub fn to_gregorian(self, date: &RepublicanDate) -> Result<Date, Error> {
+        let day_of_year = date.month as i64 * 30 + date.day as i64 - 1;
+        self.year_start(date.year)?
+            .checked_add(Duration::days(day_of_year))
+            .ok_or(Error::OutOfRange)
+    }
+
+    /// Like `9 ventôse 233`, `1er Vendémiaire an I` or `3 sans-culottides an CCXXXIII`
+    pub fn parse(self, s: &str) -> Result<RepublicanDate, Error> {
+        let words = s.split_whitespace().collect::<Vec<_>>();
+        let (day, rest) = words.split_first().ok_or(Error::Unparseable)?;
+        let (year, rest) = rest.split_last().ok_or(Error::Unparseable)?;
+        let month = match rest {
+            [month @ .., an] if an.eq_ignore_ascii_case("an") => month,
+            month => month,
+        };
+        let day = day
+            .strip_suffix("er")
+            .unwrap_or(day)
+            .parse::<u8>()
+            .map_err(|_| Error::Unparseable)?;
+        let month = normalize(&month.join(" "));
+        let month = (0..=12)
+            .map(|m| Month::try_from(m).expect("13 months"))
+            .position(|m| normalize(&m.to_string()) == month)
+            .ok_or(Error::Unparseable)?;
+        let year = match year.parse::<i32>() {
+            Ok(year) => year,
+            Err(_) => from_roman(year).ok_or(Error::Unparseable)?,
+        };
+        self.date(year, month as u8 + 1, day)
+    }
+}
+
+/// How many years before `year`, from the year 1, are sextile by the Romme rule
+fn sextiles_before(year: i64) -> i64 {
+    let y = year - 1;
+    y / 4 - y / 100 + y / 400
+}
+
+/// 1 Vendémiaire of the given year, with the default rule
+pub fn year_start(year: i32) -> Result<Date, Error> {
+    SextileRule::default().year_start(year)
+}
+
+/// With a 6th sans-culottide, with the default rule
+pub fn is_sextile(year: i32) -> Result<bool, Error> {
+    SextileRule::default().is_sextile(year)
+}
 
 #[allow(dead_code)] // never constructed because I'm using the mapping to u8
 #[derive(Eq, PartialEq, Debug, Clone, Copy)]
@@ -54,7 +249,7 @@ impl TryFrom<u8> for Month {
     }
 }
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
 pub struct RepublicanDate {
     year: i32,
     month: Month,
@@ -80,9 +275,25 @@ impl std::fmt::Display for RepublicanDate {
 // but with less features (at least for now)
 
 impl RepublicanDate {
-    fn from_yd(y: i32, day_of_year: i64) -> Result<Self, &'static str> {
+    /// `month` from 1 for Vendémiaire to 13 for the sans-culottides, with the
+    /// default sextile rule
+    pub fn new(year: i32, month: u8, day: u8) -> Result<Self, Error> {
+        SextileRule::default().date(year, month, day)
+    }
+
+    /// Like `9 Ventôse 233`, without the day symbol nor name
+    pub fn short(&self) -> String {
+        format!("{} {} {}", self.day, self.month, self.year)
+    }
+
+    /// With the default sextile rule
+    pub fn to_gregorian(&self) -> Result<Date, Error> {
+        SextileRule::default().to_gregorian(self)
+    }
+
+    fn from_yd(y: i32, day_of_year: i64) -> Result<Self, Error> {
         let raw_m = day_of_year / 30;
-        let month = Month::try_from(raw_m as u8)?; // .context(format!("cannot find a month for {}", raw_m))?;
+        let month = Month::try_from(raw_m as u8).map_err(|_| Error::OutOfRange)?;
         let day = day_of_year - raw_m * 30 + 1; // 0 based
         Ok(RepublicanDate {
             year: y,
@@ -109,407 +320,107 @@ impl RepublicanDate {
     }
 
     /// name of a plant, tool, animal or symbol of the peasan world associated
-    /// to this day
-    pub fn day_symbol(&self) -> &'static str {
-        use Month::*;
-        match (self.month, self.day) {
-            (Vnd, 1) => "du raisin",
-            (Vnd, 2) => "du safran",
-            (Vnd, 3) => "de la châtaigne",
-            (Vnd, 4) => "de la colchique",
-            (Vnd, 5) => "du cheval",
-            (Vnd, 6) => "de la balsamine",
-            (Vnd, 7) => "de la carotte",
-            (Vnd, 8) => "de l'amaranthe",
-            (Vnd, 9) => "du panais",
-            (Vnd, 10) => "de la cuve",
-            (Vnd, 11) => "de la pomme de terre",
-            (Vnd, 12) => "de l'immortelle",
-            (Vnd, 13) => "du potiron",
-            (Vnd, 14) => "de la réséda",
-            (Vnd, 15) => "de l'âne",
-            (Vnd, 16) => "de la belle de nuit",
-            (Vnd, 17) => "de la citrouille",
-            (Vnd, 18) => "du sarrasin",
-            (Vnd, 19) => "du tournesol",
-            (Vnd, 20) => "du pressoir",
-            (Vnd, 21) => "du chanvre",
-            (Vnd, 22) => "de la pêche",
-            (Vnd, 23) => "du navet",
-            (Vnd, 24) => "de l'amaryllis",
-            (Vnd, 25) => "du bœuf",
-            (Vnd, 26) => "de l'aubergine",
-            (Vnd, 27) => "du piment",
-            (Vnd, 28) => "de la tomate",
-            (Vnd, 29) => "de l'orge",
-            (Vnd, 30) => "du tonneau",
-            (Bru, 1) => "de la pomme",
-            (Bru, 2) => "du céleri",
-            (Bru, 3) => "de la poire",
-            (Bru, 4) => "de la betterave",
-            (Bru, 5) => "de l'oie",
-            (Bru, 6) => "de l'héliotrope",
-            (Bru, 7) => "de la figue",
-            (Bru, 8) => "de la scorsonère",
-            (Bru, 9) => "de l'alisier",
-            (Bru, 10) => "de la charrue",
-            (Bru, 11) => "du salsifis",
-            (Bru, 12) => "de la mâcre",
-            (Bru, 13) => "du topinambour",
-            (Bru, 14) => "de l'endive",
-            (Bru, 15) => "du dindon",
-            (Bru, 16) => "du chervis",
-            (Bru, 17) => "du cresson",
-            (Bru, 18) => "de la dentelaire",
-            (Bru, 19) => "de la grenade",
-            (Bru, 20) => "de la herse",
-            (Bru, 21) => "de la bacchante",
-            (Bru, 22) => "de l'azerole",
-            (Bru, 23) => "de la garance",
-            (Bru, 24) => "de l'orange",
-            (Bru, 25) => "du faisan",
-            (Bru, 26) => "de la pistache",
-            (Bru, 27) => "du macjonc",
-            (Bru, 28) => "du coing",
-            (Bru, 29) => "du cormier",
-            (Bru, 30) => "du rouleau",
-            (Fri, 1) => "de la raiponce",
-            (Fri, 2) => "du turneps",
-            (Fri, 3) => "de la chicorée",
-            (Fri, 4) => "du nèfle",
-            (Fri, 5) => "du cochon",
-            (Fri, 6) => "de la mâche",
-            (Fri, 7) => "du chou-fleur",
-            (Fri, 8) => "du miel",
-            (Fri, 9) => "de la genièvre",
-            (Fri, 10) => "de la pioche",
-            (Fri, 11) => "de la cire",
-            (Fri, 12) => "de la cire",
-            (Fri, 13) => "du cèdre",
-            (Fri, 14) => "du sapin",
-            (Fri, 15) => "du chevreuil",
-            (Fri, 16) => "de l'ajonc",
-            (Fri, 17) => "du cyprès",
-            (Fri, 18) => "du lierre",
-            (Fri, 19) => "de la sabine",
-            (Fri, 20) => "du hoyau",
-            (Fri, 21) => "de l'érable à sucre",
-            (Fri, 22) => "de la bruyère",
-            (Fri, 23) => "du roseau",
-            (Fri, 24) => "de l'oseille",
-            (Fri, 25) => "du grillon",
-            (Fri, 26) => "du pignon",
-            (Fri, 27) => "de la liège",
-            (Fri, 28) => "de la truffe",
-            (Fri, 29) => "de l'olive",
-            (Fri, 30) => "de la pelle",
-            (Niv, 1) => "de la tourbe",
-            (Niv, 2) => "de la houille",
-            (Niv, 3) => "du bitume",
-            (Niv, 4) => "du soufre",
-            (Niv, 5) => "du chien",
-            (Niv, 6) => "de la lave",
-            (Niv, 7) => "de la terre végétale",
-            (Niv, 8) => "du fumier",
-            (Niv, 9) => "du salpêtre",
-            (Niv, 10) => "du fléau",
-            (Niv, 11) => "du granit",
-            (Niv, 12) => "de l'argile",
-            (Niv, 13) => "de l'ardoise",
-            (Niv, 14) => "du grès",
-            (Niv, 15) => "du lapin",
-            (Niv, 16) => "du silex",
-            (Niv, 17) => "de la marne",
-            (Niv, 18) => "de la pierre à chaux",
-            (Niv, 19) => "du marbre",
-            (Niv, 20) => "du van",
-            (Niv, 21) => "de la pierre à plâtre",
-            (Niv, 22) => "du sel",
-            (Niv, 23) => "du fer",
-            (Niv, 24) => "du cuivre",
-            (Niv, 25) => "du chat",
-            (Niv, 26) => "de l'étain",
-            (Niv, 27) => "du plomb",
-            (Niv, 28) => "du zinc",
-            (Niv, 29) => "du mercure",
-            (Niv, 30) => "du crible",
-            (Plu, 1) => "de lauréole",
-            (Plu, 2) => "de la mousse",
-            (Plu, 3) => "du fragon",
-            (Plu, 4) => "du perce-neige",
-            (Plu, 5) => "du taureau",
-            (Plu, 6) => "du laurier-tin",
-            (Plu, 7) => "de l'amadouvier",
-            (Plu, 8) => "du mézéréon",
-            (Plu, 9) => "du peuplier",
-            (Plu, 10) => "de la cognée",
-            (Plu, 11) => "de l'ellébore",
-            (Plu, 12) => "du brocoli",
-            (Plu, 13) => "du laurier",
-            (Plu, 14) => "de l'avelinier",
-            (Plu, 15) => "de la vache",
-            (Plu, 16) => "du buis",
-            (Plu, 17) => "du lichen",
-            (Plu, 18) => "de l'if",
-            (Plu, 19) => "de la pulmonaire",
-            (Plu, 20) => "de la serpette",
-            (Plu, 21) => "de la thlaspi",
-            (Plu, 22) => "du thimele",
-            (Plu, 23) => "du chiendent",
-            (Plu, 24) => "de la trainasse",
-            (Plu, 25) => "du lièvre",
-            (Plu, 26) => "du guède",
-            (Plu, 27) => "du noisetier",
-            (Plu, 28) => "de la cyclamen",
-            (Plu, 29) => "de la chélidoine",
-            (Plu, 30) => "du traîneau",
-            (Vnt, 1) => "du tussilage",
-            (Vnt, 2) => "du cornouiller",
-            (Vnt, 3) => "du violier",
-            (Vnt, 4) => "du troène",
-            (Vnt, 5) => "du bouc",
-            (Vnt, 6) => "des asaret",
-            (Vnt, 7) => "de l'alaterne",
-            (Vnt, 8) => "de la violette",
-            (Vnt, 9) => "du marceau",
-            (Vnt, 10) => "de la bêche",
-            (Vnt, 11) => "de la narcisse",
-            (Vnt, 12) => "de l'orme",
-            (Vnt, 13) => "des fumeterre",
-            (Vnt, 14) => "de la vélar",
-            (Vnt, 15) => "de la chèvre",
-            (Vnt, 16) => "de l'épinard",
-            (Vnt, 17) => "de la doronic",
-            (Vnt, 18) => "du mouron",
-            (Vnt, 19) => "du cerfeuil",
-            (Vnt, 20) => "du cordeau",
-            (Vnt, 21) => "de la mandragore",
-            (Vnt, 22) => "du persil",
-            (Vnt, 23) => "du cochléaria",
-            (Vnt, 24) => "de la pâquerette",
-            (Vnt, 25) => "du thon",
-            (Vnt, 26) => "du pissenlit",
-            (Vnt, 27) => "de la sylvie",
-            (Vnt, 28) => "de la capillaire",
-            (Vnt, 29) => "du frêne",
-            (Vnt, 30) => "du plantoir",
-            (Ger, 1) => "de la primevère",
-            (Ger, 2) => "du platane",
-            (Ger, 3) => "de l'asperge",
-            (Ger, 4) => "de la tulipe",
-            (Ger, 5) => "de la poule",
-            (Ger, 6) => "de la bette",
-            (Ger, 7) => "du bouleau",
-            (Ger, 8) => "de la jonquille",
-            (Ger, 9) => "de l'aulne",
-            (Ger, 10) => "du couvoir",
-            (Ger, 11) => "de la pervenche",
-            (Ger, 12) => "du charme",
-            (Ger, 13) => "de la morille",
-            (Ger, 14) => "de l'hêtre",
-            (Ger, 15) => "de l'abeille",
-            (Ger, 16) => "de la laitue",
-            (Ger, 17) => "du mélèze",
-            (Ger, 18) => "de la ciguë",
-            (Ger, 19) => "du radis",
-            (Ger, 20) => "de la ruche",
-            (Ger, 21) => "du gainier",
-            (Ger, 22) => "de la romaine",
-            (Ger, 23) => "du marronnier",
-            (Ger, 24) => "de la roquette",
-            (Ger, 25) => "du pigeon",
-            (Ger, 26) => "du lilas",
-            (Ger, 27) => "de l'anémone",
-            (Ger, 28) => "de la pensée",
-            (Ger, 29) => "de la myrtille",
-            (Ger, 30) => "du greffoir",
-            (Flo, 1) => "de la rose",
-            (Flo, 2) => "du chêne",
-            (Flo, 3) => "de la fougère",
-            (Flo, 4) => "de l'aubépine",
-            (Flo, 5) => "du rossignol",
-            (Flo, 6) => "de l'ancolie",
-            (Flo, 7) => "du muguet",
-            (Flo, 8) => "du champignon",
-            (Flo, 9) => "de la hyacinthe",
-            (Flo, 10) => "du râteau",
-            (Flo, 11) => "de la rhubarbe",
-            (Flo, 12) => "du sainfoin",
-            (Flo, 13) => "du bâton-d'or",
-            (Flo, 14) => "du chamérisier",
-            (Flo, 15) => "du ver à soie",
-            (Flo, 16) => "de la consoude",
-            (Flo, 17) => "de la pimprenelle",
-            (Flo, 18) => "de la corbeille d'or",
-            (Flo, 19) => "de l'arroche",
-            (Flo, 20) => "du sarcloir",
-            (Flo, 21) => "de la statice",
-            (Flo, 22) => "de la fritillaire",
-            (Flo, 23) => "de la bourrache",
-            (Flo, 24) => "de la valériane",
-            (Flo, 25) => "de la carpe",
-            (Flo, 26) => "du fusain",
-            (Flo, 27) => "de la civette",
-            (Flo, 28) => "de la buglosse",
-            (Flo, 29) => "de la sénevé",
-            (Flo, 30) => "de la houlette",
-            (Pra, 1) => "de la luzerne",
-            (Pra, 2) => "de l'hémérocalle",
-            (Pra, 3) => "du trèfle",
-            (Pra, 4) => "de l'angélique",
-            (Pra, 5) => "du canard",
-            (Pra, 6) => "de la mélisse",
-            (Pra, 7) => "du fromental",
-            (Pra, 8) => "du martagon",
-            (Pra, 9) => "du serpolet",
-            (Pra, 10) => "de la faux",
-            (Pra, 11) => "de la fraise",
-            (Pra, 12) => "de la bétoine",
-            (Pra, 13) => "du pois",
-            (Pra, 14) => "de l'acacia",
-            (Pra, 15) => "de la caille",
-            (Pra, 16) => "de l'œillet",
-            (Pra, 17) => "du sureau",
-            (Pra, 18) => "du pavot",
-            (Pra, 19) => "du tilleul",
-            (Pra, 20) => "de la fourche",
-            (Pra, 21) => "du barbeau",
-            (Pra, 22) => "de la camomille",
-            (Pra, 23) => "du chèvrefeuille",
-            (Pra, 24) => "de la caille-lait",
-            (Pra, 25) => "de la tanche",
-            (Pra, 26) => "du jasmin",
-            (Pra, 27) => "de la verveine",
-            (Pra, 28) => "du thym",
-            (Pra, 29) => "de la pivoine",
-            (Pra, 30) => "du chariot",
-            (Mes, 1) => "du seigle",
-            (Mes, 2) => "de l'avoine",
-            (Mes, 3) => "de l'oignon",
-            (Mes, 4) => "de la véronique",
-            (Mes, 5) => "du mulet",
-            (Mes, 6) => "du romarin",
-            (Mes, 7) => "du concombre",
-            (Mes, 8) => "de l'échalote",
-            (Mes, 9) => "de l'absinthe",
-            (Mes, 10) => "de la faucille",
-            (Mes, 11) => "de la coriandre",
-            (Mes, 12) => "de l'artichaut",
-            (Mes, 13) => "de la giroflée",
-            (Mes, 14) => "de la lavande",
-            (Mes, 15) => "du chamois",
-            (Mes, 16) => "du tabac",
-            (Mes, 17) => "de la groseille",
-            (Mes, 18) => "de la gesse",
-            (Mes, 19) => "de la cerise",
-            (Mes, 20) => "du parc",
-            (Mes, 21) => "de la menthe",
-            (Mes, 22) => "du cumin",
-            (Mes, 23) => "du haricot",
-            (Mes, 24) => "de l'orcanète",
-            (Mes, 25) => "de la pintade",
-            (Mes, 26) => "de la sauge",
-            (Mes, 27) => "de l'ail",
-            (Mes, 28) => "de la vesce",
-            (Mes, 29) => "du blé",
-            (Mes, 30) => "de la chalemie",
-            (The, 1) => "de l'épeautre",
-            (The, 2) => "du bouillon-blanc",
-            (The, 3) => "du melon",
-            (The, 4) => "de l'ivraie",
-            (The, 5) => "du bélier",
-            (The, 6) => "de la prêle",
-            (The, 7) => "de l'armoise",
-            (The, 8) => "de la carthame",
-            (The, 9) => "de la mûre",
-            (The, 10) => "de l'arrosoir",
-            (The, 11) => "de la panic",
-            (The, 12) => "de la salicorne",
-            (The, 13) => "de l'abricot",
-            (The, 14) => "du basilic",
-            (The, 15) => "de la brebis",
-            (The, 16) => "de la guimauve",
-            (The, 17) => "du lin",
-            (The, 18) => "de l'amande",
-            (The, 19) => "de la gentiane",
-            (The, 20) => "de l'écluse",
-            (The, 21) => "de la carline",
-            (The, 22) => "du câprier",
-            (The, 23) => "de la lentille",
-            (The, 24) => "de l'aunée",
-            (The, 25) => "de la loutre",
-            (The, 26) => "de la myrte",
-            (The, 27) => "du colza",
-            (The, 28) => "du lupin",
-            (The, 29) => "du coton",
-            (The, 30) => "du moulin",
-            (Fru, 1) => "de la prune",
-            (Fru, 2) => "du millet",
-            (Fru, 3) => "du lycoperdon",
-            (Fru, 4) => "de l'escourgeon",
-            (Fru, 5) => "du saumon",
-            (Fru, 6) => "de la tubéreuse",
-            (Fru, 7) => "du sucrion",
-            (Fru, 8) => "de l'apocyn",
-            (Fru, 9) => "de la réglisse",
-            (Fru, 10) => "de l'échelle",
-            (Fru, 11) => "de la pastèque",
-            (Fru, 12) => "du fenouil",
-            (Fru, 13) => "de l'épine-vinette",
-            (Fru, 14) => "de la noix",
-            (Fru, 15) => "de la truite",
-            (Fru, 16) => "du citron",
-            (Fru, 17) => "de la cardère",
-            (Fru, 18) => "du nerprun",
-            (Fru, 19) => "de la tagette",
-            (Fru, 20) => "de la hotte",
-            (Fru, 21) => "de l'églantier",
-            (Fru, 22) => "de la noisette",
-            (Fru, 23) => "du houblon",
-            (Fru, 24) => "du sorgho",
-            (Fru, 25) => "de l'écrevisse",
-            (Fru, 26) => "de la bigarade",
-            (Fru, 27) => "de la verge d'or",
-            (Fru, 28) => "du maïs",
-            (Fru, 29) => "du marron",
-            (Fru, 30) => "du panier",
-            (SC, 1) => "de la fête de la vertu",
-            (SC, 2) => "de la fête du génie",
-            (SC, 3) => "de la fête du travail",
-            (SC, 4) => "de la fête de l'opinion",
-            (SC, 5) => "de la fête des récompenses",
-            (SC, 6) => "de la fête de la révolution",
-            _ => "ERROR",
-        }
+    /// to this day, with its article like `du raisin`
+    pub fn day_symbol(&self) -> String {
+        rural::with_article(self.month, self.day).expect("a valid day")
+    }
+
+    pub fn year(&self) -> i32 {
+        self.year
     }
 
+    /// From 1 to 30, or to 6 for the sans-culottides
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    /// Like `Ventôse` or `Sans-Culottides`
+    pub fn month_name(&self) -> String {
+        self.month.to_string()
+    }
 }
 
+/// With the default sextile rule
 impl TryFrom<Date> for RepublicanDate {
-    type Error = &'static str;
+    type Error = Error;
 
     fn try_from(value: Date) -> Result<Self, Self::Error> {
-        let french_era_end =
-            Date::from_calendar_date(1811, time::Month::September, 23).map_err(|e| e.name())?;
-        let duration_since_french_era_end = value - french_era_end;
-        if duration_since_french_era_end.is_negative() {
-            return Err("Can only convert date from after the official end of the calendar");
+        SextileRule::default().to_republican(value)
+    }
+}
+
+/// With the default sextile rule, see `SextileRule::parse`
+impl FromStr for RepublicanDate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SextileRule::default().parse(s)
+    }
+}
+
+/// Lowercase, without accents nor dashes, to compare the month and item names
+fn normalize(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    for c in name.to_lowercase().chars() {
+        match c {
+            'é' | 'è' | 'ê' | 'ë' => normalized.push('e'),
+            'à' | 'â' => normalized.push('a'),
+            'î' | 'ï' => normalized.push('i'),
+            'ô' => normalized.push('o'),
+            'û' | 'ù' | 'ü' => normalized.push('u'),
+            'ç' => normalized.push('c'),
+            'œ' => normalized.push_str("oe"),
+            '-' => normalized.push(' '),
+            c => normalized.push(c),
         }
+    }
+    normalized
+}
 
-        // create a fake Date object so we can perform conversion on it
-        // and then extract the year and day of year. In the republican calendar
-        // the last year was 20, but at that time, there was no leap year yet, so
-        // artificially pad it.
-        let padding = 2000;
-        let fake_french_date = Date::from_calendar_date(20 + padding, time::Month::January, 1)
-            .map_err(|e| e.name())?;
-        let french_date = fake_french_date + duration_since_french_era_end;
-        let tmp_date =
-            Date::from_calendar_date(french_date.year(), time::Month::January, 1).unwrap();
-        let day_of_year = (french_date - tmp_date).whole_days();
+const ROMAN: [(u32, &str); 13] = [
+    (1000, "M"),
+    (900, "CM"),
+    (500, "D"),
+    (400, "CD"),
+    (100, "C"),
+    (90, "XC"),
+    (50, "L"),
+    (40, "XL"),
+    (10, "X"),
+    (9, "IX"),
+    (5, "V"),
+    (4, "IV"),
+    (1, "I"),
+];
 
-        RepublicanDate::from_yd(french_date.year() - padding, day_of_year)
+/// Like `CCXXXIII` for 233 or `CDXLIV` for 444, with the subtractive notation.
+/// Empty for 0, and the thousands are repeated beyond 3999, like `MMMM`.
+pub fn to_roman(mut n: u32) -> String {
+    let mut roman = String::new();
+    for (value, numeral) in ROMAN {
+        while n >= value {
+            roman.push_str(numeral);
+            n -= value;
+        }
     }
+    roman
+}
+
+/// Only the canonical numerals, like `IV` and not `IIII`
+pub fn from_roman(roman: &str) -> Option<i32> {
+    let roman = roman.to_uppercase();
+    let mut rest = roman.as_str();
+    let mut n = 0;
+    for (value, numeral) in ROMAN {
+        while let Some(r) = rest.strip_prefix(numeral) {
+            n += value;
+            rest = r;
+        }
+    }
+    if !rest.is_empty() || n == 0 || to_roman(n) != roman {
+        return None;
+    }
+    Some(n as i32)
 }
 
 #[cfg(test)]
@@ -529,4 +440,244 @@ mod test {
             })
         );
     }
+
+    fn gregorian(year: i32, month: time::Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    fn republican(year: i32, month: Month, day: u8) -> RepublicanDate {
+        RepublicanDate { year, month, day }
+    }
+
+    #[test]
+    fn test_epoch() {
+        use time::Month::September;
+        assert_eq!(
+            RepublicanDate::try_from(gregorian(1792, September, 22)),
+            Ok(republican(1, Month::Vnd, 1))
+        );
+        assert_eq!(
+            RepublicanDate::try_from(gregorian(1792, September, 21)),
+            Err(Error::BeforeEpoch)
+        );
+        assert_eq!(
+            RepublicanDate::try_from(gregorian(1515, September, 13)),
+            Err(Error::BeforeEpoch)
+        );
+        assert_eq!(RepublicanDate::new(0, 1, 1), Err(Error::BeforeEpoch));
+        assert_eq!(
+            republican(1, Month::Vnd, 1).to_gregorian(),
+            Ok(gregorian(1792, September, 22))
+        );
+    }
+
+    #[test]
+    fn test_historical_dates() {
+        use time::Month::*;
+        for (date, expected) in [
+            (gregorian(1794, July, 27), republican(2, Month::The, 9)),
+            (gregorian(1799, November, 9), republican(8, Month::Bru, 18)),
+            (gregorian(1805, December, 2), republican(14, Month::Fri, 11)),
+        ] {
+            assert_eq!(RepublicanDate::try_from(date), Ok(expected), "{date}");
+        }
+    }
+
+    #[test]
+    fn test_year_boundaries() {
+        use time::Month::September;
+        for (date, expected) in [
+            // year 3 is sextile
+            (gregorian(1795, September, 22), republican(3, Month::SC, 6)),
+            (gregorian(1795, September, 23), republican(4, Month::Vnd, 1)),
+            (gregorian(1796, September, 21), republican(4, Month::SC, 5)),
+            (gregorian(1796, September, 22), republican(5, Month::Vnd, 1)),
+            // from the historical years to the computed ones
+            (gregorian(1807, September, 22), republican(15, Month::SC, 5)),
+            (gregorian(1807, September, 23), republican(16, Month::Vnd, 1)),
+            (gregorian(1808, September, 22), republican(16, Month::SC, 6)),
+            (gregorian(1808, September, 23), republican(17, Month::Vnd, 1)),
+            (gregorian(1811, September, 22), republican(19, Month::SC, 5)),
+            (gregorian(1811, September, 23), republican(20, Month::Vnd, 1)),
+            (gregorian(1812, September, 22), republican(20, Month::SC, 6)),
+            (gregorian(1812, September, 23), republican(21, Month::Vnd, 1)),
+            (gregorian(2024, September, 21), republican(232, Month::SC, 6)),
+            (gregorian(2024, September, 22), republican(233, Month::Vnd, 1)),
+            (gregorian(2025, September, 21), republican(233, Month::SC, 5)),
+            (gregorian(2025, September, 22), republican(234, Month::Vnd, 1)),
+        ] {
+            assert_eq!(RepublicanDate::try_from(date), Ok(expected), "{date}");
+            assert_eq!(expected.to_gregorian(), Ok(date), "{}", expected.short());
+        }
+    }
+
+    #[test]
+    fn test_sextile_years() {
+        let sextile = (1..=24).filter(|y| is_sextile(*y).unwrap()).collect::<Vec<_>>();
+        assert_eq!(sextile, vec![3, 7, 11, 16, 20, 24]);
+        assert_eq!(is_sextile(100), Ok(false));
+        assert_eq!(is_sextile(200), Ok(false));
+        assert_eq!(is_sextile(400), Ok(true));
+        assert_eq!(is_sextile(232), Ok(true));
+        assert_eq!(is_sextile(233), Ok(false));
+        assert_eq!(is_sextile(0), Err(Error::BeforeEpoch));
+        assert_eq!(SextileRule::default(), SextileRule::Romme);
+    }
+
+    #[test]
+    fn test_continuous_rule() {
+        let rule = SextileRule::Continuous;
+        let sextile = (1..=24)
+            .filter(|y| rule.is_sextile(*y).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(sextile, vec![3, 7, 11, 15, 19, 23]);
+        assert_eq!(rule.is_sextile(99), Ok(false));
+        assert_eq!(rule.is_sextile(399), Ok(true));
+
+        use time::Month::*;
+        for (date, expected) in [
+            (gregorian(1794, July, 27), republican(2, Month::The, 9)),
+            (gregorian(1807, September, 23), republican(15, Month::SC, 6)),
+            (gregorian(1807, September, 24), republican(16, Month::Vnd, 1)),
+            (gregorian(2024, September, 21), republican(232, Month::SC, 5)),
+            (gregorian(2024, September, 22), republican(233, Month::Vnd, 1)),
+        ] {
+            assert_eq!(rule.to_republican(date), Ok(expected), "{date}");
+            assert_eq!(rule.to_gregorian(&expected), Ok(date), "{}", expected.short());
+        }
+        assert_eq!(rule.date(232, 13, 6), Err(Error::InvalidDay));
+        assert_eq!(rule.date(231, 13, 6), Ok(republican(231, Month::SC, 6)));
+        assert_eq!(rule.parse("6 sans-culottides an XV"), Ok(republican(15, Month::SC, 6)));
+        assert_eq!(
+            "6 sans-culottides an XV".parse::<RepublicanDate>(),
+            Err(Error::InvalidDay),
+            "not with the default rule"
+        );
+    }
+
+    #[test]
+    fn test_known_correspondences() {
+        use time::Month::*;
+        for (input, date) in [
+            ("1er vendémiaire an I", gregorian(1792, September, 22)),
+            ("9 thermidor an II", gregorian(1794, July, 27)),
+            ("18 brumaire an VIII", gregorian(1799, November, 9)),
+            ("11 frimaire an XIV", gregorian(1805, December, 2)),
+            ("25 nivôse an CCXXIX", gregorian(2021, January, 14)),
+            ("1er vendémiaire an CCXXXIII", gregorian(2024, September, 22)),
+            ("11 ventôse an CCXXXIII", gregorian(2025, March, 1)),
+        ] {
+            for rule in [SextileRule::Romme, SextileRule::Continuous] {
+                let rd = rule.parse(input).unwrap();
+                assert_eq!(rule.to_gregorian(&rd), Ok(date), "{input} {rule:?}");
+                assert_eq!(rule.to_republican(date), Ok(rd), "{date} {rule:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_sans_culottides() {
+        assert_eq!(RepublicanDate::new(232, 13, 6), Ok(republican(232, Month::SC, 6)));
+        assert_eq!(RepublicanDate::new(233, 13, 5), Ok(republican(233, Month::SC, 5)));
+        assert_eq!(RepublicanDate::new(233, 13, 6), Err(Error::InvalidDay));
+        assert_eq!(RepublicanDate::new(3, 13, 6), Ok(republican(3, Month::SC, 6)));
+        assert_eq!(RepublicanDate::new(4, 13, 6), Err(Error::InvalidDay));
+        assert_eq!(RepublicanDate::new(233, 6, 31), Err(Error::InvalidDay));
+        assert_eq!(RepublicanDate::new(233, 6, 0), Err(Error::InvalidDay));
+        assert_eq!(RepublicanDate::new(233, 14, 1), Err(Error::InvalidDay));
+        assert_eq!(RepublicanDate::new(233, 0, 1), Err(Error::InvalidDay));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for rule in [SextileRule::Romme, SextileRule::Continuous] {
+            round_trip(rule);
+        }
+    }
+
+    fn round_trip(rule: SextileRule) {
+        let mut date = gregorian(1792, time::Month::September, 22);
+        let end = gregorian(2200, time::Month::January, 1);
+        let mut previous: Option<RepublicanDate> = None;
+        while date < end {
+            let rd = rule.to_republican(date).unwrap();
+            assert_eq!(rule.to_gregorian(&rd), Ok(date), "{}", rd.short());
+            if let Some(previous) = previous {
+                let next_day = match (previous.month, previous.day) {
+                    (Month::SC, day) if rd.year == previous.year + 1 => {
+                        let last = if rule.is_sextile(previous.year).unwrap() { 6 } else { 5 };
+                        (rd.month, rd.day) == (Month::Vnd, 1) && day == last
+                    }
+                    (Month::SC, _) => (rd.month, rd.day) == (Month::SC, previous.day + 1),
+                    (month, 30) => rd.month as u8 == month as u8 + 1 && rd.day == 1,
+                    (month, day) => rd.month == month && rd.day == day + 1,
+                };
+                assert!(next_day, "{} after {}", rd.short(), previous.short());
+            }
+            previous = Some(rd);
+            date = date.next_day().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        for (input, expected) in [
+            ("9 ventôse 233", republican(233, Month::Vnt, 9)),
+            ("9 Ventose an 233", republican(233, Month::Vnt, 9)),
+            ("9 VENTÔSE an CCXXXIII", republican(233, Month::Vnt, 9)),
+            ("1er vendémiaire an I", republican(1, Month::Vnd, 1)),
+            ("18 brumaire an viii", republican(8, Month::Bru, 18)),
+            ("6 sans-culottides 3", republican(3, Month::SC, 6)),
+            ("5 Sans Culottides 233", republican(233, Month::SC, 5)),
+        ] {
+            assert_eq!(input.parse::<RepublicanDate>(), Ok(expected), "{input}");
+        }
+        for unparseable in [
+            "",
+            "9",
+            "9 ventôse",
+            "ventôse 233",
+            "9 mars 233",
+            "9 ventôse an",
+            "9 ventôse IIII",
+            "neuf ventôse 233",
+        ] {
+            assert_eq!(
+                unparseable.parse::<RepublicanDate>(),
+                Err(Error::Unparseable),
+                "{unparseable:?}"
+            );
+        }
+        assert_eq!("31 ventôse 233".parse::<RepublicanDate>(), Err(Error::InvalidDay));
+        assert_eq!("1 ventôse 0".parse::<RepublicanDate>(), Err(Error::BeforeEpoch));
+    }
+
+    #[test]
+    fn test_roman() {
+        for (n, roman) in [
+            (1, "I"),
+            (4, "IV"),
+            (8, "VIII"),
+            (14, "XIV"),
+            (233, "CCXXXIII"),
+            (240, "CCXL"),
+            (249, "CCXLIX"),
+            (294, "CCXCIV"),
+            (399, "CCCXCIX"),
+            (400, "CD"),
+            (444, "CDXLIV"),
+            (949, "CMXLIX"),
+            (1994, "MCMXCIV"),
+            (3999, "MMMCMXCIX"),
+        ] {
+            assert_eq!(to_roman(n), roman);
+            assert_eq!(from_roman(roman), Some(n as i32));
+        }
+        assert_eq!(from_roman("ccxxxiii"), Some(233));
+        assert_eq!(to_roman(0), "");
+        assert_eq!(to_roman(4000), "MMMM");
+        for invalid in ["", "IIII", "IM", "VX", "ABC"] {
+            assert_eq!(from_roman(invalid), None, "{invalid:?}");
+        }
+    }
 }
@@ -0,0 +1,491 @@
+use crate::{normalize, Month};
+
+/// The item of each day in the rural calendar of Fabre d'Églantine, a plant,
+/// an animal, a tool or a mineral, with its article. The 30 days of each month
+/// from Vendémiaire to Fructidor, then the 6 sans-culottides.
+const DAYS: [(&str, &str); 366] = [
+    // Vendémiaire
+    ("du ", "raisin"),
+    ("du ", "safran"),
+    ("de la ", "châtaigne"),
+    ("de la ", "colchique"),
+    ("du ", "cheval"),
+    ("de la ", "balsamine"),
+    ("de la ", "carotte"),
+    ("de l'", "amaranthe"),
+    ("du ", "panais"),
+    ("de la ", "cuve"),
+    ("de la ", "pomme de terre"),
+    ("de l'", "immortelle"),
+    ("du ", "potiron"),
+    ("du ", "réséda"),
+    ("de l'", "âne"),
+    ("de la ", "belle de nuit"),
+    ("de la ", "citrouille"),
+    ("du ", "sarrasin"),
+    ("du ", "tournesol"),
+    ("du ", "pressoir"),
+    ("du ", "chanvre"),
+    ("de la ", "pêche"),
+    ("du ", "navet"),
+    ("de l'", "amaryllis"),
+    ("du ", "bœuf"),
+    ("de l'", "aubergine"),
+    ("du ", "piment"),
+    ("de la ", "tomate"),
+    ("de l'", "orge"),
+    ("du ", "tonneau"),
+    // Brumaire
+    ("de la ", "pomme"),
+    ("du ", "céleri"),
+    ("de la ", "poire"),
+    ("de la ", "betterave"),
+    ("de l'", "oie"),
+    ("de l'", "héliotrope"),
+    ("de la ", "figue"),
+    ("de la ", "scorsonère"),
+    ("de l'", "alisier"),
+    ("de la ", "charrue"),
+    ("du ", "salsifis"),
+    ("de la ", "mâcre"),
+    ("du ", "topinambour"),
+    ("de l'", "endive"),
+    ("du ", "dindon"),
+    ("du ", "chervis"),
+    ("du ", "cresson"),
+    ("de la ", "dentelaire"),
+    ("de la ", "grenade"),
+    ("de la ", "herse"),
+    ("de la ", "bacchante"),
+    ("de l'", "azerole"),
+    ("de la ", "garance"),
+    ("de l'", "orange"),
+    ("du ", "faisan"),
+    ("de la ", "pistache"),
+    ("du ", "macjonc"),
+    ("du ", "coing"),
+    ("du ", "cormier"),
+    ("du ", "rouleau"),
+    // Frimaire
+    ("de la ", "raiponce"),
+    ("du ", "turneps"),
+    ("de la ", "chicorée"),
+    ("de la ", "nèfle"),
+    ("du ", "cochon"),
+    ("de la ", "mâche"),
+    ("du ", "chou-fleur"),
+    ("du ", "miel"),
+    ("du ", "genièvre"),
+    ("de la ", "pioche"),
+    ("de la ", "cire"),
+    ("du ", "raifort"),
+    ("du ", "cèdre"),
+    ("du ", "sapin"),
+    ("du ", "chevreuil"),
+    ("de l'", "ajonc"),
+    ("du ", "cyprès"),
+    ("du ", "lierre"),
+    ("de la ", "sabine"),
+    ("du ", "hoyau"),
+    ("de l'", "érable à sucre"),
+    ("de la ", "bruyère"),
+    ("du ", "roseau"),
+    ("de l'", "oseille"),
+    ("du ", "grillon"),
+    ("du ", "pignon"),
+    ("du ", "liège"),
+    ("de la ", "truffe"),
+    ("de l'", "olive"),
+    ("de la ", "pelle"),
+    // Nivôse
+    ("de la ", "tourbe"),
+    ("de la ", "houille"),
+    ("du ", "bitume"),
+    ("du ", "soufre"),
+    ("du ", "chien"),
+    ("de la ", "lave"),
+    ("de la ", "terre végétale"),
+    ("du ", "fumier"),
+    ("du ", "salpêtre"),
+    ("du ", "fléau"),
+    ("du ", "granit"),
+    ("de l'", "argile"),
+    ("de l'", "ardoise"),
+    ("du ", "grès"),
+    ("du ", "lapin"),
+    ("du ", "silex"),
+    ("de la ", "marne"),
+    ("de la ", "pierre à chaux"),
+    ("du ", "marbre"),
+    ("du ", "van"),
+    ("de la ", "pierre à plâtre"),
+    ("du ", "sel"),
+    ("du ", "fer"),
+    ("du ", "cuivre"),
+    ("du ", "chat"),
+    ("de l'", "étain"),
+    ("du ", "plomb"),
+    ("du ", "zinc"),
+    ("du ", "mercure"),
+    ("du ", "crible"),
+    // Pluviôse
+    ("de la ", "lauréole"),
+    ("de la ", "mousse"),
+    ("du ", "fragon"),
+    ("du ", "perce-neige"),
+    ("du ", "taureau"),
+    ("du ", "laurier-tin"),
+    ("de l'", "amadouvier"),
+    ("du ", "mézéréon"),
+    ("du ", "peuplier"),
+    ("de la ", "cognée"),
+    ("de l'", "ellébore"),
+    ("du ", "brocoli"),
+    ("du ", "laurier"),
+    ("de l'", "avelinier"),
+    ("de la ", "vache"),
+    ("du ", "buis"),
+    ("du ", "lichen"),
+    ("de l'", "if"),
+    ("de la ", "pulmonaire"),
+    ("de la ", "serpette"),
+    ("du ", "thlaspi"),
+    ("du ", "thimele"),
+    ("du ", "chiendent"),
+    ("de la ", "trainasse"),
+    ("du ", "lièvre"),
+    ("de la ", "guède"),
+    ("du ", "noisetier"),
+    ("du ", "cyclamen"),
+    ("de la ", "chélidoine"),
+    ("du ", "traîneau"),
+    // Ventôse
+    ("du ", "tussilage"),
+    ("du ", "cornouiller"),
+    ("du ", "violier"),
+    ("du ", "troène"),
+    ("du ", "bouc"),
+    ("de l'", "asaret"),
+    ("de l'", "alaterne"),
+    ("de la ", "violette"),
+    ("du ", "marceau"),
+    ("de la ", "bêche"),
+    ("du ", "narcisse"),
+    ("de l'", "orme"),
+    ("de la ", "fumeterre"),
+    ("du ", "vélar"),
+    ("de la ", "chèvre"),
+    ("de l'", "épinard"),
+    ("du ", "doronic"),
+    ("du ", "mouron"),
+    ("du ", "cerfeuil"),
+    ("du ", "cordeau"),
+    ("de la ", "mandragore"),
+    ("du ", "persil"),
+    ("du ", "cochléaria"),
+    ("de la ", "pâquerette"),
+    ("du ", "thon"),
+    ("du ", "pissenlit"),
+    ("de la ", "sylvie"),
+    ("de la ", "capillaire"),
+    ("du ", "frêne"),
+    ("du ", "plantoir"),
+    // Germinal
+    ("de la ", "primevère"),
+    ("du ", "platane"),
+    ("de l'", "asperge"),
+    ("de la ", "tulipe"),
+    ("de la ", "poule"),
+    ("de la ", "bette"),
+    ("du ", "bouleau"),
+    ("de la ", "jonquille"),
+    ("de l'", "aulne"),
+    ("du ", "couvoir"),
+    ("de la ", "pervenche"),
+    ("du ", "charme"),
+    ("de la ", "morille"),
+    ("du ", "hêtre"),
+    ("de l'", "abeille"),
+    ("de la ", "laitue"),
+    ("du ", "mélèze"),
+    ("de la ", "ciguë"),
+    ("du ", "radis"),
+    ("de la ", "ruche"),
+    ("du ", "gainier"),
+    ("de la ", "romaine"),
+    ("du ", "marronnier"),
+    ("de la ", "roquette"),
+    ("du ", "pigeon"),
+    ("du ", "lilas"),
+    ("de l'", "anémone"),
+    ("de la ", "pensée"),
+    ("de la ", "myrtille"),
+    ("du ", "greffoir"),
+    // Floréal
+    ("de la ", "rose"),
+    ("du ", "chêne"),
+    ("de la ", "fougère"),
+    ("de l'", "aubépine"),
+    ("du ", "rossignol"),
+    ("de l'", "ancolie"),
+    ("du ", "muguet"),
+    ("du ", "champignon"),
+    ("de la ", "hyacinthe"),
+    ("du ", "râteau"),
+    ("de la ", "rhubarbe"),
+    ("du ", "sainfoin"),
+    ("du ", "bâton-d'or"),
+    ("du ", "chamérisier"),
+    ("du ", "ver à soie"),
+    ("de la ", "consoude"),
+    ("de la ", "pimprenelle"),
+    ("de la ", "corbeille d'or"),
+    ("de l'", "arroche"),
+    ("du ", "sarcloir"),
+    ("du ", "statice"),
+    ("de la ", "fritillaire"),
+    ("de la ", "bourrache"),
+    ("de la ", "valériane"),
+    ("de la ", "carpe"),
+    ("du ", "fusain"),
+    ("de la ", "civette"),
+    ("de la ", "buglosse"),
+    ("du ", "sénevé"),
+    ("de la ", "houlette"),
+    // Prairial
+    ("de la ", "luzerne"),
+    ("de l'", "hémérocalle"),
+    ("du ", "trèfle"),
+    ("de l'", "angélique"),
+    ("du ", "canard"),
+    ("de la ", "mélisse"),
+    ("du ", "fromental"),
+    ("du ", "martagon"),
+    ("du ", "serpolet"),
+    ("de la ", "faux"),
+    ("de la ", "fraise"),
+    ("de la ", "bétoine"),
+    ("du ", "pois"),
+    ("de l'", "acacia"),
+    ("de la ", "caille"),
+    ("de l'", "œillet"),
+    ("du ", "sureau"),
+    ("du ", "pavot"),
+    ("du ", "tilleul"),
+    ("de la ", "fourche"),
+    ("du ", "barbeau"),
+    ("de la ", "camomille"),
+    ("du ", "chèvrefeuille"),
+    ("de la ", "caille-lait"),
+    ("de la ", "tanche"),
+    ("du ", "jasmin"),
+    ("de la ", "verveine"),
+    ("du ", "thym"),
+    ("de la ", "pivoine"),
+    ("du ", "chariot"),
+    // Messidor
+    ("du ", "seigle"),
+    ("de l'", "avoine"),
+    ("de l'", "oignon"),
+    ("de la ", "véronique"),
+    ("du ", "mulet"),
+    ("du ", "romarin"),
+    ("du ", "concombre"),
+    ("de l'", "échalote"),
+    ("de l'", "absinthe"),
+    ("de la ", "faucille"),
+    ("de la ", "coriandre"),
+    ("de l'", "artichaut"),
+    ("de la ", "giroflée"),
+    ("de la ", "lavande"),
+    ("du ", "chamois"),
+    ("du ", "tabac"),
+    ("de la ", "groseille"),
+    ("de la ", "gesse"),
+    ("de la ", "cerise"),
+    ("du ", "parc"),
+    ("de la ", "menthe"),
+    ("du ", "cumin"),
+    ("du ", "haricot"),
+    ("de l'", "orcanète"),
+    ("de la ", "pintade"),
+    ("de la ", "sauge"),
+    ("de l'", "ail"),
+    ("de la ", "vesce"),
+    ("du ", "blé"),
+    ("de la ", "chalemie"),
+    // Thermidor
+    ("de l'", "épeautre"),
+    ("du ", "bouillon-blanc"),
+    ("du ", "melon"),
+    ("de l'", "ivraie"),
+    ("du ", "bélier"),
+    ("de la ", "prêle"),
+    ("de l'", "armoise"),
+    ("du ", "carthame"),
+    ("de la ", "mûre"),
+    ("de l'", "arrosoir"),
+    ("du ", "panic"),
+    ("de la ", "salicorne"),
+    ("de l'", "abricot"),
+    ("du ", "basilic"),
+    ("de la ", "brebis"),
+    ("de la ", "guimauve"),
+    ("du ", "lin"),
+    ("de l'", "amande"),
+    ("de la ", "gentiane"),
+    ("de l'", "écluse"),
+    ("de la ", "carline"),
+    ("du ", "câprier"),
+    ("de la ", "lentille"),
+    ("de l'", "aunée"),
+    ("de la ", "loutre"),
+    ("du ", "myrte"),
+    ("du ", "colza"),
+    ("du ", "lupin"),
+    ("du ", "coton"),
+    ("du ", "moulin"),
+    // Fructidor
+    ("de la ", "prune"),
+    ("du ", "millet"),
+    ("du ", "lycoperdon"),
+    ("de l'", "escourgeon"),
+    ("du ", "saumon"),
+    ("de la ", "tubéreuse"),
+    ("du ", "sucrion"),
+    ("de l'", "apocyn"),
+    ("de la ", "réglisse"),
+    ("de l'", "échelle"),
+    ("de la ", "pastèque"),
+    ("du ", "fenouil"),
+    ("de l'", "épine-vinette"),
+    ("de la ", "noix"),
+    ("de la ", "truite"),
+    ("du ", "citron"),
+    ("de la ", "cardère"),
+    ("du ", "nerprun"),
+    ("de la ", "tagette"),
+    ("de la ", "hotte"),
+    ("de l'", "églantier"),
+    ("de la ", "noisette"),
+    ("du ", "houblon"),
+    ("du ", "sorgho"),
+    ("de l'", "écrevisse"),
+    ("de la ", "bigarade"),
+    ("de la ", "verge d'or"),
+    ("du ", "maïs"),
+    ("du ", "marron"),
+    ("du ", "panier"),
+    // Sans-culottides
+    ("de la ", "vertu"),
+    ("du ", "génie"),
+    ("du ", "travail"),
+    ("de l'", "opinion"),
+    ("des ", "récompenses"),
+    ("de la ", "révolution"),
+];
+
+fn index(month: Month, day: u8) -> Option<usize> {
+    let days = match month {
+        Month::SC => 6,
+        _ => 30,
+    };
+    if day == 0 || day > days {
+        return None;
+    }
+    Some(month as usize * 30 + usize::from(day) - 1)
+}
+
+/// Like `raisin` for the 1st of Vendémiaire or `vertu` for the 1st
+/// sans-culottide, `month` from 1 for Vendémiaire to 13 for the sans-culottides
+pub fn item(month: u8, day: u8) -> Option<&'static str> {
+    let month = Month::try_from(month.checked_sub(1)?).ok()?;
+    index(month, day).map(|i| DAYS[i].1)
+}
+
+/// Like `du raisin`, to go after `jour`
+pub(crate) fn with_article(month: Month, day: u8) -> Option<String> {
+    index(month, day).map(|i| format!("{}{}", DAYS[i].0, DAYS[i].1))
+}
+
+/// The day of an item, like `(6, 11)` for `narcisse` or `du Narcisse`, without
+/// caring for the case nor the accents. The month from 1 to 13.
+pub fn find(item: &str) -> Option<(u8, u8)> {
+    let item = normalize(item.trim());
+    let i = DAYS.iter().position(|(article, name)| {
+        let name = normalize(name);
+        item == name || item == normalize(article) + &name
+    })?;
+    Some(((i / 30) as u8 + 1, (i % 30) as u8 + 1))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_complete_table() {
+        assert_eq!(DAYS.len(), 12 * 30 + 6);
+        let names = DAYS
+            .iter()
+            .map(|(_, name)| normalize(name))
+            .collect::<HashSet<_>>();
+        assert_eq!(names.len(), DAYS.len(), "no duplicates");
+        for (article, name) in DAYS {
+            assert!(
+                ["du ", "de la ", "de l'", "des "].contains(&article),
+                "{article}{name}"
+            );
+            assert!(!name.is_empty());
+        }
+        for month in 1..=12 {
+            for day in 1..=30 {
+                let item = item(month, day).unwrap();
+                assert_eq!(find(item), Some((month, day)), "{item}");
+            }
+        }
+        for day in 1..=6 {
+            assert!(item(13, day).is_some());
+        }
+    }
+
+    #[test]
+    fn test_item() {
+        assert_eq!(item(1, 1), Some("raisin"));
+        assert_eq!(item(6, 11), Some("narcisse"));
+        assert_eq!(item(12, 30), Some("panier"));
+        assert_eq!(item(13, 1), Some("vertu"));
+        assert_eq!(item(13, 6), Some("révolution"));
+        assert_eq!(
+            with_article(Month::Vnt, 11),
+            Some("du narcisse".to_string())
+        );
+        assert_eq!(with_article(Month::SC, 2), Some("du génie".to_string()));
+        for (month, day) in [(0, 1), (14, 1), (1, 0), (1, 31), (13, 7)] {
+            assert_eq!(item(month, day), None, "{month} {day}");
+        }
+    }
+
+    #[test]
+    fn test_find() {
+        for (input, expected) in [
+            ("narcisse", (6, 11)),
+            ("Narcisse", (6, 11)),
+            ("du narcisse", (6, 11)),
+            ("chataigne", (1, 3)),
+            ("CHÂTAIGNE", (1, 3)),
+            ("boeuf", (1, 25)),
+            ("chou fleur", (3, 7)),
+            ("de l'orge", (1, 29)),
+            ("pomme de terre", (1, 11)),
+            ("génie", (13, 2)),
+            ("revolution", (13, 6)),
+        ] {
+            assert_eq!(find(input), Some(expected), "{input}");
+        }
+        for unknown in ["", "pomme de", "licorne", "jour du raisin"] {
+            assert_eq!(find(unknown), None, "{unknown:?}");
+        }
+    }
+}
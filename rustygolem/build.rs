@@ -0,0 +1,72 @@
+//! Records what the golem is built from, for `build_info::BUILD_INFO`
+
+use std::path::PathBuf;
+use std::process::Command;
+
+fn main() {
+    let target = std::env::var("TARGET").unwrap_or_else(|_| "unknown".to_string());
+    println!("cargo:rustc-env=RUSTYGOLEM_TARGET={target}");
+
+    // reproducible builds give the date instead
+    let now = match std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<i64>().ok())
+    {
+        Some(epoch) => time::OffsetDateTime::from_unix_timestamp(epoch)
+            .unwrap_or_else(|_| time::OffsetDateTime::now_utc()),
+        None => time::OffsetDateTime::now_utc(),
+    };
+    let date = now
+        .format(time::macros::format_description!("[year]-[month]-[day]"))
+        .expect("formatted build date");
+    println!("cargo:rustc-env=RUSTYGOLEM_BUILD_DATE={date}");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    // not a git checkout when built from a tarball, or without git at all
+    let describe = Command::new("git")
+        .args(["describe", "--tags", "--always", "--dirty"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|describe| describe.trim().to_string())
+        .filter(|describe| !describe.is_empty());
+    if let Some(describe) = describe {
+        println!("cargo:rustc-env=RUSTYGOLEM_GIT_DESCRIBE={describe}");
+        rerun_on_git_changes();
+    }
+    // once a rerun-if is printed, cargo stops rerunning this on its own
+    // when the sources change, leaving a stale -dirty and build date
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+}
+
+/// Rerun on checkouts, commits and tags, not only when HEAD moves to
+/// another branch
+fn rerun_on_git_changes() {
+    let git_dir = match Command::new("git")
+        .args(["rev-parse", "--absolute-git-dir"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+    {
+        Some(git_dir) => PathBuf::from(git_dir.trim()),
+        None => return,
+    };
+    let head = git_dir.join("HEAD");
+    // a branch checked out: the commit is in its ref, HEAD stays the same
+    let branch = std::fs::read_to_string(&head)
+        .ok()
+        .and_then(|head| Some(git_dir.join(head.strip_prefix("ref: ")?.trim())));
+    let others = ["index", "packed-refs", "refs/tags"].map(|path| git_dir.join(path));
+    // cargo reruns every time for a path that doesn't exist, like the ref
+    // of a branch only in packed-refs
+    for path in std::iter::once(head)
+        .chain(branch)
+        .chain(others)
+        .filter(|path| path.exists())
+    {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+}
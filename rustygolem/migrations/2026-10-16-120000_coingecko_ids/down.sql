@@ -0,0 +1,22 @@
+ALTER TABLE crypto_rate RENAME TO crypto_rate_tmp;
+CREATE TABLE crypto_rate (
+  date DATETIME NOT NULL,
+  coin TEXT CHECK(coin in ("BTC", "ETH", "DOGE", "XRP", "ALGO")) NOT NULL,
+  rate REAL NOT NULL,
+  PRIMARY KEY(date, coin)
+);
+
+-- the rates of the other coins are lost
+INSERT INTO crypto_rate
+SELECT date,
+  CASE coin
+    WHEN "bitcoin" THEN "BTC"
+    WHEN "ethereum" THEN "ETH"
+    WHEN "dogecoin" THEN "DOGE"
+    WHEN "ripple" THEN "XRP"
+    WHEN "algorand" THEN "ALGO"
+  END,
+  rate
+FROM crypto_rate_tmp
+WHERE coin in ("bitcoin", "ethereum", "dogecoin", "ripple", "algorand");
+DROP TABLE crypto_rate_tmp;
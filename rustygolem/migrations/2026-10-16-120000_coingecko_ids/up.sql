@@ -0,0 +1,21 @@
+-- coins are now identified by their coingecko id, and any coin can be asked for
+ALTER TABLE crypto_rate RENAME TO crypto_rate_tmp;
+CREATE TABLE crypto_rate (
+  date DATETIME NOT NULL,
+  coin TEXT NOT NULL,
+  rate REAL NOT NULL,
+  PRIMARY KEY(date, coin)
+);
+
+INSERT INTO crypto_rate
+SELECT date,
+  CASE coin
+    WHEN "BTC" THEN "bitcoin"
+    WHEN "ETH" THEN "ethereum"
+    WHEN "DOGE" THEN "dogecoin"
+    WHEN "XRP" THEN "ripple"
+    WHEN "ALGO" THEN "algorand"
+  END,
+  rate
+FROM crypto_rate_tmp;
+DROP TABLE crypto_rate_tmp;
@@ -0,0 +1,101 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while1},
+    character::complete::{multispace0, multispace1, one_of},
+    combinator::{all_consuming, map, recognize, value},
+    sequence::{preceded, terminated, tuple},
+    Finish, IResult,
+};
+use plugin_core::utils::parser::command_prefix;
+
+/// Commands only accepted from the configured admins
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminCommand<'a> {
+    PluginList,
+    PluginEnable(&'a str),
+    PluginDisable(&'a str),
+    Mute(&'a str),
+    Unmute(&'a str),
+}
+
+/// λadmin plugin list|enable <name>|disable <name>
+/// λadmin mute|unmute <#channel>
+pub fn parse_command(input: &str) -> Option<AdminCommand> {
+    let plugin_cmd = alt((
+        value(AdminCommand::PluginList, tag("list")),
+        map(
+            preceded(tuple((tag("enable"), multispace1)), plugin_name),
+            AdminCommand::PluginEnable,
+        ),
+        map(
+            preceded(tuple((tag("disable"), multispace1)), plugin_name),
+            AdminCommand::PluginDisable,
+        ),
+    ));
+    let cmd = preceded(
+        tuple((command_prefix, tag("admin"), multispace1)),
+        alt((
+            preceded(tuple((tag("plugin"), multispace1)), plugin_cmd),
+            map(
+                preceded(tuple((tag("mute"), multispace1)), channel),
+                AdminCommand::Mute,
+            ),
+            map(
+                preceded(tuple((tag("unmute"), multispace1)), channel),
+                AdminCommand::Unmute,
+            ),
+        )),
+    );
+    all_consuming(terminated(cmd, multispace0))(input)
+        .finish()
+        .ok()
+        .map(|(_, cmd)| cmd)
+}
+
+fn plugin_name(input: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| c.is_alphanumeric() || c == '_' || c == '-')(input)
+}
+
+fn channel(input: &str) -> IResult<&str, &str> {
+    recognize(tuple((
+        one_of("#&"),
+        take_while1(|c: char| !c.is_whitespace() && c != ','),
+    )))(input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(
+            parse_command("λadmin plugin list"),
+            Some(AdminCommand::PluginList)
+        );
+        assert_eq!(
+            parse_command("λadmin plugin enable republican_calendar "),
+            Some(AdminCommand::PluginEnable("republican_calendar"))
+        );
+        assert_eq!(
+            parse_command("&admin  plugin disable url"),
+            Some(AdminCommand::PluginDisable("url"))
+        );
+        assert_eq!(parse_command("λadmin plugin enable"), None);
+        assert_eq!(parse_command("λadmin plugin list all"), None);
+        assert_eq!(parse_command("admin plugin list"), None);
+
+        assert_eq!(
+            parse_command("λadmin mute #arch-fr-free"),
+            Some(AdminCommand::Mute("#arch-fr-free"))
+        );
+        assert_eq!(
+            parse_command("λadmin unmute ##rust "),
+            Some(AdminCommand::Unmute("##rust"))
+        );
+        assert_eq!(parse_command("λadmin mute alice"), None);
+        assert_eq!(parse_command("λadmin mute #a,#b"), None);
+        assert_eq!(parse_command("λadmin unmute"), None);
+    }
+}
@@ -0,0 +1,74 @@
+use std::fmt;
+
+/// What the golem was built from, gathered at compile time by build.rs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    /// `git describe`, None when built outside of a git checkout
+    pub git: Option<&'static str>,
+    pub target: &'static str,
+    /// YYYY-MM-DD, in UTC
+    pub date: &'static str,
+}
+
+pub const BUILD_INFO: BuildInfo = BuildInfo {
+    name: env!("CARGO_PKG_NAME"),
+    version: env!("CARGO_PKG_VERSION"),
+    git: option_env!("RUSTYGOLEM_GIT_DESCRIBE"),
+    target: env!("RUSTYGOLEM_TARGET"),
+    date: env!("RUSTYGOLEM_BUILD_DATE"),
+};
+
+/// Like `rustygolem 0.1.0 (v0.1.0-42-g1234abc, x86_64-unknown-linux-gnu, 2022-01-29)`
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} (", self.name, self.version)?;
+        if let Some(git) = self.git {
+            write!(f, "{git}, ")?;
+        }
+        write!(f, "{}, {})", self.target, self.date)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn info(git: Option<&'static str>) -> BuildInfo {
+        BuildInfo {
+            name: "rustygolem",
+            version: "0.1.0",
+            git,
+            target: "x86_64-unknown-linux-gnu",
+            date: "2022-01-29",
+        }
+    }
+
+    #[test]
+    async fn test_display() {
+        assert_eq!(
+            info(Some("v0.1.0-42-g1234abc-dirty")).to_string(),
+            "rustygolem 0.1.0 (v0.1.0-42-g1234abc-dirty, x86_64-unknown-linux-gnu, 2022-01-29)"
+        );
+        assert_eq!(
+            info(None).to_string(),
+            "rustygolem 0.1.0 (x86_64-unknown-linux-gnu, 2022-01-29)",
+            "built from a tarball"
+        );
+    }
+
+    #[test]
+    async fn test_build_info() {
+        assert_eq!(BUILD_INFO.name, "rustygolem");
+        assert_eq!(BUILD_INFO.version, env!("CARGO_PKG_VERSION"));
+        assert!(!BUILD_INFO.target.is_empty());
+        assert_eq!(
+            BUILD_INFO.date.len(),
+            "2022-01-29".len(),
+            "{}",
+            BUILD_INFO.date
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use irc::proto::{Command, Message};
+pub use plugin_core::CaseMapping;
+
+/// Line length when the server doesn't advertise LINELEN, crlf included
+pub const DEFAULT_LINELEN: usize = 512;
+
+/// Room kept for the `:nick!user@host ` prefix the server adds when relaying
+/// our messages, when the server doesn't advertise NICKLEN
+const DEFAULT_NICKLEN: usize = 30;
+const USERLEN: usize = 10;
+const HOSTLEN: usize = 63;
+
+/// Never split text in chunks smaller than that, whatever the server says
+const MIN_TEXT_BUDGET: usize = 64;
+
+/// What the server advertises in RPL_ISUPPORT (005)
+/// https://modern.ircdocs.horse/#rplisupport-parameter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerCaps {
+    pub casemapping: CaseMapping,
+    pub linelen: usize,
+    pub nicklen: Option<usize>,
+}
+
+impl Default for ServerCaps {
+    fn default() -> Self {
+        ServerCaps {
+            casemapping: CaseMapping::default(),
+            linelen: DEFAULT_LINELEN,
+            nicklen: None,
+        }
+    }
+}
+
+impl ServerCaps {
+    /// Parameters of a 005 reply: our nick, the tokens, then
+    /// "are supported by this server". Unknown tokens are ignored.
+    pub fn apply_isupport(&mut self, params: &[String]) {
+        let tokens = match params {
+            [_nick, tokens @ .., _text] => tokens,
+            _ => return,
+        };
+        for token in tokens {
+            let (key, value) = match token.split_once('=') {
+                Some((key, value)) => (key, Some(value)),
+                None => (token.as_str(), None),
+            };
+            match (key, value) {
+                ("CASEMAPPING", Some(value)) => match CaseMapping::parse(value) {
+                    Some(casemapping) => self.casemapping = casemapping,
+                    None => log::warn!("Unknown casemapping {value}, keeping the current one"),
+                },
+                ("-CASEMAPPING", _) => self.casemapping = ServerCaps::default().casemapping,
+                ("LINELEN", Some(value)) => {
+                    if let Ok(linelen) = value.parse() {
+                        self.linelen = linelen;
+                    }
+                }
+                ("-LINELEN", _) => self.linelen = DEFAULT_LINELEN,
+                ("NICKLEN", Some(value)) => self.nicklen = value.parse().ok(),
+                ("-NICKLEN", _) => self.nicklen = None,
+                _ => (),
+            }
+        }
+    }
+
+    /// Bytes available for the text of a PRIVMSG or NOTICE
+    fn text_budget(&self, command: &str, target: &str) -> usize {
+        let prefix = 1 + self.nicklen.unwrap_or(DEFAULT_NICKLEN) + 1 + USERLEN + 1 + HOSTLEN + 1;
+        // COMMAND target :text\r\n
+        let overhead = prefix + command.len() + 1 + target.len() + 2 + 2;
+        self.linelen.saturating_sub(overhead).max(MIN_TEXT_BUDGET)
+    }
+
+    /// Split PRIVMSG and NOTICE too long for a single line,
+    /// other messages are left untouched.
+    pub fn split_message(&self, msg: Message) -> Vec<Message> {
+        let (target, text, is_notice) = match &msg.command {
+            Command::PRIVMSG(target, text) => (target, text, false),
+            Command::NOTICE(target, text) => (target, text, true),
+            _ => return vec![msg],
+        };
+        let command = if is_notice { "NOTICE" } else { "PRIVMSG" };
+        let budget = self.text_budget(command, target);
+        if text.len() <= budget {
+            return vec![msg];
+        }
+        let (action, text) = match text
+            .strip_prefix("\x01ACTION ")
+            .and_then(|t| t.strip_suffix('\x01'))
+        {
+            Some(inner) => (true, inner),
+            None => (false, text.as_str()),
+        };
+        let budget = if action { budget - 9 } else { budget };
+        split_text(text, budget)
+            .into_iter()
+            .map(|chunk| {
+                let chunk = if action {
+                    format!("\x01ACTION {chunk}\x01")
+                } else {
+                    chunk.to_string()
+                };
+                let command = if is_notice {
+                    Command::NOTICE(target.clone(), chunk)
+                } else {
+                    Command::PRIVMSG(target.clone(), chunk)
+                };
+                Message {
+                    tags: msg.tags.clone(),
+                    prefix: msg.prefix.clone(),
+                    command,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Chunks of at most `max_bytes`, cut on spaces when possible
+/// and always on char boundaries.
+fn split_text(text: &str, max_bytes: usize) -> Vec<&str> {
+    let mut chunks = vec![];
+    let mut rest = text;
+    while rest.len() > max_bytes {
+        let mut end = max_bytes;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let cut = rest[..end].rfind(' ').filter(|i| *i > 0).unwrap_or(end);
+        chunks.push(&rest[..cut]);
+        rest = rest[cut..].trim_start_matches(' ');
+    }
+    if !rest.is_empty() || chunks.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn params(line: &str) -> Vec<String> {
+        let (tokens, text) = line.split_once(" :").unwrap();
+        tokens
+            .split(' ')
+            .map(String::from)
+            .chain(std::iter::once(text.to_string()))
+            .collect()
+    }
+
+    #[test]
+    async fn test_libera_isupport() {
+        let mut caps = ServerCaps::default();
+        caps.apply_isupport(&params(
+            "rustygolem CALLERID=g WHOX ETRACE FNC SAFELIST ELIST=CMNTU KNOCK MONITOR=100 \
+             CHANTYPES=# EXCEPTS INVEX CHANMODES=eIbq,k,flj,CFLMPQRSTcgimnprstuz \
+             :are supported by this server",
+        ));
+        caps.apply_isupport(&params(
+            "rustygolem CHANLIMIT=#:250 PREFIX=(ov)@+ MAXLIST=bqeI:100 MODES=4 \
+             NETWORK=Libera.Chat STATUSMSG=@+ CASEMAPPING=rfc1459 NICKLEN=16 MAXNICKLEN=16 \
+             CHANNELLEN=50 TOPICLEN=390 DEAF=D :are supported by this server",
+        ));
+        caps.apply_isupport(&params(
+            "rustygolem TARGMAX=NAMES:1,LIST:1,KICK:1,WHOIS:1,PRIVMSG:4,NOTICE:4,ACCEPT:,MONITOR: \
+             EXTBAN=$,agjrxz :are supported by this server",
+        ));
+        assert_eq!(
+            caps,
+            ServerCaps {
+                casemapping: CaseMapping::Rfc1459,
+                linelen: DEFAULT_LINELEN,
+                nicklen: Some(16),
+            }
+        );
+
+        caps.apply_isupport(&params(
+            "rustygolem CASEMAPPING=ascii LINELEN=2048 :are supported by this server",
+        ));
+        assert_eq!(caps.casemapping, CaseMapping::Ascii);
+        assert_eq!(caps.linelen, 2048);
+        caps.apply_isupport(&params(
+            "rustygolem -CASEMAPPING -LINELEN :are supported by this server",
+        ));
+        assert_eq!(
+            caps,
+            ServerCaps {
+                nicklen: Some(16),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    async fn test_split_text() {
+        assert_eq!(split_text("hello there", 100), vec!["hello there"]);
+        assert_eq!(split_text("", 100), vec![""]);
+        assert_eq!(
+            split_text("hello there general kenobi", 12),
+            vec!["hello there", "general", "kenobi"]
+        );
+        assert_eq!(split_text("abcdefgh", 3), vec!["abc", "def", "gh"]);
+        // never in the middle of a char
+        assert_eq!(split_text("ééé", 3), vec!["é", "é", "é"]);
+    }
+
+    #[test]
+    async fn test_split_message() {
+        let caps = ServerCaps {
+            linelen: 184,
+            nicklen: Some(10),
+            ..Default::default()
+        };
+        // 184 - ":nick!user@host " - "PRIVMSG #chan :\r\n"
+        assert_eq!(caps.text_budget("PRIVMSG", "#chan"), 80);
+
+        let words = vec!["coucou"; 20].join(" ");
+        let msg: Message = Command::PRIVMSG("#chan".to_string(), words.clone()).into();
+        let lines = caps.split_message(msg);
+        assert_eq!(lines.len(), 2);
+        let texts: Vec<String> = lines
+            .iter()
+            .map(|l| match &l.command {
+                Command::PRIVMSG(_, text) => text.clone(),
+                _ => panic!("not a privmsg {l:?}"),
+            })
+            .collect();
+        assert!(texts.iter().all(|t| t.len() <= 80));
+        assert_eq!(texts.join(" "), words);
+
+        let action: Message =
+            Command::PRIVMSG("#chan".to_string(), format!("\x01ACTION {words}\x01")).into();
+        for line in caps.split_message(action) {
+            match line.command {
+                Command::PRIVMSG(_, text) => {
+                    assert!(text.starts_with("\x01ACTION ") && text.ends_with('\x01'));
+                    assert!(text.len() <= 80);
+                }
+                _ => panic!("not a privmsg"),
+            }
+        }
+
+        let join: Message = Command::JOIN(words.clone(), None, None).into();
+        assert_eq!(caps.split_message(join.clone()), vec![join]);
+    }
+}
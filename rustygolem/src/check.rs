@@ -0,0 +1,116 @@
+use crate::registry;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+
+/// Names of the fields of a struct deriving Deserialize, so that
+/// the list of the valid config keys cannot drift from the struct
+pub fn struct_fields<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldsCollector(&mut fields));
+    fields
+}
+
+/// Only implements deserialize_struct, to get the field names
+struct FieldsCollector<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> Deserializer<'de> for FieldsCollector<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(de::Error::custom("only collecting the fields"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// Top level keys of the config which are neither in `fields`
+/// nor the section of a known plugin
+pub fn unknown_keys(config: &serde_json::Value, fields: &[&str], plugins: &[&str]) -> Vec<String> {
+    let keys = match config.as_object() {
+        Some(object) => object.keys(),
+        None => return vec!["The golem config must be a record".to_string()],
+    };
+    let known = fields.iter().chain(plugins).copied().collect::<Vec<_>>();
+    keys.filter(|key| !known.contains(&key.as_str()))
+        .map(|key| match registry::suggest(key, &known) {
+            Some(s) => format!("Unknown field {key}. Did you mean {s}?"),
+            None => format!("Unknown field {key}"),
+        })
+        .collect()
+}
+
+/// A nickname as defined by RFC 2812, which servers accept everywhere
+pub fn is_valid_nick(nick: &str) -> bool {
+    const SPECIAL: &str = "[]\\`_^{|}";
+    let mut chars = nick.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || SPECIAL.contains(c) => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || SPECIAL.contains(c))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct Section {
+        name: String,
+        #[serde(default)]
+        channels: Vec<String>,
+    }
+
+    #[test]
+    async fn test_struct_fields() {
+        assert_eq!(struct_fields::<Section>(), &["name", "channels"]);
+        assert!(struct_fields::<String>().is_empty());
+    }
+
+    #[test]
+    async fn test_unknown_keys() {
+        let config = serde_json::json!({
+            "name": "golem",
+            "chanels": [],
+            "url": {},
+            "colour": "blue",
+        });
+        assert_eq!(
+            unknown_keys(&config, &["name", "channels"], &["url", "twitch"]),
+            vec![
+                "Unknown field chanels. Did you mean channels?",
+                "Unknown field colour",
+            ]
+        );
+        assert_eq!(
+            unknown_keys(&serde_json::json!([]), &["name"], &[]),
+            vec!["The golem config must be a record"]
+        );
+    }
+
+    #[test]
+    async fn test_is_valid_nick() {
+        for nick in ["Geekingfrog", "M`arch`ov", "[coucou]", "golem_2-bis"] {
+            assert!(is_valid_nick(nick), "{nick}");
+        }
+        for nick in ["", "2coucou", "-coucou", "geeking frog", "*!*@host", "été"] {
+            assert!(!is_valid_nick(nick), "{nick}");
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use anyhow::{Context, Result};
+use futures::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+/// One command per line on the control socket, for example
+/// {"cmd":"say","target":"#chan","text":"deploy finished"}
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+pub enum ControlCommand {
+    Say {
+        target: String,
+        text: String,
+        /// routed like any out of band message when absent
+        network: Option<String>,
+    },
+    Status,
+    Reload,
+}
+
+/// Written back as a single json line for each command
+#[derive(Debug, Default, Serialize)]
+pub struct ControlResponse {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(flatten)]
+    pub status: Option<Status>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Status {
+    pub networks: Vec<NetworkStatus>,
+    pub plugins: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NetworkStatus {
+    pub name: String,
+    pub nickname: Option<String>,
+    pub lag: String,
+}
+
+impl ControlResponse {
+    pub fn ok() -> Self {
+        ControlResponse {
+            ok: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn error<S: Into<String>>(error: S) -> Self {
+        ControlResponse {
+            ok: false,
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
+
+    pub fn status(status: Status) -> Self {
+        ControlResponse {
+            ok: true,
+            status: Some(status),
+            ..Default::default()
+        }
+    }
+}
+
+/// Listen on the given path, only accessible to the user running the bot.
+/// A socket left over by a previous run is replaced.
+pub fn bind(path: &Path) -> Result<UnixListener> {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path)
+            .with_context(|| format!("Cannot remove stale socket {}", path.display()))?,
+        Ok(_) => anyhow::bail!("{} exists and isn't a socket", path.display()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+        Err(err) => return Err(err).with_context(|| format!("Cannot stat {}", path.display())),
+    }
+    // the socket is created with the right permissions, rather than
+    // restricted right after being bound, when anyone could connect already
+    // SAFETY: umask can't fail, it only swaps the mask of the process
+    let previous = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(path);
+    unsafe { libc::umask(previous) };
+    listener.with_context(|| format!("Cannot bind control socket {}", path.display()))
+}
+
+/// Accept connections forever. A misbehaving client only affects
+/// its own connection.
+pub async fn serve<F, Fut>(listener: UnixListener, handler: F)
+where
+    F: Fn(ControlCommand) -> Fut,
+    Fut: Future<Output = ControlResponse>,
+{
+    let connections = stream::unfold(listener, |listener| async move {
+        let conn = listener.accept().await;
+        Some((conn, listener))
+    });
+    connections
+        .for_each_concurrent(None, |conn| async {
+            let result = match conn {
+                Ok((stream, _addr)) => handle_connection(stream, &handler).await,
+                Err(err) => Err(err.into()),
+            };
+            if let Err(err) = result {
+                log::warn!("Control socket connection failed: {err:?}");
+            }
+        })
+        .await
+}
+
+async fn handle_connection<F, Fut>(stream: UnixStream, handler: &F) -> Result<()>
+where
+    F: Fn(ControlCommand) -> Fut,
+    Fut: Future<Output = ControlResponse>,
+{
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<ControlCommand>(&line) {
+            Ok(cmd) => {
+                log::info!("Control command: {cmd:?}");
+                handler(cmd).await
+            }
+            Err(err) => ControlResponse::error(format!("Invalid command: {err}")),
+        };
+        let mut out = serde_json::to_vec(&response)?;
+        out.push(b'\n');
+        write.write_all(&out).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    async fn test_parse_commands() {
+        assert_eq!(
+            serde_json::from_str::<ControlCommand>(
+                r##"{"cmd":"say","target":"#chan","text":"deploy finished"}"##
+            )
+            .unwrap(),
+            ControlCommand::Say {
+                target: "#chan".to_string(),
+                text: "deploy finished".to_string(),
+                network: None,
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<ControlCommand>(r#"{"cmd":"status"}"#).unwrap(),
+            ControlCommand::Status
+        );
+        assert!(serde_json::from_str::<ControlCommand>(r#"{"cmd":"shutdown"}"#).is_err());
+    }
+
+    #[test]
+    async fn test_serialize_responses() {
+        assert_eq!(
+            serde_json::to_string(&ControlResponse::ok()).unwrap(),
+            r#"{"ok":true}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&ControlResponse::error("nope")).unwrap(),
+            r#"{"ok":false,"error":"nope"}"#
+        );
+        let status = Status {
+            networks: vec![],
+            plugins: vec!["joke".to_string()],
+        };
+        assert_eq!(
+            serde_json::to_string(&ControlResponse::status(status)).unwrap(),
+            r#"{"ok":true,"networks":[],"plugins":["joke"]}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_socket_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("golem.sock");
+        let listener = bind(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // a restart replaces the previous socket
+        drop(listener);
+        assert!(bind(&path).is_ok());
+
+        let file = dir.path().join("not-a-socket");
+        std::fs::write(&file, "precious").unwrap();
+        assert!(bind(&file).is_err());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "precious");
+    }
+}
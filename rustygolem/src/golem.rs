@@ -1,25 +1,162 @@
+use crate::admin::{self, AdminCommand};
+use crate::check;
+use crate::control::{self, ControlCommand, ControlResponse};
+use crate::journal::{self, Journal};
+use crate::lag;
+use crate::metrics::{self, Metrics};
+use crate::mute::Mute;
+use crate::network::{self, Network, NetworkConfig};
+use crate::plugin_state::{self, ErrorBudget, PluginStates};
 use crate::plugins;
+use crate::priority::{self, Priority};
+use crate::recent::{self, RecentMessages};
+use crate::registry;
+use crate::requirements;
+use crate::supervisor;
+use crate::web;
 use anyhow::{Context, Result};
 use axum::Router;
 use futures::prelude::*;
-use irc::client::ClientStream;
-use irc::proto::{CapSubCommand, Command, Message, Response};
-use plugin_core::{Initialised, Plugin};
+use irc::proto::{ChannelExt, Command, Message, Response};
+use plugin_core::i18n::{Lang, Languages};
+use plugin_core::utils::account::is_admin;
+use plugin_core::utils::network::{set_network, strip_network};
+use plugin_core::utils::parser::{self, CommandPrefixes};
+use plugin_core::utils::private::{is_private, set_private};
+use plugin_core::{BackgroundTask, FilterDecision, Members, MsgCtx, Outbound, Plugin};
 use serde::Deserialize;
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
-use tokio::sync::{mpsc, oneshot, Mutex as AsyncMutex};
-use tokio::time::timeout;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot, watch};
+
+/// How often to check whether the primary nickname should be regained.
+/// The actual attempts are spaced by the backoff of `NickKeeper`.
+const NICK_KEEPER_TICK: Duration = Duration::from_secs(10);
+
+/// Between two JOIN after connecting, mass joins get throttled by some networks
+const DEFAULT_JOIN_DELAY: Duration = Duration::from_millis(500);
+
+/// Messages sent out of band are held for that long after
+/// connecting, so that queued announcements don't flood the network
+const DEFAULT_WARM_UP: Duration = Duration::from_secs(5);
+
+/// How long a plugin can take to handle a message before its reply is dropped
+const DEFAULT_IN_MESSAGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the background tasks of plugins get to stop when shutting down
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Deserialize)]
 struct GolemConfig {
+    /// ignored on every network
     blacklisted_users: Vec<String>,
     plugins: Vec<String>,
+    /// only used without any network defined
     sasl_password: Option<String>,
+    /// only used without any network defined
+    nickserv_password: Option<String>,
+    /// networks to connect to. When empty, connect to the network given
+    /// on the command line.
+    #[serde(default)]
+    networks: Vec<NetworkConfig>,
     server_bind_address: String,
     server_bind_port: u16,
+    /// how many messages to keep around for the debug endpoint
+    recent_messages_size: Option<usize>,
+    /// bearer token protecting the debug endpoint. No token, no endpoint.
+    debug_token: Option<String>,
+    /// messages bodies on these channels are never exposed for debugging
+    #[serde(default)]
+    private_channels: Vec<String>,
+    /// replaces the default command prefixes (λ and &)
+    command_prefix: Option<String>,
+    /// shared secrets protecting the non public routes of plugins
+    #[serde(default)]
+    web_secrets: Vec<WebSecret>,
+    /// command prefix for specific channels, overriding the global one
+    #[serde(default)]
+    channel_prefixes: Vec<ChannelPrefix>,
+    /// language of the replies, "fr" or "en". English by default
+    default_language: Option<Lang>,
+    /// language for specific channels, overriding the default one
+    #[serde(default)]
+    channel_languages: Vec<ChannelLanguage>,
+    /// seconds a plugin can spend in in_message, 10 by default.
+    /// Longer operations should be spawned from the plugin's run()
+    in_message_timeout: Option<u64>,
+    /// overrides in_message_timeout for specific plugins
+    #[serde(default)]
+    plugin_timeouts: Vec<PluginTimeout>,
+    /// order of the replies when several plugins answer the same message
+    #[serde(default)]
+    plugin_priorities: Vec<PluginPriority>,
+    /// where to persist the messages sent out of band by plugins, so that
+    /// they aren't lost when restarting. No journal when unset.
+    outbound_journal: Option<String>,
+    /// seconds after which a journaled message isn't worth sending anymore,
+    /// 10 minutes by default
+    outbound_journal_max_age: Option<u64>,
+    /// seconds between two lag probes, never less than a minute
+    lag_probe_interval: Option<u64>,
+    /// unix socket accepting json commands, to make the bot talk from
+    /// scripts. Disabled when unset.
+    control_socket: Option<String>,
+    /// plugins receiving private messages. All of them when unset
+    pm_plugins: Option<Vec<String>>,
+    /// milliseconds between joining two channels, 500 by default
+    join_delay_ms: Option<u64>,
+    /// seconds after connecting during which the messages sent out of band
+    /// are held, 5 by default
+    warm_up: Option<u64>,
+    /// nicks allowed to use the λadmin commands
+    #[serde(default)]
+    admins: Vec<String>,
+    /// a plugin failing that many times (5 by default) within
+    /// plugin_failure_window seconds (10 minutes by default) is disabled
+    plugin_max_failures: Option<usize>,
+    plugin_failure_window: Option<u64>,
+    /// sqlite database shared by the plugins, the ones needing
+    /// it refuse to start without it
+    database_path: Option<String>,
+    /// settings of the http client shared by the plugins
+    http: Option<plugin_core::HttpConfig>,
+    /// refuse to start when a plugin lacks something it requires,
+    /// instead of only warning about it. False by default
+    strict_requirements: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebSecret {
+    plugin: String,
+    token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PluginTimeout {
+    plugin: String,
+    seconds: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct PluginPriority {
+    plugin: String,
+    priority: u32,
+    exclusive: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChannelPrefix {
+    channel: String,
+    prefix: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChannelLanguage {
+    channel: String,
+    language: Lang,
 }
 
 impl GolemConfig {
@@ -29,19 +166,166 @@ impl GolemConfig {
     {
         serde_dhall::from_file(config_path).parse::<GolemConfig>()
     }
+
+    /// Every problem found in the config at the given path, reported all
+    /// together. Nothing connects anywhere, the plugins only check their settings.
+    fn check(config_path: &str) -> Vec<String> {
+        let value: serde_json::Value = match serde_dhall::from_file(config_path).parse() {
+            Ok(value) => value,
+            Err(err) => return vec![format!("Cannot parse the config: {err}")],
+        };
+        let known = plugins::known_plugin_names();
+        let mut problems =
+            check::unknown_keys(&value, check::struct_fields::<GolemConfig>(), known);
+        let conf: GolemConfig = match serde_json::from_value(value) {
+            Ok(conf) => conf,
+            Err(err) => {
+                problems.push(format!("Invalid golem config: {err}"));
+                return problems;
+            }
+        };
+
+        problems.extend(
+            registry::unknown_plugins(&conf.plugins, known)
+                .iter()
+                .map(|err| err.to_string()),
+        );
+        if let Err(err) = std::net::IpAddr::from_str(&conf.server_bind_address) {
+            problems.push(format!(
+                "Invalid server_bind_address {:?}: {err}",
+                conf.server_bind_address
+            ));
+        }
+
+        let nicks = conf
+            .admins
+            .iter()
+            .map(|nick| ("admins".to_string(), nick))
+            .chain(
+                conf.blacklisted_users
+                    .iter()
+                    .map(|nick| ("blacklisted_users".to_string(), nick)),
+            )
+            .chain(conf.networks.iter().flat_map(|network| {
+                network
+                    .blacklisted_users
+                    .iter()
+                    .map(|nick| (format!("blacklisted_users of {}", network.name), nick))
+            }));
+        for (field, nick) in nicks {
+            if !check::is_valid_nick(nick) {
+                problems.push(format!("Invalid nick in {field}: {nick:?}"));
+            }
+        }
+
+        let core_config = plugin_core::Config::new(config_path);
+        for name in conf.plugins.iter().filter(|n| known.contains(&n.as_str())) {
+            if let Err(err) = plugins::check_plugin_config(&core_config, name) {
+                problems.push(format!("{err:#}"));
+            }
+        }
+        problems
+    }
+
+    fn command_prefixes(&self) -> CommandPrefixes {
+        let prefixes = match &self.command_prefix {
+            Some(prefix) => CommandPrefixes::new(vec![prefix.clone()]),
+            None => CommandPrefixes::default(),
+        };
+        self.channel_prefixes.iter().fold(prefixes, |prefixes, cp| {
+            prefixes.with_channel(&cp.channel, vec![cp.prefix.clone()])
+        })
+    }
+
+    fn languages(&self) -> Languages {
+        let languages = Languages::new(self.default_language.unwrap_or_default());
+        self.channel_languages
+            .iter()
+            .fold(languages, |languages, cl| {
+                languages.with_channel(&cl.channel, cl.language)
+            })
+    }
+}
+
+/// The settings taking effect right away when the config is reloaded
+/// through the control socket
+#[derive(Debug)]
+struct RuntimeSettings {
+    in_message_timeout: Duration,
+    plugin_timeouts: HashMap<String, Duration>,
+    /// default priority for the plugins not in there
+    priorities: HashMap<String, Priority>,
+    /// None when every plugin gets private messages
+    pm_plugins: Option<Vec<String>>,
+    admins: Vec<String>,
+}
+
+impl RuntimeSettings {
+    fn new(conf: &GolemConfig) -> Self {
+        RuntimeSettings {
+            in_message_timeout: conf
+                .in_message_timeout
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_IN_MESSAGE_TIMEOUT),
+            plugin_timeouts: conf
+                .plugin_timeouts
+                .iter()
+                .map(|t| (t.plugin.clone(), Duration::from_secs(t.seconds)))
+                .collect(),
+            priorities: conf
+                .plugin_priorities
+                .iter()
+                .map(|p| {
+                    let priority = Priority {
+                        priority: p.priority,
+                        exclusive: p.exclusive,
+                    };
+                    (p.plugin.clone(), priority)
+                })
+                .collect(),
+            pm_plugins: conf.pm_plugins.clone(),
+            admins: conf.admins.clone(),
+        }
+    }
 }
 
 pub struct Golem {
-    irc_client: Arc<Mutex<irc::client::Client>>,
-    message_stream: AsyncMutex<ClientStream>,
-    sasl_password: Option<String>,
-    blacklisted_users: Vec<String>,
+    /// the first network gets the messages which can't be routed elsewhere
+    networks: Vec<Network>,
     plugins: Vec<Box<dyn Plugin>>,
     /// bind the local server on this address
     address: std::net::SocketAddr,
     /// axum router so that plugins can define their own routes and state
     /// if required. For example for webhooks
     router: Option<Router<()>>,
+    /// recent inbound and outbound traffic, for debugging purpose
+    recent: Arc<RecentMessages>,
+    prefixes: CommandPrefixes,
+    languages: Languages,
+    lag_probe_interval: Duration,
+    metrics: Arc<Metrics>,
+    /// shared with the plugins, for them to know who is where
+    members: Arc<Members>,
+    settings: std::sync::RwLock<RuntimeSettings>,
+    /// where to reload the settings from
+    config_path: String,
+    /// messages sent by plugins out of band, persisted until they are sent
+    journal: Option<Journal>,
+    control_socket: Option<PathBuf>,
+    join_delay: Duration,
+    warm_up: Duration,
+    plugin_states: PluginStates,
+    /// channels muted by the admins
+    mute: Mute,
+    /// background tasks of the plugins, spawned when the golem runs
+    tasks: Vec<(&'static str, BackgroundTask)>,
+    /// true is sent once the golem shuts down
+    shutdown: watch::Sender<bool>,
+}
+
+/// Problems in the golem config at the given path, for --check-config
+pub fn check_config(config_path: &str) -> Vec<String> {
+    GolemConfig::check(config_path)
 }
 
 impl Golem {
@@ -50,213 +334,651 @@ impl Golem {
         irc_config: irc::client::data::Config,
         golem_config_path: String,
     ) -> Result<Self> {
-        let mut irc_client = irc::client::Client::from_config(irc_config).await?;
+        let problems = GolemConfig::check(&golem_config_path);
+        if !problems.is_empty() {
+            return Err(anyhow!(
+                "Invalid golem config at {golem_config_path}:\n{}",
+                problems.join("\n")
+            ));
+        }
         let conf = GolemConfig::from_path(&golem_config_path)
             .with_context(|| format!("Cannot parse golem config at {golem_config_path}"))?;
         log::debug!("Loaded config: {conf:?}");
+        let prefixes = conf.command_prefixes();
+        let languages = conf.languages();
 
-        let core_config = plugin_core::Config {
-            config_path: golem_config_path,
+        let networks = if conf.networks.is_empty() {
+            if irc_config.channels.is_empty() {
+                return Err(anyhow!("No channels to join, aborting"));
+            }
+            log::info!("Joining channel(s): {:?}", irc_config.channels);
+            let network = Network::connect(
+                network::DEFAULT_NETWORK,
+                irc_config,
+                conf.sasl_password,
+                conf.nickserv_password,
+                conf.blacklisted_users,
+            )
+            .await?;
+            vec![network]
+        } else {
+            let mut networks = Vec::with_capacity(conf.networks.len());
+            for network_conf in conf.networks {
+                log::info!(
+                    "Joining channel(s) {:?} on {}",
+                    network_conf.channels,
+                    network_conf.name
+                );
+                let blacklisted_users = conf
+                    .blacklisted_users
+                    .iter()
+                    .chain(network_conf.blacklisted_users.iter())
+                    .cloned()
+                    .collect();
+                let network = Network::connect(
+                    &network_conf.name,
+                    network_conf.irc_config(&irc_config),
+                    network_conf.sasl_password,
+                    network_conf.nickserv_password,
+                    blacklisted_users,
+                )
+                .await?;
+                networks.push(network);
+            }
+            networks
         };
+
+        let http_client = conf.http.clone().unwrap_or_default().build_client()?;
+        // shared with the plugins, for them to add their own metrics
+        let metrics = Arc::new(Metrics::default());
+        let members = Arc::new(Members::default());
+        let settings = RuntimeSettings::new(&conf);
+        let mut core_config = plugin_core::Config::new(&golem_config_path)
+            .with_http_client(http_client)
+            .with_metrics(Arc::clone(&metrics))
+            .with_members(Arc::clone(&members));
+        if let Some(path) = &conf.database_path {
+            log::info!("Using the database at {path}");
+            core_config = core_config.with_database(plugin_core::Database::open(path)?);
+        }
         let core_config = Arc::new(core_config);
 
         let inits = stream::iter(conf.plugins)
             .map(|name| {
                 let core_config = Arc::clone(&core_config);
-                async move { init_plugin(&core_config, &name).await }
+                async move { plugins::init_plugin(&core_config, &name).await }
             })
-            .buffer_unordered(10)
+            // keep the config order, the outbound filters run in that order
+            .buffered(10)
             .collect::<Vec<_>>()
             .await
             .into_iter()
             .collect::<Result<Vec<_>>>()?;
 
+        let loaded = inits.iter().map(|i| i.plugin.as_ref()).collect::<Vec<_>>();
+        requirements::check(
+            &loaded,
+            &requirements::Provided {
+                config: &core_config,
+                networks: networks.len(),
+                // the metrics are always served
+                web_server: true,
+            },
+            conf.strict_requirements.unwrap_or(false),
+        )?;
+
+        let mut web_secrets = conf
+            .web_secrets
+            .into_iter()
+            .map(|s| (s.plugin, s.token))
+            .collect::<HashMap<_, _>>();
         let mut router: Option<Router<()>> = None;
         let mut plugins = Vec::with_capacity(inits.len());
+        let mut tasks = vec![];
         for init in inits {
-            if let Some(r) = init.router {
-                match router {
-                    Some(x) => {
-                        log::info!("Mounting a router from plugin {}", init.plugin.get_name());
-                        router = Some(x.merge(r))
-                    }
-                    None => router = Some(r),
-                }
+            let name = init.plugin.get_name();
+            let plugin_router = web::plugin_router(
+                name,
+                init.router,
+                init.public_router,
+                web_secrets.remove(name),
+            );
+            if let Some(r) = plugin_router {
+                log::info!("Mounting a router from plugin {name} under /{name}");
+                router = match router {
+                    Some(x) => Some(x.merge(r)),
+                    None => Some(r),
+                };
             }
+            tasks.extend(init.tasks.into_iter().map(|task| (name, task)));
             plugins.push(init.plugin);
         }
+        for plugin in web_secrets.keys() {
+            log::warn!("Web secret configured for {plugin}, but this plugin isn't loaded");
+        }
+
+        let recent = Arc::new(RecentMessages::new(
+            conf.recent_messages_size
+                .unwrap_or(recent::DEFAULT_CAPACITY),
+            conf.private_channels,
+        ));
+        if let Some(token) = conf.debug_token {
+            let debug_router = recent::router(Arc::clone(&recent), token);
+            router = match router {
+                Some(r) => Some(r.merge(debug_router)),
+                None => Some(debug_router),
+            };
+        }
+
+        let metrics_router = metrics::router(Arc::clone(&metrics));
+        router = match router {
+            Some(r) => Some(r.merge(metrics_router)),
+            None => Some(metrics_router),
+        };
+
+        let lag_probe_interval = conf
+            .lag_probe_interval
+            .map(Duration::from_secs)
+            .unwrap_or(lag::MIN_PROBE_INTERVAL)
+            .max(lag::MIN_PROBE_INTERVAL);
+
+        let journal_max_age = conf
+            .outbound_journal_max_age
+            .map(Duration::from_secs)
+            .unwrap_or(journal::DEFAULT_MAX_AGE);
+        let journal = conf
+            .outbound_journal
+            .map(|path| Journal::open(path, journal_max_age))
+            .transpose()?;
 
         let addr = std::net::IpAddr::from_str(&conf.server_bind_address)?;
         let address = std::net::SocketAddr::from((addr, conf.server_bind_port));
-        let message_stream = irc_client.stream()?;
 
         Ok(Self {
-            irc_client: Arc::new(Mutex::new(irc_client)),
-            message_stream: AsyncMutex::new(message_stream),
-            sasl_password: conf.sasl_password,
-            blacklisted_users: conf.blacklisted_users,
+            networks,
             plugins,
             address,
             router,
+            recent,
+            prefixes,
+            languages,
+            lag_probe_interval,
+            metrics,
+            members,
+            settings: std::sync::RwLock::new(settings),
+            config_path: golem_config_path,
+            journal,
+            control_socket: conf.control_socket.map(PathBuf::from),
+            join_delay: conf
+                .join_delay_ms
+                .map(Duration::from_millis)
+                .unwrap_or(DEFAULT_JOIN_DELAY),
+            warm_up: conf
+                .warm_up
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_WARM_UP),
+            mute: Mute::default(),
+            tasks,
+            shutdown: watch::channel(false).0,
+            plugin_states: PluginStates::new(ErrorBudget {
+                max_failures: conf
+                    .plugin_max_failures
+                    .unwrap_or(plugin_state::DEFAULT_MAX_FAILURES),
+                window: conf
+                    .plugin_failure_window
+                    .map(Duration::from_secs)
+                    .unwrap_or(plugin_state::DEFAULT_FAILURE_WINDOW),
+            }),
         })
     }
 
     pub async fn run(&mut self) -> Result<()> {
-        self.authenticate_and_identify()
-            .await
-            .context("Problem while authenticating")?;
+        future::try_join_all(self.networks.iter().map(|network| async move {
+            network
+                .authenticate_and_identify()
+                .await
+                .with_context(|| format!("Problem while authenticating on {}", network.name))
+        }))
+        .await?;
 
         let router = self.router.take();
+        let tasks = std::mem::take(&mut self.tasks)
+            .into_iter()
+            .map(|(plugin, task)| {
+                tokio::spawn(supervisor::supervise(
+                    plugin,
+                    task,
+                    self.shutdown.subscribe(),
+                ))
+            })
+            .collect::<Vec<_>>();
 
-        tokio::try_join!(
-            self.run_plugins(),
-            self.recv_irc_messages(),
-            self.run_server(router)
-        )?;
+        let result = tokio::select! {
+            result = async {
+                tokio::try_join!(
+                    self.run_plugins(),
+                    self.recv_irc_messages(),
+                    self.run_lag_probes(),
+                    self.run_nick_keepers(),
+                    self.run_channel_joins(),
+                    self.run_control_socket(),
+                    self.run_server(router)
+                )
+            } => {
+                log::error!("golem exited");
+                result.map(|_| ())
+            }
+            result = shutdown_signal() => {
+                log::info!("Shutting down");
+                result
+            }
+        };
 
-        log::error!("golem exited");
-        Ok(())
+        self.shutdown.send_replace(true);
+        if tokio::time::timeout(SHUTDOWN_GRACE, future::join_all(tasks))
+            .await
+            .is_err()
+        {
+            log::warn!("Background tasks still running after {SHUTDOWN_GRACE:?}, leaving them");
+        }
+        result
     }
 
-    async fn authenticate_and_identify(&self) -> Result<()> {
-        match self.sasl_password {
-            None => {
-                log::info!("No SASL_PASSWORD env var found, not authenticating anything.");
-                self.irc_client.lock().unwrap().identify()?;
-                Ok(())
+    async fn recv_irc_messages(&self) -> Result<()> {
+        future::try_join_all(
+            self.networks
+                .iter()
+                .map(|network| self.recv_network_messages(network)),
+        )
+        .await?;
+        Err(anyhow!("IRC receiving streams exited"))
+    }
+
+    async fn recv_network_messages(&self, network: &Network) -> Result<()> {
+        let mut message_stream = network.stream.lock().await;
+        while let Some(mut irc_message) = message_stream.next().await.transpose()? {
+            let received_at = Instant::now();
+            set_network(&mut irc_message, &network.name);
+            if is_private_message(&irc_message) {
+                set_private(&mut irc_message);
             }
-            Some(ref password) => {
-                self.sasl_auth(password).await?;
-                Ok(())
+            self.recent.record_inbound(&irc_message);
+            if let Command::Response(Response::RPL_ENDOFMOTD | Response::ERR_NOMOTD, _) =
+                irc_message.command
+            {
+                network.set_registered(tokio::time::Instant::now());
+            }
+            if let Command::Response(Response::RPL_ISUPPORT, params) = &irc_message.command {
+                let mut caps = network.caps.lock().expect("caps lock");
+                caps.apply_isupport(params);
+                log::debug!("Server capabilities for {}: {caps:?}", network.name);
+                self.members.set_casemapping(&network.name, caps.casemapping);
+                if let Some(keeper) = &network.nick {
+                    keeper
+                        .lock()
+                        .expect("nick keeper lock")
+                        .set_casemapping(caps.casemapping);
+                }
+            }
+            if let Some(keeper) = &network.nick {
+                let replies = keeper
+                    .lock()
+                    .expect("nick keeper lock")
+                    .on_message(&irc_message, received_at);
+                for reply in replies {
+                    network.send(reply)?;
+                }
+            }
+            if let Some(own_nick) = own_nick(network) {
+                self.members
+                    .on_message(&network.name, &own_nick, &irc_message);
+            }
+            if let Some(channel) = self_join(network, &irc_message) {
+                self.self_joined(network, &channel).await?;
+            }
+            for channel in self_part(network, &irc_message) {
+                self.self_parted(network, &channel).await?;
+            }
+            if let Some(rtt) = network.lag.on_pong(&irc_message.command, received_at) {
+                log::debug!(
+                    "Server lag on {}: {}",
+                    network.name,
+                    lag::format_duration(rtt)
+                );
+                self.metrics.set_gauge(
+                    "golem_lag_seconds",
+                    &[("network", network.name.as_str())],
+                    rtt.as_secs_f64(),
+                );
+            }
+            if let Some(reply) = self.core_command(network, &irc_message, received_at).await {
+                self.outbound_message(&("golem", network.name.clone(), reply))
+                    .await?;
+            }
+            let messages = self
+                .plugins_in_messages(network, &irc_message)
+                .await
+                .with_context(|| "Plugin error !")?;
+
+            for message in self.dedup(messages.into_iter().flatten()) {
+                self.outbound_message(&message).await?;
             }
         }
+        Err(anyhow!("IRC receiving stream exited for {}", network.name))
     }
 
-    // SASL PLAIN authentication
-    // https://ircv3.net/specs/extensions/sasl-3.1.html
-    async fn sasl_auth(&self, password: &str) -> Result<()> {
-        let client = self.irc_client.lock().unwrap();
-        let nick = client.current_nickname();
-        log::info!("Authenticating with SASL for {nick}");
-
-        client.send_cap_req(&[irc::proto::Capability::Sasl])?;
-        // the call client.identify() provided by the irc library starts
-        // by sending a CAP END before sending NICK and USER messages.
-        // but as far as I can tell, this is incorrect for SASL, so manually send
-        // the stuff
-        client.send(Command::NICK(nick.to_string()))?;
-        client.send(Command::USER(
-            nick.to_string(),
-            "0".to_string(),
-            format!(":{nick}"),
-        ))?;
-
-        let duration = Duration::from_secs(10);
-        timeout(
-            duration,
-            self.wait_for_message(|msg| match &msg.command {
-                Command::CAP(_, CapSubCommand::ACK, Some(opt), _) if opt == "sasl" => true,
-                _ => false,
-            }),
+    /// Lets the plugins react to the golem joining a channel
+    async fn self_joined(&self, network: &Network, channel: &str) -> Result<()> {
+        log::info!("Joined {channel} on {}", network.name);
+        let replies = future::join_all(
+            self.plugins
+                .iter()
+                .filter(|plugin| self.plugin_states.is_enabled(plugin.get_name()))
+                .map(|plugin| async move {
+                    let name = plugin.get_name();
+                    let deadline = self.in_message_timeout(name);
+                    let joined = plugin.on_self_join(&network.name, channel);
+                    let outbounds = match tokio::time::timeout(deadline, joined).await {
+                        Ok(Ok(outbounds)) => outbounds,
+                        Ok(Err(err)) => {
+                            self.plugin_failed(network, name, &err).await?;
+                            vec![]
+                        }
+                        Err(_) => {
+                            log::warn!(
+                                "Plugin {name} didn't handle joining {channel} within {deadline:?}"
+                            );
+                            vec![]
+                        }
+                    };
+                    Ok::<_, anyhow::Error>(
+                        outbounds
+                            .into_iter()
+                            .map(|o| (name, network.name.clone(), Message::from(o)))
+                            .collect::<Vec<_>>(),
+                    )
+                }),
         )
-        .await
-        .context("Timeout waiting for CAP ACK sasl")??;
+        .await;
+        for replies in replies {
+            for message in replies? {
+                self.outbound_message(&message).await?;
+            }
+        }
+        Ok(())
+    }
 
-        log::info!("GOT ACK for SASL !");
-        client.send_sasl_plain()?;
+    /// Lets the plugins forget about a channel the golem left
+    async fn self_parted(&self, network: &Network, channel: &str) -> Result<()> {
+        log::info!("Left {channel} on {}", network.name);
+        let failures = future::join_all(self.plugins.iter().map(|plugin| async move {
+            let name = plugin.get_name();
+            let deadline = self.in_message_timeout(name);
+            match tokio::time::timeout(deadline, plugin.on_self_part(channel)).await {
+                Ok(Ok(())) => None,
+                Ok(Err(err)) => Some((name, err)),
+                Err(_) => {
+                    log::warn!("Plugin {name} didn't handle leaving {channel} within {deadline:?}");
+                    None
+                }
+            }
+        }))
+        .await;
+        for (name, err) in failures.into_iter().flatten() {
+            self.plugin_failed(network, name, &err).await?;
+        }
+        Ok(())
+    }
 
-        timeout(
-            duration,
-            self.wait_for_message(|msg| match &msg.command {
-                Command::AUTHENTICATE(s) if s == "+" => true,
-                _ => false,
-            }),
-        )
-        .await
-        .context("Timeout waiting for AUTHENTICATE + from server")??;
-
-        let sasl_str = base64::encode(format!("\0{}\0{}", nick, password));
-        client.send(Command::AUTHENTICATE(sasl_str))?;
-
-        let resp = timeout(
-            duration,
-            self.wait_for_message(|msg| match &msg.command {
-                Command::Response(Response::RPL_SASLSUCCESS, _) => true,
-                Command::Response(resp, _) if is_sasl_error(resp) => true,
-                _ => false,
-            }),
+    /// Commands handled by the golem itself, before any plugin
+    async fn core_command(
+        &self,
+        network: &Network,
+        msg: &Message,
+        received_at: Instant,
+    ) -> Option<Message> {
+        let text = match &msg.command {
+            Command::PRIVMSG(_, text) => text,
+            _ => return None,
+        };
+        let target = msg.response_target()?;
+        let prefixes = self.prefixes.for_channel(Some(target));
+        let reply = parser::with_prefixes(prefixes, async {
+            if parser::single_command("ping", text).is_some() {
+                // measured as late as possible, right before queuing the reply
+                let elapsed = received_at.elapsed();
+                Some(format!("pong ({})", lag::format_duration(elapsed)))
+            } else if parser::single_command("lag", text).is_some() {
+                Some(
+                    network
+                        .lag
+                        .lag(Instant::now(), self.lag_probe_interval)
+                        .to_string(),
+                )
+            } else if let Some(cmd) = admin::parse_command(text) {
+                self.admin_command(network, msg, cmd)
+            } else {
+                None
+            }
+        })
+        .await?;
+        Some(Command::PRIVMSG(target.to_string(), reply).into())
+    }
+
+    fn admin_command(&self, network: &Network, msg: &Message, cmd: AdminCommand) -> Option<String> {
+        let source = msg.source_nickname()?;
+        let casemapping = network.caps.lock().expect("caps lock").casemapping;
+        let settings = self.settings.read().expect("settings lock");
+        if !is_admin(&settings.admins, msg, casemapping) {
+            log::warn!("Ignoring admin command from {source}: {cmd:?}");
+            return None;
+        }
+        drop(settings);
+        let reply = match cmd {
+            AdminCommand::PluginList => self
+                .plugins
+                .iter()
+                .map(|p| {
+                    format!(
+                        "{}: {}",
+                        p.get_name(),
+                        self.plugin_states.status(p.get_name())
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+            AdminCommand::PluginEnable(name) | AdminCommand::PluginDisable(name)
+                if !self.plugins.iter().any(|p| p.get_name() == name) =>
+            {
+                format!("Unknown plugin {name}")
+            }
+            AdminCommand::PluginEnable(name) => {
+                self.plugin_states.enable(name);
+                format!("Plugin {name} enabled")
+            }
+            AdminCommand::PluginDisable(name) => {
+                self.plugin_states.disable(name);
+                format!("Plugin {name} disabled")
+            }
+            AdminCommand::Mute(channel) => {
+                if self.mute.mute(&network.name, casemapping, channel) {
+                    format!("Muted {channel}")
+                } else {
+                    format!("{channel} is already muted")
+                }
+            }
+            AdminCommand::Unmute(channel) => {
+                if self.mute.unmute(&network.name, casemapping, channel) {
+                    format!("Unmuted {channel}")
+                } else {
+                    format!("{channel} isn't muted")
+                }
+            }
+        };
+        Some(reply)
+    }
+
+    async fn run_lag_probes(&self) -> Result<()> {
+        future::try_join_all(
+            self.networks
+                .iter()
+                .map(|network| self.run_lag_probe(network)),
         )
-        .await
-        .context("Timeout waiting for SASL acknowledment")??;
+        .await?;
+        Ok(())
+    }
 
-        if matches!(resp.command, Command::Response(resp, _) if is_sasl_error(&resp)) {
-            anyhow::bail!("SASL auth failed {resp:?}");
+    /// Periodically PING the server to measure the lag. A server not answering
+    /// isn't a fatal error, the next probe simply replaces the lost one.
+    async fn run_lag_probe(&self, network: &Network) -> Result<()> {
+        let mut interval = tokio::time::interval(self.lag_probe_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let token = network.lag.start(Instant::now());
+            network.send(Command::PING(token, None).into())?;
         }
-        log::info!("SASL authenticated");
+    }
+
+    async fn run_channel_joins(&self) -> Result<()> {
+        future::try_join_all(
+            self.networks
+                .iter()
+                .map(|network| self.run_channel_join(network)),
+        )
+        .await?;
+        Ok(())
+    }
 
-        client.send(Command::CAP(None, CapSubCommand::END, None, None))?;
-        log::info!("Handshake finished, ready to work");
+    /// Join the channels one by one every time the network registers
+    async fn run_channel_join(&self, network: &Network) -> Result<()> {
+        let mut registered = network.registered();
+        loop {
+            // the network may have registered before we started watching
+            let is_registered = registered.borrow_and_update().is_some();
+            if is_registered {
+                self.join_channels(network).await?;
+            }
+            if registered.changed().await.is_err() {
+                return Ok(());
+            }
+        }
+    }
 
+    async fn join_channels(&self, network: &Network) -> Result<()> {
+        for (i, channel) in network.join_channels().iter().enumerate() {
+            if i > 0 && !self.join_delay.is_zero() {
+                tokio::time::sleep(self.join_delay).await;
+            }
+            log::info!("Joining {channel} on {}", network.name);
+            network.send(Command::JOIN(channel.clone(), None, None).into())?;
+        }
         Ok(())
     }
 
-    /// wait until the client receive a message that matches the given predicate
-    /// and returns it. Warning, use timeout to prevent a deadlock.
-    async fn wait_for_message<F>(&self, pred: F) -> Result<Message>
-    where
-        F: Fn(&Message) -> bool,
-    {
-        let mut message_stream = self.message_stream.lock().await;
-        while let Some(message) = message_stream.next().await.transpose()? {
-            if pred(&message) {
-                return Ok(message);
+    /// Resolves once the network is registered and the warm-up is over
+    async fn warmed_up(&self, network: &Network) {
+        if self.warm_up.is_zero() {
+            return;
+        }
+        let mut registered = network.registered();
+        loop {
+            let registered_at = *registered.borrow_and_update();
+            if let Some(at) = registered_at {
+                tokio::time::sleep_until(at + self.warm_up).await;
+                return;
+            }
+            if registered.changed().await.is_err() {
+                return;
             }
         }
-        anyhow::bail!("Waited for message failed");
     }
 
-    async fn recv_irc_messages(&self) -> Result<()> {
-        let mut message_stream = self.message_stream.lock().await;
-        while let Some(irc_message) = message_stream.next().await.transpose()? {
-            let messages = self
-                .plugins_in_messages(&irc_message)
-                .await
-                .with_context(|| "Plugin error !")?;
+    async fn run_nick_keepers(&self) -> Result<()> {
+        future::try_join_all(
+            self.networks
+                .iter()
+                .map(|network| self.run_nick_keeper(network)),
+        )
+        .await?;
+        Ok(())
+    }
 
-            for message in messages.into_iter().flatten() {
-                self.outbound_message(&message).await?;
+    /// Try to regain the primary nickname when the network gave us another one
+    async fn run_nick_keeper(&self, network: &Network) -> Result<()> {
+        let keeper = match &network.nick {
+            Some(keeper) => keeper,
+            None => return Ok(()),
+        };
+        let mut interval = tokio::time::interval(NICK_KEEPER_TICK);
+        loop {
+            interval.tick().await;
+            let msgs = keeper
+                .lock()
+                .expect("nick keeper lock")
+                .tick(Instant::now());
+            for msg in msgs {
+                network.send(msg)?;
             }
         }
-        Err(anyhow!("IRC receiving stream exited"))
     }
 
     async fn plugins_in_messages(
         &self,
+        network: &Network,
         msg: &Message,
-    ) -> Result<Vec<Option<(&'static str, Message)>>> {
+    ) -> Result<Vec<Option<(&'static str, String, Message)>>> {
         let mut results = Vec::with_capacity(self.plugins.len());
 
         let (txs, rxs): (Vec<_>, Vec<_>) = self.plugins.iter().map(|_| oneshot::channel()).unzip();
+        let prefixes = &self.prefixes.for_channel(msg.response_target());
+        let mut ctx = MsgCtx::from_message(msg);
+        ctx.casemapping = network.caps.lock().expect("caps lock").casemapping;
+        ctx.lang = self
+            .languages
+            .for_channel(ctx.channel.as_deref(), ctx.casemapping);
+        let ctx = &ctx;
 
         futures::stream::iter(self.plugins.iter().zip(txs))
             .map(Ok)
             .try_for_each_concurrent(5, |(plugin, tx)| async move {
-                if let Some(source) = msg.source_nickname() {
-                    if plugin.ignore_blacklisted_users()
-                        && self.blacklisted_users.contains(&source.to_string())
-                    {
-                        log::debug!("Message from blacklisted user: {}, discarding", source);
-                        if tx.send(None).is_err() {
-                            return Err(anyhow!("cannot send plugin message !"));
-                        };
-                        return Ok::<(), anyhow::Error>(());
-                    }
+                if !self.should_handle(plugin.as_ref(), network, msg) {
+                    if tx.send(None).is_err() {
+                        return Err(anyhow!("cannot send plugin message !"));
+                    };
+                    return Ok::<(), anyhow::Error>(());
                 }
 
-                let mb_msg = plugin.in_message(msg).await.with_context(|| {
-                    format!("in_message error from plugin {}", plugin.get_name())
-                })?;
-                let msg = mb_msg.map(|m| (plugin.get_name(), m));
+                // a slow plugin must not delay the replies of the other ones
+                let deadline = self.in_message_timeout(plugin.get_name());
+                let in_message =
+                    parser::with_prefixes(Arc::clone(prefixes), plugin.in_message_ctx(ctx, msg));
+                let mb_msg = match tokio::time::timeout(deadline, in_message).await {
+                    Ok(Ok(mb_msg)) => mb_msg,
+                    Ok(Err(err)) => match err.reply_to(msg, ctx.lang, prefixes.as_slice()) {
+                        // not the plugin's fault, the users are told why nothing happened
+                        Some(reply) => {
+                            log::info!("Plugin {} declined: {err}", plugin.get_name());
+                            Some(reply)
+                        }
+                        None => {
+                            self.plugin_failed(network, plugin.get_name(), &err).await?;
+                            None
+                        }
+                    },
+                    Err(_) => {
+                        log::warn!(
+                            "Plugin {} didn't handle the message within {deadline:?}, dropping its reply",
+                            plugin.get_name()
+                        );
+                        self.metrics.inc_counter(
+                            "golem_plugin_timeouts_total",
+                            &[("plugin", plugin.get_name())],
+                        );
+                        None
+                    }
+                };
+                let msg = mb_msg.map(|m| (plugin.get_name(), network.name.clone(), Message::from(m)));
                 if tx.send(msg).is_err() {
                     return Err(anyhow!("cannot send plugin message !"));
                 }
@@ -264,12 +986,135 @@ impl Golem {
             })
             .await?;
 
-        for rx in rxs {
-            let rx: oneshot::Receiver<Option<(&'static str, Message)>> = rx;
-            results.push(rx.await?);
+        for (plugin, rx) in self.plugins.iter().zip(rxs) {
+            let rx: oneshot::Receiver<Option<(&'static str, String, Message)>> = rx;
+            results.push((self.priority(plugin.get_name()), rx.await?));
         }
 
-        Ok(results)
+        Ok(priority::prioritize(results))
+    }
+
+    /// Drop the replies identical to a previous one: same command, same target
+    /// and same text, ignoring case and whitespaces.
+    fn dedup(
+        &self,
+        messages: impl IntoIterator<Item = (&'static str, String, Message)>,
+    ) -> Vec<(&'static str, String, Message)> {
+        let mut seen = std::collections::HashSet::new();
+        messages
+            .into_iter()
+            .filter(|(name, network, msg)| {
+                let key = match &msg.command {
+                    Command::PRIVMSG(target, text) => ("PRIVMSG", target.clone(), normalize(text)),
+                    Command::NOTICE(target, text) => ("NOTICE", target.clone(), normalize(text)),
+                    _ => return true,
+                };
+                let allow_duplicate = self
+                    .plugins
+                    .iter()
+                    .any(|p| p.get_name() == *name && p.allow_duplicate_output());
+                if seen.insert((network.clone(), key)) || allow_duplicate {
+                    true
+                } else {
+                    log::info!("Suppressed duplicate reply from plugin {name}: {msg:?}");
+                    false
+                }
+            })
+            .collect()
+    }
+
+    fn should_handle(&self, plugin: &dyn Plugin, network: &Network, msg: &Message) -> bool {
+        if let Some(source) = msg.source_nickname() {
+            if plugin.ignore_blacklisted_users() && network.is_blacklisted(source) {
+                log::debug!("Message from blacklisted user: {}, discarding", source);
+                return false;
+            }
+        }
+        if is_private(msg) && !self.receives_private_messages(plugin.get_name()) {
+            return false;
+        }
+        self.plugin_states.is_enabled(plugin.get_name())
+    }
+
+    /// Errors from a plugin aren't fatal, but a plugin failing too often
+    /// is disabled until an admin enables it again.
+    async fn plugin_failed(
+        &self,
+        network: &Network,
+        name: &'static str,
+        err: &plugin_core::Error,
+    ) -> Result<()> {
+        let error = match err {
+            plugin_core::Error::Generic(err) => format!("{err:#}"),
+            err => err.to_string(),
+        };
+        log::error!("in_message error from plugin {name}: {error}");
+        self.metrics
+            .inc_counter("golem_plugin_errors_total", &[("plugin", name)]);
+        if !self
+            .plugin_states
+            .record_failure(name, &error, Instant::now())
+        {
+            return Ok(());
+        }
+        let budget = self.plugin_states.budget();
+        let text = format!(
+            "Plugin {name} disabled after {} failures within {}, last error: {error}",
+            budget.max_failures,
+            lag::format_duration(budget.window)
+        );
+        log::warn!("{text}");
+        let admins = self.settings.read().expect("settings lock").admins.clone();
+        for admin in admins {
+            let notice = Command::NOTICE(admin, text.clone()).into();
+            self.outbound_message(&("golem", network.name.clone(), notice))
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn receives_private_messages(&self, plugin: &str) -> bool {
+        match &self.settings.read().expect("settings lock").pm_plugins {
+            Some(names) => names.iter().any(|name| name == plugin),
+            None => true,
+        }
+    }
+
+    fn priority(&self, plugin: &str) -> Priority {
+        let settings = self.settings.read().expect("settings lock");
+        settings.priorities.get(plugin).copied().unwrap_or_default()
+    }
+
+    fn in_message_timeout(&self, plugin: &str) -> Duration {
+        let settings = self.settings.read().expect("settings lock");
+        settings
+            .plugin_timeouts
+            .get(plugin)
+            .copied()
+            .unwrap_or(settings.in_message_timeout)
+    }
+
+    /// Read the config again, and apply what can change without
+    /// reconnecting nor restarting the plugins. Nothing changes when the
+    /// config is invalid.
+    fn reload(&self) -> Result<()> {
+        let problems = GolemConfig::check(&self.config_path);
+        if !problems.is_empty() {
+            return Err(anyhow!(
+                "Invalid golem config at {}: {}",
+                self.config_path,
+                problems.join(", ")
+            ));
+        }
+        let conf = GolemConfig::from_path(&self.config_path)
+            .with_context(|| format!("Cannot parse golem config at {}", self.config_path))?;
+        let settings = RuntimeSettings::new(&conf);
+        log::info!(
+            "Reloaded the settings from {}: {settings:?}",
+            self.config_path
+        );
+        *self.settings.write().expect("settings lock") = settings;
+        Ok(())
     }
 
     async fn run_plugins(&self) -> Result<()> {
@@ -290,8 +1135,10 @@ impl Golem {
                         Ok::<(), anyhow::Error>(())
                     },
                     async {
-                        while let Some(plugin_message) = plug_rx.recv().await {
-                            tx.send((name, plugin_message))
+                        while let Some(outbound) = plug_rx.recv().await {
+                            let plugin_message = Message::from(outbound);
+                            let journal_id = self.journal_append(name, &plugin_message);
+                            tx.send((name, plugin_message, journal_id))
                                 .await
                                 .with_context(|| format!("Plugin {}.run() failed", p.get_name()))?;
                         }
@@ -303,8 +1150,10 @@ impl Golem {
             }
         });
         let process = async move {
-            while let Some(msg) = rx.recv().await {
-                self.outbound_message(&msg).await?;
+            self.replay_journal().await?;
+            while let Some((name, msg, journal_id)) = rx.recv().await {
+                self.send_out_of_band(name, msg).await?;
+                self.journal_ack(journal_id);
             }
             Ok::<(), anyhow::Error>(())
         };
@@ -312,12 +1161,101 @@ impl Golem {
         Ok(())
     }
 
-    async fn outbound_message(&self, message: &(&'static str, Message)) -> Result<()> {
+    async fn send_out_of_band(&self, name: &'static str, msg: Message) -> Result<()> {
+        match self.route(&msg) {
+            Some(network) => {
+                self.warmed_up(network).await;
+                self.outbound_message(&(name, network.name.clone(), msg))
+                    .await
+            }
+            None => {
+                log::error!("No network to send message from {name}: {msg:?}");
+                Ok(())
+            }
+        }
+    }
+
+    /// An error with the journal must not prevent the message to be sent
+    fn journal_append(&self, plugin: &'static str, msg: &Message) -> Option<u64> {
+        let journal = self.journal.as_ref()?;
+        journal
+            .append(plugin, msg, chrono::Utc::now())
+            .unwrap_or_else(|err| {
+                log::error!("Cannot journal message from {plugin}: {err:?}");
+                None
+            })
+    }
+
+    fn journal_ack(&self, journal_id: Option<u64>) {
+        if let (Some(journal), Some(id)) = (&self.journal, journal_id) {
+            if let Err(err) = journal.ack(id) {
+                log::error!("Cannot remove message {id} from the journal: {err:?}");
+            }
+        }
+    }
+
+    /// Send the messages queued but not sent before the last restart
+    async fn replay_journal(&self) -> Result<()> {
+        let journal = match &self.journal {
+            Some(journal) => journal,
+            None => return Ok(()),
+        };
+        for entry in journal.replay(chrono::Utc::now())? {
+            log::info!("Replaying message from the journal: {entry:?}");
+            let name = self
+                .plugins
+                .iter()
+                .map(|p| p.get_name())
+                .find(|name| *name == entry.plugin)
+                .unwrap_or("journal");
+            self.send_out_of_band(name, entry.message()).await?;
+            self.journal_ack(Some(entry.id));
+        }
+        Ok(())
+    }
+
+    /// Which network should get a message not sent in response to another one.
+    /// The network tag wins, then the network where the target channel is
+    /// configured, then the first network.
+    fn route(&self, msg: &Message) -> Option<&Network> {
+        if let Some(name) = plugin_core::utils::network::network(msg) {
+            return self.network(name);
+        }
+        let target = match &msg.command {
+            Command::PRIVMSG(target, _) | Command::NOTICE(target, _) => Some(target),
+            _ => None,
+        };
+        target
+            .and_then(|t| self.networks.iter().find(|n| n.has_channel(t)))
+            .or_else(|| self.networks.first())
+    }
+
+    fn network(&self, name: &str) -> Option<&Network> {
+        self.networks.iter().find(|n| n.name == name)
+    }
+
+    async fn outbound_message(&self, message: &(&'static str, String, Message)) -> Result<()> {
+        let (orig_name, network_name, msg) = message;
+        let network = match self.network(network_name) {
+            Some(n) => n,
+            None => {
+                log::error!("Unknown network {network_name}, dropping message from {orig_name}");
+                return Ok(());
+            }
+        };
+        // so that plugins know where this message is going
+        let mut msg = msg.clone();
+        set_network(&mut msg, network_name);
+        let mut msg = match self.filter_outbound(network, msg).await {
+            Some(msg) => msg,
+            None => return Ok(()),
+        };
+
         // TODO don't crash if a plugin returns an error
         futures::stream::iter(self.plugins.iter())
             .map(Ok)
             .try_for_each_concurrent(5, |plugin| {
-                let (orig_name, msg) = &message;
+                let msg = &msg;
                 async move {
                     if &plugin.get_name() != orig_name {
                         plugin.out_message(msg).await?;
@@ -326,12 +1264,117 @@ impl Golem {
                 }
             })
             .await?;
-        let client = self.irc_client.lock().expect("lock golem irc client");
-        // TODO this is blocking
-        client.send(message.1.clone())?;
+        self.recent.record_outbound(*orig_name, &msg);
+        // the tag is only meaningful within the golem
+        strip_network(&mut msg);
+        let lines = network.caps.lock().expect("caps lock").split_message(msg);
+        for line in lines {
+            network.send(line)?;
+        }
         Ok(())
     }
 
+    /// Runs the filters of the enabled plugins in the config order, then the mute.
+    /// None when the message must not be sent.
+    async fn filter_outbound(&self, network: &Network, mut msg: Message) -> Option<Message> {
+        for plugin in &self.plugins {
+            let name = plugin.get_name();
+            if !self.plugin_states.is_enabled(name) {
+                continue;
+            }
+            match plugin.filter_out_message(&msg).await {
+                Ok(FilterDecision::Pass) => (),
+                Ok(FilterDecision::Drop { reason }) => {
+                    log::info!("Outbound message dropped by {name}: {reason}");
+                    self.metrics
+                        .inc_counter("golem_outbound_dropped_total", &[("filter", name)]);
+                    return None;
+                }
+                Ok(FilterDecision::Replace(replacement)) => {
+                    msg = replacement;
+                    set_network(&mut msg, &network.name);
+                }
+                // not through plugin_failed, which sends messages itself
+                Err(err) => {
+                    log::error!("filter_out_message error from plugin {name}, message kept: {err}");
+                    self.metrics
+                        .inc_counter("golem_plugin_errors_total", &[("plugin", name)]);
+                }
+            }
+        }
+        let casemapping = network.caps.lock().expect("caps lock").casemapping;
+        if let FilterDecision::Drop { reason } = self.mute.filter(&network.name, casemapping, &msg)
+        {
+            log::debug!("Outbound message dropped: {reason}");
+            self.metrics
+                .inc_counter("golem_outbound_dropped_total", &[("filter", "mute")]);
+            return None;
+        }
+        Some(msg)
+    }
+
+    async fn run_control_socket(&self) -> Result<()> {
+        let path = match &self.control_socket {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let listener = control::bind(path)?;
+        log::info!("Listening for control commands on {}", path.display());
+        control::serve(listener, |cmd| self.control_command(cmd)).await;
+        Err(anyhow!("Control socket exited"))
+    }
+
+    async fn control_command(&self, cmd: ControlCommand) -> ControlResponse {
+        match cmd {
+            ControlCommand::Say {
+                target,
+                text,
+                network,
+            } => {
+                let mut msg = Message::from(Outbound::reply(target, text));
+                if let Some(network) = network {
+                    if self.network(&network).is_none() {
+                        return ControlResponse::error(format!("Unknown network {network}"));
+                    }
+                    set_network(&mut msg, &network);
+                }
+                match self.send_out_of_band("control", msg).await {
+                    Ok(()) => ControlResponse::ok(),
+                    Err(err) => ControlResponse::error(format!("{err:#}")),
+                }
+            }
+            ControlCommand::Status => {
+                let now = Instant::now();
+                let networks = self
+                    .networks
+                    .iter()
+                    .map(|network| control::NetworkStatus {
+                        name: network.name.clone(),
+                        nickname: network.nick.as_ref().map(|keeper| {
+                            keeper
+                                .lock()
+                                .expect("nick keeper lock")
+                                .current()
+                                .to_string()
+                        }),
+                        lag: network.lag.lag(now, self.lag_probe_interval).to_string(),
+                    })
+                    .collect();
+                let plugins = self
+                    .plugins
+                    .iter()
+                    .map(|p| p.get_name().to_string())
+                    .collect();
+                ControlResponse::status(control::Status { networks, plugins })
+            }
+            // the plugins and the networks are only set up at startup
+            ControlCommand::Reload => match self.reload() {
+                Ok(()) => ControlResponse::ok(),
+                Err(err) => ControlResponse::error(format!("{err:#}")),
+            },
+        }
+    }
+
     async fn run_server(&self, router: Option<Router<()>>) -> Result<()> {
         let router = match router {
             Some(r) => r,
@@ -346,28 +1389,1179 @@ impl Golem {
     }
 }
 
-// The function https://docs.rs/irc/latest/irc/client/prelude/enum.Response.html#method.is_error
-// is broken, and consider anything with a code above 400 to be an error
-// which doesn't account for SASL successes 900, 901, 902 and 903
-fn is_sasl_error(resp: &Response) -> bool {
-    // https://ircv3.net/specs/extensions/sasl-3.1.html
-    *resp as u16 >= 904
+/// SIGINT or SIGTERM
+async fn shutdown_signal() -> Result<()> {
+    let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = terminate.recv() => (),
+    }
+    Ok(())
+}
+
+/// The current nick of the golem, None for the networks without a
+/// nickname, like the fake ones
+fn own_nick(network: &Network) -> Option<String> {
+    Some(
+        network
+            .nick
+            .as_ref()?
+            .lock()
+            .expect("nick keeper lock")
+            .current()
+            .to_string(),
+    )
+}
+
+/// The channel the golem just finished joining, if any. Unknown
+/// for the networks without a nickname, like the fake ones.
+fn self_join(network: &Network, msg: &Message) -> Option<String> {
+    let own_nick = own_nick(network)?;
+    let casemapping = network.caps.lock().expect("caps lock").casemapping;
+    network
+        .joins
+        .lock()
+        .expect("joins lock")
+        .on_message(msg, &own_nick, casemapping)
 }
 
-async fn init_plugin(config: &plugin_core::Config, name: &str) -> Result<Initialised> {
-    // TODO: generate a macro which automatically match the name
-    // with the correct module based on the exports of crate::plugins
-    let plugin = match name {
-        "crypto" => plugins::Crypto::init(&config).await,
-        "ctcp" => plugins::Ctcp::init(&config).await,
-        "echo" => plugins::Echo::init(&config).await,
-        "joke" => plugins::Joke::init(&config).await,
-        "republican_calendar" => plugins::RepublicanCalendar::init(&config).await,
-        "twitch" => plugin_twitch::Twitch::init(&config).await,
-        "url" => plugin_url::UrlPlugin::init(&config).await,
-        _ => return Err(anyhow!("Unknown plugin name: {}", name)),
+/// The channels the golem just left, parted or kicked out of
+fn self_part(network: &Network, msg: &Message) -> Vec<String> {
+    let own_nick = match own_nick(network) {
+        Some(nick) => nick,
+        None => return vec![],
     };
-    let plugin = plugin.with_context(|| format!("Cannot initalize plugin {}", name))?;
-    log::info!("Plugin initialized: {}", name);
-    Ok(plugin)
+    let casemapping = network.caps.lock().expect("caps lock").casemapping;
+    match &msg.command {
+        Command::PART(channels, _)
+            if msg
+                .source_nickname()
+                .map_or(false, |nick| casemapping.eq_ignore_case(nick, &own_nick)) =>
+        {
+            channels.split(',').map(String::from).collect()
+        }
+        Command::KICK(channels, nick, _) if casemapping.eq_ignore_case(nick, &own_nick) => {
+            channels.split(',').map(String::from).collect()
+        }
+        _ => vec![],
+    }
+}
+
+/// Messages addressed to the bot itself rather than to a channel
+fn is_private_message(msg: &Message) -> bool {
+    match &msg.command {
+        Command::PRIVMSG(target, _) | Command::NOTICE(target, _) => !target.is_channel_name(),
+        _ => false,
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nick::NickKeeper;
+    use async_trait::async_trait;
+    use plugin_core::Initialised;
+    use pretty_assertions::assert_eq;
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    /// Replies with the name of the network the message comes from
+    struct NetworkEcho;
+
+    #[async_trait]
+    impl Plugin for NetworkEcho {
+        async fn init(_config: &plugin_core::Config) -> plugin_core::Result<Initialised> {
+            Ok(Initialised::from(NetworkEcho))
+        }
+
+        fn get_name(&self) -> &'static str {
+            "network_echo"
+        }
+
+        async fn in_message(&self, msg: &Message) -> plugin_core::Result<Option<Outbound>> {
+            let text = match &msg.command {
+                Command::PRIVMSG(_, text) => text,
+                _ => return Ok(None),
+            };
+            let network = plugin_core::utils::network::network(msg).unwrap_or("none");
+            Ok(msg
+                .response_target()
+                .map(|t| Outbound::reply(t, format!("{network}: {text}"))))
+        }
+    }
+
+    /// Replies with the casemapping the plugins are given
+    struct CaseMappingEcho;
+
+    #[async_trait]
+    impl Plugin for CaseMappingEcho {
+        async fn init(_config: &plugin_core::Config) -> plugin_core::Result<Initialised> {
+            Ok(Initialised::from(CaseMappingEcho))
+        }
+
+        fn get_name(&self) -> &'static str {
+            "casemapping_echo"
+        }
+
+        async fn in_message_ctx(
+            &self,
+            ctx: &MsgCtx,
+            msg: &Message,
+        ) -> plugin_core::Result<Option<Outbound>> {
+            if !matches!(msg.command, Command::PRIVMSG(..)) {
+                return Ok(None);
+            }
+            Ok(msg
+                .response_target()
+                .map(|t| Outbound::reply(t, format!("{:?}", ctx.casemapping))))
+        }
+    }
+
+    /// Takes an hour to reply anything
+    struct Slow;
+
+    #[async_trait]
+    impl Plugin for Slow {
+        async fn init(_config: &plugin_core::Config) -> plugin_core::Result<Initialised> {
+            Ok(Initialised::from(Slow))
+        }
+
+        fn get_name(&self) -> &'static str {
+            "slow"
+        }
+
+        async fn in_message(&self, msg: &Message) -> plugin_core::Result<Option<Outbound>> {
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+            Ok(msg.response_target().map(|t| Outbound::reply(t, "finally")))
+        }
+    }
+
+    /// Always replies the same thing
+    struct Says {
+        name: &'static str,
+        text: &'static str,
+        allow_duplicates: bool,
+    }
+
+    #[async_trait]
+    impl Plugin for Says {
+        async fn init(_config: &plugin_core::Config) -> plugin_core::Result<Initialised> {
+            Ok(Initialised::from(Says {
+                name: "says",
+                text: "coucou",
+                allow_duplicates: false,
+            }))
+        }
+
+        fn get_name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn in_message(&self, msg: &Message) -> plugin_core::Result<Option<Outbound>> {
+            Ok(msg.response_target().map(|t| Outbound::reply(t, self.text)))
+        }
+
+        fn allow_duplicate_output(&self) -> bool {
+            self.allow_duplicates
+        }
+    }
+
+    /// Fails on every message
+    struct Failing {
+        calls: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Plugin for Failing {
+        async fn init(_config: &plugin_core::Config) -> plugin_core::Result<Initialised> {
+            Ok(Initialised::from(Failing {
+                calls: Arc::default(),
+            }))
+        }
+
+        fn get_name(&self) -> &'static str {
+            "failing"
+        }
+
+        async fn in_message(&self, _msg: &Message) -> plugin_core::Result<Option<Outbound>> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Err(plugin_core::Error::Synthetic(
+                "upstream API changed".to_string(),
+            ))
+        }
+    }
+
+    /// Fails with the error named in the message
+    struct Refusing;
+
+    #[async_trait]
+    impl Plugin for Refusing {
+        async fn init(_config: &plugin_core::Config) -> plugin_core::Result<Initialised> {
+            Ok(Initialised::from(Refusing))
+        }
+
+        fn get_name(&self) -> &'static str {
+            "refusing"
+        }
+
+        async fn in_message(&self, msg: &Message) -> plugin_core::Result<Option<Outbound>> {
+            let text = match &msg.command {
+                Command::PRIVMSG(_, text) => text.as_str(),
+                _ => return Ok(None),
+            };
+            Err(match text {
+                "visible" => plugin_core::Error::user_visible("Cette vidéo n'existe pas"),
+                "limited" => plugin_core::Error::RateLimited {
+                    retry_after: Some(Duration::from_secs(30)),
+                },
+                "missing" => plugin_core::Error::not_found("Url at index 3"),
+                _ => plugin_core::Error::Internal {
+                    source: "connection reset".into(),
+                    ctx: "Cannot fetch the title".to_string(),
+                },
+            })
+        }
+    }
+
+    /// Appends its suffix to everything said in channels, drops the secrets
+    struct Censor {
+        name: &'static str,
+        suffix: &'static str,
+    }
+
+    #[async_trait]
+    impl Plugin for Censor {
+        async fn init(_config: &plugin_core::Config) -> plugin_core::Result<Initialised> {
+            Ok(Initialised::from(Censor {
+                name: "censor",
+                suffix: "",
+            }))
+        }
+
+        fn get_name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn filter_out_message(&self, msg: &Message) -> plugin_core::Result<FilterDecision> {
+            let (target, text) = match &msg.command {
+                Command::PRIVMSG(target, text) => (target, text),
+                _ => return Ok(FilterDecision::Pass),
+            };
+            if text.contains("secret") {
+                Ok(FilterDecision::Drop {
+                    reason: format!("secret for {target}"),
+                })
+            } else if text.contains("broken") {
+                Err(plugin_core::Error::Synthetic("broken filter".to_string()))
+            } else {
+                Ok(FilterDecision::Replace(
+                    Command::PRIVMSG(target.clone(), format!("{text}{}", self.suffix)).into(),
+                ))
+            }
+        }
+    }
+
+    /// Greets the channels it joins, except the secret ones
+    struct Greeter;
+
+    #[async_trait]
+    impl Plugin for Greeter {
+        async fn init(_config: &plugin_core::Config) -> plugin_core::Result<Initialised> {
+            Ok(Initialised::from(Greeter))
+        }
+
+        fn get_name(&self) -> &'static str {
+            "greeter"
+        }
+
+        async fn on_self_join(
+            &self,
+            network: &str,
+            channel: &str,
+        ) -> plugin_core::Result<Vec<Outbound>> {
+            if channel == "#secret" {
+                return Err(plugin_core::Error::Synthetic("not here".to_string()));
+            }
+            Ok(vec![Outbound::reply(
+                channel,
+                format!("coucou {channel} on {network}"),
+            )])
+        }
+    }
+
+    /// Remembers the channels the golem left
+    struct Leaver {
+        left: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Plugin for Leaver {
+        async fn init(_config: &plugin_core::Config) -> plugin_core::Result<Initialised> {
+            Ok(Initialised::from(Leaver {
+                left: Arc::default(),
+            }))
+        }
+
+        fn get_name(&self) -> &'static str {
+            "leaver"
+        }
+
+        async fn on_self_part(&self, channel: &str) -> plugin_core::Result<()> {
+            self.left.lock().unwrap().push(channel.to_string());
+            Ok(())
+        }
+    }
+
+    fn says(name: &'static str, text: &'static str) -> Box<dyn Plugin> {
+        Box::new(Says {
+            name,
+            text,
+            allow_duplicates: false,
+        })
+    }
+
+    fn golem(networks: Vec<Network>) -> Golem {
+        Golem {
+            networks,
+            plugins: vec![Box::new(NetworkEcho)],
+            address: ([127, 0, 0, 1], 0).into(),
+            router: None,
+            recent: Arc::new(RecentMessages::new(10, vec![])),
+            prefixes: CommandPrefixes::default(),
+            languages: Languages::default(),
+            lag_probe_interval: lag::MIN_PROBE_INTERVAL,
+            metrics: Arc::new(Metrics::default()),
+            members: Arc::default(),
+            settings: std::sync::RwLock::new(RuntimeSettings {
+                in_message_timeout: DEFAULT_IN_MESSAGE_TIMEOUT,
+                plugin_timeouts: HashMap::new(),
+                priorities: HashMap::new(),
+                pm_plugins: None,
+                admins: vec![],
+            }),
+            config_path: fixture("valid"),
+            journal: None,
+            control_socket: None,
+            join_delay: Duration::ZERO,
+            warm_up: Duration::ZERO,
+            plugin_states: PluginStates::new(ErrorBudget::default()),
+            mute: Mute::default(),
+            tasks: vec![],
+            shutdown: watch::channel(false).0,
+        }
+    }
+
+    fn privmsg(nick: &str, target: &str, text: &str) -> Message {
+        Message::new(
+            Some(format!("{nick}!~{nick}@localhost").as_str()),
+            "PRIVMSG",
+            vec![target, text],
+        )
+        .unwrap()
+    }
+
+    fn sent(rx: &mut UnboundedReceiver<Message>) -> Vec<String> {
+        let mut sent = vec![];
+        while let Ok(msg) = rx.try_recv() {
+            sent.push(msg.to_string());
+        }
+        sent
+    }
+
+    fn fixture(name: &str) -> String {
+        format!(
+            "{}/fixtures/check_config/{name}.dhall",
+            env!("CARGO_MANIFEST_DIR")
+        )
+    }
+
+    #[test]
+    async fn test_check_config() {
+        assert_eq!(check_config(&fixture("valid")), Vec::<String>::new());
+
+        let problems = check_config(&fixture("broken"));
+        let unknown_plugin = format!(
+            "Unknown plugin name: jokes. Did you mean joke? Known plugins: {}",
+            plugins::known_plugin_names().join(", ")
+        );
+        assert_eq!(
+            problems[..6],
+            [
+                "Unknown field comand_prefix. Did you mean command_prefix?",
+                &unknown_plugin,
+                "Invalid server_bind_address \"localhost\": invalid IP address syntax",
+                "Invalid nick in admins: \"Geeking frog\"",
+                "Invalid nick in blacklisted_users: \"*!*@spam.example\"",
+                "Invalid nick in blacklisted_users of libera: \"2fast\"",
+            ]
+        );
+        assert!(
+            problems[6].starts_with("Invalid config for plugin url: "),
+            "{problems:?}"
+        );
+        assert_eq!(problems.len(), 7);
+
+        let problems = check_config(&fixture("wrong_type"));
+        assert_eq!(
+            problems,
+            vec!["Invalid golem config: invalid type: string \"7777\", expected u16"]
+        );
+
+        let problems = check_config(&fixture("not_dhall"));
+        assert_eq!(problems.len(), 1);
+        assert!(
+            problems[0].starts_with("Cannot parse the config: "),
+            "{problems:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reply_on_the_same_network() {
+        let (libera, libera_in, mut libera_out) = network::fake("libera", &["#rust"]);
+        let (private, private_in, mut private_out) = network::fake("private", &["#rust"]);
+        let golem = golem(vec![libera, private]);
+
+        private_in
+            .send(privmsg("charlie", "#rust", "coucou"))
+            .unwrap();
+        drop(private_in);
+        libera_in.send(privmsg("alice", "#rust", "hello")).unwrap();
+        drop(libera_in);
+
+        // the streams end once the fake connections are dropped
+        assert!(golem
+            .recv_network_messages(&golem.networks[1])
+            .await
+            .is_err());
+        assert_eq!(
+            sent(&mut private_out),
+            vec!["PRIVMSG #rust :private: coucou\r\n"]
+        );
+        assert_eq!(sent(&mut libera_out), Vec::<String>::new());
+
+        assert!(golem
+            .recv_network_messages(&golem.networks[0])
+            .await
+            .is_err());
+        assert_eq!(
+            sent(&mut libera_out),
+            vec!["PRIVMSG #rust :libera: hello\r\n"]
+        );
+        assert_eq!(sent(&mut private_out), Vec::<String>::new());
+    }
+
+    #[tokio::test]
+    async fn test_casemapping_of_the_network() {
+        let (libera, libera_in, mut libera_out) = network::fake("libera", &["#rust"]);
+        let mut golem = golem(vec![libera]);
+        golem.plugins = vec![Box::new(CaseMappingEcho)];
+
+        libera_in.send(privmsg("alice", "#rust", "before")).unwrap();
+        let isupport = ":irc.server 005 golem CASEMAPPING=ascii :are supported by this server\r\n";
+        libera_in.send(isupport.parse().unwrap()).unwrap();
+        libera_in.send(privmsg("alice", "#rust", "after")).unwrap();
+        drop(libera_in);
+
+        assert!(golem
+            .recv_network_messages(&golem.networks[0])
+            .await
+            .is_err());
+        assert_eq!(
+            sent(&mut libera_out),
+            vec!["PRIVMSG #rust :Rfc1459\r\n", "PRIVMSG #rust :Ascii\r\n"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blacklist_per_network() {
+        let (libera, libera_in, mut libera_out) = network::fake("libera", &["#rust"]);
+        let (mut private, private_in, mut private_out) = network::fake("private", &["#rust"]);
+        private.blacklisted_users = vec!["bot".to_string()];
+        let golem = golem(vec![libera, private]);
+
+        libera_in.send(privmsg("bot", "#rust", "beep")).unwrap();
+        drop(libera_in);
+        private_in.send(privmsg("bot", "#rust", "beep")).unwrap();
+        drop(private_in);
+
+        assert!(golem
+            .recv_network_messages(&golem.networks[0])
+            .await
+            .is_err());
+        assert!(golem
+            .recv_network_messages(&golem.networks[1])
+            .await
+            .is_err());
+        assert_eq!(
+            sent(&mut libera_out),
+            vec!["PRIVMSG #rust :libera: beep\r\n"]
+        );
+        assert_eq!(sent(&mut private_out), Vec::<String>::new());
+    }
+
+    #[tokio::test]
+    async fn test_private_messages() {
+        let (mut libera, libera_in, mut libera_out) = network::fake("libera", &["#rust"]);
+        libera.blacklisted_users = vec!["bot".to_string()];
+        let mut golem = golem(vec![libera]);
+        golem.plugins = vec![Box::new(NetworkEcho), says("joke", "private joke")];
+        golem.settings.get_mut().unwrap().pm_plugins = Some(vec!["network_echo".to_string()]);
+
+        libera_in
+            .send(privmsg("alice", "rustygolem", "hello there"))
+            .unwrap();
+        libera_in
+            .send(privmsg("bot", "rustygolem", "beep boop"))
+            .unwrap();
+        libera_in
+            .send(privmsg("alice", "#rust", "hello everyone"))
+            .unwrap();
+        drop(libera_in);
+
+        assert!(golem
+            .recv_network_messages(&golem.networks[0])
+            .await
+            .is_err());
+        assert_eq!(
+            sent(&mut libera_out),
+            vec![
+                "PRIVMSG alice :libera: hello there\r\n",
+                "PRIVMSG #rust :libera: hello everyone\r\n",
+                "PRIVMSG #rust :private joke\r\n",
+            ],
+            "only the pm plugins answer in private, and never to blacklisted users"
+        );
+
+        assert!(is_private_message(&privmsg(
+            "alice",
+            "rustygolem",
+            "hello there"
+        )));
+        assert!(!is_private_message(&privmsg(
+            "alice",
+            "#rust",
+            "hello there"
+        )));
+    }
+
+    fn end_of_motd() -> Message {
+        Command::Response(
+            Response::RPL_ENDOFMOTD,
+            vec![
+                "rustygolem".to_string(),
+                "End of /MOTD command.".to_string(),
+            ],
+        )
+        .into()
+    }
+
+    #[tokio::test]
+    async fn test_on_self_part() {
+        let (mut libera, libera_in, _libera_out) = network::fake("libera", &["#rust"]);
+        libera.nick = Some(std::sync::Mutex::new(NickKeeper::new("golem", None)));
+        let mut golem = golem(vec![libera]);
+        let left = Arc::new(std::sync::Mutex::new(vec![]));
+        golem.plugins = vec![Box::new(Leaver {
+            left: Arc::clone(&left),
+        })];
+
+        for (source, command, args) in [
+            ("alice!~alice@localhost", "PART", vec!["#rust"]),
+            (
+                "golem!~golem@localhost",
+                "PART",
+                vec!["#rust,#ocaml", "bye"],
+            ),
+            (
+                "alice!~alice@localhost",
+                "KICK",
+                vec!["#haskell", "bob", "out"],
+            ),
+            (
+                "alice!~alice@localhost",
+                "KICK",
+                vec!["#haskell", "Golem", "out"],
+            ),
+        ] {
+            libera_in
+                .send(Message::new(Some(source), command, args).unwrap())
+                .unwrap();
+        }
+        drop(libera_in);
+
+        assert!(golem
+            .recv_network_messages(&golem.networks[0])
+            .await
+            .is_err());
+        assert_eq!(*left.lock().unwrap(), vec!["#rust", "#ocaml", "#haskell"]);
+    }
+
+    #[tokio::test]
+    async fn test_on_self_join() {
+        let (mut libera, libera_in, mut libera_out) = network::fake("libera", &["#rust"]);
+        libera.nick = Some(std::sync::Mutex::new(NickKeeper::new("golem", None)));
+        let mut golem = golem(vec![libera]);
+        golem.plugins = vec![Box::new(Greeter), Box::new(NetworkEcho)];
+
+        let join = |nick: &str, channel: &str| {
+            Message::new(
+                Some(format!("{nick}!~{nick}@localhost").as_str()),
+                "JOIN",
+                vec![channel],
+            )
+            .unwrap()
+        };
+        let end_of_names = |channel: &str| {
+            Message::new(
+                Some("irc.libera.chat"),
+                "366",
+                vec!["golem", channel, "End of /NAMES list."],
+            )
+            .unwrap()
+        };
+        for msg in [
+            join("golem", "#secret"),
+            end_of_names("#secret"),
+            join("alice", "#rust"),
+            join("golem", "#rust"),
+            end_of_names("#rust"),
+            join("bob", "#rust"),
+        ] {
+            libera_in.send(msg).unwrap();
+        }
+        drop(libera_in);
+
+        assert!(golem
+            .recv_network_messages(&golem.networks[0])
+            .await
+            .is_err());
+        assert_eq!(
+            sent(&mut libera_out),
+            vec!["PRIVMSG #rust :coucou #rust on libera\r\n"],
+            "only after our own join completed, an error doesn't prevent the next joins"
+        );
+        assert!(golem.members.has_channel("libera", "#secret"));
+        assert!(golem.members.is_member("libera", "#rust", "bob"));
+        assert!(
+            !golem.members.is_member("libera", "#rust", "alice"),
+            "before our join"
+        );
+        let metrics = golem.metrics.render();
+        assert!(
+            metrics.contains(r#"golem_plugin_errors_total{plugin="greeter"} 1"#),
+            "{metrics}"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_join_pacing() {
+        let (libera, libera_in, mut libera_out) =
+            network::fake("libera", &["#rust", "#Haskell-fr", "#arch-fr-free"]);
+        let mut golem = golem(vec![libera]);
+        golem.join_delay = Duration::from_millis(500);
+        let network = &golem.networks[0];
+
+        let start = tokio::time::Instant::now();
+        libera_in.send(end_of_motd()).unwrap();
+        let joins = async {
+            let mut joins = vec![];
+            while joins.len() < 3 {
+                let msg = libera_out.recv().await.unwrap();
+                if let Command::JOIN(channel, _, _) = msg.command {
+                    joins.push((channel, start.elapsed()));
+                }
+            }
+            joins
+        };
+        let joins = tokio::select! {
+            res = golem.recv_network_messages(network) => panic!("stream exited: {res:?}"),
+            res = golem.run_channel_join(network) => panic!("joins exited: {res:?}"),
+            joins = joins => joins,
+        };
+        assert_eq!(
+            joins,
+            vec![
+                ("#rust".to_string(), Duration::ZERO),
+                ("#Haskell-fr".to_string(), Duration::from_millis(500)),
+                ("#arch-fr-free".to_string(), Duration::from_millis(1000)),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_warm_up() {
+        let (libera, libera_in, mut libera_out) = network::fake("libera", &["#rust"]);
+        let mut golem = golem(vec![libera]);
+        golem.warm_up = Duration::from_secs(5);
+        let network = &golem.networks[0];
+        let announce = || Command::PRIVMSG("#rust".to_string(), "coucou is live".to_string());
+
+        let start = tokio::time::Instant::now();
+        let sent_at = async {
+            // not even registered yet, held until registration and warm-up
+            golem.send_out_of_band("twitch", announce().into()).await?;
+            let first = start.elapsed();
+            golem.send_out_of_band("twitch", announce().into()).await?;
+            Ok::<_, anyhow::Error>((first, start.elapsed()))
+        };
+        let registration = async {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            libera_in.send(end_of_motd()).unwrap();
+            golem.recv_network_messages(network).await
+        };
+        let (first, second) = tokio::select! {
+            res = registration => panic!("stream exited: {res:?}"),
+            res = sent_at => res.unwrap(),
+        };
+        assert_eq!(first, Duration::from_secs(7));
+        assert_eq!(
+            second,
+            Duration::from_secs(7),
+            "no more waiting once warmed up"
+        );
+        assert_eq!(
+            sent(&mut libera_out),
+            vec![
+                "PRIVMSG #rust :coucou is live\r\n",
+                "PRIVMSG #rust :coucou is live\r\n"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_error_budget() {
+        let (libera, libera_in, mut libera_out) = network::fake("libera", &["#rust"]);
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut golem = golem(vec![libera]);
+        golem.plugins = vec![Box::new(Failing {
+            calls: Arc::clone(&calls),
+        })];
+        golem.settings.get_mut().unwrap().admins = vec!["admin".to_string()];
+        golem.plugin_states = PluginStates::new(ErrorBudget {
+            max_failures: 2,
+            window: Duration::from_secs(600),
+        });
+
+        for _ in 0..3 {
+            libera_in
+                .send(privmsg("alice", "#rust", "hello there"))
+                .unwrap();
+        }
+        for (nick, text) in [
+            ("admin", "λadmin plugin list"),
+            ("alice", "λadmin plugin enable failing"),
+            ("admin", "λadmin plugin enable failing"),
+        ] {
+            libera_in.send(privmsg(nick, "#rust", text)).unwrap();
+        }
+        drop(libera_in);
+
+        assert!(golem
+            .recv_network_messages(&golem.networks[0])
+            .await
+            .is_err());
+        assert_eq!(
+            sent(&mut libera_out),
+            vec![
+                "NOTICE admin :Plugin failing disabled after 2 failures within 10min 0s, last error: Generic plugin error upstream API changed\r\n",
+                "PRIVMSG #rust :failing: disabled after errors (Generic plugin error upstream API changed)\r\n",
+                "PRIVMSG #rust :Plugin failing enabled\r\n",
+            ]
+        );
+        assert_eq!(
+            calls.load(std::sync::atomic::Ordering::SeqCst),
+            3,
+            "not called while disabled, called again once enabled"
+        );
+        assert!(golem.plugin_states.is_enabled("failing"));
+    }
+
+    #[tokio::test]
+    async fn test_error_translation() {
+        let (libera, libera_in, mut libera_out) = network::fake("libera", &["#rust"]);
+        let mut golem = golem(vec![libera]);
+        golem.plugins = vec![Box::new(Refusing)];
+        golem.plugin_states = PluginStates::new(ErrorBudget {
+            max_failures: 1,
+            window: Duration::from_secs(600),
+        });
+
+        for text in ["visible", "limited", "missing", "broken", "visible"] {
+            libera_in.send(privmsg("alice", "#rust", text)).unwrap();
+        }
+        drop(libera_in);
+
+        assert!(golem
+            .recv_network_messages(&golem.networks[0])
+            .await
+            .is_err());
+        assert_eq!(
+            sent(&mut libera_out),
+            vec![
+                "PRIVMSG #rust :Cette vidéo n'existe pas\r\n",
+                "PRIVMSG #rust :Too many requests, try again in 30s\r\n",
+                "PRIVMSG #rust :Url at index 3 not found\r\n",
+            ],
+            "internal errors are only logged, and disable the plugin"
+        );
+        assert_eq!(
+            golem.plugin_states.status("refusing"),
+            plugin_state::Status::Failing {
+                last_error: "Cannot fetch the title, plugin error from \"connection reset\""
+                    .to_string()
+            },
+            "only the internal error counts as a failure"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_route_out_of_band_messages() {
+        let (libera, _libera_in, mut libera_out) = network::fake("libera", &["#rust"]);
+        let (private, _private_in, mut private_out) = network::fake("private", &["#secret"]);
+        let golem = golem(vec![libera, private]);
+
+        let mut tagged: Message =
+            Command::PRIVMSG("#rust".to_string(), "tagged message".to_string()).into();
+        set_network(&mut tagged, "private");
+        let by_channel: Message =
+            Command::PRIVMSG("#Secret".to_string(), "by channel".to_string()).into();
+        let unknown: Message =
+            Command::PRIVMSG("#nowhere".to_string(), "fallback".to_string()).into();
+
+        assert_eq!(
+            golem.route(&tagged).map(|n| n.name.as_str()),
+            Some("private")
+        );
+        assert_eq!(
+            golem.route(&by_channel).map(|n| n.name.as_str()),
+            Some("private")
+        );
+        assert_eq!(
+            golem.route(&unknown).map(|n| n.name.as_str()),
+            Some("libera")
+        );
+
+        golem
+            .outbound_message(&("test", "private".to_string(), tagged))
+            .await
+            .unwrap();
+        golem
+            .outbound_message(&("test", "nope".to_string(), unknown))
+            .await
+            .unwrap();
+        assert_eq!(
+            sent(&mut private_out),
+            vec!["PRIVMSG #rust :tagged message\r\n"],
+            "the network tag is never sent"
+        );
+        assert_eq!(sent(&mut libera_out), Vec::<String>::new());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_in_message_timeout() {
+        let (libera, _libera_in, _libera_out) = network::fake("libera", &["#rust"]);
+        let mut golem = golem(vec![libera]);
+        golem.plugins = vec![Box::new(Slow), Box::new(NetworkEcho)];
+        let msg = privmsg("alice", "#rust", "hello");
+
+        let started = tokio::time::Instant::now();
+        let replies = golem
+            .plugins_in_messages(&golem.networks[0], &msg)
+            .await
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(11));
+        let replies = replies
+            .into_iter()
+            .map(|r| r.map(|(name, _, _)| name))
+            .collect::<Vec<_>>();
+        assert_eq!(replies, vec![None, Some("network_echo")]);
+        assert!(golem
+            .metrics
+            .render()
+            .contains("golem_plugin_timeouts_total{plugin=\"slow\"} 1"));
+
+        golem
+            .settings
+            .get_mut()
+            .unwrap()
+            .plugin_timeouts
+            .insert("slow".to_string(), Duration::from_secs(7200));
+        let replies = golem
+            .plugins_in_messages(&golem.networks[0], &msg)
+            .await
+            .unwrap();
+        assert_eq!(replies.iter().flatten().count(), 2);
+    }
+
+    async fn deduped_replies(golem: &Golem) -> Vec<&'static str> {
+        let msg = privmsg("alice", "#rust", "https://twitch.tv/coucou");
+        let replies = golem
+            .plugins_in_messages(&golem.networks[0], &msg)
+            .await
+            .unwrap();
+        golem
+            .dedup(replies.into_iter().flatten())
+            .into_iter()
+            .map(|(name, _, _)| name)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_outbound_filters() {
+        let (libera, _libera_in, mut libera_out) = network::fake("libera", &["#rust"]);
+        let mut golem = golem(vec![libera]);
+        golem.plugins = vec![
+            Box::new(Censor {
+                name: "first",
+                suffix: " [1]",
+            }),
+            Box::new(Censor {
+                name: "second",
+                suffix: " [2]",
+            }),
+        ];
+
+        let say = |text: &str| -> (&'static str, String, Message) {
+            let msg = Command::PRIVMSG("#rust".to_string(), text.to_string()).into();
+            ("test", "libera".to_string(), msg)
+        };
+        golem.outbound_message(&say("hello")).await.unwrap();
+        golem.outbound_message(&say("a secret")).await.unwrap();
+        golem.outbound_message(&say("broken")).await.unwrap();
+        let notice = Command::NOTICE("#rust".to_string(), "notice".to_string()).into();
+        golem
+            .outbound_message(&("test", "libera".to_string(), notice))
+            .await
+            .unwrap();
+        golem.plugin_states.disable("first");
+        golem.outbound_message(&say("disabled")).await.unwrap();
+
+        assert_eq!(
+            sent(&mut libera_out),
+            vec![
+                "PRIVMSG #rust :hello [1] [2]\r\n",
+                "PRIVMSG #rust :broken\r\n",
+                "NOTICE #rust :notice\r\n",
+                "PRIVMSG #rust :disabled [2]\r\n",
+            ],
+            "filters are applied in the plugins order, failing ones let the message pass"
+        );
+        let metrics = golem.metrics.render();
+        assert!(
+            metrics.contains(r#"golem_outbound_dropped_total{filter="first"} 1"#),
+            "{metrics}"
+        );
+        assert!(
+            metrics.contains(r#"golem_plugin_errors_total{plugin="second"} 1"#),
+            "{metrics}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mute() {
+        let (libera, libera_in, mut libera_out) = network::fake("libera", &["#rust", "#golem"]);
+        let mut golem = golem(vec![libera]);
+        golem.settings.get_mut().unwrap().admins = vec!["admin".to_string()];
+
+        for (nick, channel, text) in [
+            ("admin", "#golem", "λadmin mute #Rust"),
+            ("alice", "#rust", "hello"),
+            ("admin", "#golem", "λadmin unmute #rust"),
+            ("alice", "#rust", "back"),
+        ] {
+            libera_in.send(privmsg(nick, channel, text)).unwrap();
+        }
+        drop(libera_in);
+
+        assert!(golem
+            .recv_network_messages(&golem.networks[0])
+            .await
+            .is_err());
+        assert_eq!(
+            sent(&mut libera_out),
+            vec![
+                "PRIVMSG #golem :Muted #Rust\r\n",
+                "PRIVMSG #golem :libera: λadmin mute #Rust\r\n",
+                "PRIVMSG #golem :Unmuted #rust\r\n",
+                "PRIVMSG #golem :libera: λadmin unmute #rust\r\n",
+                "PRIVMSG #rust :libera: back\r\n",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plugin_output_unchanged() {
+        let (libera, libera_in, mut libera_out) = network::fake("libera", &["#rust"]);
+        let mut golem = golem(vec![libera]);
+        golem.plugins = vec![Box::new(plugins::Echo::default())];
+
+        libera_in
+            .send(privmsg("alice", "#rust", "hello there"))
+            .unwrap();
+        drop(libera_in);
+        assert!(golem
+            .recv_network_messages(&golem.networks[0])
+            .await
+            .is_err());
+        assert_eq!(
+            sent(&mut libera_out),
+            vec!["PRIVMSG #rust :echo - hello there\r\n"]
+        );
+    }
+
+    #[test]
+    async fn test_reload_invalid_config() {
+        let (libera, _libera_in, _libera_out) = network::fake("libera", &["#rust"]);
+        let mut golem = golem(vec![libera]);
+        golem.config_path = fixture("broken");
+        golem.settings.get_mut().unwrap().admins = vec!["admin".to_string()];
+        assert!(golem.reload().is_err());
+        assert_eq!(
+            golem.settings.read().unwrap().admins,
+            vec!["admin".to_string()],
+            "unchanged"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_control_socket() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("golem.sock");
+        let (libera, _libera_in, mut libera_out) = network::fake("libera", &["#rust"]);
+        let mut golem = golem(vec![libera]);
+        golem.control_socket = Some(path.clone());
+
+        let client = async {
+            let stream = loop {
+                match tokio::net::UnixStream::connect(&path).await {
+                    Ok(stream) => break stream,
+                    Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            };
+            let (read, mut write) = stream.into_split();
+            write
+                .write_all(
+                    concat!(
+                        r##"{"cmd":"say","target":"#rust","text":"deploy finished"}"##,
+                        "\n",
+                        r##"{"cmd":"say","target":"#rust","text":"hi","network":"nope"}"##,
+                        "\n",
+                        r#"{"cmd":"status"}"#,
+                        "\n",
+                        r#"{"cmd":"reload"}"#,
+                        "\n",
+                    )
+                    .as_bytes(),
+                )
+                .await
+                .unwrap();
+            let mut lines = BufReader::new(read).lines();
+            let mut responses = vec![];
+            for _ in 0..4 {
+                responses.push(lines.next_line().await.unwrap().unwrap());
+            }
+            responses
+        };
+        let responses = tokio::select! {
+            res = golem.run_control_socket() => panic!("control socket exited: {res:?}"),
+            responses = client => responses,
+        };
+
+        assert_eq!(responses[0], r#"{"ok":true}"#);
+        assert_eq!(
+            responses[1],
+            r#"{"ok":false,"error":"Unknown network nope"}"#
+        );
+        assert_eq!(
+            responses[2],
+            r#"{"ok":true,"networks":[{"name":"libera","nickname":null,"lag":"no lag measurement yet"}],"plugins":["network_echo"]}"#
+        );
+        assert_eq!(responses[3], r#"{"ok":true}"#);
+        assert_eq!(
+            golem.settings.read().unwrap().admins,
+            vec!["Geekingfrog".to_string()],
+            "reloaded from the config"
+        );
+        assert_eq!(
+            sent(&mut libera_out),
+            vec!["PRIVMSG #rust :deploy finished\r\n"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dedup_replies() {
+        let (libera, _libera_in, _libera_out) = network::fake("libera", &["#rust"]);
+        let mut golem = golem(vec![libera]);
+        golem.plugins = vec![
+            says("twitch", "coucou is live: https://twitch.tv/coucou"),
+            says("url", "Coucou is  live:\thttps://twitch.tv/coucou "),
+            says("joke", "coucou is live"),
+        ];
+        assert_eq!(deduped_replies(&golem).await, vec!["twitch", "joke"]);
+
+        golem.plugins.push(Box::new(Says {
+            name: "echo",
+            text: "coucou is live",
+            allow_duplicates: true,
+        }));
+        assert_eq!(
+            deduped_replies(&golem).await,
+            vec!["twitch", "joke", "echo"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plugin_priorities() {
+        let (libera, _libera_in, _libera_out) = network::fake("libera", &["#rust"]);
+        let mut golem = golem(vec![libera]);
+        golem.plugins = vec![
+            says("echo", "echo - https://twitch.tv/coucou"),
+            says("url", "Coucou - Twitch"),
+            says("twitch", "coucou is live"),
+        ];
+        assert_eq!(
+            deduped_replies(&golem).await,
+            vec!["echo", "url", "twitch"],
+            "the plugins order by default"
+        );
+
+        golem.settings.get_mut().unwrap().priorities = HashMap::from([
+            (
+                "twitch".to_string(),
+                Priority {
+                    priority: 10,
+                    exclusive: false,
+                },
+            ),
+            (
+                "url".to_string(),
+                Priority {
+                    priority: 50,
+                    exclusive: false,
+                },
+            ),
+        ]);
+        assert_eq!(deduped_replies(&golem).await, vec!["twitch", "url", "echo"]);
+
+        golem.settings.get_mut().unwrap().priorities.insert(
+            "url".to_string(),
+            Priority {
+                priority: 50,
+                exclusive: true,
+            },
+        );
+        assert_eq!(
+            deduped_replies(&golem).await,
+            vec!["twitch", "url"],
+            "only the replies with a lower priority than the exclusive plugin are dropped"
+        );
+    }
 }
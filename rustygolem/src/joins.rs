@@ -0,0 +1,85 @@
+use crate::caps::CaseMapping;
+use irc::proto::{Command, Message, Response};
+use std::collections::HashSet;
+
+/// Channels the golem joined, until the server is done listing their
+/// members (RPL_ENDOFNAMES), at which point the join is complete.
+/// Replies to a NAMES sent for another reason aren't mistaken for a join.
+#[derive(Debug, Default)]
+pub struct PendingJoins {
+    /// normalized with the network's casemapping
+    channels: HashSet<String>,
+}
+
+impl PendingJoins {
+    /// The channel whose join just completed, as written by the server
+    pub fn on_message(
+        &mut self,
+        msg: &Message,
+        own_nick: &str,
+        casemapping: CaseMapping,
+    ) -> Option<String> {
+        match &msg.command {
+            Command::JOIN(channels, _, _)
+                if msg
+                    .source_nickname()
+                    .map_or(false, |nick| casemapping.eq_ignore_case(nick, own_nick)) =>
+            {
+                self.channels.extend(
+                    channels
+                        .split(',')
+                        .map(|channel| casemapping.normalize(channel)),
+                );
+                None
+            }
+            Command::Response(Response::RPL_ENDOFNAMES, args) => {
+                let channel = args.get(1)?;
+                self.channels
+                    .remove(&casemapping.normalize(channel))
+                    .then(|| channel.clone())
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn join(nick: &str, channel: &str) -> Message {
+        Message::new(
+            Some(&format!("{nick}!~{nick}@localhost")),
+            "JOIN",
+            vec![channel],
+        )
+        .unwrap()
+    }
+
+    fn end_of_names(channel: &str) -> Message {
+        Message::new(
+            Some("irc.libera.chat"),
+            "366",
+            vec!["golem", channel, "End of /NAMES list."],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    async fn test_own_joins() {
+        let mut joins = PendingJoins::default();
+        let casemapping = CaseMapping::Rfc1459;
+        let mut on_message = |msg: Message| joins.on_message(&msg, "Golem", casemapping);
+
+        assert_eq!(on_message(join("golem", "#Rust")), None);
+        assert_eq!(on_message(join("alice", "#haskell")), None);
+        assert_eq!(on_message(end_of_names("#haskell")), None, "not our join");
+        assert_eq!(on_message(end_of_names("#rust")), Some("#rust".to_string()));
+        assert_eq!(on_message(end_of_names("#rust")), None, "already joined");
+
+        assert_eq!(on_message(join("golem", "#a,#b")), None);
+        assert_eq!(on_message(end_of_names("#b")), Some("#b".to_string()));
+        assert_eq!(on_message(end_of_names("#a")), Some("#a".to_string()));
+    }
+}
@@ -0,0 +1,283 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use irc::proto::{Command, Message};
+use plugin_core::utils::network::{network, set_network};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: u64,
+    pub plugin: String,
+    pub network: Option<String>,
+    pub target: String,
+    pub text: String,
+    /// RFC3339 timestamp of when the message was queued
+    pub timestamp: String,
+}
+
+impl JournalEntry {
+    pub fn message(&self) -> Message {
+        let mut msg: Message = Command::PRIVMSG(self.target.clone(), self.text.clone()).into();
+        if let Some(network) = &self.network {
+            set_network(&mut msg, network);
+        }
+        msg
+    }
+}
+
+/// Write-ahead log of the messages sent by plugins out of band, so that
+/// announcements queued right before a restart are sent after it.
+/// One json entry per line, removed once the message has been sent.
+pub struct Journal {
+    path: PathBuf,
+    max_age: Duration,
+    state: Mutex<JournalState>,
+}
+
+#[derive(Default)]
+struct JournalState {
+    next_id: u64,
+    /// the ids below were left over by the previous run, the others are
+    /// appended by this one and sent by whoever appended them
+    first_live_id: u64,
+    pending: BTreeMap<u64, JournalEntry>,
+}
+
+impl Journal {
+    pub fn open<P: AsRef<Path>>(path: P, max_age: Duration) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut state = JournalState::default();
+        match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                for (i, line) in content.lines().enumerate() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    match serde_json::from_str::<JournalEntry>(line) {
+                        Ok(entry) => {
+                            state.next_id = state.next_id.max(entry.id + 1);
+                            state.pending.insert(entry.id, entry);
+                        }
+                        Err(err) => log::warn!(
+                            "Skipping corrupt line {} of journal {}: {err}",
+                            i + 1,
+                            path.display()
+                        ),
+                    }
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Cannot read journal at {}", path.display()))
+            }
+        }
+        state.first_live_id = state.next_id;
+        Ok(Journal {
+            path,
+            max_age,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Record a message about to be sent. Only PRIVMSG are journaled,
+    /// returns the id to ack once the message is sent.
+    pub fn append(&self, plugin: &str, msg: &Message, now: DateTime<Utc>) -> Result<Option<u64>> {
+        let (target, text) = match &msg.command {
+            Command::PRIVMSG(target, text) => (target, text),
+            _ => return Ok(None),
+        };
+        let mut state = self.state.lock().expect("journal lock");
+        let entry = JournalEntry {
+            id: state.next_id,
+            plugin: plugin.to_string(),
+            network: network(msg).map(String::from),
+            target: target.clone(),
+            text: text.clone(),
+            timestamp: now.to_rfc3339(),
+        };
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Cannot open journal at {}", self.path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        state.next_id += 1;
+        state.pending.insert(entry.id, entry.clone());
+        Ok(Some(entry.id))
+    }
+
+    /// The message has been sent, forget about it
+    pub fn ack(&self, id: u64) -> Result<()> {
+        let mut state = self.state.lock().expect("journal lock");
+        if state.pending.remove(&id).is_some() {
+            self.rewrite(&state)?;
+        }
+        Ok(())
+    }
+
+    /// Entries left over by a previous run which are still worth sending,
+    /// only once. The older ones are dropped from the journal. The entries
+    /// appended meanwhile aren't part of them, even when the plugins already
+    /// run, so that they aren't sent twice.
+    pub fn replay(&self, now: DateTime<Utc>) -> Result<Vec<JournalEntry>> {
+        let mut state = self.state.lock().expect("journal lock");
+        let max_age = chrono::Duration::from_std(self.max_age)?;
+        let before = state.pending.len();
+        state.pending.retain(
+            |_, entry| match DateTime::parse_from_rfc3339(&entry.timestamp) {
+                Ok(ts) => now.signed_duration_since(ts) <= max_age,
+                Err(err) => {
+                    log::warn!("Invalid timestamp in journal entry {entry:?}: {err}");
+                    false
+                }
+            },
+        );
+        if state.pending.len() != before {
+            log::info!(
+                "Dropping {} journal entries older than {:?}",
+                before - state.pending.len(),
+                self.max_age
+            );
+            self.rewrite(&state)?;
+        }
+        let left_over = state
+            .pending
+            .range(..state.first_live_id)
+            .map(|(_, entry)| entry.clone())
+            .collect();
+        state.first_live_id = 0;
+        Ok(left_over)
+    }
+
+    fn rewrite(&self, state: &JournalState) -> Result<()> {
+        let tmp_path = self.path.with_extension("tmp");
+        let mut content = String::new();
+        for entry in state.pending.values() {
+            content.push_str(&serde_json::to_string(entry)?);
+            content.push('\n');
+        }
+        std::fs::write(&tmp_path, content)
+            .with_context(|| format!("Cannot write journal at {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, &self.path)
+            .with_context(|| format!("Cannot replace journal at {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn privmsg(target: &str, text: &str) -> Message {
+        Command::PRIVMSG(target.to_string(), text.to_string()).into()
+    }
+
+    #[test]
+    async fn test_append_ack_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("outbound.jsonl");
+        let now = Utc::now();
+
+        let journal = Journal::open(&path, DEFAULT_MAX_AGE).unwrap();
+        let mut live = privmsg("#arch-fr-free", "coucou is live");
+        set_network(&mut live, "libera");
+        let first = journal.append("twitch", &live, now).unwrap().unwrap();
+        let second = journal
+            .append("rss", &privmsg("#rust", "new post"), now)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            journal
+                .append("rss", &Command::NICK("nope".to_string()).into(), now)
+                .unwrap(),
+            None
+        );
+        journal.ack(second).unwrap();
+
+        // restart
+        let journal = Journal::open(&path, DEFAULT_MAX_AGE).unwrap();
+        let replayed = journal.replay(now).unwrap();
+        assert_eq!(replayed.len(), 1);
+        assert_eq!(replayed[0].id, first);
+        assert_eq!(replayed[0].plugin, "twitch");
+        assert_eq!(
+            replayed[0].message().to_string(),
+            "@rustygolem/network=libera PRIVMSG #arch-fr-free :coucou is live\r\n"
+        );
+
+        // new ids never collide with the replayed ones
+        let third = journal.append("rss", &privmsg("#rust", "another post"), now);
+        assert_eq!(third.unwrap(), Some(first + 1));
+    }
+
+    #[test]
+    async fn test_replay_skips_live_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("outbound.jsonl");
+        let now = Utc::now();
+
+        let journal = Journal::open(&path, DEFAULT_MAX_AGE).unwrap();
+        let left_over = journal
+            .append("rss", &privmsg("#rust", "new post"), now)
+            .unwrap()
+            .unwrap();
+
+        // restart, a plugin sends something before the replay
+        let journal = Journal::open(&path, DEFAULT_MAX_AGE).unwrap();
+        let live = journal
+            .append("twitch", &privmsg("#arch-fr-free", "coucou is live"), now)
+            .unwrap()
+            .unwrap();
+        let replayed = journal.replay(now).unwrap();
+        assert_eq!(
+            replayed.iter().map(|e| e.id).collect::<Vec<_>>(),
+            vec![left_over],
+            "the live one is sent by its plugin"
+        );
+        assert_eq!(journal.replay(now).unwrap(), vec![], "only once");
+
+        journal.ack(live).unwrap();
+        let journal = Journal::open(&path, DEFAULT_MAX_AGE).unwrap();
+        assert_eq!(journal.replay(now).unwrap().len(), 1, "not acked yet");
+    }
+
+    #[test]
+    async fn test_age_cutoff_and_corrupt_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("outbound.jsonl");
+        let now = Utc::now();
+        let entry = |id: u64, age_secs: i64| JournalEntry {
+            id,
+            plugin: "twitch".to_string(),
+            network: None,
+            target: "#chan".to_string(),
+            text: format!("message {id}"),
+            timestamp: (now - chrono::Duration::seconds(age_secs)).to_rfc3339(),
+        };
+        let content = format!(
+            "{}\n{{\"id\": 2, \"plugin\"\n{}\n",
+            serde_json::to_string(&entry(1, 3600)).unwrap(),
+            serde_json::to_string(&entry(3, 60)).unwrap(),
+        );
+        std::fs::write(&path, content).unwrap();
+
+        let journal = Journal::open(&path, DEFAULT_MAX_AGE).unwrap();
+        assert_eq!(journal.replay(now).unwrap(), vec![entry(3, 60)]);
+
+        let journal = Journal::open(&path, DEFAULT_MAX_AGE).unwrap();
+        assert_eq!(
+            journal.replay(now).unwrap(),
+            vec![entry(3, 60)],
+            "old and corrupt entries are gone from the file"
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use irc::proto::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Never probe the server more often than that
+pub const MIN_PROBE_INTERVAL: Duration = Duration::from_secs(60);
+
+const TOKEN_PREFIX: &str = "rustygolem-lag-";
+
+/// Measure the round trip time to the IRC server by sending PING with a
+/// token and waiting for the matching PONG.
+/// All timestamps are monotonic.
+#[derive(Debug, Default)]
+pub struct LagProbe {
+    state: Mutex<LagState>,
+}
+
+#[derive(Debug, Default)]
+struct LagState {
+    counter: u64,
+    /// token and sending time of the probe waiting for an answer
+    pending: Option<(String, Instant)>,
+    /// last round trip time, and when it was measured
+    last: Option<(Duration, Instant)>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Lag {
+    Unknown,
+    /// the server hasn't answered the probe sent that long ago
+    NoAnswer(Duration),
+    Measured {
+        rtt: Duration,
+        age: Duration,
+    },
+}
+
+impl LagProbe {
+    /// Register a new probe and returns the token to send with the PING.
+    /// A previous probe still waiting for an answer is considered lost.
+    pub fn start(&self, now: Instant) -> String {
+        let mut state = self.state.lock().expect("lag probe lock");
+        state.counter += 1;
+        let token = format!("{TOKEN_PREFIX}{}", state.counter);
+        if let Some((lost, _)) = state.pending.replace((token.clone(), now)) {
+            log::warn!("No answer from the server for lag probe {lost}");
+        }
+        token
+    }
+
+    /// If the given command answers the pending probe, returns the round trip time
+    pub fn on_pong(&self, cmd: &Command, now: Instant) -> Option<Duration> {
+        let token = pong_token(cmd)?;
+        let mut state = self.state.lock().expect("lag probe lock");
+        match &state.pending {
+            Some((expected, sent_at)) if expected == token => {
+                let rtt = now.saturating_duration_since(*sent_at);
+                state.pending = None;
+                state.last = Some((rtt, now));
+                Some(rtt)
+            }
+            _ => None,
+        }
+    }
+
+    /// `patience` is how long to wait for an answer before reporting the
+    /// server as unresponsive
+    pub fn lag(&self, now: Instant, patience: Duration) -> Lag {
+        let state = self.state.lock().expect("lag probe lock");
+        match (&state.pending, &state.last) {
+            (Some((_, sent_at)), _) if now.saturating_duration_since(*sent_at) > patience => {
+                Lag::NoAnswer(now.saturating_duration_since(*sent_at))
+            }
+            (_, Some((rtt, measured_at))) => Lag::Measured {
+                rtt: *rtt,
+                age: now.saturating_duration_since(*measured_at),
+            },
+            _ => Lag::Unknown,
+        }
+    }
+}
+
+impl std::fmt::Display for Lag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Lag::Unknown => f.write_str("no lag measurement yet"),
+            Lag::NoAnswer(d) => write!(
+                f,
+                "the server didn't answer the lag probe sent {} ago",
+                format_duration(*d)
+            ),
+            Lag::Measured { rtt, age } => write!(
+                f,
+                "lag: {} (measured {} ago)",
+                format_duration(*rtt),
+                format_duration(*age)
+            ),
+        }
+    }
+}
+
+/// Extract the token of a PONG. Servers answer `PING token` either with
+/// `PONG server :token` or with `PONG :token`
+pub fn pong_token(cmd: &Command) -> Option<&str> {
+    match cmd {
+        Command::PONG(_, Some(token)) => Some(token),
+        Command::PONG(token, None) => Some(token),
+        _ => None,
+    }
+}
+
+pub fn format_duration(d: Duration) -> String {
+    if d < Duration::from_secs(1) {
+        format!("{}ms", d.as_millis())
+    } else if d < Duration::from_secs(60) {
+        format!("{:.2}s", d.as_secs_f64())
+    } else {
+        format!("{}min {}s", d.as_secs() / 60, d.as_secs() % 60)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_pong_token() {
+        assert_eq!(
+            pong_token(&Command::PONG(
+                "irc.libera.chat".to_string(),
+                Some("rustygolem-lag-1".to_string())
+            )),
+            Some("rustygolem-lag-1")
+        );
+        assert_eq!(
+            pong_token(&Command::PONG("rustygolem-lag-1".to_string(), None)),
+            Some("rustygolem-lag-1")
+        );
+        assert_eq!(
+            pong_token(&Command::PING("rustygolem-lag-1".to_string(), None)),
+            None
+        );
+    }
+
+    #[test]
+    async fn test_matching_token() {
+        let probe = LagProbe::default();
+        let t0 = Instant::now();
+        let token = probe.start(t0);
+
+        let other = Command::PONG("srv".to_string(), Some("someone-else".to_string()));
+        assert_eq!(probe.on_pong(&other, t0 + Duration::from_millis(10)), None);
+
+        let answer = Command::PONG("srv".to_string(), Some(token));
+        assert_eq!(
+            probe.on_pong(&answer, t0 + Duration::from_millis(42)),
+            Some(Duration::from_millis(42))
+        );
+        assert_eq!(
+            probe.on_pong(&answer, t0 + Duration::from_millis(50)),
+            None,
+            "a probe is only answered once"
+        );
+        assert_eq!(
+            probe.lag(t0 + Duration::from_millis(1042), MIN_PROBE_INTERVAL),
+            Lag::Measured {
+                rtt: Duration::from_millis(42),
+                age: Duration::from_secs(1)
+            }
+        );
+    }
+
+    #[test]
+    async fn test_lost_probe() {
+        let probe = LagProbe::default();
+        let t0 = Instant::now();
+        let first = probe.start(t0);
+        assert_eq!(probe.lag(t0, MIN_PROBE_INTERVAL), Lag::Unknown);
+
+        let t1 = t0 + MIN_PROBE_INTERVAL;
+        let second = probe.start(t1);
+        assert_ne!(first, second);
+
+        let late = Command::PONG("srv".to_string(), Some(first));
+        assert_eq!(
+            probe.on_pong(&late, t1 + Duration::from_millis(5)),
+            None,
+            "answers to lost probes are ignored"
+        );
+
+        let t2 = t1 + Duration::from_secs(90);
+        assert_eq!(
+            probe.lag(t2, MIN_PROBE_INTERVAL),
+            Lag::NoAnswer(Duration::from_secs(90))
+        );
+    }
+
+    #[test]
+    async fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_micros(1500)), "1ms");
+        assert_eq!(format_duration(Duration::from_millis(999)), "999ms");
+        assert_eq!(format_duration(Duration::from_millis(1234)), "1.23s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2min 5s");
+    }
+}
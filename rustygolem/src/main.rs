@@ -12,17 +12,38 @@ extern crate diesel;
 extern crate diesel_migrations;
 
 use anyhow::{Context, Result};
-use log::info;
 use structopt::StructOpt;
 
+// first, for register_plugins! to be usable in the other modules
+#[macro_use]
+mod registry;
+
+mod admin;
+pub mod build_info;
+mod caps;
+mod check;
+mod control;
 mod golem;
+mod joins;
+mod journal;
+mod lag;
+mod metrics;
+mod mute;
+mod network;
+mod nick;
+mod plugin_state;
 mod plugins;
+mod priority;
+mod recent;
+mod requirements;
 mod schema;
+mod supervisor;
 mod utils;
+mod web;
 
 #[derive(Debug, StructOpt)]
 struct Opt {
-    /// list of channels to join
+    /// list of channels to join, when the golem config doesn't define any network
     #[structopt(long)]
     channels: Vec<String>,
 
@@ -39,7 +60,11 @@ struct Opt {
     disable_tls: bool,
 
     #[structopt(long, default_value="golem_config.dhall")]
-    config: String
+    config: String,
+
+    /// only check the golem config, exits with 1 if it has any problem
+    #[structopt(long)]
+    check_config: bool,
 }
 
 #[tokio::main(flavor = "multi_thread")]
@@ -48,11 +73,18 @@ async fn main() -> Result<()> {
 
     let opt = Opt::from_args();
 
-    if opt.channels.is_empty() {
-        return Err(anyhow!("No channels to join, aborting"));
+    if opt.check_config {
+        let problems = golem::check_config(&opt.config);
+        if problems.is_empty() {
+            println!("{} is valid", opt.config);
+            return Ok(());
+        }
+        for problem in &problems {
+            eprintln!("{problem}");
+        }
+        std::process::exit(1);
     }
 
-    info!("Joining channel(s): {:?}", opt.channels);
     let alt_nicks = vec![format!("{}_", opt.nickname), "brokenGolem".to_string()];
 
     let config = Config {
@@ -0,0 +1,55 @@
+use axum::{extract::State, http::header, response::IntoResponse, routing, Router};
+use std::sync::Arc;
+
+/// The registry lives in plugin_core, so that plugins can add their own metrics
+pub use plugin_core::Metrics;
+
+pub fn router(metrics: Arc<Metrics>) -> Router<()> {
+    Router::new()
+        .route("/metrics", routing::get(get_metrics))
+        .with_state(metrics)
+}
+
+async fn get_metrics(State(metrics): State<Arc<Metrics>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::{Body, HttpBody};
+    use axum::http::{Request, StatusCode};
+    use plugin_core::MetricsHandle;
+    use pretty_assertions::assert_eq;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_plugin_metrics_are_served() {
+        let metrics = Arc::new(Metrics::default());
+        metrics.inc_counter("golem_timeouts_total", &[("plugin", "url")]);
+        MetricsHandle::new(Arc::clone(&metrics), "url")
+            .counter("fetches_total", &[("result", "ok")])
+            .inc();
+
+        let resp = router(metrics)
+            .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let mut body = resp.into_body();
+        let mut bytes = vec![];
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(
+            String::from_utf8_lossy(&bytes),
+            "# TYPE golem_timeouts_total counter\n\
+             golem_timeouts_total{plugin=\"url\"} 1\n\
+             # TYPE url_fetches_total counter\n\
+             url_fetches_total{result=\"ok\"} 1\n"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use crate::caps::CaseMapping;
+use irc::proto::{Command, Message};
+use plugin_core::FilterDecision;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+/// Channels where the golem doesn't say anything, set by the admins.
+/// Forgotten on restart.
+#[derive(Default)]
+pub struct Mute {
+    /// (network, channel normalized with the network's casemapping)
+    channels: Mutex<BTreeSet<(String, String)>>,
+}
+
+impl Mute {
+    /// false if the channel was already muted
+    pub fn mute(&self, network: &str, casemapping: CaseMapping, channel: &str) -> bool {
+        self.channels
+            .lock()
+            .expect("mute lock")
+            .insert((network.to_string(), casemapping.normalize(channel)))
+    }
+
+    /// false if the channel wasn't muted
+    pub fn unmute(&self, network: &str, casemapping: CaseMapping, channel: &str) -> bool {
+        self.channels
+            .lock()
+            .expect("mute lock")
+            .remove(&(network.to_string(), casemapping.normalize(channel)))
+    }
+
+    pub fn is_muted(&self, network: &str, casemapping: CaseMapping, channel: &str) -> bool {
+        self.channels
+            .lock()
+            .expect("mute lock")
+            .contains(&(network.to_string(), casemapping.normalize(channel)))
+    }
+
+    /// Drops anything said toward a muted channel of the network
+    pub fn filter(&self, network: &str, casemapping: CaseMapping, msg: &Message) -> FilterDecision {
+        let target = match &msg.command {
+            Command::PRIVMSG(target, _) | Command::NOTICE(target, _) => target,
+            _ => return FilterDecision::Pass,
+        };
+        if self.is_muted(network, casemapping, target) {
+            FilterDecision::Drop {
+                reason: format!("{target} is muted on {network}"),
+            }
+        } else {
+            FilterDecision::Pass
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn privmsg(target: &str) -> Message {
+        Command::PRIVMSG(target.to_string(), "coucou".to_string()).into()
+    }
+
+    #[test]
+    async fn test_mute() {
+        let mute = Mute::default();
+        let casemapping = CaseMapping::Rfc1459;
+        assert!(mute.mute("libera", casemapping, "#Rust[]"));
+        assert!(!mute.mute("libera", casemapping, "#rust{}"));
+
+        assert_eq!(
+            mute.filter("libera", casemapping, &privmsg("#RUST{}")),
+            FilterDecision::Drop {
+                reason: "#RUST{} is muted on libera".to_string()
+            }
+        );
+        assert_eq!(
+            mute.filter("oftc", casemapping, &privmsg("#rust[]")),
+            FilterDecision::Pass,
+            "muted on a single network"
+        );
+        let join: Message = Command::JOIN("#rust[]".to_string(), None, None).into();
+        assert_eq!(
+            mute.filter("libera", casemapping, &join),
+            FilterDecision::Pass
+        );
+
+        assert!(mute.unmute("libera", casemapping, "#rust[]"));
+        assert!(!mute.unmute("libera", casemapping, "#rust[]"));
+        assert_eq!(
+            mute.filter("libera", casemapping, &privmsg("#rust[]")),
+            FilterDecision::Pass
+        );
+    }
+}
@@ -0,0 +1,365 @@
+use crate::caps::ServerCaps;
+use crate::joins::PendingJoins;
+use crate::lag::LagProbe;
+use crate::nick::NickKeeper;
+use anyhow::{Context, Result};
+use futures::prelude::*;
+use irc::proto::{CapSubCommand, Command, Message, Response};
+use serde::Deserialize;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch, Mutex as AsyncMutex};
+use tokio::time::timeout;
+
+/// Name of the network when there is no network in the golem config,
+/// and only the command line is used to configure the connection.
+pub const DEFAULT_NETWORK: &str = "default";
+
+pub type MessageStream = Pin<Box<dyn Stream<Item = Result<Message>> + Send>>;
+
+/// Where to write the messages going to a network
+pub trait MessageSink: Send + Sync {
+    fn send(&self, msg: Message) -> Result<()>;
+}
+
+impl MessageSink for irc::client::Sender {
+    fn send(&self, msg: Message) -> Result<()> {
+        irc::client::Sender::send(self, msg)?;
+        Ok(())
+    }
+}
+
+impl MessageSink for mpsc::UnboundedSender<Message> {
+    fn send(&self, msg: Message) -> Result<()> {
+        mpsc::UnboundedSender::send(self, msg).map_err(|_| anyhow!("Message sink closed"))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NetworkConfig {
+    pub name: String,
+    pub server: String,
+    pub port: Option<u16>,
+    pub use_tls: Option<bool>,
+    /// defaults to the nickname given on the command line
+    pub nickname: Option<String>,
+    pub channels: Vec<String>,
+    pub sasl_password: Option<String>,
+    /// used to ghost whoever holds our nickname
+    pub nickserv_password: Option<String>,
+    /// in addition to the global blacklist
+    #[serde(default)]
+    pub blacklisted_users: Vec<String>,
+}
+
+impl NetworkConfig {
+    /// Irc configuration for this network. Everything not specified
+    /// in the network config is taken from `base`.
+    pub fn irc_config(&self, base: &irc::client::data::Config) -> irc::client::data::Config {
+        irc::client::data::Config {
+            nickname: self.nickname.clone().or_else(|| base.nickname.clone()),
+            server: Some(self.server.clone()),
+            port: self.port.or(base.port),
+            use_tls: self.use_tls.or(base.use_tls),
+            channels: self.channels.clone(),
+            ..base.clone()
+        }
+    }
+}
+
+/// A connection to an IRC network
+pub struct Network {
+    pub name: String,
+    /// Only used for the handshake. None for connections which don't
+    /// need any, like the fake ones in tests.
+    client: Option<Mutex<irc::client::Client>>,
+    sink: Box<dyn MessageSink>,
+    pub stream: AsyncMutex<MessageStream>,
+    sasl_password: Option<String>,
+    pub blacklisted_users: Vec<String>,
+    /// joined by the golem once registered, rather than by the irc crate,
+    /// to pace the joins
+    join_channels: Vec<String>,
+    /// lowercased, used to route messages which don't specify a network
+    channels: Vec<String>,
+    /// what the server advertised during registration
+    pub caps: Mutex<ServerCaps>,
+    /// when the registration to the network completed, None before
+    registered: watch::Sender<Option<tokio::time::Instant>>,
+    pub lag: LagProbe,
+    /// joined channels waiting for the end of their NAMES
+    pub joins: Mutex<PendingJoins>,
+    /// None for connections without handshake, since the nickname is unknown
+    pub nick: Option<Mutex<NickKeeper>>,
+}
+
+impl Network {
+    pub async fn connect(
+        name: &str,
+        irc_config: irc::client::data::Config,
+        sasl_password: Option<String>,
+        nickserv_password: Option<String>,
+        blacklisted_users: Vec<String>,
+    ) -> Result<Self> {
+        let mut irc_config = irc_config;
+        let channels = std::mem::take(&mut irc_config.channels);
+        let nick = irc_config
+            .nickname
+            .as_deref()
+            .map(|primary| Mutex::new(NickKeeper::new(primary, nickserv_password)));
+        let mut client = irc::client::Client::from_config(irc_config)
+            .await
+            .with_context(|| format!("Cannot connect to network {name}"))?;
+        let stream = client.stream()?.map_err(anyhow::Error::from);
+        let sink = client.sender();
+        let mut network = Network::new(name, Box::new(sink), Box::pin(stream), channels);
+        network.client = Some(Mutex::new(client));
+        network.sasl_password = sasl_password;
+        network.blacklisted_users = blacklisted_users;
+        network.nick = nick;
+        Ok(network)
+    }
+
+    pub fn new(
+        name: &str,
+        sink: Box<dyn MessageSink>,
+        stream: MessageStream,
+        channels: Vec<String>,
+    ) -> Self {
+        Network {
+            name: name.to_string(),
+            client: None,
+            sink,
+            stream: AsyncMutex::new(stream),
+            sasl_password: None,
+            blacklisted_users: vec![],
+            channels: channels.iter().map(|c| c.to_lowercase()).collect(),
+            join_channels: channels,
+            caps: Mutex::new(ServerCaps::default()),
+            registered: watch::channel(None).0,
+            lag: LagProbe::default(),
+            joins: Mutex::default(),
+            nick: None,
+        }
+    }
+
+    pub fn send(&self, msg: Message) -> Result<()> {
+        self.sink.send(msg)
+    }
+
+    pub fn join_channels(&self) -> &[String] {
+        &self.join_channels
+    }
+
+    /// The server is done with the welcome burst (end of MOTD), we can join
+    pub fn set_registered(&self, now: tokio::time::Instant) {
+        self.registered.send_replace(Some(now));
+    }
+
+    /// Changes every time the network registers, after a reconnection for example
+    pub fn registered(&self) -> watch::Receiver<Option<tokio::time::Instant>> {
+        self.registered.subscribe()
+    }
+
+    pub fn is_blacklisted(&self, nick: &str) -> bool {
+        let casemapping = self.caps.lock().expect("caps lock").casemapping;
+        self.blacklisted_users
+            .iter()
+            .any(|user| casemapping.eq_ignore_case(user, nick))
+    }
+
+    pub fn has_channel(&self, channel: &str) -> bool {
+        let channel = channel.to_lowercase();
+        self.channels.iter().any(|c| c == &channel)
+    }
+
+    pub async fn authenticate_and_identify(&self) -> Result<()> {
+        let client = match &self.client {
+            Some(client) => client,
+            None => return Ok(()),
+        };
+        match self.sasl_password {
+            None => {
+                log::info!(
+                    "No SASL password for network {}, not authenticating anything.",
+                    self.name
+                );
+                client.lock().unwrap().identify()?;
+                Ok(())
+            }
+            Some(ref password) => {
+                self.sasl_auth(client, password).await?;
+                Ok(())
+            }
+        }
+    }
+
+    // SASL PLAIN authentication
+    // https://ircv3.net/specs/extensions/sasl-3.1.html
+    async fn sasl_auth(&self, client: &Mutex<irc::client::Client>, password: &str) -> Result<()> {
+        let client = client.lock().unwrap();
+        let nick = client.current_nickname();
+        log::info!("Authenticating with SASL for {nick} on {}", self.name);
+
+        client.send_cap_req(&[irc::proto::Capability::Sasl])?;
+        // the call client.identify() provided by the irc library starts
+        // by sending a CAP END before sending NICK and USER messages.
+        // but as far as I can tell, this is incorrect for SASL, so manually send
+        // the stuff
+        client.send(Command::NICK(nick.to_string()))?;
+        client.send(Command::USER(
+            nick.to_string(),
+            "0".to_string(),
+            format!(":{nick}"),
+        ))?;
+
+        let duration = Duration::from_secs(10);
+        timeout(
+            duration,
+            self.wait_for_message(|msg| match &msg.command {
+                Command::CAP(_, CapSubCommand::ACK, Some(opt), _) if opt == "sasl" => true,
+                _ => false,
+            }),
+        )
+        .await
+        .context("Timeout waiting for CAP ACK sasl")??;
+
+        log::info!("GOT ACK for SASL !");
+        client.send_sasl_plain()?;
+
+        timeout(
+            duration,
+            self.wait_for_message(|msg| match &msg.command {
+                Command::AUTHENTICATE(s) if s == "+" => true,
+                _ => false,
+            }),
+        )
+        .await
+        .context("Timeout waiting for AUTHENTICATE + from server")??;
+
+        let sasl_str = base64::encode(format!("\0{}\0{}", nick, password));
+        client.send(Command::AUTHENTICATE(sasl_str))?;
+
+        let resp = timeout(
+            duration,
+            self.wait_for_message(|msg| match &msg.command {
+                Command::Response(Response::RPL_SASLSUCCESS, _) => true,
+                Command::Response(resp, _) if is_sasl_error(resp) => true,
+                _ => false,
+            }),
+        )
+        .await
+        .context("Timeout waiting for SASL acknowledment")??;
+
+        if matches!(resp.command, Command::Response(resp, _) if is_sasl_error(&resp)) {
+            anyhow::bail!("SASL auth failed {resp:?}");
+        }
+        log::info!("SASL authenticated");
+
+        client.send(Command::CAP(None, CapSubCommand::END, None, None))?;
+        log::info!("Handshake finished on {}, ready to work", self.name);
+
+        Ok(())
+    }
+
+    /// wait until the client receive a message that matches the given predicate
+    /// and returns it. Warning, use timeout to prevent a deadlock.
+    async fn wait_for_message<F>(&self, pred: F) -> Result<Message>
+    where
+        F: Fn(&Message) -> bool,
+    {
+        let mut message_stream = self.stream.lock().await;
+        while let Some(message) = message_stream.next().await.transpose()? {
+            if pred(&message) {
+                return Ok(message);
+            }
+        }
+        anyhow::bail!("Waited for message failed");
+    }
+}
+
+/// A connection that never touches the network. Messages pushed in the
+/// returned sender are received by the golem, and what the golem sends
+/// ends up in the returned receiver.
+#[cfg(test)]
+pub fn fake(
+    name: &str,
+    channels: &[&str],
+) -> (
+    Network,
+    mpsc::UnboundedSender<Message>,
+    mpsc::UnboundedReceiver<Message>,
+) {
+    let (inbound_tx, inbound_rx) = mpsc::unbounded_channel();
+    let (outbound_tx, outbound_rx) = mpsc::unbounded_channel();
+    let stream = stream::unfold(inbound_rx, |mut rx| async move {
+        rx.recv().await.map(|msg| (Ok(msg), rx))
+    });
+    let network = Network::new(
+        name,
+        Box::new(outbound_tx),
+        Box::pin(stream),
+        channels.iter().map(|c| c.to_string()).collect(),
+    );
+    (network, inbound_tx, outbound_rx)
+}
+
+// The function https://docs.rs/irc/latest/irc/client/prelude/enum.Response.html#method.is_error
+// is broken, and consider anything with a code above 400 to be an error
+// which doesn't account for SASL successes 900, 901, 902 and 903
+fn is_sasl_error(resp: &Response) -> bool {
+    // https://ircv3.net/specs/extensions/sasl-3.1.html
+    *resp as u16 >= 904
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_irc_config() {
+        let base = irc::client::data::Config {
+            nickname: Some("rustygolem".to_string()),
+            server: Some("irc.libera.chat".to_string()),
+            port: Some(6697),
+            use_tls: Some(true),
+            channels: vec!["#haskell-fr".to_string()],
+            ..Default::default()
+        };
+        let conf = NetworkConfig {
+            name: "private".to_string(),
+            server: "irc.example.org".to_string(),
+            port: None,
+            use_tls: None,
+            nickname: Some("golem".to_string()),
+            channels: vec!["#secret".to_string()],
+            sasl_password: None,
+            nickserv_password: None,
+            blacklisted_users: vec![],
+        };
+        let irc_config = conf.irc_config(&base);
+        assert_eq!(irc_config.server.as_deref(), Some("irc.example.org"));
+        assert_eq!(irc_config.nickname.as_deref(), Some("golem"));
+        assert_eq!(irc_config.port, Some(6697));
+        assert_eq!(irc_config.channels, vec!["#secret".to_string()]);
+    }
+
+    #[test]
+    async fn test_blacklist_casemapping() {
+        let (mut network, _, _) = fake("libera", &["#rust"]);
+        network.blacklisted_users = vec!["[bot]".to_string()];
+        assert!(network.is_blacklisted("{BOT}"));
+        network.caps.lock().unwrap().casemapping = crate::caps::CaseMapping::Ascii;
+        assert!(!network.is_blacklisted("{BOT}"));
+        assert!(network.is_blacklisted("[BOT]"));
+    }
+
+    #[test]
+    async fn test_has_channel() {
+        let (network, _, _) = fake("libera", &["#Haskell-fr"]);
+        assert!(network.has_channel("#haskell-fr"));
+        assert!(!network.has_channel("#rust"));
+    }
+}
@@ -0,0 +1,273 @@
+use crate::caps::{CaseMapping, ServerCaps};
+use irc::proto::{Command, Message, Response};
+use std::time::{Duration, Instant};
+
+pub const MIN_BACKOFF: Duration = Duration::from_secs(30);
+pub const MAX_BACKOFF: Duration = Duration::from_secs(30 * 60);
+
+/// Get back the primary nickname after falling back on an alternative one,
+/// typically because our ghost was still holding it after a netsplit.
+///
+/// Periodically ask the server whether the primary nick is in use with ISON,
+/// and switch back with NICK as soon as it's free. When it's still in use and
+/// a nickserv password is configured, ask NickServ to GHOST the holder first.
+/// Attempts are spaced with an exponential backoff, and stop once the primary
+/// nick is ours.
+#[derive(Debug)]
+pub struct NickKeeper {
+    primary: String,
+    current: String,
+    nickserv_password: Option<String>,
+    /// of the server, to compare the nicks
+    casemapping: CaseMapping,
+    backoff: Duration,
+    /// None when we hold the primary nick
+    next_attempt: Option<Instant>,
+}
+
+impl NickKeeper {
+    pub fn new(primary: &str, nickserv_password: Option<String>) -> Self {
+        NickKeeper {
+            primary: primary.to_string(),
+            current: primary.to_string(),
+            nickserv_password,
+            casemapping: ServerCaps::default().casemapping,
+            backoff: MIN_BACKOFF,
+            next_attempt: None,
+        }
+    }
+
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    pub fn has_primary(&self) -> bool {
+        self.casemapping
+            .eq_ignore_case(&self.current, &self.primary)
+    }
+
+    /// Once the server told its casemapping in RPL_ISUPPORT
+    pub fn set_casemapping(&mut self, casemapping: CaseMapping) {
+        self.casemapping = casemapping;
+    }
+
+    /// Follow our own nick changes, and react to the server answers.
+    /// Returns the messages to send.
+    pub fn on_message(&mut self, msg: &Message, now: Instant) -> Vec<Message> {
+        let source = msg.source_nickname();
+        match &msg.command {
+            Command::Response(Response::RPL_WELCOME, args) => {
+                if let Some(nick) = args.first() {
+                    self.set_current(nick, now);
+                }
+                vec![]
+            }
+            Command::NICK(new_nick) if source.map_or(false, |s| self.is_current(s)) => {
+                log::info!("Nickname changed from {} to {new_nick}", self.current);
+                self.set_current(new_nick, now);
+                vec![]
+            }
+            // whoever was holding our nick just released it
+            Command::NICK(_) | Command::QUIT(_)
+                if !self.has_primary() && source.map_or(false, |s| self.is_primary(s)) =>
+            {
+                vec![self.nick()]
+            }
+            Command::Response(Response::RPL_ISON, args) if !self.has_primary() => {
+                let online = args.last().map_or(false, |nicks| {
+                    nicks.split_whitespace().any(|n| self.is_primary(n))
+                });
+                match (online, &self.nickserv_password) {
+                    (false, _) => vec![self.nick()],
+                    (true, Some(password)) => vec![Command::PRIVMSG(
+                        "NickServ".to_string(),
+                        format!("GHOST {} {password}", self.primary),
+                    )
+                    .into()],
+                    (true, None) => vec![],
+                }
+            }
+            Command::Response(Response::ERR_NICKNAMEINUSE, args)
+            | Command::Response(Response::ERR_UNAVAILRESOURCE, args)
+                if !self.has_primary() && args.get(1).map_or(false, |n| self.is_primary(n)) =>
+            {
+                log::info!(
+                    "Primary nick {} still unavailable, next attempt in {:?}",
+                    self.primary,
+                    self.next_attempt.map(|t| t.saturating_duration_since(now))
+                );
+                vec![]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// To be called periodically. Returns the messages to send
+    /// if an attempt to regain the primary nick is due.
+    pub fn tick(&mut self, now: Instant) -> Vec<Message> {
+        match self.next_attempt {
+            Some(at) if at <= now => {
+                self.next_attempt = Some(now + self.backoff);
+                self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                vec![Command::ISON(vec![self.primary.clone()]).into()]
+            }
+            _ => vec![],
+        }
+    }
+
+    fn set_current(&mut self, nick: &str, now: Instant) {
+        self.current = nick.to_string();
+        if self.has_primary() {
+            self.next_attempt = None;
+            self.backoff = MIN_BACKOFF;
+        } else if self.next_attempt.is_none() {
+            log::warn!(
+                "Using nick {} instead of {}, will try to regain it",
+                self.current,
+                self.primary
+            );
+            self.next_attempt = Some(now);
+        }
+    }
+
+    fn nick(&self) -> Message {
+        Command::NICK(self.primary.clone()).into()
+    }
+
+    fn is_current(&self, nick: &str) -> bool {
+        self.casemapping.eq_ignore_case(&self.current, nick)
+    }
+
+    fn is_primary(&self, nick: &str) -> bool {
+        self.casemapping.eq_ignore_case(&self.primary, nick)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn msg(raw: &str) -> Message {
+        raw.parse().unwrap()
+    }
+
+    fn sent(msgs: Vec<Message>) -> Vec<String> {
+        msgs.into_iter()
+            .map(|m| m.to_string().trim_end().to_string())
+            .collect()
+    }
+
+    fn on_alt_nick(nickserv_password: Option<&str>, now: Instant) -> NickKeeper {
+        let mut keeper = NickKeeper::new("rustygolem", nickserv_password.map(String::from));
+        let welcome = msg(":irc.server 001 rustygolem_ :Welcome to the network rustygolem_");
+        assert_eq!(keeper.on_message(&welcome, now), vec![]);
+        assert_eq!(keeper.current(), "rustygolem_");
+        keeper
+    }
+
+    #[test]
+    async fn test_nothing_to_do_with_primary() {
+        let now = Instant::now();
+        let mut keeper = NickKeeper::new("rustygolem", None);
+        let welcome = msg(":irc.server 001 rustygolem :Welcome to the network rustygolem");
+        assert_eq!(keeper.on_message(&welcome, now), vec![]);
+        assert_eq!(keeper.tick(now), vec![]);
+    }
+
+    #[test]
+    async fn test_backoff_on_433() {
+        let t0 = Instant::now();
+        let mut keeper = on_alt_nick(None, t0);
+
+        assert_eq!(sent(keeper.tick(t0)), vec!["ISON rustygolem"]);
+        let online = msg(":irc.server 303 rustygolem_ :rustygolem");
+        assert_eq!(keeper.on_message(&online, t0), vec![], "nick still in use");
+        assert_eq!(keeper.tick(t0 + Duration::from_secs(29)), vec![]);
+
+        let t1 = t0 + MIN_BACKOFF;
+        assert_eq!(sent(keeper.tick(t1)), vec!["ISON rustygolem"]);
+        let offline = msg(":irc.server 303 rustygolem_ :");
+        assert_eq!(
+            sent(keeper.on_message(&offline, t1)),
+            vec!["NICK rustygolem"]
+        );
+        // someone was faster than us
+        let in_use = msg(":irc.server 433 rustygolem_ rustygolem :Nickname is already in use.");
+        assert_eq!(keeper.on_message(&in_use, t1), vec![]);
+
+        // the backoff doubled
+        assert_eq!(keeper.tick(t1 + MIN_BACKOFF), vec![]);
+        let t2 = t1 + 2 * MIN_BACKOFF;
+        assert_eq!(sent(keeper.tick(t2)), vec!["ISON rustygolem"]);
+    }
+
+    #[test]
+    async fn test_backoff_is_capped() {
+        let mut now = Instant::now();
+        let mut keeper = on_alt_nick(None, now);
+        for _ in 0..20 {
+            assert_eq!(sent(keeper.tick(now)), vec!["ISON rustygolem"]);
+            now += MAX_BACKOFF;
+        }
+    }
+
+    #[test]
+    async fn test_regain_on_quit() {
+        let t0 = Instant::now();
+        let mut keeper = on_alt_nick(None, t0);
+
+        let other_quit = msg(":someone!~someone@host QUIT :bye");
+        assert_eq!(keeper.on_message(&other_quit, t0), vec![]);
+
+        let ghost_quit = msg(":rustygolem!~rustygolem@host QUIT :Ping timeout: 250 seconds");
+        assert_eq!(
+            sent(keeper.on_message(&ghost_quit, t0)),
+            vec!["NICK rustygolem"]
+        );
+
+        let confirmation = msg(":rustygolem_!~rustygolem@host NICK :rustygolem");
+        assert_eq!(keeper.on_message(&confirmation, t0), vec![]);
+        assert!(keeper.has_primary());
+        assert_eq!(keeper.tick(t0 + MAX_BACKOFF), vec![], "attempts stop");
+    }
+
+    #[test]
+    async fn test_casemapping() {
+        let t0 = Instant::now();
+        let mut keeper = NickKeeper::new("golem[m]", None);
+        let welcome = msg(":irc.server 001 golem_ :Welcome to the network golem_");
+        assert_eq!(keeper.on_message(&welcome, t0), vec![]);
+        let ghost_quit = msg(":Golem{M}!~golem@host QUIT :Ping timeout: 250 seconds");
+        assert_eq!(
+            sent(keeper.on_message(&ghost_quit, t0)),
+            vec!["NICK golem[m]"],
+            "the same nick in rfc1459"
+        );
+        let confirmation = msg(":golem_!~golem@host NICK :GOLEM{m}");
+        assert_eq!(keeper.on_message(&confirmation, t0), vec![]);
+        assert!(keeper.has_primary());
+
+        keeper.set_casemapping(CaseMapping::Ascii);
+        assert!(!keeper.has_primary(), "not in ascii");
+    }
+
+    #[test]
+    async fn test_ghost_with_nickserv() {
+        let t0 = Instant::now();
+        let mut keeper = on_alt_nick(Some("s3cr3t"), t0);
+
+        assert_eq!(sent(keeper.tick(t0)), vec!["ISON rustygolem"]);
+        let online = msg(":irc.server 303 rustygolem_ :rustygolem");
+        assert_eq!(
+            sent(keeper.on_message(&online, t0)),
+            vec!["PRIVMSG NickServ :GHOST rustygolem s3cr3t"]
+        );
+        // services kill the ghost
+        let ghost_quit = msg(":rustygolem!~rustygolem@host QUIT :Killed (NickServ (GHOST command used by rustygolem_))");
+        assert_eq!(
+            sent(keeper.on_message(&ghost_quit, t0)),
+            vec!["NICK rustygolem"]
+        );
+    }
+}
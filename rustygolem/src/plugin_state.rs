@@ -0,0 +1,185 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_MAX_FAILURES: usize = 5;
+pub const DEFAULT_FAILURE_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// A plugin failing `max_failures` times within `window` gets disabled
+#[derive(Debug, Clone, Copy)]
+pub struct ErrorBudget {
+    pub max_failures: usize,
+    pub window: Duration,
+}
+
+impl Default for ErrorBudget {
+    fn default() -> Self {
+        ErrorBudget {
+            max_failures: DEFAULT_MAX_FAILURES,
+            window: DEFAULT_FAILURE_WINDOW,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Enabled,
+    /// by an admin
+    Disabled,
+    /// by the golem, after exhausting the error budget
+    Failing {
+        last_error: String,
+    },
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::Enabled => f.write_str("enabled"),
+            Status::Disabled => f.write_str("disabled"),
+            Status::Failing { last_error } => write!(f, "disabled after errors ({last_error})"),
+        }
+    }
+}
+
+/// Whether plugins are enabled, can be changed while the bot is running
+pub struct PluginStates {
+    budget: ErrorBudget,
+    states: Mutex<HashMap<String, PluginState>>,
+}
+
+struct PluginState {
+    status: Status,
+    /// within the budget window
+    failures: VecDeque<Instant>,
+}
+
+impl Default for PluginState {
+    fn default() -> Self {
+        PluginState {
+            status: Status::Enabled,
+            failures: VecDeque::new(),
+        }
+    }
+}
+
+impl PluginStates {
+    pub fn new(budget: ErrorBudget) -> Self {
+        PluginStates {
+            budget,
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn budget(&self) -> ErrorBudget {
+        self.budget
+    }
+
+    pub fn status(&self, plugin: &str) -> Status {
+        self.states
+            .lock()
+            .expect("plugin states lock")
+            .get(plugin)
+            .map(|s| s.status.clone())
+            .unwrap_or(Status::Enabled)
+    }
+
+    pub fn is_enabled(&self, plugin: &str) -> bool {
+        self.status(plugin) == Status::Enabled
+    }
+
+    /// Also gives a fresh error budget to the plugin
+    pub fn enable(&self, plugin: &str) {
+        let mut states = self.states.lock().expect("plugin states lock");
+        states.insert(plugin.to_string(), PluginState::default());
+    }
+
+    pub fn disable(&self, plugin: &str) {
+        let mut states = self.states.lock().expect("plugin states lock");
+        states.entry(plugin.to_string()).or_default().status = Status::Disabled;
+    }
+
+    /// Returns true when this failure exhausts the budget, the plugin is then disabled
+    pub fn record_failure(&self, plugin: &str, error: &str, now: Instant) -> bool {
+        let mut states = self.states.lock().expect("plugin states lock");
+        let state = states.entry(plugin.to_string()).or_default();
+        if state.status != Status::Enabled {
+            return false;
+        }
+        while let Some(oldest) = state.failures.front() {
+            if now.saturating_duration_since(*oldest) > self.budget.window {
+                state.failures.pop_front();
+            } else {
+                break;
+            }
+        }
+        state.failures.push_back(now);
+        if state.failures.len() >= self.budget.max_failures {
+            state.status = Status::Failing {
+                last_error: error.to_string(),
+            };
+            state.failures.clear();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn states() -> PluginStates {
+        PluginStates::new(ErrorBudget {
+            max_failures: 3,
+            window: Duration::from_secs(60),
+        })
+    }
+
+    #[test]
+    async fn test_budget_window() {
+        let states = states();
+        let t0 = Instant::now();
+        let at = |secs| t0 + Duration::from_secs(secs);
+
+        assert!(!states.record_failure("url", "boom", at(0)));
+        assert!(!states.record_failure("url", "boom", at(30)));
+        // the first failure is out of the window
+        assert!(!states.record_failure("url", "boom", at(61)));
+        assert!(states.is_enabled("url"));
+
+        assert!(states.record_failure("url", "boom again", at(62)));
+        assert_eq!(
+            states.status("url"),
+            Status::Failing {
+                last_error: "boom again".to_string()
+            }
+        );
+        assert!(
+            !states.record_failure("url", "boom", at(63)),
+            "only reported once"
+        );
+        assert!(states.is_enabled("twitch"));
+    }
+
+    #[test]
+    async fn test_enable_resets_budget() {
+        let states = states();
+        let t0 = Instant::now();
+        states.record_failure("url", "boom", t0);
+        states.record_failure("url", "boom", t0);
+        states.record_failure("url", "boom", t0);
+        assert!(!states.is_enabled("url"));
+
+        states.enable("url");
+        assert!(states.is_enabled("url"));
+        assert!(!states.record_failure("url", "boom", t0));
+        assert!(!states.record_failure("url", "boom", t0));
+
+        states.disable("url");
+        assert_eq!(states.status("url"), Status::Disabled);
+        assert!(!states.record_failure("url", "boom", t0));
+    }
+}
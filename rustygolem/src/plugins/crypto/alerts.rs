@@ -0,0 +1,514 @@
+use super::db;
+use super::fiat::Fiat;
+use diesel::prelude::*;
+use diesel::sql_types::{Double, Integer, Nullable, Text};
+use nom::bytes::complete::tag;
+use nom::sequence::preceded;
+use plugin_core::{parse, Database, Result};
+use std::collections::HashMap;
+use std::result::Result as StdResult;
+use std::sync::Mutex;
+
+/// Active alerts a nick can have on a network
+pub const MAX_ALERTS_PER_NICK: usize = 5;
+
+pub const USAGE: &str =
+    "Usage: λcrypto alert <coin> > <price>, λcrypto alert <coin> < <price>, λcrypto alerts, λcrypto alert rm <id>";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Above,
+    Below,
+}
+
+impl Direction {
+    fn from_op(op: &str) -> Option<Self> {
+        match op {
+            ">" => Some(Direction::Above),
+            "<" => Some(Direction::Below),
+            _ => None,
+        }
+    }
+
+    fn op(self) -> &'static str {
+        match self {
+            Direction::Above => ">",
+            Direction::Below => "<",
+        }
+    }
+
+    /// Whether `price` is past `threshold`, reaching it counts
+    fn is_past(self, threshold: f64, price: f64) -> bool {
+        match self {
+            Direction::Above => price >= threshold,
+            Direction::Below => price <= threshold,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AlertCommand<'a> {
+    /// the coin as typed, resolved later
+    Add {
+        coin: &'a str,
+        direction: Direction,
+        threshold: f64,
+    },
+    List,
+    Remove(i32),
+}
+
+/// None when this isn't an alert command, an error with the usage
+/// when it is one, but malformed
+pub fn parse_command(input: &str) -> Option<StdResult<AlertCommand, String>> {
+    let (rest, _) = preceded(parse::command_prefix, tag("crypto"))(input).ok()?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let mut words = rest.split_whitespace();
+    let command = match words.next()? {
+        "alerts" => Some(AlertCommand::List).filter(|_| words.next().is_none()),
+        "alert" => match words.next() {
+            Some("rm") => match (words.next(), words.next()) {
+                (Some(id), None) => id
+                    .trim_start_matches('#')
+                    .parse()
+                    .ok()
+                    .map(AlertCommand::Remove),
+                _ => None,
+            },
+            Some(coin) => words
+                .next()
+                .and_then(Direction::from_op)
+                .zip(parse_amount(&words.collect::<String>()))
+                .map(|(direction, threshold)| AlertCommand::Add {
+                    coin,
+                    direction,
+                    threshold,
+                }),
+            None => None,
+        },
+        _ => return None,
+    };
+    Some(command.ok_or_else(|| USAGE.to_string()))
+}
+
+/// Like `70000`, `70 000 €` or `0,25`
+fn parse_amount(input: &str) -> Option<f64> {
+    let amount = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '_' && *c != '€')
+        .map(|c| if c == ',' { '.' } else { c })
+        .collect::<String>()
+        .parse::<f64>()
+        .ok()?;
+    Some(amount).filter(|a| a.is_finite() && *a > 0.0)
+}
+
+#[derive(Debug, Clone, PartialEq, QueryableByName)]
+pub struct Alert {
+    #[sql_type = "Integer"]
+    pub id: i32,
+    #[sql_type = "Text"]
+    pub nick: String,
+    #[sql_type = "Nullable<Text>"]
+    pub network: Option<String>,
+    /// where the alert was created, and where it is sent
+    #[sql_type = "Text"]
+    pub channel: String,
+    /// coingecko id
+    #[sql_type = "Text"]
+    pub coin: String,
+    #[sql_type = "Text"]
+    pub symbol: String,
+    #[sql_type = "Text"]
+    direction: String,
+    #[sql_type = "Double"]
+    pub threshold: f64,
+}
+
+impl Alert {
+    pub fn new(
+        nick: &str,
+        network: Option<&str>,
+        channel: &str,
+        coin: &str,
+        symbol: &str,
+        direction: Direction,
+        threshold: f64,
+    ) -> Self {
+        Alert {
+            id: 0,
+            nick: nick.to_string(),
+            network: network.map(String::from),
+            channel: channel.to_string(),
+            coin: coin.to_string(),
+            symbol: symbol.to_uppercase(),
+            direction: direction.op().to_string(),
+            threshold,
+        }
+    }
+
+    pub fn direction(&self) -> Direction {
+        Direction::from_op(&self.direction).unwrap_or(Direction::Above)
+    }
+
+    fn belongs_to(&self, network: Option<&str>, nick: &str) -> bool {
+        self.network.as_deref() == network && self.nick.to_lowercase() == nick.to_lowercase()
+    }
+
+    /// Like `BTC > 70 000 €`, the threshold is in the default fiat
+    pub fn describe(&self, fiat: Fiat) -> String {
+        format!(
+            "{} {} {}",
+            self.symbol,
+            self.direction().op(),
+            fiat.format_f64(self.threshold)
+        )
+    }
+
+    /// Sent to the channel of the alert once triggered
+    pub fn triggered_message(&self, price: f64, fiat: Fiat) -> String {
+        format!(
+            "{}: {} a franchi {} (actuellement {})",
+            self.nick,
+            self.symbol,
+            fiat.format_f64(self.threshold),
+            fiat.format_f64(price)
+        )
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Added {
+    Alert(Alert),
+    /// the nick already has MAX_ALERTS_PER_NICK alerts
+    TooMany,
+    /// the price is already past the threshold, the alert would
+    /// trigger right away
+    AlreadyPast,
+}
+
+/// The alerts of everyone, persisted in the database
+pub struct Alerts {
+    db: Database,
+    alerts: Mutex<Vec<Alert>>,
+}
+
+impl Alerts {
+    /// Create the table if needed, and load the alerts from before the last restart
+    pub fn load(db: Database) -> Result<Self> {
+        db::ensure_schema(&db)?;
+        let alerts = db.with_connection(|conn| {
+            diesel::sql_query(
+                "SELECT id, nick, network, channel, coin, symbol, direction, threshold \
+                 FROM crypto_alerts ORDER BY id",
+            )
+            .load::<Alert>(conn)
+        })?;
+        log::info!("Loaded {} crypto alerts", alerts.len());
+        Ok(Alerts {
+            db,
+            alerts: Mutex::new(alerts),
+        })
+    }
+
+    /// `price` is the current one of the coin
+    pub fn add(&self, mut alert: Alert, price: f64) -> Result<Added> {
+        if alert.direction().is_past(alert.threshold, price) {
+            return Ok(Added::AlreadyPast);
+        }
+        let mut alerts = self.alerts.lock().expect("alerts lock");
+        let active = alerts
+            .iter()
+            .filter(|a| a.belongs_to(alert.network.as_deref(), &alert.nick))
+            .count();
+        if active >= MAX_ALERTS_PER_NICK {
+            return Ok(Added::TooMany);
+        }
+        let id = self.db.with_connection(|conn| {
+            conn.transaction(|| {
+                diesel::sql_query(
+                    "INSERT INTO crypto_alerts \
+                     (nick, network, channel, coin, symbol, direction, threshold) \
+                     VALUES (?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind::<Text, _>(&alert.nick)
+                .bind::<Nullable<Text>, _>(&alert.network)
+                .bind::<Text, _>(&alert.channel)
+                .bind::<Text, _>(&alert.coin)
+                .bind::<Text, _>(&alert.symbol)
+                .bind::<Text, _>(&alert.direction)
+                .bind::<Double, _>(alert.threshold)
+                .execute(conn)?;
+                diesel::sql_query("SELECT last_insert_rowid() AS id").get_result::<db::LastId>(conn)
+            })
+        })?;
+        alert.id = id.id as i32;
+        alerts.push(alert.clone());
+        Ok(Added::Alert(alert))
+    }
+
+    pub fn list(&self, network: Option<&str>, nick: &str) -> Vec<Alert> {
+        self.alerts
+            .lock()
+            .expect("alerts lock")
+            .iter()
+            .filter(|a| a.belongs_to(network, nick))
+            .cloned()
+            .collect()
+    }
+
+    /// Only the alerts of the nick can be removed. False when it has no such alert.
+    pub fn remove(&self, network: Option<&str>, nick: &str, id: i32) -> Result<bool> {
+        let mut alerts = self.alerts.lock().expect("alerts lock");
+        match alerts
+            .iter()
+            .position(|a| a.id == id && a.belongs_to(network, nick))
+        {
+            Some(idx) => {
+                self.delete(&[id])?;
+                alerts.remove(idx);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// The (coingecko id, symbol) of the coins to watch
+    pub fn coins(&self) -> Vec<(String, String)> {
+        let mut coins = self
+            .alerts
+            .lock()
+            .expect("alerts lock")
+            .iter()
+            .map(|a| (a.coin.clone(), a.symbol.clone()))
+            .collect::<Vec<_>>();
+        coins.sort();
+        coins.dedup();
+        coins
+    }
+
+    /// Removes and returns the alerts past their threshold, with the price
+    /// of their coin. `prices` is by coingecko id.
+    pub fn take_triggered(&self, prices: &HashMap<String, f64>) -> Result<Vec<(Alert, f64)>> {
+        let mut alerts = self.alerts.lock().expect("alerts lock");
+        let triggered = alerts
+            .iter()
+            .filter_map(|a| {
+                let price = *prices.get(&a.coin)?;
+                a.direction()
+                    .is_past(a.threshold, price)
+                    .then(|| (a.clone(), price))
+            })
+            .collect::<Vec<_>>();
+        if triggered.is_empty() {
+            return Ok(triggered);
+        }
+        self.delete(&triggered.iter().map(|(a, _)| a.id).collect::<Vec<_>>())?;
+        alerts.retain(|a| !triggered.iter().any(|(t, _)| t.id == a.id));
+        Ok(triggered)
+    }
+
+    fn delete(&self, ids: &[i32]) -> Result<()> {
+        self.db.with_connection(|conn| {
+            conn.transaction(|| {
+                for id in ids {
+                    diesel::sql_query("DELETE FROM crypto_alerts WHERE id = ?")
+                        .bind::<Integer, _>(id)
+                        .execute(conn)?;
+                }
+                Ok(())
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn alert(nick: &str, direction: Direction, threshold: f64) -> Alert {
+        Alert::new(
+            nick,
+            Some("libera"),
+            "#rust",
+            "bitcoin",
+            "btc",
+            direction,
+            threshold,
+        )
+    }
+
+    fn added(added: Added) -> Alert {
+        match added {
+            Added::Alert(alert) => alert,
+            other => panic!("alert not added: {other:?}"),
+        }
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(
+            parse_command("λcrypto alert btc > 70000"),
+            Some(Ok(AlertCommand::Add {
+                coin: "btc",
+                direction: Direction::Above,
+                threshold: 70000.0
+            }))
+        );
+        assert_eq!(
+            parse_command("λcrypto alert eth < 2 000,5 €"),
+            Some(Ok(AlertCommand::Add {
+                coin: "eth",
+                direction: Direction::Below,
+                threshold: 2000.5
+            }))
+        );
+        assert_eq!(
+            parse_command("λcrypto alerts"),
+            Some(Ok(AlertCommand::List))
+        );
+        assert_eq!(
+            parse_command("λcrypto alert rm #12"),
+            Some(Ok(AlertCommand::Remove(12)))
+        );
+
+        for malformed in [
+            "λcrypto alert",
+            "λcrypto alert btc",
+            "λcrypto alert btc = 12",
+            "λcrypto alert btc > lots",
+            "λcrypto alert btc > -3",
+            "λcrypto alert rm",
+            "λcrypto alert rm twelve",
+            "λcrypto alerts please",
+        ] {
+            assert_eq!(
+                parse_command(malformed),
+                Some(Err(USAGE.to_string())),
+                "{malformed}"
+            );
+        }
+
+        assert_eq!(parse_command("λcrypto btc"), None, "a regular quote");
+        assert_eq!(parse_command("λcryptoalerts"), None);
+        assert_eq!(parse_command("coucou alerts"), None);
+    }
+
+    #[test]
+    async fn test_alerts_survive_restart() {
+        let db = Database::in_memory().unwrap();
+        let alerts = Alerts::load(db.clone()).unwrap();
+        let above = added(
+            alerts
+                .add(alert("Geekingfrog", Direction::Above, 70000.0), 64000.0)
+                .unwrap(),
+        );
+        let below = added(
+            alerts
+                .add(alert("Geekingfrog", Direction::Below, 2000.5), 64000.0)
+                .unwrap(),
+        );
+        assert_ne!(above.id, below.id);
+
+        let alerts = Alerts::load(db.clone()).unwrap();
+        assert_eq!(
+            alerts.list(Some("libera"), "geekingfrog"),
+            vec![above.clone(), below.clone()]
+        );
+        assert_eq!(alerts.list(Some("oftc"), "Geekingfrog"), vec![]);
+        assert_eq!(
+            alerts.coins(),
+            vec![("bitcoin".to_string(), "BTC".to_string())]
+        );
+
+        assert!(!alerts.remove(Some("libera"), "Someone", above.id).unwrap());
+        assert!(alerts
+            .remove(Some("libera"), "Geekingfrog", above.id)
+            .unwrap());
+        let alerts = Alerts::load(db).unwrap();
+        assert_eq!(alerts.list(Some("libera"), "Geekingfrog"), vec![below]);
+    }
+
+    #[test]
+    async fn test_limit_per_nick() {
+        let alerts = Alerts::load(Database::in_memory().unwrap()).unwrap();
+        for i in 0..MAX_ALERTS_PER_NICK {
+            let threshold = 70000.0 + i as f64;
+            added(
+                alerts
+                    .add(alert("Geekingfrog", Direction::Above, threshold), 1.0)
+                    .unwrap(),
+            );
+        }
+        assert_eq!(
+            alerts
+                .add(alert("Geekingfrog", Direction::Above, 80000.0), 1.0)
+                .unwrap(),
+            Added::TooMany
+        );
+        added(
+            alerts
+                .add(alert("Someone", Direction::Above, 80000.0), 1.0)
+                .unwrap(),
+        );
+    }
+
+    #[test]
+    async fn test_take_triggered() {
+        let alerts = Alerts::load(Database::in_memory().unwrap()).unwrap();
+        assert_eq!(
+            alerts
+                .add(alert("Geekingfrog", Direction::Above, 70000.0), 71000.0)
+                .unwrap(),
+            Added::AlreadyPast
+        );
+        assert_eq!(
+            alerts
+                .add(alert("Geekingfrog", Direction::Below, 70000.0), 70000.0)
+                .unwrap(),
+            Added::AlreadyPast,
+            "reaching the threshold counts"
+        );
+
+        let above = added(
+            alerts
+                .add(alert("Geekingfrog", Direction::Above, 70000.0), 64000.0)
+                .unwrap(),
+        );
+        let below = added(
+            alerts
+                .add(alert("Geekingfrog", Direction::Below, 60000.0), 64000.0)
+                .unwrap(),
+        );
+
+        let prices = |btc: f64| HashMap::from([("bitcoin".to_string(), btc)]);
+        assert_eq!(alerts.take_triggered(&prices(65000.0)).unwrap(), vec![]);
+        assert_eq!(
+            alerts.take_triggered(&HashMap::new()).unwrap(),
+            vec![],
+            "no price, nothing triggers"
+        );
+        assert_eq!(
+            alerts.take_triggered(&prices(70120.0)).unwrap(),
+            vec![(above.clone(), 70120.0)]
+        );
+        assert_eq!(
+            alerts.take_triggered(&prices(70120.0)).unwrap(),
+            vec![],
+            "removed once triggered"
+        );
+        assert_eq!(alerts.list(Some("libera"), "Geekingfrog"), vec![below]);
+
+        assert_eq!(
+            above.triggered_message(70120.0, Fiat::EUR),
+            "Geekingfrog: BTC a franchi 70\u{2009}000 € (actuellement 70\u{2009}120 €)"
+        );
+        assert_eq!(
+            above.triggered_message(70120.0, Fiat::parse("usd").unwrap()),
+            "Geekingfrog: BTC a franchi $70\u{2009}000 (actuellement $70\u{2009}120)"
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use super::fiat::Fiat;
+use super::quotes::Cached;
+use async_trait::async_trait;
+use plugin_core::Outbound;
+use serde::Deserialize;
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+
+/// An entry of the `announcements` list of the crypto config section
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Announcement {
+    pub channel: String,
+    /// as typed in a λcrypto command, like btc
+    pub coins: Vec<String>,
+    pub every_minutes: u64,
+}
+
+impl Announcement {
+    pub fn check(&self) -> anyhow::Result<()> {
+        if self.every_minutes == 0 {
+            bail!(
+                "crypto.announcements: every_minutes must be at least 1 for {}",
+                self.channel
+            );
+        }
+        if self.coins.is_empty() {
+            bail!(
+                "crypto.announcements: no coin to announce in {}",
+                self.channel
+            );
+        }
+        Ok(())
+    }
+
+    fn every(&self) -> Duration {
+        Duration::from_secs(self.every_minutes * 60)
+    }
+}
+
+/// Where the announced quotes come from
+#[async_trait]
+pub trait QuoteSource: Send + Sync {
+    /// By coin as given, missing when unknown or without any quote
+    async fn quotes_of(&self, coins: &[String], fiat: Fiat) -> HashMap<String, Cached>;
+
+    /// The fiat to announce the quotes in
+    fn fiat_of(&self, channel: &str) -> Fiat;
+}
+
+/// When each announcement is due next
+struct Schedule<'a> {
+    due: Vec<(&'a Announcement, Instant)>,
+}
+
+impl<'a> Schedule<'a> {
+    /// Nothing is due right away, a restart would post twice otherwise
+    fn new(announcements: &'a [Announcement], start: Instant) -> Self {
+        Schedule {
+            due: announcements
+                .iter()
+                .map(|announcement| (announcement, start + announcement.every()))
+                .collect(),
+        }
+    }
+
+    fn next_due(&self) -> Option<Instant> {
+        self.due.iter().map(|(_, at)| *at).min()
+    }
+
+    /// The announcements to make now, each one then due an interval later.
+    /// Late ones are made once, not once per interval missed.
+    fn take_due_at(&mut self, now: Instant) -> Vec<&'a Announcement> {
+        self.due
+            .iter_mut()
+            .filter(|(_, at)| *at <= now)
+            .map(|(announcement, at)| {
+                *at = now + announcement.every();
+                *announcement
+            })
+            .collect()
+    }
+}
+
+/// Like `BTC 64 230 € ▲2.3% | ETH 3 120 € ▼0.8%`, the quotes by coin as
+/// given. None when a quote is missing or outdated, better nothing than
+/// wrong prices.
+pub fn ticker(coins: &[String], quotes: &HashMap<String, Cached>, fiat: Fiat) -> Option<String> {
+    let parts = coins
+        .iter()
+        .map(|coin| {
+            let cached = quotes.get(coin).filter(|cached| !cached.stale)?;
+            let change = match cached.quote.change_24h.filter(|c| c.is_finite()) {
+                Some(change) => format!(" {}", compact_change(change)),
+                None => "".to_string(),
+            };
+            Some(format!(
+                "{} {}{change}",
+                coin.to_uppercase(),
+                fiat.format(cached.quote.price)
+            ))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(parts.join(" | "))
+}
+
+/// Like `▲2.3%`, shorter than in the replies
+fn compact_change(change: f32) -> String {
+    let rounded = (change * 10.0).round() / 10.0;
+    let arrow = match rounded.partial_cmp(&0.) {
+        Some(std::cmp::Ordering::Greater) => "▲",
+        Some(std::cmp::Ordering::Less) => "▼",
+        _ => "→",
+    };
+    format!("{arrow}{:.1}%", rounded.abs())
+}
+
+/// Posts the announcements forever, at their own pace
+pub async fn run(
+    announcements: &[Announcement],
+    source: &impl QuoteSource,
+    bot_chan: &mpsc::Sender<Outbound>,
+) -> anyhow::Result<()> {
+    let mut schedule = Schedule::new(announcements, Instant::now());
+    while let Some(due) = schedule.next_due() {
+        tokio::time::sleep_until(due).await;
+        for announcement in schedule.take_due_at(Instant::now()) {
+            let fiat = source.fiat_of(&announcement.channel);
+            let quotes = source.quotes_of(&announcement.coins, fiat).await;
+            match ticker(&announcement.coins, &quotes, fiat) {
+                Some(line) => {
+                    bot_chan
+                        .send(Outbound::reply(&announcement.channel, line))
+                        .await?
+                }
+                None => log::info!(
+                    "No fresh quotes for {:?}, skipping the announcement in {}",
+                    announcement.coins,
+                    announcement.channel
+                ),
+            }
+        }
+    }
+    // nothing to announce
+    futures::future::pending().await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::plugins::crypto::quotes::Quote;
+    use pretty_assertions::assert_eq;
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+    use std::sync::Mutex;
+
+    fn announcement(channel: &str, coins: &[&str], every_minutes: u64) -> Announcement {
+        Announcement {
+            channel: channel.to_string(),
+            coins: coins.iter().map(|c| c.to_string()).collect(),
+            every_minutes,
+        }
+    }
+
+    fn cached(price: &str, change_24h: Option<f32>, stale: bool) -> Cached {
+        Cached {
+            quote: Quote {
+                price: Decimal::from_str(price).unwrap(),
+                change_24h,
+                at: std::time::Instant::now(),
+            },
+            stale,
+        }
+    }
+
+    fn minutes(m: u64) -> Duration {
+        Duration::from_secs(m * 60)
+    }
+
+    #[test]
+    async fn test_check() {
+        assert!(announcement("#crypto", &["btc"], 60).check().is_ok());
+        assert!(announcement("#crypto", &["btc"], 0).check().is_err());
+        assert!(announcement("#crypto", &[], 60).check().is_err());
+    }
+
+    #[test]
+    async fn test_schedule() {
+        let announcements = [
+            announcement("#crypto", &["btc"], 60),
+            announcement("#doge", &["doge"], 90),
+        ];
+        let t0 = Instant::now();
+        let mut schedule = Schedule::new(&announcements, t0);
+        assert_eq!(schedule.take_due_at(t0), Vec::<&Announcement>::new());
+        assert_eq!(schedule.next_due(), Some(t0 + minutes(60)));
+
+        assert_eq!(
+            schedule.take_due_at(t0 + minutes(60)),
+            vec![&announcements[0]]
+        );
+        assert_eq!(schedule.next_due(), Some(t0 + minutes(90)));
+        assert_eq!(
+            schedule.take_due_at(t0 + minutes(90)),
+            vec![&announcements[1]]
+        );
+
+        assert_eq!(
+            schedule.take_due_at(t0 + minutes(500)),
+            vec![&announcements[0], &announcements[1]],
+            "once, however late"
+        );
+        assert_eq!(schedule.next_due(), Some(t0 + minutes(560)));
+
+        assert_eq!(Schedule::new(&[], t0).next_due(), None);
+    }
+
+    #[test]
+    async fn test_ticker() {
+        let btc_eth = announcement("#crypto", &["btc", "eth"], 60);
+        let mut quotes = HashMap::from([
+            ("btc".to_string(), cached("64230.4", Some(2.345), false)),
+            ("eth".to_string(), cached("3120", Some(-0.8), false)),
+        ]);
+        assert_eq!(
+            ticker(&btc_eth.coins, &quotes, Fiat::EUR),
+            Some("BTC 64\u{2009}230 € ▲2.3% | ETH 3\u{2009}120 € ▼0.8%".to_string())
+        );
+        assert_eq!(
+            ticker(&btc_eth.coins, &quotes, Fiat::parse("usd").unwrap()),
+            Some("BTC $64\u{2009}230 ▲2.3% | ETH $3\u{2009}120 ▼0.8%".to_string())
+        );
+
+        quotes.insert("eth".to_string(), cached("3120", None, false));
+        assert_eq!(
+            ticker(&btc_eth.coins, &quotes, Fiat::EUR),
+            Some("BTC 64\u{2009}230 € ▲2.3% | ETH 3\u{2009}120 €".to_string()),
+            "without 24h change"
+        );
+
+        quotes.insert("eth".to_string(), cached("3120", Some(0.0), true));
+        assert_eq!(ticker(&btc_eth.coins, &quotes, Fiat::EUR), None, "outdated");
+        quotes.remove("eth");
+        assert_eq!(ticker(&btc_eth.coins, &quotes, Fiat::EUR), None, "missing");
+    }
+
+    /// Fresh quotes for every coin, until it starts failing. In USD for
+    /// #crypto-us.
+    struct Fake {
+        failing: Mutex<bool>,
+    }
+
+    #[async_trait]
+    impl QuoteSource for Fake {
+        async fn quotes_of(&self, coins: &[String], _fiat: Fiat) -> HashMap<String, Cached> {
+            if *self.failing.lock().unwrap() {
+                return HashMap::new();
+            }
+            coins
+                .iter()
+                .map(|coin| (coin.clone(), cached("1", Some(1.0), false)))
+                .collect()
+        }
+
+        fn fiat_of(&self, channel: &str) -> Fiat {
+            match channel {
+                "#crypto-us" => Fiat::parse("usd").unwrap(),
+                _ => Fiat::EUR,
+            }
+        }
+    }
+
+    fn drain(rx: &mut mpsc::Receiver<Outbound>) -> Vec<Outbound> {
+        let mut sent = vec![];
+        while let Ok(outbound) = rx.try_recv() {
+            sent.push(outbound);
+        }
+        sent
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run() {
+        let announcements = [
+            announcement("#crypto", &["btc"], 60),
+            announcement("#crypto-us", &["btc"], 120),
+        ];
+        let source = Fake {
+            failing: Mutex::new(false),
+        };
+        let (tx, mut rx) = mpsc::channel(10);
+
+        let until = |m| tokio::time::timeout(minutes(m), run(&announcements, &source, &tx));
+        assert!(until(59).await.is_err(), "still running");
+        assert_eq!(drain(&mut rx), vec![], "not right after a restart");
+
+        assert!(until(150).await.is_err());
+        assert_eq!(
+            drain(&mut rx),
+            vec![
+                Outbound::reply("#crypto", "BTC 1.00 € ▲1.0%"),
+                Outbound::reply("#crypto", "BTC 1.00 € ▲1.0%"),
+                Outbound::reply("#crypto-us", "BTC $1.00 ▲1.0%"),
+            ]
+        );
+
+        *source.failing.lock().unwrap() = true;
+        assert!(until(150).await.is_err());
+        assert_eq!(drain(&mut rx), vec![], "suppressed without quotes");
+    }
+}
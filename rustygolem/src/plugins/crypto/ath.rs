@@ -0,0 +1,236 @@
+use super::fiat::Fiat;
+use super::providers::AllTimeHigh;
+use crate::utils::numbers::decimal_from_f64;
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::result::Result as StdResult;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const USAGE: &str = "Usage: λcrypto ath <coin>, like λcrypto ath btc";
+
+/// The all-time highs change rarely, unless the price is at one
+const TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// `ath <coin>`, None when the args are something else
+pub fn parse(args: &str) -> Option<StdResult<&str, String>> {
+    let rest = args.trim().strip_prefix("ath")?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        // some coin starting with ath
+        return None;
+    }
+    match rest.trim() {
+        "" => Some(Err(USAGE.to_string())),
+        coin => Some(Ok(coin)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ath {
+    pub price: Decimal,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lookup {
+    Known(Ath),
+    /// never fetched, or too old
+    Missing,
+    /// the live price is above the known high
+    NewHigh,
+}
+
+/// The all-time highs by (coingecko id, fiat), with when they were fetched
+#[derive(Default)]
+pub struct AthCache {
+    highs: Mutex<HashMap<(String, Fiat), (Ath, Instant)>>,
+}
+
+impl AthCache {
+    pub fn record_at(&self, id: &str, fiat: Fiat, high: AllTimeHigh, now: Instant) {
+        match decimal_from_f64(high.price) {
+            Some(price) => {
+                let ath = Ath { price, at: high.at };
+                self.highs
+                    .lock()
+                    .expect("ath lock")
+                    .insert((id.to_string(), fiat), (ath, now));
+            }
+            None => log::warn!("Ignoring the all-time high {} of {id}", high.price),
+        }
+    }
+
+    /// The cached high is dropped when the live price beats it, it's
+    /// outdated and the next lookup fetches it again
+    pub fn lookup_at(&self, id: &str, fiat: Fiat, live: Decimal, now: Instant) -> Lookup {
+        let mut highs = self.highs.lock().expect("ath lock");
+        let key = (id.to_string(), fiat);
+        let ath = match highs.get(&key) {
+            Some((ath, at)) if now.saturating_duration_since(*at) <= TTL => *ath,
+            _ => return Lookup::Missing,
+        };
+        if live > ath.price {
+            highs.remove(&key);
+            Lookup::NewHigh
+        } else {
+            Lookup::Known(ath)
+        }
+    }
+}
+
+/// How far below the high, in percents
+pub fn drawdown(live: Decimal, high: Decimal) -> Option<f64> {
+    if high.is_zero() {
+        return None;
+    }
+    ((high - live) / high * Decimal::ONE_HUNDRED).to_f64()
+}
+
+/// Like `2024-03-14`
+fn format_date(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m-%d").to_string()
+}
+
+/// Like `ATH 73 750 € (2024-03-14), −12.9% from ATH`
+pub fn describe(lookup: Lookup, live: Decimal, fiat: Fiat) -> Option<String> {
+    match lookup {
+        Lookup::Missing => None,
+        Lookup::NewHigh => Some("new all-time high! 🎉".to_string()),
+        Lookup::Known(ath) => {
+            let high = format!("ATH {} ({})", fiat.format(ath.price), format_date(ath.at));
+            let rounded = drawdown(live, ath.price).map(|d| (d * 10.0).round() / 10.0);
+            Some(match rounded {
+                Some(d) if d > 0.0 => format!("{high}, −{d:.1}% from ATH"),
+                _ => format!("{high}, right at the ATH"),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn march_14() -> DateTime<Utc> {
+        Utc.ymd(2024, 3, 14).and_hms(7, 10, 36)
+    }
+
+    fn ath(price: &str) -> Ath {
+        Ath {
+            price: dec(price),
+            at: march_14(),
+        }
+    }
+
+    #[test]
+    async fn test_parse() {
+        assert_eq!(parse("ath btc"), Some(Ok("btc")));
+        assert_eq!(parse("ath shiba inu"), Some(Ok("shiba inu")));
+        assert_eq!(parse("ath"), Some(Err(USAGE.to_string())));
+        assert_eq!(parse("btc"), None);
+        assert_eq!(parse("athena"), None, "a coin");
+    }
+
+    #[test]
+    async fn test_drawdown() {
+        let d = drawdown(dec("64230"), dec("73750")).unwrap();
+        assert!((d - 12.908).abs() < 0.001, "{d}");
+        assert_eq!(drawdown(dec("73750"), dec("73750")), Some(0.0));
+        assert_eq!(drawdown(dec("1"), dec("0")), None);
+    }
+
+    #[test]
+    async fn test_describe() {
+        assert_eq!(
+            describe(Lookup::Known(ath("73750")), dec("64230"), Fiat::EUR),
+            Some("ATH 73\u{2009}750 € (2024-03-14), −12.9% from ATH".to_string())
+        );
+        assert_eq!(
+            describe(Lookup::Known(ath("73750")), dec("73749.99"), Fiat::EUR),
+            Some("ATH 73\u{2009}750 € (2024-03-14), right at the ATH".to_string())
+        );
+        assert_eq!(
+            describe(Lookup::NewHigh, dec("75000"), Fiat::EUR),
+            Some("new all-time high! 🎉".to_string())
+        );
+        assert_eq!(
+            describe(
+                Lookup::Known(ath("80000")),
+                dec("69000"),
+                Fiat::parse("usd").unwrap()
+            ),
+            Some("ATH $80\u{2009}000 (2024-03-14), −13.8% from ATH".to_string())
+        );
+        assert_eq!(describe(Lookup::Missing, dec("1"), Fiat::EUR), None);
+    }
+
+    #[test]
+    async fn test_format_date() {
+        assert_eq!(format_date(march_14()), "2024-03-14");
+        assert_eq!(
+            format_date(Utc.ymd(2021, 11, 10).and_hms(23, 59, 59)),
+            "2021-11-10",
+            "in UTC"
+        );
+    }
+
+    #[test]
+    async fn test_cache() {
+        let cache = AthCache::default();
+        let t0 = Instant::now();
+        assert_eq!(
+            cache.lookup_at("bitcoin", Fiat::EUR, dec("64230"), t0),
+            Lookup::Missing
+        );
+
+        let high = AllTimeHigh {
+            price: 73750.0,
+            at: march_14(),
+        };
+        cache.record_at("bitcoin", Fiat::EUR, high, t0);
+        assert_eq!(
+            cache.lookup_at("bitcoin", Fiat::EUR, dec("64230"), t0 + TTL),
+            Lookup::Known(ath("73750"))
+        );
+        assert_eq!(
+            cache.lookup_at("bitcoin", Fiat::parse("usd").unwrap(), dec("69000"), t0),
+            Lookup::Missing,
+            "by fiat"
+        );
+        assert_eq!(
+            cache.lookup_at(
+                "bitcoin",
+                Fiat::EUR,
+                dec("64230"),
+                t0 + TTL + Duration::from_secs(1)
+            ),
+            Lookup::Missing,
+            "too old"
+        );
+
+        cache.record_at("bitcoin", Fiat::EUR, high, t0);
+        assert_eq!(
+            cache.lookup_at("bitcoin", Fiat::EUR, dec("73750"), t0),
+            Lookup::Known(ath("73750")),
+            "equal isn't above"
+        );
+        assert_eq!(
+            cache.lookup_at("bitcoin", Fiat::EUR, dec("75000"), t0),
+            Lookup::NewHigh
+        );
+        assert_eq!(
+            cache.lookup_at("bitcoin", Fiat::EUR, dec("64230"), t0),
+            Lookup::Missing,
+            "invalidated by the new high"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use super::fiat::Fiat;
+use super::history::{self, Window};
+use super::providers::PricePoint;
+use crate::utils::numbers::decimal_from_f64;
+use crate::utils::sparkline::sparkline;
+use chrono::{DateTime, Duration, Utc};
+use std::result::Result as StdResult;
+
+pub const USAGE: &str =
+    "Usage: λcrypto chart <coin> [1d, 7d, 30d or 1y], like λcrypto chart btc 7d";
+
+/// Each block takes 3 bytes, that's plenty for a single line on IRC
+const MAX_POINTS: u32 = 60;
+
+/// `chart <coin> [window]`, over a day by default.
+/// None when the args are not a chart.
+pub fn parse(args: &str) -> Option<StdResult<(&str, Window), String>> {
+    let rest = args.trim().strip_prefix("chart")?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        // some coin starting with chart
+        return None;
+    }
+    let rest = rest.trim();
+    if rest.is_empty() {
+        return Some(Err(USAGE.to_string()));
+    }
+    Some(match history::parse(rest) {
+        Some(Ok(query)) => Ok(query),
+        Some(Err(_)) => Err(USAGE.to_string()),
+        None => Ok((rest, Window::Day)),
+    })
+}
+
+/// Hourly over a day, daily beyond, but never more than MAX_POINTS
+fn point_count(window: Window) -> u32 {
+    match window {
+        Window::Day => 24,
+        _ => window.days().min(MAX_POINTS),
+    }
+}
+
+/// The prices at regular steps over the window, ending at `now`. NaN when
+/// there is no data point close enough to a step.
+fn resample(points: &[PricePoint], window: Window, now: DateTime<Utc>) -> Vec<f64> {
+    let count = point_count(window);
+    let step = Duration::days(window.days().into()) / count as i32;
+    let start = history::start(window, now);
+    (1..=count)
+        .map(|i| {
+            let target = start + step * i as i32;
+            let distance = |point: &&PricePoint| (point.at - target).num_seconds().abs();
+            points
+                .iter()
+                .filter(|point| distance(point) <= step.num_seconds() / 2)
+                .min_by_key(distance)
+                .map_or(f64::NAN, |point| point.price)
+        })
+        .collect()
+}
+
+/// Like `BTC 1d: ▁▂▃▅▆▇ min 63 100 € max 64 900 € last 64 230 €`,
+/// None without any price
+pub fn describe(
+    symbol: &str,
+    points: &[PricePoint],
+    window: Window,
+    fiat: Fiat,
+    now: DateTime<Utc>,
+) -> Option<String> {
+    let values = resample(points, window, now);
+    let prices = values
+        .iter()
+        .filter_map(|v| decimal_from_f64(*v))
+        .collect::<Vec<_>>();
+    let min = prices.iter().min()?;
+    let max = prices.iter().max()?;
+    let last = prices.last()?;
+    Some(format!(
+        "{} {}: {} min {} max {} last {}",
+        symbol.to_uppercase(),
+        window.label(),
+        sparkline(&values),
+        fiat.format(*min),
+        fiat.format(*max),
+        fiat.format(*last),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_parse() {
+        assert_eq!(parse("chart btc"), Some(Ok(("btc", Window::Day))));
+        assert_eq!(parse("chart btc 7d"), Some(Ok(("btc", Window::Week))));
+        assert_eq!(
+            parse("chart shiba inu 1y"),
+            Some(Ok(("shiba inu", Window::Year)))
+        );
+        assert_eq!(parse("chart"), Some(Err(USAGE.to_string())));
+        assert_eq!(parse("chart btc 2w"), Some(Err(USAGE.to_string())));
+
+        assert_eq!(parse("btc"), None);
+        assert_eq!(parse("btc 7d"), None);
+        assert_eq!(parse("chartcoin"), None);
+    }
+
+    #[test]
+    async fn test_point_count() {
+        assert_eq!(point_count(Window::Day), 24);
+        assert_eq!(point_count(Window::Week), 7);
+        assert_eq!(point_count(Window::Month), 30);
+        assert_eq!(point_count(Window::Year), MAX_POINTS);
+    }
+
+    fn hourly(now: DateTime<Utc>, prices: &[f64]) -> Vec<PricePoint> {
+        let start = now - Duration::hours(prices.len() as i64);
+        prices
+            .iter()
+            .enumerate()
+            .map(|(i, &price)| PricePoint {
+                // slightly off the steps, like actual data
+                at: start + Duration::hours(i as i64 + 1) - Duration::minutes(3),
+                price,
+            })
+            .collect()
+    }
+
+    #[test]
+    async fn test_resample() {
+        let now = Utc.ymd(2024, 5, 8).and_hms(12, 0, 0);
+        let prices = (0..48).map(|i| i as f64).collect::<Vec<_>>();
+        let values = resample(&hourly(now, &prices), Window::Day, now);
+        assert_eq!(values.len(), 24);
+        assert_eq!(values[0], 24.0, "the first step is an hour in the window");
+        assert_eq!(values[23], 47.0);
+
+        let values = resample(&hourly(now, &[1.0, 2.0]), Window::Day, now);
+        assert!(values[..22].iter().all(|v| v.is_nan()), "no data");
+        assert_eq!(&values[22..], &[1.0, 2.0]);
+    }
+
+    #[test]
+    async fn test_describe() {
+        let now = Utc.ymd(2024, 5, 8).and_hms(12, 0, 0);
+        let mut prices = vec![64000.0; 20];
+        prices.extend([64900.0, 63100.0, 64000.0, 64230.0]);
+        let points = hourly(now, &prices);
+        assert_eq!(
+            describe("btc", &points, Window::Day, Fiat::EUR, now),
+            Some(format!(
+                "BTC 1d: {}█▁▅▅ min 63\u{2009}100 € max 64\u{2009}900 € last 64\u{2009}230 €",
+                "▅".repeat(20)
+            ))
+        );
+        assert_eq!(describe("btc", &[], Window::Day, Fiat::EUR, now), None);
+
+        let points = hourly(now, &[2.0; 24 * 365]);
+        let line = describe("btc", &points, Window::Year, Fiat::EUR, now).unwrap();
+        assert!(line.len() < 300, "{line}");
+    }
+}
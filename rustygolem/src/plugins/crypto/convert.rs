@@ -0,0 +1,349 @@
+use super::fiat::Fiat;
+use super::providers::CoinRef;
+use super::quotes::{format_age, Cached};
+use crate::utils::numbers::format_decimal;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::result::Result as StdResult;
+use std::str::FromStr;
+use std::time::Instant;
+
+pub const USAGE: &str =
+    "Usage: λcrypto <amount> <coin or fiat> in <coin or fiat>, like λcrypto 0.5 btc in eur";
+
+/// Anything bigger is a typo, or a joke
+const MAX_AMOUNT: i64 = 1_000_000_000_000_000;
+
+/// Also the euro, on top of its ISO code
+const EURO: &[&str] = &["euro", "euros", "€"];
+
+#[derive(Debug, PartialEq)]
+pub struct Conversion<'a> {
+    pub amount: Decimal,
+    /// as typed, resolved later
+    pub from: &'a str,
+    /// None for the fiat of the channel
+    pub to: Option<&'a str>,
+}
+
+/// `<amount> <from> [in <to>]`, in the fiat of the channel by default.
+/// None when the args don't start with an amount, they're then a coin to quote.
+pub fn parse(args: &str) -> Option<StdResult<Conversion, String>> {
+    let mut words = args.split_whitespace();
+    let amount = words.next()?;
+    if !amount
+        .chars()
+        .all(|c| c.is_ascii_digit() || ".,-+".contains(c))
+    {
+        // like 1inch, a coin
+        return None;
+    }
+    let amount = match parse_amount(amount) {
+        Some(amount) => amount,
+        None => return Some(Err(USAGE.to_string())),
+    };
+    if amount <= Decimal::ZERO || amount > Decimal::from(MAX_AMOUNT) {
+        return Some(Err(format!(
+            "Sorry, I only convert amounts between 0 and {}",
+            format_decimal(Decimal::from(MAX_AMOUNT))
+        )));
+    }
+    let conversion = match (words.next(), words.next(), words.next(), words.next()) {
+        (Some(from), None, _, _) => Some((from, None)),
+        (Some(from), Some("in"), Some(to), None) => Some((from, Some(to))),
+        _ => None,
+    };
+    Some(
+        conversion
+            .map(|(from, to)| Conversion { amount, from, to })
+            .ok_or_else(|| USAGE.to_string()),
+    )
+}
+
+/// Both `0.5` and `0,5`
+fn parse_amount(input: &str) -> Option<Decimal> {
+    Decimal::from_str(&input.replace(',', ".")).ok()
+}
+
+/// A supported fiat, by its ISO code or like `€`
+pub fn parse_fiat(input: &str) -> Option<Fiat> {
+    match EURO.contains(&input.to_lowercase().as_str()) {
+        true => Some(Fiat::EUR),
+        false => Fiat::parse(input),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Unit {
+    Fiat(Fiat),
+    Coin { id: String, symbol: String },
+}
+
+impl Unit {
+    fn label(&self) -> String {
+        match self {
+            Unit::Fiat(fiat) => fiat.label(),
+            Unit::Coin { symbol, .. } => symbol.to_uppercase(),
+        }
+    }
+
+    /// None for a fiat, which needs no quote
+    pub fn coin_id(&self) -> Option<&str> {
+        match self {
+            Unit::Fiat(_) => None,
+            Unit::Coin { id, .. } => Some(id),
+        }
+    }
+
+    /// None for a fiat, which needs no quote
+    pub fn coin(&self) -> Option<CoinRef> {
+        match self {
+            Unit::Fiat(_) => None,
+            Unit::Coin { id, symbol } => Some(CoinRef { id, symbol }),
+        }
+    }
+}
+
+/// The fiat the quotes of a conversion are in: the one converted from or
+/// to, `default` between two coins
+pub fn quoted_in(from: &Unit, to: &Unit, default: Fiat) -> Fiat {
+    match (from, to) {
+        (Unit::Fiat(fiat), _) | (_, Unit::Fiat(fiat)) => *fiat,
+        _ => default,
+    }
+}
+
+/// Like `0.5 BTC ≈ 32 115 € (rate 64 230 €/BTC, 40s old)`, the quotes
+/// are by coingecko id, in the fiat given by `quoted_in`. Between two coins,
+/// the rate goes through that fiat.
+pub fn convert(
+    amount: Decimal,
+    from: &Unit,
+    to: &Unit,
+    quotes: &HashMap<String, Cached>,
+    now: Instant,
+) -> StdResult<String, String> {
+    if from == to {
+        return Err(format!("{amount} {} is {amount} {0}", from.label()));
+    }
+    if let (Unit::Fiat(_), Unit::Fiat(_)) = (from, to) {
+        return Err("I only convert from or to a coin".to_string());
+    }
+    let in_fiat = |unit: &Unit| match unit.coin_id() {
+        None => Ok((Decimal::ONE, None)),
+        Some(id) => quotes
+            .get(id)
+            .map(|c| (c.quote.price, Some(c)))
+            .ok_or_else(|| format!("No quote for {}, try again later", unit.label())),
+    };
+    let (from_fiat, from_quote) = in_fiat(from)?;
+    let (to_fiat, to_quote) = in_fiat(to)?;
+    let overflow = || "That's too much money for me".to_string();
+    // the rate is given per coin, whichever way the conversion goes
+    let (rate, rate_label) = match (from, to) {
+        (_, Unit::Fiat(_)) => (from_fiat, format!("{}/{}", to.label(), from.label())),
+        (Unit::Fiat(_), _) => (to_fiat, format!("{}/{}", from.label(), to.label())),
+        _ => (
+            from_fiat.checked_div(to_fiat).ok_or_else(overflow)?,
+            format!("{}/{}", to.label(), from.label()),
+        ),
+    };
+    let converted = amount
+        .checked_mul(from_fiat)
+        .and_then(|amount| amount.checked_div(to_fiat))
+        .ok_or_else(overflow)?;
+    let used = [from_quote, to_quote].into_iter().flatten();
+    let oldest = used.clone().map(|c| c.quote.at).min().unwrap_or(now);
+    let outdated = if used.clone().any(|c| c.stale) {
+        ", outdated"
+    } else {
+        ""
+    };
+    Ok(format!(
+        "{} {} ≈ {} {} (rate {} {rate_label}, {} old{outdated})",
+        amount.normalize(),
+        from.label(),
+        format_decimal(converted),
+        to.label(),
+        format_decimal(rate),
+        format_age(now.saturating_duration_since(oldest)),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::plugins::crypto::quotes::Quote;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn coin(id: &str, symbol: &str) -> Unit {
+        Unit::Coin {
+            id: id.to_string(),
+            symbol: symbol.to_string(),
+        }
+    }
+
+    #[test]
+    async fn test_parse() {
+        assert_eq!(
+            parse("0.5 btc in eur"),
+            Some(Ok(Conversion {
+                amount: dec("0.5"),
+                from: "btc",
+                to: Some("eur")
+            }))
+        );
+        assert_eq!(
+            parse("1200 eur in eth"),
+            Some(Ok(Conversion {
+                amount: dec("1200"),
+                from: "eur",
+                to: Some("eth")
+            }))
+        );
+        assert_eq!(
+            parse("0,25 eth in btc"),
+            Some(Ok(Conversion {
+                amount: dec("0.25"),
+                from: "eth",
+                to: Some("btc")
+            })),
+            "comma as decimal separator"
+        );
+        assert_eq!(
+            parse("2 doge"),
+            Some(Ok(Conversion {
+                amount: dec("2"),
+                from: "doge",
+                to: None
+            })),
+            "in the fiat of the channel by default"
+        );
+
+        assert_eq!(parse("btc"), None, "a quote");
+        assert_eq!(parse("1inch"), None, "a coin starting with a digit");
+        assert_eq!(parse(""), None);
+
+        for malformed in [
+            "1.2.3 btc",
+            "2 btc to eur",
+            "2 btc in",
+            "2",
+            "2 btc in eur please",
+        ] {
+            assert_eq!(
+                parse(malformed),
+                Some(Err(USAGE.to_string())),
+                "{malformed}"
+            );
+        }
+        for absurd in ["-3 btc", "0 btc", "1000000000000001 btc"] {
+            assert!(
+                matches!(parse(absurd), Some(Err(msg)) if msg.starts_with("Sorry")),
+                "{absurd}"
+            );
+        }
+    }
+
+    fn cached(price: &str, at: Instant, stale: bool) -> Cached {
+        Cached {
+            quote: Quote {
+                price: dec(price),
+                change_24h: None,
+                at,
+            },
+            stale,
+        }
+    }
+
+    #[test]
+    async fn test_convert() {
+        let now = Instant::now();
+        let mut quotes = HashMap::from([
+            (
+                "bitcoin".to_string(),
+                cached("64230", now - Duration::from_secs(40), false),
+            ),
+            (
+                "ethereum".to_string(),
+                cached("2000", now - Duration::from_secs(150), false),
+            ),
+        ]);
+        let btc = coin("bitcoin", "btc");
+        let eth = coin("ethereum", "eth");
+
+        assert_eq!(
+            convert(dec("0.5"), &btc, &Unit::Fiat(Fiat::EUR), &quotes, now),
+            Ok("0.5 BTC ≈ 32\u{2009}115 € (rate 64\u{2009}230 €/BTC, 40s old)".to_string())
+        );
+        assert_eq!(
+            convert(dec("1200"), &Unit::Fiat(Fiat::EUR), &eth, &quotes, now),
+            Ok("1200 € ≈ 0.60 ETH (rate 2\u{2009}000 €/ETH, 2 min old)".to_string())
+        );
+        assert_eq!(
+            convert(dec("2"), &eth, &btc, &quotes, now),
+            Ok("2 ETH ≈ 0.06228 BTC (rate 0.03114 BTC/ETH, 2 min old)".to_string()),
+            "chained through the euro, as old as the oldest quote"
+        );
+        assert_eq!(
+            convert(dec("0.1"), &btc, &btc, &quotes, now),
+            Err("0.1 BTC is 0.1 BTC".to_string())
+        );
+        assert_eq!(
+            convert(
+                dec("1"),
+                &coin("dogecoin", "doge"),
+                &Unit::Fiat(Fiat::EUR),
+                &quotes,
+                now
+            ),
+            Err("No quote for DOGE, try again later".to_string())
+        );
+
+        quotes.insert(
+            "bitcoin".to_string(),
+            cached("64230", now - Duration::from_secs(720), true),
+        );
+        assert_eq!(
+            convert(dec("0.5"), &btc, &Unit::Fiat(Fiat::EUR), &quotes, now),
+            Ok(
+                "0.5 BTC ≈ 32\u{2009}115 € (rate 64\u{2009}230 €/BTC, 12 min old, outdated)"
+                    .to_string()
+            )
+        );
+
+        let usd = Unit::Fiat(Fiat::parse("usd").unwrap());
+        assert_eq!(
+            convert(dec("100"), &usd, &eth, &quotes, now),
+            Ok("100 USD ≈ 0.05 ETH (rate 2\u{2009}000 USD/ETH, 2 min old)".to_string()),
+            "the quotes given in dollars"
+        );
+        assert_eq!(
+            convert(dec("100"), &usd, &Unit::Fiat(Fiat::EUR), &quotes, now),
+            Err("I only convert from or to a coin".to_string())
+        );
+    }
+
+    #[test]
+    async fn test_fiats() {
+        assert_eq!(parse_fiat("€"), Some(Fiat::EUR));
+        assert_eq!(parse_fiat("Euros"), Some(Fiat::EUR));
+        assert_eq!(parse_fiat("USD"), Fiat::parse("usd"));
+        assert_eq!(parse_fiat("btc"), None);
+
+        let btc = coin("bitcoin", "btc");
+        let usd = Fiat::parse("usd").unwrap();
+        assert_eq!(quoted_in(&btc, &Unit::Fiat(usd), Fiat::EUR), usd);
+        assert_eq!(quoted_in(&Unit::Fiat(usd), &btc, Fiat::EUR), usd);
+        assert_eq!(
+            quoted_in(&btc, &coin("ethereum", "eth"), usd),
+            usd,
+            "between two coins"
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use anyhow::{Context, Result};
 use diesel::prelude::*;
+use diesel::sql_types::BigInt;
 use diesel::Connection;
+use plugin_core::Database;
 diesel_migrations::embed_migrations!("./migrations/");
 
 pub fn establish_connection() -> Result<SqliteConnection> {
@@ -12,3 +14,41 @@ pub fn run_migrations(connection: &SqliteConnection) -> Result<()> {
     embedded_migrations::run(connection)
         .context("Cannot run migration")
 }
+
+/// The tables of the crypto plugin in the shared database, see
+/// `plugin_core::ensure_schema`
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE crypto_alerts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        nick TEXT NOT NULL,
+        network TEXT,
+        channel TEXT NOT NULL,
+        coin TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        direction TEXT NOT NULL,
+        threshold REAL NOT NULL,
+        created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );",
+    // amounts as text, to keep all their decimals
+    "CREATE TABLE crypto_holdings (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        network TEXT,
+        owner_kind TEXT NOT NULL,
+        owner TEXT NOT NULL,
+        coin TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        amount TEXT NOT NULL,
+        updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );",
+];
+
+pub fn ensure_schema(db: &Database) -> plugin_core::Result<()> {
+    plugin_core::ensure_schema(db, "crypto", MIGRATIONS)
+}
+
+/// Id of the row inserted last on the connection
+#[derive(QueryableByName)]
+pub struct LastId {
+    #[sql_type = "BigInt"]
+    pub id: i64,
+}
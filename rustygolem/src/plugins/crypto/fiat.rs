@@ -0,0 +1,188 @@
+use crate::utils::numbers::{format_amount, format_decimal};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// The currencies every provider quotes the coins in
+const SUPPORTED: &[&str] = &["eur", "usd", "gbp", "chf", "jpy", "cad", "aud"];
+
+/// A fiat currency the coins are quoted in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fiat(&'static str);
+
+impl Fiat {
+    pub const EUR: Fiat = Fiat("eur");
+
+    /// From its ISO code, in any case. None for a currency not supported.
+    pub fn parse(code: &str) -> Option<Self> {
+        let code = code.to_lowercase();
+        SUPPORTED
+            .iter()
+            .find(|supported| **supported == code)
+            .map(|supported| Fiat(*supported))
+    }
+
+    /// Lowercase, like the providers want it
+    pub fn code(&self) -> &'static str {
+        self.0
+    }
+
+    /// Like `64 230 €`, `$64 230` or `64 230 GBP`
+    pub fn format(&self, amount: Decimal) -> String {
+        self.place(format_decimal(amount))
+    }
+
+    /// Like `format`, from a float
+    pub fn format_f64(&self, amount: f64) -> String {
+        self.place(format_amount(amount))
+    }
+
+    /// `€` for the euro, the uppercase code otherwise
+    pub fn label(&self) -> String {
+        match self.0 {
+            "eur" => "€".to_string(),
+            code => code.to_uppercase(),
+        }
+    }
+
+    fn place(&self, amount: String) -> String {
+        match self.0 {
+            "eur" => format!("{amount} €"),
+            "usd" => format!("${amount}"),
+            code => format!("{amount} {}", code.to_uppercase()),
+        }
+    }
+}
+
+/// An entry of the `channel_fiats` list of the crypto config section
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ChannelFiat {
+    pub channel: String,
+    /// ISO code, like USD
+    pub fiat: String,
+}
+
+/// Which fiat to quote in, by channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fiats {
+    default: Fiat,
+    by_channel: HashMap<String, Fiat>,
+}
+
+impl Fiats {
+    /// EUR without a default
+    pub fn new(default: Option<&str>, by_channel: &[ChannelFiat]) -> anyhow::Result<Self> {
+        let parse = |code: &str| {
+            Fiat::parse(code).ok_or_else(|| {
+                anyhow!(
+                    "Unsupported fiat {code}, the supported ones are {}",
+                    SUPPORTED.join(", ")
+                )
+            })
+        };
+        Ok(Fiats {
+            default: default.map_or(Ok(Fiat::EUR), parse)?,
+            by_channel: by_channel
+                .iter()
+                .map(|c| Ok((c.channel.to_lowercase(), parse(&c.fiat)?)))
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+
+    /// The alerts, holdings and the recorded rates are in this one
+    pub fn default(&self) -> Fiat {
+        self.default
+    }
+
+    /// The one given in the command, then the one of the channel, then
+    /// the default one
+    pub fn resolve(&self, inline: Option<Fiat>, channel: Option<&str>) -> Fiat {
+        inline
+            .or_else(|| {
+                let channel = channel?.to_lowercase();
+                self.by_channel.get(&channel).copied()
+            })
+            .unwrap_or(self.default)
+    }
+}
+
+/// `<coin> <fiat>`, like `btc usd`. The input is a coin alone when the last
+/// word isn't a supported fiat.
+pub fn split_inline(input: &str) -> (&str, Option<Fiat>) {
+    match input.trim().rsplit_once(char::is_whitespace) {
+        Some((coin, code)) => match Fiat::parse(code) {
+            Some(fiat) => (coin.trim(), Some(fiat)),
+            None => (input, None),
+        },
+        None => (input, None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::str::FromStr;
+
+    fn usd() -> Fiat {
+        Fiat::parse("usd").unwrap()
+    }
+
+    fn fiats() -> Fiats {
+        Fiats::new(
+            Some("EUR"),
+            &[ChannelFiat {
+                channel: "#Crypto-US".to_string(),
+                fiat: "USD".to_string(),
+            }],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    async fn test_parse() {
+        assert_eq!(Fiat::parse("EUR"), Some(Fiat::EUR));
+        assert_eq!(Fiat::parse("usd").map(|f| f.code()), Some("usd"));
+        assert_eq!(Fiat::parse("btc"), None);
+        assert_eq!(Fiat::parse("inu"), None);
+    }
+
+    #[test]
+    async fn test_format() {
+        let amount = Decimal::from_str("64230.4").unwrap();
+        assert_eq!(Fiat::EUR.format(amount), "64\u{2009}230 €");
+        assert_eq!(usd().format(amount), "$64\u{2009}230");
+        assert_eq!(
+            Fiat::parse("gbp").unwrap().format(amount),
+            "64\u{2009}230 GBP"
+        );
+        assert_eq!(usd().format_f64(0.061234), "$0.06123");
+    }
+
+    #[test]
+    async fn test_resolve() {
+        let fiats = fiats();
+        let gbp = Fiat::parse("gbp");
+        assert_eq!(
+            fiats.resolve(gbp, Some("#crypto-us")),
+            gbp.unwrap(),
+            "inline"
+        );
+        assert_eq!(fiats.resolve(None, Some("#crypto-us")), usd(), "channel");
+        assert_eq!(fiats.resolve(None, Some("#rust")), Fiat::EUR, "default");
+        assert_eq!(fiats.resolve(None, None), Fiat::EUR, "in private");
+
+        let fiats = Fiats::new(None, &[]).unwrap();
+        assert_eq!(fiats.default(), Fiat::EUR);
+        assert!(Fiats::new(Some("btc"), &[]).is_err());
+    }
+
+    #[test]
+    async fn test_split_inline() {
+        assert_eq!(split_inline("btc usd"), ("btc", Some(usd())));
+        assert_eq!(split_inline("shiba inu USD"), ("shiba inu", Some(usd())));
+        assert_eq!(split_inline("shiba inu"), ("shiba inu", None));
+        assert_eq!(split_inline("btc"), ("btc", None));
+        assert_eq!(split_inline("usd"), ("usd", None), "a coin, maybe");
+    }
+}
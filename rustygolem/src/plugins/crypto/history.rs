@@ -0,0 +1,269 @@
+use super::fiat::Fiat;
+use super::providers::PricePoint;
+use crate::utils::numbers::decimal_from_f64;
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::result::Result as StdResult;
+
+pub const USAGE: &str = "Usage: λcrypto <coin> <1d, 7d, 30d or 1y>, like λcrypto btc 7d";
+
+/// A data point further than that from the start of the window is given
+/// with its date
+const SHOW_DATE_AFTER: i64 = 3 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Window {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Window {
+    fn parse(input: &str) -> Option<Self> {
+        match input.to_lowercase().as_str() {
+            "1d" => Some(Window::Day),
+            "7d" => Some(Window::Week),
+            "30d" => Some(Window::Month),
+            "1y" => Some(Window::Year),
+            _ => None,
+        }
+    }
+
+    pub fn days(&self) -> u32 {
+        match self {
+            Window::Day => 1,
+            Window::Week => 7,
+            Window::Month => 30,
+            Window::Year => 365,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Window::Day => "1d",
+            Window::Week => "7d",
+            Window::Month => "30d",
+            Window::Year => "1y",
+        }
+    }
+}
+
+/// `<coin> <window>`, the coin as typed.
+/// None when the last word doesn't look like a window, the args are then a coin.
+pub fn parse(args: &str) -> Option<StdResult<(&str, Window), String>> {
+    let (coin, window) = args.trim().rsplit_once(char::is_whitespace)?;
+    let mut chars = window.chars();
+    let unit = chars.next_back()?;
+    if !unit.is_ascii_alphabetic() || !chars.as_str().chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    if chars.as_str().is_empty() {
+        // like `shiba inu`, no digits
+        return None;
+    }
+    Some(
+        Window::parse(window)
+            .map(|window| (coin.trim(), window))
+            .ok_or_else(|| USAGE.to_string()),
+    )
+}
+
+/// The price at the start of the window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Past {
+    pub at: DateTime<Utc>,
+    pub price: Decimal,
+}
+
+/// The data point the closest to `target`
+pub fn closest(points: &[PricePoint], target: DateTime<Utc>) -> Option<Past> {
+    points
+        .iter()
+        .filter_map(|point| {
+            Some(Past {
+                at: point.at,
+                price: decimal_from_f64(point.price)?,
+            })
+        })
+        .min_by_key(|past| (past.at - target).num_seconds().abs())
+}
+
+/// In percents, None from a zero price
+pub fn percent_change(then: Decimal, now: Decimal) -> Option<f64> {
+    if then.is_zero() {
+        return None;
+    }
+    ((now - then) / then * Decimal::ONE_HUNDRED).to_f64()
+}
+
+/// Like `+8.7%`
+fn format_percent(change: f64) -> String {
+    let rounded = (change * 10.0).round() / 10.0;
+    // no -0.0%
+    let rounded = if rounded == 0.0 { 0.0 } else { rounded };
+    format!("{rounded:+.1}%")
+}
+
+/// When the window started
+pub fn start(window: Window, now: DateTime<Utc>) -> DateTime<Utc> {
+    now - Duration::days(window.days().into())
+}
+
+/// Like `BTC: 64 230 € — 7d ago: 59 100 € (+8.7%)`
+pub fn describe(
+    symbol: &str,
+    price: Decimal,
+    window: Window,
+    past: Past,
+    fiat: Fiat,
+    now: DateTime<Utc>,
+) -> String {
+    let date = if (past.at - start(window, now)).num_seconds().abs() > SHOW_DATE_AFTER {
+        format!(" (on {})", past.at.format("%Y-%m-%d %H:%M UTC"))
+    } else {
+        "".to_string()
+    };
+    let change = match percent_change(past.price, price) {
+        Some(change) => format!(" ({})", format_percent(change)),
+        None => "".to_string(),
+    };
+    format!(
+        "{}: {} — {} ago{date}: {}{change}",
+        symbol.to_uppercase(),
+        fiat.format(price),
+        window.label(),
+        fiat.format(past.price),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    async fn test_parse() {
+        assert_eq!(parse("btc 7d"), Some(Ok(("btc", Window::Week))));
+        assert_eq!(parse("BTC 1Y"), Some(Ok(("BTC", Window::Year))));
+        assert_eq!(
+            parse("shiba inu 30d"),
+            Some(Ok(("shiba inu", Window::Month)))
+        );
+        assert_eq!(parse("ethereum 1d "), Some(Ok(("ethereum", Window::Day))));
+
+        assert_eq!(parse("btc"), None);
+        assert_eq!(parse("7d"), None, "a coin, maybe");
+        assert_eq!(parse("shiba inu"), None);
+        assert_eq!(parse("btc 1inch"), None);
+
+        for unsupported in ["btc 2d", "btc 7w", "btc 24h", "btc 0d"] {
+            assert_eq!(
+                parse(unsupported),
+                Some(Err(USAGE.to_string())),
+                "{unsupported}"
+            );
+        }
+    }
+
+    #[test]
+    async fn test_percent_change() {
+        assert_eq!(
+            percent_change(dec("59100"), dec("64230")).map(format_percent),
+            Some("+8.7%".to_string())
+        );
+        assert_eq!(
+            percent_change(dec("2000"), dec("1500")).map(format_percent),
+            Some("-25.0%".to_string())
+        );
+        assert_eq!(
+            percent_change(dec("100"), dec("99.99")).map(format_percent),
+            Some("+0.0%".to_string()),
+            "no negative zero"
+        );
+        assert_eq!(percent_change(dec("0"), dec("1")), None);
+    }
+
+    fn point(at: DateTime<Utc>, price: f64) -> PricePoint {
+        PricePoint { at, price }
+    }
+
+    fn past(at: DateTime<Utc>, price: &str) -> Past {
+        Past {
+            at,
+            price: dec(price),
+        }
+    }
+
+    #[test]
+    async fn test_closest() {
+        let t0 = Utc.ymd(2024, 5, 1).and_hms(0, 0, 0);
+        let points = [
+            point(t0, 1.0),
+            point(t0 + Duration::hours(1), 2.0),
+            point(t0 + Duration::hours(2), 3.0),
+            point(t0 + Duration::hours(3), f64::NAN),
+        ];
+        assert_eq!(
+            closest(&points, t0 + Duration::minutes(40)),
+            Some(past(t0 + Duration::hours(1), "2"))
+        );
+        assert_eq!(
+            closest(&points, t0 - Duration::days(1)),
+            Some(past(t0, "1"))
+        );
+        assert_eq!(
+            closest(&points, t0 + Duration::hours(3)),
+            Some(past(t0 + Duration::hours(2), "3")),
+            "skipping the invalid prices"
+        );
+        assert_eq!(closest(&[], t0), None);
+    }
+
+    #[test]
+    async fn test_describe() {
+        let now = Utc.ymd(2024, 5, 8).and_hms(12, 0, 0);
+        let week_ago = Utc.ymd(2024, 5, 1).and_hms(12, 0, 0);
+        assert_eq!(
+            describe(
+                "btc",
+                dec("64230"),
+                Window::Week,
+                past(week_ago + Duration::hours(1), "59100"),
+                Fiat::EUR,
+                now
+            ),
+            "BTC: 64\u{2009}230 € — 7d ago: 59\u{2009}100 € (+8.7%)"
+        );
+        assert_eq!(
+            describe(
+                "btc",
+                dec("69000"),
+                Window::Week,
+                past(week_ago, "63500"),
+                Fiat::parse("usd").unwrap(),
+                now
+            ),
+            "BTC: $69\u{2009}000 — 7d ago: $63\u{2009}500 (+8.7%)"
+        );
+        assert_eq!(
+            describe(
+                "btc",
+                dec("64230"),
+                Window::Week,
+                past(week_ago - Duration::hours(12), "59100"),
+                Fiat::EUR,
+                now
+            ),
+            "BTC: 64\u{2009}230 € — 7d ago (on 2024-05-01 00:00 UTC): 59\u{2009}100 € (+8.7%)",
+            "too far from the start of the window"
+        );
+    }
+}
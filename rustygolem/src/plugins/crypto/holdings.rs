@@ -0,0 +1,627 @@
+use super::db;
+use super::fiat::Fiat;
+use super::quotes::Cached;
+use crate::utils::numbers::decimal_from_f32;
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Nullable, Text};
+use irc::proto::Message;
+use plugin_core::utils::account::account;
+use plugin_core::{Database, Result};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::result::Result as StdResult;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// Coins someone can hold on a network
+pub const MAX_HOLDINGS: usize = 20;
+
+/// More than the supply of about any coin, and far enough from the limits
+/// of Decimal to value it
+const MAX_AMOUNT: i64 = 1_000_000_000_000_000;
+
+pub const USAGE: &str =
+    "Usage: λcrypto hold add <amount> <coin>, λcrypto hold rm <coin>, λcrypto hold list, λcrypto hold";
+
+/// The answer to `hold list` in a channel
+pub const LIST_IN_PRIVATE: &str =
+    "Your holdings stay between us, ask me in private: λcrypto hold list";
+
+#[derive(Debug, PartialEq)]
+pub enum HoldCommand<'a> {
+    /// the coin as typed, resolved later
+    Add {
+        amount: Decimal,
+        coin: &'a str,
+    },
+    Remove(&'a str),
+    List,
+    /// the value of everything held
+    Total,
+}
+
+/// `hold …`, None when the args are something else
+pub fn parse(args: &str) -> Option<StdResult<HoldCommand, String>> {
+    let rest = args.trim().strip_prefix("hold")?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        // some coin starting with hold
+        return None;
+    }
+    let rest = rest.trim();
+    let (word, rest) = rest
+        .split_once(char::is_whitespace)
+        .map_or((rest, ""), |(word, rest)| (word, rest.trim()));
+    let command = match (word, rest) {
+        ("", _) => Some(HoldCommand::Total),
+        ("list", "") => Some(HoldCommand::List),
+        ("rm", coin) if !coin.is_empty() => Some(HoldCommand::Remove(coin)),
+        ("add", rest) => rest
+            .split_once(char::is_whitespace)
+            .and_then(|(amount, coin)| {
+                Some(HoldCommand::Add {
+                    amount: parse_amount(amount)?,
+                    coin: coin.trim(),
+                })
+            }),
+        _ => None,
+    };
+    Some(command.ok_or_else(|| USAGE.to_string()))
+}
+
+/// Like `0.2`, `0,25` or `1_000`, never rounded through a float
+fn parse_amount(input: &str) -> Option<Decimal> {
+    let amount = input
+        .chars()
+        .filter(|c| *c != '_')
+        .map(|c| if c == ',' { '.' } else { c })
+        .collect::<String>();
+    Decimal::from_str(&amount)
+        .ok()
+        .filter(|a| a.is_sign_positive() && !a.is_zero() && *a <= Decimal::from(MAX_AMOUNT))
+}
+
+/// Whose holdings they are
+#[derive(Debug, Clone, PartialEq)]
+pub enum Owner {
+    /// services account, the same whatever the nick
+    Account(String),
+    /// when the server doesn't tell the account, or the sender isn't logged in
+    Nick(String),
+}
+
+impl Owner {
+    pub fn of(msg: &Message) -> Option<Self> {
+        match account(msg) {
+            Some(account) => Some(Owner::Account(account.to_lowercase())),
+            None => Some(Owner::Nick(msg.source_nickname()?.to_lowercase())),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Owner::Account(_) => "account",
+            Owner::Nick(_) => "nick",
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Owner::Account(name) | Owner::Nick(name) => name,
+        }
+    }
+
+    fn from_row(kind: &str, name: String) -> Self {
+        match kind {
+            "account" => Owner::Account(name),
+            _ => Owner::Nick(name),
+        }
+    }
+}
+
+#[derive(QueryableByName)]
+struct Row {
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Nullable<Text>"]
+    network: Option<String>,
+    #[sql_type = "Text"]
+    owner_kind: String,
+    #[sql_type = "Text"]
+    owner: String,
+    #[sql_type = "Text"]
+    coin: String,
+    #[sql_type = "Text"]
+    symbol: String,
+    #[sql_type = "Text"]
+    amount: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Holding {
+    id: i32,
+    network: Option<String>,
+    owner: Owner,
+    /// coingecko id
+    pub coin: String,
+    pub symbol: String,
+    pub amount: Decimal,
+}
+
+impl Holding {
+    fn from_row(row: Row) -> Option<Self> {
+        let amount = match Decimal::from_str(&row.amount) {
+            Ok(amount) => amount,
+            Err(err) => {
+                log::warn!("Ignoring the holding #{} of {}: {err}", row.id, row.amount);
+                return None;
+            }
+        };
+        Some(Holding {
+            id: row.id,
+            network: row.network,
+            owner: Owner::from_row(&row.owner_kind, row.owner),
+            coin: row.coin,
+            symbol: row.symbol,
+            amount,
+        })
+    }
+
+    fn belongs_to(&self, network: Option<&str>, owner: &Owner) -> bool {
+        self.network.as_deref() == network && &self.owner == owner
+    }
+}
+
+/// The holdings of everyone, persisted in the database
+pub struct Holdings {
+    db: Database,
+    holdings: Mutex<Vec<Holding>>,
+}
+
+impl Holdings {
+    /// Create the table if needed, and load the holdings from before the last restart
+    pub fn load(db: Database) -> Result<Self> {
+        db::ensure_schema(&db)?;
+        let rows = db.with_connection(|conn| {
+            diesel::sql_query(
+                "SELECT id, network, owner_kind, owner, coin, symbol, amount \
+                 FROM crypto_holdings ORDER BY id",
+            )
+            .load::<Row>(conn)
+        })?;
+        let holdings = rows
+            .into_iter()
+            .filter_map(Holding::from_row)
+            .collect::<Vec<_>>();
+        log::info!("Loaded {} crypto holdings", holdings.len());
+        Ok(Holdings {
+            db,
+            holdings: Mutex::new(holdings),
+        })
+    }
+
+    /// On top of what the owner already holds of the coin. The amount now
+    /// held, None when the owner already holds MAX_HOLDINGS other coins.
+    pub fn add(
+        &self,
+        network: Option<&str>,
+        owner: &Owner,
+        coin: &str,
+        symbol: &str,
+        amount: Decimal,
+    ) -> Result<Option<Decimal>> {
+        let mut holdings = self.holdings.lock().expect("holdings lock");
+        if let Some(holding) = holdings
+            .iter_mut()
+            .find(|h| h.coin == coin && h.belongs_to(network, owner))
+        {
+            let total = holding.amount + amount;
+            self.db.with_connection(|conn| {
+                diesel::sql_query(
+                    "UPDATE crypto_holdings SET amount = ?, updated_at = CURRENT_TIMESTAMP \
+                     WHERE id = ?",
+                )
+                .bind::<Text, _>(total.to_string())
+                .bind::<Integer, _>(holding.id)
+                .execute(conn)
+            })?;
+            holding.amount = total;
+            return Ok(Some(total));
+        }
+
+        let held = holdings
+            .iter()
+            .filter(|h| h.belongs_to(network, owner))
+            .count();
+        if held >= MAX_HOLDINGS {
+            return Ok(None);
+        }
+        let mut holding = Holding {
+            id: 0,
+            network: network.map(String::from),
+            owner: owner.clone(),
+            coin: coin.to_string(),
+            symbol: symbol.to_uppercase(),
+            amount,
+        };
+        let id = self.db.with_connection(|conn| {
+            conn.transaction(|| {
+                diesel::sql_query(
+                    "INSERT INTO crypto_holdings \
+                     (network, owner_kind, owner, coin, symbol, amount) \
+                     VALUES (?, ?, ?, ?, ?, ?)",
+                )
+                .bind::<Nullable<Text>, _>(&holding.network)
+                .bind::<Text, _>(holding.owner.kind())
+                .bind::<Text, _>(holding.owner.name())
+                .bind::<Text, _>(&holding.coin)
+                .bind::<Text, _>(&holding.symbol)
+                .bind::<Text, _>(holding.amount.to_string())
+                .execute(conn)?;
+                diesel::sql_query("SELECT last_insert_rowid() AS id").get_result::<db::LastId>(conn)
+            })
+        })?;
+        holding.id = id.id as i32;
+        holdings.push(holding);
+        Ok(Some(amount))
+    }
+
+    /// False when the owner doesn't hold the coin
+    pub fn remove(&self, network: Option<&str>, owner: &Owner, coin: &str) -> Result<bool> {
+        let mut holdings = self.holdings.lock().expect("holdings lock");
+        match holdings
+            .iter()
+            .position(|h| h.coin == coin && h.belongs_to(network, owner))
+        {
+            Some(idx) => {
+                self.db.with_connection(|conn| {
+                    diesel::sql_query("DELETE FROM crypto_holdings WHERE id = ?")
+                        .bind::<Integer, _>(holdings[idx].id)
+                        .execute(conn)
+                })?;
+                holdings.remove(idx);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// By symbol
+    pub fn list(&self, network: Option<&str>, owner: &Owner) -> Vec<Holding> {
+        let mut holdings = self
+            .holdings
+            .lock()
+            .expect("holdings lock")
+            .iter()
+            .filter(|h| h.belongs_to(network, owner))
+            .cloned()
+            .collect::<Vec<_>>();
+        holdings.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        holdings
+    }
+}
+
+/// What the holdings are worth
+#[derive(Debug, PartialEq)]
+pub struct Valuation {
+    pub total: Decimal,
+    /// the total 24h ago, at today's amounts
+    pub previous: Decimal,
+    /// symbols of the coins without a quote, not counted
+    pub missing: Vec<String>,
+}
+
+impl Valuation {
+    /// `quotes` by coingecko id. A coin without its 24h change counts as
+    /// unchanged.
+    pub fn of(holdings: &[Holding], quotes: &HashMap<String, Cached>) -> Self {
+        let mut valuation = Valuation {
+            total: Decimal::ZERO,
+            previous: Decimal::ZERO,
+            missing: vec![],
+        };
+        for holding in holdings {
+            let cached = match quotes.get(&holding.coin) {
+                Some(cached) => cached,
+                None => {
+                    valuation.missing.push(holding.symbol.clone());
+                    continue;
+                }
+            };
+            let value = holding.amount * cached.quote.price;
+            valuation.total += value;
+            valuation.previous += cached
+                .quote
+                .change_24h
+                .and_then(decimal_from_f32)
+                .map(|change| Decimal::ONE_HUNDRED + change)
+                .filter(|ratio| ratio.is_sign_positive() && !ratio.is_zero())
+                .map_or(value, |ratio| value * Decimal::ONE_HUNDRED / ratio);
+        }
+        valuation
+    }
+
+    pub fn delta(&self) -> Decimal {
+        self.total - self.previous
+    }
+
+    /// In percents, None when nothing was worth anything
+    pub fn change_24h(&self) -> Option<f32> {
+        if self.previous.is_zero() {
+            return None;
+        }
+        (self.delta() / self.previous * Decimal::ONE_HUNDRED).to_f32()
+    }
+}
+
+/// Like `BTC 0.2 (12 846 €) | ETH 1.5 (4 680 €)`, the details are only
+/// ever given in private
+pub fn describe_list(
+    private: bool,
+    holdings: &[Holding],
+    quotes: &HashMap<String, Cached>,
+    fiat: Fiat,
+) -> String {
+    if !private {
+        return LIST_IN_PRIVATE.to_string();
+    }
+    if holdings.is_empty() {
+        return nothing_held();
+    }
+    holdings
+        .iter()
+        .map(|holding| {
+            let amount = format!("{} {}", holding.symbol, holding.amount.normalize());
+            match quotes.get(&holding.coin) {
+                Some(cached) => format!(
+                    "{amount} ({})",
+                    fiat.format(holding.amount * cached.quote.price)
+                ),
+                None => amount,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+pub fn nothing_held() -> String {
+    "You don't hold anything, add some with λcrypto hold add 0.2 btc".to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::plugins::crypto::quotes::Quote;
+    use pretty_assertions::assert_eq;
+    use std::time::Instant;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn geek() -> Owner {
+        Owner::Account("geekingfrog".to_string())
+    }
+
+    fn cached(price: &str, change_24h: Option<f32>) -> Cached {
+        Cached {
+            quote: Quote {
+                price: dec(price),
+                change_24h,
+                at: Instant::now(),
+            },
+            stale: false,
+        }
+    }
+
+    fn holding(coin: &str, symbol: &str, amount: &str) -> Holding {
+        Holding {
+            id: 0,
+            network: Some("libera".to_string()),
+            owner: geek(),
+            coin: coin.to_string(),
+            symbol: symbol.to_string(),
+            amount: dec(amount),
+        }
+    }
+
+    #[test]
+    async fn test_parse() {
+        assert_eq!(parse("hold"), Some(Ok(HoldCommand::Total)));
+        assert_eq!(parse("hold list"), Some(Ok(HoldCommand::List)));
+        assert_eq!(
+            parse("hold add 0.2 btc"),
+            Some(Ok(HoldCommand::Add {
+                amount: dec("0.2"),
+                coin: "btc"
+            }))
+        );
+        assert_eq!(
+            parse("hold add 1_000,5 shiba inu"),
+            Some(Ok(HoldCommand::Add {
+                amount: dec("1000.5"),
+                coin: "shiba inu"
+            }))
+        );
+        assert_eq!(
+            parse("hold rm shiba inu"),
+            Some(Ok(HoldCommand::Remove("shiba inu")))
+        );
+
+        for malformed in [
+            "hold add",
+            "hold add 0.2",
+            "hold add btc 0.2",
+            "hold add 0 btc",
+            "hold add -1 btc",
+            "hold add 1e3 btc",
+            "hold rm",
+            "hold list please",
+            "hold btc",
+        ] {
+            assert_eq!(
+                parse(malformed),
+                Some(Err(USAGE.to_string())),
+                "{malformed}"
+            );
+        }
+
+        assert_eq!(parse("btc"), None);
+        assert_eq!(parse("holdcoin"), None, "a coin");
+    }
+
+    #[test]
+    async fn test_parse_amount() {
+        assert_eq!(parse_amount("0.2"), Some(dec("0.2")));
+        assert_eq!(parse_amount("0,25"), Some(dec("0.25")));
+        assert_eq!(
+            parse_amount("0.123456789012345678"),
+            Some(dec("0.123456789012345678")),
+            "every decimal kept"
+        );
+        assert_eq!(parse_amount("1000000000000001"), None, "too much");
+        assert_eq!(parse_amount("0"), None);
+        assert_eq!(parse_amount("-0.2"), None);
+        assert_eq!(parse_amount("lots"), None);
+    }
+
+    #[test]
+    async fn test_owner() {
+        let msg: Message = "@account=GeekingFrog :Geek!~g@host PRIVMSG #rust :λcrypto hold\r\n"
+            .parse()
+            .unwrap();
+        assert_eq!(Owner::of(&msg), Some(geek()));
+
+        let msg: Message = ":Geek!~g@host PRIVMSG #rust :λcrypto hold\r\n"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            Owner::of(&msg),
+            Some(Owner::Nick("geek".to_string())),
+            "not logged in"
+        );
+    }
+
+    #[test]
+    async fn test_holdings_survive_restart() {
+        let db = Database::in_memory().unwrap();
+        let holdings = Holdings::load(db.clone()).unwrap();
+        let libera = Some("libera");
+        let add = |coin, symbol, amount| {
+            holdings
+                .add(libera, &geek(), coin, symbol, dec(amount))
+                .unwrap()
+        };
+        assert_eq!(add("bitcoin", "btc", "0.2"), Some(dec("0.2")));
+        assert_eq!(add("ethereum", "eth", "1.5"), Some(dec("1.5")));
+        assert_eq!(
+            add("bitcoin", "btc", "0.05"),
+            Some(dec("0.25")),
+            "on top of the previous amount"
+        );
+
+        let holdings = Holdings::load(db.clone()).unwrap();
+        let listed = holdings.list(libera, &geek());
+        assert_eq!(
+            listed
+                .iter()
+                .map(|h| (h.symbol.as_str(), h.amount))
+                .collect::<Vec<_>>(),
+            vec![("BTC", dec("0.25")), ("ETH", dec("1.5"))]
+        );
+        assert_eq!(holdings.list(Some("oftc"), &geek()), vec![]);
+        assert_eq!(
+            holdings.list(libera, &Owner::Nick("geekingfrog".to_string())),
+            vec![],
+            "a nick isn't an account"
+        );
+
+        assert!(!holdings
+            .remove(libera, &Owner::Nick("someone".to_string()), "bitcoin")
+            .unwrap());
+        assert!(holdings.remove(libera, &geek(), "bitcoin").unwrap());
+        assert!(!holdings.remove(libera, &geek(), "bitcoin").unwrap());
+        let holdings = Holdings::load(db).unwrap();
+        assert_eq!(
+            holdings
+                .list(libera, &geek())
+                .iter()
+                .map(|h| h.symbol.as_str())
+                .collect::<Vec<_>>(),
+            vec!["ETH"]
+        );
+    }
+
+    #[test]
+    async fn test_limit() {
+        let holdings = Holdings::load(Database::in_memory().unwrap()).unwrap();
+        for i in 0..MAX_HOLDINGS {
+            let coin = format!("coin-{i}");
+            assert!(holdings
+                .add(None, &geek(), &coin, "c", Decimal::ONE)
+                .unwrap()
+                .is_some());
+        }
+        assert_eq!(
+            holdings
+                .add(None, &geek(), "bitcoin", "btc", Decimal::ONE)
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            holdings
+                .add(None, &geek(), "coin-0", "c", Decimal::ONE)
+                .unwrap(),
+            Some(dec("2")),
+            "more of a coin already held"
+        );
+    }
+
+    #[test]
+    async fn test_valuation() {
+        let holdings = [
+            holding("bitcoin", "BTC", "0.2"),
+            holding("ethereum", "ETH", "2"),
+            holding("dogecoin", "DOGE", "1000"),
+        ];
+        let quotes = HashMap::from([
+            ("bitcoin".to_string(), cached("60000", Some(20.0))),
+            ("ethereum".to_string(), cached("3000", None)),
+        ]);
+        let valuation = Valuation::of(&holdings, &quotes);
+        assert_eq!(
+            valuation,
+            Valuation {
+                total: dec("18000"),
+                previous: dec("16000"),
+                missing: vec!["DOGE".to_string()],
+            }
+        );
+        assert_eq!(valuation.delta(), dec("2000"));
+        assert_eq!(valuation.change_24h(), Some(12.5));
+
+        assert_eq!(Valuation::of(&[], &quotes).change_24h(), None);
+    }
+
+    #[test]
+    async fn test_list_in_private_only() {
+        let holdings = [
+            holding("bitcoin", "BTC", "0.20"),
+            holding("dogecoin", "DOGE", "1000"),
+        ];
+        let quotes = HashMap::from([("bitcoin".to_string(), cached("64230", Some(1.0)))]);
+        assert_eq!(
+            describe_list(true, &holdings, &quotes, Fiat::EUR),
+            "BTC 0.2 (12\u{2009}846 €) | DOGE 1000"
+        );
+        assert_eq!(
+            describe_list(false, &holdings, &quotes, Fiat::EUR),
+            LIST_IN_PRIVATE
+        );
+        assert_eq!(
+            describe_list(false, &[], &quotes, Fiat::EUR),
+            LIST_IN_PRIVATE
+        );
+        assert_eq!(describe_list(true, &[], &quotes, Fiat::EUR), nothing_held());
+    }
+}
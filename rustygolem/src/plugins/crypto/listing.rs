@@ -0,0 +1,278 @@
+use crate::utils::text::distance;
+use anyhow::Context;
+use reqwest::Client;
+use serde::Deserialize;
+
+/// The coingecko listing is paginated, the first pages have the
+/// coins with the biggest market caps
+const LISTING_PAGES: usize = 4;
+const PER_PAGE: usize = 250;
+
+/// How many coins to suggest when the input matches none
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Symbols people use which aren't the ones of coingecko
+const ALIASES: &[(&str, &str)] = &[("xbt", "btc")];
+
+/// Their rates are saved every hour, as (coingecko id, symbol, name)
+pub const TRACKED_COINS: &[(&str, &str, &str)] = &[
+    ("bitcoin", "btc", "Bitcoin"),
+    ("ethereum", "eth", "Ethereum"),
+    ("dogecoin", "doge", "Dogecoin"),
+    ("ripple", "xrp", "XRP"),
+    ("algorand", "algo", "Algorand"),
+];
+
+/// A coin as listed by https://api.coingecko.com/api/v3/coins/markets
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Coin {
+    /// what coingecko uses in the other endpoints, like "bitcoin"
+    pub id: String,
+    /// the ticker, like "btc", not unique
+    pub symbol: String,
+    pub name: String,
+    pub market_cap: Option<f64>,
+}
+
+impl Coin {
+    fn new(id: &str, symbol: &str, name: &str) -> Self {
+        Coin {
+            id: id.to_string(),
+            symbol: symbol.to_string(),
+            name: name.to_string(),
+            market_cap: None,
+        }
+    }
+
+    fn market_cap(&self) -> f64 {
+        self.market_cap.unwrap_or(0.0)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Resolution<'a> {
+    /// `others` coins also matched, with a smaller market cap
+    Found {
+        coin: &'a Coin,
+        others: usize,
+    },
+    Unknown {
+        suggestions: Vec<String>,
+    },
+}
+
+/// An entry of the `aliases` list of the crypto config section, like
+/// `{ alias = "bitcoin", symbol = "btc" }`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Alias {
+    pub alias: String,
+    /// anything a λcrypto command takes, the symbol, name or coingecko id
+    pub symbol: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Listing {
+    coins: Vec<Coin>,
+    /// the configured ones, lowercase
+    aliases: Vec<(String, String)>,
+}
+
+impl Listing {
+    pub fn new(coins: Vec<Coin>) -> Self {
+        Listing {
+            coins,
+            aliases: vec![],
+        }
+    }
+
+    /// Applied before the built-in ALIASES, which they can override
+    pub fn with_aliases(mut self, aliases: &[Alias]) -> Self {
+        self.aliases = aliases
+            .iter()
+            .map(|a| (a.alias.to_lowercase(), a.symbol.to_lowercase()))
+            .collect();
+        self
+    }
+
+    /// The coins always tracked by the plugin, until the full
+    /// listing can be fetched
+    pub fn fallback() -> Self {
+        Listing::new(
+            TRACKED_COINS
+                .iter()
+                .map(|(id, symbol, name)| Coin::new(id, symbol, name))
+                .collect(),
+        )
+    }
+
+    pub async fn fetch(client: &Client) -> anyhow::Result<Self> {
+        let mut coins = Vec::with_capacity(LISTING_PAGES * PER_PAGE);
+        for page in 1..=LISTING_PAGES {
+            let url = format!(
+                "https://api.coingecko.com/api/v3/coins/markets?vs_currency=eur&order=market_cap_desc&per_page={PER_PAGE}&page={page}"
+            );
+            let page: Vec<Coin> = client
+                .get(&url)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await
+                .with_context(|| format!("Cannot parse the coin listing at {url}"))?;
+            coins.extend(page);
+        }
+        log::info!("Fetched a listing of {} coins", coins.len());
+        Ok(Listing::new(coins))
+    }
+
+    /// Case insensitive, on the symbol, the name or the coingecko id.
+    /// When several coins match, the one with the biggest market cap wins.
+    pub fn resolve(&self, input: &str) -> Resolution {
+        let mut input = input.to_lowercase();
+        let configured = self.aliases.iter().map(|(a, s)| (a.as_str(), s.as_str()));
+        if let Some((_, symbol)) = configured
+            .chain(ALIASES.iter().copied())
+            .find(|(alias, _)| *alias == input)
+        {
+            input = symbol.to_string();
+        }
+        let mut matches = self
+            .coins
+            .iter()
+            .filter(|c| {
+                c.symbol.to_lowercase() == input || c.name.to_lowercase() == input || c.id == input
+            })
+            .collect::<Vec<_>>();
+        matches.sort_by(|a, b| b.market_cap().total_cmp(&a.market_cap()));
+        match matches.first() {
+            Some(&coin) => Resolution::Found {
+                coin,
+                others: matches.len() - 1,
+            },
+            None => Resolution::Unknown {
+                suggestions: self.suggest(&input),
+            },
+        }
+    }
+
+    /// The closest symbols or names, favoring the biggest coins
+    fn suggest(&self, input: &str) -> Vec<String> {
+        let max_distance = (input.chars().count() / 2).max(1);
+        let mut candidates = self
+            .coins
+            .iter()
+            .flat_map(|c| [c.symbol.to_lowercase(), c.name.to_lowercase()].map(|k| (k, c)))
+            .map(|(k, c)| (distance(input, &k), k, c))
+            .filter(|(d, _, _)| *d <= max_distance)
+            .collect::<Vec<_>>();
+        candidates.sort_by(|(d1, _, c1), (d2, _, c2)| {
+            d1.cmp(d2).then(c2.market_cap().total_cmp(&c1.market_cap()))
+        });
+        let mut suggestions: Vec<String> = vec![];
+        for (_, k, _) in candidates {
+            if !suggestions.contains(&k) {
+                suggestions.push(k);
+            }
+            if suggestions.len() == MAX_SUGGESTIONS {
+                break;
+            }
+        }
+        suggestions
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Trimmed down from the coingecko response, with duplicate tickers
+    const LISTING: &str = r#"[
+        {"id":"bitcoin","symbol":"btc","name":"Bitcoin","current_price":25000.0,"market_cap":480000000000},
+        {"id":"ethereum","symbol":"eth","name":"Ethereum","current_price":1600.0,"market_cap":195000000000},
+        {"id":"dogecoin","symbol":"doge","name":"Dogecoin","current_price":0.06,"market_cap":8500000000},
+        {"id":"uniswap","symbol":"uni","name":"Uniswap","current_price":4.2,"market_cap":3200000000},
+        {"id":"universe-token","symbol":"UNI","name":"Universe","current_price":0.01,"market_cap":120000},
+        {"id":"unicorn-token","symbol":"uni","name":"Unicorn","current_price":0.001,"market_cap":null},
+        {"id":"bitcoin-bep2","symbol":"btcb","name":"Bitcoin BEP2","current_price":25000.0,"market_cap":1000000}
+    ]"#;
+
+    fn listing() -> Listing {
+        Listing::new(serde_json::from_str(LISTING).unwrap())
+    }
+
+    fn found(resolution: Resolution) -> (&str, usize) {
+        match resolution {
+            Resolution::Found { coin, others } => (coin.id.as_str(), others),
+            Resolution::Unknown { suggestions } => panic!("not found, suggested {suggestions:?}"),
+        }
+    }
+
+    #[test]
+    async fn test_resolve() {
+        let listing = listing();
+        assert_eq!(found(listing.resolve("BTC")), ("bitcoin", 0));
+        assert_eq!(found(listing.resolve("dogecoin")), ("dogecoin", 0));
+        assert_eq!(found(listing.resolve("Bitcoin BEP2")), ("bitcoin-bep2", 0));
+        assert_eq!(
+            found(listing.resolve("uni")),
+            ("uniswap", 2),
+            "the biggest market cap among the shared tickers"
+        );
+    }
+
+    #[test]
+    async fn test_suggestions() {
+        let listing = listing();
+        assert_eq!(
+            listing.resolve("dgoe"),
+            Resolution::Unknown {
+                suggestions: vec!["doge".to_string()]
+            }
+        );
+        assert_eq!(
+            listing.resolve("btcc"),
+            Resolution::Unknown {
+                suggestions: vec!["btc".to_string(), "btcb".to_string()]
+            }
+        );
+        assert_eq!(
+            listing.resolve("unx"),
+            Resolution::Unknown {
+                suggestions: vec!["uni".to_string()]
+            },
+            "a single suggestion for the shared tickers"
+        );
+        assert_eq!(
+            listing.resolve("monero"),
+            Resolution::Unknown {
+                suggestions: vec![]
+            }
+        );
+    }
+
+    #[test]
+    async fn test_aliases() {
+        let alias = |alias: &str, symbol: &str| Alias {
+            alias: alias.to_string(),
+            symbol: symbol.to_string(),
+        };
+        let listing = listing().with_aliases(&[alias("Toutou", "DOGE"), alias("xbt", "btcb")]);
+        assert_eq!(found(listing.resolve("toutou")), ("dogecoin", 0));
+        assert_eq!(found(listing.resolve("TOUTOU")), ("dogecoin", 0));
+        assert_eq!(
+            found(listing.resolve("xbt")),
+            ("bitcoin-bep2", 0),
+            "overrides the built-in aliases"
+        );
+        assert_eq!(found(listing.resolve("btc")), ("bitcoin", 0));
+        assert_eq!(found(listing().resolve("xbt")), ("bitcoin", 0));
+    }
+
+    #[test]
+    async fn test_fallback() {
+        let listing = Listing::fallback();
+        assert_eq!(found(listing.resolve("xbt")), ("bitcoin", 0));
+        assert_eq!(found(listing.resolve("algo")), ("algorand", 0));
+    }
+}
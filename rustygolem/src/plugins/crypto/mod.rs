@@ -1,4 +1,15 @@
 mod plugin;
+mod alerts;
+mod announce;
+mod ath;
+mod chart;
+mod convert;
 mod db;
+mod fiat;
+mod history;
+mod holdings;
+mod listing;
+mod providers;
+mod quotes;
 
 pub use plugin::Crypto;
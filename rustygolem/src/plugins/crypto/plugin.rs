@@ -1,34 +1,139 @@
 use anyhow::Context;
 use async_trait::async_trait;
-use chrono::Utc;
-use diesel::serialize::ToSql;
-use diesel::{backend::Backend, prelude::*, sql_types};
-use diesel::{deserialize::FromSql, sql_types::Text};
-use nom::branch::alt;
-use nom::bytes::complete::tag;
-use nom::character::complete::{multispace0, multispace1};
-use nom::combinator::{all_consuming, map};
-use nom::sequence::{preceded, terminated, tuple};
-use nom::{Finish, IResult};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use nom::Finish;
 use republican_calendar::RepublicanDate;
 use reqwest::Client;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::result::Result as StdResult;
-use std::time::Duration;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tokio::task;
 
+use super::alerts::{self, Added, Alert, AlertCommand, Alerts};
+use super::announce::{self, Announcement, QuoteSource};
+use super::ath::{self, AthCache, Lookup};
+use super::chart;
+use super::convert::{self, Conversion, Unit};
 use super::db;
+use super::fiat::{self, ChannelFiat, Fiat, Fiats};
+use super::history::{self, Window};
+use super::holdings::{self, HoldCommand, Holding, Holdings, Owner, Valuation};
+use super::listing::{Alias, Coin, Listing, Resolution, TRACKED_COINS};
+use super::providers::{
+    self, CoinRef, Fetched, FetchedQuote, PricePoint, ProviderSettings, Providers,
+};
+use super::quotes::{self, Cached, QuoteCache};
 use crate::schema::crypto_rate::{self, dsl};
-use crate::utils::parser::{self, command_prefix};
 use irc::proto::{Command, Message};
-use plugin_core::{Error, Initialised, Plugin, Result};
+use plugin_core::utils::network::{network, set_network};
+use plugin_core::utils::private::is_private;
+use plugin_core::{
+    parse, BackgroundTask, Error, Initialised, Outbound, Plugin, Requirement, Restart, Result,
+};
 
-pub struct Crypto {}
+/// How often to fetch the list of all the coins
+const LISTING_REFRESH: Duration = Duration::from_secs(24 * 60 * 60);
+/// When the listing cannot be fetched
+const LISTING_RETRY: Duration = Duration::from_secs(10 * 60);
+/// More would not fit in a line
+const MAX_WATCHLIST: usize = 4;
+
+/// The `crypto` section of the golem config
+#[derive(Default, Deserialize)]
+struct Settings {
+    /// green and red 24h changes, with mIRC color codes
+    #[serde(default)]
+    use_colors: bool,
+    /// seconds during which the quotes are answered from the cache
+    quote_ttl: Option<u64>,
+    /// calls to the API on behalf of the users
+    api_calls_per_minute: Option<u32>,
+    /// tried in order until one answers, providers::DEFAULT_PROVIDERS by default
+    providers: Option<Vec<ProviderSettings>>,
+    /// a coin whose price wasn't updated for that long has no active market
+    inactive_after_hours: Option<u64>,
+    /// ISO code of the currency to quote in, EUR by default. The alerts,
+    /// the holdings and the recorded rates are in this one.
+    default_fiat: Option<String>,
+    /// the channels quoting in another currency
+    #[serde(default)]
+    channel_fiats: Vec<ChannelFiat>,
+    /// quotes posted on a schedule
+    #[serde(default)]
+    announcements: Vec<Announcement>,
+    /// other names for the coins, on top of the symbols, names and ids
+    #[serde(default)]
+    aliases: Vec<Alias>,
+    /// the coins quoted by a bare λcrypto
+    #[serde(default)]
+    default_watchlist: Vec<String>,
+    /// the all-time high after every quote
+    #[serde(default)]
+    include_ath: bool,
+}
+
+impl Settings {
+    fn load(config: &plugin_core::Config) -> Result<Self> {
+        let settings: Settings = config.plugin_section("crypto")?.unwrap_or_default();
+        if settings.api_calls_per_minute == Some(0) {
+            return Err(anyhow!("crypto.api_calls_per_minute must be at least 1").into());
+        }
+        if settings.inactive_after_hours == Some(0) {
+            return Err(anyhow!("crypto.inactive_after_hours must be at least 1").into());
+        }
+        settings.fiats()?;
+        // the providers need a client, only to validate their names here
+        providers::build(&config.http_client(), settings.providers.as_deref())?;
+        for announcement in &settings.announcements {
+            announcement.check()?;
+        }
+        if settings.default_watchlist.len() > MAX_WATCHLIST {
+            return Err(
+                anyhow!("crypto.default_watchlist can have {MAX_WATCHLIST} coins at most").into(),
+            );
+        }
+        Ok(settings)
+    }
+
+    fn fiats(&self) -> anyhow::Result<Fiats> {
+        Fiats::new(self.default_fiat.as_deref(), &self.channel_fiats).context("Invalid crypto fiat")
+    }
+}
+
+pub struct Crypto {
+    client: Client,
+    /// only the tracked coins until the first fetch
+    listing: Arc<RwLock<Listing>>,
+    /// applied to every fresh listing
+    aliases: Vec<Alias>,
+    default_watchlist: Vec<String>,
+    use_colors: bool,
+    fiats: Fiats,
+    include_ath: bool,
+    aths: AthCache,
+    alerts: Alerts,
+    holdings: Holdings,
+    quotes: QuoteCache,
+    providers: Providers,
+    announcements: Vec<Announcement>,
+}
 
 #[async_trait]
 impl Plugin for Crypto {
-    async fn init(_config: &plugin_core::Config) -> Result<Initialised> {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        Settings::load(config)?;
+        config.check_database("crypto")?;
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let settings = Settings::load(config)?;
         let _db_conn: Result<_> = tokio::task::spawn_blocking(|| {
             let conn = db::establish_connection()?;
             db::run_migrations(&conn)?;
@@ -40,259 +145,807 @@ impl Plugin for Crypto {
             e
         })?;
 
-        Ok(Initialised::from(Crypto {}))
+        let db = config.require_database("crypto")?;
+        let client = config.http_client();
+        let providers = Providers::new(
+            providers::build(&client, settings.providers.as_deref())?,
+            settings
+                .inactive_after_hours
+                .map_or(providers::DEFAULT_INACTIVE_AFTER, |hours| {
+                    Duration::from_secs(hours * 60 * 60)
+                }),
+        );
+        let crypto = Crypto {
+            client,
+            listing: Arc::new(RwLock::new(
+                Listing::fallback().with_aliases(&settings.aliases),
+            )),
+            aliases: settings.aliases,
+            default_watchlist: settings.default_watchlist,
+            use_colors: settings.use_colors,
+            fiats: settings.fiats()?,
+            include_ath: settings.include_ath,
+            aths: AthCache::default(),
+            alerts: Alerts::load(db.clone())?,
+            holdings: Holdings::load(db)?,
+            quotes: QuoteCache::new(
+                settings
+                    .quote_ttl
+                    .map_or(quotes::DEFAULT_TTL, Duration::from_secs),
+                settings
+                    .api_calls_per_minute
+                    .unwrap_or(quotes::DEFAULT_CALLS_PER_MINUTE),
+            ),
+            providers,
+            announcements: settings.announcements,
+        };
+        let listing_refresh = crypto.listing_refresh_task();
+        Ok(Initialised {
+            tasks: vec![listing_refresh],
+            ..Initialised::from(crypto)
+        })
     }
 
     fn get_name(&self) -> &'static str {
         "crypto"
     }
 
-    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
-        in_msg(msg).await
+    async fn in_message(&self, msg: &Message) -> Result<Option<Outbound>> {
+        self.in_msg(msg).await
     }
 
-    async fn run(&self, _bot_chan: mpsc::Sender<Message>) -> Result<()> {
-        monitor_crypto_coins().await?;
+    async fn run(&self, bot_chan: mpsc::Sender<Outbound>) -> Result<()> {
+        tokio::try_join!(
+            monitor_crypto_coins(
+                &self.providers,
+                &self.alerts,
+                &self.quotes,
+                self.fiats.default(),
+                &bot_chan
+            ),
+            announce::run(&self.announcements, self, &bot_chan),
+        )?;
         Err(Error::Synthetic(
             "crypto coin monitoring job stopped".to_string(),
         ))
     }
+
+    fn requirements(&self) -> Vec<Requirement> {
+        // to persist the alerts and the holdings
+        vec![Requirement::Database]
+    }
 }
 
-async fn in_msg(msg: &Message) -> Result<Option<Message>> {
-    let response_target = match msg.response_target() {
-        None => return Ok(None),
-        Some(target) => target.to_string(),
-    };
+impl Crypto {
+    async fn in_msg(&self, msg: &Message) -> Result<Option<Outbound>> {
+        let response_target = match msg.response_target() {
+            None => return Ok(None),
+            Some(target) => target.to_string(),
+        };
 
-    if let Command::PRIVMSG(_source, message) = &msg.command {
-        let (mb_coin, mb_target) = match parse_command(message) {
-            Ok(x) => x,
-            Err(_) => return Ok(None),
+        if let Command::PRIVMSG(_source, message) = &msg.command {
+            if let Some(command) = alerts::parse_command(message) {
+                let reply = match command {
+                    Ok(command) => self.alert_command(msg, &response_target, command).await?,
+                    Err(usage) => usage,
+                };
+                return Ok(Some(Outbound::reply(response_target, reply)));
+            }
+            let (input, mb_target) = match parse_command(message) {
+                Ok(x) => x,
+                Err(_) => return Ok(None),
+            };
+            if let Some(command) = holdings::parse(input) {
+                // never to someone else, the holdings are personal
+                let reply = match command {
+                    Ok(command) => self.hold_command(msg, command).await?,
+                    Err(usage) => usage,
+                };
+                return Ok(Some(Outbound::reply(response_target, reply)));
+            }
+            let channel = (!is_private(msg)).then(|| response_target.as_str());
+            if input.is_empty() {
+                if self.default_watchlist.is_empty() {
+                    return Ok(None);
+                }
+                let msg = self.watchlist(self.fiats.resolve(None, channel)).await;
+                let full_msg = crate::utils::messages::with_target(&msg, &mb_target);
+                return Ok(Some(Outbound::reply(response_target, full_msg)));
+            }
+            if let Some(conversion) = convert::parse(input) {
+                let msg = match conversion {
+                    Ok(conversion) => {
+                        self.convert(conversion, self.fiats.resolve(None, channel))
+                            .await?
+                    }
+                    Err(msg) => msg,
+                };
+                let full_msg = crate::utils::messages::with_target(&msg, &mb_target);
+                return Ok(Some(Outbound::reply(response_target, full_msg)));
+            }
+            if let Some(query) = ath::parse(input) {
+                let msg = match query {
+                    Ok(coin) => {
+                        let (coin, inline) = fiat::split_inline(coin);
+                        self.ath(coin, self.fiats.resolve(inline, channel)).await
+                    }
+                    Err(usage) => usage,
+                };
+                let full_msg = crate::utils::messages::with_target(&msg, &mb_target);
+                return Ok(Some(Outbound::reply(response_target, full_msg)));
+            }
+            if let Some(query) = chart::parse(input) {
+                let msg = match query {
+                    Ok((coin, window)) => {
+                        let (coin, inline) = fiat::split_inline(coin);
+                        self.chart(coin, window, self.fiats.resolve(inline, channel))
+                            .await
+                    }
+                    Err(usage) => usage,
+                };
+                let full_msg = crate::utils::messages::with_target(&msg, &mb_target);
+                return Ok(Some(Outbound::reply(response_target, full_msg)));
+            }
+            if let Some(query) = history::parse(input) {
+                let msg = match query {
+                    Ok((coin, window)) => {
+                        let (coin, inline) = fiat::split_inline(coin);
+                        self.history(coin, window, self.fiats.resolve(inline, channel))
+                            .await
+                    }
+                    Err(usage) => usage,
+                };
+                let full_msg = crate::utils::messages::with_target(&msg, &mb_target);
+                return Ok(Some(Outbound::reply(response_target, full_msg)));
+            }
+            let (input, inline) = fiat::split_inline(input);
+            let fiat = self.fiats.resolve(inline, channel);
+            // resolved before any await, the listing lock cannot be held across one
+            let resolved = match self.listing.read().expect("listing lock").resolve(input) {
+                Resolution::Found { coin, others } => Ok((coin.clone(), others)),
+                Resolution::Unknown { suggestions } => Err(unknown_coin(input, &suggestions)),
+            };
+            let msg = match resolved {
+                Ok((coin, others)) => {
+                    let cached = self
+                        .quotes(&[CoinRef::from(&coin)], fiat)
+                        .await
+                        .remove(&coin.id);
+                    match cached {
+                        Some(cached) => {
+                            let ath = match self.include_ath {
+                                true => self.ath_note(&coin, cached.quote.price, fiat).await,
+                                false => None,
+                            };
+                            let recorded = fiat == self.fiats.default();
+                            let quote = get_rate_and_history(
+                                coin,
+                                others,
+                                cached,
+                                fiat,
+                                recorded,
+                                self.use_colors,
+                            )
+                            .await?;
+                            match ath {
+                                Some(ath) => format!("{quote} — {ath}"),
+                                None => quote,
+                            }
+                        }
+                        None => self.no_quote_for(&coin),
+                    }
+                }
+                Err(msg) => msg,
+            };
+            let full_msg = crate::utils::messages::with_target(&msg, &mb_target);
+            return Ok(Some(Outbound::reply(response_target, full_msg)));
+        }
+        Ok(None)
+    }
+
+    async fn alert_command(
+        &self,
+        msg: &Message,
+        response_target: &str,
+        command: AlertCommand<'_>,
+    ) -> Result<String> {
+        let nick = match msg.source_nickname() {
+            Some(nick) => nick,
+            None => return Ok("Qui êtes-vous ?".to_string()),
         };
-        let msg = match mb_coin {
-            Ok(coin) => get_rate_and_history(coin).await?,
-            Err(x) => {
-                format!("Dénomination inconnue: {}. Ici on ne deal qu'avec des monnais vaguement respectueuses comme btc (aka xbt), eth, doge, xrp et algo.", x)
+        let network = network(msg);
+        let fiat = self.fiats.default();
+        match command {
+            AlertCommand::Add {
+                coin,
+                direction,
+                threshold,
+            } => {
+                let resolved = match self.listing.read().expect("listing lock").resolve(coin) {
+                    Resolution::Found { coin, .. } => Ok(coin.clone()),
+                    Resolution::Unknown { suggestions } => Err(unknown_coin(coin, &suggestions)),
+                };
+                let coin = match resolved {
+                    Ok(coin) => coin,
+                    Err(reply) => return Ok(reply),
+                };
+                let price = match self
+                    .quotes(&[CoinRef::from(&coin)], fiat)
+                    .await
+                    .remove(&coin.id)
+                {
+                    Some(cached) => cached.quote.price.to_f64().unwrap_or_default(),
+                    None => return Ok(self.no_quote_for(&coin)),
+                };
+                let alert = Alert::new(
+                    nick,
+                    network,
+                    response_target,
+                    &coin.id,
+                    &coin.symbol,
+                    direction,
+                    threshold,
+                );
+                let description = alert.describe(fiat);
+                Ok(match self.alerts.add(alert, price)? {
+                    Added::Alert(alert) => format!(
+                        "Alerte #{} posée: {description} (actuellement {})",
+                        alert.id,
+                        fiat.format_f64(price)
+                    ),
+                    Added::TooMany => format!(
+                        "Vous avez déjà {} alertes, supprimez-en une avec λcrypto alert rm <id>",
+                        alerts::MAX_ALERTS_PER_NICK
+                    ),
+                    Added::AlreadyPast => format!(
+                        "Déjà atteint: {description} (actuellement {})",
+                        fiat.format_f64(price)
+                    ),
+                })
             }
+            AlertCommand::List => {
+                let alerts = self.alerts.list(network, nick);
+                if alerts.is_empty() {
+                    return Ok("Vous n'avez aucune alerte".to_string());
+                }
+                Ok(alerts
+                    .iter()
+                    .map(|a| format!("#{} {}", a.id, a.describe(fiat)))
+                    .collect::<Vec<_>>()
+                    .join(", "))
+            }
+            AlertCommand::Remove(id) => Ok(if self.alerts.remove(network, nick, id)? {
+                format!("Alerte #{id} supprimée")
+            } else {
+                format!("Vous n'avez pas d'alerte #{id}")
+            }),
+        }
+    }
+
+    async fn hold_command(&self, msg: &Message, command: HoldCommand<'_>) -> Result<String> {
+        let owner = match Owner::of(msg) {
+            Some(owner) => owner,
+            None => return Ok("Who are you?".to_string()),
         };
-        let full_msg = crate::utils::messages::with_target(&msg, &mb_target);
-        let irc_message = Command::PRIVMSG(response_target, full_msg).into();
-        return Ok(Some(irc_message));
+        let network = network(msg);
+        match command {
+            HoldCommand::Add { amount, coin } => {
+                let coin = match self.resolve(coin) {
+                    Ok(coin) => coin,
+                    Err(reply) => return Ok(reply),
+                };
+                let symbol = coin.symbol.to_uppercase();
+                let added = self
+                    .holdings
+                    .add(network, &owner, &coin.id, &coin.symbol, amount)?;
+                Ok(match added {
+                    // the total only in private, the amount added was given anyway
+                    Some(total) if is_private(msg) => {
+                        format!("You now hold {} {symbol}", total.normalize())
+                    }
+                    Some(_) => format!("Added {} {symbol} to your holdings", amount.normalize()),
+                    None => format!(
+                        "You already hold {} coins, remove one with λcrypto hold rm <coin>",
+                        holdings::MAX_HOLDINGS
+                    ),
+                })
+            }
+            HoldCommand::Remove(coin) => {
+                let coin = match self.resolve(coin) {
+                    Ok(coin) => coin,
+                    Err(reply) => return Ok(reply),
+                };
+                let symbol = coin.symbol.to_uppercase();
+                Ok(match self.holdings.remove(network, &owner, &coin.id)? {
+                    true => format!("Removed {symbol} from your holdings"),
+                    false => format!("You don't hold any {symbol}"),
+                })
+            }
+            HoldCommand::List => {
+                let private = is_private(msg);
+                let held = self.holdings.list(network, &owner);
+                // no need for any quote to give the hint
+                let quotes = match private {
+                    true => self.held_quotes(&held).await,
+                    false => HashMap::new(),
+                };
+                Ok(holdings::describe_list(
+                    private,
+                    &held,
+                    &quotes,
+                    self.fiats.default(),
+                ))
+            }
+            HoldCommand::Total => {
+                let held = self.holdings.list(network, &owner);
+                if held.is_empty() {
+                    return Ok(holdings::nothing_held());
+                }
+                let quotes = self.held_quotes(&held).await;
+                Ok(describe_total(
+                    &Valuation::of(&held, &quotes),
+                    self.fiats.default(),
+                    self.use_colors,
+                ))
+            }
+        }
     }
-    Ok(None)
-}
 
-fn parse_command(input: &str) -> StdResult<(StdResult<CryptoCoin, &str>, Option<&str>), String> {
-    all_consuming(terminated(parse_crypto, multispace0))(input)
-        .finish()
-        .map(|x| x.1)
-        .map_err(|e| format!("{:?}", e))
-}
+    /// In the default fiat
+    async fn held_quotes(&self, held: &[Holding]) -> HashMap<String, Cached> {
+        if held.is_empty() {
+            return HashMap::new();
+        }
+        let coins = held
+            .iter()
+            .map(|holding| CoinRef {
+                id: &holding.coin,
+                symbol: &holding.symbol,
+            })
+            .collect::<Vec<_>>();
+        self.quotes(&coins, self.fiats.default()).await
+    }
 
-fn parse_crypto(input: &str) -> IResult<&str, (StdResult<CryptoCoin, &str>, Option<&str>)> {
-    preceded(
-        command_prefix,
-        map(
-            parser::with_target(tuple((tag("crypto"), multispace1, crypto_cmd))),
-            |((_, _, c), t)| (c, t),
-        ),
-    )(input)
-}
+    /// To `fiat` when the conversion doesn't say
+    async fn convert(&self, conversion: Conversion<'_>, fiat: Fiat) -> Result<String> {
+        let to = match conversion.to {
+            Some(to) => self.unit(to),
+            None => Ok(Unit::Fiat(fiat)),
+        };
+        let (from, to) = match (self.unit(conversion.from), to) {
+            (Ok(from), Ok(to)) => (from, to),
+            (Err(msg), _) | (_, Err(msg)) => return Ok(msg),
+        };
+        let coins = [&from, &to]
+            .into_iter()
+            .filter_map(Unit::coin)
+            .collect::<Vec<_>>();
+        let quotes = self
+            .quotes(&coins, convert::quoted_in(&from, &to, fiat))
+            .await;
+        Ok(
+            match convert::convert(conversion.amount, &from, &to, &quotes, Instant::now()) {
+                Ok(msg) | Err(msg) => msg,
+            },
+        )
+    }
 
-fn crypto_cmd(input: &str) -> IResult<&str, StdResult<CryptoCoin, &str>> {
-    alt((
-        map(tag("xbt"), |_| Ok(CryptoCoin::Bitcoin)),
-        map(tag("btc"), |_| Ok(CryptoCoin::Bitcoin)),
-        map(tag("eth"), |_| Ok(CryptoCoin::Ethereum)),
-        map(tag("doge"), |_| Ok(CryptoCoin::Doge)),
-        map(tag("xrp"), |_| Ok(CryptoCoin::Ripple)),
-        map(tag("algo"), |_| Ok(CryptoCoin::Algorand)),
-        map(parser::word, Err),
-    ))(input)
-}
+    /// The price now, and at the start of the window
+    async fn history(&self, input: &str, window: Window, fiat: Fiat) -> String {
+        let coin = match self.resolve(input) {
+            Ok(coin) => coin,
+            Err(reply) => return reply,
+        };
+        let price = match self
+            .quotes(&[CoinRef::from(&coin)], fiat)
+            .await
+            .remove(&coin.id)
+        {
+            Some(cached) => cached.quote.price,
+            None => return self.no_quote_for(&coin),
+        };
+        let points = match self.price_history(&coin, window, fiat).await {
+            Some(points) => points,
+            None => return self.no_quote_for(&coin),
+        };
+        let now = Utc::now();
+        match history::closest(&points, history::start(window, now)) {
+            Some(past) => history::describe(&coin.symbol, price, window, past, fiat, now),
+            None => self.no_quote_for(&coin),
+        }
+    }
 
-#[derive(Debug, FromSqlRow, AsExpression, PartialEq, Clone, Copy)]
-#[sql_type = "Text"]
-enum CryptoCoin {
-    Bitcoin,
-    Ethereum,
-    Doge,
-    Ripple,
-    Algorand,
-}
+    /// The quotes of the default watchlist, in a line
+    async fn watchlist(&self, fiat: Fiat) -> String {
+        let quotes = self
+            .quotes_by_input(Caller::User, &self.default_watchlist, fiat)
+            .await;
+        announce::ticker(&self.default_watchlist, &quotes, fiat)
+            .unwrap_or_else(|| "No fresh quotes right now, try again in a minute".to_string())
+    }
 
-impl<DB> FromSql<sql_types::Text, DB> for CryptoCoin
-where
-    DB: Backend,
-    String: FromSql<sql_types::Text, DB>,
-{
-    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
-        match &(String::from_sql(bytes)?)[..] {
-            "BTC" => Ok(CryptoCoin::Bitcoin),
-            "ETH" => Ok(CryptoCoin::Ethereum),
-            "DOGE" => Ok(CryptoCoin::Doge),
-            "XRP" => Ok(CryptoCoin::Ripple),
-            "ALGO" => Ok(CryptoCoin::Algorand),
-            x => Err(format!("Unknown denomination: {}", x).into()),
+    /// The price now, and how far from its all-time high
+    async fn ath(&self, input: &str, fiat: Fiat) -> String {
+        let coin = match self.resolve(input) {
+            Ok(coin) => coin,
+            Err(reply) => return reply,
+        };
+        let live = match self
+            .quotes(&[CoinRef::from(&coin)], fiat)
+            .await
+            .remove(&coin.id)
+        {
+            Some(cached) => cached.quote.price,
+            None => return self.no_quote_for(&coin),
+        };
+        let symbol = coin.symbol.to_uppercase();
+        match self.ath_note(&coin, live, fiat).await {
+            Some(note) => format!("{symbol}: {} — {note}", fiat.format(live)),
+            None => format!("No all-time high for {symbol} right now, try again in a minute"),
         }
     }
-}
 
-impl<DB> ToSql<sql_types::Text, DB> for CryptoCoin
-where
-    DB: Backend,
-{
-    fn to_sql<W: std::io::Write>(
+    /// Like `ATH 73 750 € (2024-03-14), −12.9% from ATH`, from the cache
+    /// unless it's missing and the API budget allows fetching it
+    async fn ath_note(&self, coin: &Coin, live: Decimal, fiat: Fiat) -> Option<String> {
+        let lookup = match self.aths.lookup_at(&coin.id, fiat, live, Instant::now()) {
+            Lookup::Missing => {
+                if !self.quotes.spend_at(Instant::now()) {
+                    log::info!("No API budget left for the all-time high of {}", coin.id);
+                    return None;
+                }
+                let high = match self
+                    .providers
+                    .all_time_high(CoinRef::from(coin), fiat.code())
+                    .await
+                {
+                    Ok(high) => high,
+                    Err(err) => {
+                        log::warn!("Cannot fetch the all-time high of {}: {err:#}", coin.id);
+                        return None;
+                    }
+                };
+                self.aths.record_at(&coin.id, fiat, high, Instant::now());
+                self.aths.lookup_at(&coin.id, fiat, live, Instant::now())
+            }
+            lookup => lookup,
+        };
+        ath::describe(lookup, live, fiat)
+    }
+
+    /// A sparkline of the prices over the window
+    async fn chart(&self, input: &str, window: Window, fiat: Fiat) -> String {
+        let coin = match self.resolve(input) {
+            Ok(coin) => coin,
+            Err(reply) => return reply,
+        };
+        let points = match self.price_history(&coin, window, fiat).await {
+            Some(points) => points,
+            None => return self.no_quote_for(&coin),
+        };
+        chart::describe(&coin.symbol, &points, window, fiat, Utc::now())
+            .unwrap_or_else(|| self.no_quote_for(&coin))
+    }
+
+    /// The coin from the listing, or what to reply
+    fn resolve(&self, input: &str) -> StdResult<Coin, String> {
+        match self.listing.read().expect("listing lock").resolve(input) {
+            Resolution::Found { coin, .. } => Ok(coin.clone()),
+            Resolution::Unknown { suggestions } => Err(unknown_coin(input, &suggestions)),
+        }
+    }
+
+    /// What to reply without a quote for the coin
+    fn no_quote_for(&self, coin: &Coin) -> String {
+        match self.quotes.inactive_at(&coin.id, Instant::now()) {
+            Some(inactive) => no_market(&coin.symbol, inactive.last_trade),
+            None => no_quote(&coin.symbol),
+        }
+    }
+
+    /// The prices over the window, if the API budget allows asking for them
+    async fn price_history(
         &self,
-        out: &mut diesel::serialize::Output<W, DB>,
-    ) -> diesel::serialize::Result {
-        let tag = match self {
-            CryptoCoin::Bitcoin => "BTC",
-            CryptoCoin::Ethereum => "ETH",
-            CryptoCoin::Doge => "DOGE",
-            CryptoCoin::Ripple => "XRP",
-            CryptoCoin::Algorand => "ALGO",
+        coin: &Coin,
+        window: Window,
+        fiat: Fiat,
+    ) -> Option<Vec<PricePoint>> {
+        if !self.quotes.spend_at(Instant::now()) {
+            log::info!("No API budget left for the history of {}", coin.id);
+            return None;
+        }
+        match self
+            .providers
+            .history(CoinRef::from(coin), fiat.code(), window.days())
+            .await
+        {
+            Ok(points) => Some(points),
+            Err(err) => {
+                log::warn!("Cannot fetch the history of {}: {err:#}", coin.id);
+                None
+            }
+        }
+    }
+
+    /// The quotes of the given coins in `fiat`, from the cache unless
+    /// they're stale and the API budget allows fetching them again. The coins
+    /// without any quote to answer with are missing.
+    async fn quotes(&self, coins: &[CoinRef<'_>], fiat: Fiat) -> HashMap<String, Cached> {
+        self.quotes_for(Caller::User, coins, fiat).await
+    }
+
+    async fn quotes_for(
+        &self,
+        caller: Caller,
+        coins: &[CoinRef<'_>],
+        fiat: Fiat,
+    ) -> HashMap<String, Cached> {
+        let ids = coins.iter().map(|c| c.id).collect::<Vec<_>>();
+        let now = Instant::now();
+        let cached = self.quotes.lookup_at(&ids, fiat, now);
+        let mut needed = quotes::needs_fetch(&ids, &cached);
+        needed.retain(|id| !self.quotes.failed_at(id, fiat, now));
+        if needed.is_empty() {
+            return cached;
+        }
+        if caller == Caller::User && !self.quotes.spend_at(now) {
+            log::info!("No API budget left to fetch {needed:?}, answering from the cache");
+            return cached;
+        }
+        let needed = coins
+            .iter()
+            .filter(|c| needed.contains(&c.id))
+            .copied()
+            .collect::<Vec<_>>();
+        let rates = match self.providers.fetch(&needed, fiat.code()).await {
+            Ok(rates) => rates,
+            Err(err) => {
+                log::warn!("Cannot fetch the rates of {needed:?}: {err:#}");
+                let ids = needed.iter().map(|c| c.id).collect::<Vec<_>>();
+                self.quotes.record_failed_at(&ids, fiat, Instant::now());
+                return cached;
+            }
         };
-        ToSql::<sql_types::Text, DB>::to_sql(tag, out)
+        let unknown = needed
+            .iter()
+            .map(|c| c.id)
+            .filter(|id| !rates.quotes.iter().any(|q| q.id == *id))
+            .filter(|id| !rates.inactive.iter().any(|i| i.id == *id))
+            .collect::<Vec<_>>();
+        self.quotes.record_failed_at(&unknown, fiat, Instant::now());
+        let recorded = fiat == self.fiats.default();
+        record_rates(&self.quotes, &rates, fiat, recorded).await;
+        self.quotes.lookup_at(&ids, fiat, Instant::now())
     }
-}
 
-impl std::fmt::Display for CryptoCoin {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            CryptoCoin::Bitcoin => f.write_str("bitcoin"),
-            CryptoCoin::Ethereum => f.write_str("ethereum"),
-            CryptoCoin::Doge => f.write_str("dogecoin"),
-            CryptoCoin::Ripple => f.write_str("ripple"),
-            CryptoCoin::Algorand => f.write_str("algorand"),
+    /// A fiat, or a coin from the listing
+    fn unit(&self, input: &str) -> StdResult<Unit, String> {
+        if let Some(fiat) = convert::parse_fiat(input) {
+            return Ok(Unit::Fiat(fiat));
+        }
+        match self.listing.read().expect("listing lock").resolve(input) {
+            Resolution::Found { coin, .. } => Ok(Unit::Coin {
+                id: coin.id.clone(),
+                symbol: coin.symbol.clone(),
+            }),
+            Resolution::Unknown { suggestions } => Err(unknown_coin(input, &suggestions)),
         }
     }
-}
 
-// a bit tedious to map a rust struct from json
-// which doesn't immediately reflect the structure.
-// So use tmp structs and the serde_derive feature
-#[derive(Debug, Deserialize, PartialEq)]
-struct CryptowatchResponse {
-    result: CryptowatchResponseResult,
-    allowance: CryptowatchResponseAllowance,
+    /// Fetches the listing right away, then every day
+    fn listing_refresh_task(&self) -> BackgroundTask {
+        let client = self.client.clone();
+        let listing = Arc::clone(&self.listing);
+        let aliases = self.aliases.clone();
+        BackgroundTask::new("listing_refresh", move || {
+            let client = client.clone();
+            let listing = Arc::clone(&listing);
+            let aliases = aliases.clone();
+            async move {
+                let delay = match Listing::fetch(&client).await {
+                    Ok(fresh) => {
+                        *listing.write().expect("listing lock") = fresh.with_aliases(&aliases);
+                        LISTING_REFRESH
+                    }
+                    Err(err) => {
+                        log::warn!("Cannot fetch the coin listing: {err:#}");
+                        LISTING_RETRY
+                    }
+                };
+                tokio::time::sleep(delay).await;
+                Ok(())
+            }
+        })
+        .restart(Restart::Always {
+            delay: Duration::ZERO,
+        })
+    }
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-struct CryptowatchResponseResult {
-    price: f32,
+/// Only the users' calls count in the API budget
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Caller {
+    User,
+    Scheduled,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-struct CryptowatchResponseAllowance {
-    cost: f32,
-    remaining: f32,
+#[async_trait]
+impl QuoteSource for Crypto {
+    async fn quotes_of(&self, coins: &[String], fiat: Fiat) -> HashMap<String, Cached> {
+        self.quotes_by_input(Caller::Scheduled, coins, fiat).await
+    }
+
+    fn fiat_of(&self, channel: &str) -> Fiat {
+        self.fiats.resolve(None, Some(channel))
+    }
 }
 
-impl CryptoCoin {
-    async fn get_rate_in_euro(&self, http_client: &Client) -> anyhow::Result<f32> {
-        let symbol = match &self {
-            CryptoCoin::Bitcoin => "btc",
-            CryptoCoin::Ethereum => "eth",
-            CryptoCoin::Doge => "doge",
-            CryptoCoin::Ripple => "xrp",
-            CryptoCoin::Algorand => "algo",
-        };
-        let exchange = match &self {
-            CryptoCoin::Bitcoin => "bitstamp",
-            CryptoCoin::Ethereum => "bitstamp",
-            CryptoCoin::Doge => "bittrex",
-            CryptoCoin::Ripple => "bittrex",
-            CryptoCoin::Algorand => "coinbase-pro",
+impl Crypto {
+    /// By coin as typed, like "btc", without the unknown ones
+    async fn quotes_by_input(
+        &self,
+        caller: Caller,
+        coins: &[String],
+        fiat: Fiat,
+    ) -> HashMap<String, Cached> {
+        let resolved = {
+            let listing = self.listing.read().expect("listing lock");
+            coins
+                .iter()
+                .filter_map(|input| match listing.resolve(input) {
+                    Resolution::Found { coin, .. } => Some((input, coin.clone())),
+                    Resolution::Unknown { .. } => {
+                        log::warn!("Cannot quote the unknown coin {input}");
+                        None
+                    }
+                })
+                .collect::<Vec<_>>()
         };
-        let url = format!(
-            "https://api.cryptowat.ch/markets/{}/{}eur/price",
-            exchange, symbol
-        );
+        let refs = resolved
+            .iter()
+            .map(|(_, coin)| CoinRef::from(coin))
+            .collect::<Vec<_>>();
+        let quotes = self.quotes_for(caller, &refs, fiat).await;
+        resolved
+            .into_iter()
+            .filter_map(|(input, coin)| Some((input.clone(), *quotes.get(&coin.id)?)))
+            .collect()
+    }
+}
 
-        let json_resp = http_client
-            .get(&url)
-            .send()
-            .await?
-            .json::<CryptowatchResponse>()
-            .await
-            .context(format!("Error while fetching response from {}", url))?;
-
-        log::info!(
-            "Got price for {} at {} cost {} remaining allowance {}",
-            &self,
-            json_resp.result.price,
-            json_resp.allowance.cost,
-            json_resp.allowance.remaining
-        );
-        Ok(json_resp.result.price)
+/// Like `No active market data for LUNA (last trade 2023-06-01)`
+fn no_market(symbol: &str, last_trade: Option<DateTime<Utc>>) -> String {
+    let last_trade = match last_trade {
+        Some(at) => format!(" (last trade {})", at.format("%Y-%m-%d")),
+        None => "".to_string(),
+    };
+    format!(
+        "No active market data for {}{last_trade}",
+        symbol.to_uppercase()
+    )
+}
+
+fn no_quote(symbol: &str) -> String {
+    format!(
+        "No quote for {} right now, try again in a minute",
+        symbol.to_uppercase()
+    )
+}
+
+fn unknown_coin(input: &str, suggestions: &[String]) -> String {
+    match suggestions {
+        [] => format!("Dénomination inconnue: {input}."),
+        _ => format!(
+            "Dénomination inconnue: {input}. Vous vouliez dire {} ?",
+            suggestions.join(", ")
+        ),
     }
 }
 
+/// The coin asked for, free form, and the target. Empty for the watchlist.
+fn parse_command(input: &str) -> StdResult<(&str, Option<&str>), String> {
+    let (_, (args, mb_target)) = parse::command("crypto")(input)
+        .finish()
+        .map_err(|e| format!("{:?}", e))?;
+    Ok((args, mb_target))
+}
+
 #[derive(Debug, Queryable, Insertable)]
 #[table_name = "crypto_rate"]
 struct CryptoCoinRate {
     date: chrono::NaiveDateTime,
-    coin: CryptoCoin,
+    /// coingecko id
+    coin: String,
     rate: f32,
 }
 
-/// fetch, and save all crypto rates every minute
-async fn monitor_crypto_coins() -> anyhow::Result<()> {
+/// fetch, and save the rates of the tracked coins every hour in the default
+/// fiat, sending the alerts triggered by the new rates
+async fn monitor_crypto_coins(
+    providers: &Providers,
+    alerts: &Alerts,
+    quotes: &QuoteCache,
+    fiat: Fiat,
+    bot_chan: &mpsc::Sender<Outbound>,
+) -> anyhow::Result<()> {
     loop {
-        get_and_save_all_rates().await?;
+        let mut coins = TRACKED_COINS
+            .iter()
+            .map(|(id, symbol, _)| (id.to_string(), symbol.to_string()))
+            .chain(alerts.coins())
+            .collect::<Vec<_>>();
+        coins.sort();
+        coins.dedup_by(|a, b| a.0 == b.0);
+        let coins = coins
+            .iter()
+            .map(|(id, symbol)| CoinRef { id, symbol })
+            .collect::<Vec<_>>();
+        let rates = providers.fetch(&coins, fiat.code()).await?;
+        let prices: HashMap<String, f64> = rates
+            .quotes
+            .iter()
+            .map(|rate| (rate.id.clone(), rate.price))
+            .collect();
+        record_rates(quotes, &rates, fiat, true).await;
+        for (alert, price) in alerts.take_triggered(&prices)? {
+            log::info!("Crypto alert #{} triggered at {price}", alert.id);
+            bot_chan.send(alert_outbound(&alert, price, fiat)).await?;
+        }
         tokio::time::sleep(Duration::from_secs(60 * 60)).await;
     }
 }
 
-async fn get_and_save_all_rates() -> anyhow::Result<()> {
-    let client = reqwest::Client::new();
-    let (btc_rate, eth_rate, doge_rate, ripple_rate, algo_rate) = try_join!(
-        CryptoCoin::Bitcoin.get_rate_in_euro(&client),
-        CryptoCoin::Ethereum.get_rate_in_euro(&client),
-        CryptoCoin::Doge.get_rate_in_euro(&client),
-        CryptoCoin::Ripple.get_rate_in_euro(&client),
-        CryptoCoin::Algorand.get_rate_in_euro(&client),
-    )?;
-
-    let btc_row = CryptoCoinRate {
-        date: chrono::Utc::now().naive_utc(),
-        coin: CryptoCoin::Bitcoin,
-        rate: btc_rate,
-    };
-
-    let eth_row = CryptoCoinRate {
-        date: chrono::Utc::now().naive_utc(),
-        coin: CryptoCoin::Ethereum,
-        rate: eth_rate,
-    };
-
-    let doge_row = CryptoCoinRate {
-        date: chrono::Utc::now().naive_utc(),
-        coin: CryptoCoin::Doge,
-        rate: doge_rate,
-    };
+/// To the channel and network where the alert was created
+fn alert_outbound(alert: &Alert, price: f64, fiat: Fiat) -> Outbound {
+    let mut msg = Message::from(Outbound::reply(
+        &alert.channel,
+        alert.triggered_message(price, fiat),
+    ));
+    if let Some(network) = &alert.network {
+        set_network(&mut msg, network);
+    }
+    Outbound::Raw(msg)
+}
 
-    let ripple_row = CryptoCoinRate {
-        date: chrono::Utc::now().naive_utc(),
-        coin: CryptoCoin::Ripple,
-        rate: ripple_rate,
-    };
+/// In the cache, and in the history for the variations over time when
+/// `recorded`, the history is in the default fiat only
+async fn record_rates(quotes: &QuoteCache, rates: &Fetched, fiat: Fiat, recorded: bool) {
+    let now = Instant::now();
+    quotes.record_at(
+        rates
+            .quotes
+            .iter()
+            .map(|rate| (rate.id.clone(), rate.price, rate.change_24h)),
+        fiat,
+        now,
+    );
+    quotes.record_inactive_at(&rates.inactive, now);
+    if !recorded {
+        return;
+    }
+    if let Err(err) = save_rates(&rates.quotes).await {
+        log::error!("Cannot save the crypto rates: {err:#}");
+    }
+}
 
-    let algo_row = CryptoCoinRate {
-        date: chrono::Utc::now().naive_utc(),
-        coin: CryptoCoin::Algorand,
-        rate: algo_rate,
-    };
+async fn save_rates(rates: &[FetchedQuote]) -> anyhow::Result<()> {
+    let date = chrono::Utc::now().naive_utc();
+    let rows = rates
+        .iter()
+        .map(|rate| CryptoCoinRate {
+            date,
+            coin: rate.id.clone(),
+            // the history table has always stored floats
+            rate: rate.price as f32,
+        })
+        .collect::<Vec<_>>();
 
     task::spawn_blocking(move || {
         let conn = db::establish_connection()?;
-        let vals = vec![&btc_row, &eth_row, &doge_row, &ripple_row, &algo_row];
         diesel::insert_into(crypto_rate::table)
-            .values(vals.clone())
+            .values(&rows)
             .execute(&conn)
-            .with_context(|| format!("Cannot insert {:?} into db", vals))
+            .with_context(|| format!("Cannot insert {:?} into db", rows))
     })
     .await??;
     log::info!("Successfully updated DB for crypto rates");
@@ -300,90 +953,143 @@ async fn get_and_save_all_rates() -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn get_rate_and_history(coin: CryptoCoin) -> anyhow::Result<String> {
-    let client = reqwest::Client::new();
-    let rate = coin.get_rate_in_euro(&client).await?;
-    let row = CryptoCoinRate {
-        date: chrono::Utc::now().naive_utc(),
-        coin,
-        rate,
+/// `others` coins share the symbol of this one, with a smaller market cap.
+/// The variations over time only when the rates are `recorded` in that fiat.
+async fn get_rate_and_history(
+    coin: Coin,
+    others: usize,
+    cached: Cached,
+    fiat: Fiat,
+    recorded: bool,
+    use_colors: bool,
+) -> anyhow::Result<String> {
+    let rate = cached.quote.price.to_f32().unwrap_or_default();
+    let age = match cached.age_note(Instant::now()) {
+        Some(note) => format!(" {note}"),
+        None => "".to_string(),
     };
     task::spawn_blocking(move || {
-        let conn = db::establish_connection()?;
-        diesel::insert_into(crypto_rate::table)
-            .values(&row)
-            .execute(&conn)
-            .with_context(|| format!("Cannot insert {:?} into db", row))?;
-
-        let now = Utc::now();
-        let past_day = dsl::crypto_rate
-            .filter(dsl::date.le((now - chrono::Duration::days(1)).naive_utc()))
-            .filter(dsl::coin.eq(coin))
-            .order_by(dsl::date.desc())
-            .limit(1)
-            .load::<CryptoCoinRate>(&conn)?
-            .into_iter()
-            .next();
-
-        let past_week = dsl::crypto_rate
-            .filter(dsl::date.le((now - chrono::Duration::days(7)).naive_utc()))
-            .filter(dsl::coin.eq(coin))
-            .order_by(dsl::date.desc())
-            .limit(1)
-            .load::<CryptoCoinRate>(&conn)?
-            .into_iter()
-            .next();
-
-        let past_month = dsl::crypto_rate
-            // not quite 1 month, but 🤷
-            .filter(dsl::date.le((now - chrono::Duration::days(30)).naive_utc()))
-            .filter(dsl::coin.eq(coin))
-            .order_by(dsl::date.desc())
-            .limit(1)
-            .load::<CryptoCoinRate>(&conn)?
-            .into_iter()
-            .next();
-
-        log::debug!(
-            "current rate: {}, past day: {:?}, past week: {:?}, past month: {:?}",
-            rate,
-            past_day,
-            past_week,
-            past_month
-        );
-
-        let variations = vec![(past_day, "1D"), (past_week, "1W"), (past_month, "1M")]
-            .into_iter()
-            .filter_map(|(mb_r, suffix)| {
-                mb_r.map(|r| {
-                    let var = RateVariation(((rate - r.rate) * 100.0) / r.rate);
-                    format!("{:.02} {}", var, suffix)
-                })
-            })
-            .collect::<Vec<_>>();
-
-        let variations = if variations.is_empty() {
-            "".to_string()
-        } else {
-            format!("({})", variations.join(" − "))
+        let variations = match recorded {
+            true => recorded_variations(&coin.id, rate)?,
+            false => "".to_string(),
         };
 
         let now = time::OffsetDateTime::now_utc();
         let rep_date = RepublicanDate::try_from(now.date()).map_err(|e| anyhow!(e))?;
 
+        let symbol = coin.symbol.to_uppercase();
+        let label = match others {
+            0 => symbol,
+            n => format!("{symbol} ({}, le plus capitalisé des {})", coin.name, n + 1),
+        };
+        let change = match format_change(cached.quote.change_24h, use_colors) {
+            Some(change) => format!(" {change} (24h)"),
+            None => "".to_string(),
+        };
         let result = format!(
-            "1 {} vaut {} euros grâce au pouvoir de la spéculation et {} ! {}",
-            coin,
-            rate,
+            "{label}: {}{change}{age} grâce au pouvoir de la spéculation et {} ! {}",
+            fiat.format(cached.quote.price),
             rep_date.day_symbol(),
             variations,
         );
 
-        Ok(result)
+        Ok(result.trim_end().to_string())
     })
     .await?
 }
 
+/// Like `(↗2.31% 1W − ↘4.50% 1M)`, from the recorded rates. Blocking.
+fn recorded_variations(id: &str, rate: f32) -> anyhow::Result<String> {
+    let conn = db::establish_connection()?;
+    let now = Utc::now();
+    let past_week = dsl::crypto_rate
+        .filter(dsl::date.le((now - chrono::Duration::days(7)).naive_utc()))
+        .filter(dsl::coin.eq(id))
+        .order_by(dsl::date.desc())
+        .limit(1)
+        .load::<CryptoCoinRate>(&conn)?
+        .into_iter()
+        .next();
+
+    let past_month = dsl::crypto_rate
+        // not quite 1 month, but 🤷
+        .filter(dsl::date.le((now - chrono::Duration::days(30)).naive_utc()))
+        .filter(dsl::coin.eq(id))
+        .order_by(dsl::date.desc())
+        .limit(1)
+        .load::<CryptoCoinRate>(&conn)?
+        .into_iter()
+        .next();
+
+    log::debug!(
+        "current rate: {}, past week: {:?}, past month: {:?}",
+        rate,
+        past_week,
+        past_month
+    );
+
+    // the past day is the 24h change from the provider
+    let variations = vec![(past_week, "1W"), (past_month, "1M")]
+        .into_iter()
+        .filter_map(|(mb_r, suffix)| {
+            mb_r.map(|r| {
+                let var = RateVariation(((rate - r.rate) * 100.0) / r.rate);
+                format!("{:.02} {}", var, suffix)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(if variations.is_empty() {
+        "".to_string()
+    } else {
+        format!("({})", variations.join(" − "))
+    })
+}
+
+/// Like `▲ +2.3%`, the percentage green or red with colors.
+/// None when there is no change to show.
+fn format_change(change: Option<f32>, use_colors: bool) -> Option<String> {
+    let change = change.filter(|c| c.is_finite())?;
+    // decided on the displayed value, so that -0.04 isn't a ▼ -0.0%
+    let rounded = (change * 10.0).round() / 10.0;
+    let (arrow, color) = match rounded.partial_cmp(&0.) {
+        Some(std::cmp::Ordering::Greater) => ("▲", Some(GREEN)),
+        Some(std::cmp::Ordering::Less) => ("▼", Some(RED)),
+        _ => return Some("→ 0.0%".to_string()),
+    };
+    let percentage = format!("{rounded:+.1}%");
+    Some(match color.filter(|_| use_colors) {
+        Some(color) => format!("{arrow} \x03{color}{percentage}\x0F"),
+        None => format!("{arrow} {percentage}"),
+    })
+}
+
+/// Like `Your holdings: 17 526 € (▲ +2.3%, +394 € over 24h)`
+fn describe_total(valuation: &Valuation, fiat: Fiat, use_colors: bool) -> String {
+    if valuation.total.is_zero() && !valuation.missing.is_empty() {
+        return "No quote for your coins right now, try again in a minute".to_string();
+    }
+    let missing = match valuation.missing.as_slice() {
+        [] => "".to_string(),
+        missing => format!(", without {} (no quote)", missing.join(", ")),
+    };
+    let delta = valuation.delta();
+    let sign = if delta.is_sign_negative() { "-" } else { "+" };
+    let change = match format_change(valuation.change_24h(), use_colors) {
+        Some(change) => format!(" ({change}, {sign}{} over 24h)", fiat.format(delta.abs())),
+        None => "".to_string(),
+    };
+    format!(
+        "Your holdings: {}{change}{missing}",
+        fiat.format(valuation.total)
+    )
+}
+
+/// mIRC color codes, always on two digits so that the text
+/// after them cannot be mistaken for a color
+const GREEN: &str = "03";
+const RED: &str = "04";
+
 struct RateVariation(f32);
 
 impl std::fmt::Display for RateVariation {
@@ -405,43 +1111,160 @@ impl std::fmt::Display for RateVariation {
 #[cfg(test)]
 mod test {
     use super::*;
+    use chrono::TimeZone;
     use pretty_assertions::assert_eq;
 
     #[test]
-    async fn price_from_json() {
-        let json = r#"{"result":{"price":30250.14},"allowance":{"cost":0.005,"remaining":9.98,"upgrade":"For unlimited API access, create an account at https://cryptowat.ch"}}"#;
-        let expected = CryptowatchResponse {
-            result: CryptowatchResponseResult { price: 30250.14 },
-            allowance: CryptowatchResponseAllowance {
-                cost: 0.005,
-                remaining: 9.98,
-            },
-        };
+    async fn test_crypto() {
+        assert_eq!(
+            parse_command("λcrypto"),
+            Ok(("", None)),
+            "the default watchlist"
+        );
+
+        assert_eq!(
+            parse_command("λcrypto xbt"),
+            Ok(("xbt", None)),
+            "can parse bitcoin"
+        );
+
+        assert_eq!(
+            parse_command("λcrypto wut"),
+            Ok(("wut", None)),
+            "any coin, resolved later"
+        );
 
         assert_eq!(
-            serde_json::from_str(json).map_err(|e| format!("{:?}", e)),
-            // CryptowatchPrice::from_str(json).map_err(|e| format!("{:?}", e)),
-            Ok(expected)
+            parse_command("&crypto doge > héloïse "),
+            Ok(("doge", Some("héloïse")))
+        );
+
+        assert!(parse_command("λcryptoxbt").is_err());
+    }
+
+    #[test]
+    async fn test_settings() {
+        let config = plugin_core::Config::from_dhall_str(
+            r#"{ crypto =
+                { aliases = [ { alias = "toutou", symbol = "doge" } ]
+                , default_watchlist = [ "btc", "eth", "toutou" ]
+                }
+            }"#,
+        )
+        .unwrap();
+        let settings = Settings::load(&config).unwrap();
+        assert_eq!(
+            settings.aliases,
+            vec![Alias {
+                alias: "toutou".to_string(),
+                symbol: "doge".to_string()
+            }]
+        );
+        assert_eq!(settings.default_watchlist, vec!["btc", "eth", "toutou"]);
+
+        let config =
+            plugin_core::Config::from_dhall_str("{ crypto = { use_colors = True } }").unwrap();
+        let settings = Settings::load(&config).unwrap();
+        assert_eq!(settings.aliases, vec![]);
+        assert_eq!(settings.default_watchlist, Vec::<String>::new());
+        assert!(!settings.include_ath, "off by default");
+        assert_eq!(settings.fiats().unwrap().default(), Fiat::EUR);
+
+        let config = plugin_core::Config::from_dhall_str(
+            r##"{ crypto =
+                { default_fiat = Some "usd"
+                , channel_fiats = [ { channel = "#crypto-fr", fiat = "EUR" } ]
+                }
+            }"##,
         )
+        .unwrap();
+        let fiats = Settings::load(&config).unwrap().fiats().unwrap();
+        assert_eq!(fiats.default(), Fiat::parse("usd").unwrap());
+        assert_eq!(fiats.resolve(None, Some("#crypto-fr")), Fiat::EUR);
+
+        let config = plugin_core::Config::from_dhall_str(
+            r##"{ crypto = { channel_fiats = [ { channel = "#crypto", fiat = "XBT" } ] } }"##,
+        )
+        .unwrap();
+        assert!(Settings::load(&config).is_err(), "unsupported fiat");
+
+        let config =
+            plugin_core::Config::from_dhall_str("{ crypto = { inactive_after_hours = Some 0 } }")
+                .unwrap();
+        assert!(Settings::load(&config).is_err());
+
+        let config = plugin_core::Config::from_dhall_str(
+            r#"{ crypto = { default_watchlist = [ "btc", "eth", "doge", "xrp", "algo" ] } }"#,
+        )
+        .unwrap();
+        assert!(Settings::load(&config).is_err(), "too long for a line");
     }
 
     #[test]
-    async fn test_crypto() {
-        assert!(
-            parse_command("λcrypto").is_err(),
-            "must have something after the command"
+    async fn test_no_market() {
+        assert_eq!(
+            no_market("luna", Some(Utc.ymd(2023, 6, 1).and_hms(8, 0, 0))),
+            "No active market data for LUNA (last trade 2023-06-01)"
         );
+        assert_eq!(no_market("xmr", None), "No active market data for XMR");
+    }
 
+    #[test]
+    async fn test_describe_total() {
+        let valuation = |total: i64, previous: i64, missing: &[&str]| Valuation {
+            total: Decimal::from(total),
+            previous: Decimal::from(previous),
+            missing: missing.iter().map(|m| m.to_string()).collect(),
+        };
         assert_eq!(
-            parse_command("λcrypto xbt"),
-            Ok((Ok(CryptoCoin::Bitcoin), None)),
-            "can parse bitcoin"
+            describe_total(&valuation(18000, 16000, &[]), Fiat::EUR, false),
+            "Your holdings: 18\u{2009}000 € (▲ +12.5%, +2\u{2009}000 € over 24h)"
+        );
+        assert_eq!(
+            describe_total(
+                &valuation(18000, 16000, &[]),
+                Fiat::parse("usd").unwrap(),
+                false
+            ),
+            "Your holdings: $18\u{2009}000 (▲ +12.5%, +$2\u{2009}000 over 24h)"
+        );
+        assert_eq!(
+            describe_total(&valuation(15000, 16000, &["DOGE"]), Fiat::EUR, false),
+            "Your holdings: 15\u{2009}000 € (▼ -6.2%, -1\u{2009}000 € over 24h), without DOGE (no quote)"
+        );
+        assert_eq!(
+            describe_total(&valuation(0, 0, &["DOGE", "ETH"]), Fiat::EUR, false),
+            "No quote for your coins right now, try again in a minute"
         );
+    }
 
+    #[test]
+    async fn test_format_change() {
         assert_eq!(
-            parse_command("λcrypto wut"),
-            Ok((Err("wut"), None)),
-            "inner error on unknown coin"
+            format_change(Some(2.345), false),
+            Some("▲ +2.3%".to_string())
+        );
+        assert_eq!(
+            format_change(Some(-12.06), false),
+            Some("▼ -12.1%".to_string())
+        );
+        assert_eq!(format_change(Some(0.0), false), Some("→ 0.0%".to_string()));
+        assert_eq!(format_change(Some(-0.0), true), Some("→ 0.0%".to_string()));
+        assert_eq!(
+            format_change(Some(-0.04), false),
+            Some("→ 0.0%".to_string()),
+            "rounds to zero"
+        );
+        assert_eq!(format_change(None, true), None);
+        assert_eq!(format_change(Some(f32::NAN), false), None);
+
+        assert_eq!(
+            format_change(Some(2.345), true),
+            Some("▲ \x0303+2.3%\x0F".to_string())
+        );
+        assert_eq!(
+            format_change(Some(-1.0), true),
+            Some("▼ \x0304-1.0%\x0F".to_string())
         );
     }
 }
@@ -0,0 +1,334 @@
+use super::{AllTimeHigh, CoinRef, Fetched, FetchedQuote, Inactive, PricePoint, QuoteProvider};
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// https://www.coingecko.com/en/api, the demo keys are optional
+pub struct CoinGecko {
+    client: Client,
+    api_key: Option<String>,
+}
+
+impl CoinGecko {
+    pub fn new(client: Client, api_key: Option<String>) -> Self {
+        CoinGecko { client, api_key }
+    }
+
+    fn get(&self, url: &str) -> RequestBuilder {
+        let request = self.client.get(url);
+        match &self.api_key {
+            Some(key) => request.header("x-cg-demo-api-key", key),
+            None => request,
+        }
+    }
+}
+
+/// https://api.coingecko.com/api/v3/simple/price response, by coin id
+/// then by fiat, like `{"bitcoin": {"eur": 30250.14, "eur_24h_change": 2.3,
+/// "eur_24h_vol": 21000000000.0, "last_updated_at": 1714567890}}`.
+/// In f64 for the timestamps.
+type SimplePrices = HashMap<String, HashMap<String, Option<f64>>>;
+
+/// The coins without a price, or not traded over 24h, are inactive
+fn parse(prices: SimplePrices, fiat: &str) -> Fetched {
+    let change_key = format!("{fiat}_24h_change");
+    let volume_key = format!("{fiat}_24h_vol");
+    let mut fetched = Fetched::default();
+    for (id, values) in prices {
+        let value = |key: &str| values.get(key).copied().flatten();
+        let updated_at = value("last_updated_at")
+            .and_then(|timestamp| Utc.timestamp_opt(timestamp as i64, 0).single());
+        let price = value(fiat).filter(|_| value(&volume_key) != Some(0.0));
+        match price {
+            Some(price) => fetched.quotes.push(FetchedQuote {
+                id,
+                price,
+                change_24h: value(&change_key).map(|change| change as f32),
+                updated_at,
+            }),
+            None => fetched.inactive.push(Inactive {
+                id,
+                last_trade: updated_at,
+            }),
+        }
+    }
+    fetched
+}
+
+/// https://api.coingecko.com/api/v3/coins/{id}/market_chart response, hourly
+/// from 2 days, daily from 90 days
+#[derive(Debug, Deserialize)]
+struct MarketChart {
+    /// [timestamp in ms, price]
+    prices: Vec<(i64, f64)>,
+}
+
+fn parse_market_chart(chart: MarketChart) -> anyhow::Result<Vec<PricePoint>> {
+    let points = chart
+        .prices
+        .into_iter()
+        .filter_map(|(ms, price)| {
+            Some(PricePoint {
+                at: Utc.timestamp_millis_opt(ms).single()?,
+                price,
+            })
+        })
+        .collect::<Vec<_>>();
+    if points.is_empty() {
+        bail!("No price history");
+    }
+    Ok(points)
+}
+
+/// https://api.coingecko.com/api/v3/coins/{id} response, only what's used
+#[derive(Debug, Deserialize)]
+struct CoinDetail {
+    market_data: MarketData,
+}
+
+#[derive(Debug, Deserialize)]
+struct MarketData {
+    /// by fiat
+    ath: HashMap<String, f64>,
+    /// by fiat, RFC 3339
+    ath_date: HashMap<String, String>,
+}
+
+fn parse_all_time_high(detail: CoinDetail, fiat: &str) -> anyhow::Result<AllTimeHigh> {
+    let market_data = detail.market_data;
+    let price = *market_data
+        .ath
+        .get(fiat)
+        .ok_or_else(|| anyhow!("No all-time high in {fiat}"))?;
+    let date = market_data
+        .ath_date
+        .get(fiat)
+        .ok_or_else(|| anyhow!("No all-time high date in {fiat}"))?;
+    let at = DateTime::parse_from_rfc3339(date)
+        .with_context(|| format!("Invalid all-time high date {date}"))?
+        .with_timezone(&Utc);
+    Ok(AllTimeHigh { price, at })
+}
+
+#[async_trait]
+impl QuoteProvider for CoinGecko {
+    fn name(&self) -> &'static str {
+        "coingecko"
+    }
+
+    /// The 24h change comes with the price, the 7d one would need another endpoint
+    async fn fetch(&self, coins: &[CoinRef<'_>], fiat: &str) -> anyhow::Result<Fetched> {
+        let ids = coins.iter().map(|c| c.id).collect::<Vec<_>>();
+        let url = format!(
+            "https://api.coingecko.com/api/v3/simple/price?ids={}&vs_currencies={fiat}&include_24hr_change=true&include_24hr_vol=true&include_last_updated_at=true",
+            ids.join(",")
+        );
+        let prices = self
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<SimplePrices>()
+            .await
+            .with_context(|| format!("Error while fetching response from {url}"))?;
+        Ok(parse(prices, fiat))
+    }
+
+    async fn history(
+        &self,
+        coin: CoinRef<'_>,
+        fiat: &str,
+        days: u32,
+    ) -> anyhow::Result<Vec<PricePoint>> {
+        let url = format!(
+            "https://api.coingecko.com/api/v3/coins/{}/market_chart?vs_currency={fiat}&days={days}",
+            coin.id
+        );
+        let chart = self
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<MarketChart>()
+            .await
+            .with_context(|| format!("Error while fetching response from {url}"))?;
+        parse_market_chart(chart)
+    }
+
+    fn has_all_time_high(&self) -> bool {
+        true
+    }
+
+    async fn all_time_high(&self, coin: CoinRef<'_>, fiat: &str) -> anyhow::Result<AllTimeHigh> {
+        let url = format!(
+            "https://api.coingecko.com/api/v3/coins/{}?localization=false&tickers=false&community_data=false&developer_data=false",
+            coin.id
+        );
+        let detail = self
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<CoinDetail>()
+            .await
+            .with_context(|| format!("Error while fetching response from {url}"))?;
+        parse_all_time_high(detail, fiat)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn sorted(mut fetched: Fetched) -> Fetched {
+        fetched.quotes.sort_by(|a, b| a.id.cmp(&b.id));
+        fetched.inactive.sort_by(|a, b| a.id.cmp(&b.id));
+        fetched
+    }
+
+    #[test]
+    async fn test_parse() {
+        let json = r#"{
+            "bitcoin":{"eur":30250.14,"eur_24h_change":2.345,"eur_24h_vol":21000000000.0,"last_updated_at":1714567890},
+            "dogecoin":{"eur":0.06,"eur_24h_change":null},
+            "ripple":{"eur":0.5}
+        }"#;
+        let fetched = sorted(parse(serde_json::from_str(json).unwrap(), "eur"));
+        assert_eq!(
+            fetched.quotes,
+            vec![
+                FetchedQuote {
+                    id: "bitcoin".to_string(),
+                    price: 30250.14,
+                    change_24h: Some(2.345),
+                    updated_at: Some(Utc.ymd(2024, 5, 1).and_hms(12, 51, 30)),
+                },
+                FetchedQuote {
+                    id: "dogecoin".to_string(),
+                    price: 0.06,
+                    change_24h: None,
+                    updated_at: None,
+                },
+                FetchedQuote {
+                    id: "ripple".to_string(),
+                    price: 0.5,
+                    change_24h: None,
+                    updated_at: None,
+                },
+            ]
+        );
+        assert_eq!(fetched.inactive, vec![]);
+    }
+
+    #[test]
+    async fn test_parse_null_price() {
+        let json = r#"{
+            "delisted":{},
+            "terra-luna":{"eur":null,"eur_24h_change":null,"last_updated_at":1685606400}
+        }"#;
+        let fetched = sorted(parse(serde_json::from_str(json).unwrap(), "eur"));
+        assert_eq!(fetched.quotes, vec![]);
+        assert_eq!(
+            fetched.inactive,
+            vec![
+                Inactive {
+                    id: "delisted".to_string(),
+                    last_trade: None,
+                },
+                Inactive {
+                    id: "terra-luna".to_string(),
+                    last_trade: Some(Utc.ymd(2023, 6, 1).and_hms(8, 0, 0)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    async fn test_parse_stale() {
+        let json = r#"{
+            "terra-luna":{"eur":0.0001,"eur_24h_vol":12.5,"last_updated_at":1685606400}
+        }"#;
+        let fetched = parse(serde_json::from_str(json).unwrap(), "eur");
+        assert_eq!(
+            fetched
+                .retire_stale_at(
+                    std::time::Duration::from_secs(24 * 60 * 60),
+                    Utc.ymd(2024, 5, 8).and_hms(12, 0, 0)
+                )
+                .inactive,
+            vec![Inactive {
+                id: "terra-luna".to_string(),
+                last_trade: Some(Utc.ymd(2023, 6, 1).and_hms(8, 0, 0)),
+            }]
+        );
+    }
+
+    #[test]
+    async fn test_parse_inactive_market() {
+        let json = r#"{
+            "squid-game":{"eur":0.0042,"eur_24h_change":0.0,"eur_24h_vol":0.0,"last_updated_at":1714567890}
+        }"#;
+        let fetched = parse(serde_json::from_str(json).unwrap(), "eur");
+        assert_eq!(fetched.quotes, vec![]);
+        assert_eq!(
+            fetched.inactive,
+            vec![Inactive {
+                id: "squid-game".to_string(),
+                last_trade: Some(Utc.ymd(2024, 5, 1).and_hms(12, 51, 30)),
+            }],
+            "nothing traded over 24h"
+        );
+    }
+
+    #[test]
+    async fn test_parse_market_chart() {
+        let json = r#"{
+            "prices": [[1714521600000, 59100.5], [1714525200000, 59250.0]],
+            "market_caps": [[1714521600000, 1164000000000.0]],
+            "total_volumes": [[1714521600000, 30000000000.0]]
+        }"#;
+        let points = parse_market_chart(serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(
+            points,
+            vec![
+                PricePoint {
+                    at: Utc.ymd(2024, 5, 1).and_hms(0, 0, 0),
+                    price: 59100.5,
+                },
+                PricePoint {
+                    at: Utc.ymd(2024, 5, 1).and_hms(1, 0, 0),
+                    price: 59250.0,
+                },
+            ]
+        );
+
+        let empty = serde_json::from_str(r#"{"prices": []}"#).unwrap();
+        assert!(parse_market_chart(empty).is_err());
+    }
+
+    #[test]
+    async fn test_parse_all_time_high() {
+        let json = r#"{
+            "id": "bitcoin",
+            "symbol": "btc",
+            "market_data": {
+                "current_price": {"eur": 64230.5, "usd": 69000.0},
+                "ath": {"eur": 73750.0, "usd": 80000.0},
+                "ath_date": {"eur": "2024-03-14T07:10:36.635Z", "usd": "2024-03-14T07:10:36.635Z"}
+            }
+        }"#;
+        let detail = || serde_json::from_str::<CoinDetail>(json).unwrap();
+        assert_eq!(
+            parse_all_time_high(detail(), "eur").unwrap(),
+            AllTimeHigh {
+                price: 73750.0,
+                at: Utc.ymd(2024, 3, 14).and_hms_milli(7, 10, 36, 635),
+            }
+        );
+        assert!(parse_all_time_high(detail(), "gbp").is_err());
+    }
+}
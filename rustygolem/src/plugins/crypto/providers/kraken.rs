@@ -0,0 +1,339 @@
+use super::{CoinRef, Fetched, FetchedQuote, Inactive, PricePoint, QuoteProvider};
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Kraken has its own names for some assets
+const ASSET_CODES: &[(&str, &str)] = &[("btc", "XBT"), ("doge", "XDG")];
+
+/// The candle durations in minutes the OHLC endpoint accepts
+const OHLC_INTERVALS: &[u32] = &[1, 5, 15, 30, 60, 240, 1440, 10080, 21600];
+/// Only the most recent candles are given, whatever the `since` parameter
+const OHLC_MAX_CANDLES: u32 = 720;
+
+/// https://docs.kraken.com/rest/#tag/Market-Data/operation/getTickerInformation,
+/// public, the api key is unused
+pub struct Kraken {
+    client: Client,
+}
+
+impl Kraken {
+    pub fn new(client: Client) -> Self {
+        Kraken { client }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TickerResponse {
+    error: Vec<String>,
+    #[serde(default)]
+    result: HashMap<String, Ticker>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Ticker {
+    /// last trade closed, as [price, lot volume]
+    c: (String, String),
+    /// volume as [today, last 24h]
+    #[serde(default)]
+    v: Option<(String, String)>,
+}
+
+impl Ticker {
+    /// Nothing traded over the last 24h, the last trade can be from long ago
+    fn is_inactive(&self) -> bool {
+        self.v
+            .as_ref()
+            .and_then(|(_, last_24h)| last_24h.parse::<f64>().ok())
+            .map_or(false, |volume| volume == 0.0)
+    }
+}
+
+fn asset_code(symbol: &str) -> String {
+    let symbol = symbol.to_lowercase();
+    ASSET_CODES
+        .iter()
+        .find(|(s, _)| *s == symbol)
+        .map_or_else(|| symbol.to_uppercase(), |(_, code)| code.to_string())
+}
+
+/// The asset of a pair of the response. Those are not the pairs asked for,
+/// the older assets have extended names like XXBTZEUR for XBTEUR.
+fn pair_asset<'a>(pair: &'a str, fiat: &str) -> Option<&'a str> {
+    let fiat = fiat.to_uppercase();
+    let asset = pair
+        .strip_suffix(&format!("Z{fiat}"))
+        .filter(|asset| asset.len() == 4 && asset.starts_with('X'))
+        .map(|asset| &asset[1..])
+        .or_else(|| pair.strip_suffix(&fiat))?;
+    Some(asset)
+}
+
+/// The pairs without any trade over 24h are inactive
+fn parse(response: TickerResponse, coins: &[CoinRef], fiat: &str) -> anyhow::Result<Fetched> {
+    if !response.error.is_empty() {
+        bail!("Kraken error: {}", response.error.join(", "));
+    }
+    let mut fetched = Fetched::default();
+    for (pair, ticker) in response.result {
+        let asset = match pair_asset(&pair, fiat) {
+            Some(asset) => asset,
+            None => continue,
+        };
+        let coin = match coins.iter().find(|c| asset_code(c.symbol) == asset) {
+            Some(coin) => coin,
+            None => continue,
+        };
+        if ticker.is_inactive() {
+            fetched.inactive.push(Inactive {
+                id: coin.id.to_string(),
+                // not in the ticker
+                last_trade: None,
+            });
+            continue;
+        }
+        let price = ticker
+            .c
+            .0
+            .parse()
+            .with_context(|| format!("Invalid price {} for {pair}", ticker.c.0))?;
+        fetched.quotes.push(FetchedQuote {
+            id: coin.id.to_string(),
+            price,
+            // the ticker only has the opening price of the day, not the one of 24h ago
+            change_24h: None,
+            updated_at: None,
+        });
+    }
+    Ok(fetched)
+}
+
+/// The shortest candles which still go back `days`
+fn ohlc_interval(days: u32) -> u32 {
+    let minutes = days * 24 * 60;
+    OHLC_INTERVALS
+        .iter()
+        .copied()
+        .find(|interval| interval * OHLC_MAX_CANDLES >= minutes)
+        .unwrap_or(21600)
+}
+
+#[derive(Debug, Deserialize)]
+struct OhlcResponse {
+    error: Vec<String>,
+    /// the candles by pair, and a `last` timestamp
+    #[serde(default)]
+    result: HashMap<String, serde_json::Value>,
+}
+
+/// [time, open, high, low, close, vwap, volume, count]
+type Candle = (i64, String, String, String, String, String, String, u64);
+
+/// The opening prices of the candles
+fn parse_ohlc(response: OhlcResponse) -> anyhow::Result<Vec<PricePoint>> {
+    if !response.error.is_empty() {
+        bail!("Kraken error: {}", response.error.join(", "));
+    }
+    let candles = response
+        .result
+        .into_iter()
+        .find(|(key, _)| key != "last")
+        .ok_or_else(|| anyhow!("No price history"))?
+        .1;
+    let candles = serde_json::from_value::<Vec<Candle>>(candles)?;
+    let mut points = vec![];
+    for (time, open, ..) in candles {
+        let at = match Utc.timestamp_opt(time, 0).single() {
+            Some(at) => at,
+            None => continue,
+        };
+        let price = open
+            .parse()
+            .with_context(|| format!("Invalid price {open} at {time}"))?;
+        points.push(PricePoint { at, price });
+    }
+    if points.is_empty() {
+        bail!("No price history");
+    }
+    Ok(points)
+}
+
+#[async_trait]
+impl QuoteProvider for Kraken {
+    fn name(&self) -> &'static str {
+        "kraken"
+    }
+
+    async fn fetch(&self, coins: &[CoinRef<'_>], fiat: &str) -> anyhow::Result<Fetched> {
+        // a single unknown pair fails the whole request, so get them all
+        let url = "https://api.kraken.com/0/public/Ticker";
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<TickerResponse>()
+            .await
+            .with_context(|| format!("Error while fetching response from {url}"))?;
+        parse(response, coins, fiat)
+    }
+
+    async fn history(
+        &self,
+        coin: CoinRef<'_>,
+        fiat: &str,
+        days: u32,
+    ) -> anyhow::Result<Vec<PricePoint>> {
+        let pair = format!("{}{}", asset_code(coin.symbol), fiat.to_uppercase());
+        let url = format!(
+            "https://api.kraken.com/0/public/OHLC?pair={pair}&interval={}",
+            ohlc_interval(days)
+        );
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<OhlcResponse>()
+            .await
+            .with_context(|| format!("Error while fetching response from {url}"))?;
+        parse_ohlc(response)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Trimmed down from the response, with the lots of fields not used
+    const TICKER: &str = r#"{
+        "error": [],
+        "result": {
+            "XXBTZEUR": {"a":["64231.0","1","1.000"],"b":["64230.9","2","2.000"],"c":["64230.5","0.00120"],"o":"63000.0","v":["120.5","1830.2"]},
+            "XETHZEUR": {"c":["2000.12","0.5"]},
+            "XDGEUR": {"c":["0.0612","1000"]},
+            "XXBTZUSD": {"c":["70000.0","0.1"]},
+            "ALGOEUR": {"c":["0.15","10"]},
+            "ADAEUR": {"c":["0.30","10"]},
+            "XXMRZEUR": {"c":["150.0","0.1"],"v":["0.00000000","0.00000000"]}
+        }
+    }"#;
+
+    fn coins() -> Vec<CoinRef<'static>> {
+        vec![
+            CoinRef {
+                id: "bitcoin",
+                symbol: "btc",
+            },
+            CoinRef {
+                id: "ethereum",
+                symbol: "eth",
+            },
+            CoinRef {
+                id: "dogecoin",
+                symbol: "doge",
+            },
+            CoinRef {
+                id: "algorand",
+                symbol: "ALGO",
+            },
+            CoinRef {
+                id: "monero",
+                symbol: "xmr",
+            },
+        ]
+    }
+
+    #[test]
+    async fn test_parse() {
+        let response = serde_json::from_str(TICKER).unwrap();
+        let mut fetched = parse(response, &coins(), "eur").unwrap();
+        fetched.quotes.sort_by(|a, b| a.id.cmp(&b.id));
+        let quote = |id: &str, price| FetchedQuote {
+            id: id.to_string(),
+            price,
+            change_24h: None,
+            updated_at: None,
+        };
+        assert_eq!(
+            fetched.quotes,
+            vec![
+                quote("algorand", 0.15),
+                quote("bitcoin", 64230.5),
+                quote("dogecoin", 0.0612),
+                quote("ethereum", 2000.12),
+            ]
+        );
+        assert_eq!(
+            fetched.inactive,
+            vec![Inactive {
+                id: "monero".to_string(),
+                last_trade: None,
+            }],
+            "nothing traded over 24h"
+        );
+    }
+
+    #[test]
+    async fn test_parse_error() {
+        let response = serde_json::from_str(r#"{"error":["EGeneral:Too many requests"]}"#).unwrap();
+        let err = parse(response, &coins(), "eur").unwrap_err();
+        assert_eq!(err.to_string(), "Kraken error: EGeneral:Too many requests");
+    }
+
+    #[test]
+    async fn test_pair_asset() {
+        assert_eq!(pair_asset("XXBTZEUR", "eur"), Some("XBT"));
+        assert_eq!(pair_asset("XDGEUR", "eur"), Some("XDG"));
+        assert_eq!(pair_asset("ALGOEUR", "eur"), Some("ALGO"));
+        assert_eq!(pair_asset("XXBTZUSD", "eur"), None);
+    }
+
+    #[test]
+    async fn test_parse_ohlc() {
+        let json = r#"{
+            "error": [],
+            "result": {
+                "XXBTZEUR": [
+                    [1714521600, "59100.5", "59300.0", "58900.0", "59250.0", "59120.3", "12.5", 1234],
+                    [1714525200, "59250.0", "59400.0", "59200.0", "59380.0", "59300.1", "8.2", 987]
+                ],
+                "last": 1714525200
+            }
+        }"#;
+        let points = parse_ohlc(serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(
+            points,
+            vec![
+                PricePoint {
+                    at: Utc.ymd(2024, 5, 1).and_hms(0, 0, 0),
+                    price: 59100.5,
+                },
+                PricePoint {
+                    at: Utc.ymd(2024, 5, 1).and_hms(1, 0, 0),
+                    price: 59250.0,
+                },
+            ]
+        );
+
+        let unknown = serde_json::from_str(r#"{"error":["EQuery:Unknown asset pair"]}"#).unwrap();
+        assert_eq!(
+            parse_ohlc(unknown).unwrap_err().to_string(),
+            "Kraken error: EQuery:Unknown asset pair"
+        );
+    }
+
+    #[test]
+    async fn test_ohlc_interval() {
+        assert_eq!(ohlc_interval(1), 5);
+        assert_eq!(ohlc_interval(7), 15);
+        assert_eq!(ohlc_interval(30), 60);
+        assert_eq!(ohlc_interval(365), 1440);
+    }
+}
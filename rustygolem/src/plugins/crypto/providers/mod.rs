@@ -0,0 +1,596 @@
+use super::listing::Coin;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+mod coingecko;
+mod kraken;
+
+pub use coingecko::CoinGecko;
+pub use kraken::Kraken;
+
+/// Tried in this order when the config doesn't say otherwise
+pub const DEFAULT_PROVIDERS: &[&str] = &["coingecko", "kraken"];
+
+/// After that many failures in a row, a provider is skipped for a while
+const MAX_FAILURES: u32 = 3;
+const UNHEALTHY_COOLDOWN: Duration = Duration::from_secs(5 * 60);
+
+/// A slow provider counts as a failing one
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A price not updated for that long is not quoted, the market is
+/// considered inactive
+pub const DEFAULT_INACTIVE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoinRef<'a> {
+    /// coingecko id, which identifies the coins everywhere in the plugin
+    pub id: &'a str,
+    pub symbol: &'a str,
+}
+
+impl<'a> From<&'a Coin> for CoinRef<'a> {
+    fn from(coin: &'a Coin) -> Self {
+        CoinRef {
+            id: &coin.id,
+            symbol: &coin.symbol,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchedQuote {
+    /// coingecko id
+    pub id: String,
+    pub price: f64,
+    /// in percents
+    pub change_24h: Option<f32>,
+    /// of the price, when the provider tells
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// A coin the provider knows, but cannot quote: no price, nothing traded
+/// lately, like a delisted coin
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inactive {
+    /// coingecko id
+    pub id: String,
+    pub last_trade: Option<DateTime<Utc>>,
+}
+
+/// The answer of a provider, the coins it doesn't know are missing
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Fetched {
+    pub quotes: Vec<FetchedQuote>,
+    pub inactive: Vec<Inactive>,
+}
+
+impl Fetched {
+    /// The quotes updated more than `inactive_after` ago are inactive
+    /// markets, their price would mislead
+    pub fn retire_stale_at(self, inactive_after: Duration, now: DateTime<Utc>) -> Self {
+        let inactive_after = chrono::Duration::from_std(inactive_after)
+            .unwrap_or_else(|_| chrono::Duration::max_value());
+        let (stale, quotes): (Vec<_>, Vec<_>) = self.quotes.into_iter().partition(|quote| {
+            quote
+                .updated_at
+                .map_or(false, |updated_at| now - updated_at > inactive_after)
+        });
+        let mut inactive = self.inactive;
+        inactive.extend(stale.into_iter().map(|quote| Inactive {
+            id: quote.id,
+            last_trade: quote.updated_at,
+        }));
+        Fetched { quotes, inactive }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.quotes.is_empty() && self.inactive.is_empty()
+    }
+}
+
+/// A past price
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PricePoint {
+    pub at: DateTime<Utc>,
+    pub price: f64,
+}
+
+/// The highest price ever
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AllTimeHigh {
+    pub price: f64,
+    pub at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait QuoteProvider: Send + Sync {
+    /// Only for the logs, the replies don't tell where the quotes come from
+    fn name(&self) -> &'static str;
+
+    async fn fetch(&self, coins: &[CoinRef<'_>], fiat: &str) -> anyhow::Result<Fetched>;
+
+    /// The prices of the coin over the last days, oldest first, from at least
+    /// `days` ago. An error rather than no prices for an unknown coin.
+    async fn history(
+        &self,
+        coin: CoinRef<'_>,
+        fiat: &str,
+        days: u32,
+    ) -> anyhow::Result<Vec<PricePoint>>;
+
+    /// Whether the provider implements `all_time_high`
+    fn has_all_time_high(&self) -> bool {
+        false
+    }
+
+    async fn all_time_high(&self, _coin: CoinRef<'_>, _fiat: &str) -> anyhow::Result<AllTimeHigh> {
+        bail!("No all-time high from {}", self.name())
+    }
+}
+
+/// An entry of the `providers` list of the crypto config section
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderSettings {
+    pub name: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+/// The providers in the configured order, DEFAULT_PROVIDERS without config
+pub fn build(
+    client: &Client,
+    settings: Option<&[ProviderSettings]>,
+) -> anyhow::Result<Vec<Box<dyn QuoteProvider>>> {
+    let defaults = DEFAULT_PROVIDERS
+        .iter()
+        .map(|name| ProviderSettings {
+            name: name.to_string(),
+            api_key: None,
+        })
+        .collect::<Vec<_>>();
+    let settings = settings.unwrap_or(&defaults);
+    if settings.is_empty() {
+        bail!("crypto.providers cannot be empty");
+    }
+    settings
+        .iter()
+        .map(|provider| -> anyhow::Result<Box<dyn QuoteProvider>> {
+            match provider.name.as_str() {
+                "coingecko" => Ok(Box::new(CoinGecko::new(
+                    client.clone(),
+                    provider.api_key.clone(),
+                ))),
+                "kraken" => Ok(Box::new(Kraken::new(client.clone()))),
+                name => bail!(
+                    "Unknown crypto provider {name}, the known ones are {}",
+                    DEFAULT_PROVIDERS.join(", ")
+                ),
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    /// in a row
+    failures: u32,
+    unhealthy_until: Option<Instant>,
+}
+
+impl Health {
+    fn is_healthy_at(&self, now: Instant) -> bool {
+        self.unhealthy_until.map_or(true, |until| now >= until)
+    }
+
+    fn failed_at(&mut self, now: Instant) {
+        self.failures += 1;
+        if self.failures >= MAX_FAILURES {
+            self.failures = 0;
+            self.unhealthy_until = Some(now + UNHEALTHY_COOLDOWN);
+        }
+    }
+
+    fn succeeded(&mut self) {
+        *self = Health::default();
+    }
+}
+
+/// Tries the providers in order until one answers, skipping the ones
+/// which failed repeatedly
+pub struct Providers {
+    providers: Vec<(Box<dyn QuoteProvider>, Mutex<Health>)>,
+    inactive_after: Duration,
+}
+
+impl Providers {
+    pub fn new(providers: Vec<Box<dyn QuoteProvider>>, inactive_after: Duration) -> Self {
+        Providers {
+            providers: providers
+                .into_iter()
+                .map(|p| (p, Mutex::new(Health::default())))
+                .collect(),
+            inactive_after,
+        }
+    }
+
+    pub async fn fetch(&self, coins: &[CoinRef<'_>], fiat: &str) -> anyhow::Result<Fetched> {
+        let fetched = self
+            .first_success(
+                |_| true,
+                |provider| async move {
+                    let fetched = provider.fetch(coins, fiat).await?;
+                    // like an empty body, would be announced as a blank line
+                    if fetched.is_empty() && !coins.is_empty() {
+                        bail!("No quote at all in the answer");
+                    }
+                    Ok(fetched)
+                },
+            )
+            .await?
+            .retire_stale_at(self.inactive_after, Utc::now());
+        log::debug!(
+            "Got {} quotes, {} inactive markets",
+            fetched.quotes.len(),
+            fetched.inactive.len()
+        );
+        Ok(fetched)
+    }
+
+    pub async fn history(
+        &self,
+        coin: CoinRef<'_>,
+        fiat: &str,
+        days: u32,
+    ) -> anyhow::Result<Vec<PricePoint>> {
+        self.first_success(|_| true, |provider| provider.history(coin, fiat, days))
+            .await
+    }
+
+    pub async fn all_time_high(
+        &self,
+        coin: CoinRef<'_>,
+        fiat: &str,
+    ) -> anyhow::Result<AllTimeHigh> {
+        self.first_success(
+            |provider| provider.has_all_time_high(),
+            |provider| provider.all_time_high(coin, fiat),
+        )
+        .await
+    }
+
+    /// The answer of the first provider which gives one, among the ones
+    /// `able` to
+    async fn first_success<'a, T, A, F, Fut>(&'a self, able: A, call: F) -> anyhow::Result<T>
+    where
+        A: Fn(&dyn QuoteProvider) -> bool,
+        F: Fn(&'a dyn QuoteProvider) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let now = Instant::now();
+        let able = self
+            .providers
+            .iter()
+            .filter(|(provider, _)| able(provider.as_ref()))
+            .collect::<Vec<_>>();
+        if able.is_empty() {
+            bail!("No quote provider can answer that");
+        }
+        let mut candidates = able
+            .iter()
+            .copied()
+            .filter(|(_, health)| health.lock().expect("health lock").is_healthy_at(now))
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            // better try them anyway than not answering at all
+            candidates = able;
+        }
+        for (provider, health) in candidates {
+            let result = match tokio::time::timeout(FETCH_TIMEOUT, call(provider.as_ref())).await {
+                Ok(result) => result,
+                Err(_) => Err(anyhow!("Timed out after {FETCH_TIMEOUT:?}")),
+            };
+            match result {
+                Ok(answer) => {
+                    log::debug!("Answer from the quote provider {}", provider.name());
+                    health.lock().expect("health lock").succeeded();
+                    return Ok(answer);
+                }
+                Err(err) => {
+                    log::debug!("Quote provider {} failed: {err:#}", provider.name());
+                    health
+                        .lock()
+                        .expect("health lock")
+                        .failed_at(Instant::now());
+                }
+            }
+        }
+        bail!("Every quote provider failed")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Answers the same price for every coin, or fails
+    struct Fake {
+        name: &'static str,
+        price: Option<f64>,
+        /// never answers
+        hangs: bool,
+        /// answers without any quote
+        empty: bool,
+        calls: AtomicUsize,
+    }
+
+    impl Fake {
+        fn new(name: &'static str, price: Option<f64>) -> Self {
+            Fake {
+                name,
+                price,
+                hangs: false,
+                empty: false,
+                calls: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl QuoteProvider for Arc<Fake> {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn fetch(&self, coins: &[CoinRef<'_>], _fiat: &str) -> anyhow::Result<Fetched> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.hangs {
+                futures::future::pending::<()>().await;
+            }
+            let price = self.price.ok_or_else(|| anyhow!("{} is down", self.name))?;
+            let quotes = coins
+                .iter()
+                .filter(|_| !self.empty)
+                .map(|coin| FetchedQuote {
+                    id: coin.id.to_string(),
+                    price,
+                    change_24h: None,
+                    updated_at: None,
+                })
+                .collect();
+            Ok(Fetched {
+                quotes,
+                inactive: vec![],
+            })
+        }
+
+        async fn history(
+            &self,
+            _coin: CoinRef<'_>,
+            _fiat: &str,
+            _days: u32,
+        ) -> anyhow::Result<Vec<PricePoint>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let price = self.price.ok_or_else(|| anyhow!("{} is down", self.name))?;
+            Ok(vec![PricePoint {
+                at: Utc::now(),
+                price,
+            }])
+        }
+    }
+
+    const BTC: &[CoinRef] = &[CoinRef {
+        id: "bitcoin",
+        symbol: "btc",
+    }];
+
+    fn prices(fetched: Fetched) -> Vec<f64> {
+        fetched.quotes.into_iter().map(|q| q.price).collect()
+    }
+
+    fn providers_of(providers: Vec<Box<dyn QuoteProvider>>) -> Providers {
+        Providers::new(providers, DEFAULT_INACTIVE_AFTER)
+    }
+
+    #[test]
+    async fn test_failover() {
+        let primary = Arc::new(Fake::new("primary", None));
+        let secondary = Arc::new(Fake::new("secondary", Some(2.0)));
+        let providers = providers_of(vec![
+            Box::new(Arc::clone(&primary)),
+            Box::new(Arc::clone(&secondary)),
+        ]);
+
+        for _ in 0..MAX_FAILURES {
+            assert_eq!(
+                prices(providers.fetch(BTC, "eur").await.unwrap()),
+                vec![2.0]
+            );
+        }
+        assert_eq!(primary.calls.load(Ordering::SeqCst), 3);
+
+        assert_eq!(
+            prices(providers.fetch(BTC, "eur").await.unwrap()),
+            vec![2.0]
+        );
+        assert_eq!(
+            primary.calls.load(Ordering::SeqCst),
+            3,
+            "skipped once unhealthy"
+        );
+        assert_eq!(secondary.calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    async fn test_history_failover() {
+        let primary = Arc::new(Fake::new("primary", None));
+        let secondary = Arc::new(Fake::new("secondary", Some(2.0)));
+        let providers = providers_of(vec![
+            Box::new(Arc::clone(&primary)),
+            Box::new(Arc::clone(&secondary)),
+        ]);
+        let history = providers.history(BTC[0], "eur", 7).await.unwrap();
+        assert_eq!(
+            history.iter().map(|p| p.price).collect::<Vec<_>>(),
+            vec![2.0]
+        );
+        assert_eq!(primary.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    async fn test_all_time_high() {
+        let only_quotes = Arc::new(Fake::new("only quotes", Some(2.0)));
+        let providers = providers_of(vec![Box::new(Arc::clone(&only_quotes))]);
+        let err = providers.all_time_high(BTC[0], "eur").await.unwrap_err();
+        assert_eq!(err.to_string(), "No quote provider can answer that");
+        assert_eq!(
+            only_quotes.calls.load(Ordering::SeqCst),
+            0,
+            "not asked, nor counted as failing"
+        );
+    }
+
+    #[test]
+    async fn test_all_failing() {
+        let primary = Arc::new(Fake::new("primary", None));
+        let providers = providers_of(vec![Box::new(Arc::clone(&primary))]);
+        for _ in 0..MAX_FAILURES + 1 {
+            let err = providers.fetch(BTC, "eur").await.unwrap_err();
+            assert_eq!(err.to_string(), "Every quote provider failed");
+        }
+        assert_eq!(
+            primary.calls.load(Ordering::SeqCst),
+            4,
+            "still tried when every provider is unhealthy"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeout() {
+        let primary = Arc::new(Fake {
+            hangs: true,
+            ..Fake::new("primary", Some(1.0))
+        });
+        let secondary = Arc::new(Fake::new("secondary", Some(2.0)));
+        let providers = providers_of(vec![
+            Box::new(Arc::clone(&primary)),
+            Box::new(Arc::clone(&secondary)),
+        ]);
+        assert_eq!(
+            prices(providers.fetch(BTC, "eur").await.unwrap()),
+            vec![2.0]
+        );
+    }
+
+    #[test]
+    async fn test_empty_answer() {
+        let primary = Arc::new(Fake {
+            empty: true,
+            ..Fake::new("primary", Some(1.0))
+        });
+        let secondary = Arc::new(Fake::new("secondary", Some(2.0)));
+        let providers = providers_of(vec![
+            Box::new(Arc::clone(&primary)),
+            Box::new(Arc::clone(&secondary)),
+        ]);
+        assert_eq!(
+            prices(providers.fetch(BTC, "eur").await.unwrap()),
+            vec![2.0]
+        );
+        assert_eq!(primary.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    async fn test_retire_stale() {
+        let now = Utc.ymd(2024, 5, 8).and_hms(12, 0, 0);
+        let quote = |id: &str, updated_at| FetchedQuote {
+            id: id.to_string(),
+            price: 1.0,
+            change_24h: None,
+            updated_at,
+        };
+        let fetched = Fetched {
+            quotes: vec![
+                quote("bitcoin", Some(now - chrono::Duration::minutes(2))),
+                quote("ripple", None),
+                quote("terra-luna", Some(Utc.ymd(2023, 6, 1).and_hms(8, 0, 0))),
+            ],
+            inactive: vec![Inactive {
+                id: "delisted".to_string(),
+                last_trade: None,
+            }],
+        };
+        assert_eq!(
+            fetched.retire_stale_at(Duration::from_secs(24 * 60 * 60), now),
+            Fetched {
+                quotes: vec![
+                    quote("bitcoin", Some(now - chrono::Duration::minutes(2))),
+                    quote("ripple", None),
+                ],
+                inactive: vec![
+                    Inactive {
+                        id: "delisted".to_string(),
+                        last_trade: None,
+                    },
+                    Inactive {
+                        id: "terra-luna".to_string(),
+                        last_trade: Some(Utc.ymd(2023, 6, 1).and_hms(8, 0, 0)),
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    async fn test_health() {
+        let mut health = Health::default();
+        let t0 = Instant::now();
+        health.failed_at(t0);
+        health.failed_at(t0);
+        assert!(health.is_healthy_at(t0));
+        health.succeeded();
+        for _ in 0..MAX_FAILURES {
+            health.failed_at(t0);
+        }
+        assert!(!health.is_healthy_at(t0));
+        assert!(!health.is_healthy_at(t0 + UNHEALTHY_COOLDOWN - Duration::from_secs(1)));
+        assert!(health.is_healthy_at(t0 + UNHEALTHY_COOLDOWN));
+    }
+
+    #[test]
+    async fn test_build() {
+        let client = Client::new();
+        let names = |providers: Vec<Box<dyn QuoteProvider>>| {
+            providers.iter().map(|p| p.name()).collect::<Vec<_>>()
+        };
+        assert_eq!(names(build(&client, None).unwrap()), DEFAULT_PROVIDERS);
+
+        let settings = vec![ProviderSettings {
+            name: "kraken".to_string(),
+            api_key: None,
+        }];
+        assert_eq!(
+            names(build(&client, Some(&settings)).unwrap()),
+            vec!["kraken"]
+        );
+
+        let settings = vec![ProviderSettings {
+            name: "binance".to_string(),
+            api_key: None,
+        }];
+        assert_eq!(
+            build(&client, Some(&settings)).unwrap_err().to_string(),
+            "Unknown crypto provider binance, the known ones are coingecko, kraken"
+        );
+        assert!(build(&client, Some(&[])).is_err());
+    }
+}
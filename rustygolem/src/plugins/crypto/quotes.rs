@@ -0,0 +1,374 @@
+use super::fiat::Fiat;
+use super::providers::Inactive;
+use crate::utils::numbers::decimal_from_f64;
+use plugin_core::TokenBucket;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+/// Calls made on behalf of the users. The hourly rates, the announcements
+/// and the daily listing are scheduled, they don't count.
+pub const DEFAULT_CALLS_PER_MINUTE: u32 = 10;
+
+/// Younger quotes are given without their age
+const SHOW_AGE_AFTER: Duration = Duration::from_secs(30);
+
+/// Older quotes are evicted, even an answer marked outdated would mislead.
+/// More than the hour between two fetches of the tracked coins.
+const MAX_STALENESS: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// A coin the providers couldn't quote isn't asked for again before that,
+/// so that repeating the command doesn't hammer them
+const FAILURE_TTL: Duration = Duration::from_secs(30);
+
+/// The budget is global, a single bucket
+const BUDGET_KEY: &str = "providers";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quote {
+    pub price: Decimal,
+    /// in percents
+    pub change_24h: Option<f32>,
+    pub at: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cached {
+    pub quote: Quote,
+    /// older than the TTL
+    pub stale: bool,
+}
+
+impl Cached {
+    /// Like `(2 min ago)`, None for a recent quote
+    pub fn age_note(&self, now: Instant) -> Option<String> {
+        let age = format_age(now.saturating_duration_since(self.quote.at));
+        if self.stale {
+            Some(format!("(outdated, {age} ago)"))
+        } else if now.saturating_duration_since(self.quote.at) > SHOW_AGE_AFTER {
+            Some(format!("({age} ago)"))
+        } else {
+            None
+        }
+    }
+}
+
+/// Like `40s`, `2 min` or `3 h`
+pub fn format_age(age: Duration) -> String {
+    match age.as_secs() {
+        s if s < 60 => format!("{s}s"),
+        s if s < 60 * 60 => format!("{} min", s / 60),
+        s => format!("{} h", s / (60 * 60)),
+    }
+}
+
+/// The last quotes fetched, by (coingecko id, fiat), and the budget of
+/// calls to the API
+pub struct QuoteCache {
+    ttl: Duration,
+    quotes: Mutex<HashMap<(String, String), Quote>>,
+    /// the coins without an active market, by coingecko id, with when it
+    /// was found out
+    inactive: Mutex<HashMap<String, (Inactive, Instant)>>,
+    /// when the lookup of a (coingecko id, fiat) last failed
+    failed: Mutex<HashMap<(String, String), Instant>>,
+    budget: TokenBucket,
+}
+
+impl QuoteCache {
+    pub fn new(ttl: Duration, calls_per_minute: u32) -> Self {
+        QuoteCache {
+            ttl,
+            quotes: Mutex::new(HashMap::new()),
+            inactive: Mutex::new(HashMap::new()),
+            failed: Mutex::new(HashMap::new()),
+            budget: TokenBucket::per_minute(calls_per_minute),
+        }
+    }
+
+    /// (coingecko id, price, 24h change) fetched at `now`, in `fiat`.
+    /// Also evicts the quotes too old to be of any use.
+    pub fn record_at<I>(&self, prices: I, fiat: Fiat, now: Instant)
+    where
+        I: IntoIterator<Item = (String, f64, Option<f32>)>,
+    {
+        let mut quotes = self.quotes.lock().expect("quotes lock");
+        let mut inactive = self.inactive.lock().expect("inactive lock");
+        let mut failed = self.failed.lock().expect("failed lock");
+        quotes.retain(|_, quote| now.saturating_duration_since(quote.at) <= MAX_STALENESS);
+        for (id, price, change_24h) in prices {
+            match decimal_from_f64(price) {
+                Some(price) => {
+                    inactive.remove(&id);
+                    failed.remove(&(id.clone(), fiat.code().to_string()));
+                    let quote = Quote {
+                        price,
+                        change_24h,
+                        at: now,
+                    };
+                    quotes.insert((id, fiat.code().to_string()), quote);
+                }
+                None => log::warn!("Ignoring the price {price} of {id}"),
+            }
+        }
+    }
+
+    /// The coins found without an active market at `now`. Their quotes
+    /// are dropped in every fiat, an old price would mislead.
+    pub fn record_inactive_at(&self, coins: &[Inactive], now: Instant) {
+        let mut quotes = self.quotes.lock().expect("quotes lock");
+        let mut inactive = self.inactive.lock().expect("inactive lock");
+        inactive.retain(|_, (_, at)| now.saturating_duration_since(*at) <= MAX_STALENESS);
+        for coin in coins {
+            quotes.retain(|(id, _), _| *id != coin.id);
+            inactive.insert(coin.id.clone(), (coin.clone(), now));
+        }
+    }
+
+    /// Whether the coin was found without an active market lately
+    pub fn inactive_at(&self, id: &str, now: Instant) -> Option<Inactive> {
+        let inactive = self.inactive.lock().expect("inactive lock");
+        let (coin, at) = inactive.get(id)?;
+        (now.saturating_duration_since(*at) <= MAX_STALENESS).then(|| coin.clone())
+    }
+
+    /// The coins the providers couldn't quote in `fiat` at `now`, whether
+    /// they failed or didn't know them
+    pub fn record_failed_at(&self, ids: &[&str], fiat: Fiat, now: Instant) {
+        let mut failed = self.failed.lock().expect("failed lock");
+        failed.retain(|_, at| now.saturating_duration_since(*at) < FAILURE_TTL);
+        for id in ids {
+            failed.insert((id.to_string(), fiat.code().to_string()), now);
+        }
+    }
+
+    /// Whether the lookup of the coin in `fiat` failed lately
+    pub fn failed_at(&self, id: &str, fiat: Fiat, now: Instant) -> bool {
+        let failed = self.failed.lock().expect("failed lock");
+        failed
+            .get(&(id.to_string(), fiat.code().to_string()))
+            .map_or(false, |at| now.saturating_duration_since(*at) < FAILURE_TTL)
+    }
+
+    /// The cached quotes of the given coins in `fiat`, stale or not. Missing
+    /// when never fetched, or evicted.
+    pub fn lookup_at(&self, ids: &[&str], fiat: Fiat, now: Instant) -> HashMap<String, Cached> {
+        let quotes = self.quotes.lock().expect("quotes lock");
+        ids.iter()
+            .filter_map(|id| {
+                let quote = *quotes.get(&(id.to_string(), fiat.code().to_string()))?;
+                let age = now.saturating_duration_since(quote.at);
+                (age <= MAX_STALENESS).then(|| {
+                    let stale = age > self.ttl;
+                    (id.to_string(), Cached { quote, stale })
+                })
+            })
+            .collect()
+    }
+
+    /// Whether a call to the API can be made, and count it
+    pub fn spend_at(&self, now: Instant) -> bool {
+        self.budget.check_at(BUDGET_KEY, now)
+    }
+}
+
+/// The coins which should be fetched again
+pub fn needs_fetch<'a>(ids: &[&'a str], cached: &HashMap<String, Cached>) -> Vec<&'a str> {
+    ids.iter()
+        .copied()
+        .filter(|id| cached.get(*id).map_or(true, |c| c.stale))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::str::FromStr;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    fn quote(price: &str, at: Instant) -> Quote {
+        Quote {
+            price: Decimal::from_str(price).unwrap(),
+            change_24h: Some(1.5),
+            at,
+        }
+    }
+
+    #[test]
+    async fn test_ttl() {
+        let cache = QuoteCache::new(secs(60), 10);
+        let t0 = Instant::now();
+        cache.record_at(
+            [
+                ("bitcoin".to_string(), 64230.12, Some(1.5)),
+                ("dogecoin".to_string(), 0.06, Some(1.5)),
+            ],
+            Fiat::EUR,
+            t0,
+        );
+        let ids = ["bitcoin", "dogecoin", "ethereum"];
+
+        let cached = cache.lookup_at(&ids, Fiat::EUR, t0 + secs(60));
+        assert_eq!(
+            cached.get("dogecoin"),
+            Some(&Cached {
+                quote: quote("0.06", t0),
+                stale: false
+            })
+        );
+        assert_eq!(
+            needs_fetch(&ids, &cached),
+            vec!["ethereum"],
+            "never fetched"
+        );
+
+        let cached = cache.lookup_at(&ids, Fiat::EUR, t0 + secs(61));
+        assert_eq!(
+            cached.get("bitcoin"),
+            Some(&Cached {
+                quote: quote("64230.12", t0),
+                stale: true
+            })
+        );
+        assert_eq!(needs_fetch(&ids, &cached), ids.to_vec());
+
+        let usd = Fiat::parse("usd").unwrap();
+        assert_eq!(
+            cache.lookup_at(&ids, usd, t0),
+            HashMap::new(),
+            "cached by fiat"
+        );
+    }
+
+    #[test]
+    async fn test_eviction() {
+        let cache = QuoteCache::new(secs(60), 10);
+        let t0 = Instant::now();
+        cache.record_at([("bitcoin".to_string(), 64230.0, None)], Fiat::EUR, t0);
+        assert_eq!(
+            cache
+                .lookup_at(&["bitcoin"], Fiat::EUR, t0 + MAX_STALENESS)
+                .len(),
+            1,
+            "outdated but still usable"
+        );
+        let later = t0 + MAX_STALENESS + secs(1);
+        assert_eq!(
+            cache.lookup_at(&["bitcoin"], Fiat::EUR, later),
+            HashMap::new()
+        );
+
+        cache.record_at([("dogecoin".to_string(), 0.06, None)], Fiat::EUR, later);
+        assert_eq!(
+            cache.quotes.lock().unwrap().keys().collect::<Vec<_>>(),
+            vec![&("dogecoin".to_string(), "eur".to_string())],
+            "evicted when recording new quotes"
+        );
+    }
+
+    #[test]
+    async fn test_inactive() {
+        let cache = QuoteCache::new(secs(60), 10);
+        let t0 = Instant::now();
+        let luna = Inactive {
+            id: "terra-luna".to_string(),
+            last_trade: None,
+        };
+        cache.record_at([("terra-luna".to_string(), 80.0, None)], Fiat::EUR, t0);
+        let usd = Fiat::parse("usd").unwrap();
+        cache.record_at([("terra-luna".to_string(), 86.0, None)], usd, t0);
+        cache.record_inactive_at(&[luna.clone()], t0 + secs(10));
+        assert_eq!(
+            cache.lookup_at(&["terra-luna"], Fiat::EUR, t0 + secs(10)),
+            HashMap::new(),
+            "the old price is dropped"
+        );
+        assert_eq!(
+            cache.lookup_at(&["terra-luna"], usd, t0 + secs(10)),
+            HashMap::new(),
+            "in every fiat"
+        );
+        assert_eq!(
+            cache.inactive_at("terra-luna", t0 + secs(10)),
+            Some(luna.clone())
+        );
+        assert_eq!(cache.inactive_at("bitcoin", t0 + secs(10)), None);
+        assert_eq!(
+            cache.inactive_at("terra-luna", t0 + secs(10) + MAX_STALENESS + secs(1)),
+            None,
+            "forgotten after a while"
+        );
+
+        cache.record_at(
+            [("terra-luna".to_string(), 0.5, None)],
+            Fiat::EUR,
+            t0 + secs(20),
+        );
+        assert_eq!(
+            cache.inactive_at("terra-luna", t0 + secs(20)),
+            None,
+            "traded again"
+        );
+    }
+
+    #[test]
+    async fn test_failed() {
+        let cache = QuoteCache::new(secs(60), 10);
+        let t0 = Instant::now();
+        cache.record_failed_at(&["bitcoin"], Fiat::EUR, t0);
+        assert!(cache.failed_at("bitcoin", Fiat::EUR, t0 + secs(29)));
+        assert!(!cache.failed_at("bitcoin", Fiat::EUR, t0 + FAILURE_TTL));
+        let usd = Fiat::parse("usd").unwrap();
+        assert!(!cache.failed_at("bitcoin", usd, t0), "by fiat");
+
+        cache.record_at(
+            [("bitcoin".to_string(), 64230.0, None)],
+            Fiat::EUR,
+            t0 + secs(10),
+        );
+        assert!(
+            !cache.failed_at("bitcoin", Fiat::EUR, t0 + secs(10)),
+            "quoted since"
+        );
+    }
+
+    #[test]
+    async fn test_budget() {
+        let cache = QuoteCache::new(secs(60), 2);
+        let t0 = Instant::now();
+        assert!(cache.spend_at(t0));
+        assert!(cache.spend_at(t0));
+        assert!(!cache.spend_at(t0), "exhausted");
+        assert!(!cache.spend_at(t0 + secs(29)));
+        assert!(cache.spend_at(t0 + secs(30)), "one call every 30s");
+    }
+
+    #[test]
+    async fn test_age_note() {
+        let t0 = Instant::now();
+        let cached = |stale| Cached {
+            quote: quote("1", t0),
+            stale,
+        };
+        assert_eq!(cached(false).age_note(t0 + secs(30)), None);
+        assert_eq!(
+            cached(false).age_note(t0 + secs(31)),
+            Some("(31s ago)".to_string())
+        );
+        assert_eq!(
+            cached(false).age_note(t0 + secs(150)),
+            Some("(2 min ago)".to_string())
+        );
+        assert_eq!(
+            cached(true).age_note(t0 + secs(720)),
+            Some("(outdated, 12 min ago)".to_string())
+        );
+    }
+}
@@ -1,130 +1,773 @@
-#![allow(clippy::upper_case_acronyms)]
-use plugin_core::{self, Plugin, Result, Initialised};
 use async_trait::async_trait;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, Local, Locale, TimeZone, Utc};
+use chrono_tz::Tz;
 use irc::proto::{Command, Message};
-use nom::branch::alt;
-use nom::bytes::complete::{is_not, tag};
-use nom::character::complete::{char, multispace0, multispace1};
-use nom::combinator::{all_consuming, flat_map, map, opt, recognize};
-use nom::sequence::{delimited, pair, preceded, terminated};
+use nom::bytes::complete::is_not;
+use nom::character::complete::{char, multispace0};
+use nom::combinator::all_consuming;
+use nom::sequence::{delimited, terminated};
 use nom::Finish;
 use nom::IResult;
-
+use plugin_core::{self, CaseMapping, Initialised, MsgCtx, Outbound, Plugin, Result, TokenBucket};
 use republican_calendar::RepublicanDate;
+use serde::Deserialize;
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
+
+use crate::build_info::BUILD_INFO;
+use crate::utils::time::parse_timezone;
+
+/// Answered to CTCP SOURCE unless the config says otherwise
+pub const SOURCE_URL: &str = "https://github.com/CoucouInc/rustygolem";
+
+/// Replies to the CTCP queries of each nick, and of everyone, per period
+pub const DEFAULT_MAX_REPLIES_PER_NICK: u32 = 3;
+pub const DEFAULT_MAX_REPLIES: u32 = 20;
+pub const DEFAULT_FLOOD_PERIOD: Duration = Duration::from_secs(30);
+
+/// Told to the nicks offering a DCC chat or file
+pub const DEFAULT_DCC_REFUSAL: &str = "This bot does not accept DCC";
+
+/// Like `Sat, 01 Mar 2025 14:03:12 +0100`
+pub const DEFAULT_TIME_FORMAT: &str = "%a, %d %b %Y %H:%M:%S %z";
+
+/// The `ctcp` section of the golem config
+#[derive(Deserialize)]
+struct Settings {
+    /// where the code of the golem is
+    #[serde(default = "default_source")]
+    source: String,
+    /// answered to CTCP FINGER
+    #[serde(default = "default_finger")]
+    finger: String,
+    /// before the build info in the reply to CTCP VERSION
+    #[serde(default)]
+    version_prefix: Option<String>,
+    /// of the reply to CTCP TIME, an IANA name like Europe/Paris,
+    /// the one of the system by default
+    #[serde(default)]
+    timezone: Option<String>,
+    /// strftime-like, see the chrono crate
+    #[serde(default = "default_time_format")]
+    time_format: String,
+    /// for the names of the days and months, like fr_FR, english by default
+    #[serde(default)]
+    time_locale: Option<String>,
+    /// the queries beyond these are ignored, not to be flooded off the network
+    #[serde(default = "default_max_replies_per_nick")]
+    max_replies_per_nick: u32,
+    #[serde(default = "default_max_replies")]
+    max_replies: u32,
+    #[serde(default = "default_flood_period_secs")]
+    flood_period_secs: u64,
+    /// tell the DCC offers that they're refused, instead of ignoring them
+    #[serde(default = "default_refuse_dcc")]
+    refuse_dcc: bool,
+    #[serde(default = "default_dcc_refusal")]
+    dcc_refusal: String,
+}
+
+impl Settings {
+    fn flood(&self) -> anyhow::Result<Flood> {
+        if self.max_replies_per_nick == 0 || self.max_replies == 0 || self.flood_period_secs == 0 {
+            return Err(anyhow!(
+                "max_replies_per_nick, max_replies and flood_period_secs of the ctcp config must be positive"
+            ));
+        }
+        Ok(Flood::new(
+            self.max_replies_per_nick,
+            self.max_replies,
+            Duration::from_secs(self.flood_period_secs),
+        ))
+    }
+
+    fn clock(&self) -> anyhow::Result<Clock> {
+        Clock::new(
+            self.timezone.as_deref(),
+            &self.time_format,
+            self.time_locale.as_deref(),
+        )
+    }
+}
+
+fn default_source() -> String {
+    SOURCE_URL.to_string()
+}
+
+fn default_max_replies_per_nick() -> u32 {
+    DEFAULT_MAX_REPLIES_PER_NICK
+}
 
-pub struct Ctcp {}
+fn default_max_replies() -> u32 {
+    DEFAULT_MAX_REPLIES
+}
+
+fn default_flood_period_secs() -> u64 {
+    DEFAULT_FLOOD_PERIOD.as_secs()
+}
+
+fn default_refuse_dcc() -> bool {
+    true
+}
+
+fn default_dcc_refusal() -> String {
+    DEFAULT_DCC_REFUSAL.to_string()
+}
+
+fn default_time_format() -> String {
+    DEFAULT_TIME_FORMAT.to_string()
+}
+
+fn default_finger() -> String {
+    "rustygolem, a golem made of rust".to_string()
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            source: default_source(),
+            finger: default_finger(),
+            version_prefix: None,
+            timezone: None,
+            time_format: default_time_format(),
+            time_locale: None,
+            max_replies_per_nick: default_max_replies_per_nick(),
+            max_replies: default_max_replies(),
+            flood_period_secs: default_flood_period_secs(),
+            refuse_dcc: default_refuse_dcc(),
+            dcc_refusal: default_dcc_refusal(),
+        }
+    }
+}
+
+pub struct Ctcp {
+    source: String,
+    finger: String,
+    version_prefix: Option<String>,
+    clock: Clock,
+    flood: Flood,
+    /// None to ignore the DCC offers
+    dcc_refusal: Option<String>,
+}
+
+/// How many CTCP queries get a reply, from each nick first, then from everyone
+struct Flood {
+    per_nick: TokenBucket,
+    global: TokenBucket,
+}
+
+impl Flood {
+    fn new(max_per_nick: u32, max: u32, period: Duration) -> Self {
+        Flood {
+            per_nick: TokenBucket::new(max_per_nick, period),
+            global: TokenBucket::new(max, period),
+        }
+    }
+
+    /// The queries of a flooding nick don't use up the replies of everyone else
+    fn allow_at(&self, casemapping: CaseMapping, nick: &str, now: Instant) -> bool {
+        self.per_nick.check_at(&casemapping.normalize(nick), now) && self.global.check_at("", now)
+    }
+}
+
+enum Zone {
+    System,
+    Named(Tz),
+}
+
+/// Tells the time for CTCP TIME
+struct Clock {
+    zone: Zone,
+    format: String,
+    locale: Option<Locale>,
+}
+
+impl Clock {
+    /// Errors on the names not known, and the invalid formats
+    fn new(timezone: Option<&str>, format: &str, locale: Option<&str>) -> anyhow::Result<Self> {
+        let zone = match timezone {
+            None => Zone::System,
+            Some(name) => Zone::Named(parse_timezone("ctcp", name)?),
+        };
+        if StrftimeItems::new(format).any(|item| item == Item::Error) {
+            return Err(anyhow!("Invalid time_format {format:?} in the ctcp config"));
+        }
+        let locale = match locale {
+            None => None,
+            Some(name) => Some(Locale::try_from(name).map_err(|_| {
+                anyhow!("Unknown time_locale {name} in the ctcp config, like fr_FR")
+            })?),
+        };
+        Ok(Clock {
+            zone,
+            format: format.to_string(),
+            locale,
+        })
+    }
+
+    fn at(&self, now: DateTime<Utc>) -> String {
+        match self.zone {
+            Zone::System => self.format(now.with_timezone(&Local)),
+            Zone::Named(tz) => self.format(now.with_timezone(&tz)),
+        }
+    }
+
+    /// The time, then the republican date of the day in the same timezone
+    fn tell(&self, now: DateTime<Utc>) -> Result<String> {
+        let day = match self.zone {
+            Zone::System => now.with_timezone(&Local).naive_local().date(),
+            Zone::Named(tz) => now.with_timezone(&tz).naive_local().date(),
+        };
+        let rd = time::Date::from_ordinal_date(day.year(), day.ordinal() as u16)
+            .map_err(|_| republican_calendar::Error::OutOfRange)
+            .and_then(RepublicanDate::try_from)
+            .map_err(|e| plugin_core::Error::Synthetic(e.to_string()))?;
+        Ok(format!("{} - {rd}", self.at(now)))
+    }
+
+    fn format<Z: TimeZone>(&self, time: DateTime<Z>) -> String
+    where
+        Z::Offset: Display,
+    {
+        match self.locale {
+            Some(locale) => time.format_localized(&self.format, locale).to_string(),
+            None => time.format(&self.format).to_string(),
+        }
+    }
+}
+
+/// What a CTCP query is answered with
+#[derive(Debug, PartialEq)]
+enum Reply {
+    /// the same verb, with this argument
+    Ctcp(Option<String>),
+    /// a regular notice
+    Notice(String),
+    Nothing,
+}
+
+/// The reply to a CTCP query, given the argument of the query
+type Handler = fn(&Ctcp, Option<&str>) -> Result<Reply>;
+
+/// Every CTCP verb answered, and whether CLIENTINFO lists it, in its order.
+/// The other ones are ignored.
+const HANDLERS: &[(&str, Handler, bool)] = &[
+    ("CLIENTINFO", clientinfo, true),
+    ("DCC", dcc, false),
+    ("FINGER", finger, true),
+    ("PING", ping, true),
+    ("SOURCE", source, true),
+    ("TIME", time, true),
+    ("VERSION", version, true),
+];
 
 #[async_trait]
 impl Plugin for Ctcp {
-    async fn init(_config: &plugin_core::Config) -> Result<Initialised> {
-        Ok(Initialised::from(Ctcp {}))
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        let settings: Settings = config.plugin_section("ctcp")?.unwrap_or_default();
+        settings.clock()?;
+        settings.flood()?;
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let settings: Settings = config.plugin_section("ctcp")?.unwrap_or_default();
+        Ok(Initialised::from(Ctcp {
+            clock: settings.clock()?,
+            flood: settings.flood()?,
+            dcc_refusal: Some(settings.dcc_refusal).filter(|_| settings.refuse_dcc),
+            source: settings.source,
+            finger: settings.finger,
+            version_prefix: settings.version_prefix,
+        }))
     }
 
     fn get_name(&self) -> &'static str {
         "ctcp"
     }
 
-    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
-        in_msg(msg).await
+    async fn in_message_ctx(&self, ctx: &MsgCtx, msg: &Message) -> Result<Option<Outbound>> {
+        in_msg(self, msg, ctx.casemapping).await
     }
 }
 
-async fn in_msg(msg: &Message) -> Result<Option<Message>> {
-    let response_target = match msg.response_target() {
+async fn in_msg(
+    plugin: &Ctcp,
+    msg: &Message,
+    casemapping: CaseMapping,
+) -> Result<Option<Outbound>> {
+    // the queries sent to a channel are answered to their sender only
+    let nick = match msg.source_nickname() {
         None => return Ok(None),
-        Some(target) => target.to_string(),
+        Some(nick) => nick,
     };
 
     if let Command::PRIVMSG(_source, message) = &msg.command {
-        // 🤮 the error handling isn't great there
-        let command = match parse_command(message) {
+        let (verb, arg) = match parse_command(message) {
             Some(x) => x,
             None => return Ok(None),
         };
-        let msg = match command {
-            CtcpCmd::VERSION => "rustygolem".to_string(),
-            CtcpCmd::TIME => {
-                let now = time::OffsetDateTime::now_utc();
-                let fmt = time::macros::format_description!("[hour]:[minute]:[second]");
-                let rd = RepublicanDate::try_from(now.date())
-                    .map_err(|e| plugin_core::Error::Synthetic(e.to_string()))?;
-                format!("TIME {} UTC - {}", now.format(fmt).unwrap(), rd)
+        let handler = match HANDLERS.iter().find(|(name, _, _)| *name == verb) {
+            Some((_, handler, _)) => handler,
+            None => {
+                log::debug!("Ignoring unknown CTCP {verb}");
+                return Ok(None);
             }
-            CtcpCmd::PING(opt_arg) => {
-                let arg = opt_arg
-                    .map(|c| format!(" {}", c))
-                    .unwrap_or_else(|| "".to_string());
-                format!("PING{}", arg)
+        };
+        if !plugin.flood.allow_at(casemapping, nick, Instant::now()) {
+            log::debug!("Too many CTCP queries, ignoring {verb} from {nick}");
+            return Ok(None);
+        }
+        let reply = match handler(plugin, arg)? {
+            Reply::Ctcp(Some(reply)) => format!("\x01{verb} {reply}\x01"),
+            Reply::Ctcp(None) => format!("\x01{verb}\x01"),
+            Reply::Notice(reply) => {
+                log::info!("Told {nick} {reply:?} for their CTCP {verb}");
+                reply
             }
+            Reply::Nothing => return Ok(None),
         };
-
-        let irc_msg = Command::PRIVMSG(response_target, msg).into();
-        return Ok(Some(irc_msg));
+        // CTCP replies are notices, so that they're never answered in turn
+        return Ok(Some(Outbound::notice(nick, reply)));
     }
 
     Ok(None)
 }
 
+fn clientinfo(_plugin: &Ctcp, _arg: Option<&str>) -> Result<Reply> {
+    let verbs = HANDLERS
+        .iter()
+        .filter(|(_, _, listed)| *listed)
+        .map(|(name, _, _)| *name)
+        .collect::<Vec<_>>();
+    Ok(Reply::Ctcp(Some(verbs.join(" "))))
+}
+
+/// Never connected to, only logged and told so, as a regular notice
+fn dcc(plugin: &Ctcp, arg: Option<&str>) -> Result<Reply> {
+    let refusal = match &plugin.dcc_refusal {
+        Some(refusal) => refusal,
+        None => return Ok(Reply::Nothing),
+    };
+    match parse_dcc(arg.unwrap_or_default()) {
+        Some(offer) => log::info!(
+            "Refused DCC {} {:?} at {}:{}{}",
+            offer.kind,
+            offer.argument,
+            offer.address,
+            offer.port,
+            offer
+                .size
+                .map(|size| format!(", {size} bytes"))
+                .unwrap_or_default()
+        ),
+        None => log::info!("Refused malformed DCC: {arg:?}"),
+    }
+    Ok(Reply::Notice(refusal.clone()))
+}
+
+fn finger(plugin: &Ctcp, _arg: Option<&str>) -> Result<Reply> {
+    Ok(Reply::Ctcp(Some(plugin.finger.clone())))
+}
+
+fn ping(_plugin: &Ctcp, arg: Option<&str>) -> Result<Reply> {
+    Ok(Reply::Ctcp(arg.map(String::from)))
+}
+
+fn source(plugin: &Ctcp, _arg: Option<&str>) -> Result<Reply> {
+    Ok(Reply::Ctcp(Some(plugin.source.clone())))
+}
+
+fn time(plugin: &Ctcp, _arg: Option<&str>) -> Result<Reply> {
+    Ok(Reply::Ctcp(Some(plugin.clock.tell(Utc::now())?)))
+}
+
+fn version(plugin: &Ctcp, _arg: Option<&str>) -> Result<Reply> {
+    let version = match &plugin.version_prefix {
+        Some(prefix) => format!("{prefix} {BUILD_INFO}"),
+        None => BUILD_INFO.to_string(),
+    };
+    Ok(Reply::Ctcp(Some(version)))
+}
+
+/// The arguments of a CTCP DCC, like `SEND "a file.txt" 3232235777 5000 1024`
 #[derive(Debug, PartialEq)]
-enum CtcpCmd<'input> {
-    VERSION,
-    TIME,
-    PING(Option<&'input str>),
+struct DccOffer<'a> {
+    /// CHAT, SEND…
+    kind: &'a str,
+    /// the file name for SEND, usually chat for CHAT
+    argument: &'a str,
+    address: IpAddr,
+    /// 0 for a reverse DCC, where the golem would be the one listening
+    port: u16,
+    /// of the file, for SEND
+    size: Option<u64>,
+}
+
+fn parse_dcc(input: &str) -> Option<DccOffer<'_>> {
+    let (kind, rest) = input.trim().split_once(' ')?;
+    let rest = rest.trim_start();
+    // file names with spaces are quoted
+    let (argument, rest) = match rest.strip_prefix('"') {
+        Some(quoted) => quoted.split_once('"')?,
+        None => rest.split_once(' ')?,
+    };
+    let mut words = rest.split_whitespace();
+    // IPv4 addresses are given as a single number, IPv6 ones as is
+    let address = words.next()?;
+    let address = match address.parse::<u32>() {
+        Ok(long) => IpAddr::V4(Ipv4Addr::from(long)),
+        Err(_) => address.parse::<IpAddr>().ok()?,
+    };
+    let port = words.next()?.parse().ok()?;
+    let size = match words.next() {
+        Some(size) => Some(size.parse().ok()?),
+        None => None,
+    };
+    Some(DccOffer {
+        kind,
+        argument,
+        address,
+        port,
+        size,
+    })
 }
 
-fn parse_command(input: &str) -> Option<CtcpCmd<'_>> {
-    all_consuming(terminated(parse_ctcp, multispace0))(input)
+/// The verb and its argument, if any
+fn parse_command(input: &str) -> Option<(&str, Option<&str>)> {
+    let (_, payload) = all_consuming(terminated(parse_ctcp, multispace0))(input)
         .finish()
-        .map(|x| x.1)
-        .ok()
+        .ok()?;
+    match payload.split_once(' ') {
+        Some((verb, arg)) => Some((verb, Some(arg.trim()).filter(|arg| !arg.is_empty()))),
+        None => Some((payload, None)),
+    }
 }
 
-fn parse_ctcp(input: &str) -> IResult<&str, CtcpCmd<'_>> {
+fn parse_ctcp(input: &str) -> IResult<&str, &str> {
     let c = '\u{0001}';
+    delimited(char(c), is_not("\x01"), char(c))(input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn plugin() -> Ctcp {
+        Ctcp {
+            source: SOURCE_URL.to_string(),
+            finger: "nothing to see here".to_string(),
+            version_prefix: None,
+            clock: Clock::new(Some("Europe/Paris"), DEFAULT_TIME_FORMAT, None).unwrap(),
+            flood: Flood::new(100, 100, DEFAULT_FLOOD_PERIOD),
+            dcc_refusal: Some(DEFAULT_DCC_REFUSAL.to_string()),
+        }
+    }
+
+    async fn query_from(plugin: &Ctcp, nick: &str, line: &str) -> Option<Outbound> {
+        let msg = format!(":{nick}!~{nick}@localhost PRIVMSG golem :{line}\r\n")
+            .parse::<Message>()
+            .unwrap();
+        in_msg(plugin, &msg, CaseMapping::Rfc1459).await.unwrap()
+    }
+
+    async fn query_with(plugin: &Ctcp, line: &str) -> Option<Outbound> {
+        let msg = format!(":alice!~alice@localhost PRIVMSG golem :{line}\r\n")
+            .parse::<Message>()
+            .unwrap();
+        in_msg(plugin, &msg, CaseMapping::Rfc1459).await.unwrap()
+    }
+
+    async fn query(line: &str) -> Option<Outbound> {
+        query_with(&plugin(), line).await
+    }
+
+    #[test]
+    async fn test_channel_query() {
+        let msg = ":alice!~alice@localhost PRIVMSG #rust :\x01PING 42\x01\r\n"
+            .parse::<Message>()
+            .unwrap();
+        assert_eq!(
+            in_msg(&plugin(), &msg, CaseMapping::Rfc1459).await.unwrap(),
+            Some(Outbound::notice("alice", "\x01PING 42\x01")),
+            "not in the channel"
+        );
+    }
 
-    let raw_parse = delimited(char(c), is_not("\x01"), char(c));
-    map(
-        // sketchy flat_map there, there is likely a better combinator.
-        flat_map(raw_parse, move |i| move |_| ctcp_cmd(i)),
-        |x| x,
-    )(input)
-}
-
-fn ctcp_cmd(input: &str) -> IResult<&str, CtcpCmd> {
-    alt((
-        map(tag("VERSION"), |_| CtcpCmd::VERSION),
-        map(tag("TIME"), |_| CtcpCmd::TIME),
-        map(
-            pair(
-                tag("PING"),
-                opt(preceded(multispace1, recognize(is_not("\x01")))),
+    #[test]
+    async fn test_replies() {
+        for (line, reply) in [
+            (
+                "\x01CLIENTINFO\x01",
+                "\x01CLIENTINFO CLIENTINFO FINGER PING SOURCE TIME VERSION\x01",
             ),
-            |(_, arg)| CtcpCmd::PING(arg),
-        ),
-    ))(input)
-}
-
-// // ctcp feature is disabled so we can override the TIME to reply with
-// // the republican calendar (crucial feature right there).
-// fn handle_ctcp(client: &Arc<Mutex<Client>>, target: String, ctcp: CTCP) -> Result<()> {
-//     let msg = match ctcp {
-//         CTCP::VERSION => "VERSION rustygolem".to_string(),
-//         CTCP::TIME => {
-//             let now = Utc::now();
-//             let fmt = StrftimeItems::new("%H:%M:%S");
-//             let rd = RepublicanDate::try_from(now.naive_utc().date())?;
-//             format!("TIME {} UTC - {}", now.format_with_items(fmt), rd)
-//         }
-//         CTCP::PING(opt_arg) => {
-//             let arg = opt_arg
-//                 .map(|c| format!(" {}", c))
-//                 .unwrap_or_else(|| "".to_string());
-//             format!("PING{}", arg)
-//         }
-//     };
-//     {
-//         let client = client.lock().unwrap();
-//         client.send(Command::NOTICE(target, format!("\u{001}{}\u{001}", msg)))?;
-//     }
-//     Ok(())
-// }
+            (
+                "\x01SOURCE\x01",
+                "\x01SOURCE https://github.com/CoucouInc/rustygolem\x01",
+            ),
+            ("\x01FINGER\x01", "\x01FINGER nothing to see here\x01"),
+            ("\x01PING 1234 5678\x01", "\x01PING 1234 5678\x01"),
+            ("\x01PING\x01", "\x01PING\x01"),
+        ] {
+            assert_eq!(
+                query(line).await,
+                Some(Outbound::notice("alice", reply)),
+                "{line:?}"
+            );
+        }
+    }
+
+    #[test]
+    async fn test_version() {
+        let version = format!("\x01VERSION {BUILD_INFO}\x01");
+        assert!(
+            version.starts_with(&format!("\x01VERSION rustygolem {} (", BUILD_INFO.version)),
+            "{version:?}"
+        );
+        assert_eq!(
+            query("\x01VERSION\x01").await,
+            Some(Outbound::notice("alice", version))
+        );
+        let plugin = Ctcp {
+            version_prefix: Some("coucou bot".to_string()),
+            ..plugin()
+        };
+        assert_eq!(
+            query_with(&plugin, "\x01VERSION\x01").await,
+            Some(Outbound::notice(
+                "alice",
+                format!("\x01VERSION coucou bot {BUILD_INFO}\x01")
+            ))
+        );
+    }
+
+    #[test]
+    async fn test_time() {
+        match query("\x01TIME\x01").await {
+            Some(Outbound::Notice { target, text }) => {
+                assert_eq!(target, "alice");
+                assert!(
+                    text.starts_with("\x01TIME ") && text.ends_with('\x01'),
+                    "{text:?}"
+                );
+                assert!(
+                    text.contains(" +0100 - ") || text.contains(" +0200 - "),
+                    "{text:?}"
+                );
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    async fn test_clock_dst() {
+        let clock = Clock::new(Some("Europe/Paris"), DEFAULT_TIME_FORMAT, None).unwrap();
+        // clocks went from 02:00 to 03:00 in France
+        let switch = Utc.ymd(2025, 3, 30).and_hms(1, 0, 0);
+        assert_eq!(
+            clock.at(switch - chrono::Duration::seconds(1)),
+            "Sun, 30 Mar 2025 01:59:59 +0100"
+        );
+        assert_eq!(clock.at(switch), "Sun, 30 Mar 2025 03:00:00 +0200");
+        assert_eq!(
+            clock.at(Utc.ymd(2025, 10, 26).and_hms(1, 0, 0)),
+            "Sun, 26 Oct 2025 02:00:00 +0100",
+            "and back"
+        );
+    }
+
+    #[test]
+    async fn test_clock_republican() {
+        let clock = Clock::new(Some("Europe/Paris"), DEFAULT_TIME_FORMAT, None).unwrap();
+        let rd = RepublicanDate::try_from(time::macros::date!(2025 - 03 - 30)).unwrap();
+        assert_eq!(
+            clock.tell(Utc.ymd(2025, 3, 29).and_hms(23, 30, 0)).unwrap(),
+            format!("Sun, 30 Mar 2025 00:30:00 +0100 - {rd}"),
+            "already the next day in Paris"
+        );
+    }
+
+    #[test]
+    async fn test_clock_locale() {
+        let clock = Clock::new(
+            Some("America/Montreal"),
+            "%A %-d %B %Y, %H:%M (%Z)",
+            Some("fr_FR"),
+        )
+        .unwrap();
+        assert_eq!(
+            clock.at(Utc.ymd(2025, 3, 1).and_hms(13, 3, 12)),
+            "samedi 1 mars 2025, 08:03 (EST)"
+        );
+    }
+
+    #[test]
+    async fn test_clock_invalid() {
+        let err = |timezone, format, locale| {
+            Clock::new(timezone, format, locale)
+                .err()
+                .map(|err| err.to_string())
+        };
+        assert_eq!(
+            err(Some("Europe/Pari"), DEFAULT_TIME_FORMAT, None),
+            Some(
+                "Unknown timezone Europe/Pari in the ctcp config, expected an IANA name like \
+                 Europe/Paris. Did you mean Europe/Paris?"
+                    .to_string()
+            )
+        );
+        assert!(err(Some("Mars/Olympus_Mons"), DEFAULT_TIME_FORMAT, None).is_some());
+        assert_eq!(
+            err(None, "%H:%M %Q", None),
+            Some("Invalid time_format \"%H:%M %Q\" in the ctcp config".to_string())
+        );
+        assert!(err(None, DEFAULT_TIME_FORMAT, Some("klingon")).is_some());
+        assert_eq!(
+            err(None, DEFAULT_TIME_FORMAT, None),
+            None,
+            "the system timezone"
+        );
+    }
+
+    #[test]
+    async fn test_flood_per_nick() {
+        let flood = Flood::new(3, 20, Duration::from_secs(30));
+        let t0 = Instant::now();
+        let secs = Duration::from_secs;
+        let allow_at = |nick, at| flood.allow_at(CaseMapping::Rfc1459, nick, at);
+        for s in 0..3 {
+            assert!(allow_at("mallory[m]", t0 + secs(s)), "{s}s");
+        }
+        assert!(!allow_at("Mallory{M}", t0 + secs(3)), "dropped");
+        assert!(allow_at("alice", t0 + secs(3)), "per nick");
+        assert!(!allow_at("mallory[m]", t0 + secs(9)));
+        assert!(allow_at("mallory[m]", t0 + secs(10)), "a reply every 10s");
+        assert!(!allow_at("mallory[m]", t0 + secs(11)));
+    }
+
+    #[test]
+    async fn test_flood_global() {
+        let flood = Flood::new(3, 5, Duration::from_secs(30));
+        let t0 = Instant::now();
+        let allow_at = |nick, at| flood.allow_at(CaseMapping::Rfc1459, nick, at);
+        for nick in ["a", "b", "c", "d", "e"] {
+            assert!(allow_at(nick, t0), "{nick}");
+        }
+        assert!(!allow_at("f", t0), "the global ceiling");
+        for _ in 0..10 {
+            assert!(!allow_at("a", t0));
+        }
+        assert!(
+            allow_at("f", t0 + Duration::from_secs(6)),
+            "a flooding nick doesn't use up the replies of the others"
+        );
+    }
+
+    #[test]
+    async fn test_flood_silent() {
+        let plugin = Ctcp {
+            flood: Flood::new(3, 20, DEFAULT_FLOOD_PERIOD),
+            ..plugin()
+        };
+        for _ in 0..3 {
+            assert!(query_from(&plugin, "mallory", "\x01VERSION\x01")
+                .await
+                .is_some());
+        }
+        assert_eq!(
+            query_from(&plugin, "mallory", "\x01PING 42\x01").await,
+            None
+        );
+        assert_eq!(
+            query_from(&plugin, "alice", "\x01PING 42\x01").await,
+            Some(Outbound::notice("alice", "\x01PING 42\x01"))
+        );
+    }
+
+    #[test]
+    async fn test_parse_dcc() {
+        assert_eq!(
+            parse_dcc("CHAT chat 3232235777 5000"),
+            Some(DccOffer {
+                kind: "CHAT",
+                argument: "chat",
+                address: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)),
+                port: 5000,
+                size: None,
+            })
+        );
+        assert_eq!(
+            parse_dcc(r#"SEND "my cat.jpg" 2130706433 0 123456 42"#),
+            Some(DccOffer {
+                kind: "SEND",
+                argument: "my cat.jpg",
+                address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+                port: 0,
+                size: Some(123456),
+            }),
+            "a reverse DCC, with its token"
+        );
+        assert_eq!(
+            parse_dcc("SEND notes.txt ::1 6000 10").map(|offer| offer.address),
+            Some("::1".parse().unwrap())
+        );
+        for malformed in [
+            "",
+            "CHAT",
+            "CHAT chat",
+            "CHAT chat 3232235777",
+            "CHAT chat 4294967296 5000",
+            "CHAT chat 3232235777 65536",
+            r#"SEND "unterminated 3232235777 5000"#,
+            "SEND file.txt 3232235777 5000 lots",
+        ] {
+            assert_eq!(parse_dcc(malformed), None, "{malformed:?}");
+        }
+    }
+
+    #[test]
+    async fn test_dcc() {
+        assert_eq!(
+            query("\x01DCC SEND virus.exe 3232235777 5000 666\x01").await,
+            Some(Outbound::notice("alice", DEFAULT_DCC_REFUSAL))
+        );
+        assert_eq!(
+            query("\x01DCC garbage\x01").await,
+            Some(Outbound::notice("alice", DEFAULT_DCC_REFUSAL)),
+            "refused all the same"
+        );
+        let plugin = Ctcp {
+            dcc_refusal: None,
+            ..plugin()
+        };
+        assert_eq!(
+            query_with(&plugin, "\x01DCC CHAT chat 3232235777 5000\x01").await,
+            None,
+            "disabled"
+        );
+        assert_eq!(
+            query("\x01CLIENTINFO\x01").await,
+            Some(Outbound::notice(
+                "alice",
+                "\x01CLIENTINFO CLIENTINFO FINGER PING SOURCE TIME VERSION\x01"
+            )),
+            "not answered as a CTCP"
+        );
+    }
+
+    #[test]
+    async fn test_ignored() {
+        for line in [
+            "\x01ACTION waves\x01",
+            "\x01USERINFO\x01",
+            "\x01version\x01",
+            "VERSION",
+            "hello \x01VERSION\x01",
+        ] {
+            assert_eq!(query(line).await, None, "{line:?}");
+        }
+    }
+}
@@ -0,0 +1,4 @@
+mod notation;
+mod plugin;
+
+pub use plugin::Dice;
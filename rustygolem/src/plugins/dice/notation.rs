@@ -0,0 +1,333 @@
+use std::fmt::{self, Display};
+
+use nom::branch::alt;
+use nom::bytes::complete::tag_no_case;
+use nom::character::complete::{one_of, u32};
+use nom::combinator::{all_consuming, map, opt, value};
+use nom::sequence::{pair, preceded, tuple};
+use nom::{Finish, IResult};
+use rand::Rng;
+
+/// Rolled at once, across all the dice of a λroll
+pub const MAX_DICE: u32 = 100;
+pub const MAX_SIDES: u32 = 1000;
+
+/// The dice counted in the total
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keep {
+    Highest(u32),
+    Lowest(u32),
+    DropHighest(u32),
+    DropLowest(u32),
+}
+
+impl Keep {
+    fn count(self) -> u32 {
+        match self {
+            Keep::Highest(n) | Keep::Lowest(n) | Keep::DropHighest(n) | Keep::DropLowest(n) => n,
+        }
+    }
+}
+
+/// Like `4d6kh3+1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dice {
+    pub count: u32,
+    pub sides: u32,
+    pub keep: Option<Keep>,
+    pub modifier: i64,
+}
+
+impl Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.count != 1 {
+            write!(f, "{}", self.count)?;
+        }
+        write!(f, "d{}", self.sides)?;
+        match self.keep {
+            Some(Keep::Highest(n)) => write!(f, "kh{n}")?,
+            Some(Keep::Lowest(n)) => write!(f, "kl{n}")?,
+            Some(Keep::DropHighest(n)) => write!(f, "dh{n}")?,
+            Some(Keep::DropLowest(n)) => write!(f, "dl{n}")?,
+            None => (),
+        }
+        match self.modifier {
+            0 => Ok(()),
+            m if m > 0 => write!(f, "+{m}"),
+            m => write!(f, "{m}"),
+        }
+    }
+}
+
+/// `[count]d<sides>[kh|kl|dh|dl<n>][+|-<modifier>]`, like `d20`, `2d6+3` or `4d6kh3`
+fn dice(input: &str) -> IResult<&str, Dice> {
+    let keep = alt((
+        map(preceded(tag_no_case("kh"), u32), Keep::Highest),
+        map(preceded(tag_no_case("kl"), u32), Keep::Lowest),
+        map(preceded(tag_no_case("dh"), u32), Keep::DropHighest),
+        map(preceded(tag_no_case("dl"), u32), Keep::DropLowest),
+    ));
+    let sign = alt((value(1, one_of("+")), value(-1, one_of("-"))));
+    let modifier = map(pair(sign, u32), |(sign, n)| sign * i64::from(n));
+    map(
+        tuple((
+            opt(u32),
+            preceded(one_of("dD"), u32),
+            opt(keep),
+            opt(modifier),
+        )),
+        |(count, sides, keep, modifier)| Dice {
+            count: count.unwrap_or(1),
+            sides,
+            keep,
+            modifier: modifier.unwrap_or(0),
+        },
+    )(input)
+}
+
+/// The whitespace separated dice, None when one of them is malformed
+pub fn parse_dice(input: &str) -> Option<Vec<Dice>> {
+    let dice = input
+        .split_whitespace()
+        .map(|word| all_consuming(dice)(word).finish().ok().map(|(_, d)| d))
+        .collect::<Option<Vec<_>>>()?;
+    if dice.is_empty() || dice.iter().any(|d| d.count == 0 || d.sides == 0) {
+        return None;
+    }
+    Some(dice)
+}
+
+/// Why the dice can't be rolled, as told to the users
+pub fn check(dice: &[Dice]) -> Result<(), String> {
+    let count = dice.iter().map(|d| u64::from(d.count)).sum::<u64>();
+    if count > u64::from(MAX_DICE) {
+        return Err(format!("That's too many dice, {MAX_DICE} at most"));
+    }
+    if let Some(d) = dice.iter().find(|d| d.sides > MAX_SIDES) {
+        return Err(format!(
+            "A d{} is too round to roll, {MAX_SIDES} sides at most",
+            d.sides
+        ));
+    }
+    if let Some(d) = dice
+        .iter()
+        .find(|d| d.keep.map_or(false, |keep| keep.count() > d.count))
+    {
+        return Err(format!("{d} keeps or drops more dice than it rolls"));
+    }
+    Ok(())
+}
+
+/// Each die of the dice, between 1 and its sides
+pub fn roll<R: Rng + ?Sized>(dice: &Dice, rng: &mut R) -> Vec<u32> {
+    (0..dice.count)
+        .map(|_| rng.gen_range(1..=dice.sides))
+        .collect()
+}
+
+/// Whether each of the rolls counts in the total. Among equal rolls, the
+/// first ones are the lowest.
+pub fn kept(rolls: &[u32], keep: Option<Keep>) -> Vec<bool> {
+    let keep = match keep {
+        Some(keep) => keep,
+        None => return vec![true; rolls.len()],
+    };
+    let mut ascending = (0..rolls.len()).collect::<Vec<_>>();
+    ascending.sort_by_key(|&i| (rolls[i], i));
+    let n = (keep.count() as usize).min(rolls.len());
+    let lowest = &ascending[..n];
+    let highest = &ascending[rolls.len() - n..];
+    (0..rolls.len())
+        .map(|i| match keep {
+            Keep::Highest(_) => highest.contains(&i),
+            Keep::Lowest(_) => lowest.contains(&i),
+            Keep::DropHighest(_) => !highest.contains(&i),
+            Keep::DropLowest(_) => !lowest.contains(&i),
+        })
+        .collect()
+}
+
+/// Like `2d6+3: [4, 2] + 3 = 9`, with the dropped dice in parentheses
+pub fn format(dice: &Dice, rolls: &[u32]) -> String {
+    let kept = kept(rolls, dice.keep);
+    let shown = rolls
+        .iter()
+        .zip(&kept)
+        .map(|(roll, kept)| {
+            if *kept {
+                roll.to_string()
+            } else {
+                format!("({roll})")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let total = rolls
+        .iter()
+        .zip(&kept)
+        .filter(|(_, kept)| **kept)
+        .map(|(roll, _)| i64::from(*roll))
+        .sum::<i64>()
+        + dice.modifier;
+    match dice.modifier {
+        0 => format!("{dice}: [{shown}] = {total}"),
+        m if m > 0 => format!("{dice}: [{shown}] + {m} = {total}"),
+        m => format!("{dice}: [{shown}] - {} = {total}", -m),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn d(count: u32, sides: u32, keep: Option<Keep>, modifier: i64) -> Dice {
+        Dice {
+            count,
+            sides,
+            keep,
+            modifier,
+        }
+    }
+
+    #[test]
+    async fn test_parse_dice() {
+        for (input, expected) in [
+            ("d20", vec![d(1, 20, None, 0)]),
+            ("D20", vec![d(1, 20, None, 0)]),
+            ("2d6+3", vec![d(2, 6, None, 3)]),
+            ("1d20-2", vec![d(1, 20, None, -2)]),
+            ("4d6kh3", vec![d(4, 6, Some(Keep::Highest(3)), 0)]),
+            ("4d6KH3", vec![d(4, 6, Some(Keep::Highest(3)), 0)]),
+            ("2d20kl1", vec![d(2, 20, Some(Keep::Lowest(1)), 0)]),
+            ("4d6dl1+2", vec![d(4, 6, Some(Keep::DropLowest(1)), 2)]),
+            ("3d10dh1-1", vec![d(3, 10, Some(Keep::DropHighest(1)), -1)]),
+            ("3d6  3d8", vec![d(3, 6, None, 0), d(3, 8, None, 0)]),
+            ("1000d1000", vec![d(1000, 1000, None, 0)]),
+        ] {
+            assert_eq!(parse_dice(input), Some(expected), "{input:?}");
+        }
+        for malformed in [
+            "",
+            "20",
+            "d",
+            "2d",
+            "d-6",
+            "2d6+",
+            "2d6++3",
+            "2d6 +3",
+            "4d6kh",
+            "4d6k3",
+            "4d6kh3kl1",
+            "2d6+3-1",
+            "0d6",
+            "2d0",
+            "2x6",
+            "2d6 heal",
+            "d99999999999",
+            "½d6",
+            "2 d6",
+        ] {
+            assert_eq!(parse_dice(malformed), None, "{malformed:?}");
+        }
+    }
+
+    #[test]
+    async fn test_check() {
+        let check = |input| check(&parse_dice(input).unwrap());
+        assert_eq!(check("100d1000"), Ok(()));
+        assert_eq!(check("50d6 50d8"), Ok(()));
+        assert_eq!(check("4d6kh4 4d6dl4"), Ok(()));
+        assert_eq!(
+            check("101d6"),
+            Err("That's too many dice, 100 at most".to_string())
+        );
+        assert_eq!(
+            check("60d6 41d8"),
+            Err("That's too many dice, 100 at most".to_string()),
+            "all of them"
+        );
+        assert_eq!(
+            check("4294967295d6 4294967295d6"),
+            Err("That's too many dice, 100 at most".to_string())
+        );
+        assert_eq!(
+            check("d1001"),
+            Err("A d1001 is too round to roll, 1000 sides at most".to_string())
+        );
+        assert_eq!(
+            check("4d6kh5"),
+            Err("4d6kh5 keeps or drops more dice than it rolls".to_string())
+        );
+    }
+
+    #[test]
+    async fn test_kept() {
+        let rolls = [3, 6, 1, 5];
+        for (keep, expected) in [
+            (None, [true, true, true, true]),
+            (Some(Keep::Highest(3)), [true, true, false, true]),
+            (Some(Keep::Highest(1)), [false, true, false, false]),
+            (Some(Keep::Highest(0)), [false, false, false, false]),
+            (Some(Keep::Highest(4)), [true, true, true, true]),
+            (Some(Keep::Lowest(1)), [false, false, true, false]),
+            (Some(Keep::Lowest(2)), [true, false, true, false]),
+            (Some(Keep::DropHighest(1)), [true, false, true, true]),
+            (Some(Keep::DropLowest(1)), [true, true, false, true]),
+            (Some(Keep::DropLowest(4)), [false, false, false, false]),
+        ] {
+            assert_eq!(kept(&rolls, keep), expected, "{keep:?}");
+        }
+        // ties
+        assert_eq!(
+            kept(&[4, 4, 4], Some(Keep::Highest(2))),
+            [false, true, true]
+        );
+        assert_eq!(kept(&[4, 4, 4], Some(Keep::Lowest(2))), [true, true, false]);
+        assert_eq!(
+            kept(&[2, 5, 2, 5], Some(Keep::DropLowest(1))),
+            [false, true, true, true]
+        );
+        assert_eq!(
+            kept(&[2, 5, 2, 5], Some(Keep::DropHighest(1))),
+            [true, true, true, false]
+        );
+    }
+
+    #[test]
+    async fn test_format() {
+        assert_eq!(format(&d(2, 6, None, 3), &[4, 2]), "2d6+3: [4, 2] + 3 = 9");
+        assert_eq!(format(&d(1, 20, None, 0), &[17]), "d20: [17] = 17");
+        assert_eq!(format(&d(1, 20, None, -2), &[1]), "d20-2: [1] - 2 = -1");
+        assert_eq!(
+            format(&d(4, 6, Some(Keep::Highest(3)), 0), &[3, 6, 1, 5]),
+            "4d6kh3: [3, 6, (1), 5] = 14"
+        );
+        assert_eq!(
+            format(&d(4, 6, Some(Keep::DropLowest(1)), 1), &[3, 6, 1, 5]),
+            "4d6dl1+1: [3, 6, (1), 5] + 1 = 15"
+        );
+    }
+
+    #[test]
+    async fn test_roll() {
+        let dice = d(100, 6, None, 0);
+        let rolls = roll(&dice, &mut StdRng::seed_from_u64(42));
+        assert_eq!(rolls.len(), 100);
+        assert!(rolls.iter().all(|r| (1..=6).contains(r)), "{rolls:?}");
+        for side in 1..=6 {
+            assert!(rolls.contains(&side), "no {side} in {rolls:?}");
+        }
+        assert_eq!(
+            roll(&dice, &mut StdRng::seed_from_u64(42)),
+            rolls,
+            "the same with the same rng"
+        );
+        assert_eq!(
+            roll(&d(3, 1, None, 0), &mut rand::thread_rng()),
+            vec![1, 1, 1]
+        );
+    }
+}
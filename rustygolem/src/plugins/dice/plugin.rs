@@ -0,0 +1,113 @@
+use async_trait::async_trait;
+use irc::proto::{Command, Message};
+use plugin_core::utils::parser;
+use plugin_core::{CommandHelp, Initialised, Outbound, Plugin, Result};
+use rand::Rng;
+
+use super::notation::{self, parse_dice};
+use crate::utils::messages::with_target;
+
+const USAGE: &str = "Usage: λroll <dice>, like d20, 2d6+3, 4d6kh3 (keep the highest 3) or 3d6 3d8";
+
+pub struct Dice;
+
+#[async_trait]
+impl Plugin for Dice {
+    async fn init(_config: &plugin_core::Config) -> Result<Initialised> {
+        Ok(Initialised::from(Dice))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "dice"
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Outbound>> {
+        Ok(in_msg(msg, &mut rand::thread_rng()))
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![CommandHelp::new("roll")
+            .usage("roll <dice>… [> nick]")
+            .description(
+                "Roll the dice, like d20, 2d6+3, 4d6kh3 to keep the highest 3 or 2d20dl1 \
+                 to drop the lowest one",
+            )]
+    }
+}
+
+fn in_msg<R: Rng + ?Sized>(msg: &Message, rng: &mut R) -> Option<Outbound> {
+    let response_target = msg.response_target()?;
+    let privmsg = match &msg.command {
+        Command::PRIVMSG(_source, privmsg) => privmsg,
+        _ => return None,
+    };
+    let (args, mb_target) = parser::command("roll")(privmsg).ok()?.1;
+    let text = match parse_dice(args).map(|dice| (notation::check(&dice), dice)) {
+        None => USAGE.to_string(),
+        Some((Err(refusal), _)) => refusal,
+        Some((Ok(()), dice)) => dice
+            .iter()
+            .map(|d| notation::format(d, &notation::roll(d, rng)))
+            .collect::<Vec<_>>()
+            .join(" − "),
+    };
+    Some(Outbound::reply(
+        response_target,
+        with_target(&text, &mb_target),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn roll(text: &str) -> Option<String> {
+        let msg = Message::new(
+            Some("alice!~alice@localhost"),
+            "PRIVMSG",
+            vec!["#rpg", text],
+        )
+        .unwrap();
+        match in_msg(&msg, &mut StdRng::seed_from_u64(7)) {
+            Some(Outbound::Reply { text, .. }) => Some(text),
+            None => None,
+            other => panic!("unexpected reply to {text:?}: {other:?}"),
+        }
+    }
+
+    #[test]
+    async fn test_roll() {
+        assert_eq!(roll("λroll d1"), Some("d1: [1] = 1".to_string()));
+        assert_eq!(
+            roll("λroll 3d1+2 2d1kh1 > bob"),
+            Some("bob: 3d1+2: [1, 1, 1] + 2 = 5 − 2d1kh1: [(1), 1] = 1".to_string())
+        );
+        let rolled = roll("λroll 2d6+3").unwrap();
+        assert!(rolled.starts_with("2d6+3: ["), "{rolled}");
+        assert_eq!(
+            roll("λroll 2d6+3"),
+            Some(rolled),
+            "the same with the same rng"
+        );
+    }
+
+    #[test]
+    async fn test_refusals() {
+        for input in ["λroll", "λroll 2d", "λroll 2d6 for damage"] {
+            assert_eq!(roll(input), Some(USAGE.to_string()), "{input:?}");
+        }
+        assert_eq!(
+            roll("λroll 1000d6"),
+            Some("That's too many dice, 100 at most".to_string())
+        );
+        assert_eq!(
+            roll("λroll d10000"),
+            Some("A d10000 is too round to roll, 1000 sides at most".to_string())
+        );
+        assert_eq!(roll("λrolls d20"), None);
+        assert_eq!(roll("roll d20"), None);
+    }
+}
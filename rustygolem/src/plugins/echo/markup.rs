@@ -0,0 +1,326 @@
+/// The colors allowed in `%color{text}`, with their mIRC code. No white nor
+/// black, unreadable on either a dark or a light background.
+pub const COLORS: &[(&str, u8)] = &[
+    ("blue", 2),
+    ("green", 3),
+    ("red", 4),
+    ("brown", 5),
+    ("purple", 6),
+    ("orange", 7),
+    ("yellow", 8),
+    ("lime", 9),
+    ("teal", 10),
+    ("cyan", 11),
+    ("royal", 12),
+    ("pink", 13),
+    ("grey", 14),
+];
+
+const BOLD: char = '\x02';
+const COLOR: char = '\x03';
+const RESET: char = '\x0F';
+
+#[derive(Debug, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    Bold(Vec<Node>),
+    Color(u8, Vec<Node>),
+}
+
+/// A marker not closed yet
+#[derive(Debug)]
+enum Open {
+    Bold,
+    /// with the `%name{` as typed, for when it's never closed
+    Color(u8, String),
+}
+
+impl Open {
+    fn typed(&self) -> &str {
+        match self {
+            Open::Bold => "*",
+            Open::Color(_, typed) => typed,
+        }
+    }
+
+    fn closing(&self) -> &'static str {
+        match self {
+            Open::Bold => "*",
+            Open::Color(..) => "}",
+        }
+    }
+}
+
+struct Frame {
+    /// None for the root
+    open: Option<Open>,
+    children: Vec<Node>,
+}
+
+/// `*bold*` and `%red{colored}` into the mIRC formatting codes, with a reset
+/// at the end. The markers never closed, `%unknown{` colors and empty
+/// markers are left as typed. The text shouldn't have any control code.
+pub fn render(text: &str) -> String {
+    let nodes = parse(text);
+    let mut rendered = String::with_capacity(text.len());
+    let formatted = nodes.iter().any(|node| !matches!(node, Node::Text(_)));
+    render_nodes(&nodes, None, &mut rendered);
+    if formatted {
+        rendered.push(RESET);
+    }
+    rendered
+}
+
+fn parse(text: &str) -> Vec<Node> {
+    let mut stack = vec![Frame {
+        open: None,
+        children: vec![],
+    }];
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '*' => {
+                if matches!(stack.last().and_then(|f| f.open.as_ref()), Some(Open::Bold)) {
+                    close(&mut stack);
+                    continue;
+                }
+                // no bold in bold
+                if !stack.iter().any(|f| matches!(f.open, Some(Open::Bold))) {
+                    open(&mut stack, Open::Bold);
+                    continue;
+                }
+            }
+            '%' => {
+                if let Some((color, typed)) = color_opening(rest) {
+                    rest = &rest[typed.len() - 1..];
+                    open(&mut stack, Open::Color(color, typed));
+                    continue;
+                }
+            }
+            '}' => {
+                let color_at = stack
+                    .iter()
+                    .rposition(|f| matches!(f.open, Some(Open::Color(..))));
+                if let Some(color_at) = color_at {
+                    // the markers opened inside the color and never closed
+                    while stack.len() > color_at + 1 {
+                        unwind(&mut stack);
+                    }
+                    close(&mut stack);
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        push_text(
+            &mut stack.last_mut().expect("markup root").children,
+            &c.to_string(),
+        );
+    }
+    while stack.len() > 1 {
+        unwind(&mut stack);
+    }
+    stack.pop().expect("markup root").children
+}
+
+/// The color and the `%name{` as typed, after the `%`
+fn color_opening(rest: &str) -> Option<(u8, String)> {
+    let (name, _) = rest.split_once('{')?;
+    let (_, code) = COLORS.iter().find(|(color, _)| *color == name)?;
+    Some((*code, format!("%{name}{{")))
+}
+
+fn open(stack: &mut Vec<Frame>, open: Open) {
+    stack.push(Frame {
+        open: Some(open),
+        children: vec![],
+    });
+}
+
+/// The innermost marker is done with
+fn close(stack: &mut Vec<Frame>) {
+    let Frame { open, children } = stack.pop().expect("marker to close");
+    let open = open.expect("not the root");
+    let parent = &mut stack.last_mut().expect("markup root").children;
+    match open {
+        // like `**` or `%red{}`
+        _ if children.is_empty() => {
+            push_text(parent, &format!("{}{}", open.typed(), open.closing()));
+        }
+        Open::Bold => parent.push(Node::Bold(children)),
+        Open::Color(color, _) => parent.push(Node::Color(color, children)),
+    }
+}
+
+/// The innermost marker is never closed, it's text after all
+fn unwind(stack: &mut Vec<Frame>) {
+    let Frame { open, children } = stack.pop().expect("marker to unwind");
+    let parent = &mut stack.last_mut().expect("markup root").children;
+    push_text(parent, open.as_ref().map_or("", Open::typed));
+    for child in children {
+        match child {
+            Node::Text(text) => push_text(parent, &text),
+            node => parent.push(node),
+        }
+    }
+}
+
+/// Merged with the previous text if any
+fn push_text(nodes: &mut Vec<Node>, text: &str) {
+    if let Some(Node::Text(previous)) = nodes.last_mut() {
+        previous.push_str(text);
+    } else if !text.is_empty() {
+        nodes.push(Node::Text(text.to_string()));
+    }
+}
+
+/// `color` is the one around the nodes, restored after an inner color
+fn render_nodes(nodes: &[Node], color: Option<u8>, rendered: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => {
+                // digits right after a color code would be taken for a color
+                if ends_with_color_code(rendered)
+                    && text.starts_with(|c: char| c.is_ascii_digit() || c == ',')
+                {
+                    rendered.push(BOLD);
+                    rendered.push(BOLD);
+                }
+                rendered.push_str(text);
+            }
+            Node::Bold(children) => {
+                rendered.push(BOLD);
+                render_nodes(children, color, rendered);
+                rendered.push(BOLD);
+            }
+            Node::Color(code, children) => {
+                rendered.push_str(&format!("{COLOR}{code:02}"));
+                render_nodes(children, Some(*code), rendered);
+                match color {
+                    Some(outer) => rendered.push_str(&format!("{COLOR}{outer:02}")),
+                    None => rendered.push(COLOR),
+                }
+            }
+        }
+    }
+}
+
+/// Like `\x03` or `\x0304`
+fn ends_with_color_code(rendered: &str) -> bool {
+    let tail = rendered
+        .chars()
+        .rev()
+        .take_while(|c| c.is_ascii_digit())
+        .count();
+    let before = &rendered[..rendered.len() - tail];
+    before.ends_with(COLOR) && tail <= 2
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_plain() {
+        for text in ["grab the pizza", "", "2 * 3 = 6", "{curly}", "100%"] {
+            assert_eq!(render(text), text, "{text:?}");
+        }
+    }
+
+    #[test]
+    async fn test_bold() {
+        assert_eq!(render("grab *the* pizza"), "grab \x02the\x02 pizza\x0F");
+        assert_eq!(
+            render("*grab* the *pizza*"),
+            "\x02grab\x02 the \x02pizza\x02\x0F"
+        );
+        assert_eq!(render("2*3 = 6"), "2*3 = 6", "unclosed");
+        assert_eq!(render("*a* 2*3"), "\x02a\x02 2*3\x0F");
+        assert_eq!(render("**"), "**", "empty");
+    }
+
+    #[test]
+    async fn test_colors() {
+        assert_eq!(
+            render("%red{grab} the pizza"),
+            "\x0304grab\x03 the pizza\x0F"
+        );
+        assert_eq!(
+            render("%royal{grab} %lime{the pizza}"),
+            "\x0312grab\x03 \x0309the pizza\x03\x0F"
+        );
+        assert_eq!(
+            render("%red{grab the pizza"),
+            "%red{grab the pizza",
+            "unclosed"
+        );
+        assert_eq!(
+            render("%white{grab} the %magenta{pizza}"),
+            "%white{grab} the %magenta{pizza}",
+            "not allowed"
+        );
+        assert_eq!(render("%Red{grab}"), "%Red{grab}", "case sensitive");
+        assert_eq!(render("%red{}"), "%red{}", "empty");
+        assert_eq!(render("100% {sure}"), "100% {sure}");
+    }
+
+    #[test]
+    async fn test_nesting() {
+        assert_eq!(
+            render("%red{grab *the* pizza}"),
+            "\x0304grab \x02the\x02 pizza\x03\x0F"
+        );
+        assert_eq!(
+            render("*grab %red{the} pizza*"),
+            "\x02grab \x0304the\x03 pizza\x02\x0F"
+        );
+        assert_eq!(
+            render("%red{grab %blue{the} pizza}"),
+            "\x0304grab \x0302the\x0304 pizza\x03\x0F",
+            "back to the outer color"
+        );
+        assert_eq!(
+            render("%red{grab *the} pizza*"),
+            "\x0304grab *the\x03 pizza*\x0F",
+            "a bold crossing the color is unclosed"
+        );
+        assert_eq!(
+            render("*grab %red{the* pizza}"),
+            "*grab \x0304the* pizza\x03\x0F",
+            "a bold can't be closed inside a color"
+        );
+        assert_eq!(
+            render("%red{grab %blue{the pizza}"),
+            "%red{grab \x0302the pizza\x03\x0F",
+            "the inner one closed"
+        );
+        assert_eq!(
+            render("*a *b* c*"),
+            "\x02a \x02b\x02 c\x02\x0F",
+            "bold doesn't nest, each * toggles"
+        );
+    }
+
+    #[test]
+    async fn test_digits_after_colors() {
+        assert_eq!(render("%red{3} apples"), "\x0304\x02\x023\x03 apples\x0F");
+        assert_eq!(render("%red{x}5 apples"), "\x0304x\x03\x02\x025 apples\x0F");
+        assert_eq!(render("%red{x},5"), "\x0304x\x03\x02\x02,5\x0F");
+        assert_eq!(
+            render("%red{%blue{x}2}"),
+            "\x0304\x0302x\x0304\x02\x022\x03\x0F"
+        );
+    }
+
+    #[test]
+    async fn test_control_codes_only_from_markup() {
+        let rendered = render("%red{*grab*} the pizza");
+        let codes = rendered
+            .chars()
+            .filter(|c| c.is_control())
+            .collect::<String>();
+        assert_eq!(codes, "\x03\x02\x02\x03\x0F");
+    }
+}
@@ -0,0 +1,142 @@
+use plugin_core::CaseMapping;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// How many echoes are remembered in each channel, unless the config says otherwise
+pub const DEFAULT_SIZE: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Remembered {
+    /// normalized with the casemapping of the network
+    requester: String,
+    /// as said by the golem
+    text: String,
+}
+
+/// The last echoes said in each channel, for λecho again
+pub struct Memory {
+    size: usize,
+    /// the last one first, keyed by the channel normalized with the
+    /// casemapping of its network
+    echoes: Mutex<HashMap<String, VecDeque<Remembered>>>,
+}
+
+impl Memory {
+    pub fn new(size: usize) -> Self {
+        Memory {
+            size,
+            echoes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn remember(&self, casemapping: CaseMapping, channel: &str, requester: &str, text: &str) {
+        if self.size == 0 {
+            return;
+        }
+        let mut echoes = self.echoes.lock().expect("echo memory lock");
+        let remembered = echoes.entry(casemapping.normalize(channel)).or_default();
+        remembered.push_front(Remembered {
+            requester: casemapping.normalize(requester),
+            text: text.to_string(),
+        });
+        remembered.truncate(self.size);
+    }
+
+    /// 1 for the last echo, 2 for the one before…
+    pub fn nth(&self, casemapping: CaseMapping, channel: &str, nth: usize) -> Option<String> {
+        self.echoes
+            .lock()
+            .expect("echo memory lock")
+            .get(&casemapping.normalize(channel))?
+            .get(nth.checked_sub(1)?)
+            .map(|remembered| remembered.text.clone())
+    }
+
+    pub fn len(&self, casemapping: CaseMapping, channel: &str) -> usize {
+        self.echoes
+            .lock()
+            .expect("echo memory lock")
+            .get(&casemapping.normalize(channel))
+            .map_or(0, VecDeque::len)
+    }
+
+    /// The echoes of that requester, or all of them for None. How many were forgotten.
+    pub fn forget(
+        &self,
+        casemapping: CaseMapping,
+        channel: &str,
+        requester: Option<&str>,
+    ) -> usize {
+        let channel = casemapping.normalize(channel);
+        let mut echoes = self.echoes.lock().expect("echo memory lock");
+        let remembered = match echoes.get_mut(&channel) {
+            Some(remembered) => remembered,
+            None => return 0,
+        };
+        let before = remembered.len();
+        match requester {
+            Some(requester) => {
+                let requester = casemapping.normalize(requester);
+                remembered.retain(|r| r.requester != requester)
+            }
+            None => remembered.clear(),
+        }
+        let forgotten = before - remembered.len();
+        if remembered.is_empty() {
+            echoes.remove(&channel);
+        }
+        forgotten
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const RFC1459: CaseMapping = CaseMapping::Rfc1459;
+
+    #[test]
+    async fn test_nth() {
+        let memory = Memory::new(DEFAULT_SIZE);
+        memory.remember(RFC1459, "#rust", "alice", "first");
+        memory.remember(RFC1459, "#Rust", "bob", "second");
+        memory.remember(RFC1459, "#ocaml", "alice", "elsewhere");
+        assert_eq!(memory.nth(RFC1459, "#RUST", 1), Some("second".to_string()));
+        assert_eq!(memory.nth(RFC1459, "#rust", 2), Some("first".to_string()));
+        assert_eq!(memory.nth(RFC1459, "#rust", 3), None);
+        assert_eq!(memory.nth(RFC1459, "#rust", 0), None);
+        assert_eq!(memory.nth(RFC1459, "#haskell", 1), None);
+        assert_eq!(memory.len(RFC1459, "#rust"), 2);
+    }
+
+    #[test]
+    async fn test_bound() {
+        let memory = Memory::new(3);
+        for i in 1..=5 {
+            memory.remember(RFC1459, "#rust", "alice", &format!("echo {i}"));
+        }
+        assert_eq!(memory.len(RFC1459, "#rust"), 3);
+        assert_eq!(memory.nth(RFC1459, "#rust", 1), Some("echo 5".to_string()));
+        assert_eq!(memory.nth(RFC1459, "#rust", 3), Some("echo 3".to_string()));
+        assert_eq!(memory.nth(RFC1459, "#rust", 4), None, "too far back");
+
+        let memory = Memory::new(0);
+        memory.remember(RFC1459, "#rust", "alice", "echo");
+        assert_eq!(memory.nth(RFC1459, "#rust", 1), None, "remembering nothing");
+    }
+
+    #[test]
+    async fn test_forget() {
+        let memory = Memory::new(DEFAULT_SIZE);
+        memory.remember(RFC1459, "#rust", "alice[m]", "first");
+        memory.remember(RFC1459, "#rust", "bob", "second");
+        memory.remember(RFC1459, "#rust", "Alice{M}", "third");
+        assert_eq!(memory.forget(RFC1459, "#rust", Some("carol")), 0);
+        assert_eq!(memory.forget(RFC1459, "#rust", Some("ALICE[M]")), 2);
+        assert_eq!(memory.nth(RFC1459, "#rust", 1), Some("second".to_string()));
+        assert_eq!(memory.forget(RFC1459, "#rust", None), 1);
+        assert_eq!(memory.len(RFC1459, "#rust"), 0);
+        assert_eq!(memory.forget(RFC1459, "#haskell", None), 0);
+    }
+}
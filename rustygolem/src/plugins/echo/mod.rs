@@ -0,0 +1,5 @@
+mod markup;
+mod memory;
+mod plugin;
+
+pub use plugin::Echo;
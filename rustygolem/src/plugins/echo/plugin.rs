@@ -0,0 +1,1140 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+use irc::proto::{ChannelExt, Command, Message};
+use plugin_core::utils::account::is_admin;
+use plugin_core::utils::network::network;
+use plugin_core::{
+    parse, CaseMapping, CommandHelp, Cooldown, Database, Delayed, Initialised, Members, MsgCtx,
+    Outbound, Plugin, Result,
+};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use super::markup;
+use super::memory::{self, Memory};
+use crate::utils::text::{sanitize, strip_formatting, unhighlight};
+use crate::utils::time::{format_duration, parse_duration};
+
+/// Longest delay of λecho in, unless the config says otherwise
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(24 * 3600);
+/// Longest echoed text, in chars, unless the config says otherwise
+pub const DEFAULT_MAX_LENGTH: usize = 300;
+/// Between two echoes of the same nick, unless the config says otherwise
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+/// λecho in waiting for each nick, unless the config says otherwise
+pub const DEFAULT_MAX_PENDING: usize = 5;
+
+/// The commands of the other bots, never echoed so that bots don't
+/// end up answering each other
+const COMMAND_PREFIXES: &[char] = &['!', 'λ', '.'];
+
+const USAGE: &str = "Usage: λecho in <duration> <text>, like λecho in 1h30m grab the pizza";
+const USAGE_TO: &str = "Usage: λecho to <#channel> <text>";
+const USAGE_AGAIN: &str = "Usage: λecho again [n], 1 for the last echo here, 2 for the one before…";
+
+/// The tables of the echo plugin in the shared database, see
+/// `plugin_core::ensure_schema`
+const MIGRATIONS: &[&str] = &[
+    // due_at in seconds since the epoch, to be posted after a restart
+    "CREATE TABLE echo_pending (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        target TEXT NOT NULL,
+        nick TEXT NOT NULL,
+        text TEXT NOT NULL,
+        due_at BIGINT NOT NULL
+    );",
+    // empty for the echoes from before the attribution
+    "ALTER TABLE echo_pending ADD COLUMN requester TEXT NOT NULL DEFAULT '';",
+];
+
+/// The `echo` section of the golem config
+#[derive(Deserialize)]
+struct Settings {
+    #[serde(default = "default_max_delay_secs")]
+    max_delay_secs: u64,
+    /// in chars, longer texts are cut
+    #[serde(default = "default_max_length")]
+    max_length: usize,
+    /// for each nick
+    #[serde(default = "default_cooldown_secs")]
+    cooldown_secs: u64,
+    /// λecho in waiting for each nick
+    #[serde(default = "default_max_pending")]
+    max_pending: usize,
+    /// `<requester>` in the delayed echoes for someone else
+    #[serde(default = "default_attribution")]
+    attribution: bool,
+    /// where `*bold*` and `%red{text}` are left as typed, like the +c channels
+    #[serde(default)]
+    no_colors: Vec<String>,
+    /// echoes remembered in each channel for λecho again
+    #[serde(default = "default_memory_size")]
+    memory_size: usize,
+}
+
+fn default_max_delay_secs() -> u64 {
+    DEFAULT_MAX_DELAY.as_secs()
+}
+
+fn default_max_length() -> usize {
+    DEFAULT_MAX_LENGTH
+}
+
+fn default_cooldown_secs() -> u64 {
+    DEFAULT_COOLDOWN.as_secs()
+}
+
+fn default_max_pending() -> usize {
+    DEFAULT_MAX_PENDING
+}
+
+fn default_attribution() -> bool {
+    true
+}
+
+fn default_memory_size() -> usize {
+    memory::DEFAULT_SIZE
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            max_delay_secs: default_max_delay_secs(),
+            max_length: default_max_length(),
+            cooldown_secs: default_cooldown_secs(),
+            max_pending: default_max_pending(),
+            attribution: default_attribution(),
+            no_colors: vec![],
+            memory_size: default_memory_size(),
+        }
+    }
+}
+
+pub struct Echo {
+    max_delay: Duration,
+    max_length: usize,
+    /// between two echoes of a nick, λecho in or said right away
+    cooldown: Cooldown,
+    /// λecho in waiting for each nick
+    max_pending: usize,
+    attribution: bool,
+    /// without the markup
+    no_colors: Vec<String>,
+    /// for λecho to, only where the requester is too, and the > nick of
+    /// λecho in, only someone in the channel
+    members: Arc<Members>,
+    pending: Pending,
+    /// the last echoes, for λecho again
+    memory: Memory,
+    /// allowed to make the golem forget any echo
+    admins: Vec<String>,
+}
+
+impl Echo {
+    /// With the markup in the formatting codes, unless the channel has no colors
+    fn format(&self, casemapping: CaseMapping, channel: &str, text: &str) -> String {
+        if self
+            .no_colors
+            .iter()
+            .any(|c| casemapping.eq_ignore_case(c, channel))
+        {
+            text.to_string()
+        } else {
+            markup::render(text)
+        }
+    }
+
+    /// Whether the cooldown of the sender of the message is over, nicks
+    /// compared with the casemapping of their network
+    fn cooled_down(&self, msg: &Message, casemapping: CaseMapping) -> bool {
+        let nick = msg.source_nickname().unwrap_or_default();
+        self.cooldown.check(&casemapping.normalize(nick))
+    }
+}
+
+/// Without any database, nor config
+impl Default for Echo {
+    fn default() -> Self {
+        Echo {
+            max_delay: DEFAULT_MAX_DELAY,
+            max_length: DEFAULT_MAX_LENGTH,
+            cooldown: Cooldown::new(DEFAULT_COOLDOWN),
+            max_pending: DEFAULT_MAX_PENDING,
+            attribution: true,
+            no_colors: vec![],
+            members: Arc::default(),
+            pending: Pending::load(None).expect("nothing to load without a database"),
+            memory: Memory::new(memory::DEFAULT_SIZE),
+            admins: vec![],
+        }
+    }
+}
+
+#[async_trait]
+impl Plugin for Echo {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        config.plugin_section::<Settings>("echo")?;
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let settings: Settings = config.plugin_section("echo")?.unwrap_or_default();
+        let db = config.database().cloned();
+        if db.is_none() {
+            log::warn!("No database, the pending λecho in won't survive a restart");
+        }
+        Ok(Initialised::from(Echo {
+            max_delay: Duration::from_secs(settings.max_delay_secs),
+            max_length: settings.max_length,
+            cooldown: Cooldown::new(Duration::from_secs(settings.cooldown_secs)),
+            max_pending: settings.max_pending,
+            attribution: settings.attribution,
+            no_colors: settings.no_colors,
+            members: config.members(),
+            pending: Pending::load(db)?,
+            memory: Memory::new(settings.memory_size),
+            admins: config.admins()?,
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "echo"
+    }
+
+    async fn in_message_ctx(&self, ctx: &MsgCtx, msg: &Message) -> Result<Option<Outbound>> {
+        in_msg(self, msg, ctx.casemapping).await
+    }
+
+    async fn run(&self, bot_chan: mpsc::Sender<Outbound>) -> Result<()> {
+        Ok(self
+            .pending
+            .run(&bot_chan, self.attribution, &self.memory)
+            .await?)
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("echo in")
+                .usage("echo in <duration> <text> [> nick]")
+                .description("Say the text here after the duration, like 30s, 10m or 1h30m"),
+            CommandHelp::new("echo to")
+                .usage("echo to <#channel> <text>")
+                .description("Say the text in another channel, where you are too"),
+            CommandHelp::new("echo again")
+                .usage("echo again [n]")
+                .description("Say again the last echo here, or the nth last one"),
+            CommandHelp::new("echo forget")
+                .description("Forget the echoes you asked for here, all of them for the admins"),
+        ]
+    }
+}
+
+async fn in_msg(
+    plugin: &Echo,
+    msg: &Message,
+    casemapping: CaseMapping,
+) -> Result<Option<Outbound>> {
+    let response_target = match msg.response_target() {
+        None => return Ok(None),
+        Some(target) => target,
+    };
+    let message = match &msg.command {
+        Command::PRIVMSG(_source, message) => message,
+        _ => return Ok(None),
+    };
+    match parse::command("echo")(message) {
+        Ok((_, (args, mb_target))) if is_subcommand(args, "in") => {
+            echo_in(plugin, msg, casemapping, response_target, args, mb_target)
+        }
+        Ok((_, (args, _))) if is_subcommand(args, "to") => {
+            echo_to(plugin, msg, casemapping, response_target, args)
+        }
+        Ok((_, (args, _))) if is_subcommand(args, "again") => {
+            Ok(echo_again(plugin, msg, casemapping, response_target, args))
+        }
+        Ok((_, (args, _))) if args.trim() == "forget" => Ok(Some(Outbound::reply(
+            response_target,
+            forget(plugin, msg, casemapping, response_target),
+        ))),
+        _ if !plugin.cooled_down(msg, casemapping) => Ok(None),
+        _ => Ok(Some(Outbound::reply(
+            response_target,
+            format!("echo - {}", sanitize(message, plugin.max_length)),
+        ))),
+    }
+}
+
+fn is_subcommand(args: &str, name: &str) -> bool {
+    args.split_whitespace().next() == Some(name)
+}
+
+/// λecho in <duration> <text>, said here later
+fn echo_in(
+    plugin: &Echo,
+    msg: &Message,
+    casemapping: CaseMapping,
+    response_target: &str,
+    args: &str,
+    mb_target: Option<&str>,
+) -> Result<Option<Outbound>> {
+    let reply = match parse_echo_in(args) {
+        None => USAGE.to_string(),
+        Some((delay, _)) if delay > plugin.max_delay => {
+            format!("At most {}", format_duration(plugin.max_delay))
+        }
+        Some((delay, text)) => {
+            let text = sanitize(text, plugin.max_length);
+            let requester = msg.source_nickname().unwrap_or_default();
+            let text = plugin.format(casemapping, response_target, &text);
+            let nick = mb_target.unwrap_or(requester);
+            let for_someone_else = !casemapping.eq_ignore_case(nick, requester);
+            let network = network(msg).unwrap_or_default();
+            let pending = plugin.pending.of(casemapping, requester);
+            if text.is_empty() {
+                USAGE.to_string()
+            } else if is_command(&text) {
+                "Not echoing the commands of other bots".to_string()
+            } else if for_someone_else && !response_target.is_channel_name() {
+                "Only in a channel can you echo for someone else".to_string()
+            } else if for_someone_else && !plugin.members.is_member(network, response_target, nick)
+            {
+                // highlighting only someone who can see the channel anyway
+                format!("{nick} isn't in {response_target}")
+            } else if pending >= plugin.max_pending {
+                format!("You have {pending} echoes waiting already")
+            } else if !plugin.cooled_down(msg, casemapping) {
+                return Ok(None);
+            } else {
+                plugin
+                    .pending
+                    .add(casemapping, response_target, nick, requester, &text, delay)?;
+                format!("ok, in {}", format_duration(delay))
+            }
+        }
+    };
+    Ok(Some(Outbound::reply(response_target, reply)))
+}
+
+/// λecho to <#channel> <text>, said there right away when both the golem
+/// and the requester are in that channel, explained in private otherwise
+fn echo_to(
+    plugin: &Echo,
+    msg: &Message,
+    casemapping: CaseMapping,
+    response_target: &str,
+    args: &str,
+) -> Result<Option<Outbound>> {
+    let requester = msg.source_nickname().unwrap_or_default();
+    let (channel, text) = match parse_echo_to(args) {
+        Some((channel, text)) => (channel, sanitize(text, plugin.max_length)),
+        None => return Ok(Some(Outbound::reply(response_target, USAGE_TO))),
+    };
+    if text.is_empty() {
+        return Ok(Some(Outbound::reply(response_target, USAGE_TO)));
+    }
+    let text = plugin.format(casemapping, channel, &text);
+    if is_command(&text) {
+        return Ok(Some(Outbound::reply(
+            response_target,
+            "Not echoing the commands of other bots",
+        )));
+    }
+    let network = network(msg).unwrap_or_default();
+    if !plugin.members.has_channel(network, channel) {
+        return Ok(Some(Outbound::notice(
+            requester,
+            format!("I'm not in {channel}"),
+        )));
+    }
+    if !plugin.members.is_member(network, channel, requester) {
+        return Ok(Some(Outbound::notice(
+            requester,
+            format!("You're not in {channel}, only there can you echo to it"),
+        )));
+    }
+    if !plugin.cooled_down(msg, casemapping) {
+        return Ok(None);
+    }
+    let from = if response_target.is_channel_name() {
+        format!("{}@{response_target}", unhighlight(requester))
+    } else {
+        unhighlight(requester)
+    };
+    let text = format!("<{from}> {text}");
+    plugin
+        .memory
+        .remember(casemapping, channel, requester, &text);
+    Ok(Some(Outbound::reply(channel, text)))
+}
+
+/// λecho again [n], the nth last echo said here
+fn echo_again(
+    plugin: &Echo,
+    msg: &Message,
+    casemapping: CaseMapping,
+    response_target: &str,
+    args: &str,
+) -> Option<Outbound> {
+    let nth = match args.trim_start_matches("again").trim() {
+        "" => Some(1),
+        nth => nth.parse::<usize>().ok().filter(|nth| *nth > 0),
+    };
+    let nth = match nth {
+        Some(nth) => nth,
+        None => return Some(Outbound::reply(response_target, USAGE_AGAIN)),
+    };
+    if !plugin.cooled_down(msg, casemapping) {
+        return None;
+    }
+    let reply = match plugin.memory.nth(casemapping, response_target, nth) {
+        Some(text) => text,
+        None => match plugin.memory.len(casemapping, response_target) {
+            0 => "Nothing echoed here yet".to_string(),
+            1 => "Only the last echo is remembered here".to_string(),
+            len => format!("Only the last {len} echoes are remembered here"),
+        },
+    };
+    Some(Outbound::reply(response_target, reply))
+}
+
+/// λecho forget, the echoes of the nick here, or all of them for the admins
+fn forget(plugin: &Echo, msg: &Message, casemapping: CaseMapping, channel: &str) -> String {
+    if plugin.memory.len(casemapping, channel) == 0 {
+        return "Nothing echoed here yet".to_string();
+    }
+    let nick = msg.source_nickname().unwrap_or_default();
+    let forgotten = if is_admin(&plugin.admins, msg, casemapping) {
+        plugin.memory.forget(casemapping, channel, None)
+    } else {
+        plugin.memory.forget(casemapping, channel, Some(nick))
+    };
+    match forgotten {
+        0 => "Only the admins and whoever asked for an echo can forget it".to_string(),
+        1 => "Forgot 1 echo".to_string(),
+        n => format!("Forgot {n} echoes"),
+    }
+}
+
+/// `to <#channel> <text>`
+fn parse_echo_to(args: &str) -> Option<(&str, &str)> {
+    let rest = args.strip_prefix("to")?.trim_start();
+    let (channel, text) = rest.split_once(char::is_whitespace)?;
+    let text = text.trim();
+    if !channel.is_channel_name() || text.is_empty() {
+        return None;
+    }
+    Some((channel, text))
+}
+
+/// `in <duration> <text>`
+fn parse_echo_in(args: &str) -> Option<(Duration, &str)> {
+    let rest = args.strip_prefix("in")?.trim_start();
+    let (duration, text) = rest.split_once(char::is_whitespace)?;
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    Some((parse_duration(duration)?, text))
+}
+
+/// Like `!kick`, once formatted as well
+fn is_command(text: &str) -> bool {
+    strip_formatting(text).starts_with(COMMAND_PREFIXES)
+}
+
+#[derive(QueryableByName)]
+struct Row {
+    #[sql_type = "BigInt"]
+    id: i64,
+    #[sql_type = "Text"]
+    target: String,
+    #[sql_type = "Text"]
+    nick: String,
+    #[sql_type = "Text"]
+    requester: String,
+    #[sql_type = "Text"]
+    text: String,
+    #[sql_type = "BigInt"]
+    due_at: i64,
+}
+
+#[derive(QueryableByName)]
+struct Id {
+    #[sql_type = "BigInt"]
+    id: i64,
+}
+
+#[derive(Debug)]
+struct Scheduled {
+    /// in the database
+    id: Option<i64>,
+    target: String,
+    nick: String,
+    /// empty when unknown
+    requester: String,
+    /// of the network of the target, the default one for the echoes
+    /// loaded from the database
+    casemapping: CaseMapping,
+    text: String,
+}
+
+impl Scheduled {
+    /// Like `bob: <alice> the oven`, without `<alice>` when she asked for herself.
+    /// `alice` isn't highlighted, unlike `bob`.
+    fn message(&self, attribution: bool) -> String {
+        let for_someone_else = !self.requester.is_empty()
+            && !self.casemapping.eq_ignore_case(&self.requester, &self.nick);
+        if attribution && for_someone_else {
+            let requester = unhighlight(&self.requester);
+            format!("{}: <{requester}> {}", self.nick, self.text)
+        } else {
+            format!("{}: {}", self.nick, self.text)
+        }
+    }
+}
+
+/// The echoes to post later, also in the database when there's one
+struct Pending {
+    db: Option<Database>,
+    scheduled: Delayed<Scheduled>,
+}
+
+impl Pending {
+    /// Create the table if needed, and load the echoes from before the last restart
+    fn load(db: Option<Database>) -> Result<Self> {
+        let scheduled = Delayed::new();
+        if let Some(db) = &db {
+            plugin_core::ensure_schema(db, "echo", MIGRATIONS)?;
+            let rows = db.with_connection(|conn| {
+                diesel::sql_query(
+                    "SELECT id, target, nick, requester, text, due_at FROM echo_pending",
+                )
+                .load::<Row>(conn)
+            })?;
+            let now = chrono::Utc::now().timestamp();
+            let started = Instant::now();
+            for row in rows {
+                // the overdue ones are posted right away
+                let delay = Duration::from_secs((row.due_at - now).max(0) as u64);
+                scheduled.push(
+                    started + delay,
+                    Scheduled {
+                        id: Some(row.id),
+                        target: row.target,
+                        nick: row.nick,
+                        requester: row.requester,
+                        casemapping: CaseMapping::default(),
+                        text: row.text,
+                    },
+                );
+            }
+        }
+        Ok(Pending { db, scheduled })
+    }
+
+    /// How many echoes the nick asked for are waiting
+    fn of(&self, casemapping: CaseMapping, requester: &str) -> usize {
+        self.scheduled
+            .count(|echo| casemapping.eq_ignore_case(&echo.requester, requester))
+    }
+
+    fn add(
+        &self,
+        casemapping: CaseMapping,
+        target: &str,
+        nick: &str,
+        requester: &str,
+        text: &str,
+        delay: Duration,
+    ) -> Result<()> {
+        let id = match &self.db {
+            None => None,
+            Some(db) => {
+                let due_at = chrono::Utc::now().timestamp() + delay.as_secs() as i64;
+                let id = db.with_connection(|conn| {
+                    conn.transaction(|| {
+                        diesel::sql_query(
+                            "INSERT INTO echo_pending (target, nick, requester, text, due_at) VALUES (?, ?, ?, ?, ?)",
+                        )
+                        .bind::<Text, _>(target)
+                        .bind::<Text, _>(nick)
+                        .bind::<Text, _>(requester)
+                        .bind::<Text, _>(text)
+                        .bind::<BigInt, _>(due_at)
+                        .execute(conn)?;
+                        diesel::sql_query("SELECT last_insert_rowid() AS id").get_result::<Id>(conn)
+                    })
+                })?;
+                Some(id.id)
+            }
+        };
+        self.scheduled.push(
+            Instant::now() + delay,
+            Scheduled {
+                id,
+                target: target.to_string(),
+                nick: nick.to_string(),
+                requester: requester.to_string(),
+                casemapping,
+                text: text.to_string(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Posts the echoes once due, with `<requester>` when `attribution`,
+    /// and remembers them
+    async fn run(
+        &self,
+        bot_chan: &mpsc::Sender<Outbound>,
+        attribution: bool,
+        memory: &Memory,
+    ) -> anyhow::Result<()> {
+        loop {
+            for echo in self.scheduled.due().await {
+                let text = echo.message(attribution);
+                memory.remember(echo.casemapping, &echo.target, &echo.requester, &text);
+                bot_chan.send(Outbound::reply(&echo.target, text)).await?;
+                if let (Some(db), Some(id)) = (&self.db, echo.id) {
+                    db.with_connection(|conn| {
+                        diesel::sql_query("DELETE FROM echo_pending WHERE id = ?")
+                            .bind::<BigInt, _>(id)
+                            .execute(conn)
+                    })?;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use plugin_core::utils::network::set_network;
+    use pretty_assertions::assert_eq;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    fn echo(db: Option<Database>) -> Echo {
+        Echo {
+            max_delay: DEFAULT_MAX_DELAY,
+            max_length: DEFAULT_MAX_LENGTH,
+            cooldown: Cooldown::new(Duration::ZERO),
+            max_pending: DEFAULT_MAX_PENDING,
+            attribution: true,
+            no_colors: vec![],
+            members: Arc::default(),
+            pending: Pending::load(db).unwrap(),
+            memory: Memory::new(memory::DEFAULT_SIZE),
+            admins: vec!["root".to_string()],
+        }
+    }
+
+    async fn said(plugin: &Echo, text: &str) -> Option<String> {
+        said_by(plugin, "alice", text).await
+    }
+
+    async fn said_by(plugin: &Echo, nick: &str, text: &str) -> Option<String> {
+        match outbound(plugin, nick, "#rust", text).await {
+            Some(Outbound::Reply { text, .. }) => Some(text),
+            None => None,
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    async fn outbound(plugin: &Echo, nick: &str, target: &str, text: &str) -> Option<Outbound> {
+        let source = format!("{nick}!~{nick}@localhost");
+        let mut msg = Message::new(Some(&source), "PRIVMSG", vec![target, text]).unwrap();
+        set_network(&mut msg, "libera");
+        in_msg(plugin, &msg, CaseMapping::Rfc1459).await.unwrap()
+    }
+
+    fn drain(rx: &mut mpsc::Receiver<Outbound>) -> Vec<Outbound> {
+        let mut sent = vec![];
+        while let Ok(outbound) = rx.try_recv() {
+            sent.push(outbound);
+        }
+        sent
+    }
+
+    #[test]
+    async fn test_commands() {
+        let plugin = echo(None);
+        assert_eq!(
+            said(&plugin, "λecho in 90m grab the pizza").await,
+            Some("ok, in 1h30m".to_string())
+        );
+        for malformed in ["λecho in", "λecho in 10m", "λecho in soon grab the pizza"] {
+            assert_eq!(
+                said(&plugin, malformed).await,
+                Some(USAGE.to_string()),
+                "{malformed}"
+            );
+        }
+        assert_eq!(
+            said(&plugin, "λecho in 25h grab the pizza").await,
+            Some("At most 24h".to_string())
+        );
+        assert_eq!(
+            said(&plugin, "coucou").await,
+            Some("echo - coucou".to_string()),
+            "still an echo"
+        );
+        assert_eq!(
+            said(&plugin, "λecho inside").await,
+            Some("echo - λecho inside".to_string())
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_delivery() {
+        let plugin = Echo {
+            members: members(),
+            ..echo(None)
+        };
+        let (tx, mut rx) = mpsc::channel(10);
+        let until = |s| tokio::time::timeout(secs(s), plugin.run(tx.clone()));
+
+        said(&plugin, "λecho in 10m grab the pizza").await;
+        said(&plugin, "λecho in 1m the oven > bob").await;
+        assert!(until(59).await.is_err());
+        assert_eq!(drain(&mut rx), vec![]);
+        assert!(until(1).await.is_err());
+        assert_eq!(
+            drain(&mut rx),
+            vec![Outbound::reply("#rust", "bob: <a\u{200D}lice> the oven")]
+        );
+        assert!(until(9 * 60).await.is_err());
+        assert_eq!(
+            drain(&mut rx),
+            vec![Outbound::reply("#rust", "alice: grab the pizza")]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_survives_restart() {
+        let db = Database::in_memory().unwrap();
+        let plugin = echo(Some(db.clone()));
+        said(&plugin, "λecho in 10m grab the pizza").await;
+        drop(plugin);
+
+        let plugin = echo(Some(db.clone()));
+        let (tx, mut rx) = mpsc::channel(10);
+        assert!(tokio::time::timeout(secs(601), plugin.run(tx))
+            .await
+            .is_err());
+        assert_eq!(
+            drain(&mut rx),
+            vec![Outbound::reply("#rust", "alice: grab the pizza")]
+        );
+        assert!(echo(Some(db)).pending.scheduled.is_empty(), "done with");
+    }
+
+    #[test]
+    async fn test_sanitize() {
+        assert_eq!(sanitize("  grab the pizza ", 300), "grab the pizza");
+        assert_eq!(
+            sanitize("\x01PING 1234\x01", 300),
+            "PING 1234",
+            "not a ctcp query"
+        );
+        assert_eq!(
+            sanitize("\x02bold\x02 \x0304,12red\x03 \x1Ditalic\x0F", 300),
+            "bold red italic"
+        );
+        assert_eq!(sanitize("multi\r\nline\x00", 300), "multiline");
+        assert_eq!(sanitize("abcdefghij", 10), "abcdefghij");
+        assert_eq!(sanitize("abcdefghijk", 10), "abcdefghi…");
+        assert_eq!(sanitize("àéîõüàéîõüà", 10), "àéîõüàéîõ…", "in chars");
+    }
+
+    #[test]
+    async fn test_command_prefixes() {
+        let plugin = echo(None);
+        for command in ["!kick bob", "λjoke", ".op alice", "\x02!kick\x02 bob"] {
+            assert_eq!(
+                said(&plugin, &format!("λecho in 1m {command}")).await,
+                Some("Not echoing the commands of other bots".to_string()),
+                "{command:?}"
+            );
+        }
+        assert_eq!(
+            said(&plugin, "λecho in 1m what about λjoke?").await,
+            Some("ok, in 1m".to_string()),
+            "only at the start"
+        );
+        assert_eq!(
+            said(&plugin, "λecho in 1m \x01\x02").await,
+            Some(USAGE.to_string()),
+            "nothing left"
+        );
+    }
+
+    #[test]
+    async fn test_cooldown() {
+        let plugin = Echo {
+            cooldown: Cooldown::new(secs(30)),
+            ..echo(None)
+        };
+        assert_eq!(
+            said(&plugin, "λecho in 1m grab the pizza").await,
+            Some("ok, in 1m".to_string())
+        );
+        assert_eq!(said(&plugin, "λecho in 1m the oven").await, None);
+        assert_eq!(
+            said_by(&plugin, "ALICE", "λecho in 1m the oven").await,
+            None,
+            "the same nick"
+        );
+        assert_eq!(
+            said_by(&plugin, "carol[m]", "λecho in 1m grab the pizza").await,
+            Some("ok, in 1m".to_string())
+        );
+        assert_eq!(
+            said_by(&plugin, "Carol{M}", "λecho in 1m the oven").await,
+            None,
+            "under the casemapping of the network"
+        );
+        assert_eq!(
+            said_by(&plugin, "bob", "λecho in 1m the oven").await,
+            Some("ok, in 1m".to_string())
+        );
+        assert_eq!(
+            said(&plugin, "λecho in 1m !kick bob").await,
+            Some("Not echoing the commands of other bots".to_string()),
+            "refusals still answered"
+        );
+        assert_eq!(said(&plugin, "coucou").await, None, "the plain echo too");
+        assert_eq!(
+            said_by(&plugin, "dave", "coucou").await,
+            Some("echo - coucou".to_string())
+        );
+        assert_eq!(said_by(&plugin, "dave", "λecho in 1m the oven").await, None);
+    }
+
+    #[test]
+    async fn test_max_pending() {
+        let plugin = Echo {
+            max_pending: 2,
+            ..echo(None)
+        };
+        for _ in 0..2 {
+            assert_eq!(
+                said(&plugin, "λecho in 1m grab the pizza").await,
+                Some("ok, in 1m".to_string())
+            );
+        }
+        assert_eq!(
+            said_by(&plugin, "ALICE", "λecho in 1m the oven").await,
+            Some("You have 2 echoes waiting already".to_string())
+        );
+        assert_eq!(
+            said_by(&plugin, "bob", "λecho in 1m the oven").await,
+            Some("ok, in 1m".to_string()),
+            "for each nick"
+        );
+    }
+
+    #[test]
+    async fn test_target() {
+        let plugin = Echo {
+            members: members(),
+            ..echo(None)
+        };
+        assert_eq!(
+            said(&plugin, "λecho in 1m the oven > bob").await,
+            Some("ok, in 1m".to_string())
+        );
+        assert_eq!(
+            said(&plugin, "λecho in 1m the oven > mallory").await,
+            Some("mallory isn't in #rust".to_string()),
+            "not highlighting just anyone"
+        );
+        assert_eq!(
+            outbound(&plugin, "alice", "golem", "λecho in 1m the oven > bob").await,
+            Some(Outbound::reply(
+                "alice",
+                "Only in a channel can you echo for someone else"
+            )),
+            "nor in private"
+        );
+        assert_eq!(
+            said_by(&plugin, "carol", "λecho in 1m the oven").await,
+            Some("ok, in 1m".to_string()),
+            "only for someone else"
+        );
+    }
+
+    #[test]
+    async fn test_attribution() {
+        let scheduled = |nick: &str, requester: &str| Scheduled {
+            id: None,
+            target: "#rust".to_string(),
+            nick: nick.to_string(),
+            requester: requester.to_string(),
+            casemapping: CaseMapping::Rfc1459,
+            text: "the oven".to_string(),
+        };
+        assert_eq!(
+            scheduled("bob", "alice").message(true),
+            "bob: <a\u{200D}lice> the oven"
+        );
+        assert_eq!(scheduled("bob", "alice").message(false), "bob: the oven");
+        assert_eq!(
+            scheduled("alice[m]", "Alice{M}").message(true),
+            "alice[m]: the oven"
+        );
+        assert_eq!(
+            scheduled("bob", "").message(true),
+            "bob: the oven",
+            "from before the attribution"
+        );
+    }
+
+    #[test]
+    async fn test_parse_echo_to() {
+        assert_eq!(
+            parse_echo_to("to #ocaml  grab the pizza "),
+            Some(("#ocaml", "grab the pizza"))
+        );
+        assert_eq!(parse_echo_to("to &local hi"), Some(("&local", "hi")));
+        for invalid in ["to", "to #ocaml", "to #ocaml  ", "to ocaml hi"] {
+            assert_eq!(parse_echo_to(invalid), None, "{invalid:?}");
+        }
+    }
+
+    /// golem, alice and bob in #rust, golem and alice in #ocaml
+    fn members() -> Arc<Members> {
+        let members = Members::default();
+        for (nick, channel) in [
+            ("golem", "#rust"),
+            ("alice", "#rust"),
+            ("bob", "#rust"),
+            ("golem", "#ocaml"),
+            ("alice", "#ocaml"),
+        ] {
+            let source = format!("{nick}!~{nick}@localhost");
+            let join = Message::new(Some(&source), "JOIN", vec![channel]).unwrap();
+            members.on_message("libera", "golem", &join);
+        }
+        Arc::new(members)
+    }
+
+    #[test]
+    async fn test_echo_to() {
+        let plugin = Echo {
+            members: members(),
+            ..echo(None)
+        };
+        assert_eq!(
+            outbound(&plugin, "alice", "#rust", "λecho to #OCaml grab the pizza").await,
+            Some(Outbound::reply(
+                "#OCaml",
+                "<a\u{200D}lice@#rust> grab the pizza"
+            ))
+        );
+        assert_eq!(
+            outbound(&plugin, "alice", "golem", "λecho to #ocaml grab the pizza").await,
+            Some(Outbound::reply("#ocaml", "<a\u{200D}lice> grab the pizza")),
+            "in private"
+        );
+        assert_eq!(
+            outbound(
+                &plugin,
+                "alice",
+                "#rust",
+                "λecho to #haskell grab the pizza"
+            )
+            .await,
+            Some(Outbound::notice("alice", "I'm not in #haskell"))
+        );
+        assert_eq!(
+            outbound(&plugin, "bob", "#rust", "λecho to #ocaml grab the pizza").await,
+            Some(Outbound::notice(
+                "bob",
+                "You're not in #ocaml, only there can you echo to it"
+            ))
+        );
+        assert_eq!(
+            outbound(&plugin, "alice", "#rust", "λecho to #ocaml !kick bob").await,
+            Some(Outbound::reply(
+                "#rust",
+                "Not echoing the commands of other bots"
+            ))
+        );
+        assert_eq!(
+            outbound(&plugin, "alice", "#rust", "λecho to ocaml hi").await,
+            Some(Outbound::reply("#rust", USAGE_TO))
+        );
+        assert_eq!(
+            said(&plugin, "λecho tomorrow").await,
+            Some("echo - λecho tomorrow".to_string())
+        );
+    }
+
+    #[test]
+    async fn test_echo_to_other_network() {
+        let plugin = Echo {
+            members: members(),
+            ..echo(None)
+        };
+        let mut msg = Message::new(
+            Some("alice!~alice@localhost"),
+            "PRIVMSG",
+            vec!["#rust", "λecho to #ocaml grab the pizza"],
+        )
+        .unwrap();
+        set_network(&mut msg, "oftc");
+        assert_eq!(
+            in_msg(&plugin, &msg, CaseMapping::Rfc1459).await.unwrap(),
+            Some(Outbound::notice("alice", "I'm not in #ocaml"))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_markup() {
+        let plugin = Echo {
+            no_colors: vec!["#OCaml".to_string()],
+            members: members(),
+            ..echo(None)
+        };
+        assert_eq!(
+            outbound(
+                &plugin,
+                "alice",
+                "#rust",
+                "λecho to #ocaml *grab* the pizza"
+            )
+            .await,
+            Some(Outbound::reply(
+                "#ocaml",
+                "<a\u{200D}lice@#rust> *grab* the pizza"
+            )),
+            "no colors there"
+        );
+        assert_eq!(
+            outbound(
+                &plugin,
+                "alice",
+                "#ocaml",
+                "λecho to #rust %red{grab} the pizza"
+            )
+            .await,
+            Some(Outbound::reply(
+                "#rust",
+                "<a\u{200D}lice@#ocaml> \x0304grab\x03 the pizza\x0F"
+            ))
+        );
+        assert_eq!(
+            said(&plugin, "λecho in 1m *!kick* bob").await,
+            Some("Not echoing the commands of other bots".to_string()),
+            "once formatted"
+        );
+        assert_eq!(
+            said(&plugin, "λecho in 1m \x0304*grab*\x03 the pizza").await,
+            Some("ok, in 1m".to_string())
+        );
+        let (tx, mut rx) = mpsc::channel(10);
+        assert!(tokio::time::timeout(secs(60), plugin.run(tx))
+            .await
+            .is_err());
+        assert_eq!(
+            drain(&mut rx),
+            vec![Outbound::reply(
+                "#rust",
+                "alice: \x02grab\x02 the pizza\x0F"
+            )],
+            "only the markup makes formatting codes"
+        );
+    }
+
+    #[test]
+    async fn test_again_and_forget() {
+        let plugin = Echo {
+            members: members(),
+            ..echo(None)
+        };
+        assert_eq!(
+            said(&plugin, "λecho again").await,
+            Some("Nothing echoed here yet".to_string())
+        );
+        for text in ["grab the pizza", "the oven"] {
+            outbound(
+                &plugin,
+                "alice",
+                "#ocaml",
+                &format!("λecho to #rust {text}"),
+            )
+            .await;
+        }
+        outbound(&plugin, "bob", "#ocaml", "λecho to #rust the pasta").await;
+        assert_eq!(
+            said(&plugin, "λecho again").await,
+            Some("<b\u{200D}ob@#ocaml> the pasta".to_string())
+        );
+        assert_eq!(
+            said(&plugin, "λecho again 3").await,
+            Some("<a\u{200D}lice@#ocaml> grab the pizza".to_string())
+        );
+        assert_eq!(
+            said(&plugin, "λecho again 4").await,
+            Some("Only the last 3 echoes are remembered here".to_string())
+        );
+        for invalid in ["λecho again 0", "λecho again last"] {
+            assert_eq!(
+                said(&plugin, invalid).await,
+                Some(USAGE_AGAIN.to_string()),
+                "{invalid}"
+            );
+        }
+
+        assert_eq!(
+            said_by(&plugin, "carol", "λecho forget").await,
+            Some("Only the admins and whoever asked for an echo can forget it".to_string())
+        );
+        assert_eq!(
+            said_by(&plugin, "alice", "λecho forget").await,
+            Some("Forgot 2 echoes".to_string()),
+            "her own"
+        );
+        assert_eq!(
+            said(&plugin, "λecho again").await,
+            Some("<b\u{200D}ob@#ocaml> the pasta".to_string())
+        );
+        assert_eq!(
+            said(&plugin, "λecho again 2").await,
+            Some("Only the last echo is remembered here".to_string())
+        );
+        outbound(&plugin, "alice", "#ocaml", "λecho to #rust grab the pizza").await;
+        assert_eq!(
+            said_by(&plugin, "Root", "λecho forget").await,
+            Some("Forgot 2 echoes".to_string()),
+            "all of them"
+        );
+        assert_eq!(
+            said(&plugin, "λecho again").await,
+            Some("Nothing echoed here yet".to_string())
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_again_after_delivery() {
+        let plugin = echo(None);
+        said(&plugin, "λecho in 1m grab the pizza > bob").await;
+        let (tx, _rx) = mpsc::channel(10);
+        assert!(tokio::time::timeout(secs(60), plugin.run(tx))
+            .await
+            .is_err());
+        assert_eq!(
+            said(&plugin, "λecho again").await,
+            Some("bob: <a\u{200D}lice> grab the pizza".to_string())
+        );
+    }
+}
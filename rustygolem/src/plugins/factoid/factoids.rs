@@ -0,0 +1,378 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+use plugin_core::{Database, Result};
+
+use crate::caps::CaseMapping;
+
+/// Definitions kept for each factoid, the current one included
+pub const MAX_REVISIONS: usize = 5;
+
+/// The tables of the factoid plugin in the shared database, see
+/// `plugin_core::ensure_schema`
+const MIGRATIONS: &[&str] = &[
+    // channel is normalized with the casemapping of the network, key with
+    // `normalize_key`, author is who learned it first
+    "CREATE TABLE factoid_factoids (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        network TEXT NOT NULL,
+        channel TEXT NOT NULL,
+        key TEXT NOT NULL,
+        author TEXT NOT NULL,
+        UNIQUE (network, channel, key)
+    );
+    CREATE TABLE factoid_revisions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        factoid_id INTEGER NOT NULL REFERENCES factoid_factoids(id),
+        text TEXT NOT NULL,
+        defined_by TEXT NOT NULL,
+        defined_at TEXT NOT NULL
+    );",
+];
+
+#[derive(QueryableByName)]
+struct Id {
+    #[sql_type = "BigInt"]
+    id: i64,
+}
+
+#[derive(QueryableByName)]
+struct Key {
+    #[sql_type = "Text"]
+    key: String,
+}
+
+#[derive(Debug, Clone, PartialEq, QueryableByName)]
+pub struct Factoid {
+    #[sql_type = "Text"]
+    pub key: String,
+    #[sql_type = "Text"]
+    pub author: String,
+    /// of the current definition
+    #[sql_type = "Text"]
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, QueryableByName)]
+pub struct Revision {
+    #[sql_type = "Text"]
+    pub text: String,
+    #[sql_type = "Text"]
+    pub defined_by: String,
+    /// rfc3339, in UTC
+    #[sql_type = "Text"]
+    pub defined_at: String,
+}
+
+impl Revision {
+    pub fn new(text: &str, defined_by: &str, defined_at: DateTime<Utc>) -> Self {
+        Revision {
+            text: text.to_string(),
+            defined_by: defined_by.to_string(),
+            defined_at: defined_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
+/// In lowercase, without the spaces around and with single spaces inside
+pub fn normalize_key(key: &str) -> String {
+    key.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// The factoids of every channel, with their last definitions
+pub struct Factoids {
+    db: Database,
+}
+
+impl Factoids {
+    /// Create the tables if needed
+    pub fn load(db: Database) -> Result<Self> {
+        plugin_core::ensure_schema(&db, "factoid", MIGRATIONS)?;
+        Ok(Factoids { db })
+    }
+
+    /// Defines the factoid, or redefines it keeping its previous definitions.
+    /// True when it was a redefinition.
+    pub fn learn(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        key: &str,
+        revision: &Revision,
+    ) -> Result<bool> {
+        let channel = casemapping.normalize(channel);
+        let key = normalize_key(key);
+        self.db.with_connection(|conn| {
+            conn.transaction(|| {
+                let inserted = diesel::sql_query(
+                    "INSERT OR IGNORE INTO factoid_factoids (network, channel, key, author) \
+                     VALUES (?, ?, ?, ?)",
+                )
+                .bind::<Text, _>(network)
+                .bind::<Text, _>(&channel)
+                .bind::<Text, _>(&key)
+                .bind::<Text, _>(&revision.defined_by)
+                .execute(conn)?;
+                let factoid = diesel::sql_query(
+                    "SELECT id FROM factoid_factoids WHERE network = ? AND channel = ? AND key = ?",
+                )
+                .bind::<Text, _>(network)
+                .bind::<Text, _>(&channel)
+                .bind::<Text, _>(&key)
+                .get_result::<Id>(conn)?;
+                diesel::sql_query(
+                    "INSERT INTO factoid_revisions (factoid_id, text, defined_by, defined_at) \
+                     VALUES (?, ?, ?, ?)",
+                )
+                .bind::<BigInt, _>(factoid.id)
+                .bind::<Text, _>(&revision.text)
+                .bind::<Text, _>(&revision.defined_by)
+                .bind::<Text, _>(&revision.defined_at)
+                .execute(conn)?;
+                diesel::sql_query(
+                    "DELETE FROM factoid_revisions WHERE factoid_id = ? AND id NOT IN \
+                     (SELECT id FROM factoid_revisions WHERE factoid_id = ? \
+                      ORDER BY id DESC LIMIT ?)",
+                )
+                .bind::<BigInt, _>(factoid.id)
+                .bind::<BigInt, _>(factoid.id)
+                .bind::<BigInt, _>(MAX_REVISIONS as i64)
+                .execute(conn)?;
+                Ok(inserted == 0)
+            })
+        })
+    }
+
+    /// With its current definition, None when there's no such factoid in
+    /// the channel
+    pub fn get(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        key: &str,
+    ) -> Result<Option<Factoid>> {
+        let factoids = self.db.with_connection(|conn| {
+            diesel::sql_query(
+                "SELECT f.key AS key, f.author AS author, r.text AS text \
+                 FROM factoid_factoids f JOIN factoid_revisions r ON r.factoid_id = f.id \
+                 WHERE f.network = ? AND f.channel = ? AND f.key = ? \
+                 ORDER BY r.id DESC LIMIT 1",
+            )
+            .bind::<Text, _>(network)
+            .bind::<Text, _>(casemapping.normalize(channel))
+            .bind::<Text, _>(normalize_key(key))
+            .load::<Factoid>(conn)
+        })?;
+        Ok(factoids.into_iter().next())
+    }
+
+    /// The definitions of the factoid, the current one first
+    pub fn history(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        key: &str,
+    ) -> Result<Vec<Revision>> {
+        self.db.with_connection(|conn| {
+            diesel::sql_query(
+                "SELECT r.text AS text, r.defined_by AS defined_by, r.defined_at AS defined_at \
+                 FROM factoid_factoids f JOIN factoid_revisions r ON r.factoid_id = f.id \
+                 WHERE f.network = ? AND f.channel = ? AND f.key = ? \
+                 ORDER BY r.id DESC",
+            )
+            .bind::<Text, _>(network)
+            .bind::<Text, _>(casemapping.normalize(channel))
+            .bind::<Text, _>(normalize_key(key))
+            .load::<Revision>(conn)
+        })
+    }
+
+    /// The keys of the channel, in alphabetical order
+    pub fn keys(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+    ) -> Result<Vec<String>> {
+        let keys = self.db.with_connection(|conn| {
+            diesel::sql_query(
+                "SELECT key FROM factoid_factoids WHERE network = ? AND channel = ? ORDER BY key",
+            )
+            .bind::<Text, _>(network)
+            .bind::<Text, _>(casemapping.normalize(channel))
+            .load::<Key>(conn)
+        })?;
+        Ok(keys.into_iter().map(|key| key.key).collect())
+    }
+
+    /// Removes the factoid and all its definitions, false when there's no
+    /// such factoid
+    pub fn forget(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        key: &str,
+    ) -> Result<bool> {
+        let channel = casemapping.normalize(channel);
+        let key = normalize_key(key);
+        self.db.with_connection(|conn| {
+            conn.transaction(|| {
+                // the foreign keys may not be enforced by sqlite
+                diesel::sql_query(
+                    "DELETE FROM factoid_revisions WHERE factoid_id IN \
+                     (SELECT id FROM factoid_factoids WHERE network = ? AND channel = ? AND key = ?)",
+                )
+                .bind::<Text, _>(network)
+                .bind::<Text, _>(&channel)
+                .bind::<Text, _>(&key)
+                .execute(conn)?;
+                let removed = diesel::sql_query(
+                    "DELETE FROM factoid_factoids WHERE network = ? AND channel = ? AND key = ?",
+                )
+                .bind::<Text, _>(network)
+                .bind::<Text, _>(&channel)
+                .bind::<Text, _>(&key)
+                .execute(conn)?;
+                Ok(removed > 0)
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    const RFC1459: CaseMapping = CaseMapping::Rfc1459;
+
+    fn learn(factoids: &Factoids, channel: &str, key: &str, text: &str, nick: &str) -> bool {
+        let now = Utc.ymd(2025, 3, 1).and_hms(10, 0, 0);
+        factoids
+            .learn(
+                "libera",
+                RFC1459,
+                channel,
+                key,
+                &Revision::new(text, nick, now),
+            )
+            .unwrap()
+    }
+
+    fn text(factoids: &Factoids, channel: &str, key: &str) -> Option<String> {
+        factoids
+            .get("libera", RFC1459, channel, key)
+            .unwrap()
+            .map(|factoid| factoid.text)
+    }
+
+    #[test]
+    async fn test_normalize_key() {
+        assert_eq!(normalize_key("  Build-Docs "), "build-docs");
+        assert_eq!(normalize_key("Release \t Process"), "release process");
+    }
+
+    #[test]
+    async fn test_persistence() {
+        let db = Database::in_memory().unwrap();
+        let factoids = Factoids::load(db.clone()).unwrap();
+        assert!(!learn(
+            &factoids,
+            "#Rust",
+            "Build-Docs",
+            "run cargo doc --open",
+            "alice"
+        ));
+        learn(
+            &factoids,
+            "#ocaml",
+            "build-docs",
+            "run dune build @doc",
+            "bob",
+        );
+
+        let factoids = Factoids::load(db).unwrap();
+        assert_eq!(
+            factoids
+                .get("libera", RFC1459, "#rust", " build-docs")
+                .unwrap(),
+            Some(Factoid {
+                key: "build-docs".to_string(),
+                author: "alice".to_string(),
+                text: "run cargo doc --open".to_string(),
+            })
+        );
+        assert_eq!(
+            text(&factoids, "#ocaml", "BUILD-DOCS").as_deref(),
+            Some("run dune build @doc"),
+            "per channel"
+        );
+        assert_eq!(
+            factoids
+                .get("oftc", RFC1459, "#rust", "build-docs")
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            factoids.keys("libera", RFC1459, "#RUST").unwrap(),
+            vec!["build-docs".to_string()]
+        );
+
+        assert!(factoids
+            .forget("libera", RFC1459, "#rust", "Build-docs")
+            .unwrap());
+        assert!(!factoids
+            .forget("libera", RFC1459, "#rust", "build-docs")
+            .unwrap());
+        assert_eq!(text(&factoids, "#rust", "build-docs"), None);
+        assert!(factoids
+            .history("libera", RFC1459, "#rust", "build-docs")
+            .unwrap()
+            .is_empty());
+        assert!(factoids
+            .keys("libera", RFC1459, "#rust")
+            .unwrap()
+            .is_empty());
+        assert!(text(&factoids, "#ocaml", "build-docs").is_some());
+    }
+
+    #[test]
+    async fn test_history() {
+        let factoids = Factoids::load(Database::in_memory().unwrap()).unwrap();
+        learn(&factoids, "#rust", "msrv", "1.56", "alice");
+        for i in 57..=62 {
+            assert!(learn(&factoids, "#rust", "msrv", &format!("1.{i}"), "bob"));
+        }
+        assert_eq!(text(&factoids, "#rust", "msrv").as_deref(), Some("1.62"));
+        let history = factoids
+            .history("libera", RFC1459, "#rust", "msrv")
+            .unwrap();
+        assert_eq!(
+            history
+                .iter()
+                .map(|revision| revision.text.as_str())
+                .collect::<Vec<_>>(),
+            vec!["1.62", "1.61", "1.60", "1.59", "1.58"],
+            "the oldest ones are forgotten"
+        );
+        assert_eq!(history[0].defined_by, "bob");
+        assert_eq!(history[0].defined_at, "2025-03-01T10:00:00Z");
+        assert_eq!(
+            factoids
+                .get("libera", RFC1459, "#rust", "msrv")
+                .unwrap()
+                .unwrap()
+                .author,
+            "alice",
+            "still the one who learned it first"
+        );
+    }
+}
@@ -0,0 +1,4 @@
+mod factoids;
+mod plugin;
+
+pub use plugin::Factoid;
@@ -0,0 +1,509 @@
+use std::result::Result as StdResult;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use irc::proto::{ChannelExt, Command, Message};
+use nom::bytes::complete::tag;
+use nom::sequence::preceded;
+use plugin_core::utils::account::is_admin;
+use plugin_core::utils::network::network;
+use plugin_core::utils::parser;
+use plugin_core::{CommandHelp, Initialised, MsgCtx, Outbound, Plugin, Requirement, Result};
+
+use super::factoids::{normalize_key, Factoids, Revision};
+use crate::caps::CaseMapping;
+use crate::utils::messages::with_target;
+use crate::utils::text::{distance, sanitize};
+
+const LEARN_USAGE: &str = "Usage: λlearn <key> = <text>";
+const FAQ_USAGE: &str = "Usage: λfaq <key>, λfaq list, λfaq history <key>";
+const FORGET_USAGE: &str = "Usage: λforget <key>";
+
+/// `λfaq list` and `λfaq history` aren't factoids
+const RESERVED_KEYS: &[&str] = &["list", "history"];
+
+/// Longest key, in chars
+const MAX_KEY_LENGTH: usize = 50;
+
+/// Longest factoid saved, in chars
+const MAX_SAVED: usize = 400;
+
+/// Longer replies are cut, in chars
+const MAX_REPLY_LENGTH: usize = 400;
+
+/// An unknown key this close to a known one is corrected
+const MAX_TYPOS: usize = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+enum FactoidCommand<'a> {
+    /// `λlearn build-docs = run cargo doc --open`
+    Learn {
+        key: &'a str,
+        text: &'a str,
+    },
+    /// `λfaq build-docs` or `λ? build-docs`
+    Recall(&'a str),
+    List,
+    History(&'a str),
+    Forget(&'a str),
+}
+
+/// The command and the target nick of the reply, or the usage
+type Parsed<'a> = StdResult<(FactoidCommand<'a>, Option<&'a str>), String>;
+
+/// None when this isn't a factoid command, an error with the usage when it
+/// is one, but malformed
+fn parse_command(input: &str) -> Option<Parsed<'_>> {
+    if let Some(learn) = parse_learn(input) {
+        return Some(learn.map(|command| (command, None)));
+    }
+    if let Ok((_, (key, target))) = parser::command("forget")(input) {
+        return Some(match key {
+            "" => Err(FORGET_USAGE.to_string()),
+            key => Ok((FactoidCommand::Forget(key), target)),
+        });
+    }
+    let (args, target) = parser::command("faq")(input)
+        .or_else(|_| parser::command("?")(input))
+        .ok()?
+        .1;
+    let (word, rest) = match args.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (args, ""),
+    };
+    let command = match (word, rest) {
+        ("", _) => None,
+        ("list", "") => Some(FactoidCommand::List),
+        ("history", "") => None,
+        ("history", key) => Some(FactoidCommand::History(key)),
+        _ => Some(FactoidCommand::Recall(args)),
+    };
+    Some(
+        command
+            .map(|command| (command, target))
+            .ok_or_else(|| FAQ_USAGE.to_string()),
+    )
+}
+
+/// The whole text is the factoid, `> nick` included
+fn parse_learn(input: &str) -> Option<StdResult<FactoidCommand<'_>, String>> {
+    let (rest, _) = preceded(parser::command_prefix, tag("learn"))(input).ok()?;
+    if rest.starts_with(|c: char| !c.is_whitespace()) {
+        return None;
+    }
+    let learn = rest.split_once('=').and_then(|(key, text)| {
+        let (key, text) = (key.trim(), text.trim());
+        if key.is_empty() || text.is_empty() {
+            None
+        } else {
+            Some(FactoidCommand::Learn { key, text })
+        }
+    });
+    Some(learn.ok_or_else(|| LEARN_USAGE.to_string()))
+}
+
+/// With `$nick` replaced
+fn substitute(text: &str, nick: &str) -> String {
+    text.replace("$nick", nick)
+}
+
+pub struct Factoid {
+    factoids: Factoids,
+    /// allowed to forget any factoid
+    admins: Vec<String>,
+}
+
+#[async_trait]
+impl Plugin for Factoid {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        config.check_database("factoid")?;
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let db = config.require_database("factoid")?;
+        Ok(Initialised::from(Factoid {
+            factoids: Factoids::load(db)?,
+            admins: config.admins()?,
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "factoid"
+    }
+
+    async fn in_message_ctx(&self, ctx: &MsgCtx, msg: &Message) -> Result<Option<Outbound>> {
+        in_msg(self, msg, ctx.casemapping, Utc::now())
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("learn")
+                .usage("learn <key> = <text>")
+                .description(
+                    "Teach the channel something, $nick in the text is whoever asks for it. \
+                     Only whoever taught a key and the admins can redefine it",
+                ),
+            CommandHelp::new("faq")
+                .usage("faq <key> [> nick]")
+                .description("What the channel learned about the key, also λ? <key>"),
+            CommandHelp::new("faq list")
+                .usage("faq list")
+                .description("The keys the channel learned"),
+            CommandHelp::new("faq history")
+                .usage("faq history <key>")
+                .description("The last definitions of the key"),
+            CommandHelp::new("forget")
+                .usage("forget <key>")
+                .description("Forget a key you taught, any of them for the admins"),
+        ]
+    }
+
+    fn requirements(&self) -> Vec<Requirement> {
+        // the factoids
+        vec![Requirement::Database]
+    }
+}
+
+fn in_msg(
+    plugin: &Factoid,
+    msg: &Message,
+    casemapping: CaseMapping,
+    now: DateTime<Utc>,
+) -> Result<Option<Outbound>> {
+    let response_target = match msg.response_target() {
+        None => return Ok(None),
+        Some(target) => target,
+    };
+    let command = match &msg.command {
+        Command::PRIVMSG(_source, privmsg) => match parse_command(privmsg) {
+            Some(command) => command,
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    let (command, mb_target) = match command {
+        Ok(_) if !response_target.is_channel_name() => {
+            return Ok(Some(Outbound::reply(
+                response_target,
+                "The factoids are per channel, ask in one",
+            )))
+        }
+        Ok(command) => command,
+        Err(usage) => return Ok(Some(Outbound::reply(response_target, usage))),
+    };
+    let channel = response_target;
+    let network = network(msg).unwrap_or_default();
+    let nick = msg.source_nickname().unwrap_or_default();
+    let text = match command {
+        FactoidCommand::Learn { key, .. }
+            if RESERVED_KEYS.contains(&normalize_key(key).as_str()) =>
+        {
+            format!("{} can't be learned", normalize_key(key))
+        }
+        FactoidCommand::Learn { key, .. } if key.chars().count() > MAX_KEY_LENGTH => {
+            format!("The keys are {MAX_KEY_LENGTH} characters at most")
+        }
+        FactoidCommand::Learn { key, text } => {
+            let key = normalize_key(key);
+            // like λforget, a redefinition would make it say anything else
+            if let Some(factoid) = plugin.factoids.get(network, casemapping, channel, &key)? {
+                if !is_admin(&plugin.admins, msg, casemapping)
+                    && !casemapping.eq_ignore_case(&factoid.author, nick)
+                {
+                    let text = format!(
+                        "Only the admins and {} who taught {} can redefine it",
+                        factoid.author, factoid.key
+                    );
+                    return Ok(Some(Outbound::reply(channel, text)));
+                }
+            }
+            let revision = Revision::new(&sanitize(text, MAX_SAVED), nick, now);
+            if plugin
+                .factoids
+                .learn(network, casemapping, channel, &key, &revision)?
+            {
+                format!("Redefined {key}, the previous definitions are in λfaq history {key}")
+            } else {
+                format!("Learned {key}")
+            }
+        }
+        FactoidCommand::Recall(key) => {
+            match plugin.factoids.get(network, casemapping, channel, key)? {
+                Some(factoid) => substitute(&factoid.text, mb_target.unwrap_or(nick)),
+                None => {
+                    let key = normalize_key(key);
+                    let closest = plugin
+                        .factoids
+                        .keys(network, casemapping, channel)?
+                        .into_iter()
+                        .map(|known| (distance(&key, &known), known))
+                        .filter(|(typos, _)| *typos <= MAX_TYPOS)
+                        .min();
+                    match closest {
+                        Some((_, known)) => format!("No factoid {key} here, did you mean {known}?"),
+                        None => format!("No factoid {key} here"),
+                    }
+                }
+            }
+        }
+        FactoidCommand::List => {
+            let keys = plugin.factoids.keys(network, casemapping, channel)?;
+            if keys.is_empty() {
+                "No factoid here yet, add one with λlearn <key> = <text>".to_string()
+            } else {
+                format!("Factoids here: {}", keys.join(", "))
+            }
+        }
+        FactoidCommand::History(key) => {
+            let history = plugin
+                .factoids
+                .history(network, casemapping, channel, key)?;
+            if history.is_empty() {
+                format!("No factoid {} here", normalize_key(key))
+            } else {
+                let revisions = history
+                    .iter()
+                    .map(|revision| {
+                        let date = revision.defined_at.split('T').next().unwrap_or_default();
+                        format!("{} ({}, {date})", revision.text, revision.defined_by)
+                    })
+                    .collect::<Vec<_>>();
+                format!("{}: {}", normalize_key(key), revisions.join(" | "))
+            }
+        }
+        FactoidCommand::Forget(key) => {
+            match plugin.factoids.get(network, casemapping, channel, key)? {
+                None => format!("No factoid {} here", normalize_key(key)),
+                Some(factoid)
+                    if !is_admin(&plugin.admins, msg, casemapping)
+                        && !casemapping.eq_ignore_case(&factoid.author, nick) =>
+                {
+                    format!(
+                        "Only the admins and {} who taught {} can forget it",
+                        factoid.author, factoid.key
+                    )
+                }
+                Some(factoid) => {
+                    plugin
+                        .factoids
+                        .forget(network, casemapping, channel, &factoid.key)?;
+                    format!("Forgot {}", factoid.key)
+                }
+            }
+        }
+    };
+    Ok(Some(Outbound::reply(
+        channel,
+        sanitize(&with_target(&text, &mb_target), MAX_REPLY_LENGTH),
+    )))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use plugin_core::utils::network::set_network;
+    use plugin_core::Database;
+    use pretty_assertions::assert_eq;
+
+    fn factoid() -> Factoid {
+        Factoid {
+            factoids: Factoids::load(Database::in_memory().unwrap()).unwrap(),
+            admins: vec!["root".to_string()],
+        }
+    }
+
+    /// Like `in_message_ctx`, at a fixed time
+    fn say(plugin: &Factoid, nick: &str, target: &str, text: &str) -> Option<String> {
+        let source = format!("{nick}!~{nick}@localhost");
+        let mut msg = Message::new(Some(&source), "PRIVMSG", vec![target, text]).unwrap();
+        set_network(&mut msg, "libera");
+        let now = DateTime::parse_from_rfc3339("2025-03-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        match in_msg(plugin, &msg, CaseMapping::Rfc1459, now).unwrap() {
+            Some(Outbound::Reply { text, .. }) => Some(text),
+            None => None,
+            other => panic!("unexpected reply to {text:?}: {other:?}"),
+        }
+    }
+
+    fn ask(plugin: &Factoid, nick: &str, text: &str) -> String {
+        say(plugin, nick, "#rust", text).unwrap_or_else(|| panic!("no reply to {text:?}"))
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        let learn_usage = Some(Err(LEARN_USAGE.to_string()));
+        let faq_usage = Some(Err(FAQ_USAGE.to_string()));
+        for (input, expected) in [
+            (
+                "λlearn build-docs = run cargo doc --open > docs.log",
+                Some(Ok((
+                    FactoidCommand::Learn {
+                        key: "build-docs",
+                        text: "run cargo doc --open > docs.log",
+                    },
+                    None,
+                ))),
+            ),
+            (
+                "λlearn  x = y = z ",
+                Some(Ok((
+                    FactoidCommand::Learn {
+                        key: "x",
+                        text: "y = z",
+                    },
+                    None,
+                ))),
+            ),
+            ("λlearn build-docs", learn_usage.clone()),
+            ("λlearn = text", learn_usage.clone()),
+            ("λlearn key =", learn_usage),
+            ("λlearning a = b", None),
+            (
+                "λfaq build-docs",
+                Some(Ok((FactoidCommand::Recall("build-docs"), None))),
+            ),
+            (
+                "λ? Release  Process > bob",
+                Some(Ok((
+                    FactoidCommand::Recall("Release  Process"),
+                    Some("bob"),
+                ))),
+            ),
+            ("λfaq list", Some(Ok((FactoidCommand::List, None)))),
+            (
+                "λfaq history build-docs",
+                Some(Ok((FactoidCommand::History("build-docs"), None))),
+            ),
+            ("λfaq history", faq_usage.clone()),
+            ("λfaq", faq_usage.clone()),
+            ("λ?", faq_usage),
+            (
+                "λforget build-docs",
+                Some(Ok((FactoidCommand::Forget("build-docs"), None))),
+            ),
+            ("λforget", Some(Err(FORGET_USAGE.to_string()))),
+            ("λfaqs build-docs", None),
+            ("λ?? build-docs", None),
+            ("what? build-docs", None),
+        ] {
+            assert_eq!(parse_command(input), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    async fn test_substitute() {
+        assert_eq!(
+            substitute("welcome $nick, $nick!", "bob"),
+            "welcome bob, bob!"
+        );
+        assert_eq!(substitute("no nick", "bob"), "no nick");
+    }
+
+    #[test]
+    async fn test_learn_and_recall() {
+        let plugin = factoid();
+        assert_eq!(
+            ask(&plugin, "alice", "λfaq list"),
+            "No factoid here yet, add one with λlearn <key> = <text>"
+        );
+        assert_eq!(
+            ask(&plugin, "alice", "λlearn Build-Docs = run cargo doc --open"),
+            "Learned build-docs"
+        );
+        assert_eq!(
+            ask(
+                &plugin,
+                "alice",
+                "λlearn welcome = hi $nick, read the topic"
+            ),
+            "Learned welcome"
+        );
+        assert_eq!(
+            ask(&plugin, "bob", "λfaq BUILD-DOCS"),
+            "run cargo doc --open"
+        );
+        assert_eq!(ask(&plugin, "bob", "λ? welcome"), "hi bob, read the topic");
+        assert_eq!(
+            ask(&plugin, "bob", "λ? welcome > charlie"),
+            "charlie: hi charlie, read the topic"
+        );
+        assert_eq!(
+            ask(&plugin, "bob", "λfaq build-doc"),
+            "No factoid build-doc here, did you mean build-docs?"
+        );
+        assert_eq!(
+            ask(&plugin, "bob", "λfaq unicorn"),
+            "No factoid unicorn here"
+        );
+        assert_eq!(
+            ask(&plugin, "bob", "λfaq list"),
+            "Factoids here: build-docs, welcome"
+        );
+        assert_eq!(
+            say(&plugin, "bob", "#ocaml", "λfaq build-docs").as_deref(),
+            Some("No factoid build-docs here"),
+            "per channel"
+        );
+        assert_eq!(
+            say(&plugin, "bob", "golem", "λfaq build-docs").as_deref(),
+            Some("The factoids are per channel, ask in one")
+        );
+        assert_eq!(
+            ask(&plugin, "alice", "λlearn list = nope"),
+            "list can't be learned"
+        );
+    }
+
+    #[test]
+    async fn test_history() {
+        let plugin = factoid();
+        ask(&plugin, "alice", "λlearn msrv = 1.56");
+        assert_eq!(
+            ask(&plugin, "bob", "λlearn MSRV = 1.60"),
+            "Only the admins and alice who taught msrv can redefine it"
+        );
+        assert_eq!(
+            ask(&plugin, "ALICE", "λlearn MSRV = 1.60"),
+            "Redefined msrv, the previous definitions are in λfaq history msrv"
+        );
+        assert_eq!(
+            ask(&plugin, "root", "λlearn msrv = 1.70"),
+            "Redefined msrv, the previous definitions are in λfaq history msrv",
+            "admin"
+        );
+        assert_eq!(ask(&plugin, "charlie", "λfaq msrv"), "1.70");
+        assert_eq!(
+            ask(&plugin, "charlie", "λfaq history msrv"),
+            "msrv: 1.70 (root, 2025-03-01) | 1.60 (ALICE, 2025-03-01) | 1.56 (alice, 2025-03-01)"
+        );
+        assert_eq!(
+            ask(&plugin, "charlie", "λfaq history unicorn"),
+            "No factoid unicorn here"
+        );
+    }
+
+    #[test]
+    async fn test_forget() {
+        let plugin = factoid();
+        ask(&plugin, "alice", "λlearn msrv = 1.56");
+        ask(&plugin, "bob", "λlearn edition = 2021");
+        assert_eq!(
+            ask(&plugin, "bob", "λforget msrv"),
+            "Only the admins and alice who taught msrv can forget it"
+        );
+        assert_eq!(ask(&plugin, "ALICE", "λforget msrv"), "Forgot msrv");
+        assert_eq!(ask(&plugin, "alice", "λfaq msrv"), "No factoid msrv here");
+        assert_eq!(
+            ask(&plugin, "alice", "λforget msrv"),
+            "No factoid msrv here"
+        );
+        assert_eq!(ask(&plugin, "root", "λforget Edition"), "Forgot edition");
+        assert_eq!(
+            ask(&plugin, "bob", "λfaq list"),
+            "No factoid here yet, add one with λlearn <key> = <text>"
+        );
+    }
+}
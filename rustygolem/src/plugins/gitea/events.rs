@@ -0,0 +1,252 @@
+use serde::Deserialize;
+
+use crate::utils::forge::{self, IssueAction, PullRequestAction, RepoEvent};
+
+#[derive(Debug, Deserialize)]
+pub struct Repository {
+    /// like `CoucouInc/rustygolem`
+    pub full_name: String,
+    pub html_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct User {
+    pub login: String,
+}
+
+/// https://docs.gitea.com/usage/webhooks, like the github one
+#[derive(Debug, Deserialize)]
+pub struct Push {
+    /// like `refs/heads/master` or `refs/tags/v0.2.0`
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    /// the null sha for a deleted branch or tag
+    pub after: String,
+    pub compare_url: String,
+    /// newest first for some versions of gitea
+    pub commits: Vec<Commit>,
+    /// when there are more than the ones given, not sent by the old versions
+    #[serde(default)]
+    pub total_commits: Option<usize>,
+    pub repository: Repository,
+    pub pusher: User,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Commit {
+    pub id: String,
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PullRequestEvent {
+    pub action: String,
+    pub number: u64,
+    pub pull_request: PullRequest,
+    pub repository: Repository,
+    pub sender: User,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PullRequest {
+    pub title: String,
+    pub html_url: String,
+    #[serde(default)]
+    pub merged: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IssuesEvent {
+    pub action: String,
+    pub issue: Issue,
+    pub repository: Repository,
+    pub sender: User,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Issue {
+    pub number: u64,
+    pub title: String,
+    pub html_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReleaseEvent {
+    pub action: String,
+    pub release: Release,
+    pub repository: Repository,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub html_url: String,
+    #[serde(default)]
+    pub prerelease: bool,
+}
+
+impl Push {
+    /// None for the deleted branches and tags, and the branches pushed
+    /// without new commits
+    fn into_event(self) -> Option<RepoEvent> {
+        if forge::is_null_sha(&self.after) {
+            return None;
+        }
+        let repo = self.repository.full_name;
+        if let Some(tag) = forge::tag(&self.git_ref) {
+            return Some(RepoEvent::Tag {
+                url: format!("{}/src/tag/{tag}", self.repository.html_url),
+                repo,
+                pusher: self.pusher.login,
+                tag: tag.to_string(),
+            });
+        }
+        let branch = forge::branch(&self.git_ref)?;
+        let first = forge::oldest(&self.commits, &self.after, |c| c.id.as_str())?;
+        Some(RepoEvent::Push {
+            repo,
+            pusher: self.pusher.login,
+            branch: branch.to_string(),
+            commits: self.total_commits.unwrap_or(0).max(self.commits.len()),
+            first_message: first.message.clone(),
+            url: self.compare_url,
+        })
+    }
+}
+
+impl PullRequestEvent {
+    fn into_event(self) -> Option<RepoEvent> {
+        let action = match self.action.as_str() {
+            "opened" => PullRequestAction::Opened,
+            "reopened" => PullRequestAction::Reopened,
+            "closed" if self.pull_request.merged => PullRequestAction::Merged,
+            "closed" => PullRequestAction::Closed,
+            _ => return None,
+        };
+        Some(RepoEvent::PullRequest {
+            repo: self.repository.full_name,
+            actor: self.sender.login,
+            action,
+            number: self.number,
+            title: self.pull_request.title,
+            url: self.pull_request.html_url,
+        })
+    }
+}
+
+impl IssuesEvent {
+    fn into_event(self) -> Option<RepoEvent> {
+        let action = match self.action.as_str() {
+            "opened" => IssueAction::Opened,
+            "reopened" => IssueAction::Reopened,
+            "closed" => IssueAction::Closed,
+            _ => return None,
+        };
+        Some(RepoEvent::Issue {
+            repo: self.repository.full_name,
+            actor: self.sender.login,
+            action,
+            number: self.issue.number,
+            title: self.issue.title,
+            url: self.issue.html_url,
+        })
+    }
+}
+
+impl ReleaseEvent {
+    fn into_event(self) -> Option<RepoEvent> {
+        if self.action != "published" {
+            return None;
+        }
+        let release = self.release;
+        let name = release
+            .name
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or(release.tag_name);
+        Some(RepoEvent::Release {
+            repo: self.repository.full_name,
+            name,
+            prerelease: release.prerelease,
+            url: release.html_url,
+        })
+    }
+}
+
+/// The payload of the event named in the `X-Gitea-Event` header, None for
+/// the events not announced
+pub fn parse(event: &str, body: &[u8]) -> serde_json::Result<Option<RepoEvent>> {
+    let event = match event {
+        "push" => serde_json::from_slice::<Push>(body)?.into_event(),
+        "pull_request" => serde_json::from_slice::<PullRequestEvent>(body)?.into_event(),
+        "issues" => serde_json::from_slice::<IssuesEvent>(body)?.into_event(),
+        "release" => serde_json::from_slice::<ReleaseEvent>(body)?.into_event(),
+        _ => None,
+    };
+    Ok(event)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn fixture(name: &str) -> Vec<u8> {
+        let path = format!("{}/fixtures/gitea/{name}.json", env!("CARGO_MANIFEST_DIR"));
+        std::fs::read(path).unwrap()
+    }
+
+    #[test]
+    async fn test_parse() {
+        assert_eq!(
+            parse("push", &fixture("push")).unwrap(),
+            Some(RepoEvent::Push {
+                repo: "CoucouInc/rustygolem".to_string(),
+                pusher: "geekingfrog".to_string(),
+                branch: "master".to_string(),
+                commits: 2,
+                first_message: "Fix the casemapping of the channels\n\n\
+                                The servers announcing rfc1459 treat [] as {}.\n"
+                    .to_string(),
+                url: "https://git.coucou.im/CoucouInc/rustygolem/compare/\
+                      9049f1265b7d61be4a8904a9a27120d2064dab3b...0d1a26e67d8f5eaf1f6ba5c57fc3c7d91ac0fd1c"
+                    .to_string(),
+            })
+        );
+        assert_eq!(
+            parse("push", &fixture("tag_push")).unwrap(),
+            Some(RepoEvent::Tag {
+                repo: "CoucouInc/rustygolem".to_string(),
+                pusher: "geekingfrog".to_string(),
+                tag: "v0.2.0".to_string(),
+                url: "https://git.coucou.im/CoucouInc/rustygolem/src/tag/v0.2.0".to_string(),
+            })
+        );
+        assert_eq!(
+            parse("pull_request", &fixture("pull_request_merged")).unwrap(),
+            Some(RepoEvent::PullRequest {
+                repo: "CoucouInc/rustygolem".to_string(),
+                actor: "geekingfrog".to_string(),
+                action: PullRequestAction::Merged,
+                number: 42,
+                title: "Add the dice plugin".to_string(),
+                url: "https://git.coucou.im/CoucouInc/rustygolem/pulls/42".to_string(),
+            })
+        );
+        assert!(parse("create", &fixture("tag_push")).unwrap().is_none());
+        assert!(parse("issues", &fixture("push")).is_err());
+    }
+
+    #[test]
+    async fn test_not_announced() {
+        let deleted = r#"{"ref": "refs/heads/dice", "after": "0000000000000000000000000000000000000000",
+            "compare_url": "", "commits": [], "pusher": {"login": "geekingfrog"},
+            "repository": {"full_name": "a/b", "html_url": "https://git.coucou.im/a/b"}}"#;
+        assert_eq!(parse("push", deleted.as_bytes()).unwrap(), None);
+        let edited = r#"{"action": "edited", "issue": {"number": 57, "title": "λmeteo",
+            "html_url": "https://git.coucou.im/a/b/issues/57"}, "sender": {"login": "Shampooing"},
+            "repository": {"full_name": "a/b", "html_url": "https://git.coucou.im/a/b"}}"#;
+        assert_eq!(parse("issues", edited.as_bytes()).unwrap(), None);
+    }
+}
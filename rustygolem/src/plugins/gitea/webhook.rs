@@ -0,0 +1,147 @@
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing, Router,
+};
+use plugin_core::Outbound;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use super::events;
+use crate::utils::forge::{self, Route};
+use crate::web;
+
+/// Gitea signs the deliveries with the secret of the webhook in
+/// `X-Gitea-Signature`, or sends it as `Authorization: Bearer <secret>` when
+/// the webhook has an authorization header instead
+fn is_authentic(secret: &str, headers: &HeaderMap, body: &[u8]) -> bool {
+    let signature = forge::header(headers, "X-Gitea-Signature");
+    forge::verify_signature(secret, signature, body) || web::is_authorized(headers, secret)
+}
+
+#[derive(Clone)]
+struct WebhookState {
+    secret: Arc<String>,
+    routes: Arc<Vec<Route>>,
+    /// to the plugin, which sends them to IRC
+    tx: mpsc::Sender<Outbound>,
+}
+
+async fn webhook(State(state): State<WebhookState>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    if !is_authentic(&state.secret, &headers, &body) {
+        log::warn!("Rejecting a gitea delivery without a valid signature or secret");
+        return StatusCode::UNAUTHORIZED;
+    }
+    let kind = match forge::header(&headers, "X-Gitea-Event") {
+        Some(kind) => kind,
+        None => return StatusCode::BAD_REQUEST,
+    };
+    let event = match events::parse(kind, &body) {
+        Ok(Some(event)) => event,
+        Ok(None) => {
+            log::debug!("Ignoring the gitea event {kind}");
+            return StatusCode::OK;
+        }
+        Err(err) => {
+            log::error!("Cannot parse the gitea event {kind}: {err}");
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+    forge::announce(&state.routes, &event, &state.tx).await
+}
+
+/// With `POST /webhook`, the announcements are sent to `tx`
+pub fn router(secret: &str, routes: Vec<Route>, tx: mpsc::Sender<Outbound>) -> Router<()> {
+    let state = WebhookState {
+        secret: Arc::new(secret.to_string()),
+        routes: Arc::new(routes),
+        tx,
+    };
+    Router::new()
+        .route("/webhook", routing::post(webhook))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use hmac::{Hmac, Mac, NewMac};
+    use pretty_assertions::assert_eq;
+    use tower::ServiceExt;
+
+    const SECRET: &str = "s3cr3t";
+
+    fn fixture(name: &str) -> Vec<u8> {
+        let path = format!("{}/fixtures/gitea/{name}.json", env!("CARGO_MANIFEST_DIR"));
+        std::fs::read(path).unwrap()
+    }
+
+    fn sign(body: &[u8]) -> String {
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(body);
+        let sig = mac.finalize().into_bytes();
+        sig.iter().map(|b| format!("{b:02x}")).collect::<String>()
+    }
+
+    async fn post(event: &str, auth: (&str, &str), body: Vec<u8>) -> (StatusCode, Vec<Outbound>) {
+        let (tx, mut rx) = mpsc::channel(10);
+        let routes = vec![Route {
+            repo: "CoucouInc/rustygolem".to_string(),
+            channel: "#coucou".to_string(),
+            events: vec![],
+        }];
+        let app = router(SECRET, routes, tx);
+        let req = Request::builder()
+            .method("POST")
+            .uri("/webhook")
+            .header("X-Gitea-Event", event)
+            .header(auth.0, auth.1)
+            .body(Body::from(body))
+            .unwrap();
+        let status = app.oneshot(req).await.unwrap().status();
+        let mut sent = vec![];
+        while let Ok(msg) = rx.try_recv() {
+            sent.push(msg);
+        }
+        (status, sent)
+    }
+
+    #[test]
+    async fn test_webhook() {
+        let line = "CoucouInc/rustygolem: Shampooing opened issue #57: λmeteo doesn't know \
+                    Saint-Étienne https://git.coucou.im/CoucouInc/rustygolem/issues/57";
+        let body = fixture("issues_opened");
+        let signature = sign(&body);
+        let (status, sent) = post("issues", ("X-Gitea-Signature", &signature), body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(sent, vec![Outbound::reply("#coucou", line)]);
+
+        let bearer = format!("Bearer {SECRET}");
+        let body = fixture("issues_opened");
+        let (status, sent) = post("issues", ("Authorization", &bearer), body).await;
+        assert_eq!(status, StatusCode::OK, "with the secret header");
+        assert_eq!(sent, vec![Outbound::reply("#coucou", line)]);
+    }
+
+    #[test]
+    async fn test_webhook_rejected() {
+        let body = fixture("push");
+        let signature = sign(b"{}");
+        let (status, sent) = post("push", ("X-Gitea-Signature", &signature), body).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(sent.is_empty());
+
+        let body = fixture("push");
+        let (status, sent) = post("push", ("Authorization", "Bearer nope"), body).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(sent.is_empty());
+
+        let body = b"not json".to_vec();
+        let signature = sign(&body);
+        let (status, _) = post("push", ("X-Gitea-Signature", &signature), body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,297 @@
+use serde::Deserialize;
+
+use crate::utils::forge::{self, IssueAction, PullRequestAction, RepoEvent};
+
+#[derive(Debug, Deserialize)]
+pub struct Repository {
+    /// like `CoucouInc/rustygolem`
+    pub full_name: String,
+    pub html_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct User {
+    pub login: String,
+}
+
+/// https://docs.github.com/en/webhooks/webhook-events-and-payloads#push
+#[derive(Debug, Deserialize)]
+pub struct Push {
+    /// like `refs/heads/master` or `refs/tags/v0.2.0`
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    pub repository: Repository,
+    pub pusher: Pusher,
+    /// oldest first
+    pub commits: Vec<Commit>,
+    pub compare: String,
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Pusher {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Commit {
+    pub message: String,
+}
+
+/// https://docs.github.com/en/webhooks/webhook-events-and-payloads#pull_request
+#[derive(Debug, Deserialize)]
+pub struct PullRequestEvent {
+    pub action: String,
+    pub number: u64,
+    pub pull_request: PullRequest,
+    pub repository: Repository,
+    pub sender: User,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PullRequest {
+    pub title: String,
+    pub html_url: String,
+    #[serde(default)]
+    pub merged: bool,
+}
+
+/// https://docs.github.com/en/webhooks/webhook-events-and-payloads#issues
+#[derive(Debug, Deserialize)]
+pub struct IssuesEvent {
+    pub action: String,
+    pub issue: Issue,
+    pub repository: Repository,
+    pub sender: User,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Issue {
+    pub number: u64,
+    pub title: String,
+    pub html_url: String,
+}
+
+/// https://docs.github.com/en/webhooks/webhook-events-and-payloads#release
+#[derive(Debug, Deserialize)]
+pub struct ReleaseEvent {
+    pub action: String,
+    pub release: Release,
+    pub repository: Repository,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    /// empty or null when the release is only named after its tag
+    #[serde(default)]
+    pub name: Option<String>,
+    pub html_url: String,
+    #[serde(default)]
+    pub prerelease: bool,
+}
+
+impl Push {
+    /// None for the deleted branches and tags, and the branches pushed
+    /// without new commits
+    fn into_event(self) -> Option<RepoEvent> {
+        if self.deleted {
+            return None;
+        }
+        let repo = self.repository.full_name;
+        if let Some(tag) = forge::tag(&self.git_ref) {
+            return Some(RepoEvent::Tag {
+                url: format!("{}/releases/tag/{tag}", self.repository.html_url),
+                repo,
+                pusher: self.pusher.name,
+                tag: tag.to_string(),
+            });
+        }
+        let branch = forge::branch(&self.git_ref)?;
+        let first = self.commits.first()?;
+        Some(RepoEvent::Push {
+            repo,
+            pusher: self.pusher.name,
+            branch: branch.to_string(),
+            commits: self.commits.len(),
+            first_message: first.message.clone(),
+            url: self.compare,
+        })
+    }
+}
+
+impl PullRequestEvent {
+    fn into_event(self) -> Option<RepoEvent> {
+        let action = match self.action.as_str() {
+            "opened" => PullRequestAction::Opened,
+            "reopened" => PullRequestAction::Reopened,
+            "closed" if self.pull_request.merged => PullRequestAction::Merged,
+            "closed" => PullRequestAction::Closed,
+            _ => return None,
+        };
+        Some(RepoEvent::PullRequest {
+            repo: self.repository.full_name,
+            actor: self.sender.login,
+            action,
+            number: self.number,
+            title: self.pull_request.title,
+            url: self.pull_request.html_url,
+        })
+    }
+}
+
+impl IssuesEvent {
+    fn into_event(self) -> Option<RepoEvent> {
+        let action = match self.action.as_str() {
+            "opened" => IssueAction::Opened,
+            "reopened" => IssueAction::Reopened,
+            "closed" => IssueAction::Closed,
+            _ => return None,
+        };
+        Some(RepoEvent::Issue {
+            repo: self.repository.full_name,
+            actor: self.sender.login,
+            action,
+            number: self.issue.number,
+            title: self.issue.title,
+            url: self.issue.html_url,
+        })
+    }
+}
+
+impl ReleaseEvent {
+    fn into_event(self) -> Option<RepoEvent> {
+        if self.action != "published" {
+            return None;
+        }
+        let release = self.release;
+        let name = release
+            .name
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or(release.tag_name);
+        Some(RepoEvent::Release {
+            repo: self.repository.full_name,
+            name,
+            prerelease: release.prerelease,
+            url: release.html_url,
+        })
+    }
+}
+
+/// The payload of the event named in the `X-GitHub-Event` header, None for
+/// the events not announced, like the unknown ones or the labels of a PR
+pub fn parse(event: &str, body: &[u8]) -> serde_json::Result<Option<RepoEvent>> {
+    let event = match event {
+        "push" => serde_json::from_slice::<Push>(body)?.into_event(),
+        "pull_request" => serde_json::from_slice::<PullRequestEvent>(body)?.into_event(),
+        "issues" => serde_json::from_slice::<IssuesEvent>(body)?.into_event(),
+        "release" => serde_json::from_slice::<ReleaseEvent>(body)?.into_event(),
+        _ => None,
+    };
+    Ok(event)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn fixture(name: &str) -> Vec<u8> {
+        let path = format!("{}/fixtures/github/{name}.json", env!("CARGO_MANIFEST_DIR"));
+        std::fs::read(path).unwrap()
+    }
+
+    fn announcement(event: &str, fixture_name: &str) -> Option<String> {
+        parse(event, &fixture(fixture_name))
+            .unwrap()
+            .map(|event| event.announcement())
+    }
+
+    #[test]
+    async fn test_parse() {
+        let push = parse("push", &fixture("push")).unwrap().unwrap();
+        assert_eq!(
+            push,
+            RepoEvent::Push {
+                repo: "CoucouInc/rustygolem".to_string(),
+                pusher: "geekingfrog".to_string(),
+                branch: "master".to_string(),
+                commits: 2,
+                first_message: "Fix the casemapping of the channels\n\n\
+                                The servers announcing rfc1459 treat [] as {}."
+                    .to_string(),
+                url: "https://github.com/CoucouInc/rustygolem/compare/9049f1265b7d...0d1a26e67d8f"
+                    .to_string(),
+            }
+        );
+        let release = parse("release", &fixture("release_published"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(release.name(), "release");
+        assert!(parse("ping", &fixture("ping")).unwrap().is_none());
+        assert!(parse("star", b"{}").unwrap().is_none(), "not announced");
+        assert!(parse("push", &fixture("ping")).is_err());
+        assert!(parse("issues", b"<html>").is_err());
+    }
+
+    #[test]
+    async fn test_announcements() {
+        assert_eq!(
+            announcement("push", "push").as_deref(),
+            Some(
+                "CoucouInc/rustygolem: 2 commits to master by geekingfrog — Fix the casemapping \
+                 of the channels https://github.com/CoucouInc/rustygolem/compare/9049f1265b7d...0d1a26e67d8f"
+            )
+        );
+        assert_eq!(
+            announcement("push", "tag_push").as_deref(),
+            Some(
+                "CoucouInc/rustygolem: geekingfrog tagged v0.2.0 \
+                 https://github.com/CoucouInc/rustygolem/releases/tag/v0.2.0"
+            )
+        );
+        assert_eq!(
+            announcement("pull_request", "pull_request_opened").as_deref(),
+            Some(
+                "CoucouInc/rustygolem: Chouhartem opened PR #42: Add the dice plugin \
+                 https://github.com/CoucouInc/rustygolem/pull/42"
+            )
+        );
+        assert_eq!(
+            announcement("pull_request", "pull_request_merged").as_deref(),
+            Some(
+                "CoucouInc/rustygolem: geekingfrog merged PR #42: Add the dice plugin \
+                 https://github.com/CoucouInc/rustygolem/pull/42"
+            )
+        );
+        assert_eq!(
+            announcement("issues", "issues_opened").as_deref(),
+            Some(
+                "CoucouInc/rustygolem: Shampooing opened issue #57: λmeteo doesn't know \
+                 Saint-Étienne https://github.com/CoucouInc/rustygolem/issues/57"
+            )
+        );
+        assert_eq!(
+            announcement("release", "release_published").as_deref(),
+            Some(
+                "CoucouInc/rustygolem: new release Printemps \
+                 https://github.com/CoucouInc/rustygolem/releases/tag/v0.2.0"
+            )
+        );
+    }
+
+    #[test]
+    async fn test_not_announced() {
+        let push = |json: &str| parse("push", json.as_bytes()).unwrap();
+        let deleted = r#"{"ref": "refs/heads/dice", "repository": {"full_name": "a/b",
+            "html_url": "https://github.com/a/b"}, "pusher": {"name": "geekingfrog"},
+            "commits": [], "deleted": true, "compare": "https://github.com/a/b/compare/abc...000"}"#;
+        assert_eq!(push(deleted), None, "a deleted branch");
+
+        let labeled = r#"{"action": "labeled", "number": 42, "repository": {"full_name": "a/b",
+            "html_url": "https://github.com/a/b"}, "sender": {"login": "geekingfrog"},
+            "pull_request": {"title": "Dice", "html_url": "https://github.com/a/b/pull/42"}}"#;
+        assert_eq!(parse("pull_request", labeled.as_bytes()).unwrap(), None);
+    }
+}
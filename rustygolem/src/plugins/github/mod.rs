@@ -0,0 +1,5 @@
+pub(super) mod events;
+mod plugin;
+mod webhook;
+
+pub use plugin::Github;
@@ -0,0 +1,200 @@
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing, Router,
+};
+use plugin_core::Outbound;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use super::events;
+use crate::utils::forge::{self, Route};
+
+/// Checks the `X-Hub-Signature-256` header, like `sha256=<hex hmac of the body>`
+fn verify(secret: &str, signature: Option<&str>, body: &[u8]) -> bool {
+    let signature = signature.and_then(|sig| sig.strip_prefix("sha256="));
+    forge::verify_signature(secret, signature, body)
+}
+
+#[derive(Clone)]
+struct WebhookState {
+    secret: Arc<String>,
+    routes: Arc<Vec<Route>>,
+    /// to the plugin, which sends them to IRC
+    tx: mpsc::Sender<Outbound>,
+}
+
+async fn webhook(State(state): State<WebhookState>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    if !verify(
+        &state.secret,
+        forge::header(&headers, "X-Hub-Signature-256"),
+        &body,
+    ) {
+        log::warn!("Rejecting a github delivery with an invalid signature");
+        return StatusCode::UNAUTHORIZED;
+    }
+    let kind = match forge::header(&headers, "X-GitHub-Event") {
+        Some(kind) => kind,
+        None => return StatusCode::BAD_REQUEST,
+    };
+    if kind == "ping" {
+        log::info!("Github says hello");
+        return StatusCode::OK;
+    }
+    let event = match events::parse(kind, &body) {
+        Ok(Some(event)) => event,
+        Ok(None) => {
+            log::debug!("Ignoring the github event {kind}");
+            return StatusCode::OK;
+        }
+        Err(err) => {
+            log::error!("Cannot parse the github event {kind}: {err}");
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+    forge::announce(&state.routes, &event, &state.tx).await
+}
+
+/// With `POST /webhook`, the announcements are sent to `tx`
+pub fn router(secret: &str, routes: Vec<Route>, tx: mpsc::Sender<Outbound>) -> Router<()> {
+    let state = WebhookState {
+        secret: Arc::new(secret.to_string()),
+        routes: Arc::new(routes),
+        tx,
+    };
+    Router::new()
+        .route("/webhook", routing::post(webhook))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use hmac::{Hmac, Mac, NewMac};
+    use pretty_assertions::assert_eq;
+    use tower::ServiceExt;
+
+    const SECRET: &str = "s3cr3t";
+
+    fn fixture(name: &str) -> Vec<u8> {
+        let path = format!("{}/fixtures/github/{name}.json", env!("CARGO_MANIFEST_DIR"));
+        std::fs::read(path).unwrap()
+    }
+
+    fn sign(body: &[u8]) -> String {
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(body);
+        let sig = mac.finalize().into_bytes();
+        let hex = sig.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        format!("sha256={hex}")
+    }
+
+    fn route(repo: &str, channel: &str, events: &[&str]) -> Route {
+        Route {
+            repo: repo.to_string(),
+            channel: channel.to_string(),
+            events: events.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    fn routes() -> Vec<Route> {
+        vec![
+            route("CoucouInc/rustygolem", "#coucou", &[]),
+            route(
+                "coucouinc/RUSTYGOLEM",
+                "#coucou-dev",
+                &["push", "pull_request"],
+            ),
+            route("CoucouInc/other", "#other", &[]),
+        ]
+    }
+
+    async fn post(
+        event: Option<&str>,
+        signature: Option<&str>,
+        body: Vec<u8>,
+    ) -> (StatusCode, Vec<Outbound>) {
+        let (tx, mut rx) = mpsc::channel(10);
+        let app = router(SECRET, routes(), tx);
+        let mut req = Request::builder().method("POST").uri("/webhook");
+        if let Some(event) = event {
+            req = req.header("X-GitHub-Event", event);
+        }
+        if let Some(signature) = signature {
+            req = req.header("X-Hub-Signature-256", signature);
+        }
+        let req = req.body(Body::from(body)).unwrap();
+        let status = app.oneshot(req).await.unwrap().status();
+        let mut sent = vec![];
+        while let Ok(msg) = rx.try_recv() {
+            sent.push(msg);
+        }
+        (status, sent)
+    }
+
+    #[test]
+    async fn test_verify() {
+        let body = fixture("push");
+        assert!(verify(SECRET, Some(&sign(&body)), &body));
+        assert!(!verify("other", Some(&sign(&body)), &body));
+        assert!(!verify(SECRET, Some(&sign(b"{}")), &body));
+        assert!(!verify(SECRET, None, &body));
+        let bare = sign(&body).replace("sha256=", "");
+        assert!(!verify(SECRET, Some(&bare), &body), "without the prefix");
+        assert!(!verify(SECRET, Some("sha256=zz"), &body));
+    }
+
+    #[test]
+    async fn test_webhook() {
+        let body = fixture("pull_request_merged");
+        let (status, sent) = post(Some("pull_request"), Some(&sign(&body)), body).await;
+        assert_eq!(status, StatusCode::OK);
+        let line = "CoucouInc/rustygolem: geekingfrog merged PR #42: Add the dice plugin \
+                    https://github.com/CoucouInc/rustygolem/pull/42";
+        assert_eq!(
+            sent,
+            vec![
+                Outbound::reply("#coucou", line),
+                Outbound::reply("#coucou-dev", line)
+            ]
+        );
+
+        let body = fixture("release_published");
+        let (status, sent) = post(Some("release"), Some(&sign(&body)), body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(sent.len(), 1, "only to #coucou");
+    }
+
+    #[test]
+    async fn test_webhook_rejected() {
+        let body = fixture("push");
+        let (status, sent) = post(Some("push"), None, body.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(sent.is_empty());
+        let (status, sent) = post(Some("push"), Some(&sign(b"{}")), body.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(sent.is_empty());
+
+        let (status, _) = post(None, Some(&sign(&body)), body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let body = b"not json".to_vec();
+        let (status, _) = post(Some("push"), Some(&sign(&body)), body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    async fn test_webhook_ignored() {
+        let body = fixture("ping");
+        let (status, sent) = post(Some("ping"), Some(&sign(&body)), body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(sent.is_empty());
+
+        let body = br#"{"action": "created"}"#.to_vec();
+        let (status, sent) = post(Some("star"), Some(&sign(&body)), body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(sent.is_empty());
+    }
+}
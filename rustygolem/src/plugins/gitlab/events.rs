@@ -0,0 +1,316 @@
+use serde::Deserialize;
+
+use crate::utils::forge::{self, IssueAction, PullRequestAction, RepoEvent};
+
+#[derive(Debug, Deserialize)]
+pub struct Project {
+    /// like `CoucouInc/rustygolem`
+    pub path_with_namespace: String,
+    pub web_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct User {
+    pub username: String,
+}
+
+/// Which event the payload is about, all of them have it
+#[derive(Debug, Deserialize)]
+struct ObjectKind {
+    object_kind: String,
+}
+
+/// https://docs.gitlab.com/ee/user/project/integrations/webhook_events.html#push-events,
+/// also for the tag events
+#[derive(Debug, Deserialize)]
+pub struct Push {
+    /// like `refs/heads/master` or `refs/tags/v0.2.0`
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    /// the null sha for a new branch
+    pub before: String,
+    /// the null sha for a deleted branch or tag
+    pub after: String,
+    pub user_username: String,
+    pub project: Project,
+    /// at most 20 of them
+    pub commits: Vec<Commit>,
+    #[serde(default)]
+    pub total_commits_count: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Commit {
+    pub id: String,
+    pub message: String,
+}
+
+/// https://docs.gitlab.com/ee/user/project/integrations/webhook_events.html#merge-request-events
+#[derive(Debug, Deserialize)]
+pub struct MergeRequestEvent {
+    pub user: User,
+    pub project: Project,
+    pub object_attributes: MergeRequest,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeRequest {
+    pub iid: u64,
+    pub title: String,
+    pub url: String,
+    /// missing for the events not triggered by a user
+    #[serde(default)]
+    pub action: Option<String>,
+}
+
+/// https://docs.gitlab.com/ee/user/project/integrations/webhook_events.html#issue-events
+#[derive(Debug, Deserialize)]
+pub struct IssueEvent {
+    pub user: User,
+    pub project: Project,
+    pub object_attributes: Issue,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Issue {
+    pub iid: u64,
+    pub title: String,
+    pub url: String,
+    #[serde(default)]
+    pub action: Option<String>,
+    #[serde(default)]
+    pub confidential: bool,
+}
+
+/// https://docs.gitlab.com/ee/user/project/integrations/webhook_events.html#release-events
+#[derive(Debug, Deserialize)]
+pub struct ReleaseEvent {
+    pub action: String,
+    pub name: String,
+    pub tag: String,
+    pub url: String,
+    pub project: Project,
+}
+
+impl Push {
+    /// None for the deleted branches and tags, and the branches pushed
+    /// without new commits
+    fn into_event(self) -> Option<RepoEvent> {
+        if forge::is_null_sha(&self.after) {
+            return None;
+        }
+        let repo = self.project.path_with_namespace;
+        let web_url = self.project.web_url;
+        if let Some(tag) = forge::tag(&self.git_ref) {
+            return Some(RepoEvent::Tag {
+                url: format!("{web_url}/-/tags/{tag}"),
+                repo,
+                pusher: self.user_username,
+                tag: tag.to_string(),
+            });
+        }
+        let branch = forge::branch(&self.git_ref)?;
+        let first = forge::oldest(&self.commits, &self.after, |c| c.id.as_str())?;
+        // there's nothing to compare a new branch with
+        let url = if forge::is_null_sha(&self.before) {
+            format!("{web_url}/-/commits/{branch}")
+        } else {
+            format!("{web_url}/-/compare/{}...{}", self.before, self.after)
+        };
+        Some(RepoEvent::Push {
+            repo,
+            pusher: self.user_username,
+            branch: branch.to_string(),
+            commits: self.total_commits_count.max(self.commits.len()),
+            first_message: first.message.clone(),
+            url,
+        })
+    }
+}
+
+impl MergeRequestEvent {
+    fn into_event(self) -> Option<RepoEvent> {
+        let mr = self.object_attributes;
+        let action = match mr.action.as_deref()? {
+            "open" => PullRequestAction::Opened,
+            "reopen" => PullRequestAction::Reopened,
+            "merge" => PullRequestAction::Merged,
+            "close" => PullRequestAction::Closed,
+            _ => return None,
+        };
+        Some(RepoEvent::PullRequest {
+            repo: self.project.path_with_namespace,
+            actor: self.user.username,
+            action,
+            number: mr.iid,
+            title: mr.title,
+            url: mr.url,
+        })
+    }
+}
+
+impl IssueEvent {
+    /// None for the confidential issues too, not to be told on IRC
+    fn into_event(self) -> Option<RepoEvent> {
+        let issue = self.object_attributes;
+        if issue.confidential {
+            return None;
+        }
+        let action = match issue.action.as_deref()? {
+            "open" => IssueAction::Opened,
+            "reopen" => IssueAction::Reopened,
+            "close" => IssueAction::Closed,
+            _ => return None,
+        };
+        Some(RepoEvent::Issue {
+            repo: self.project.path_with_namespace,
+            actor: self.user.username,
+            action,
+            number: issue.iid,
+            title: issue.title,
+            url: issue.url,
+        })
+    }
+}
+
+impl ReleaseEvent {
+    fn into_event(self) -> Option<RepoEvent> {
+        if self.action != "create" {
+            return None;
+        }
+        let name = if self.name.trim().is_empty() {
+            self.tag
+        } else {
+            self.name
+        };
+        Some(RepoEvent::Release {
+            repo: self.project.path_with_namespace,
+            name,
+            // gitlab only has upcoming releases
+            prerelease: false,
+            url: self.url,
+        })
+    }
+}
+
+/// The payload of an event, after its `object_kind`. None for the events not
+/// announced, like the comments or the pipelines.
+pub fn parse(body: &[u8]) -> serde_json::Result<Option<RepoEvent>> {
+    let kind: ObjectKind = serde_json::from_slice(body)?;
+    let event = match kind.object_kind.as_str() {
+        "push" | "tag_push" => serde_json::from_slice::<Push>(body)?.into_event(),
+        "merge_request" => serde_json::from_slice::<MergeRequestEvent>(body)?.into_event(),
+        "issue" => serde_json::from_slice::<IssueEvent>(body)?.into_event(),
+        "release" => serde_json::from_slice::<ReleaseEvent>(body)?.into_event(),
+        _ => None,
+    };
+    Ok(event)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::plugins::{gitea, github};
+    use pretty_assertions::assert_eq;
+
+    fn fixture(forge: &str, name: &str) -> Vec<u8> {
+        let path = format!(
+            "{}/fixtures/{forge}/{name}.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        std::fs::read(path).unwrap()
+    }
+
+    #[test]
+    async fn test_parse() {
+        assert_eq!(
+            parse(&fixture("gitlab", "push")).unwrap(),
+            Some(RepoEvent::Push {
+                repo: "CoucouInc/rustygolem".to_string(),
+                pusher: "geekingfrog".to_string(),
+                branch: "master".to_string(),
+                commits: 2,
+                first_message: "Fix the casemapping of the channels\n\n\
+                                The servers announcing rfc1459 treat [] as {}.\n"
+                    .to_string(),
+                url: "https://gitlab.com/CoucouInc/rustygolem/-/compare/\
+                      9049f1265b7d61be4a8904a9a27120d2064dab3b...0d1a26e67d8f5eaf1f6ba5c57fc3c7d91ac0fd1c"
+                    .to_string(),
+            })
+        );
+        assert_eq!(
+            parse(&fixture("gitlab", "merge_request_opened")).unwrap(),
+            Some(RepoEvent::PullRequest {
+                repo: "CoucouInc/rustygolem".to_string(),
+                actor: "Chouhartem".to_string(),
+                action: PullRequestAction::Opened,
+                number: 42,
+                title: "Add the dice plugin".to_string(),
+                url: "https://gitlab.com/CoucouInc/rustygolem/-/merge_requests/42".to_string(),
+            })
+        );
+        assert_eq!(
+            parse(&fixture("gitlab", "release_create")).unwrap(),
+            Some(RepoEvent::Release {
+                repo: "CoucouInc/rustygolem".to_string(),
+                name: "Printemps".to_string(),
+                prerelease: false,
+                url: "https://gitlab.com/CoucouInc/rustygolem/-/releases/v0.2.0".to_string(),
+            })
+        );
+        assert_eq!(parse(br#"{"object_kind": "note"}"#).unwrap(), None);
+        assert!(parse(br#"{"object_kind": "issue"}"#).is_err());
+        assert!(parse(b"[]").is_err());
+    }
+
+    #[test]
+    async fn test_not_announced() {
+        let issue = String::from_utf8(fixture("gitlab", "issue_opened")).unwrap();
+        let confidential = issue.replace(r#""confidential": false"#, r#""confidential": true"#);
+        assert_eq!(parse(confidential.as_bytes()).unwrap(), None);
+        let updated = issue.replace(r#""action": "open""#, r#""action": "update""#);
+        assert_eq!(parse(updated.as_bytes()).unwrap(), None);
+
+        let push = String::from_utf8(fixture("gitlab", "push")).unwrap();
+        let deleted = push.replace(
+            r#""after": "0d1a26e67d8f5eaf1f6ba5c57fc3c7d91ac0fd1c""#,
+            r#""after": "0000000000000000000000000000000000000000""#,
+        );
+        assert_eq!(parse(deleted.as_bytes()).unwrap(), None);
+    }
+
+    /// The same events from the three forges, announced the same way but for
+    /// the links
+    #[test]
+    async fn test_same_announcements() {
+        let equivalents = [
+            ("push", "push", "push"),
+            ("push", "tag_push", "tag_push"),
+            (
+                "pull_request",
+                "pull_request_opened",
+                "merge_request_opened",
+            ),
+            (
+                "pull_request",
+                "pull_request_merged",
+                "merge_request_merged",
+            ),
+            ("issues", "issues_opened", "issue_opened"),
+            ("release", "release_published", "release_create"),
+        ];
+        let without_link = |event: RepoEvent| event.announcement().replace(event.url(), "<link>");
+        for (kind, name, gitlab_name) in equivalents {
+            let from_github = github::events::parse(kind, &fixture("github", name))
+                .unwrap()
+                .unwrap();
+            let from_gitea = gitea::events::parse(kind, &fixture("gitea", name))
+                .unwrap()
+                .unwrap();
+            let from_gitlab = parse(&fixture("gitlab", gitlab_name)).unwrap().unwrap();
+            let expected = without_link(from_gitlab);
+            assert_eq!(without_link(from_github), expected, "{name}");
+            assert_eq!(without_link(from_gitea), expected, "{name}");
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod events;
+mod plugin;
+mod webhook;
+
+pub use plugin::Gitlab;
@@ -0,0 +1,44 @@
+use async_trait::async_trait;
+use plugin_core::{Initialised, Outbound, Plugin, Requirement, Result};
+use tokio::sync::mpsc;
+
+use super::webhook;
+use crate::utils::forge::{Relay, Settings};
+
+pub struct Gitlab {
+    // announcements coming from the webhook, and that need to be sent to IRC
+    relay: Relay,
+}
+
+#[async_trait]
+impl Plugin for Gitlab {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        Settings::load(config, "gitlab")?;
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let settings = Settings::load(config, "gitlab")?;
+        let (relay, tx) = Relay::new();
+        let router = webhook::router(&settings.secret, settings.routes, tx);
+        Ok(Initialised {
+            plugin: Box::new(Gitlab { relay }),
+            router: None,
+            // the deliveries come with the secret token of the webhook instead
+            public_router: Some(router),
+            tasks: vec![],
+        })
+    }
+
+    fn get_name(&self) -> &'static str {
+        "gitlab"
+    }
+
+    async fn run(&self, bot_chan: mpsc::Sender<Outbound>) -> Result<()> {
+        self.relay.run(&bot_chan).await
+    }
+
+    fn requirements(&self) -> Vec<Requirement> {
+        vec![Requirement::WebRouter]
+    }
+}
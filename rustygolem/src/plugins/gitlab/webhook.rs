@@ -0,0 +1,144 @@
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing, Router,
+};
+use plugin_core::Outbound;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use super::events;
+use crate::utils::forge::{self, Route};
+use crate::web;
+
+/// GitLab doesn't sign the deliveries, it sends the secret token of the
+/// webhook in `X-Gitlab-Token`
+fn is_authentic(secret: &str, headers: &HeaderMap) -> bool {
+    forge::header(headers, "X-Gitlab-Token")
+        .map(|token| web::constant_time_eq(token.as_bytes(), secret.as_bytes()))
+        .unwrap_or(false)
+}
+
+#[derive(Clone)]
+struct WebhookState {
+    secret: Arc<String>,
+    routes: Arc<Vec<Route>>,
+    /// to the plugin, which sends them to IRC
+    tx: mpsc::Sender<Outbound>,
+}
+
+async fn webhook(State(state): State<WebhookState>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    if !is_authentic(&state.secret, &headers) {
+        log::warn!("Rejecting a gitlab delivery without a valid token");
+        return StatusCode::UNAUTHORIZED;
+    }
+    // like "Push Hook", only for the logs since the payload tells it too
+    let kind = forge::header(&headers, "X-Gitlab-Event").unwrap_or("unnamed");
+    let event = match events::parse(&body) {
+        Ok(Some(event)) => event,
+        Ok(None) => {
+            log::debug!("Ignoring the gitlab event {kind}");
+            return StatusCode::OK;
+        }
+        Err(err) => {
+            log::error!("Cannot parse the gitlab event {kind}: {err}");
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+    forge::announce(&state.routes, &event, &state.tx).await
+}
+
+/// With `POST /webhook`, the announcements are sent to `tx`
+pub fn router(secret: &str, routes: Vec<Route>, tx: mpsc::Sender<Outbound>) -> Router<()> {
+    let state = WebhookState {
+        secret: Arc::new(secret.to_string()),
+        routes: Arc::new(routes),
+        tx,
+    };
+    Router::new()
+        .route("/webhook", routing::post(webhook))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use pretty_assertions::assert_eq;
+    use tower::ServiceExt;
+
+    const SECRET: &str = "s3cr3t";
+
+    fn fixture(name: &str) -> Vec<u8> {
+        let path = format!("{}/fixtures/gitlab/{name}.json", env!("CARGO_MANIFEST_DIR"));
+        std::fs::read(path).unwrap()
+    }
+
+    async fn post(token: Option<&str>, body: Vec<u8>) -> (StatusCode, Vec<Outbound>) {
+        let (tx, mut rx) = mpsc::channel(10);
+        let routes = vec![
+            Route {
+                repo: "CoucouInc/rustygolem".to_string(),
+                channel: "#coucou".to_string(),
+                events: vec!["pull_request".to_string()],
+            },
+            Route {
+                repo: "CoucouInc/rustygolem".to_string(),
+                channel: "#coucou-dev".to_string(),
+                events: vec![],
+            },
+        ];
+        let app = router(SECRET, routes, tx);
+        let mut req = Request::builder()
+            .method("POST")
+            .uri("/webhook")
+            .header("X-Gitlab-Event", "Merge Request Hook");
+        if let Some(token) = token {
+            req = req.header("X-Gitlab-Token", token);
+        }
+        let req = req.body(Body::from(body)).unwrap();
+        let status = app.oneshot(req).await.unwrap().status();
+        let mut sent = vec![];
+        while let Ok(msg) = rx.try_recv() {
+            sent.push(msg);
+        }
+        (status, sent)
+    }
+
+    #[test]
+    async fn test_webhook() {
+        let (status, sent) = post(Some(SECRET), fixture("merge_request_merged")).await;
+        assert_eq!(status, StatusCode::OK);
+        let line = "CoucouInc/rustygolem: geekingfrog merged PR #42: Add the dice plugin \
+                    https://gitlab.com/CoucouInc/rustygolem/-/merge_requests/42";
+        assert_eq!(
+            sent,
+            vec![
+                Outbound::reply("#coucou", line),
+                Outbound::reply("#coucou-dev", line)
+            ]
+        );
+
+        let (status, sent) = post(Some(SECRET), fixture("tag_push")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(sent.len(), 1, "only to #coucou-dev");
+
+        let (status, sent) = post(Some(SECRET), br#"{"object_kind": "pipeline"}"#.to_vec()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(sent.is_empty());
+    }
+
+    #[test]
+    async fn test_webhook_rejected() {
+        let (status, sent) = post(None, fixture("push")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(sent.is_empty());
+        let (status, sent) = post(Some("s3cr3"), fixture("push")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(sent.is_empty());
+        let (status, _) = post(Some(SECRET), b"not json".to_vec()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,35 @@
+use plugin_core::{Database, Result};
+
+/// The tables of the joke plugin in the shared database, see
+/// `plugin_core::ensure_schema`
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE joke_recent (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        channel TEXT NOT NULL,
+        joke_id TEXT NOT NULL
+    );
+    CREATE INDEX joke_recent_channel ON joke_recent (channel, id);",
+    // status is pending, approved or rejected
+    "CREATE TABLE joke_submitted (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        author TEXT NOT NULL,
+        channel TEXT NOT NULL,
+        text TEXT NOT NULL,
+        normalized TEXT NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending',
+        global INTEGER NOT NULL DEFAULT 0,
+        submitted_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE UNIQUE INDEX joke_submitted_normalized ON joke_submitted (normalized);",
+    // only when the stats are persisted
+    "CREATE TABLE joke_tells (
+        channel TEXT NOT NULL,
+        source TEXT NOT NULL,
+        count INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY (channel, source)
+    );",
+];
+
+pub fn ensure_schema(db: &Database) -> Result<()> {
+    plugin_core::ensure_schema(db, "joke", MIGRATIONS)
+}
@@ -0,0 +1,10 @@
+mod db;
+mod plugin;
+mod punchline;
+pub(crate) mod recent;
+mod sources;
+mod stats;
+mod submitted;
+mod throttle;
+
+pub use plugin::Joke;
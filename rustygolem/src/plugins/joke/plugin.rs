@@ -0,0 +1,637 @@
+use async_trait::async_trait;
+use irc::proto::{ChannelExt, Command, Message};
+use plugin_core::utils::account::is_admin;
+use plugin_core::{
+    CaseMapping, CommandHelp, Database, Initialised, Lang, MsgCtx, Outbound, Plugin, Result,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use super::punchline::{self, Punchlines};
+use super::recent::{self, Recent};
+use super::sources::{self, SourceSettings, Weighted};
+use super::stats::{self, Pool, Tells};
+use super::submitted::{self, JokeCommand, Moderated, Submission, Submitted};
+use super::throttle::{self, Allowed, OnCooldown, Throttle};
+use crate::utils::text::unhighlight;
+
+/// The `joke` section of the golem config
+#[derive(Deserialize)]
+struct Settings {
+    /// picked by weight for every joke
+    #[serde(default = "SourceSettings::default_sources")]
+    sources: Vec<SourceSettings>,
+    /// jokes not told again in a channel until that many others were
+    #[serde(default = "default_no_repeat_window")]
+    no_repeat_window: usize,
+    /// between two jokes in a channel
+    #[serde(default = "default_cooldown_secs")]
+    cooldown_secs: u64,
+    /// between two jokes in a private conversation
+    #[serde(default = "default_private_cooldown_secs")]
+    private_cooldown_secs: u64,
+    #[serde(default)]
+    on_cooldown: OnCooldown,
+    /// where λjoke is ignored
+    #[serde(default)]
+    disabled_channels: Vec<String>,
+    /// the language of the jokes there, any language elsewhere
+    #[serde(default)]
+    channel_languages: Vec<ChannelLanguage>,
+    /// between the setup of a two-part joke and its punchline
+    #[serde(default = "default_punchline_min_delay_secs")]
+    punchline_min_delay_secs: u64,
+    #[serde(default = "default_punchline_max_delay_secs")]
+    punchline_max_delay_secs: u64,
+    /// `— soumis par {nick}` after the jokes of the users, the nick broken
+    /// so that they aren't highlighted
+    #[serde(default = "default_attribution")]
+    attribution: bool,
+    /// keep counting the jokes told across restarts for λjoke stats
+    #[serde(default)]
+    persist_stats: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ChannelLanguage {
+    channel: String,
+    language: Lang,
+}
+
+fn default_cooldown_secs() -> u64 {
+    throttle::DEFAULT_COOLDOWN.as_secs()
+}
+
+fn default_private_cooldown_secs() -> u64 {
+    throttle::DEFAULT_PRIVATE_COOLDOWN.as_secs()
+}
+
+fn default_no_repeat_window() -> usize {
+    recent::DEFAULT_WINDOW
+}
+
+fn default_attribution() -> bool {
+    true
+}
+
+fn default_punchline_min_delay_secs() -> u64 {
+    punchline::DEFAULT_MIN_DELAY.as_secs()
+}
+
+fn default_punchline_max_delay_secs() -> u64 {
+    punchline::DEFAULT_MAX_DELAY.as_secs()
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            sources: SourceSettings::default_sources(),
+            no_repeat_window: default_no_repeat_window(),
+            cooldown_secs: default_cooldown_secs(),
+            private_cooldown_secs: default_private_cooldown_secs(),
+            on_cooldown: OnCooldown::default(),
+            disabled_channels: vec![],
+            channel_languages: vec![],
+            punchline_min_delay_secs: default_punchline_min_delay_secs(),
+            punchline_max_delay_secs: default_punchline_max_delay_secs(),
+            attribution: default_attribution(),
+            persist_stats: false,
+        }
+    }
+}
+
+impl Settings {
+    fn load(config: &plugin_core::Config) -> Result<Self> {
+        Ok(config.plugin_section("joke")?.unwrap_or_default())
+    }
+}
+
+pub struct Joke {
+    throttle: Throttle,
+    sources: Vec<Weighted>,
+    recent: Recent,
+    submitted: Arc<Submitted>,
+    /// false without a database, the submissions wouldn't survive a restart
+    accepts_submissions: bool,
+    /// allowed to approve and reject the submitted jokes
+    admins: Vec<String>,
+    channel_languages: Vec<ChannelLanguage>,
+    punchlines: Punchlines,
+    tells: Tells,
+    attribution: bool,
+}
+
+#[async_trait]
+impl Plugin for Joke {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        let settings = Settings::load(config)?;
+        // only to build the sources, nothing is stored in it
+        let submitted = Arc::new(Submitted::new(Database::in_memory()?)?);
+        sources::build(&config.http_client(), &submitted, &settings.sources)?;
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let settings = Settings::load(config)?;
+        let client = config.http_client();
+        let db = match config.database() {
+            Some(db) => db.clone(),
+            None => {
+                log::warn!(
+                    "No database, the jokes told recently won't survive a restart and λjoke add is disabled"
+                );
+                Database::in_memory()?
+            }
+        };
+        let submitted = Arc::new(Submitted::new(db.clone())?);
+        let tells = if settings.persist_stats {
+            Tells::load(db.clone())?
+        } else {
+            Tells::in_memory()
+        };
+        Ok(Initialised::from(Joke {
+            throttle: Throttle::new(
+                Duration::from_secs(settings.cooldown_secs),
+                Duration::from_secs(settings.private_cooldown_secs),
+                settings.on_cooldown,
+                settings.disabled_channels,
+            ),
+            sources: sources::build(&client, &submitted, &settings.sources)?,
+            recent: Recent::load(db, recent::JOKES, settings.no_repeat_window)?,
+            submitted,
+            accepts_submissions: config.database().is_some(),
+            admins: config.admins()?,
+            channel_languages: settings.channel_languages,
+            punchlines: Punchlines::new(
+                Duration::from_secs(settings.punchline_min_delay_secs),
+                Duration::from_secs(settings.punchline_max_delay_secs),
+            ),
+            tells,
+            attribution: settings.attribution,
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "joke"
+    }
+
+    async fn in_message_ctx(&self, ctx: &MsgCtx, msg: &Message) -> Result<Option<Outbound>> {
+        in_msg(self, msg, ctx.casemapping).await
+    }
+
+    async fn run(&self, bot_chan: mpsc::Sender<Outbound>) -> Result<()> {
+        Ok(self.punchlines.run(&bot_chan).await?)
+    }
+
+    async fn on_self_part(&self, channel: &str) -> Result<()> {
+        self.punchlines.forget(channel);
+        Ok(())
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("joke")
+                .usage("joke [fr|en] [category] [> nick]")
+                .description(
+                    "Tell a joke, in the language of the channel unless given, of that category when given",
+                ),
+            CommandHelp::new("joke add")
+                .usage("joke add <joke>")
+                .description("Submit a joke, told in the channel once approved by an admin"),
+            CommandHelp::new("joke stats")
+                .usage("joke stats")
+                .description("How many jokes each source has, and how many were told here"),
+        ]
+    }
+}
+
+impl Joke {
+    /// The one asked for, or else the one of the channel
+    fn language(&self, channel: &str, asked: Option<Lang>) -> Option<Lang> {
+        asked.or_else(|| {
+            self.channel_languages
+                .iter()
+                .find(|c| c.channel.eq_ignore_ascii_case(channel))
+                .map(|c| c.language)
+        })
+    }
+
+    async fn stats(&self, casemapping: CaseMapping, channel: &str) -> anyhow::Result<String> {
+        let normalized = casemapping.normalize(channel);
+        let mut pools = vec![];
+        for weighted in &self.sources {
+            if weighted.settings.enabled_in(channel) {
+                pools.push(Pool {
+                    source: weighted.source.name(),
+                    language: weighted.source.language(),
+                    size: weighted.source.size(&normalized).await?,
+                });
+            }
+        }
+        Ok(stats::format(
+            &pools,
+            &self.tells.told(casemapping, channel),
+        ))
+    }
+
+    fn add(&self, author: &str, channel: &str, text: &str) -> Result<String> {
+        if !channel.is_channel_name() {
+            return Ok("Jokes can only be added in a channel".to_string());
+        }
+        let message = match self.submitted.add(author, channel, text)? {
+            Submission::Pending(id) => {
+                format!("Thanks, joke #{id} is waiting for an admin's approval")
+            }
+            Submission::Duplicate(id) => format!("Already submitted as joke #{id}"),
+            Submission::TooLong => {
+                format!("Too long, {} characters at most", submitted::MAX_LENGTH)
+            }
+            Submission::Empty => submitted::USAGE.to_string(),
+        };
+        Ok(message)
+    }
+
+    fn moderate(&self, command: JokeCommand) -> Result<String> {
+        let (id, moderated) = match command {
+            JokeCommand::Approve { id, global } => (id, self.submitted.approve(id, global)?),
+            JokeCommand::Reject(id) => (id, self.submitted.reject(id)?),
+            _ => unreachable!("not a moderation command"),
+        };
+        let message = match (moderated, command) {
+            (Moderated::Approved, JokeCommand::Approve { global: true, .. }) => {
+                format!("Joke #{id} approved for every channel")
+            }
+            (Moderated::Approved, _) => format!("Joke #{id} approved"),
+            (Moderated::Rejected, _) => format!("Joke #{id} rejected"),
+            (Moderated::NoSuchJoke, _) => format!("No joke #{id}"),
+        };
+        Ok(message)
+    }
+}
+
+async fn in_msg(
+    plugin: &Joke,
+    msg: &Message,
+    casemapping: CaseMapping,
+) -> Result<Option<Outbound>> {
+    let response_target = match msg.response_target() {
+        None => return Ok(None),
+        Some(target) => target,
+    };
+
+    if let Command::PRIVMSG(_source, privmsg) = &msg.command {
+        if let Some((command, mb_target)) = submitted::parse_command(privmsg) {
+            if plugin.throttle.is_disabled(response_target) {
+                log::debug!("Jokes are disabled in {response_target}");
+                return Ok(None);
+            }
+            let source = msg.source_nickname().unwrap_or_default();
+            let msg = match command {
+                Ok(command)
+                    if command.is_moderation() && !is_admin(&plugin.admins, msg, casemapping) =>
+                {
+                    log::warn!("{source} isn't an admin, ignoring {privmsg:?}");
+                    return Ok(None);
+                }
+                Ok(JokeCommand::Add(_) | JokeCommand::Approve { .. } | JokeCommand::Reject(_))
+                    if !plugin.accepts_submissions =>
+                {
+                    "No database to keep the submitted jokes, set database_path in the golem config"
+                        .to_string()
+                }
+                Ok(JokeCommand::Tell { lang, category }) => {
+                    match plugin.throttle.check(response_target) {
+                        Allowed::Yes => (),
+                        Allowed::Wait(OnCooldown::Notify) => {
+                            return Ok(Some(Outbound::reply(
+                                response_target,
+                                "Enough jokes for now, try again later",
+                            )))
+                        }
+                        Allowed::Wait(OnCooldown::Silent) | Allowed::Disabled => {
+                            log::debug!("Joke on cooldown for {response_target}");
+                            return Ok(None);
+                        }
+                    }
+                    let lang = plugin.language(response_target, lang);
+                    match handle_command(plugin, casemapping, response_target, lang, category).await
+                    {
+                        Ok((text, punchline)) => {
+                            if let Some(punchline) = punchline {
+                                plugin.punchlines.schedule(
+                                    casemapping,
+                                    response_target,
+                                    punchline,
+                                    rand::random(),
+                                );
+                            }
+                            text
+                        }
+                        Err(err) => {
+                            log::error!("Error handling joke: {err:?}");
+                            "Error handling joke".to_string()
+                        }
+                    }
+                }
+                Ok(JokeCommand::Add(text)) => {
+                    plugin.add(source, &casemapping.normalize(response_target), text)?
+                }
+                Ok(JokeCommand::Stats) => plugin.stats(casemapping, response_target).await?,
+                Ok(command) => plugin.moderate(command)?,
+                Err(usage) => usage,
+            };
+
+            return Ok(Some(Outbound::reply(
+                response_target,
+                crate::utils::messages::with_target(&msg, &mb_target),
+            )));
+        }
+    }
+    Ok(None)
+}
+
+/// What to reply, and the punchline to tell a bit later for a two-part joke
+async fn handle_command(
+    plugin: &Joke,
+    casemapping: CaseMapping,
+    response_target: &str,
+    lang: Option<Lang>,
+    category: Option<&str>,
+) -> anyhow::Result<(String, Option<String>)> {
+    let channel = casemapping.normalize(response_target);
+    let mut enabled = vec![];
+    for weighted in &plugin.sources {
+        if weighted.settings.enabled_in(response_target)
+            && weighted.source.has_jokes(&channel).await?
+        {
+            enabled.push(weighted);
+        }
+    }
+    let enabled = sources::in_language(enabled, lang);
+    let candidates = match sources::with_category(enabled, category).await? {
+        Ok(candidates) => candidates,
+        Err(known) if known.is_empty() => {
+            return Ok(("No joke category available here".to_string(), None))
+        }
+        Err(known) => {
+            return Ok((
+                format!(
+                    "Unknown category {}, available ones: {}",
+                    category.unwrap_or_default(),
+                    known.join(", ")
+                ),
+                None,
+            ))
+        }
+    };
+    let weights = candidates
+        .iter()
+        .map(|w| w.settings.weight)
+        .collect::<Vec<_>>();
+    let total = weights.iter().map(|w| u64::from(*w)).sum::<u64>();
+    if total == 0 {
+        return Ok(("No joke source available here".to_string(), None));
+    }
+    let roll = rand::random::<u64>() % total;
+    let source = &candidates[sources::pick(&weights, roll).expect("a roll below the total")];
+    log::debug!("Joke from {}", source.source.name());
+    let joke = source
+        .source
+        .get(
+            &channel,
+            category,
+            &plugin.recent.told(casemapping, response_target),
+        )
+        .await?;
+    plugin
+        .recent
+        .record(casemapping, response_target, &joke.id)?;
+    plugin
+        .tells
+        .record(casemapping, response_target, source.source.name())?;
+
+    // https://github.com/CoucouInc/rustygolem/issues/9
+    let mut text = joke.text.lines().collect::<Vec<_>>().join(" − ");
+    let mut punchline = joke.punchline;
+    if let Some(author) = joke.author.filter(|_| plugin.attribution) {
+        // after the punchline, if any
+        let last = punchline.as_mut().unwrap_or(&mut text);
+        last.push_str(&format!(" — soumis par {}", unhighlight(&author)));
+    }
+    Ok((text, punchline))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use plugin_core::HttpConfig;
+    use pretty_assertions::assert_eq;
+
+    fn plugin() -> Joke {
+        let db = Database::in_memory().unwrap();
+        let submitted = Arc::new(Submitted::new(db.clone()).unwrap());
+        let client = HttpConfig::default().build_client().unwrap();
+        // only the submitted jokes
+        let settings = vec![SourceSettings::default_sources().remove(1)];
+        Joke {
+            throttle: Throttle::new(
+                Duration::ZERO,
+                Duration::ZERO,
+                OnCooldown::Silent,
+                vec!["#serious".to_string()],
+            ),
+            sources: sources::build(&client, &submitted, &settings).unwrap(),
+            recent: Recent::load(db, recent::JOKES, recent::DEFAULT_WINDOW).unwrap(),
+            submitted,
+            accepts_submissions: true,
+            admins: vec!["Geekingfrog".to_string()],
+            channel_languages: vec![ChannelLanguage {
+                channel: "##arch-fr-free".to_string(),
+                language: Lang::Fr,
+            }],
+            punchlines: Punchlines::new(punchline::DEFAULT_MIN_DELAY, punchline::DEFAULT_MAX_DELAY),
+            tells: Tells::in_memory(),
+            attribution: true,
+        }
+    }
+
+    async fn said(plugin: &Joke, nick: &str, target: &str, text: &str) -> Option<String> {
+        let msg = Message::new(
+            Some(format!("{nick}!~{nick}@localhost").as_str()),
+            "PRIVMSG",
+            vec![target, text],
+        )
+        .unwrap();
+        match in_msg(plugin, &msg, CaseMapping::Rfc1459).await.unwrap() {
+            Some(Outbound::Reply { text, .. }) => Some(text),
+            None => None,
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    async fn test_moderation_permissions() {
+        let plugin = plugin();
+        assert_eq!(
+            said(
+                &plugin,
+                "charlie",
+                "#rust",
+                "λjoke add Rust jokes never segfault"
+            )
+            .await,
+            Some("Thanks, joke #1 is waiting for an admin's approval".to_string())
+        );
+        assert_eq!(
+            said(&plugin, "charlie", "#rust", "λjoke approve 1").await,
+            None,
+            "not an admin"
+        );
+        assert_eq!(
+            said(&plugin, "charlie", "#rust", "λjoke").await,
+            Some("No joke source available here".to_string()),
+            "still pending"
+        );
+        assert_eq!(
+            said(&plugin, "geekingfrog", "#rust", "λjoke approve 1").await,
+            Some("Joke #1 approved".to_string())
+        );
+        assert_eq!(
+            said(&plugin, "charlie", "#Rust", "λjoke > alice").await,
+            Some("alice: Rust jokes never segfault — soumis par c\u{200D}harlie".to_string()),
+            "the same channel under the casemapping"
+        );
+        assert_eq!(
+            said(&plugin, "charlie", "#ocaml", "λjoke").await,
+            Some("No joke source available here".to_string()),
+            "only approved for #rust"
+        );
+        assert_eq!(
+            said(&plugin, "charlie", "#rust", "λjoke reject 1").await,
+            None
+        );
+        assert_eq!(
+            said(&plugin, "Geekingfrog", "#rust", "λjoke reject 1").await,
+            Some("Joke #1 rejected".to_string())
+        );
+        assert_eq!(
+            said(&plugin, "charlie", "charlie", "λjoke add in private").await,
+            Some("Jokes can only be added in a channel".to_string())
+        );
+        assert_eq!(
+            said(&plugin, "charlie", "#serious", "λjoke add no jokes here").await,
+            None,
+            "disabled"
+        );
+
+        let in_memory = Joke {
+            accepts_submissions: false,
+            ..plugin
+        };
+        assert_eq!(
+            said(&in_memory, "charlie", "#rust", "λjoke add lost at restart").await,
+            Some(
+                "No database to keep the submitted jokes, set database_path in the golem config"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    async fn test_language() {
+        let plugin = plugin();
+        assert_eq!(plugin.language("##Arch-fr-free", None), Some(Lang::Fr));
+        assert_eq!(
+            plugin.language("##arch-fr-free", Some(Lang::En)),
+            Some(Lang::En),
+            "inline override"
+        );
+        assert_eq!(plugin.language("#rust", None), None, "any language");
+        assert_eq!(plugin.language("#rust", Some(Lang::Fr)), Some(Lang::Fr));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_two_part_joke() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jokes.txt");
+        std::fs::write(
+            &path,
+            r#"{"text": "Why do programmers prefer dark mode?", "punchline": "Because light attracts bugs", "category": "dev"}
+A SQL query walks into a bar and asks two tables: can I join you?"#,
+        )
+        .unwrap();
+        let mut plugin = plugin();
+        let settings = SourceSettings {
+            name: "file".to_string(),
+            weight: 1,
+            path: Some(path.display().to_string()),
+            language: None,
+            token: None,
+            channels: vec![],
+        };
+        let client = HttpConfig::default().build_client().unwrap();
+        plugin.sources = sources::build(&client, &plugin.submitted, &[settings]).unwrap();
+        let (tx, mut rx) = mpsc::channel(10);
+        let until = |s| tokio::time::timeout(Duration::from_secs(s), plugin.run(tx.clone()));
+
+        assert_eq!(
+            said(&plugin, "charlie", "#rust", "λjoke dev").await,
+            Some("Why do programmers prefer dark mode?".to_string()),
+            "the setup right away"
+        );
+        assert!(until(3).await.is_err());
+        assert!(rx.try_recv().is_err(), "not before the delay");
+        assert!(until(6).await.is_err());
+        assert_eq!(
+            rx.try_recv().ok(),
+            Some(Outbound::reply("#rust", "Because light attracts bugs"))
+        );
+
+        assert_eq!(
+            said(&plugin, "charlie", "#rust", "λjoke").await,
+            Some("A SQL query walks into a bar and asks two tables: can I join you?".to_string()),
+            "the other one, not told recently"
+        );
+        assert!(until(10).await.is_err());
+        assert!(rx.try_recv().is_err(), "nothing after a one-liner");
+    }
+
+    #[test]
+    async fn test_stats_and_attribution() {
+        let mut plugin = plugin();
+        said(
+            &plugin,
+            "charlie",
+            "#rust",
+            "λjoke add Rust jokes never segfault",
+        )
+        .await;
+        said(&plugin, "geekingfrog", "#rust", "λjoke approve 1").await;
+        assert_eq!(
+            said(&plugin, "alice", "#rust", "λjoke stats").await,
+            Some("Jokes: submitted 1 − none told here yet".to_string())
+        );
+        assert_eq!(
+            said(&plugin, "alice", "#rust", "λjoke").await,
+            Some("Rust jokes never segfault — soumis par c\u{200D}harlie".to_string())
+        );
+
+        plugin.attribution = false;
+        assert_eq!(
+            said(&plugin, "alice", "#rust", "λjoke").await,
+            Some("Rust jokes never segfault".to_string()),
+            "attribution disabled"
+        );
+        assert_eq!(
+            said(&plugin, "alice", "#rust", "λjoke stats").await,
+            Some("Jokes: submitted 1 − 2 told here (submitted 2)".to_string())
+        );
+        assert_eq!(
+            said(&plugin, "alice", "#ocaml", "λjoke stats").await,
+            Some("Jokes: submitted 0 − none told here yet".to_string()),
+            "per channel"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use plugin_core::{CaseMapping, Delayed, Outbound};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// Between the setup of a two-part joke and its punchline
+pub const DEFAULT_MIN_DELAY: Duration = Duration::from_secs(4);
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(8);
+
+#[derive(Debug)]
+struct Pending {
+    target: String,
+    /// of the network of the target
+    casemapping: CaseMapping,
+    text: String,
+}
+
+/// The punchlines of the two-part jokes, waiting to be told by `run`
+pub struct Punchlines {
+    min_delay: Duration,
+    max_delay: Duration,
+    pending: Delayed<Pending>,
+}
+
+impl Punchlines {
+    pub fn new(min_delay: Duration, max_delay: Duration) -> Self {
+        Punchlines {
+            min_delay,
+            max_delay: max_delay.max(min_delay),
+            pending: Delayed::new(),
+        }
+    }
+
+    /// To be told between the min and the max delay from now, `roll` picking when
+    pub fn schedule(&self, casemapping: CaseMapping, target: &str, text: String, roll: u64) {
+        let spread = (self.max_delay - self.min_delay).as_millis() as u64;
+        let delay = self.min_delay + Duration::from_millis(roll % (spread + 1));
+        self.pending.push(
+            Instant::now() + delay,
+            Pending {
+                target: target.to_string(),
+                casemapping,
+                text,
+            },
+        );
+    }
+
+    /// The golem left the channel, nothing to tell there anymore
+    pub fn forget(&self, channel: &str) {
+        self.pending
+            .retain(|p| !p.casemapping.eq_ignore_case(&p.target, channel));
+    }
+
+    /// Sends the punchlines once due. The ones still pending when the golem
+    /// shuts down are dropped along with this future.
+    pub async fn run(&self, bot_chan: &mpsc::Sender<Outbound>) -> anyhow::Result<()> {
+        loop {
+            for punchline in self.pending.due().await {
+                bot_chan
+                    .send(Outbound::reply(punchline.target, punchline.text))
+                    .await?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const RFC1459: CaseMapping = CaseMapping::Rfc1459;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    fn drain(rx: &mut mpsc::Receiver<Outbound>) -> Vec<Outbound> {
+        let mut sent = vec![];
+        while let Ok(outbound) = rx.try_recv() {
+            sent.push(outbound);
+        }
+        sent
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_delay() {
+        let punchlines = Punchlines::new(DEFAULT_MIN_DELAY, DEFAULT_MAX_DELAY);
+        let (tx, mut rx) = mpsc::channel(10);
+        punchlines.schedule(RFC1459, "#rust", "at 8s".to_string(), 4_000);
+        punchlines.schedule(RFC1459, "#ocaml", "at 4s".to_string(), 0);
+        punchlines.schedule(RFC1459, "#haskell", "at 5s".to_string(), 1_000 + 4_001);
+
+        let until = |s| tokio::time::timeout(secs(s), punchlines.run(&tx));
+        assert!(until(3).await.is_err(), "still running");
+        assert_eq!(drain(&mut rx), vec![], "not before the min delay");
+
+        assert!(until(3).await.is_err());
+        assert_eq!(
+            drain(&mut rx),
+            vec![
+                Outbound::reply("#ocaml", "at 4s"),
+                Outbound::reply("#haskell", "at 5s"),
+            ]
+        );
+
+        punchlines.schedule(RFC1459, "#rust", "at 10s".to_string(), 0);
+        assert!(until(10).await.is_err());
+        assert_eq!(
+            drain(&mut rx),
+            vec![
+                Outbound::reply("#rust", "at 8s"),
+                Outbound::reply("#rust", "at 10s"),
+            ],
+            "scheduled while running"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_forget() {
+        let punchlines = Punchlines::new(DEFAULT_MIN_DELAY, DEFAULT_MAX_DELAY);
+        let (tx, mut rx) = mpsc::channel(10);
+        punchlines.schedule(RFC1459, "#rust[fr]", "parted".to_string(), 0);
+        punchlines.schedule(RFC1459, "#ocaml", "still there".to_string(), 0);
+        punchlines.forget("#RUST{FR}");
+
+        assert!(tokio::time::timeout(secs(10), punchlines.run(&tx))
+            .await
+            .is_err());
+        assert_eq!(
+            drain(&mut rx),
+            vec![Outbound::reply("#ocaml", "still there")]
+        );
+    }
+}
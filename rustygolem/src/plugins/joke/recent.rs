@@ -0,0 +1,190 @@
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use plugin_core::{CaseMapping, Database, Result};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use super::db;
+
+/// Jokes not told again in a channel until that many others were
+pub const DEFAULT_WINDOW: usize = 20;
+
+/// Where the last items told are kept: a table with an autoincrement `id`,
+/// the normalized `channel`, and the column of the ids of the items
+pub struct Table {
+    pub name: &'static str,
+    pub item: &'static str,
+    /// creates the table along with the others of its plugin
+    pub ensure_schema: fn(&Database) -> Result<()>,
+}
+
+pub const JOKES: Table = Table {
+    name: "joke_recent",
+    item: "joke_id",
+    ensure_schema: db::ensure_schema,
+};
+
+#[derive(QueryableByName)]
+struct Row {
+    #[sql_type = "Text"]
+    channel: String,
+    #[sql_type = "Text"]
+    item: String,
+}
+
+/// The ids of the last items told in each channel, oldest first, like the
+/// jokes or the quotes
+pub struct Recent {
+    window: usize,
+    db: Database,
+    table: Table,
+    told: Mutex<HashMap<String, VecDeque<String>>>,
+}
+
+impl Recent {
+    /// Create the table if needed, and load the items told before the last restart
+    pub fn load(db: Database, table: Table, window: usize) -> Result<Self> {
+        (table.ensure_schema)(&db)?;
+        let rows = db.with_connection(|conn| {
+            diesel::sql_query(format!(
+                "SELECT channel, {} AS item FROM {} ORDER BY id",
+                table.item, table.name
+            ))
+            .load::<Row>(conn)
+        })?;
+        let recent = Recent {
+            window,
+            db,
+            table,
+            told: Mutex::new(HashMap::new()),
+        };
+        for row in rows {
+            recent.push(&row.channel, row.item);
+        }
+        Ok(recent)
+    }
+
+    /// Channels are compared with the casemapping of their network
+    pub fn told(&self, casemapping: CaseMapping, channel: &str) -> Vec<String> {
+        self.told
+            .lock()
+            .expect("recent jokes lock")
+            .get(&casemapping.normalize(channel))
+            .map(|ids| ids.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn record(&self, casemapping: CaseMapping, channel: &str, item: &str) -> Result<()> {
+        let channel = casemapping.normalize(channel);
+        self.push(&channel, item.to_string());
+        let (name, column) = (self.table.name, self.table.item);
+        let window = self.window;
+        self.db.with_connection(|conn| {
+            conn.transaction(|| {
+                diesel::sql_query(format!(
+                    "INSERT INTO {name} (channel, {column}) VALUES (?, ?)"
+                ))
+                .bind::<Text, _>(&channel)
+                .bind::<Text, _>(item)
+                .execute(conn)?;
+                diesel::sql_query(format!(
+                    "DELETE FROM {name} WHERE channel = ? AND id NOT IN \
+                     (SELECT id FROM {name} WHERE channel = ? ORDER BY id DESC LIMIT {window})"
+                ))
+                .bind::<Text, _>(&channel)
+                .bind::<Text, _>(&channel)
+                .execute(conn)?;
+                Ok(())
+            })
+        })
+    }
+
+    /// Only keep the last `window` items of the channel, already normalized
+    fn push(&self, channel: &str, item: String) {
+        let mut told = self.told.lock().expect("recent jokes lock");
+        let ids = told.entry(channel.to_string()).or_default();
+        ids.push_back(item);
+        while ids.len() > self.window {
+            ids.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::plugins::joke::sources::{draw, Joke};
+    use pretty_assertions::assert_eq;
+    use std::collections::HashSet;
+
+    const RFC1459: CaseMapping = CaseMapping::Rfc1459;
+
+    fn pool(size: usize) -> Vec<Joke> {
+        (0..size)
+            .map(|i| Joke {
+                id: format!("joke-{i}"),
+                text: format!("joke number {i}"),
+                punchline: None,
+                author: None,
+                category: None,
+            })
+            .collect()
+    }
+
+    /// Draws like the file source, with the given rolls
+    fn tell(recent: &Recent, pool: &[Joke], rolls: impl Iterator<Item = usize>) -> Vec<String> {
+        rolls
+            .map(|roll| {
+                let joke = draw(pool, &recent.told(RFC1459, "#rust"), roll).unwrap();
+                recent.record(RFC1459, "#rust", &joke.id).unwrap();
+                joke.id.clone()
+            })
+            .collect()
+    }
+
+    #[test]
+    async fn test_no_repeat_large_pool() {
+        let recent = Recent::load(Database::in_memory().unwrap(), JOKES, DEFAULT_WINDOW).unwrap();
+        let pool = pool(50);
+        // always rolling the first joke would tell the same one every time
+        let told = tell(&recent, &pool, std::iter::repeat(0).take(DEFAULT_WINDOW));
+        assert_eq!(
+            told.iter().collect::<HashSet<_>>().len(),
+            DEFAULT_WINDOW,
+            "{told:?}"
+        );
+        assert_eq!(recent.told(RFC1459, "#RUST"), told);
+    }
+
+    #[test]
+    async fn test_tiny_pool_repeats() {
+        let recent = Recent::load(Database::in_memory().unwrap(), JOKES, DEFAULT_WINDOW).unwrap();
+        let pool = pool(3);
+        let told = tell(&recent, &pool, std::iter::repeat(0).take(5));
+        assert_eq!(
+            told,
+            vec!["joke-0", "joke-1", "joke-2", "joke-0", "joke-0"],
+            "the whole pool once everything was told"
+        );
+    }
+
+    #[test]
+    async fn test_survives_restart() {
+        let db = Database::in_memory().unwrap();
+        let recent = Recent::load(db.clone(), JOKES, 3).unwrap();
+        for i in 0..5 {
+            recent
+                .record(RFC1459, "#Rust[fr]", &format!("joke-{i}"))
+                .unwrap();
+        }
+        recent.record(RFC1459, "#haskell-fr", "joke-42").unwrap();
+
+        let recent = Recent::load(db, JOKES, 3).unwrap();
+        assert_eq!(
+            recent.told(RFC1459, "#rust{fr}"),
+            vec!["joke-2", "joke-3", "joke-4"]
+        );
+        assert_eq!(recent.told(RFC1459, "#haskell-fr"), vec!["joke-42"]);
+        assert_eq!(recent.told(RFC1459, "#ocaml"), Vec::<String>::new());
+    }
+}
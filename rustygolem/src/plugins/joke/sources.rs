@@ -0,0 +1,672 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use plugin_core::Lang;
+use reqwest::Client;
+use serde::Deserialize;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::submitted::Submitted;
+
+/// Used when the config doesn't list any source
+pub const DEFAULT_SOURCE: &str = "icanhazdadjoke";
+
+/// Draws from an API before telling a joke told recently anyway
+const MAX_DRAWS: usize = 5;
+
+/// The types of blagues-api.fr, its categories
+const BLAGUES_TYPES: &[&str] = &["global", "dev", "dark", "limit", "beauf", "blondes"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joke {
+    /// to tell whether it was told recently
+    pub id: String,
+    /// the whole joke, or the setup of a two-part one
+    pub text: String,
+    /// of a two-part joke, told a few seconds after the setup
+    pub punchline: Option<String>,
+    pub category: Option<String>,
+    /// who submitted it, for the attribution
+    pub author: Option<String>,
+}
+
+#[async_trait]
+pub trait JokeSource: Send + Sync {
+    /// Only for the logs
+    fn name(&self) -> &'static str;
+
+    /// Of its jokes, None when they can be in any language
+    fn language(&self) -> Option<Lang>;
+
+    /// Empty when the source doesn't sort its jokes
+    async fn categories(&self) -> anyhow::Result<Vec<String>>;
+
+    /// Whether it has any joke to tell in the channel. The channel given to
+    /// these methods is normalized with the casemapping of its network.
+    async fn has_jokes(&self, _channel: &str) -> anyhow::Result<bool> {
+        Ok(true)
+    }
+
+    /// How many jokes it has for the channel, None when it can't tell
+    async fn size(&self, _channel: &str) -> anyhow::Result<Option<usize>> {
+        Ok(None)
+    }
+
+    /// A random joke for the channel, of that category when given, and not
+    /// one of the `recent` ids unless there's nothing else
+    async fn get(
+        &self,
+        channel: &str,
+        category: Option<&str>,
+        recent: &[String],
+    ) -> anyhow::Result<Joke>;
+}
+
+/// An entry of the `sources` of the `joke` config section
+#[derive(Debug, Clone, Deserialize)]
+pub struct SourceSettings {
+    /// icanhazdadjoke, blagues-api, file, or submitted for the jokes added
+    /// by the users
+    pub name: String,
+    /// relative to the other sources, 0 to never pick it
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    /// of the jokes, for the file source
+    #[serde(default)]
+    pub path: Option<String>,
+    /// of the jokes of the file source, english by default
+    #[serde(default)]
+    pub language: Option<Lang>,
+    /// for blagues-api
+    #[serde(default)]
+    pub token: Option<String>,
+    /// where the source is used, everywhere when empty
+    #[serde(default)]
+    pub channels: Vec<String>,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+impl SourceSettings {
+    pub fn default_sources() -> Vec<Self> {
+        [DEFAULT_SOURCE, "submitted"]
+            .into_iter()
+            .map(|name| SourceSettings {
+                name: name.to_string(),
+                weight: default_weight(),
+                path: None,
+                language: None,
+                token: None,
+                channels: vec![],
+            })
+            .collect()
+    }
+
+    /// Where this source can be picked, private messages included when it
+    /// isn't restricted to some channels
+    pub fn enabled_in(&self, target: &str) -> bool {
+        self.channels.is_empty() || self.channels.iter().any(|c| c.eq_ignore_ascii_case(target))
+    }
+}
+
+pub struct Weighted {
+    pub source: Arc<dyn JokeSource>,
+    pub settings: SourceSettings,
+}
+
+pub fn build(
+    client: &Client,
+    submitted: &Arc<Submitted>,
+    settings: &[SourceSettings],
+) -> anyhow::Result<Vec<Weighted>> {
+    if settings.is_empty() {
+        return Err(anyhow!("joke.sources cannot be empty"));
+    }
+    settings
+        .iter()
+        .map(|settings| {
+            let source: Arc<dyn JokeSource> = match settings.name.as_str() {
+                "icanhazdadjoke" => Arc::new(Icanhazdadjoke {
+                    client: client.clone(),
+                }),
+                "blagues-api" => match &settings.token {
+                    Some(token) => Arc::new(BlaguesApi {
+                        client: client.clone(),
+                        token: token.clone(),
+                    }),
+                    None => return Err(anyhow!("The blagues-api joke source needs a token")),
+                },
+                "file" => match &settings.path {
+                    Some(path) => Arc::new(LocalFile {
+                        path: PathBuf::from(path),
+                        language: settings.language.unwrap_or(Lang::En),
+                    }),
+                    None => return Err(anyhow!("The joke file source needs a path")),
+                },
+                "submitted" => Arc::clone(submitted) as Arc<dyn JokeSource>,
+                name => {
+                    return Err(anyhow!(
+                        "Unknown joke source {name}, known ones: icanhazdadjoke, blagues-api, file, submitted"
+                    ))
+                }
+            };
+            Ok(Weighted {
+                source,
+                settings: settings.clone(),
+            })
+        })
+        .collect()
+}
+
+/// The index of the weight the roll falls in, the roll being below the total
+/// of the weights. None when it isn't.
+pub fn pick(weights: &[u32], roll: u64) -> Option<usize> {
+    let mut upper = 0;
+    for (idx, weight) in weights.iter().enumerate() {
+        upper += u64::from(*weight);
+        if roll < upper {
+            return Some(idx);
+        }
+    }
+    None
+}
+
+/// https://icanhazdadjoke.com, without categories
+pub struct Icanhazdadjoke {
+    client: Client,
+}
+
+impl Icanhazdadjoke {
+    async fn draw(&self) -> anyhow::Result<Joke> {
+        #[derive(Deserialize)]
+        struct Drawn {
+            id: String,
+            joke: String,
+        }
+
+        let drawn: Drawn = self
+            .client
+            .get("https://icanhazdadjoke.com")
+            .header("Accept", "application/json")
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .context("Error while querying icanhazdadjoke API")?
+            .json()
+            .await
+            .context("Error while getting the response from icanhazdadjoke")?;
+        Ok(Joke {
+            id: drawn.id,
+            text: drawn.joke,
+            punchline: None,
+            author: None,
+            category: None,
+        })
+    }
+}
+
+#[async_trait]
+impl JokeSource for Icanhazdadjoke {
+    fn name(&self) -> &'static str {
+        "icanhazdadjoke"
+    }
+
+    fn language(&self) -> Option<Lang> {
+        Some(Lang::En)
+    }
+
+    async fn categories(&self) -> anyhow::Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    async fn get(
+        &self,
+        _channel: &str,
+        _category: Option<&str>,
+        recent: &[String],
+    ) -> anyhow::Result<Joke> {
+        redraw(|| self.draw(), recent).await
+    }
+}
+
+/// Draws again while the joke was told recently. More jokes in an API than in
+/// any window, a recent one is only told again after a bad streak of luck.
+async fn redraw<F, Fut>(draw: F, recent: &[String]) -> anyhow::Result<Joke>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = anyhow::Result<Joke>>,
+{
+    let mut joke = draw().await?;
+    for _ in 1..MAX_DRAWS {
+        if !recent.contains(&joke.id) {
+            break;
+        }
+        joke = draw().await?;
+    }
+    Ok(joke)
+}
+
+/// https://www.blagues-api.fr, in french, its types as categories
+pub struct BlaguesApi {
+    client: Client,
+    token: String,
+}
+
+impl BlaguesApi {
+    async fn draw(&self, category: Option<&str>) -> anyhow::Result<Joke> {
+        #[derive(Deserialize)]
+        struct Drawn {
+            id: u64,
+            #[serde(rename = "type")]
+            kind: String,
+            joke: String,
+            answer: String,
+        }
+
+        let url = match category {
+            Some(kind) => format!(
+                "https://www.blagues-api.fr/api/type/{}/random",
+                kind.to_lowercase()
+            ),
+            None => "https://www.blagues-api.fr/api/random".to_string(),
+        };
+        let drawn: Drawn = self
+            .client
+            .get(url)
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .context("Error while querying blagues-api")?
+            .json()
+            .await
+            .context("Error while getting the response from blagues-api")?;
+        Ok(Joke {
+            id: format!("blagues-api-{}", drawn.id),
+            text: drawn.joke,
+            punchline: Some(drawn.answer),
+            author: None,
+            category: Some(drawn.kind),
+        })
+    }
+}
+
+#[async_trait]
+impl JokeSource for BlaguesApi {
+    fn name(&self) -> &'static str {
+        "blagues-api"
+    }
+
+    fn language(&self) -> Option<Lang> {
+        Some(Lang::Fr)
+    }
+
+    async fn categories(&self) -> anyhow::Result<Vec<String>> {
+        Ok(BLAGUES_TYPES.iter().map(|t| t.to_string()).collect())
+    }
+
+    async fn get(
+        &self,
+        _channel: &str,
+        category: Option<&str>,
+        recent: &[String],
+    ) -> anyhow::Result<Joke> {
+        redraw(|| self.draw(category), recent).await
+    }
+}
+
+/// A file of jokes, read again for every joke so that it can be edited
+/// without restarting the golem
+pub struct LocalFile {
+    path: PathBuf,
+    language: Lang,
+}
+
+impl LocalFile {
+    async fn load(&self) -> anyhow::Result<Vec<Joke>> {
+        let content = tokio::fs::read_to_string(&self.path)
+            .await
+            .with_context(|| format!("Cannot read the jokes at {}", self.path.display()))?;
+        parse_jokes(&content)
+            .map_err(|err| anyhow!("Invalid jokes at {}: {err}", self.path.display()))
+    }
+}
+
+#[async_trait]
+impl JokeSource for LocalFile {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn language(&self) -> Option<Lang> {
+        Some(self.language)
+    }
+
+    async fn categories(&self) -> anyhow::Result<Vec<String>> {
+        let mut categories = self
+            .load()
+            .await?
+            .into_iter()
+            .filter_map(|joke| joke.category)
+            .collect::<Vec<_>>();
+        categories.sort();
+        categories.dedup();
+        Ok(categories)
+    }
+
+    async fn size(&self, _channel: &str) -> anyhow::Result<Option<usize>> {
+        Ok(Some(self.load().await?.len()))
+    }
+
+    async fn get(
+        &self,
+        _channel: &str,
+        category: Option<&str>,
+        recent: &[String],
+    ) -> anyhow::Result<Joke> {
+        let jokes = self
+            .load()
+            .await?
+            .into_iter()
+            .filter(|joke| in_category(joke, category))
+            .collect::<Vec<_>>();
+        draw(&jokes, recent, rand::random())
+            .cloned()
+            .ok_or_else(|| anyhow!("No joke in {}", self.path.display()))
+    }
+}
+
+/// The joke the roll falls on among the ones not told recently, or among all
+/// of them when the pool is too small to avoid repeats. None for an empty pool.
+pub fn draw<'a>(pool: &'a [Joke], recent: &[String], roll: usize) -> Option<&'a Joke> {
+    let fresh = pool
+        .iter()
+        .filter(|joke| !recent.contains(&joke.id))
+        .collect::<Vec<_>>();
+    if fresh.is_empty() {
+        return pool.get(roll % pool.len().max(1));
+    }
+    Some(fresh[roll % fresh.len()])
+}
+
+fn in_category(joke: &Joke, category: Option<&str>) -> bool {
+    match (category, &joke.category) {
+        (None, _) => true,
+        (Some(wanted), Some(category)) => wanted.eq_ignore_ascii_case(category),
+        (Some(_), None) => false,
+    }
+}
+
+/// A line of a jokes file, as json
+#[derive(Deserialize)]
+struct Entry {
+    /// the text by default
+    #[serde(default)]
+    id: Option<String>,
+    text: String,
+    /// for a two-part joke
+    #[serde(default)]
+    punchline: Option<String>,
+    #[serde(default)]
+    category: Option<String>,
+}
+
+/// One joke per line, either as is or as a json object like
+/// `{"text": "…", "punchline": "…", "category": "pun"}`, the punchline
+/// for two-part jokes. Blank lines and the ones starting
+/// with a # are skipped.
+pub fn parse_jokes(content: &str) -> Result<Vec<Joke>, String> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_number, line)| {
+            if line.starts_with('{') {
+                let entry: Entry = serde_json::from_str(line)
+                    .map_err(|err| format!("line {line_number}: {err}"))?;
+                Ok(Joke {
+                    id: entry.id.unwrap_or_else(|| entry.text.clone()),
+                    text: entry.text,
+                    punchline: entry.punchline,
+                    author: None,
+                    category: entry.category,
+                })
+            } else {
+                Ok(Joke {
+                    id: line.to_string(),
+                    text: line.to_string(),
+                    punchline: None,
+                    author: None,
+                    category: None,
+                })
+            }
+        })
+        .collect()
+}
+
+/// The sources with jokes in that language, or in any language
+pub fn in_language(sources: Vec<&Weighted>, lang: Option<Lang>) -> Vec<&Weighted> {
+    sources
+        .into_iter()
+        .filter(|weighted| match (lang, weighted.source.language()) {
+            (Some(wanted), Some(language)) => wanted == language,
+            _ => true,
+        })
+        .collect()
+}
+
+/// The sources having jokes of the category, all of them without a category.
+/// Otherwise, the categories that would have some.
+pub async fn with_category<'a>(
+    sources: Vec<&'a Weighted>,
+    category: Option<&str>,
+) -> anyhow::Result<Result<Vec<&'a Weighted>, Vec<String>>> {
+    let category = match category {
+        None => return Ok(Ok(sources)),
+        Some(category) => category,
+    };
+    let mut known = vec![];
+    let mut found = vec![];
+    for weighted in sources {
+        let categories = weighted.source.categories().await?;
+        if categories.iter().any(|c| c.eq_ignore_ascii_case(category)) {
+            found.push(weighted);
+        }
+        known.extend(categories);
+    }
+    if found.is_empty() {
+        known.sort();
+        known.dedup();
+        return Ok(Err(known));
+    }
+    Ok(Ok(found))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    struct Fake(Vec<Joke>, Option<Lang>);
+
+    #[async_trait]
+    impl JokeSource for Fake {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn language(&self) -> Option<Lang> {
+            self.1
+        }
+
+        async fn categories(&self) -> anyhow::Result<Vec<String>> {
+            Ok(self.0.iter().filter_map(|j| j.category.clone()).collect())
+        }
+
+        async fn get(
+            &self,
+            _channel: &str,
+            category: Option<&str>,
+            _recent: &[String],
+        ) -> anyhow::Result<Joke> {
+            self.0
+                .iter()
+                .find(|joke| in_category(joke, category))
+                .cloned()
+                .ok_or_else(|| anyhow!("no joke"))
+        }
+    }
+
+    fn joke(text: &str, category: Option<&str>) -> Joke {
+        Joke {
+            id: text.to_string(),
+            text: text.to_string(),
+            punchline: None,
+            author: None,
+            category: category.map(|c| c.to_string()),
+        }
+    }
+
+    fn weighted(name: &str, jokes: Vec<Joke>) -> Weighted {
+        in_lang(name, jokes, Some(Lang::En))
+    }
+
+    fn in_lang(name: &str, jokes: Vec<Joke>, lang: Option<Lang>) -> Weighted {
+        Weighted {
+            source: Arc::new(Fake(jokes, lang)),
+            settings: SourceSettings {
+                name: name.to_string(),
+                weight: 1,
+                path: None,
+                language: None,
+                token: None,
+                channels: vec![],
+            },
+        }
+    }
+
+    #[test]
+    async fn test_pick() {
+        let weights = [3, 0, 1];
+        let picked = (0..4).map(|roll| pick(&weights, roll)).collect::<Vec<_>>();
+        assert_eq!(picked, vec![Some(0), Some(0), Some(0), Some(2)]);
+        assert_eq!(pick(&weights, 4), None, "beyond the total");
+        assert_eq!(pick(&[0, 0], 0), None);
+        assert_eq!(pick(&[], 0), None);
+    }
+
+    #[test]
+    async fn test_parse_jokes() {
+        let content = r#"
+# dad jokes
+Why did the scarecrow win an award? Because he was outstanding in his field.
+
+   {"text": "I'd tell you a UDP joke, but you might not get it", "category": "network"}
+{"id": "pirate", "text": "What's a pirate's favourite language? R"}
+{"id": "atoms", "text": "Why don't scientists trust atoms?", "punchline": "They make up everything"}
+"#;
+        assert_eq!(
+            parse_jokes(content).unwrap(),
+            vec![
+                joke(
+                    "Why did the scarecrow win an award? Because he was outstanding in his field.",
+                    None
+                ),
+                joke(
+                    "I'd tell you a UDP joke, but you might not get it",
+                    Some("network")
+                ),
+                Joke {
+                    id: "pirate".to_string(),
+                    ..joke("What's a pirate's favourite language? R", None)
+                },
+                Joke {
+                    id: "atoms".to_string(),
+                    punchline: Some("They make up everything".to_string()),
+                    ..joke("Why don't scientists trust atoms?", None)
+                },
+            ]
+        );
+        assert_eq!(parse_jokes("\n# nothing\n\n").unwrap(), vec![]);
+        let err = parse_jokes("a joke\n{\"category\": \"pun\"}").unwrap_err();
+        assert!(err.starts_with("line 2:"), "{err}");
+    }
+
+    #[test]
+    async fn test_with_category() {
+        let dad = weighted("dad", vec![joke("dad joke", None)]);
+        let file = weighted(
+            "file",
+            vec![
+                joke("udp joke", Some("network")),
+                joke("tcp joke", Some("network")),
+                joke("pun", Some("pun")),
+            ],
+        );
+        let names = |found: Vec<&Weighted>| {
+            found
+                .into_iter()
+                .map(|w| w.settings.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let all = with_category(vec![&dad, &file], None).await.unwrap();
+        assert_eq!(names(all.unwrap()), vec!["dad", "file"]);
+
+        let network = with_category(vec![&dad, &file], Some("Network"))
+            .await
+            .unwrap();
+        assert_eq!(names(network.unwrap()), vec!["file"]);
+
+        let unknown = with_category(vec![&dad, &file], Some("knock-knock"))
+            .await
+            .unwrap();
+        assert_eq!(unknown.unwrap_err(), vec!["network", "pun"]);
+
+        let none = with_category(vec![&dad], Some("pun")).await.unwrap();
+        assert_eq!(none.unwrap_err(), Vec::<String>::new());
+
+        let picked = file.source.get("#rust", Some("pun"), &[]).await.unwrap();
+        assert_eq!(picked, joke("pun", Some("pun")));
+    }
+
+    #[test]
+    async fn test_in_language() {
+        let dad = in_lang("dad", vec![], Some(Lang::En));
+        let blagues = in_lang("blagues", vec![], Some(Lang::Fr));
+        let submitted = in_lang("submitted", vec![], None);
+        let names = |found: Vec<&Weighted>| {
+            found
+                .into_iter()
+                .map(|w| w.settings.name.clone())
+                .collect::<Vec<_>>()
+        };
+        let all = || vec![&dad, &blagues, &submitted];
+        assert_eq!(
+            names(in_language(all(), Some(Lang::Fr))),
+            vec!["blagues", "submitted"]
+        );
+        assert_eq!(
+            names(in_language(all(), Some(Lang::En))),
+            vec!["dad", "submitted"]
+        );
+        assert_eq!(
+            names(in_language(all(), None)),
+            vec!["dad", "blagues", "submitted"]
+        );
+    }
+
+    #[test]
+    async fn test_enabled_in() {
+        let mut settings = SourceSettings::default_sources().remove(0);
+        assert!(settings.enabled_in("#rust"));
+        assert!(settings.enabled_in("charlie"), "private messages");
+        settings.channels = vec!["#Rust".to_string()];
+        assert!(settings.enabled_in("#rust"));
+        assert!(!settings.enabled_in("##arch-fr-free"));
+    }
+}
@@ -0,0 +1,212 @@
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+use plugin_core::{CaseMapping, Database, Lang, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use super::db;
+
+#[derive(QueryableByName)]
+struct Row {
+    #[sql_type = "Text"]
+    channel: String,
+    #[sql_type = "Text"]
+    source: String,
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+/// How many jokes were told in each channel, from each source
+pub struct Tells {
+    /// to persist the counts, since the startup only without
+    db: Option<Database>,
+    counts: Mutex<HashMap<String, BTreeMap<String, u64>>>,
+}
+
+impl Tells {
+    pub fn in_memory() -> Self {
+        Tells {
+            db: None,
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Create the table if needed, and load the counts from before the last restart
+    pub fn load(db: Database) -> Result<Self> {
+        db::ensure_schema(&db)?;
+        let rows = db.with_connection(|conn| {
+            diesel::sql_query("SELECT channel, source, count FROM joke_tells").load::<Row>(conn)
+        })?;
+        let mut counts = HashMap::<String, BTreeMap<String, u64>>::new();
+        for row in rows {
+            counts
+                .entry(row.channel)
+                .or_default()
+                .insert(row.source, row.count as u64);
+        }
+        Ok(Tells {
+            db: Some(db),
+            counts: Mutex::new(counts),
+        })
+    }
+
+    /// Channels are compared with the casemapping of their network
+    pub fn record(&self, casemapping: CaseMapping, channel: &str, source: &str) -> Result<()> {
+        let channel = casemapping.normalize(channel);
+        *self
+            .counts
+            .lock()
+            .expect("joke tells lock")
+            .entry(channel.clone())
+            .or_default()
+            .entry(source.to_string())
+            .or_default() += 1;
+        let db = match &self.db {
+            Some(db) => db,
+            None => return Ok(()),
+        };
+        db.with_connection(|conn| {
+            conn.transaction(|| {
+                diesel::sql_query(
+                    "INSERT OR IGNORE INTO joke_tells (channel, source) VALUES (?, ?)",
+                )
+                .bind::<Text, _>(&channel)
+                .bind::<Text, _>(source)
+                .execute(conn)?;
+                diesel::sql_query(
+                    "UPDATE joke_tells SET count = count + 1 WHERE channel = ? AND source = ?",
+                )
+                .bind::<Text, _>(&channel)
+                .bind::<Text, _>(source)
+                .execute(conn)?;
+                Ok(())
+            })
+        })
+    }
+
+    /// For each source
+    pub fn told(&self, casemapping: CaseMapping, channel: &str) -> BTreeMap<String, u64> {
+        self.counts
+            .lock()
+            .expect("joke tells lock")
+            .get(&casemapping.normalize(channel))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// A source as shown by λjoke stats
+pub struct Pool {
+    pub source: &'static str,
+    pub language: Option<Lang>,
+    /// None when the source can't tell
+    pub size: Option<usize>,
+}
+
+/// On a single line, like
+/// `Jokes: icanhazdadjoke (en) ?, submitted 3 − 5 told here (icanhazdadjoke 3, submitted 2)`
+pub fn format(pools: &[Pool], told: &BTreeMap<String, u64>) -> String {
+    let pools = pools
+        .iter()
+        .map(|pool| {
+            let language = match pool.language {
+                Some(Lang::Fr) => " (fr)",
+                Some(Lang::En) => " (en)",
+                None => "",
+            };
+            let size = pool
+                .size
+                .map(|size| size.to_string())
+                .unwrap_or_else(|| "?".to_string());
+            format!("{}{language} {size}", pool.source)
+        })
+        .collect::<Vec<_>>();
+    let pools = if pools.is_empty() {
+        "none".to_string()
+    } else {
+        pools.join(", ")
+    };
+    let total = told.values().sum::<u64>();
+    if total == 0 {
+        return format!("Jokes: {pools} − none told here yet");
+    }
+    let per_source = told
+        .iter()
+        .map(|(source, count)| format!("{source} {count}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("Jokes: {pools} − {total} told here ({per_source})")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const RFC1459: CaseMapping = CaseMapping::Rfc1459;
+
+    #[test]
+    async fn test_counts() {
+        let tells = Tells::in_memory();
+        tells.record(RFC1459, "#Rust[fr]", "file").unwrap();
+        tells.record(RFC1459, "#RUST[FR]", "submitted").unwrap();
+        tells.record(RFC1459, "#rust{fr}", "file").unwrap();
+        tells.record(RFC1459, "#ocaml", "file").unwrap();
+        assert_eq!(
+            tells.told(RFC1459, "#RUST{FR}"),
+            BTreeMap::from([("file".to_string(), 2), ("submitted".to_string(), 1)])
+        );
+        assert_eq!(tells.told(RFC1459, "#haskell"), BTreeMap::new());
+    }
+
+    #[test]
+    async fn test_persisted_counts() {
+        let db = Database::in_memory().unwrap();
+        let tells = Tells::load(db.clone()).unwrap();
+        tells.record(RFC1459, "#rust", "file").unwrap();
+        tells.record(RFC1459, "#rust", "file").unwrap();
+        tells.record(RFC1459, "#ocaml", "submitted").unwrap();
+
+        let tells = Tells::load(db).unwrap();
+        assert_eq!(
+            tells.told(RFC1459, "#rust"),
+            BTreeMap::from([("file".to_string(), 2)])
+        );
+        assert_eq!(
+            tells.told(RFC1459, "#ocaml"),
+            BTreeMap::from([("submitted".to_string(), 1)])
+        );
+    }
+
+    #[test]
+    async fn test_format() {
+        let pools = [
+            Pool {
+                source: "icanhazdadjoke",
+                language: Some(Lang::En),
+                size: None,
+            },
+            Pool {
+                source: "submitted",
+                language: None,
+                size: Some(3),
+            },
+        ];
+        assert_eq!(
+            format(&pools, &BTreeMap::new()),
+            "Jokes: icanhazdadjoke (en) ?, submitted 3 − none told here yet"
+        );
+        let told = BTreeMap::from([
+            ("submitted".to_string(), 2),
+            ("icanhazdadjoke".to_string(), 3),
+        ]);
+        assert_eq!(
+            format(&pools, &told),
+            "Jokes: icanhazdadjoke (en) ?, submitted 3 − 5 told here (icanhazdadjoke 3, submitted 2)"
+        );
+        assert_eq!(
+            format(&[], &told),
+            "Jokes: none − 5 told here (icanhazdadjoke 3, submitted 2)"
+        );
+    }
+}
@@ -0,0 +1,404 @@
+use async_trait::async_trait;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Nullable, Text};
+use plugin_core::{parse, Database, Lang, Result};
+use std::result::Result as StdResult;
+
+use super::db;
+use super::sources::{self, Joke, JokeSource};
+use crate::utils::text::strip_formatting;
+
+/// Longer ones don't fit in a line anyway
+pub const MAX_LENGTH: usize = 300;
+
+pub const USAGE: &str =
+    "Usage: λjoke [fr|en] [category], λjoke add <joke>, λjoke stats, λjoke approve <id> [--global] or λjoke reject <id>";
+
+#[derive(Debug, PartialEq)]
+pub enum JokeCommand<'a> {
+    /// in the language of the channel without one
+    Tell {
+        lang: Option<Lang>,
+        category: Option<&'a str>,
+    },
+    Add(&'a str),
+    /// for the channel it was submitted in, or everywhere
+    Approve {
+        id: i64,
+        global: bool,
+    },
+    Reject(i64),
+    /// the size of the pools, and how many jokes were told in the channel
+    Stats,
+}
+
+impl JokeCommand<'_> {
+    pub fn is_moderation(&self) -> bool {
+        matches!(self, JokeCommand::Approve { .. } | JokeCommand::Reject(_))
+    }
+}
+
+/// None when this isn't a joke command, an error with the usage when it
+/// is one, but malformed. Along with the `> nick` target.
+pub fn parse_command(input: &str) -> Option<(StdResult<JokeCommand, String>, Option<&str>)> {
+    let (_, (args, target)) = parse::command("joke")(input).ok()?;
+    let (word, rest) = match args.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (args, ""),
+    };
+    let id = |word: &str| word.trim_start_matches('#').parse::<i64>().ok();
+    let mut words = rest.split_whitespace();
+    let command = match (word, words.next(), words.next(), words.next()) {
+        ("add", _, _, _) if !rest.is_empty() => Some(JokeCommand::Add(rest)),
+        ("approve", Some(n), global, None) => match (id(n), global) {
+            (Some(id), None) => Some(JokeCommand::Approve { id, global: false }),
+            (Some(id), Some("--global")) => Some(JokeCommand::Approve { id, global: true }),
+            _ => None,
+        },
+        ("reject", Some(n), None, None) => id(n).map(JokeCommand::Reject),
+        ("stats", None, _, _) => Some(JokeCommand::Stats),
+        ("add" | "approve" | "reject" | "stats", _, _, _) => None,
+        ("", _, _, _) => Some(JokeCommand::Tell {
+            lang: None,
+            category: None,
+        }),
+        (word, category, None, _) if word.parse::<Lang>().is_ok() => Some(JokeCommand::Tell {
+            lang: word.parse().ok(),
+            category,
+        }),
+        (category, None, _, _) => Some(JokeCommand::Tell {
+            lang: None,
+            category: Some(category),
+        }),
+        _ => None,
+    };
+    Some((command.ok_or_else(|| USAGE.to_string()), target))
+}
+
+/// To tell resubmissions: lowercase, and only the letters and digits of the words
+pub fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Submission {
+    /// waiting for an admin, with its id
+    Pending(i64),
+    /// already submitted with that id
+    Duplicate(i64),
+    TooLong,
+    Empty,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Moderated {
+    Approved,
+    Rejected,
+    NoSuchJoke,
+}
+
+#[derive(QueryableByName)]
+struct Id {
+    #[sql_type = "BigInt"]
+    id: i64,
+}
+
+#[derive(QueryableByName)]
+struct Row {
+    #[sql_type = "BigInt"]
+    id: i64,
+    #[sql_type = "Text"]
+    text: String,
+    #[sql_type = "Text"]
+    author: String,
+}
+
+/// The jokes of the users, told once approved by an admin
+pub struct Submitted {
+    db: Database,
+}
+
+impl Submitted {
+    pub fn new(db: Database) -> Result<Self> {
+        db::ensure_schema(&db)?;
+        Ok(Submitted { db })
+    }
+
+    /// The channel is normalized with the casemapping of its network
+    pub fn add(&self, author: &str, channel: &str, text: &str) -> Result<Submission> {
+        let text = strip_formatting(text).trim().to_string();
+        let normalized = normalize(&text);
+        if normalized.is_empty() {
+            return Ok(Submission::Empty);
+        }
+        if text.chars().count() > MAX_LENGTH {
+            return Ok(Submission::TooLong);
+        }
+        self.db.with_connection(|conn| {
+            conn.transaction(|| {
+                let existing = diesel::sql_query("SELECT id FROM joke_submitted WHERE normalized = ?")
+                    .bind::<Text, _>(&normalized)
+                    .get_result::<Id>(conn)
+                    .optional()?;
+                if let Some(existing) = existing {
+                    return Ok(Submission::Duplicate(existing.id));
+                }
+                diesel::sql_query(
+                    "INSERT INTO joke_submitted (author, channel, text, normalized) VALUES (?, ?, ?, ?)",
+                )
+                .bind::<Text, _>(author)
+                .bind::<Text, _>(channel)
+                .bind::<Text, _>(&text)
+                .bind::<Text, _>(&normalized)
+                .execute(conn)?;
+                let id = diesel::sql_query("SELECT last_insert_rowid() AS id").get_result::<Id>(conn)?;
+                Ok(Submission::Pending(id.id))
+            })
+        })
+    }
+
+    /// Into the pool of the channel it was submitted in, or of every channel
+    pub fn approve(&self, id: i64, global: bool) -> Result<Moderated> {
+        self.moderate(id, "approved", Some(global)).map(|found| {
+            if found {
+                Moderated::Approved
+            } else {
+                Moderated::NoSuchJoke
+            }
+        })
+    }
+
+    /// Out of the pool, if it was approved before
+    pub fn reject(&self, id: i64) -> Result<Moderated> {
+        self.moderate(id, "rejected", None).map(|found| {
+            if found {
+                Moderated::Rejected
+            } else {
+                Moderated::NoSuchJoke
+            }
+        })
+    }
+
+    fn moderate(&self, id: i64, status: &str, global: Option<bool>) -> Result<bool> {
+        let updated = self.db.with_connection(|conn| {
+            diesel::sql_query(
+                "UPDATE joke_submitted SET status = ?, global = COALESCE(?, global) WHERE id = ?",
+            )
+            .bind::<Text, _>(status)
+            .bind::<Nullable<Bool>, _>(global)
+            .bind::<BigInt, _>(id)
+            .execute(conn)
+        })?;
+        Ok(updated > 0)
+    }
+
+    /// The approved jokes told in the channel
+    pub fn pool(&self, channel: &str) -> Result<Vec<Joke>> {
+        let rows = self.db.with_connection(|conn| {
+            diesel::sql_query(
+                "SELECT id, text, author FROM joke_submitted \
+                 WHERE status = 'approved' AND (global OR channel = ?) ORDER BY id",
+            )
+            .bind::<Text, _>(channel)
+            .load::<Row>(conn)
+        })?;
+        Ok(rows
+            .into_iter()
+            .map(|row| Joke {
+                id: format!("submitted-{}", row.id),
+                text: row.text,
+                punchline: None,
+                author: Some(row.author),
+                category: None,
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl JokeSource for Submitted {
+    fn name(&self) -> &'static str {
+        "submitted"
+    }
+
+    fn language(&self) -> Option<Lang> {
+        None
+    }
+
+    async fn categories(&self) -> anyhow::Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    async fn has_jokes(&self, channel: &str) -> anyhow::Result<bool> {
+        Ok(!self.pool(channel)?.is_empty())
+    }
+
+    async fn size(&self, channel: &str) -> anyhow::Result<Option<usize>> {
+        Ok(Some(self.pool(channel)?.len()))
+    }
+
+    async fn get(
+        &self,
+        channel: &str,
+        _category: Option<&str>,
+        recent: &[String],
+    ) -> anyhow::Result<Joke> {
+        let pool = self.pool(channel)?;
+        sources::draw(&pool, recent, rand::random())
+            .cloned()
+            .ok_or_else(|| anyhow!("No approved joke for {channel}"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_parse_command() {
+        let parsed = |input| parse_command(input).map(|(command, _)| command);
+        let tell = |lang, category| Some(Ok(JokeCommand::Tell { lang, category }));
+        assert_eq!(parsed("λjoke"), tell(None, None));
+        assert_eq!(parsed("λjoke pun"), tell(None, Some("pun")));
+        assert_eq!(parsed("λjoke fr"), tell(Some(Lang::Fr), None));
+        assert_eq!(parsed("λjoke EN"), tell(Some(Lang::En), None));
+        assert_eq!(parsed("λjoke fr dev"), tell(Some(Lang::Fr), Some("dev")));
+        assert_eq!(
+            parse_command("λjoke add Why do   programmers > prefer dark mode? > charlie"),
+            Some((
+                Ok(JokeCommand::Add("Why do   programmers > prefer dark mode?")),
+                Some("charlie")
+            ))
+        );
+        assert_eq!(
+            parsed("λjoke approve 12"),
+            Some(Ok(JokeCommand::Approve {
+                id: 12,
+                global: false
+            }))
+        );
+        assert_eq!(
+            parsed("λjoke approve #12 --global"),
+            Some(Ok(JokeCommand::Approve {
+                id: 12,
+                global: true
+            }))
+        );
+        assert_eq!(parsed("λjoke reject 3"), Some(Ok(JokeCommand::Reject(3))));
+        assert_eq!(parsed("λjoke stats"), Some(Ok(JokeCommand::Stats)));
+        for malformed in [
+            "λjoke add",
+            "λjoke approve",
+            "λjoke approve twelve",
+            "λjoke approve 12 --everywhere",
+            "λjoke reject 3 4",
+            "λjoke stats here",
+            "λjoke two words",
+            "λjoke fr dev dark",
+        ] {
+            assert_eq!(
+                parsed(malformed),
+                Some(Err(USAGE.to_string())),
+                "{malformed}"
+            );
+        }
+        assert_eq!(parsed("λjokes"), None);
+    }
+
+    #[test]
+    async fn test_add_and_moderate() {
+        let submitted = Submitted::new(Database::in_memory().unwrap()).unwrap();
+        assert_eq!(
+            submitted
+                .add(
+                    "charlie",
+                    "#rust",
+                    "\x02Why\x02 did the \x0304,01borrow checker\x0F cross the road?"
+                )
+                .unwrap(),
+            Submission::Pending(1)
+        );
+        assert_eq!(
+            submitted
+                .add("alice", "#haskell-fr", "A monad is a monoid")
+                .unwrap(),
+            Submission::Pending(2)
+        );
+        assert_eq!(submitted.pool("#rust").unwrap(), vec![], "pending");
+
+        assert_eq!(submitted.approve(1, false).unwrap(), Moderated::Approved);
+        assert_eq!(submitted.approve(42, false).unwrap(), Moderated::NoSuchJoke);
+        assert_eq!(
+            submitted.pool("#rust").unwrap(),
+            vec![Joke {
+                id: "submitted-1".to_string(),
+                text: "Why did the borrow checker cross the road?".to_string(),
+                punchline: None,
+                author: Some("charlie".to_string()),
+                category: None,
+            }],
+            "without the formatting"
+        );
+        assert_eq!(submitted.pool("#haskell-fr").unwrap(), vec![]);
+
+        assert_eq!(submitted.approve(2, true).unwrap(), Moderated::Approved);
+        assert_eq!(submitted.pool("#rust").unwrap().len(), 2, "global");
+
+        assert_eq!(submitted.reject(1).unwrap(), Moderated::Rejected);
+        let ids = |pool: Vec<Joke>| pool.into_iter().map(|j| j.id).collect::<Vec<_>>();
+        assert_eq!(ids(submitted.pool("#rust").unwrap()), vec!["submitted-2"]);
+    }
+
+    #[test]
+    async fn test_dedup() {
+        let submitted = Submitted::new(Database::in_memory().unwrap()).unwrap();
+        assert_eq!(
+            submitted
+                .add("charlie", "#rust", "I'm reading a book about anti-gravity.")
+                .unwrap(),
+            Submission::Pending(1)
+        );
+        assert_eq!(
+            submitted
+                .add("alice", "#ocaml", "  im READING a book about antigravity ")
+                .unwrap(),
+            Submission::Duplicate(1)
+        );
+        submitted.reject(1).unwrap();
+        assert_eq!(
+            submitted
+                .add("bob", "#rust", "I'm reading a book about anti gravity!")
+                .unwrap(),
+            Submission::Pending(2),
+            "not the same words"
+        );
+        assert_eq!(
+            submitted.add("bob", "#rust", "\x0304,05\x02").unwrap(),
+            Submission::Empty
+        );
+        let long = "ha ".repeat(MAX_LENGTH);
+        assert_eq!(
+            submitted.add("bob", "#rust", &long).unwrap(),
+            Submission::TooLong
+        );
+    }
+
+    #[test]
+    async fn test_normalize() {
+        assert_eq!(
+            normalize("  Knock, KNOCK!  Who's there? "),
+            "knock knock whos there"
+        );
+        assert_eq!(normalize("!!! ..."), "");
+    }
+}
@@ -0,0 +1,159 @@
+use irc::proto::ChannelExt;
+use plugin_core::Cooldown;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Private messages only bother the one who asked
+pub const DEFAULT_PRIVATE_COOLDOWN: Duration = Duration::from_secs(10);
+
+/// What a λjoke on cooldown gets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum OnCooldown {
+    /// nothing
+    #[default]
+    Silent,
+    /// a message telling to wait
+    Notify,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Allowed {
+    Yes,
+    /// on cooldown, with whether to tell so
+    Wait(OnCooldown),
+    /// the channel opted out of jokes
+    Disabled,
+}
+
+/// How often jokes get told, for each channel and each private conversation
+pub struct Throttle {
+    channels: Cooldown,
+    private: Cooldown,
+    on_cooldown: OnCooldown,
+    disabled_channels: Vec<String>,
+}
+
+impl Throttle {
+    pub fn new(
+        cooldown: Duration,
+        private_cooldown: Duration,
+        on_cooldown: OnCooldown,
+        disabled_channels: Vec<String>,
+    ) -> Self {
+        Throttle {
+            channels: Cooldown::new(cooldown),
+            private: Cooldown::new(private_cooldown),
+            on_cooldown,
+            disabled_channels,
+        }
+    }
+
+    /// Whether the plugin answers at all there
+    pub fn is_disabled(&self, target: &str) -> bool {
+        self.disabled_channels
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(target))
+    }
+
+    pub fn check(&self, target: &str) -> Allowed {
+        self.check_at(target, Instant::now())
+    }
+
+    /// Same as `check`, with the current time given by the caller
+    pub fn check_at(&self, target: &str, now: Instant) -> Allowed {
+        if self.is_disabled(target) {
+            return Allowed::Disabled;
+        }
+        let cooldown = if target.is_channel_name() {
+            &self.channels
+        } else {
+            &self.private
+        };
+        if cooldown.check_at(&target.to_lowercase(), now) {
+            Allowed::Yes
+        } else {
+            Allowed::Wait(self.on_cooldown)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    fn throttle(on_cooldown: OnCooldown) -> Throttle {
+        Throttle::new(
+            DEFAULT_COOLDOWN,
+            DEFAULT_PRIVATE_COOLDOWN,
+            on_cooldown,
+            vec!["#Serious".to_string()],
+        )
+    }
+
+    #[test]
+    async fn test_channel_cooldown() {
+        let throttle = throttle(OnCooldown::Silent);
+        let t0 = Instant::now();
+        assert_eq!(throttle.check_at("#rust", t0), Allowed::Yes);
+        assert_eq!(
+            throttle.check_at("#ocaml", t0 + secs(1)),
+            Allowed::Yes,
+            "per channel"
+        );
+        assert_eq!(
+            throttle.check_at("#RUST", t0 + secs(59)),
+            Allowed::Wait(OnCooldown::Silent)
+        );
+        assert_eq!(
+            throttle.check_at("#rust", t0 + Duration::from_millis(59_999)),
+            Allowed::Wait(OnCooldown::Silent)
+        );
+        assert_eq!(
+            throttle.check_at("#rust", t0 + secs(60)),
+            Allowed::Yes,
+            "exactly at the edge"
+        );
+    }
+
+    #[test]
+    async fn test_private_cooldown() {
+        let throttle = throttle(OnCooldown::Notify);
+        let t0 = Instant::now();
+        assert_eq!(throttle.check_at("#rust", t0), Allowed::Yes);
+        assert_eq!(throttle.check_at("charlie", t0), Allowed::Yes);
+        assert_eq!(throttle.check_at("alice", t0), Allowed::Yes);
+        assert_eq!(
+            throttle.check_at("charlie", t0 + secs(9)),
+            Allowed::Wait(OnCooldown::Notify)
+        );
+        assert_eq!(
+            throttle.check_at("charlie", t0 + secs(10)),
+            Allowed::Yes,
+            "more generous than the channels"
+        );
+        assert_eq!(
+            throttle.check_at("#rust", t0 + secs(10)),
+            Allowed::Wait(OnCooldown::Notify)
+        );
+    }
+
+    #[test]
+    async fn test_disabled() {
+        let throttle = throttle(OnCooldown::Notify);
+        let t0 = Instant::now();
+        assert_eq!(throttle.check_at("#serious", t0), Allowed::Disabled);
+        assert_eq!(
+            throttle.check_at("#serious", t0 + secs(120)),
+            Allowed::Disabled
+        );
+        assert!(throttle.is_disabled("#SERIOUS"));
+        assert!(!throttle.is_disabled("#rust"));
+    }
+}
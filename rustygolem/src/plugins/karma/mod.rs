@@ -0,0 +1,4 @@
+mod plugin;
+mod scores;
+
+pub use plugin::Karma;
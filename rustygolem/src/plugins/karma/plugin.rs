@@ -0,0 +1,475 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use irc::proto::{ChannelExt, Command, Message};
+use plugin_core::utils::network::network;
+use plugin_core::utils::parser;
+use plugin_core::{
+    CommandHelp, Initialised, Members, MsgCtx, Outbound, Plugin, Requirement, Result,
+};
+use serde::Deserialize;
+
+use super::scores::{Score, Scores};
+use crate::caps::CaseMapping;
+use crate::utils::messages::with_target;
+
+/// Scores in the top and the bottom of bare λkarma
+const RANKED: usize = 5;
+
+/// Seconds before a nick can give karma to the same thing again, unless the
+/// config says otherwise
+pub const DEFAULT_COOLDOWN_SECS: u64 = 600;
+
+/// The `karma` section of the golem config
+#[derive(Deserialize)]
+struct Settings {
+    /// before a nick can give karma to the same thing again in a channel
+    #[serde(default = "default_cooldown_secs")]
+    cooldown_secs: u64,
+}
+
+fn default_cooldown_secs() -> u64 {
+    DEFAULT_COOLDOWN_SECS
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            cooldown_secs: default_cooldown_secs(),
+        }
+    }
+}
+
+impl Settings {
+    fn load(config: &plugin_core::Config) -> Result<Self> {
+        Ok(config.plugin_section("karma")?.unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target<'a> {
+    /// `alice++`, only when alice is in the channel
+    Nick(&'a str),
+    /// `(the borrow checker)++`, with the inner whitespace as is
+    Phrase(&'a str),
+}
+
+/// What trails a `nick++` at the end of a sentence
+const TRAILING: &[char] = &[',', '.', ';', ':', '!', '?', ')'];
+
+/// The `thing++` and `thing--` in the message, as whole words so that
+/// `c++11` or `x+++y` don't count. Whether `c` in `c++` is a nick is up to
+/// the caller.
+fn parse_karma(text: &str) -> Vec<(Target<'_>, i64)> {
+    let mut changes = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        rest = &rest[start..];
+        if let Some((phrase, delta, after)) = parse_phrase(rest) {
+            changes.push((Target::Phrase(phrase), delta));
+            rest = after;
+            continue;
+        }
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let word = rest[..end].trim_end_matches(TRAILING);
+        if let Some((nick, delta)) = suffix(word) {
+            if is_nick(nick) {
+                changes.push((Target::Nick(nick), delta));
+            }
+        }
+        rest = &rest[end..];
+    }
+    changes
+}
+
+/// `(some phrase)++` at the start of the input, and what follows it
+fn parse_phrase(input: &str) -> Option<(&str, i64, &str)> {
+    let inner = input.strip_prefix('(')?;
+    let close = inner.find(')')?;
+    let phrase = inner[..close].trim();
+    let after = &inner[close + 1..];
+    let delta = suffix(after.get(..2)?)?.1;
+    let after = &after[2..];
+    let ends_word = after
+        .chars()
+        .next()
+        .map_or(true, |c| c.is_whitespace() || TRAILING.contains(&c));
+    if phrase.is_empty() || !ends_word {
+        return None;
+    }
+    Some((phrase, delta, after))
+}
+
+/// `thing++` gives `(thing, 1)`
+fn suffix(word: &str) -> Option<(&str, i64)> {
+    if let Some(thing) = word.strip_suffix("++") {
+        Some((thing, 1))
+    } else {
+        word.strip_suffix("--").map(|thing| (thing, -1))
+    }
+}
+
+/// Like `alice`, `[m]atrix` or `héloïse`, but not `+` or `2`
+fn is_nick(word: &str) -> bool {
+    let special = |c: char| "-_[]\\`^{}|".contains(c);
+    match word.chars().next() {
+        Some(first) if first.is_alphabetic() || (special(first) && first != '-') => {
+            word.chars().all(|c| c.is_alphanumeric() || special(c)) && !word.ends_with('-')
+        }
+        _ => false,
+    }
+}
+
+pub struct Karma {
+    scores: Scores,
+    /// a nick only gets karma from `nick++` when it is in the channel
+    members: Arc<Members>,
+    /// when each nick last gave karma to each thing, by network, channel,
+    /// giver and thing, all normalized
+    given: Mutex<HashMap<(String, String, String, String), DateTime<Utc>>>,
+    cooldown: Duration,
+}
+
+#[async_trait]
+impl Plugin for Karma {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        Settings::load(config)?;
+        config.check_database("karma")?;
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let settings = Settings::load(config)?;
+        let db = config.require_database("karma")?;
+        Ok(Initialised::from(Karma {
+            scores: Scores::load(db)?,
+            members: config.members(),
+            given: Mutex::new(HashMap::new()),
+            cooldown: Duration::seconds(settings.cooldown_secs as i64),
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "karma"
+    }
+
+    async fn in_message_ctx(&self, ctx: &MsgCtx, msg: &Message) -> Result<Option<Outbound>> {
+        in_msg(self, msg, ctx.casemapping, Utc::now())
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![CommandHelp::new("karma")
+            .usage("karma [thing] [> nick]")
+            .description(
+                "The karma of the thing here, or the top and bottom 5. \
+                 Give karma with nick++ or (some thing)++, take it with --",
+            )]
+    }
+
+    fn requirements(&self) -> Vec<Requirement> {
+        // the scores
+        vec![Requirement::Database]
+    }
+}
+
+impl Karma {
+    /// Unless the giver already gave karma to that thing recently
+    fn may_give(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        giver: &str,
+        thing: &str,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let key = (
+            network.to_string(),
+            casemapping.normalize(channel),
+            casemapping.normalize(giver),
+            casemapping.normalize(&thing.to_lowercase()),
+        );
+        let mut given = self.given.lock().expect("karma lock");
+        given.retain(|_, at| now - *at < self.cooldown);
+        if given.contains_key(&key) {
+            return false;
+        }
+        given.insert(key, now);
+        true
+    }
+}
+
+fn in_msg(
+    plugin: &Karma,
+    msg: &Message,
+    casemapping: CaseMapping,
+    now: DateTime<Utc>,
+) -> Result<Option<Outbound>> {
+    let (channel, privmsg) = match &msg.command {
+        Command::PRIVMSG(target, privmsg) if target.is_channel_name() => (target, privmsg),
+        Command::PRIVMSG(_, privmsg) if parser::command("karma")(privmsg).is_ok() => {
+            let response_target = msg.response_target().unwrap_or_default();
+            return Ok(Some(Outbound::reply(
+                response_target,
+                "The karma is per channel, ask in one",
+            )));
+        }
+        _ => return Ok(None),
+    };
+    let network = network(msg).unwrap_or_default();
+    if let Ok((_, (args, mb_target))) = parser::command("karma")(privmsg) {
+        let text = query(plugin, network, casemapping, channel, args)?;
+        return Ok(Some(Outbound::reply(
+            channel,
+            with_target(&text, &mb_target),
+        )));
+    }
+
+    let giver = msg.source_nickname().unwrap_or_default();
+    let mut reply = None;
+    for (target, delta) in parse_karma(privmsg) {
+        let thing = match target {
+            Target::Nick(nick) if !plugin.members.is_member(network, channel, nick) => continue,
+            Target::Nick(thing) | Target::Phrase(thing) => thing,
+        };
+        if casemapping.eq_ignore_case(thing, giver) {
+            reply = Some(format!(
+                "{giver}: nice try, karma is something the others give you"
+            ));
+            continue;
+        }
+        if !plugin.may_give(network, casemapping, channel, giver, thing, now) {
+            log::debug!("{giver} already gave karma to {thing} in {channel} recently");
+            continue;
+        }
+        let score = plugin
+            .scores
+            .add(network, casemapping, channel, thing, delta)?;
+        log::debug!("{giver} gave {delta} karma to {thing} in {channel}, now {score}");
+    }
+    Ok(reply.map(|text| Outbound::reply(channel, text)))
+}
+
+/// `λkarma thing`, or the top and the bottom of the channel when bare
+fn query(
+    plugin: &Karma,
+    network: &str,
+    casemapping: CaseMapping,
+    channel: &str,
+    thing: &str,
+) -> Result<String> {
+    if !thing.is_empty() {
+        let thing = thing
+            .strip_prefix('(')
+            .and_then(|thing| thing.strip_suffix(')'))
+            .unwrap_or(thing)
+            .trim();
+        return Ok(
+            match plugin.scores.of(network, casemapping, channel, thing)? {
+                Some(Score { shown, score }) => format!("{shown} has a karma of {score}"),
+                None => format!("{thing} has no karma yet"),
+            },
+        );
+    }
+    let ranked = |best| -> Result<String> {
+        let scores = plugin
+            .scores
+            .ranked(network, casemapping, channel, best, RANKED)?;
+        Ok(scores
+            .iter()
+            .map(|s| format!("{} ({})", s.shown, s.score))
+            .collect::<Vec<_>>()
+            .join(", "))
+    };
+    Ok(match (ranked(true)?, ranked(false)?) {
+        (top, bottom) if top.is_empty() && bottom.is_empty() => "No karma here yet".to_string(),
+        (top, bottom) if bottom.is_empty() => format!("Top: {top}"),
+        (top, bottom) if top.is_empty() => format!("Bottom: {bottom}"),
+        (top, bottom) => format!("Top: {top} − Bottom: {bottom}"),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use plugin_core::utils::network::set_network;
+    use plugin_core::Database;
+    use pretty_assertions::assert_eq;
+
+    fn at(min: i64) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2025-03-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+            + Duration::minutes(min)
+    }
+
+    fn message(nick: &str, command: &str, args: Vec<&str>) -> Message {
+        let source = format!("{nick}!~{nick}@localhost");
+        let mut msg = Message::new(Some(&source), command, args).unwrap();
+        set_network(&mut msg, "libera");
+        msg
+    }
+
+    /// With alice, bob and héloïse in #rust
+    fn karma() -> Karma {
+        let members = Members::default();
+        for nick in ["golem", "alice", "bob", "héloïse"] {
+            members.on_message("libera", "golem", &message(nick, "JOIN", vec!["#rust"]));
+        }
+        Karma {
+            scores: Scores::load(Database::in_memory().unwrap()).unwrap(),
+            members: Arc::new(members),
+            given: Mutex::new(HashMap::new()),
+            cooldown: Duration::seconds(DEFAULT_COOLDOWN_SECS as i64),
+        }
+    }
+
+    fn say(plugin: &Karma, nick: &str, text: &str, min: i64) -> Option<String> {
+        let msg = message(nick, "PRIVMSG", vec!["#rust", text]);
+        match in_msg(plugin, &msg, CaseMapping::Rfc1459, at(min)).unwrap() {
+            Some(Outbound::Reply { text, .. }) => Some(text),
+            None => None,
+            other => panic!("unexpected reply to {text:?}: {other:?}"),
+        }
+    }
+
+    fn karma_of(plugin: &Karma, thing: &str) -> String {
+        say(plugin, "dave", &format!("λkarma {thing}"), 0).unwrap()
+    }
+
+    #[test]
+    async fn test_parse_karma() {
+        use Target::*;
+        for (input, expected) in [
+            ("alice++", vec![(Nick("alice"), 1)]),
+            (
+                "thanks alice++, bob--!",
+                vec![(Nick("alice"), 1), (Nick("bob"), -1)],
+            ),
+            (
+                "I like C++ more than C--",
+                vec![(Nick("C"), 1), (Nick("C"), -1)],
+            ),
+            ("c++11 or c++/cli", vec![]),
+            ("for (i = 0; i < n; i++) {", vec![(Nick("i"), 1)]),
+            ("x+++y a+++ ++ -- 2++", vec![]),
+            (
+                "(the borrow checker)++ and (  php  )--",
+                vec![(Phrase("the borrow checker"), 1), (Phrase("php"), -1)],
+            ),
+            ("(not closed ++", vec![]),
+            ("(nope)++x ()++", vec![]),
+            (
+                "héloïse++ [m]atrix--",
+                vec![(Nick("héloïse"), 1), (Nick("[m]atrix"), -1)],
+            ),
+            (
+                "i--; but -- and a-b-- ok",
+                vec![(Nick("i"), -1), (Nick("a-b"), -1)],
+            ),
+        ] {
+            assert_eq!(parse_karma(input), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    async fn test_karma() {
+        let plugin = karma();
+        assert_eq!(say(&plugin, "alice", "bob++ great fix", 0), None);
+        assert_eq!(
+            say(&plugin, "dave", "Héloïse++ (the borrow checker)--", 0),
+            None
+        );
+        assert_eq!(
+            say(&plugin, "alice", "C++ is fine, carol++ too", 0),
+            None,
+            "not members"
+        );
+        assert_eq!(karma_of(&plugin, "BOB"), "bob has a karma of 1");
+        assert_eq!(karma_of(&plugin, "héloïse"), "Héloïse has a karma of 1");
+        assert_eq!(
+            karma_of(&plugin, "(The Borrow Checker)"),
+            "the borrow checker has a karma of -1"
+        );
+        assert_eq!(karma_of(&plugin, "C"), "C has no karma yet");
+        assert_eq!(karma_of(&plugin, "carol"), "carol has no karma yet");
+        assert_eq!(
+            say(&plugin, "dave", "λkarma", 0).unwrap(),
+            "Top: Héloïse (1), bob (1) − Bottom: the borrow checker (-1)"
+        );
+        assert_eq!(
+            say(&plugin, "dave", "λkarma bob > alice", 0).unwrap(),
+            "alice: bob has a karma of 1"
+        );
+    }
+
+    #[test]
+    async fn test_self_karma() {
+        let plugin = karma();
+        assert_eq!(
+            say(&plugin, "alice", "ALICE++ bob++", 0).unwrap(),
+            "alice: nice try, karma is something the others give you"
+        );
+        assert_eq!(
+            say(&plugin, "alice", "(alice)++", 1).unwrap(),
+            "alice: nice try, karma is something the others give you"
+        );
+        assert_eq!(karma_of(&plugin, "alice"), "alice has no karma yet");
+        assert_eq!(
+            karma_of(&plugin, "bob"),
+            "bob has a karma of 1",
+            "still given"
+        );
+    }
+
+    #[test]
+    async fn test_rate_limit() {
+        let plugin = karma();
+        say(&plugin, "alice", "bob++", 0);
+        say(&plugin, "alice", "bob++ bob++", 5);
+        say(&plugin, "alice", "Bob--", 9);
+        assert_eq!(
+            karma_of(&plugin, "bob"),
+            "bob has a karma of 1",
+            "once per 10 minutes"
+        );
+        say(&plugin, "dave", "bob++", 9);
+        assert_eq!(
+            karma_of(&plugin, "bob"),
+            "bob has a karma of 2",
+            "per giver"
+        );
+        say(&plugin, "alice", "héloïse++", 9);
+        assert_eq!(
+            karma_of(&plugin, "héloïse"),
+            "héloïse has a karma of 1",
+            "per target"
+        );
+        say(&plugin, "alice", "bob--", 10);
+        assert_eq!(
+            karma_of(&plugin, "bob"),
+            "bob has a karma of 1",
+            "after the cooldown"
+        );
+    }
+
+    #[test]
+    async fn test_in_private() {
+        let plugin = karma();
+        let msg = message("alice", "PRIVMSG", vec!["golem", "λkarma bob"]);
+        assert_eq!(
+            in_msg(&plugin, &msg, CaseMapping::Rfc1459, at(0)).unwrap(),
+            Some(Outbound::reply(
+                "alice",
+                "The karma is per channel, ask in one"
+            ))
+        );
+        let msg = message("alice", "PRIVMSG", vec!["golem", "bob++"]);
+        assert_eq!(
+            in_msg(&plugin, &msg, CaseMapping::Rfc1459, at(0)).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+use plugin_core::{Database, Result};
+
+use crate::caps::CaseMapping;
+
+/// The tables of the karma plugin in the shared database, see
+/// `plugin_core::ensure_schema`
+const MIGRATIONS: &[&str] = &[
+    // thing and channel are normalized, thing in lowercase and with the
+    // casemapping of the network. shown is the thing as last given karma.
+    "CREATE TABLE karma_scores (
+        network TEXT NOT NULL,
+        channel TEXT NOT NULL,
+        thing TEXT NOT NULL,
+        shown TEXT NOT NULL,
+        score INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY (network, channel, thing)
+    );",
+];
+
+#[derive(Debug, Clone, PartialEq, QueryableByName)]
+pub struct Score {
+    #[sql_type = "Text"]
+    pub shown: String,
+    #[sql_type = "BigInt"]
+    pub score: i64,
+}
+
+/// The karma of everything, per channel
+pub struct Scores {
+    db: Database,
+}
+
+impl Scores {
+    /// Create the table if needed
+    pub fn load(db: Database) -> Result<Self> {
+        plugin_core::ensure_schema(&db, "karma", MIGRATIONS)?;
+        Ok(Scores { db })
+    }
+
+    /// Adds the delta to the karma of the thing in that channel, and gives
+    /// the new one
+    pub fn add(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        thing: &str,
+        delta: i64,
+    ) -> Result<i64> {
+        let channel = casemapping.normalize(channel);
+        let key = normalize(casemapping, thing);
+        let score = self.db.with_connection(|conn| {
+            conn.transaction(|| {
+                diesel::sql_query(
+                    "INSERT OR IGNORE INTO karma_scores (network, channel, thing, shown) \
+                     VALUES (?, ?, ?, ?)",
+                )
+                .bind::<Text, _>(network)
+                .bind::<Text, _>(&channel)
+                .bind::<Text, _>(&key)
+                .bind::<Text, _>(thing)
+                .execute(conn)?;
+                diesel::sql_query(
+                    "UPDATE karma_scores SET score = score + ?, shown = ? \
+                     WHERE network = ? AND channel = ? AND thing = ?",
+                )
+                .bind::<BigInt, _>(delta)
+                .bind::<Text, _>(thing)
+                .bind::<Text, _>(network)
+                .bind::<Text, _>(&channel)
+                .bind::<Text, _>(&key)
+                .execute(conn)?;
+                diesel::sql_query(
+                    "SELECT shown, score FROM karma_scores \
+                     WHERE network = ? AND channel = ? AND thing = ?",
+                )
+                .bind::<Text, _>(network)
+                .bind::<Text, _>(&channel)
+                .bind::<Text, _>(&key)
+                .get_result::<Score>(conn)
+            })
+        })?;
+        Ok(score.score)
+    }
+
+    /// None when the thing never got any karma in that channel
+    pub fn of(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        thing: &str,
+    ) -> Result<Option<Score>> {
+        let scores = self.db.with_connection(|conn| {
+            diesel::sql_query(
+                "SELECT shown, score FROM karma_scores \
+                 WHERE network = ? AND channel = ? AND thing = ?",
+            )
+            .bind::<Text, _>(network)
+            .bind::<Text, _>(casemapping.normalize(channel))
+            .bind::<Text, _>(normalize(casemapping, thing))
+            .load::<Score>(conn)
+        })?;
+        Ok(scores.into_iter().next())
+    }
+
+    /// The best positive scores of the channel if `best`, else the worst
+    /// negative ones, the most extreme first
+    pub fn ranked(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        best: bool,
+        count: usize,
+    ) -> Result<Vec<Score>> {
+        let query = if best {
+            "SELECT shown, score FROM karma_scores \
+             WHERE network = ? AND channel = ? AND score > 0 \
+             ORDER BY score DESC, shown LIMIT ?"
+        } else {
+            "SELECT shown, score FROM karma_scores \
+             WHERE network = ? AND channel = ? AND score < 0 \
+             ORDER BY score ASC, shown LIMIT ?"
+        };
+        self.db.with_connection(|conn| {
+            diesel::sql_query(query)
+                .bind::<Text, _>(network)
+                .bind::<Text, _>(casemapping.normalize(channel))
+                .bind::<BigInt, _>(count as i64)
+                .load::<Score>(conn)
+        })
+    }
+}
+
+/// `Rust` and `rust` are the same thing, and `[m]atrix` and `{m}atrix` too
+/// in rfc1459
+fn normalize(casemapping: CaseMapping, thing: &str) -> String {
+    casemapping.normalize(&thing.to_lowercase())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const RFC1459: CaseMapping = CaseMapping::Rfc1459;
+
+    fn score(shown: &str, score: i64) -> Score {
+        Score {
+            shown: shown.to_string(),
+            score,
+        }
+    }
+
+    #[test]
+    async fn test_scores() {
+        let scores = Scores::load(Database::in_memory().unwrap()).unwrap();
+        let add = |channel, thing, delta| {
+            scores
+                .add("libera", RFC1459, channel, thing, delta)
+                .unwrap()
+        };
+        assert_eq!(add("#rust", "alice", 1), 1);
+        assert_eq!(add("#Rust", "Alice", 1), 2, "case insensitive");
+        assert_eq!(add("#ocaml", "alice", -1), -1, "per channel");
+        assert_eq!(add("#rust", "Élodie", 1), 1);
+        assert_eq!(add("#rust", "élodie", 1), 2, "in unicode too");
+        assert_eq!(add("#rust", "[m]atrix", -1), -1);
+        assert_eq!(add("#rust", "{m}atrix", -2), -3, "in rfc1459");
+        add("#rust", "the borrow checker", -1);
+        add("#rust", "bob", 1);
+        add("#rust", "bob", -1);
+
+        assert_eq!(
+            scores.of("libera", RFC1459, "#RUST", "ALICE").unwrap(),
+            Some(score("Alice", 2))
+        );
+        assert_eq!(
+            scores.of("libera", RFC1459, "#rust", "bob").unwrap(),
+            Some(score("bob", 0))
+        );
+        assert_eq!(scores.of("libera", RFC1459, "#rust", "dave").unwrap(), None);
+        assert_eq!(scores.of("oftc", RFC1459, "#rust", "alice").unwrap(), None);
+
+        assert_eq!(
+            scores.ranked("libera", RFC1459, "#rust", true, 5).unwrap(),
+            vec![score("Alice", 2), score("élodie", 2)]
+        );
+        assert_eq!(
+            scores.ranked("libera", RFC1459, "#rust", false, 1).unwrap(),
+            vec![score("{m}atrix", -3)]
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use plugin_core::{Database, Result};
+
+use crate::caps::CaseMapping;
+
+/// The tables of the meteo plugin in the shared database, see
+/// `plugin_core::ensure_schema`
+const MIGRATIONS: &[&str] = &[
+    // nick is normalized with the casemapping of the network
+    "CREATE TABLE meteo_cities (
+        network TEXT NOT NULL,
+        nick TEXT NOT NULL,
+        city TEXT NOT NULL,
+        PRIMARY KEY (network, nick)
+    );",
+];
+
+#[derive(QueryableByName)]
+struct City {
+    #[sql_type = "Text"]
+    city: String,
+}
+
+/// The default city of each nick, for a bare λmeteo
+pub struct Cities {
+    db: Database,
+}
+
+impl Cities {
+    /// Create the table if needed
+    pub fn load(db: Database) -> Result<Self> {
+        plugin_core::ensure_schema(&db, "meteo", MIGRATIONS)?;
+        Ok(Cities { db })
+    }
+
+    /// Replaces the default city of the nick
+    pub fn set(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        nick: &str,
+        city: &str,
+    ) -> Result<()> {
+        self.db.with_connection(|conn| {
+            diesel::sql_query(
+                "INSERT OR REPLACE INTO meteo_cities (network, nick, city) VALUES (?, ?, ?)",
+            )
+            .bind::<Text, _>(network)
+            .bind::<Text, _>(casemapping.normalize(nick))
+            .bind::<Text, _>(city)
+            .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// None when the nick never set one
+    pub fn get(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        nick: &str,
+    ) -> Result<Option<String>> {
+        let cities = self.db.with_connection(|conn| {
+            diesel::sql_query("SELECT city FROM meteo_cities WHERE network = ? AND nick = ?")
+                .bind::<Text, _>(network)
+                .bind::<Text, _>(casemapping.normalize(nick))
+                .load::<City>(conn)
+        })?;
+        Ok(cities.into_iter().next().map(|c| c.city))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const RFC1459: CaseMapping = CaseMapping::Rfc1459;
+
+    #[test]
+    async fn test_persistence() {
+        let db = Database::in_memory().unwrap();
+        let cities = Cities::load(db.clone()).unwrap();
+        assert_eq!(cities.get("libera", RFC1459, "alice").unwrap(), None);
+        cities.set("libera", RFC1459, "Alice", "Paris").unwrap();
+        cities.set("libera", RFC1459, "[bob]", "Brest").unwrap();
+        cities.set("libera", RFC1459, "alice", "Lyon").unwrap();
+
+        let cities = Cities::load(db).unwrap();
+        assert_eq!(
+            cities.get("libera", RFC1459, "ALICE").unwrap(),
+            Some("Lyon".to_string()),
+            "the last one"
+        );
+        assert_eq!(
+            cities.get("libera", RFC1459, "{bob}").unwrap(),
+            Some("Brest".to_string())
+        );
+        assert_eq!(cities.get("oftc", RFC1459, "alice").unwrap(), None);
+    }
+}
@@ -0,0 +1,6 @@
+mod cities;
+mod openmeteo;
+mod plugin;
+mod report;
+
+pub use plugin::Meteo;
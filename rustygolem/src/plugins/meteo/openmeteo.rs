@@ -0,0 +1,282 @@
+use anyhow::Context;
+use reqwest::Client;
+use serde::Deserialize;
+
+/// https://open-meteo.com/en/docs/geocoding-api, keyless
+const GEOCODING_URL: &str = "https://geocoding-api.open-meteo.com/v1/search";
+/// https://open-meteo.com/en/docs, keyless
+const FORECAST_URL: &str = "https://api.open-meteo.com/v1/forecast";
+
+/// Among the homonyms, the most populous is taken
+const CANDIDATES: u32 = 10;
+
+/// A city as geocoded
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Place {
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    #[serde(default)]
+    pub country: Option<String>,
+    #[serde(default)]
+    pub population: Option<u64>,
+}
+
+impl Place {
+    /// Like `Lyon (France)`
+    pub fn describe(&self) -> String {
+        match &self.country {
+            Some(country) => format!("{} ({country})", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// The geocoding response, without any results when nothing matches
+#[derive(Debug, Deserialize)]
+struct Geocoding {
+    #[serde(default)]
+    results: Vec<Place>,
+}
+
+/// The most populous of the matches, the first one among the ones without
+/// a known population
+fn pick(geocoding: Geocoding) -> Option<Place> {
+    let mut best: Option<Place> = None;
+    for place in geocoding.results {
+        match &best {
+            Some(b) if b.population.unwrap_or(0) >= place.population.unwrap_or(0) => (),
+            _ => best = Some(place),
+        }
+    }
+    best
+}
+
+/// The forecast response, in °C and km/h, in the timezone of the place
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Forecast {
+    pub current: Current,
+    pub daily: Daily,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Current {
+    pub temperature_2m: f64,
+    pub apparent_temperature: f64,
+    /// in %
+    pub relative_humidity_2m: f64,
+    /// WMO code
+    pub weather_code: u8,
+    pub wind_speed_10m: f64,
+    /// where the wind comes from, in degrees
+    pub wind_direction_10m: f64,
+}
+
+/// By day, today first
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Daily {
+    pub weather_code: Vec<u8>,
+    pub temperature_2m_min: Vec<f64>,
+    pub temperature_2m_max: Vec<f64>,
+    /// in %, unknown far away or in some places
+    pub precipitation_probability_max: Vec<Option<f64>>,
+    pub wind_speed_10m_max: Vec<f64>,
+}
+
+/// The forecast of a day
+#[derive(Debug, Clone, PartialEq)]
+pub struct Day {
+    pub weather_code: u8,
+    pub min: f64,
+    pub max: f64,
+    pub precipitation_probability: Option<f64>,
+    pub max_wind_speed: f64,
+}
+
+impl Forecast {
+    /// 0 for today, 1 for tomorrow
+    pub fn day(&self, index: usize) -> Option<Day> {
+        let daily = &self.daily;
+        Some(Day {
+            weather_code: *daily.weather_code.get(index)?,
+            min: *daily.temperature_2m_min.get(index)?,
+            max: *daily.temperature_2m_max.get(index)?,
+            precipitation_probability: daily
+                .precipitation_probability_max
+                .get(index)
+                .copied()
+                .flatten(),
+            max_wind_speed: *daily.wind_speed_10m_max.get(index)?,
+        })
+    }
+}
+
+/// None when nothing is called like that
+pub async fn geocode(client: &Client, city: &str) -> anyhow::Result<Option<Place>> {
+    let count = CANDIDATES.to_string();
+    let geocoding = client
+        .get(GEOCODING_URL)
+        .query(&[
+            ("name", city),
+            ("count", count.as_str()),
+            ("language", "fr"),
+            ("format", "json"),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Geocoding>()
+        .await
+        .with_context(|| format!("Error while geocoding {city}"))?;
+    Ok(pick(geocoding))
+}
+
+/// The current conditions, and the forecast of today and tomorrow
+pub async fn forecast(client: &Client, place: &Place) -> anyhow::Result<Forecast> {
+    client
+        .get(FORECAST_URL)
+        .query(&[
+            ("latitude", place.latitude.to_string()),
+            ("longitude", place.longitude.to_string()),
+            (
+                "current",
+                "temperature_2m,apparent_temperature,relative_humidity_2m,weather_code,\
+                 wind_speed_10m,wind_direction_10m"
+                    .to_string(),
+            ),
+            (
+                "daily",
+                "weather_code,temperature_2m_min,temperature_2m_max,\
+                 precipitation_probability_max,wind_speed_10m_max"
+                    .to_string(),
+            ),
+            ("wind_speed_unit", "kmh".to_string()),
+            ("timezone", "auto".to_string()),
+            ("forecast_days", "2".to_string()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Forecast>()
+        .await
+        .with_context(|| format!("Error while fetching the forecast of {}", place.name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn place(name: &str, country: &str, population: Option<u64>) -> Place {
+        Place {
+            name: name.to_string(),
+            latitude: 0.0,
+            longitude: 0.0,
+            country: Some(country.to_string()),
+            population,
+        }
+    }
+
+    #[test]
+    async fn test_pick() {
+        let json = r#"{
+            "results": [
+                {"id": 6616213, "name": "Paris", "latitude": 33.66094, "longitude": -95.55551,
+                 "country_code": "US", "country": "États-Unis", "admin1": "Texas", "population": 24782},
+                {"id": 2988507, "name": "Paris", "latitude": 48.85341, "longitude": 2.3488,
+                 "country_code": "FR", "country": "France", "admin1": "Île-de-France",
+                 "population": 2138551, "timezone": "Europe/Paris"},
+                {"id": 4717560, "name": "Paris", "latitude": 39.61115, "longitude": -87.69614,
+                 "country_code": "US", "country": "États-Unis", "admin1": "Illinois"}
+            ],
+            "generationtime_ms": 0.9
+        }"#;
+        assert_eq!(
+            pick(serde_json::from_str(json).unwrap()),
+            Some(Place {
+                name: "Paris".to_string(),
+                latitude: 48.85341,
+                longitude: 2.3488,
+                country: Some("France".to_string()),
+                population: Some(2138551),
+            }),
+            "the most populous"
+        );
+        let unknown = r#"{"generationtime_ms": 0.4}"#;
+        assert_eq!(pick(serde_json::from_str(unknown).unwrap()), None);
+
+        let without_population = Geocoding {
+            results: vec![
+                place("Saint-Martin", "France", None),
+                place("Saint-Martin", "Suisse", None),
+            ],
+        };
+        assert_eq!(
+            pick(without_population),
+            Some(place("Saint-Martin", "France", None)),
+            "the first one"
+        );
+        let mixed = Geocoding {
+            results: vec![
+                place("Valence", "France", None),
+                place("Valencia", "Espagne", Some(800_000)),
+                place("Valence", "France", Some(64_000)),
+            ],
+        };
+        assert_eq!(
+            pick(mixed),
+            Some(place("Valencia", "Espagne", Some(800_000)))
+        );
+    }
+
+    #[test]
+    async fn test_describe_place() {
+        assert_eq!(place("Lyon", "France", None).describe(), "Lyon (France)");
+        let nowhere = Place {
+            country: None,
+            ..place("Null Island", "", None)
+        };
+        assert_eq!(nowhere.describe(), "Null Island");
+    }
+
+    #[test]
+    async fn test_forecast() {
+        let json = r#"{
+            "latitude": 45.76, "longitude": 4.84, "timezone": "Europe/Paris",
+            "current_units": {"temperature_2m": "°C", "wind_speed_10m": "km/h"},
+            "current": {
+                "time": "2025-03-01T15:00", "interval": 900, "temperature_2m": 12.3,
+                "apparent_temperature": 10.1, "relative_humidity_2m": 65, "weather_code": 3,
+                "wind_speed_10m": 14.8, "wind_direction_10m": 200
+            },
+            "daily": {
+                "time": ["2025-03-01", "2025-03-02"], "weather_code": [3, 61],
+                "temperature_2m_min": [4.1, 6.3], "temperature_2m_max": [14.2, 11.0],
+                "precipitation_probability_max": [10, null], "wind_speed_10m_max": [20.5, 30.1]
+            }
+        }"#;
+        let forecast: Forecast = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            forecast.current,
+            Current {
+                temperature_2m: 12.3,
+                apparent_temperature: 10.1,
+                relative_humidity_2m: 65.0,
+                weather_code: 3,
+                wind_speed_10m: 14.8,
+                wind_direction_10m: 200.0,
+            }
+        );
+        assert_eq!(
+            forecast.day(1),
+            Some(Day {
+                weather_code: 61,
+                min: 6.3,
+                max: 11.0,
+                precipitation_probability: None,
+                max_wind_speed: 30.1,
+            })
+        );
+        assert_eq!(forecast.day(2), None);
+    }
+}
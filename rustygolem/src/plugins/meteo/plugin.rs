@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use irc::proto::{Command, Message};
+use plugin_core::utils::network::network;
+use plugin_core::utils::parser;
+use plugin_core::{CommandHelp, Error, Initialised, MsgCtx, Outbound, Plugin, Requirement, Result};
+use reqwest::Client;
+use serde::Deserialize;
+
+use super::cities::Cities;
+use super::openmeteo::{self, Forecast, Place};
+use super::report;
+use crate::caps::CaseMapping;
+use crate::utils::messages::with_target;
+
+/// Seconds a forecast is reused for the same city, unless the config says
+/// otherwise
+pub const DEFAULT_CACHE_SECS: u64 = 600;
+
+/// The `meteo` section of the golem config
+#[derive(Deserialize)]
+struct Settings {
+    /// before asking Open-Meteo again about the same city
+    #[serde(default = "default_cache_secs")]
+    cache_secs: u64,
+}
+
+fn default_cache_secs() -> u64 {
+    DEFAULT_CACHE_SECS
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            cache_secs: default_cache_secs(),
+        }
+    }
+}
+
+impl Settings {
+    fn load(config: &plugin_core::Config) -> Result<Self> {
+        Ok(config.plugin_section("meteo")?.unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MeteoCommand<'a> {
+    /// `λmeteo set Lyon`
+    Set(&'a str),
+    /// `λmeteo [Lyon] [demain]`, the default city of the nick when none is
+    /// given
+    Show {
+        city: Option<&'a str>,
+        tomorrow: bool,
+    },
+}
+
+/// None when `set` has no city
+fn parse_command(args: &str) -> Option<MeteoCommand<'_>> {
+    let args = args.trim();
+    let (first, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    if first.eq_ignore_ascii_case("set") {
+        let city = rest.trim();
+        return if city.is_empty() {
+            None
+        } else {
+            Some(MeteoCommand::Set(city))
+        };
+    }
+    let (city, tomorrow) = match args.rsplit_once(char::is_whitespace) {
+        Some((city, last)) if last.eq_ignore_ascii_case("demain") => (city.trim(), true),
+        _ if args.eq_ignore_ascii_case("demain") => ("", true),
+        _ => (args, false),
+    };
+    Some(MeteoCommand::Show {
+        city: Some(city).filter(|city| !city.is_empty()),
+        tomorrow,
+    })
+}
+
+pub struct Meteo {
+    client: Client,
+    cities: Cities,
+    /// by city as asked, in lowercase, with when it was fetched
+    cache: Mutex<HashMap<String, (Place, Forecast, Instant)>>,
+    ttl: Duration,
+}
+
+#[async_trait]
+impl Plugin for Meteo {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        Settings::load(config)?;
+        config.check_database("meteo")?;
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let settings = Settings::load(config)?;
+        let db = config.require_database("meteo")?;
+        Ok(Initialised::from(Meteo {
+            client: config.http_client(),
+            cities: Cities::load(db)?,
+            cache: Mutex::new(HashMap::new()),
+            ttl: Duration::from_secs(settings.cache_secs),
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "meteo"
+    }
+
+    async fn in_message_ctx(&self, ctx: &MsgCtx, msg: &Message) -> Result<Option<Outbound>> {
+        self.in_msg(msg, ctx.casemapping, Instant::now()).await
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("meteo")
+                .usage("meteo [ville] [demain] [> nick]")
+                .description(
+                    "La météo de la ville, ou de ta ville par défaut, maintenant ou demain",
+                ),
+            CommandHelp::new("meteo set")
+                .usage("meteo set <ville>")
+                .description("Ta ville par défaut, pour un λmeteo sans ville"),
+        ]
+    }
+
+    fn requirements(&self) -> Vec<Requirement> {
+        // the default city of each nick
+        vec![Requirement::Database]
+    }
+}
+
+impl Meteo {
+    async fn in_msg(
+        &self,
+        msg: &Message,
+        casemapping: CaseMapping,
+        now: Instant,
+    ) -> Result<Option<Outbound>> {
+        let response_target = match msg.response_target() {
+            Some(target) => target.to_string(),
+            None => return Ok(None),
+        };
+        let privmsg = match &msg.command {
+            Command::PRIVMSG(_, privmsg) => privmsg,
+            _ => return Ok(None),
+        };
+        let (args, mb_target) = match parser::command("meteo")(privmsg) {
+            Ok((_, command)) => command,
+            Err(_) => return Ok(None),
+        };
+        let network = network(msg).unwrap_or_default();
+        let nick = msg.source_nickname().unwrap_or_default();
+
+        let text = match parse_command(args) {
+            None => "Usage: λmeteo set <ville>".to_string(),
+            Some(MeteoCommand::Set(city)) => match self.lookup(city, now).await? {
+                None => format!("Je ne connais pas {city}"),
+                Some((place, _)) => {
+                    self.cities.set(network, casemapping, nick, city)?;
+                    format!("Ta ville par défaut est maintenant {}", place.describe())
+                }
+            },
+            Some(MeteoCommand::Show { city, tomorrow }) => {
+                let city =
+                    match city {
+                        Some(city) => city.to_string(),
+                        None => match self.cities.get(network, casemapping, nick)? {
+                            Some(city) => city,
+                            None => return Ok(Some(Outbound::reply(
+                                response_target,
+                                "Pas de ville par défaut, choisis-en une avec λmeteo set <ville>",
+                            ))),
+                        },
+                    };
+                match self.lookup(&city, now).await? {
+                    None => format!("Je ne connais pas {city}"),
+                    Some((place, forecast)) if tomorrow => match forecast.day(1) {
+                        Some(day) => report::tomorrow(&place, &day),
+                        None => format!("Pas de prévision pour demain à {}", place.describe()),
+                    },
+                    Some((place, forecast)) => report::current(&place, &forecast.current),
+                }
+            }
+        };
+        Ok(Some(Outbound::reply(
+            response_target,
+            with_target(&text, &mb_target),
+        )))
+    }
+
+    /// The place and its forecast, from the cache when fetched recently,
+    /// None when there's no such city
+    async fn lookup(&self, city: &str, now: Instant) -> Result<Option<(Place, Forecast)>> {
+        let key = city.to_lowercase();
+        if let Some(cached) = self.cached(&key, now) {
+            return Ok(Some(cached));
+        }
+        let fetched = self.fetch(city).await.map_err(|err| {
+            log::error!("Cannot fetch the weather of {city}: {err:#}");
+            Error::user_visible("La météo est indisponible pour le moment")
+        })?;
+        if let Some((place, forecast)) = &fetched {
+            let mut cache = self.cache.lock().expect("meteo cache lock");
+            cache.insert(key, (place.clone(), forecast.clone(), now));
+        }
+        Ok(fetched)
+    }
+
+    /// From Open-Meteo, None when there's no such city
+    async fn fetch(&self, city: &str) -> anyhow::Result<Option<(Place, Forecast)>> {
+        let place = match openmeteo::geocode(&self.client, city).await? {
+            Some(place) => place,
+            None => return Ok(None),
+        };
+        let forecast = openmeteo::forecast(&self.client, &place).await?;
+        Ok(Some((place, forecast)))
+    }
+
+    /// Evicts the outdated forecasts on the way
+    fn cached(&self, key: &str, now: Instant) -> Option<(Place, Forecast)> {
+        let mut cache = self.cache.lock().expect("meteo cache lock");
+        cache.retain(|_, (_, _, at)| now.saturating_duration_since(*at) < self.ttl);
+        cache
+            .get(key)
+            .map(|(place, forecast, _)| (place.clone(), forecast.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::plugins::meteo::openmeteo::{Current, Daily};
+    use plugin_core::utils::network::set_network;
+    use plugin_core::Database;
+    use pretty_assertions::assert_eq;
+
+    fn lyon() -> (Place, Forecast) {
+        let place = Place {
+            name: "Lyon".to_string(),
+            latitude: 45.75,
+            longitude: 4.85,
+            country: Some("France".to_string()),
+            population: Some(522_969),
+        };
+        let forecast = Forecast {
+            current: Current {
+                temperature_2m: 12.3,
+                apparent_temperature: 10.1,
+                relative_humidity_2m: 65.0,
+                weather_code: 3,
+                wind_speed_10m: 14.8,
+                wind_direction_10m: 200.0,
+            },
+            daily: Daily {
+                weather_code: vec![3, 61],
+                temperature_2m_min: vec![4.1, 6.3],
+                temperature_2m_max: vec![14.2, 11.0],
+                precipitation_probability_max: vec![Some(10.0), Some(80.0)],
+                wind_speed_10m_max: vec![20.5, 30.1],
+            },
+        };
+        (place, forecast)
+    }
+
+    /// With Lyon already fetched, so that nothing is asked to Open-Meteo
+    fn meteo(now: Instant) -> Meteo {
+        let (place, forecast) = lyon();
+        Meteo {
+            client: Client::new(),
+            cities: Cities::load(Database::in_memory().unwrap()).unwrap(),
+            cache: Mutex::new(HashMap::from([(
+                "lyon".to_string(),
+                (place, forecast, now),
+            )])),
+            ttl: Duration::from_secs(DEFAULT_CACHE_SECS),
+        }
+    }
+
+    async fn say(plugin: &Meteo, nick: &str, text: &str, now: Instant) -> Option<String> {
+        let source = format!("{nick}!~{nick}@localhost");
+        let mut msg = Message::new(Some(&source), "PRIVMSG", vec!["#lyon", text]).unwrap();
+        set_network(&mut msg, "libera");
+        match plugin
+            .in_msg(&msg, CaseMapping::Rfc1459, now)
+            .await
+            .unwrap()
+        {
+            Some(Outbound::Reply { text, .. }) => Some(text),
+            None => None,
+            other => panic!("unexpected reply to {text:?}: {other:?}"),
+        }
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        for (args, expected) in [
+            (
+                "",
+                Some(MeteoCommand::Show {
+                    city: None,
+                    tomorrow: false,
+                }),
+            ),
+            (
+                "Lyon",
+                Some(MeteoCommand::Show {
+                    city: Some("Lyon"),
+                    tomorrow: false,
+                }),
+            ),
+            (
+                "Saint-Étienne  demain",
+                Some(MeteoCommand::Show {
+                    city: Some("Saint-Étienne"),
+                    tomorrow: true,
+                }),
+            ),
+            (
+                "New York",
+                Some(MeteoCommand::Show {
+                    city: Some("New York"),
+                    tomorrow: false,
+                }),
+            ),
+            (
+                "Demain",
+                Some(MeteoCommand::Show {
+                    city: None,
+                    tomorrow: true,
+                }),
+            ),
+            ("set Lyon", Some(MeteoCommand::Set("Lyon"))),
+            ("SET  La Rochelle ", Some(MeteoCommand::Set("La Rochelle"))),
+            ("set", None),
+            (
+                "Sète",
+                Some(MeteoCommand::Show {
+                    city: Some("Sète"),
+                    tomorrow: false,
+                }),
+            ),
+        ] {
+            assert_eq!(parse_command(args), expected, "{args:?}");
+        }
+    }
+
+    #[test]
+    async fn test_meteo() {
+        let now = Instant::now();
+        let plugin = meteo(now);
+        let current = "Lyon (France) : 12 °C (ressenti 10 °C), couvert, vent 15 km/h S, \
+                       humidité 65 %";
+        let tomorrow = "Lyon (France) demain : 6 à 11 °C, pluie faible, pluie 80 %, \
+                        vent jusqu'à 30 km/h";
+        assert_eq!(
+            say(&plugin, "alice", "λmeteo LYON", now).await.as_deref(),
+            Some(current)
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λmeteo lyon demain > bob", now).await,
+            Some(format!("bob: {tomorrow}"))
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λmeteo", now).await.as_deref(),
+            Some("Pas de ville par défaut, choisis-en une avec λmeteo set <ville>")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λmeteo set lyon", now)
+                .await
+                .as_deref(),
+            Some("Ta ville par défaut est maintenant Lyon (France)")
+        );
+        assert_eq!(
+            say(&plugin, "Alice", "λmeteo", now).await.as_deref(),
+            Some(current)
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λmeteo demain", now).await.as_deref(),
+            Some(tomorrow)
+        );
+        assert_eq!(
+            say(&plugin, "bob", "λmeteo", now).await.as_deref(),
+            Some("Pas de ville par défaut, choisis-en une avec λmeteo set <ville>"),
+            "per nick"
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λmeteo set", now).await.as_deref(),
+            Some("Usage: λmeteo set <ville>")
+        );
+        assert_eq!(say(&plugin, "alice", "meteo Lyon", now).await, None);
+    }
+
+    #[test]
+    async fn test_cache() {
+        let now = Instant::now();
+        let plugin = meteo(now);
+        assert_eq!(plugin.cached("lyon", now), Some(lyon()));
+        assert_eq!(plugin.cached("brest", now), None);
+        let later = now + Duration::from_secs(DEFAULT_CACHE_SECS - 1);
+        assert_eq!(plugin.cached("lyon", later), Some(lyon()));
+        let outdated = now + Duration::from_secs(DEFAULT_CACHE_SECS);
+        assert_eq!(plugin.cached("lyon", outdated), None);
+        assert!(plugin.cache.lock().unwrap().is_empty(), "evicted");
+    }
+}
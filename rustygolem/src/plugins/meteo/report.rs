@@ -0,0 +1,164 @@
+use super::openmeteo::{Current, Day, Place};
+
+/// The sky of a WMO weather code, as used by Open-Meteo
+pub fn sky(code: u8) -> &'static str {
+    match code {
+        0 => "ciel dégagé",
+        1 => "plutôt dégagé",
+        2 => "partiellement nuageux",
+        3 => "couvert",
+        45 | 48 => "brouillard",
+        51 | 53 | 55 => "bruine",
+        56 | 57 => "bruine verglaçante",
+        61 => "pluie faible",
+        63 => "pluie",
+        65 => "forte pluie",
+        66 | 67 => "pluie verglaçante",
+        71 => "neige faible",
+        73 => "neige",
+        75 => "forte neige",
+        77 => "grains de neige",
+        80 | 81 => "averses",
+        82 => "violentes averses",
+        85 | 86 => "averses de neige",
+        95 => "orages",
+        96 | 99 => "orages avec grêle",
+        _ => "temps inconnu",
+    }
+}
+
+/// Like `12 °C`, rounded, without any `-0 °C`
+pub fn temperature(celsius: f64) -> String {
+    format!("{} °C", celsius.round() as i64)
+}
+
+/// Like `15 km/h SO`, the direction the wind comes from
+pub fn wind(speed: f64, direction: f64) -> String {
+    let speed = speed.round() as i64;
+    if speed == 0 {
+        return "pas de vent".to_string();
+    }
+    const POINTS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SO", "O", "NO"];
+    let point = ((direction.rem_euclid(360.0) / 45.0).round() as usize) % POINTS.len();
+    format!("vent {speed} km/h {}", POINTS[point])
+}
+
+/// Like `Lyon (France) : 12 °C (ressenti 10 °C), couvert, vent 15 km/h S, humidité 65 %`
+pub fn current(place: &Place, current: &Current) -> String {
+    format!(
+        "{} : {} (ressenti {}), {}, {}, humidité {} %",
+        place.describe(),
+        temperature(current.temperature_2m),
+        temperature(current.apparent_temperature),
+        sky(current.weather_code),
+        wind(current.wind_speed_10m, current.wind_direction_10m),
+        current.relative_humidity_2m.round() as i64,
+    )
+}
+
+/// Like `Lyon (France) demain : 6 à 11 °C, pluie faible, pluie 80 %, vent jusqu'à 30 km/h`
+pub fn tomorrow(place: &Place, day: &Day) -> String {
+    let mut parts = vec![
+        format!("{} à {}", day.min.round() as i64, temperature(day.max)),
+        sky(day.weather_code).to_string(),
+    ];
+    if let Some(probability) = day.precipitation_probability {
+        parts.push(format!("pluie {} %", probability.round() as i64));
+    }
+    parts.push(format!(
+        "vent jusqu'à {} km/h",
+        day.max_wind_speed.round() as i64
+    ));
+    format!("{} demain : {}", place.describe(), parts.join(", "))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn lyon() -> Place {
+        Place {
+            name: "Lyon".to_string(),
+            latitude: 45.75,
+            longitude: 4.85,
+            country: Some("France".to_string()),
+            population: Some(522_969),
+        }
+    }
+
+    #[test]
+    async fn test_temperature() {
+        for (celsius, expected) in [
+            (12.3, "12 °C"),
+            (12.5, "13 °C"),
+            (-0.4, "0 °C"),
+            (-0.6, "-1 °C"),
+            (-12.5, "-13 °C"),
+            (0.0, "0 °C"),
+        ] {
+            assert_eq!(temperature(celsius), expected, "{celsius}");
+        }
+    }
+
+    #[test]
+    async fn test_wind() {
+        for (speed, direction, expected) in [
+            (14.8, 200.0, "vent 15 km/h S"),
+            (14.8, 0.0, "vent 15 km/h N"),
+            (3.2, 359.0, "vent 3 km/h N"),
+            (3.2, 337.4, "vent 3 km/h NO"),
+            (20.0, 90.0, "vent 20 km/h E"),
+            (20.0, 225.0, "vent 20 km/h SO"),
+            (20.0, 270.0, "vent 20 km/h O"),
+            (0.4, 90.0, "pas de vent"),
+        ] {
+            assert_eq!(wind(speed, direction), expected, "{speed} {direction}");
+        }
+    }
+
+    #[test]
+    async fn test_sky() {
+        assert_eq!(sky(0), "ciel dégagé");
+        assert_eq!(sky(63), "pluie");
+        assert_eq!(sky(99), "orages avec grêle");
+        assert_eq!(sky(42), "temps inconnu");
+    }
+
+    #[test]
+    async fn test_current() {
+        let current = Current {
+            temperature_2m: 12.3,
+            apparent_temperature: 10.1,
+            relative_humidity_2m: 65.0,
+            weather_code: 3,
+            wind_speed_10m: 14.8,
+            wind_direction_10m: 200.0,
+        };
+        assert_eq!(
+            super::current(&lyon(), &current),
+            "Lyon (France) : 12 °C (ressenti 10 °C), couvert, vent 15 km/h S, humidité 65 %"
+        );
+    }
+
+    #[test]
+    async fn test_tomorrow() {
+        let mut day = Day {
+            weather_code: 61,
+            min: 6.3,
+            max: 11.0,
+            precipitation_probability: Some(80.0),
+            max_wind_speed: 30.1,
+        };
+        assert_eq!(
+            tomorrow(&lyon(), &day),
+            "Lyon (France) demain : 6 à 11 °C, pluie faible, pluie 80 %, vent jusqu'à 30 km/h"
+        );
+        day.precipitation_probability = None;
+        day.min = -2.6;
+        assert_eq!(
+            tomorrow(&lyon(), &day),
+            "Lyon (France) demain : -3 à 11 °C, pluie faible, vent jusqu'à 30 km/h"
+        );
+    }
+}
@@ -1,11 +1,69 @@
 mod crypto;
 mod ctcp;
+mod dice;
 mod echo;
+mod factoid;
+mod gitea;
+mod github;
+mod gitlab;
 mod joke;
+mod karma;
+mod meteo;
+mod poll;
+mod quote;
+mod remind;
 mod republican_calendar;
+mod rss;
+mod sed;
+mod seen;
+mod tell;
+mod time;
+mod translate;
 
 pub use crypto::Crypto;
 pub use ctcp::Ctcp;
+pub use dice::Dice;
 pub use echo::Echo;
+pub use factoid::Factoid;
+pub use gitea::Gitea;
+pub use github::Github;
+pub use gitlab::Gitlab;
 pub use joke::Joke;
+pub use karma::Karma;
+pub use meteo::Meteo;
+pub use poll::PollPlugin;
+pub use quote::Quote;
+pub use remind::Remind;
 pub use self::republican_calendar::RepublicanCalendar;
+pub use rss::Rss;
+pub use sed::Sed;
+pub use seen::Seen;
+pub use tell::Tell;
+pub use time::Time;
+pub use translate::Translate;
+
+register_plugins! {
+    crypto => Crypto,
+    ctcp => Ctcp,
+    dice => Dice,
+    echo => Echo,
+    factoid => Factoid,
+    gitea => Gitea,
+    github => Github,
+    gitlab => Gitlab,
+    joke => Joke,
+    karma => Karma,
+    meteo => Meteo,
+    poll => PollPlugin,
+    quote => Quote,
+    remind => Remind,
+    republican_calendar => RepublicanCalendar,
+    rss => Rss,
+    sed => Sed,
+    seen => Seen,
+    tell => Tell,
+    time => Time,
+    translate => Translate,
+    twitch => plugin_twitch::Twitch,
+    url => plugin_url::UrlPlugin,
+}
@@ -0,0 +1,4 @@
+mod plugin;
+mod polls;
+
+pub use plugin::PollPlugin;
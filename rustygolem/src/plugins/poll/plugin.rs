@@ -0,0 +1,498 @@
+use std::result::Result as StdResult;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use irc::proto::{ChannelExt, Command, Message};
+use nom::bytes::complete::tag;
+use nom::sequence::preceded;
+use plugin_core::utils::account::is_admin;
+use plugin_core::utils::network::network;
+use plugin_core::utils::parser;
+use plugin_core::{CommandHelp, Initialised, MsgCtx, Outbound, Plugin, Result};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use super::polls::{Ended, Poll, Polls, Voted, Voter, MAX_OPTIONS};
+use crate::caps::CaseMapping;
+use crate::utils::text::sanitize;
+
+const USAGE: &str = "Usage: λpoll start \"<question>\" <option> <option>…, λpoll status, λpoll end";
+const VOTE_USAGE: &str = "Usage: λvote <number or option>";
+
+/// Longer questions and options are refused, in chars
+const MAX_LENGTH: usize = 200;
+
+/// Longer replies are cut, in chars
+const MAX_REPLY_LENGTH: usize = 400;
+
+/// The `poll` section of the golem config
+#[derive(Deserialize, Default)]
+struct Settings {
+    /// the polls are closed after that long, unless ended before
+    #[serde(default)]
+    close_after_minutes: Option<u64>,
+}
+
+impl Settings {
+    fn load(config: &plugin_core::Config) -> Result<Self> {
+        Ok(config.plugin_section("poll")?.unwrap_or_default())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum PollCommand<'a> {
+    /// `λpoll start "Pizza ce soir?" oui non peut-être`
+    Start {
+        question: &'a str,
+        options: Vec<&'a str>,
+    },
+    Status,
+    End,
+    /// `λvote 2` or `λvote non`
+    Vote(&'a str),
+}
+
+/// The words of the input, the ones between double quotes as one. None when
+/// a quote isn't closed. True for the quoted ones.
+fn words(input: &str) -> Option<Vec<(&str, bool)>> {
+    let mut words = vec![];
+    let mut rest = input.trim_start();
+    while !rest.is_empty() {
+        let (word, quoted, after) = match rest.strip_prefix(['"', '“']) {
+            Some(quoted) => {
+                let end = quoted.find(['"', '”'])?;
+                let closing = quoted[end..].chars().next()?;
+                (&quoted[..end], true, &quoted[end + closing.len_utf8()..])
+            }
+            None => {
+                let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                (&rest[..end], false, &rest[end..])
+            }
+        };
+        words.push((word.trim(), quoted));
+        rest = after.trim_start();
+    }
+    Some(words)
+}
+
+/// None when this isn't a poll command, an error with the usage or what's
+/// wrong when it is one, but malformed. The quoted texts can contain `>`, so
+/// there's no target nick.
+fn parse_command(input: &str) -> Option<StdResult<PollCommand<'_>, String>> {
+    if let Ok((_, (choice, _))) = parser::command("vote")(input) {
+        return Some(match choice {
+            "" => Err(VOTE_USAGE.to_string()),
+            choice => Ok(PollCommand::Vote(choice)),
+        });
+    }
+    let (rest, _) = preceded(parser::command_prefix, tag("poll"))(input).ok()?;
+    if rest.starts_with(|c: char| !c.is_whitespace()) {
+        return None;
+    }
+    let rest = rest.trim();
+    let (word, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    Some(match (word, args.trim()) {
+        ("status", "") => Ok(PollCommand::Status),
+        ("end", "") => Ok(PollCommand::End),
+        ("start", args) => parse_start(args),
+        _ => Err(USAGE.to_string()),
+    })
+}
+
+fn parse_start(args: &str) -> StdResult<PollCommand<'_>, String> {
+    let words = match words(args) {
+        Some(words) => words,
+        None => return Err("A quote isn't closed".to_string()),
+    };
+    let (question, options) = match words.split_first() {
+        Some(((question, true), options)) if !question.is_empty() => (*question, options),
+        _ => return Err(USAGE.to_string()),
+    };
+    let options = options
+        .iter()
+        .map(|(option, _)| *option)
+        .filter(|option| !option.is_empty())
+        .collect::<Vec<_>>();
+    if options.len() < 2 {
+        return Err("A poll needs two options at least".to_string());
+    }
+    if options.len() > MAX_OPTIONS {
+        return Err(format!("A poll has {MAX_OPTIONS} options at most"));
+    }
+    if std::iter::once(question)
+        .chain(options.iter().copied())
+        .any(|text| text.chars().count() > MAX_LENGTH)
+    {
+        return Err(format!(
+            "The question and the options are {MAX_LENGTH} characters at most"
+        ));
+    }
+    for (i, option) in options.iter().enumerate() {
+        if options[..i]
+            .iter()
+            .any(|other| other.to_lowercase() == option.to_lowercase())
+        {
+            return Err(format!("{option} is there twice"));
+        }
+        if option.parse::<usize>().is_ok() {
+            return Err(format!(
+                "{option} would be mistaken for the number of an option"
+            ));
+        }
+    }
+    Ok(PollCommand::Start { question, options })
+}
+
+pub struct PollPlugin {
+    polls: Polls,
+    /// allowed to end any poll
+    admins: Vec<String>,
+}
+
+#[async_trait]
+impl Plugin for PollPlugin {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        Settings::load(config)?;
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let settings = Settings::load(config)?;
+        let close_after = settings
+            .close_after_minutes
+            .map(|minutes| Duration::from_secs(minutes * 60));
+        Ok(Initialised::from(PollPlugin {
+            polls: Polls::new(close_after),
+            admins: config.admins()?,
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "poll"
+    }
+
+    async fn in_message_ctx(&self, ctx: &MsgCtx, msg: &Message) -> Result<Option<Outbound>> {
+        Ok(in_msg(self, msg, ctx.casemapping, Instant::now()))
+    }
+
+    async fn run(&self, bot_chan: mpsc::Sender<Outbound>) -> Result<()> {
+        Ok(self.polls.run(&bot_chan).await?)
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("poll start")
+                .usage("poll start \"<question>\" <option> <option>…")
+                .description(
+                    "Open a poll in the channel, the options with spaces between quotes too",
+                ),
+            CommandHelp::new("poll status")
+                .usage("poll status")
+                .description("The votes so far"),
+            CommandHelp::new("poll end")
+                .usage("poll end")
+                .description("Close the poll you opened, any of them for the admins"),
+            CommandHelp::new("vote")
+                .usage("vote <number or option>")
+                .description("Vote in the poll of the channel, again to change your vote"),
+        ]
+    }
+}
+
+fn in_msg(
+    plugin: &PollPlugin,
+    msg: &Message,
+    casemapping: CaseMapping,
+    now: Instant,
+) -> Option<Outbound> {
+    let response_target = msg.response_target()?;
+    let command = match &msg.command {
+        Command::PRIVMSG(_source, privmsg) => parse_command(privmsg)?,
+        _ => return None,
+    };
+    let command = match command {
+        Ok(_) if !response_target.is_channel_name() => {
+            return Some(Outbound::reply(
+                response_target,
+                "The polls are per channel, ask in one",
+            ))
+        }
+        Ok(command) => command,
+        Err(usage) => return Some(Outbound::reply(response_target, usage)),
+    };
+    let channel = response_target;
+    let network = network(msg).unwrap_or_default();
+    let nick = msg.source_nickname().unwrap_or_default();
+    let text = match command {
+        PollCommand::Start { question, options } => {
+            let opened_by = Voter::of(msg, casemapping)?;
+            let poll = Poll::new(network, channel, question, &options, nick, opened_by);
+            match plugin.polls.start(casemapping, poll, now) {
+                Ok(poll) => {
+                    let options = poll
+                        .options
+                        .iter()
+                        .enumerate()
+                        .map(|(i, option)| format!("{} {option}", i + 1))
+                        .collect::<Vec<_>>();
+                    format!(
+                        "Poll: {} {}, λvote <number or option>",
+                        poll.question,
+                        options.join(" | ")
+                    )
+                }
+                Err(open) => format!(
+                    "A poll is open here already: {}, λpoll end to close it",
+                    open.question
+                ),
+            }
+        }
+        PollCommand::Status => match plugin.polls.status(network, casemapping, channel, now) {
+            Some(poll) => poll.results(),
+            None => "No poll here, open one with λpoll start".to_string(),
+        },
+        PollCommand::End => {
+            let ending = Voter::of(msg, casemapping);
+            let allowed = |poll: &Poll| {
+                is_admin(&plugin.admins, msg, casemapping)
+                    || ending.as_ref() == Some(&poll.opened_by)
+            };
+            match plugin.polls.end(network, casemapping, channel, allowed) {
+                Ended::Closed(poll) => format!("Poll closed: {}", poll.results()),
+                Ended::NotAllowed { creator } => {
+                    format!("Only the admins and {creator} who opened the poll can end it")
+                }
+                Ended::NoPoll => "No poll here".to_string(),
+            }
+        }
+        PollCommand::Vote(choice) => {
+            let voter = Voter::of(msg, casemapping)?;
+            match plugin
+                .polls
+                .vote(network, casemapping, channel, voter, choice, now)
+            {
+                Voted::Counted(option) => format!("{nick} votes {option}"),
+                Voted::Changed(option) => format!("{nick} now votes {option}"),
+                Voted::Unchanged(option) => format!("{nick} votes {option} already"),
+                Voted::NoPoll => "No poll here".to_string(),
+                Voted::NoSuchOption => format!("No option {choice}, λpoll status to see them"),
+            }
+        }
+    };
+    Some(Outbound::reply(channel, sanitize(&text, MAX_REPLY_LENGTH)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use plugin_core::utils::network::set_network;
+    use pretty_assertions::assert_eq;
+
+    fn poll_plugin() -> PollPlugin {
+        PollPlugin {
+            polls: Polls::new(None),
+            admins: vec!["root".to_string()],
+        }
+    }
+
+    fn say_in(plugin: &PollPlugin, nick: &str, target: &str, text: &str) -> Option<String> {
+        let source = format!("{nick}!~{nick}@localhost");
+        let mut msg = Message::new(Some(&source), "PRIVMSG", vec![target, text]).unwrap();
+        set_network(&mut msg, "libera");
+        match in_msg(plugin, &msg, CaseMapping::Rfc1459, Instant::now()) {
+            Some(Outbound::Reply { text, .. }) => Some(text),
+            None => None,
+            other => panic!("unexpected reply to {text:?}: {other:?}"),
+        }
+    }
+
+    fn ask(plugin: &PollPlugin, nick: &str, text: &str) -> String {
+        say_in(plugin, nick, "#rust", text).unwrap_or_else(|| panic!("no reply to {text:?}"))
+    }
+
+    fn start<'a>(
+        question: &'a str,
+        options: &[&'a str],
+    ) -> Option<StdResult<PollCommand<'a>, String>> {
+        Some(Ok(PollCommand::Start {
+            question,
+            options: options.to_vec(),
+        }))
+    }
+
+    #[test]
+    async fn test_words() {
+        assert_eq!(
+            words(r#""Pizza ce soir?" oui  "pas ce soir" non"#),
+            Some(vec![
+                ("Pizza ce soir?", true),
+                ("oui", false),
+                ("pas ce soir", true),
+                ("non", false),
+            ])
+        );
+        assert_eq!(
+            words("“Tabs > spaces?” oui"),
+            Some(vec![("Tabs > spaces?", true), ("oui", false)])
+        );
+        assert_eq!(words(""), Some(vec![]));
+        assert_eq!(words(r#""not closed oui non"#), None);
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        let usage = Some(Err(USAGE.to_string()));
+        for (input, expected) in [
+            (
+                r#"λpoll start "Pizza ce soir?" oui non peut-être"#,
+                start("Pizza ce soir?", &["oui", "non", "peut-être"]),
+            ),
+            (
+                r#"λpoll start "Tabs > spaces?" "bien sûr" jamais"#,
+                start("Tabs > spaces?", &["bien sûr", "jamais"]),
+            ),
+            ("λpoll status", Some(Ok(PollCommand::Status))),
+            ("λpoll end", Some(Ok(PollCommand::End))),
+            ("λvote 2", Some(Ok(PollCommand::Vote("2")))),
+            ("λvote peut-être", Some(Ok(PollCommand::Vote("peut-être")))),
+            ("λvote", Some(Err(VOTE_USAGE.to_string()))),
+            ("λpoll", usage.clone()),
+            ("λpoll start Pizza? oui non", usage.clone()),
+            (r#"λpoll start "" oui non"#, usage),
+            (
+                r#"λpoll start "Pizza?" oui"#,
+                Some(Err("A poll needs two options at least".to_string())),
+            ),
+            (
+                r#"λpoll start "Pizza?" oui OUI"#,
+                Some(Err("OUI is there twice".to_string())),
+            ),
+            (
+                r#"λpoll start "Pizza?" 1 2"#,
+                Some(Err(
+                    "1 would be mistaken for the number of an option".to_string()
+                )),
+            ),
+            (
+                r#"λpoll start "Pizza? oui non"#,
+                Some(Err("A quote isn't closed".to_string())),
+            ),
+            ("λpolls start", None),
+            ("λvoter 2", None),
+        ] {
+            assert_eq!(parse_command(input), expected, "{input:?}");
+        }
+        let many = format!("λpoll start \"?\" {}", "a b c d e f g h i j k");
+        assert_eq!(
+            parse_command(&many),
+            Some(Err(format!("A poll has {MAX_OPTIONS} options at most")))
+        );
+    }
+
+    #[test]
+    async fn test_poll() {
+        let plugin = poll_plugin();
+        assert_eq!(ask(&plugin, "bob", "λvote 1"), "No poll here");
+        assert_eq!(
+            ask(
+                &plugin,
+                "alice",
+                r#"λpoll start "Pizza ce soir?" oui non peut-être"#
+            ),
+            "Poll: Pizza ce soir? 1 oui | 2 non | 3 peut-être, λvote <number or option>"
+        );
+        assert_eq!(
+            ask(&plugin, "bob", r#"λpoll start "Sushi?" oui non"#),
+            "A poll is open here already: Pizza ce soir?, λpoll end to close it"
+        );
+        assert_eq!(ask(&plugin, "bob", "λvote 2"), "bob votes non");
+        assert_eq!(ask(&plugin, "bob", "λvote OUI"), "bob now votes oui");
+        assert_eq!(ask(&plugin, "charlie", "λvote oui"), "charlie votes oui");
+        assert_eq!(
+            ask(&plugin, "charlie", "λvote 1"),
+            "charlie votes oui already"
+        );
+        assert_eq!(
+            ask(&plugin, "dave", "λvote jamais"),
+            "No option jamais, λpoll status to see them"
+        );
+        assert_eq!(ask(&plugin, "dave", "λvote non"), "dave votes non");
+        assert_eq!(
+            ask(&plugin, "dave", "λpoll status"),
+            "Pizza ce soir? oui ██ 2 | non █ 1 | peut-être 0 (3 votes)"
+        );
+        assert_eq!(
+            ask(&plugin, "bob", "λpoll end"),
+            "Only the admins and alice who opened the poll can end it"
+        );
+        assert_eq!(
+            ask(&plugin, "ALICE", "λpoll end"),
+            "Poll closed: Pizza ce soir? oui ██ 2 | non █ 1 | peut-être 0 (3 votes)"
+        );
+        assert_eq!(ask(&plugin, "bob", "λvote 1"), "No poll here");
+        assert_eq!(
+            ask(&plugin, "bob", "λpoll status"),
+            "No poll here, open one with λpoll start"
+        );
+
+        ask(&plugin, "bob", r#"λpoll start "Sushi?" oui non"#);
+        assert_eq!(
+            say_in(&plugin, "bob", "#ocaml", "λpoll status").as_deref(),
+            Some("No poll here, open one with λpoll start"),
+            "per channel"
+        );
+        assert_eq!(
+            ask(&plugin, "root", "λpoll end"),
+            "Poll closed: Sushi? oui 0 | non 0 (0 votes)"
+        );
+        assert_eq!(
+            say_in(&plugin, "bob", "golem", "λvote 1").as_deref(),
+            Some("The polls are per channel, ask in one")
+        );
+    }
+
+    #[test]
+    async fn test_vote_by_account() {
+        let plugin = poll_plugin();
+        ask(&plugin, "alice", r#"λpoll start "Pizza?" oui non"#);
+        for (source, choice) in [
+            ("@account=bob :bob!~b@host", "oui"),
+            ("@account=bob :bob_!~b@host", "non"),
+        ] {
+            let mut msg: Message = format!("{source} PRIVMSG #rust :λvote {choice}\r\n")
+                .parse()
+                .unwrap();
+            set_network(&mut msg, "libera");
+            in_msg(&plugin, &msg, CaseMapping::Rfc1459, Instant::now()).unwrap();
+        }
+        assert_eq!(
+            ask(&plugin, "alice", "λpoll status"),
+            "Pizza? oui 0 | non █ 1 (1 vote)",
+            "a single vote whatever the nick"
+        );
+    }
+
+    #[test]
+    async fn test_end_by_account() {
+        let plugin = poll_plugin();
+        let say = |line: &str| {
+            let mut msg: Message = format!("{line}\r\n").parse().unwrap();
+            set_network(&mut msg, "libera");
+            match in_msg(&plugin, &msg, CaseMapping::Rfc1459, Instant::now()) {
+                Some(Outbound::Reply { text, .. }) => text,
+                other => panic!("unexpected reply to {line:?}: {other:?}"),
+            }
+        };
+        say(r#"@account=alice :alice!~a@host PRIVMSG #rust :λpoll start "Pizza?" oui non"#);
+        assert_eq!(
+            say("@account=mallory :Alice!~m@host PRIVMSG #rust :λpoll end"),
+            "Only the admins and alice who opened the poll can end it",
+            "the nick isn't enough"
+        );
+        assert_eq!(
+            say("@account=ALICE :alice_away!~a@host PRIVMSG #rust :λpoll end"),
+            "Poll closed: Pizza? oui 0 | non 0 (0 votes)"
+        );
+    }
+}
@@ -0,0 +1,492 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use irc::proto::Message;
+use plugin_core::utils::account::account;
+use plugin_core::utils::network::set_network;
+use plugin_core::{Delayed, Outbound};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::caps::CaseMapping;
+
+/// Options of a poll, at most
+pub const MAX_OPTIONS: usize = 10;
+
+/// Longest bar of the results, the others are scaled accordingly
+const BAR_WIDTH: usize = 10;
+
+/// Who voted, a single vote each
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Voter {
+    /// services account, the same whatever the nick
+    Account(String),
+    /// when the server doesn't tell the account, or the sender isn't logged in
+    Nick(String),
+}
+
+impl Voter {
+    /// Normalized with the casemapping of the network
+    pub fn of(msg: &Message, casemapping: CaseMapping) -> Option<Self> {
+        match account(msg) {
+            Some(account) => Some(Voter::Account(casemapping.normalize(account))),
+            None => Some(Voter::Nick(casemapping.normalize(msg.source_nickname()?))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Poll {
+    pub network: String,
+    /// as given when started
+    pub channel: String,
+    pub question: String,
+    pub options: Vec<String>,
+    /// nick who started it, as told
+    pub creator: String,
+    /// allowed to end it, by account like the votes
+    pub opened_by: Voter,
+    /// index of the option of each voter
+    votes: HashMap<Voter, usize>,
+    pub closes_at: Option<Instant>,
+}
+
+impl Poll {
+    pub fn new(
+        network: &str,
+        channel: &str,
+        question: &str,
+        options: &[&str],
+        creator: &str,
+        opened_by: Voter,
+    ) -> Self {
+        Poll {
+            network: network.to_string(),
+            channel: channel.to_string(),
+            question: question.to_string(),
+            options: options.iter().map(|option| option.to_string()).collect(),
+            creator: creator.to_string(),
+            opened_by,
+            votes: HashMap::new(),
+            closes_at: None,
+        }
+    }
+
+    /// The votes for each option, in order
+    pub fn tally(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.options.len()];
+        for option in self.votes.values() {
+            counts[*option] += 1;
+        }
+        counts
+    }
+
+    /// Index of the option by its number from 1, or by its name ignoring
+    /// the case
+    pub fn option(&self, choice: &str) -> Option<usize> {
+        if let Ok(number) = choice.parse::<usize>() {
+            return (1..=self.options.len())
+                .contains(&number)
+                .then(|| number - 1);
+        }
+        self.options
+            .iter()
+            .position(|option| option.to_lowercase() == choice.to_lowercase())
+    }
+
+    /// Like `Pizza ce soir? oui ████ 4 | non ██ 2 | peut-être 0 (6 votes)`
+    pub fn results(&self) -> String {
+        let votes = self.votes.len();
+        let plural = if votes == 1 { "" } else { "s" };
+        format!(
+            "{} {} ({votes} vote{plural})",
+            self.question,
+            bars(&self.options, &self.tally())
+        )
+    }
+
+    fn is_open(&self, now: Instant) -> bool {
+        self.closes_at.map_or(true, |at| now < at)
+    }
+
+    fn outbound(&self) -> Outbound {
+        let text = format!("Poll closed: {}", self.results());
+        let mut msg = Message::from(Outbound::reply(&self.channel, text));
+        set_network(&mut msg, &self.network);
+        Outbound::Raw(msg)
+    }
+}
+
+/// Like `oui ████ 4 | non ██ 2 | peut-être 0`, scaled to `BAR_WIDTH` for
+/// the larger counts
+pub fn bars(options: &[String], counts: &[usize]) -> String {
+    let max = counts.iter().copied().max().unwrap_or_default();
+    options
+        .iter()
+        .zip(counts)
+        .map(|(option, &count)| {
+            let width = if max <= BAR_WIDTH {
+                count
+            } else {
+                // rounded up, so that a single vote still shows
+                (count * BAR_WIDTH + max - 1) / max
+            };
+            if width == 0 {
+                format!("{option} {count}")
+            } else {
+                format!("{option} {} {count}", "█".repeat(width))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Voted {
+    /// for that option
+    Counted(String),
+    /// from the previous one to that option
+    Changed(String),
+    /// already for that option
+    Unchanged(String),
+    NoPoll,
+    NoSuchOption,
+}
+
+#[derive(Debug)]
+pub enum Ended {
+    Closed(Poll),
+    NotAllowed { creator: String },
+    NoPoll,
+}
+
+/// The open polls, one per channel
+pub struct Polls {
+    /// by network and normalized channel
+    polls: Mutex<HashMap<(String, String), Poll>>,
+    /// the keys of the polls to close, for `run`
+    closing: Delayed<(String, String)>,
+    /// the polls are closed after that long, unless ended before
+    close_after: Option<Duration>,
+}
+
+impl Polls {
+    pub fn new(close_after: Option<Duration>) -> Self {
+        Polls {
+            polls: Mutex::new(HashMap::new()),
+            closing: Delayed::new(),
+            close_after,
+        }
+    }
+
+    /// Opens the poll, closed after `close_after`. The poll already open in
+    /// the channel when there's one.
+    pub fn start(
+        &self,
+        casemapping: CaseMapping,
+        mut poll: Poll,
+        now: Instant,
+    ) -> Result<Poll, Poll> {
+        let key = (poll.network.clone(), casemapping.normalize(&poll.channel));
+        let mut polls = self.polls.lock().expect("polls lock");
+        if let Some(open) = polls.get(&key) {
+            return Err(open.clone());
+        }
+        poll.closes_at = self.close_after.map(|after| now + after);
+        if let Some(at) = poll.closes_at {
+            self.closing.push(at, key.clone());
+        }
+        polls.insert(key, poll.clone());
+        Ok(poll)
+    }
+
+    /// Replaces the previous vote of the voter, the expired polls are closed
+    /// already even when `run` didn't announce it yet
+    pub fn vote(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        voter: Voter,
+        choice: &str,
+        now: Instant,
+    ) -> Voted {
+        let key = (network.to_string(), casemapping.normalize(channel));
+        let mut polls = self.polls.lock().expect("polls lock");
+        let poll = match polls.get_mut(&key) {
+            Some(poll) if poll.is_open(now) => poll,
+            _ => return Voted::NoPoll,
+        };
+        let option = match poll.option(choice) {
+            Some(option) => option,
+            None => return Voted::NoSuchOption,
+        };
+        let name = poll.options[option].clone();
+        match poll.votes.insert(voter, option) {
+            None => Voted::Counted(name),
+            Some(previous) if previous == option => Voted::Unchanged(name),
+            Some(_) => Voted::Changed(name),
+        }
+    }
+
+    pub fn status(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        now: Instant,
+    ) -> Option<Poll> {
+        let key = (network.to_string(), casemapping.normalize(channel));
+        let polls = self.polls.lock().expect("polls lock");
+        polls.get(&key).filter(|poll| poll.is_open(now)).cloned()
+    }
+
+    /// Closes the poll when `allowed` to, no vote is counted after that
+    pub fn end(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        allowed: impl FnOnce(&Poll) -> bool,
+    ) -> Ended {
+        let key = (network.to_string(), casemapping.normalize(channel));
+        let mut polls = self.polls.lock().expect("polls lock");
+        match polls.get(&key) {
+            None => Ended::NoPoll,
+            Some(poll) if !allowed(poll) => Ended::NotAllowed {
+                creator: poll.creator.clone(),
+            },
+            Some(_) => Ended::Closed(polls.remove(&key).expect("the poll of the channel")),
+        }
+    }
+
+    /// Announces the results of the polls once they expire
+    pub async fn run(&self, bot_chan: &mpsc::Sender<Outbound>) -> anyhow::Result<()> {
+        loop {
+            for key in self.closing.due().await {
+                if let Some(poll) = self.close_expired(&key, Instant::now()) {
+                    bot_chan.send(poll.outbound()).await?;
+                }
+            }
+        }
+    }
+
+    /// Takes the poll when expired, not when ended already and another one
+    /// started since in the channel
+    fn close_expired(&self, key: &(String, String), now: Instant) -> Option<Poll> {
+        let mut polls = self.polls.lock().expect("polls lock");
+        match polls.get(key) {
+            Some(poll) if !poll.is_open(now) => polls.remove(key),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::sync::Arc;
+
+    const RFC1459: CaseMapping = CaseMapping::Rfc1459;
+
+    fn options(options: &[&str]) -> Vec<String> {
+        options.iter().map(|option| option.to_string()).collect()
+    }
+
+    fn start(polls: &Polls, now: Instant) -> Poll {
+        let poll = Poll::new(
+            "libera",
+            "#Rust",
+            "Pizza ce soir?",
+            &["oui", "non", "peut-être"],
+            "alice",
+            Voter::Nick("alice".to_string()),
+        );
+        polls.start(RFC1459, poll, now).unwrap()
+    }
+
+    fn vote(polls: &Polls, voter: &str, choice: &str, now: Instant) -> Voted {
+        let voter = Voter::Nick(voter.to_string());
+        polls.vote("libera", RFC1459, "#rust", voter, choice, now)
+    }
+
+    #[test]
+    async fn test_bars() {
+        let names = options(&["oui", "non", "peut-être"]);
+        assert_eq!(
+            bars(&names, &[4, 2, 0]),
+            "oui ████ 4 | non ██ 2 | peut-être 0"
+        );
+        assert_eq!(bars(&names, &[0, 0, 0]), "oui 0 | non 0 | peut-être 0");
+        assert_eq!(
+            bars(&names, &[40, 1, 20]),
+            "oui ██████████ 40 | non █ 1 | peut-être █████ 20",
+            "scaled"
+        );
+    }
+
+    #[test]
+    async fn test_option() {
+        let now = Instant::now();
+        let poll = start(&Polls::new(None), now);
+        assert_eq!(poll.option("1"), Some(0));
+        assert_eq!(poll.option("3"), Some(2));
+        assert_eq!(poll.option("NON"), Some(1));
+        assert_eq!(poll.option("Peut-Être"), Some(2));
+        assert_eq!(poll.option("0"), None);
+        assert_eq!(poll.option("4"), None);
+        assert_eq!(poll.option("jamais"), None);
+    }
+
+    #[test]
+    async fn test_votes() {
+        let now = Instant::now();
+        let polls = Polls::new(None);
+        assert_eq!(vote(&polls, "bob", "oui", now), Voted::NoPoll);
+        start(&polls, now);
+        let bob = Voter::Nick("bob".to_string());
+        let sushi = Poll::new("libera", "#rust", "Sushi?", &["oui", "non"], "bob", bob);
+        assert!(
+            polls.start(RFC1459, sushi, now).is_err(),
+            "one poll per channel"
+        );
+        assert_eq!(
+            vote(&polls, "bob", "2", now),
+            Voted::Counted("non".to_string())
+        );
+        assert_eq!(
+            vote(&polls, "bob", "oui", now),
+            Voted::Changed("oui".to_string())
+        );
+        assert_eq!(
+            vote(&polls, "bob", "1", now),
+            Voted::Unchanged("oui".to_string())
+        );
+        assert_eq!(
+            vote(&polls, "charlie", "non", now),
+            Voted::Counted("non".to_string())
+        );
+        assert_eq!(vote(&polls, "charlie", "jamais", now), Voted::NoSuchOption);
+        let account = Voter::Account("charlie".to_string());
+        assert_eq!(
+            polls.vote("libera", RFC1459, "#rust", account, "oui", now),
+            Voted::Counted("oui".to_string()),
+            "not the same voter as the nick"
+        );
+        let poll = polls.status("libera", RFC1459, "#RUST", now).unwrap();
+        assert_eq!(poll.tally(), vec![2, 1, 0]);
+        assert_eq!(
+            poll.results(),
+            "Pizza ce soir? oui ██ 2 | non █ 1 | peut-être 0 (3 votes)"
+        );
+        assert!(polls.status("libera", RFC1459, "#ocaml", now).is_none());
+    }
+
+    #[test]
+    async fn test_voter() {
+        let logged_in: Message = "@account=Geekingfrog :Geek!~g@host PRIVMSG #chan :λvote 1\r\n"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            Voter::of(&logged_in, RFC1459),
+            Some(Voter::Account("geekingfrog".to_string()))
+        );
+        let anonymous: Message = ":Geek!~g@host PRIVMSG #chan :λvote 1\r\n".parse().unwrap();
+        assert_eq!(
+            Voter::of(&anonymous, RFC1459),
+            Some(Voter::Nick("geek".to_string()))
+        );
+        let brackets: Message = ":[Geek]!~g@host PRIVMSG #chan :λvote 1\r\n"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            Voter::of(&brackets, RFC1459),
+            Some(Voter::Nick("{geek}".to_string()))
+        );
+        assert_eq!(
+            Voter::of(&brackets, CaseMapping::Ascii),
+            Some(Voter::Nick("[geek]".to_string()))
+        );
+    }
+
+    #[test]
+    async fn test_end() {
+        let now = Instant::now();
+        let polls = Polls::new(None);
+        start(&polls, now);
+        vote(&polls, "bob", "oui", now);
+        assert!(matches!(
+            polls.end("libera", RFC1459, "#rust", |poll| poll.creator == "bob"),
+            Ended::NotAllowed { creator } if creator == "alice"
+        ));
+        match polls.end("libera", RFC1459, "#rust", |poll| poll.creator == "alice") {
+            Ended::Closed(poll) => assert_eq!(poll.tally(), vec![1, 0, 0]),
+            other => panic!("not closed: {other:?}"),
+        }
+        assert!(matches!(
+            polls.end("libera", RFC1459, "#rust", |_| true),
+            Ended::NoPoll
+        ));
+        assert_eq!(vote(&polls, "charlie", "oui", now), Voted::NoPoll);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_end_while_voting() {
+        let now = Instant::now();
+        let polls = Arc::new(Polls::new(None));
+        start(&polls, now);
+        let voters = (0..200)
+            .map(|i| {
+                let polls = Arc::clone(&polls);
+                tokio::spawn(async move { vote(&polls, &format!("nick{i}"), "oui", now) })
+            })
+            .collect::<Vec<_>>();
+        let ended = polls.end("libera", RFC1459, "#rust", |_| true);
+        let mut counted = 0;
+        for voter in voters {
+            match voter.await.unwrap() {
+                Voted::Counted(_) => counted += 1,
+                Voted::NoPoll => (),
+                other => panic!("unexpected vote {other:?}"),
+            }
+        }
+        match ended {
+            Ended::Closed(poll) => {
+                assert_eq!(poll.tally(), vec![counted, 0, 0], "no vote lost nor late")
+            }
+            other => panic!("not closed: {other:?}"),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run() {
+        let polls = Arc::new(Polls::new(Some(Duration::from_secs(60))));
+        let (tx, mut rx) = mpsc::channel(10);
+        let runner = Arc::clone(&polls);
+        tokio::spawn(async move { runner.run(&tx).await });
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        let poll = start(&polls, Instant::now());
+        assert_eq!(
+            poll.closes_at,
+            Some(Instant::now() + Duration::from_secs(60))
+        );
+        vote(&polls, "bob", "non", Instant::now());
+
+        tokio::time::sleep(Duration::from_secs(59)).await;
+        assert!(rx.try_recv().is_err(), "not yet");
+        let closed = rx.recv().await.unwrap();
+        let closed = Message::from(closed).to_string();
+        assert!(
+            closed.contains("PRIVMSG #Rust :Poll closed: Pizza ce soir? oui 0 | non █ 1"),
+            "{closed}"
+        );
+        assert!(closed.contains("network=libera"), "{closed}");
+        assert!(polls
+            .status("libera", RFC1459, "#rust", Instant::now())
+            .is_none());
+        assert_eq!(vote(&polls, "bob", "oui", Instant::now()), Voted::NoPoll);
+    }
+}
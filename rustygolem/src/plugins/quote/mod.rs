@@ -0,0 +1,4 @@
+mod plugin;
+mod quotes;
+
+pub use plugin::Quote;
@@ -0,0 +1,549 @@
+use std::result::Result as StdResult;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use irc::proto::{ChannelExt, Command, Message};
+use nom::bytes::complete::tag;
+use nom::sequence::preceded;
+use plugin_core::utils::account::is_admin;
+use plugin_core::utils::network::network;
+use plugin_core::utils::parser;
+use plugin_core::{CommandHelp, Initialised, MsgCtx, Outbound, Plugin, Requirement, Result};
+use serde::Deserialize;
+
+use super::quotes::{self, Quotes};
+use crate::caps::CaseMapping;
+use crate::utils::backlog::Backlog;
+use crate::utils::text::sanitize;
+
+const USAGE: &str = "Usage: λquote [id], λquote add <nick> <text>, λquote last [nick], \
+     λquote search <text>, λquote rm <id>";
+
+/// Longest quote saved, in chars, they are cut to `max_length` when told anyway
+const MAX_SAVED: usize = 1000;
+
+/// Longest quote told, in chars, unless the config says otherwise
+pub const DEFAULT_MAX_LENGTH: usize = 300;
+
+/// The `quote` section of the golem config
+#[derive(Deserialize)]
+struct Settings {
+    /// how many other quotes are told in a channel before one is told again
+    #[serde(default = "default_no_repeat_window")]
+    no_repeat_window: usize,
+    /// longer quotes are cut when told, in chars
+    #[serde(default = "default_max_length")]
+    max_length: usize,
+}
+
+fn default_no_repeat_window() -> usize {
+    quotes::DEFAULT_WINDOW
+}
+
+fn default_max_length() -> usize {
+    DEFAULT_MAX_LENGTH
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            no_repeat_window: default_no_repeat_window(),
+            max_length: default_max_length(),
+        }
+    }
+}
+
+impl Settings {
+    fn load(config: &plugin_core::Config) -> Result<Self> {
+        Ok(config.plugin_section("quote")?.unwrap_or_default())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum QuoteCommand<'a> {
+    Random,
+    Get(i64),
+    /// like `<charlie> something hilarious`
+    Add {
+        author: &'a str,
+        text: &'a str,
+    },
+    /// the last message of the channel, or of that nick in there
+    Last(Option<&'a str>),
+    Search(&'a str),
+    Remove(i64),
+}
+
+/// None when this isn't a quote command, an error with the usage when it
+/// is one, but malformed. The whole text is the quote, `> nick` included.
+fn parse_command(input: &str) -> Option<StdResult<QuoteCommand<'_>, String>> {
+    let (rest, _) = preceded(parser::command_prefix, tag("quote"))(input).ok()?;
+    if rest.starts_with(|c: char| !c.is_whitespace()) {
+        return None;
+    }
+    let (word, args) = match rest.trim().split_once(char::is_whitespace) {
+        Some((word, args)) => (word, args.trim()),
+        None => (rest.trim(), ""),
+    };
+    let command = match (word, args) {
+        ("", _) => Some(QuoteCommand::Random),
+        ("add", args) => parse_add(args),
+        ("last", "") => Some(QuoteCommand::Last(None)),
+        ("last", nick) if !nick.contains(char::is_whitespace) => {
+            Some(QuoteCommand::Last(Some(nick)))
+        }
+        ("search", "") => None,
+        ("search", text) => Some(QuoteCommand::Search(text)),
+        ("rm", id) => parse_id(id).map(QuoteCommand::Remove),
+        (id, "") => parse_id(id).map(QuoteCommand::Get),
+        _ => None,
+    };
+    Some(command.ok_or_else(|| USAGE.to_string()))
+}
+
+/// `<charlie> text` as pasted from a client, with the `@` of the ops, or
+/// `charlie text`
+fn parse_add(input: &str) -> Option<QuoteCommand<'_>> {
+    let (author, text) = input.split_once(char::is_whitespace)?;
+    let author = author
+        .strip_prefix('<')
+        .and_then(|author| author.strip_suffix('>'))
+        .unwrap_or(author)
+        .trim_start_matches(['@', '+', '%', '&', '~']);
+    let text = text.trim();
+    if author.is_empty() || text.is_empty() {
+        return None;
+    }
+    Some(QuoteCommand::Add { author, text })
+}
+
+fn parse_id(input: &str) -> Option<i64> {
+    input.trim_start_matches('#').parse().ok()
+}
+
+pub struct Quote {
+    quotes: Quotes,
+    /// the last messages of each channel, for λquote last
+    backlog: Backlog,
+    /// allowed to remove any quote
+    admins: Vec<String>,
+    max_length: usize,
+}
+
+#[async_trait]
+impl Plugin for Quote {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        Settings::load(config)?;
+        config.check_database("quote")?;
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let settings = Settings::load(config)?;
+        let db = config.require_database("quote")?;
+        Ok(Initialised::from(Quote {
+            quotes: Quotes::load(db, settings.no_repeat_window)?,
+            backlog: Backlog::default(),
+            admins: config.admins()?,
+            max_length: settings.max_length,
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "quote"
+    }
+
+    async fn in_message_ctx(&self, ctx: &MsgCtx, msg: &Message) -> Result<Option<Outbound>> {
+        let reply = in_msg(self, msg, ctx.casemapping, Utc::now(), rand::random())?;
+        self.remember(msg, ctx.casemapping);
+        Ok(reply)
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("quote")
+                .usage("quote [id]")
+                .description("A quote of the channel, that one when given"),
+            CommandHelp::new("quote add")
+                .usage("quote add <nick> <text>")
+                .description("Save what the nick said here"),
+            CommandHelp::new("quote last")
+                .usage("quote last [nick]")
+                .description("Save the last message here, or the last one of the nick"),
+            CommandHelp::new("quote search")
+                .usage("quote search <text>")
+                .description("A quote of the channel containing the text"),
+            CommandHelp::new("quote rm")
+                .usage("quote rm <id>")
+                .description("Remove a quote you added, any of them for the admins"),
+        ]
+    }
+
+    fn requirements(&self) -> Vec<Requirement> {
+        // the quotes
+        vec![Requirement::Database]
+    }
+}
+
+impl Quote {
+    /// Keeps the messages of the channels for λquote last, but not the commands
+    fn remember(&self, msg: &Message, casemapping: CaseMapping) {
+        let (channel, text) = match &msg.command {
+            Command::PRIVMSG(target, text) if target.is_channel_name() => (target, text),
+            _ => return,
+        };
+        let nick = match msg.source_nickname() {
+            Some(nick) => nick,
+            None => return,
+        };
+        if parser::command_prefix(text).is_ok() || text.starts_with('\x01') {
+            return;
+        }
+        let network = network(msg).unwrap_or_default();
+        self.backlog
+            .remember(network, casemapping, channel, nick, text);
+    }
+
+    /// The last message of the channel, or the last one of the nick in there
+    fn last(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        nick: Option<&str>,
+    ) -> Option<(String, String)> {
+        match nick {
+            None => self.backlog.last(network, casemapping, channel),
+            Some(nick) => {
+                let (said_by, mut messages) =
+                    self.backlog
+                        .messages_of(network, casemapping, channel, nick)?;
+                Some((said_by, messages.pop()?))
+            }
+        }
+    }
+
+    fn show(&self, quote: &quotes::Quote) -> String {
+        sanitize(
+            &format!("#{} <{}> {}", quote.id, quote.author, quote.text),
+            self.max_length,
+        )
+    }
+}
+
+fn in_msg(
+    plugin: &Quote,
+    msg: &Message,
+    casemapping: CaseMapping,
+    now: DateTime<Utc>,
+    roll: usize,
+) -> Result<Option<Outbound>> {
+    let response_target = match msg.response_target() {
+        None => return Ok(None),
+        Some(target) => target,
+    };
+    let command = match &msg.command {
+        Command::PRIVMSG(_source, privmsg) => match parse_command(privmsg) {
+            Some(command) => command,
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    let command = match command {
+        Ok(_) if !response_target.is_channel_name() => {
+            return Ok(Some(Outbound::reply(
+                response_target,
+                "The quotes are per channel, ask in one",
+            )))
+        }
+        Ok(command) => command,
+        Err(usage) => return Ok(Some(Outbound::reply(response_target, usage))),
+    };
+    let channel = response_target;
+    let network = network(msg).unwrap_or_default();
+    let nick = msg.source_nickname().unwrap_or_default();
+    let add = |author: &str, text: &str| {
+        let quote = quotes::Quote {
+            id: 0,
+            author: author.to_string(),
+            text: sanitize(text, MAX_SAVED),
+            added_by: nick.to_string(),
+        };
+        plugin
+            .quotes
+            .add(network, casemapping, channel, &quote, now)
+    };
+    let text = match command {
+        QuoteCommand::Random => {
+            match plugin
+                .quotes
+                .draw(network, casemapping, channel, None, roll)?
+            {
+                Some(quote) => plugin.show(&quote),
+                None => "No quote here yet, add one with λquote add <nick> <text>".to_string(),
+            }
+        }
+        QuoteCommand::Get(id) => match plugin.quotes.get(network, casemapping, channel, id)? {
+            Some(quote) => plugin.show(&quote),
+            None => format!("No quote #{id} here"),
+        },
+        QuoteCommand::Search(text) => {
+            match plugin
+                .quotes
+                .draw(network, casemapping, channel, Some(text), roll)?
+            {
+                Some(quote) => plugin.show(&quote),
+                None => format!("No quote here contains {text}"),
+            }
+        }
+        QuoteCommand::Add { author, text } => format!("Quote #{} saved", add(author, text)?),
+        QuoteCommand::Last(said_by) => match plugin.last(network, casemapping, channel, said_by) {
+            Some((author, text)) => {
+                let id = add(&author, &text)?;
+                let quote = plugin.quotes.get(network, casemapping, channel, id)?;
+                match quote {
+                    Some(quote) => format!("Quote saved: {}", plugin.show(&quote)),
+                    None => format!("Quote #{id} saved"),
+                }
+            }
+            None => match said_by {
+                Some(said_by) => format!("{said_by} said nothing here lately"),
+                None => "Nothing said here lately".to_string(),
+            },
+        },
+        QuoteCommand::Remove(id) => match plugin.quotes.get(network, casemapping, channel, id)? {
+            None => format!("No quote #{id} here"),
+            Some(quote)
+                if !is_admin(&plugin.admins, msg, casemapping)
+                    && !casemapping.eq_ignore_case(&quote.added_by, nick) =>
+            {
+                format!("Only the admins and whoever added quote #{id} can remove it")
+            }
+            Some(_) => {
+                plugin.quotes.remove(network, casemapping, channel, id)?;
+                format!("Quote #{id} removed")
+            }
+        },
+    };
+    Ok(Some(Outbound::reply(channel, text)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use plugin_core::utils::network::set_network;
+    use plugin_core::Database;
+    use pretty_assertions::assert_eq;
+
+    fn quote() -> Quote {
+        Quote {
+            quotes: Quotes::load(Database::in_memory().unwrap(), quotes::DEFAULT_WINDOW).unwrap(),
+            backlog: Backlog::default(),
+            admins: vec!["root".to_string()],
+            max_length: 40,
+        }
+    }
+
+    fn message(nick: &str, target: &str, text: &str) -> Message {
+        let source = format!("{nick}!~{nick}@localhost");
+        let mut msg = Message::new(Some(&source), "PRIVMSG", vec![target, text]).unwrap();
+        set_network(&mut msg, "libera");
+        msg
+    }
+
+    /// Like `in_message_ctx`, with a fixed time and roll
+    fn say(plugin: &Quote, nick: &str, target: &str, text: &str) -> Option<String> {
+        let msg = message(nick, target, text);
+        let now = DateTime::parse_from_rfc3339("2025-03-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let reply = in_msg(plugin, &msg, CaseMapping::Rfc1459, now, 0).unwrap();
+        plugin.remember(&msg, CaseMapping::Rfc1459);
+        match reply {
+            Some(Outbound::Reply { text, .. }) => Some(text),
+            None => None,
+            other => panic!("unexpected reply to {text:?}: {other:?}"),
+        }
+    }
+
+    fn ask(plugin: &Quote, nick: &str, text: &str) -> String {
+        say(plugin, nick, "#rust", text).unwrap_or_else(|| panic!("no reply to {text:?}"))
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        let usage = Some(Err(USAGE.to_string()));
+        for (input, expected) in [
+            ("λquote", Some(Ok(QuoteCommand::Random))),
+            ("λquote 42", Some(Ok(QuoteCommand::Get(42)))),
+            ("λquote #42", Some(Ok(QuoteCommand::Get(42)))),
+            (
+                "λquote add <charlie> something > hilarious",
+                Some(Ok(QuoteCommand::Add {
+                    author: "charlie",
+                    text: "something > hilarious",
+                })),
+            ),
+            (
+                "λquote add <@charlie>  ok ",
+                Some(Ok(QuoteCommand::Add {
+                    author: "charlie",
+                    text: "ok",
+                })),
+            ),
+            (
+                "λquote add charlie ok",
+                Some(Ok(QuoteCommand::Add {
+                    author: "charlie",
+                    text: "ok",
+                })),
+            ),
+            ("λquote add <charlie>", usage.clone()),
+            ("λquote add <> hi", usage.clone()),
+            ("λquote last", Some(Ok(QuoteCommand::Last(None)))),
+            (
+                "λquote last charlie",
+                Some(Ok(QuoteCommand::Last(Some("charlie")))),
+            ),
+            ("λquote last charlie bob", usage.clone()),
+            (
+                "λquote search  ship it",
+                Some(Ok(QuoteCommand::Search("ship it"))),
+            ),
+            ("λquote search", usage.clone()),
+            ("λquote rm 42", Some(Ok(QuoteCommand::Remove(42)))),
+            ("λquote rm", usage.clone()),
+            ("λquote forty-two", usage.clone()),
+            ("λquotes", None),
+            ("quote 42", None),
+        ] {
+            assert_eq!(parse_command(input), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    async fn test_quote() {
+        let plugin = quote();
+        assert_eq!(
+            ask(&plugin, "alice", "λquote"),
+            "No quote here yet, add one with λquote add <nick> <text>"
+        );
+        assert_eq!(
+            ask(
+                &plugin,
+                "alice",
+                "λquote add <charlie> it \x02compiles\x02, ship it"
+            ),
+            "Quote #1 saved"
+        );
+        assert_eq!(
+            ask(
+                &plugin,
+                "alice",
+                "λquote add <bob> I'll just rewrite it in rust over the weekend"
+            ),
+            "Quote #2 saved"
+        );
+        assert_eq!(
+            ask(&plugin, "dave", "λquote 1"),
+            "#1 <charlie> it compiles, ship it"
+        );
+        assert_eq!(
+            ask(&plugin, "dave", "λquote 2"),
+            "#2 <bob> I'll just rewrite it in rust o…",
+            "cut"
+        );
+        assert_eq!(ask(&plugin, "dave", "λquote 3"), "No quote #3 here");
+        assert_eq!(
+            ask(&plugin, "dave", "λquote search SHIP"),
+            "#1 <charlie> it compiles, ship it"
+        );
+        assert_eq!(
+            ask(&plugin, "dave", "λquote search php"),
+            "No quote here contains php"
+        );
+        assert_eq!(
+            say(&plugin, "dave", "#ocaml", "λquote 1").unwrap(),
+            "No quote #1 here",
+            "per channel"
+        );
+        assert_eq!(
+            say(&plugin, "dave", "golem", "λquote 1").unwrap(),
+            "The quotes are per channel, ask in one"
+        );
+    }
+
+    #[test]
+    async fn test_no_repeat() {
+        let plugin = quote();
+        for author in ["alice", "bob", "charlie"] {
+            ask(&plugin, "dave", &format!("λquote add {author} hi"));
+        }
+        let told = (0..3)
+            .map(|_| ask(&plugin, "dave", "λquote"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            told,
+            vec!["#1 <alice> hi", "#2 <bob> hi", "#3 <charlie> hi"]
+        );
+    }
+
+    #[test]
+    async fn test_last() {
+        let plugin = quote();
+        assert_eq!(
+            ask(&plugin, "alice", "λquote last"),
+            "Nothing said here lately"
+        );
+        say(&plugin, "charlie", "#rust", "it compiles, ship it");
+        say(&plugin, "bob", "#rust", "lol");
+        say(&plugin, "bob", "#rust", "λjoke");
+        say(&plugin, "bob", "#rust", "\x01ACTION facepalms\x01");
+        say(&plugin, "dave", "#ocaml", "not in here");
+        assert_eq!(
+            ask(&plugin, "alice", "λquote last"),
+            "Quote saved: #1 <bob> lol",
+            "not the commands nor the actions"
+        );
+        assert_eq!(
+            ask(&plugin, "alice", "λquote last CHARLIE"),
+            "Quote saved: #2 <charlie> it compiles, ship it"
+        );
+        assert_eq!(
+            ask(&plugin, "alice", "λquote last dave"),
+            "dave said nothing here lately"
+        );
+        assert_eq!(
+            ask(&plugin, "alice", "λquote last"),
+            "Quote saved: #3 <bob> lol",
+            "not the λquote commands either"
+        );
+    }
+
+    #[test]
+    async fn test_remove() {
+        let plugin = quote();
+        ask(
+            &plugin,
+            "alice",
+            "λquote add <charlie> it compiles, ship it",
+        );
+        ask(&plugin, "alice", "λquote add <charlie> lgtm");
+        assert_eq!(
+            ask(&plugin, "charlie", "λquote rm 1"),
+            "Only the admins and whoever added quote #1 can remove it",
+            "not even the author"
+        );
+        assert_eq!(ask(&plugin, "ALICE", "λquote rm 1"), "Quote #1 removed");
+        assert_eq!(ask(&plugin, "alice", "λquote rm 1"), "No quote #1 here");
+        assert_eq!(
+            ask(&plugin, "Root", "λquote rm 2"),
+            "Quote #2 removed",
+            "admin"
+        );
+        assert_eq!(
+            say(&plugin, "root", "#ocaml", "λquote rm 2").unwrap(),
+            "No quote #2 here"
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+use plugin_core::{Database, Result};
+
+use crate::caps::CaseMapping;
+use crate::plugins::joke::recent::{Recent, Table};
+
+/// Quotes not told again in a channel until that many others were
+pub const DEFAULT_WINDOW: usize = 5;
+
+/// The tables of the quote plugin in the shared database, see
+/// `plugin_core::ensure_schema`
+const MIGRATIONS: &[&str] = &[
+    // channel is normalized with the casemapping of the network, author is
+    // who said it and added_by who saved it
+    "CREATE TABLE quote_quotes (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        network TEXT NOT NULL,
+        channel TEXT NOT NULL,
+        author TEXT NOT NULL,
+        text TEXT NOT NULL,
+        added_by TEXT NOT NULL,
+        added_at TEXT NOT NULL
+    );",
+    // the quotes told lately, see `Recent`. The ids are unique across the
+    // networks, the channels of the same name share their window.
+    "CREATE TABLE quote_recent (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        channel TEXT NOT NULL,
+        quote_id TEXT NOT NULL
+    );
+    CREATE INDEX quote_recent_channel ON quote_recent (channel, id);",
+];
+
+const RECENT: Table = Table {
+    name: "quote_recent",
+    item: "quote_id",
+    ensure_schema,
+};
+
+fn ensure_schema(db: &Database) -> Result<()> {
+    plugin_core::ensure_schema(db, "quote", MIGRATIONS)
+}
+
+#[derive(QueryableByName)]
+struct LastId {
+    #[sql_type = "BigInt"]
+    id: i64,
+}
+
+#[derive(QueryableByName)]
+struct Id {
+    #[sql_type = "BigInt"]
+    id: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, QueryableByName)]
+pub struct Quote {
+    #[sql_type = "BigInt"]
+    pub id: i64,
+    #[sql_type = "Text"]
+    pub author: String,
+    #[sql_type = "Text"]
+    pub text: String,
+    #[sql_type = "Text"]
+    pub added_by: String,
+}
+
+/// The quotes of every channel, and the ones told recently there
+pub struct Quotes {
+    db: Database,
+    told: Recent,
+}
+
+impl Quotes {
+    /// Create the tables if needed, and load the quotes told before the last restart
+    pub fn load(db: Database, window: usize) -> Result<Self> {
+        ensure_schema(&db)?;
+        Ok(Quotes {
+            told: Recent::load(db.clone(), RECENT, window)?,
+            db,
+        })
+    }
+
+    /// Saves the quote in that channel, and gives its id. The id of the
+    /// given quote is ignored.
+    pub fn add(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        quote: &Quote,
+        added_at: DateTime<Utc>,
+    ) -> Result<i64> {
+        let id = self.db.with_connection(|conn| {
+            conn.transaction(|| {
+                diesel::sql_query(
+                    "INSERT INTO quote_quotes (network, channel, author, text, added_by, added_at) \
+                     VALUES (?, ?, ?, ?, ?, ?)",
+                )
+                .bind::<Text, _>(network)
+                .bind::<Text, _>(casemapping.normalize(channel))
+                .bind::<Text, _>(&quote.author)
+                .bind::<Text, _>(&quote.text)
+                .bind::<Text, _>(&quote.added_by)
+                .bind::<Text, _>(added_at.to_rfc3339_opts(SecondsFormat::Secs, true))
+                .execute(conn)?;
+                diesel::sql_query("SELECT last_insert_rowid() AS id").get_result::<LastId>(conn)
+            })
+        })?;
+        Ok(id.id)
+    }
+
+    /// None when there's no such quote in that channel
+    pub fn get(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        id: i64,
+    ) -> Result<Option<Quote>> {
+        let quotes = self.db.with_connection(|conn| {
+            diesel::sql_query(
+                "SELECT id, author, text, added_by FROM quote_quotes \
+                 WHERE network = ? AND channel = ? AND id = ?",
+            )
+            .bind::<Text, _>(network)
+            .bind::<Text, _>(casemapping.normalize(channel))
+            .bind::<BigInt, _>(id)
+            .load::<Quote>(conn)
+        })?;
+        Ok(quotes.into_iter().next())
+    }
+
+    /// A quote of the channel, containing the text when given, among the ones
+    /// not told recently unless there are too few of them. The roll picks it.
+    pub fn draw(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        containing: Option<&str>,
+        roll: usize,
+    ) -> Result<Option<Quote>> {
+        let channel = casemapping.normalize(channel);
+        let ids = self.db.with_connection(|conn| {
+            let query = diesel::sql_query(
+                "SELECT id FROM quote_quotes WHERE network = ? AND channel = ? \
+                 AND (author || ' ' || text) LIKE ? ESCAPE '\\' ORDER BY id",
+            )
+            .bind::<Text, _>(network)
+            .bind::<Text, _>(&channel)
+            .bind::<Text, _>(like_pattern(containing.unwrap_or_default()));
+            query.load::<Id>(conn)
+        })?;
+        let ids = ids.into_iter().map(|id| id.id).collect::<Vec<_>>();
+        if ids.is_empty() {
+            return Ok(None);
+        }
+        let told = self.told.told(casemapping, &channel);
+        let fresh = ids
+            .iter()
+            .filter(|id| !told.contains(&id.to_string()))
+            .collect::<Vec<_>>();
+        let id = if fresh.is_empty() {
+            ids[roll % ids.len()]
+        } else {
+            *fresh[roll % fresh.len()]
+        };
+        self.told.record(casemapping, &channel, &id.to_string())?;
+        self.get(network, casemapping, &channel, id)
+    }
+
+    /// Removes the quote from the channel, false when there's no such quote
+    pub fn remove(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        id: i64,
+    ) -> Result<bool> {
+        let removed = self.db.with_connection(|conn| {
+            diesel::sql_query(
+                "DELETE FROM quote_quotes WHERE network = ? AND channel = ? AND id = ?",
+            )
+            .bind::<Text, _>(network)
+            .bind::<Text, _>(casemapping.normalize(channel))
+            .bind::<BigInt, _>(id)
+            .execute(conn)
+        })?;
+        Ok(removed > 0)
+    }
+}
+
+/// Matches anything containing the text, case insensitively in ASCII, with
+/// the `%` and `_` of the text as is
+fn like_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    const RFC1459: CaseMapping = CaseMapping::Rfc1459;
+
+    fn add(quotes: &Quotes, channel: &str, author: &str, text: &str) -> i64 {
+        let quote = Quote {
+            id: 0,
+            author: author.to_string(),
+            text: text.to_string(),
+            added_by: "alice".to_string(),
+        };
+        let added_at = Utc.ymd(2025, 3, 1).and_hms(10, 0, 0);
+        quotes
+            .add("libera", RFC1459, channel, &quote, added_at)
+            .unwrap()
+    }
+
+    fn draw(quotes: &Quotes, containing: Option<&str>, roll: usize) -> Option<i64> {
+        quotes
+            .draw("libera", RFC1459, "#rust", containing, roll)
+            .unwrap()
+            .map(|quote| quote.id)
+    }
+
+    #[test]
+    async fn test_persistence() {
+        let db = Database::in_memory().unwrap();
+        let quotes = Quotes::load(db.clone(), DEFAULT_WINDOW).unwrap();
+        let id = add(&quotes, "#Rust", "charlie", "it compiles, ship it");
+        add(&quotes, "#ocaml", "bob", "monads are burritos");
+
+        let quotes = Quotes::load(db, DEFAULT_WINDOW).unwrap();
+        assert_eq!(
+            quotes.get("libera", RFC1459, "#rust", id).unwrap(),
+            Some(Quote {
+                id,
+                author: "charlie".to_string(),
+                text: "it compiles, ship it".to_string(),
+                added_by: "alice".to_string(),
+            })
+        );
+        assert_eq!(
+            quotes.get("libera", RFC1459, "#ocaml", id).unwrap(),
+            None,
+            "per channel"
+        );
+        assert_eq!(quotes.get("oftc", RFC1459, "#rust", id).unwrap(), None);
+        assert!(!quotes.remove("libera", RFC1459, "#ocaml", id).unwrap());
+        assert!(quotes.remove("libera", RFC1459, "#RUST", id).unwrap());
+        assert_eq!(quotes.get("libera", RFC1459, "#rust", id).unwrap(), None);
+    }
+
+    #[test]
+    async fn test_no_repeat() {
+        let quotes = Quotes::load(Database::in_memory().unwrap(), 2).unwrap();
+        assert_eq!(draw(&quotes, None, 0), None, "no quote yet");
+        for i in 1..=3 {
+            add(&quotes, "#rust", "charlie", &format!("quote number {i}"));
+        }
+        // always rolling the first quote would tell the same one every time
+        let told = (0..5).map(|_| draw(&quotes, None, 0)).collect::<Vec<_>>();
+        assert_eq!(told, vec![Some(1), Some(2), Some(3), Some(1), Some(2)]);
+
+        let quotes = Quotes::load(Database::in_memory().unwrap(), 5).unwrap();
+        add(&quotes, "#rust", "charlie", "lonely");
+        assert_eq!(draw(&quotes, None, 7), Some(1));
+        assert_eq!(draw(&quotes, None, 7), Some(1), "too few to avoid repeats");
+    }
+
+    #[test]
+    async fn test_no_repeat_after_restart() {
+        let db = Database::in_memory().unwrap();
+        let quotes = Quotes::load(db.clone(), 2).unwrap();
+        for i in 1..=3 {
+            add(&quotes, "#rust", "charlie", &format!("quote number {i}"));
+        }
+        assert_eq!(draw(&quotes, None, 0), Some(1));
+        assert_eq!(draw(&quotes, None, 0), Some(2));
+
+        let quotes = Quotes::load(db, 2).unwrap();
+        assert_eq!(draw(&quotes, None, 0), Some(3));
+    }
+
+    #[test]
+    async fn test_search() {
+        let quotes = Quotes::load(Database::in_memory().unwrap(), DEFAULT_WINDOW).unwrap();
+        add(&quotes, "#rust", "charlie", "100% safe, trust me");
+        add(&quotes, "#rust", "bob", "unsafe is fine");
+        add(&quotes, "#ocaml", "bob", "Safe enough");
+        assert_eq!(draw(&quotes, Some("SAFE"), 1), Some(2));
+        assert_eq!(draw(&quotes, Some("safe"), 1), Some(1), "the other one");
+        assert_eq!(draw(&quotes, Some("100%"), 0), Some(1));
+        assert_eq!(draw(&quotes, Some("0% s"), 0), Some(1));
+        assert_eq!(draw(&quotes, Some("1_0"), 0), None, "_ as is");
+        assert_eq!(draw(&quotes, Some("charlie"), 0), Some(1), "by author");
+        assert_eq!(draw(&quotes, Some("enough"), 0), None, "only here");
+    }
+}
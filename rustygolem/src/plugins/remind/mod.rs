@@ -0,0 +1,5 @@
+mod plugin;
+mod reminders;
+mod when;
+
+pub use plugin::Remind;
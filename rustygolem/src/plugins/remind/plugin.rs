@@ -0,0 +1,332 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use irc::proto::{ChannelExt, Command, Message};
+use plugin_core::utils::network::network;
+use plugin_core::{CommandHelp, Initialised, MsgCtx, Outbound, Plugin, Requirement, Result};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use super::reminders::{Added, Reminder, Reminders};
+use super::when::{next_at, parse_command, RemindCommand, When, USAGE};
+use crate::caps::CaseMapping;
+use crate::utils::text::sanitize;
+use crate::utils::time::{format_duration, parse_timezone};
+
+/// The timezone of the times like 18:00, unless the config says otherwise
+pub const DEFAULT_TIMEZONE: &str = "Europe/Paris";
+/// Longest delay of λremind in, unless the config says otherwise
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(7 * 24 * 3600);
+/// Pending reminders a nick can have on a network, unless the config says otherwise
+pub const DEFAULT_MAX_PENDING: usize = 10;
+/// Longest reminder, in chars, unless the config says otherwise
+pub const DEFAULT_MAX_LENGTH: usize = 300;
+
+/// The `remind` section of the golem config
+#[derive(Deserialize)]
+struct Settings {
+    /// IANA name, for the times like 18:00
+    #[serde(default = "default_timezone")]
+    timezone: String,
+    #[serde(default = "default_max_delay_secs")]
+    max_delay_secs: u64,
+    #[serde(default = "default_max_pending")]
+    max_pending: usize,
+    /// longer reminders are cut, in chars
+    #[serde(default = "default_max_length")]
+    max_length: usize,
+}
+
+fn default_timezone() -> String {
+    DEFAULT_TIMEZONE.to_string()
+}
+
+fn default_max_delay_secs() -> u64 {
+    DEFAULT_MAX_DELAY.as_secs()
+}
+
+fn default_max_pending() -> usize {
+    DEFAULT_MAX_PENDING
+}
+
+fn default_max_length() -> usize {
+    DEFAULT_MAX_LENGTH
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            timezone: default_timezone(),
+            max_delay_secs: default_max_delay_secs(),
+            max_pending: default_max_pending(),
+            max_length: default_max_length(),
+        }
+    }
+}
+
+impl Settings {
+    fn load(config: &plugin_core::Config) -> Result<Self> {
+        Ok(config.plugin_section("remind")?.unwrap_or_default())
+    }
+
+    fn timezone(&self) -> anyhow::Result<Tz> {
+        parse_timezone("remind", &self.timezone)
+    }
+}
+
+pub struct Remind {
+    reminders: Reminders,
+    tz: Tz,
+    max_delay: Duration,
+    max_pending: usize,
+    max_length: usize,
+}
+
+#[async_trait]
+impl Plugin for Remind {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        Settings::load(config)?.timezone()?;
+        config.check_database("remind")?;
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let settings = Settings::load(config)?;
+        let db = config.require_database("remind")?;
+        Ok(Initialised::from(Remind {
+            reminders: Reminders::load(db, Utc::now())?,
+            tz: settings.timezone()?,
+            max_delay: Duration::from_secs(settings.max_delay_secs),
+            max_pending: settings.max_pending,
+            max_length: settings.max_length,
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "remind"
+    }
+
+    async fn in_message_ctx(&self, ctx: &MsgCtx, msg: &Message) -> Result<Option<Outbound>> {
+        in_msg(self, msg, ctx.casemapping, Utc::now())
+    }
+
+    /// Sends the reminders once due
+    async fn run(&self, bot_chan: mpsc::Sender<Outbound>) -> Result<()> {
+        Ok(self.reminders.run(&bot_chan).await?)
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("remind")
+                .usage("remind [me] [in] <duration> <text>, remind [me] at <HH:MM> <text>")
+                .description(
+                    "Ping you here with the text after the duration like 45m or 2h30, \
+                     or at the next time like 18:00",
+                ),
+            CommandHelp::new("remind list")
+                .usage("remind list")
+                .description("Your pending reminders"),
+            CommandHelp::new("remind cancel")
+                .usage("remind cancel <id>")
+                .description("Don't send that reminder"),
+        ]
+    }
+
+    fn requirements(&self) -> Vec<Requirement> {
+        // the pending reminders
+        vec![Requirement::Database]
+    }
+}
+
+fn in_msg(
+    plugin: &Remind,
+    msg: &Message,
+    casemapping: CaseMapping,
+    now: DateTime<Utc>,
+) -> Result<Option<Outbound>> {
+    let response_target = match msg.response_target() {
+        None => return Ok(None),
+        Some(target) => target,
+    };
+    let command = match &msg.command {
+        Command::PRIVMSG(_source, privmsg) => match parse_command(privmsg) {
+            Some(command) => command,
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    let network = network(msg).unwrap_or_default();
+    let nick = msg.source_nickname().unwrap_or_default();
+    let text = match command {
+        Err(usage) => usage,
+        Ok(RemindCommand::Add { .. }) if !response_target.is_channel_name() => {
+            "Reminders can only be set in a channel, to ping you there".to_string()
+        }
+        Ok(RemindCommand::Add {
+            when: When::In(delay),
+            ..
+        }) if delay > plugin.max_delay => format!("At most {}", format_duration(plugin.max_delay)),
+        Ok(RemindCommand::Add { when, text }) => {
+            let text = sanitize(text, plugin.max_length);
+            let due_at = match when {
+                When::In(delay) => chrono::Duration::from_std(delay)
+                    .ok()
+                    .and_then(|delay| now.checked_add_signed(delay)),
+                When::At(at) => Some(next_at(plugin.tz, at, now)),
+            };
+            let due_at = match due_at {
+                Some(due_at) if !text.is_empty() => due_at,
+                _ => return Ok(Some(Outbound::reply(response_target, USAGE))),
+            };
+            let reminder = Reminder {
+                id: 0,
+                network: network.to_string(),
+                channel: response_target.to_string(),
+                nick: nick.to_string(),
+                text,
+                due_at,
+            };
+            match plugin
+                .reminders
+                .add(reminder, casemapping, now, plugin.max_pending)?
+            {
+                Added::Reminder(reminder) => format!(
+                    "Reminder #{} set, in {}",
+                    reminder.id,
+                    in_words(reminder.due_at, now)
+                ),
+                Added::TooMany => format!(
+                    "You already have {} pending reminders, cancel one with λremind cancel <id>",
+                    plugin.max_pending
+                ),
+            }
+        }
+        Ok(RemindCommand::List) => {
+            let reminders = plugin.reminders.list(network, casemapping, nick);
+            if reminders.is_empty() {
+                "You have no pending reminder".to_string()
+            } else {
+                reminders
+                    .iter()
+                    .map(|r| {
+                        format!(
+                            "#{} in {} in {}: {}",
+                            r.id,
+                            in_words(r.due_at, now),
+                            r.channel,
+                            sanitize(&r.text, 30)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        }
+        Ok(RemindCommand::Cancel(id)) => {
+            if plugin.reminders.cancel(network, casemapping, nick, id)? {
+                format!("Reminder #{id} cancelled")
+            } else {
+                format!("You have no pending reminder #{id}")
+            }
+        }
+    };
+    Ok(Some(Outbound::reply(response_target, text)))
+}
+
+/// Like `2h30m`, to the minute
+fn in_words(due_at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let secs = (due_at - now).num_seconds().max(0) as u64;
+    format_duration(std::time::Duration::from_secs((secs + 59) / 60 * 60))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use plugin_core::utils::network::set_network;
+    use plugin_core::Database;
+    use pretty_assertions::assert_eq;
+
+    fn remind() -> Remind {
+        Remind {
+            reminders: Reminders::load(Database::in_memory().unwrap(), now()).unwrap(),
+            tz: DEFAULT_TIMEZONE.parse().unwrap(),
+            max_delay: DEFAULT_MAX_DELAY,
+            max_pending: 2,
+            max_length: DEFAULT_MAX_LENGTH,
+        }
+    }
+
+    /// 16:00 in Paris
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2025, 3, 1).and_hms(15, 0, 0)
+    }
+
+    fn ask(plugin: &Remind, nick: &str, target: &str, text: &str) -> String {
+        let source = format!("{nick}!~{nick}@localhost");
+        let mut msg = Message::new(Some(&source), "PRIVMSG", vec![target, text]).unwrap();
+        set_network(&mut msg, "libera");
+        match in_msg(plugin, &msg, CaseMapping::Rfc1459, now()).unwrap() {
+            Some(Outbound::Reply { text, .. }) => text,
+            other => panic!("no reply to {text:?}: {other:?}"),
+        }
+    }
+
+    #[test]
+    async fn test_remind() {
+        let plugin = remind();
+        assert_eq!(
+            ask(&plugin, "alice", "#rust", "λremind 45m stand-up"),
+            "Reminder #1 set, in 45m"
+        );
+        assert_eq!(
+            ask(&plugin, "alice", "#rust", "λremind at 18:00 apéro"),
+            "Reminder #2 set, in 2h",
+            "in Paris"
+        );
+        assert_eq!(
+            ask(
+                &plugin,
+                "ALICE",
+                "#rust",
+                "λremind me in 2h30 check the oven"
+            ),
+            "You already have 2 pending reminders, cancel one with λremind cancel <id>"
+        );
+        assert_eq!(
+            ask(&plugin, "bob", "#rust", "λremind me at 9:30 café"),
+            "Reminder #3 set, in 17h30m",
+            "tomorrow"
+        );
+        assert_eq!(
+            ask(&plugin, "bob", "golem", "λremind 5m tea"),
+            "Reminders can only be set in a channel, to ping you there"
+        );
+        assert_eq!(ask(&plugin, "bob", "#rust", "λremind 5m"), USAGE);
+        assert_eq!(
+            ask(&plugin, "bob", "#rust", "λremind 200h tea"),
+            "At most 168h"
+        );
+        assert_eq!(
+            ask(&plugin, "alice", "golem", "λremind list"),
+            "#1 in 45m in #rust: stand-up, #2 in 2h in #rust: apéro"
+        );
+        assert_eq!(
+            ask(&plugin, "bob", "#rust", "λremind cancel 1"),
+            "You have no pending reminder #1"
+        );
+        assert_eq!(
+            ask(&plugin, "alice", "#rust", "λremind cancel 1"),
+            "Reminder #1 cancelled"
+        );
+        assert_eq!(
+            ask(&plugin, "alice", "#rust", "λremind list"),
+            "#2 in 2h in #rust: apéro"
+        );
+        assert_eq!(
+            ask(&plugin, "charlie", "#rust", "λremind list"),
+            "You have no pending reminder"
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use std::time::Duration;
+
+use chrono::{DateTime, TimeZone, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+use irc::proto::Message;
+use plugin_core::utils::network::set_network;
+use plugin_core::{Database, Delayed, Outbound, Result};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::caps::CaseMapping;
+
+/// The tables of the remind plugin in the shared database, see
+/// `plugin_core::ensure_schema`
+const MIGRATIONS: &[&str] = &[
+    // due_at in seconds since the epoch
+    "CREATE TABLE remind_pending (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        network TEXT NOT NULL,
+        channel TEXT NOT NULL,
+        nick TEXT NOT NULL,
+        text TEXT NOT NULL,
+        due_at INTEGER NOT NULL
+    );",
+];
+
+#[derive(QueryableByName)]
+struct Row {
+    #[sql_type = "BigInt"]
+    id: i64,
+    #[sql_type = "Text"]
+    network: String,
+    #[sql_type = "Text"]
+    channel: String,
+    #[sql_type = "Text"]
+    nick: String,
+    #[sql_type = "Text"]
+    text: String,
+    #[sql_type = "BigInt"]
+    due_at: i64,
+}
+
+#[derive(QueryableByName)]
+struct LastId {
+    #[sql_type = "BigInt"]
+    id: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reminder {
+    pub id: i64,
+    pub network: String,
+    /// where it was asked for, and where the nick is pinged
+    pub channel: String,
+    pub nick: String,
+    pub text: String,
+    pub due_at: DateTime<Utc>,
+}
+
+impl Reminder {
+    /// To the channel and network where it was asked for, `(en retard)` when
+    /// it was due while the golem was down
+    fn outbound(&self, late: bool) -> Outbound {
+        let late = if late { " (en retard)" } else { "" };
+        let text = format!("{}: rappel − {}{late}", self.nick, self.text);
+        let mut msg = Message::from(Outbound::reply(&self.channel, text));
+        set_network(&mut msg, &self.network);
+        Outbound::Raw(msg)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Added {
+    Reminder(Reminder),
+    /// the nick already has that many pending reminders
+    TooMany,
+}
+
+#[derive(Debug, Clone)]
+struct Scheduled {
+    reminder: Reminder,
+    late: bool,
+}
+
+/// The reminders to send later, persisted in the database
+pub struct Reminders {
+    db: Database,
+    scheduled: Delayed<Scheduled>,
+}
+
+impl Reminders {
+    /// Create the table if needed, and reschedule the reminders from before
+    /// the last restart. The ones due while the golem was down are sent right
+    /// away, late.
+    pub fn load(db: Database, now: DateTime<Utc>) -> Result<Self> {
+        plugin_core::ensure_schema(&db, "remind", MIGRATIONS)?;
+        let rows = db.with_connection(|conn| {
+            diesel::sql_query(
+                "SELECT id, network, channel, nick, text, due_at FROM remind_pending ORDER BY id",
+            )
+            .load::<Row>(conn)
+        })?;
+        let started = Instant::now();
+        log::info!("Loaded {} pending reminders", rows.len());
+        let scheduled = Delayed::new();
+        for row in rows {
+            let reminder = Reminder {
+                id: row.id,
+                network: row.network,
+                channel: row.channel,
+                nick: row.nick,
+                text: row.text,
+                due_at: Utc.timestamp(row.due_at, 0),
+            };
+            scheduled.push(
+                started + until(reminder.due_at, now),
+                Scheduled {
+                    late: reminder.due_at <= now,
+                    reminder,
+                },
+            );
+        }
+        Ok(Reminders { db, scheduled })
+    }
+
+    /// Unless the nick already has `max_pending` reminders on that network
+    pub fn add(
+        &self,
+        mut reminder: Reminder,
+        casemapping: CaseMapping,
+        now: DateTime<Utc>,
+        max_pending: usize,
+    ) -> Result<Added> {
+        let pending = self.scheduled.count(|s| {
+            s.reminder
+                .belongs_to(&reminder.network, casemapping, &reminder.nick)
+        });
+        if pending >= max_pending {
+            return Ok(Added::TooMany);
+        }
+        let id = self.db.with_connection(|conn| {
+            conn.transaction(|| {
+                diesel::sql_query(
+                    "INSERT INTO remind_pending (network, channel, nick, text, due_at) \
+                     VALUES (?, ?, ?, ?, ?)",
+                )
+                .bind::<Text, _>(&reminder.network)
+                .bind::<Text, _>(&reminder.channel)
+                .bind::<Text, _>(&reminder.nick)
+                .bind::<Text, _>(&reminder.text)
+                .bind::<BigInt, _>(reminder.due_at.timestamp())
+                .execute(conn)?;
+                diesel::sql_query("SELECT last_insert_rowid() AS id").get_result::<LastId>(conn)
+            })
+        })?;
+        reminder.id = id.id;
+        self.scheduled.push(
+            Instant::now() + until(reminder.due_at, now),
+            Scheduled {
+                late: false,
+                reminder: reminder.clone(),
+            },
+        );
+        Ok(Added::Reminder(reminder))
+    }
+
+    /// The pending reminders of the nick on that network, the next one first
+    pub fn list(&self, network: &str, casemapping: CaseMapping, nick: &str) -> Vec<Reminder> {
+        let mut reminders = self
+            .scheduled
+            .filter(|s| s.reminder.belongs_to(network, casemapping, nick))
+            .into_iter()
+            .map(|s| s.reminder)
+            .collect::<Vec<_>>();
+        reminders.sort_by_key(|r| r.due_at);
+        reminders
+    }
+
+    /// Only the reminders of the nick can be cancelled. False when it has no such reminder.
+    pub fn cancel(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        nick: &str,
+        id: i64,
+    ) -> Result<bool> {
+        let is_it = |s: &Scheduled| {
+            s.reminder.id == id && s.reminder.belongs_to(network, casemapping, nick)
+        };
+        if self.scheduled.count(is_it) == 0 {
+            return Ok(false);
+        }
+        self.delete(id)?;
+        self.scheduled.retain(|s| !is_it(s));
+        Ok(true)
+    }
+
+    /// Sends the reminders once due. The ones still pending when the golem
+    /// shuts down are sent late after the restart.
+    pub async fn run(&self, bot_chan: &mpsc::Sender<Outbound>) -> anyhow::Result<()> {
+        loop {
+            for scheduled in self.scheduled.due().await {
+                bot_chan
+                    .send(scheduled.reminder.outbound(scheduled.late))
+                    .await?;
+                self.delete(scheduled.reminder.id)?;
+            }
+        }
+    }
+
+    fn delete(&self, id: i64) -> Result<()> {
+        self.db.with_connection(|conn| {
+            diesel::sql_query("DELETE FROM remind_pending WHERE id = ?")
+                .bind::<BigInt, _>(id)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+}
+
+impl Reminder {
+    fn belongs_to(&self, network: &str, casemapping: CaseMapping, nick: &str) -> bool {
+        self.network == network && casemapping.eq_ignore_case(&self.nick, nick)
+    }
+}
+
+/// Right away when already due
+fn until(due_at: DateTime<Utc>, now: DateTime<Utc>) -> Duration {
+    (due_at - now).to_std().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const RFC1459: CaseMapping = CaseMapping::Rfc1459;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2025, 3, 1).and_hms(10, 0, 0)
+    }
+
+    fn reminder(nick: &str, text: &str, in_secs: i64) -> Reminder {
+        Reminder {
+            id: 0,
+            network: "libera".to_string(),
+            channel: "#rust".to_string(),
+            nick: nick.to_string(),
+            text: text.to_string(),
+            due_at: now() + chrono::Duration::seconds(in_secs),
+        }
+    }
+
+    fn reminded(nick: &str, text: &str) -> Outbound {
+        let mut msg = Message::from(Outbound::reply("#rust", format!("{nick}: rappel − {text}")));
+        set_network(&mut msg, "libera");
+        Outbound::Raw(msg)
+    }
+
+    fn drain(rx: &mut mpsc::Receiver<Outbound>) -> Vec<Outbound> {
+        let mut sent = vec![];
+        while let Ok(outbound) = rx.try_recv() {
+            sent.push(outbound);
+        }
+        sent
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run() {
+        let reminders = Reminders::load(Database::in_memory().unwrap(), now()).unwrap();
+        let (tx, mut rx) = mpsc::channel(10);
+        let until = |s| tokio::time::timeout(secs(s), reminders.run(&tx));
+        reminders
+            .add(reminder("alice", "stand-up", 45 * 60), RFC1459, now(), 5)
+            .unwrap();
+        reminders
+            .add(reminder("bob", "the oven", 60), RFC1459, now(), 5)
+            .unwrap();
+        assert!(until(59).await.is_err());
+        assert_eq!(drain(&mut rx), vec![]);
+        assert!(until(1).await.is_err());
+        assert_eq!(drain(&mut rx), vec![reminded("bob", "the oven")]);
+        assert!(until(44 * 60).await.is_err());
+        assert_eq!(drain(&mut rx), vec![reminded("alice", "stand-up")]);
+        assert_eq!(
+            reminders.list("libera", RFC1459, "alice"),
+            vec![],
+            "done with"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reschedule_after_restart() {
+        let db = Database::in_memory().unwrap();
+        let reminders = Reminders::load(db.clone(), now()).unwrap();
+        reminders
+            .add(reminder("alice", "stand-up", 600), RFC1459, now(), 5)
+            .unwrap();
+        drop(reminders);
+
+        // restarted 2 minutes later
+        let restarted = now() + chrono::Duration::minutes(2);
+        let reminders = Reminders::load(db.clone(), restarted).unwrap();
+        let (tx, mut rx) = mpsc::channel(10);
+        assert!(tokio::time::timeout(secs(479), reminders.run(&tx))
+            .await
+            .is_err());
+        assert_eq!(drain(&mut rx), vec![], "still 8 minutes to go");
+        assert!(tokio::time::timeout(secs(1), reminders.run(&tx))
+            .await
+            .is_err());
+        assert_eq!(drain(&mut rx), vec![reminded("alice", "stand-up")]);
+        assert_eq!(
+            Reminders::load(db, restarted)
+                .unwrap()
+                .list("libera", RFC1459, "alice"),
+            vec![],
+            "not sent again after another restart"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_late() {
+        let db = Database::in_memory().unwrap();
+        let reminders = Reminders::load(db.clone(), now()).unwrap();
+        reminders
+            .add(reminder("alice", "stand-up", 600), RFC1459, now(), 5)
+            .unwrap();
+        reminders
+            .add(reminder("bob", "apéro", 7200), RFC1459, now(), 5)
+            .unwrap();
+        drop(reminders);
+
+        // down for an hour
+        let reminders = Reminders::load(db, now() + chrono::Duration::hours(1)).unwrap();
+        let (tx, mut rx) = mpsc::channel(10);
+        assert!(tokio::time::timeout(secs(1), reminders.run(&tx))
+            .await
+            .is_err());
+        let mut late = Message::from(Outbound::reply(
+            "#rust",
+            "alice: rappel − stand-up (en retard)",
+        ));
+        set_network(&mut late, "libera");
+        assert_eq!(drain(&mut rx), vec![Outbound::Raw(late)], "right away");
+        assert!(tokio::time::timeout(secs(3600), reminders.run(&tx))
+            .await
+            .is_err());
+        assert_eq!(drain(&mut rx), vec![reminded("bob", "apéro")], "on time");
+    }
+
+    #[test]
+    async fn test_cap() {
+        let reminders = Reminders::load(Database::in_memory().unwrap(), now()).unwrap();
+        let add = |nick| {
+            reminders
+                .add(reminder(nick, "stand-up", 60), RFC1459, now(), 2)
+                .unwrap()
+        };
+        assert!(matches!(add("alice"), Added::Reminder(_)));
+        assert!(matches!(add("Alice"), Added::Reminder(_)));
+        assert_eq!(add("ALICE"), Added::TooMany);
+        assert!(matches!(add("bob"), Added::Reminder(_)), "per nick");
+        assert!(matches!(add("[dave]"), Added::Reminder(_)));
+        assert!(matches!(add("{dave}"), Added::Reminder(_)));
+        assert_eq!(add("{DAVE}"), Added::TooMany, "in rfc1459");
+
+        let id = reminders.list("libera", RFC1459, "alice")[0].id;
+        let cancel = |nick| reminders.cancel("libera", RFC1459, nick, id).unwrap();
+        assert!(!cancel("bob"), "not bob's");
+        assert!(cancel("alice"));
+        assert!(!cancel("alice"), "already cancelled");
+        assert!(
+            matches!(add("alice"), Added::Reminder(_)),
+            "room for another one"
+        );
+    }
+}
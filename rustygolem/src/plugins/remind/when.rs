@@ -0,0 +1,210 @@
+use std::result::Result as StdResult;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use nom::bytes::complete::tag;
+use nom::sequence::preceded;
+use plugin_core::utils::parser;
+
+use crate::utils::time::{local, parse_duration};
+
+pub const USAGE: &str =
+    "Usage: λremind [me] [in] <duration> <text>, λremind [me] at <HH:MM> <text>, \
+     λremind list, λremind cancel <id>";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum When {
+    /// like 45m or 2h30
+    In(Duration),
+    /// like 18:00, in the configured timezone
+    At(NaiveTime),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemindCommand<'a> {
+    Add { when: When, text: &'a str },
+    List,
+    Cancel(i64),
+}
+
+/// None when this isn't a remind command, an error with the usage when it
+/// is one, but malformed
+pub fn parse_command(input: &str) -> Option<StdResult<RemindCommand<'_>, String>> {
+    let (rest, _) = preceded(parser::command_prefix, tag("remind"))(input).ok()?;
+    if rest.starts_with(|c: char| !c.is_whitespace()) {
+        return None;
+    }
+    let command = match next_word(rest) {
+        ("list", "") => Some(RemindCommand::List),
+        ("cancel", id) => id
+            .trim_start_matches('#')
+            .parse()
+            .ok()
+            .map(RemindCommand::Cancel),
+        ("me", rest) => parse_add(rest),
+        _ => parse_add(rest),
+    };
+    Some(command.ok_or_else(|| USAGE.to_string()))
+}
+
+/// `[in] <duration> <text>` or `at <HH:MM> <text>`
+fn parse_add(input: &str) -> Option<RemindCommand<'_>> {
+    let (when, text) = match next_word(input) {
+        ("at" | "à", rest) => {
+            let (time, text) = next_word(rest);
+            (When::At(parse_time(time)?), text)
+        }
+        ("in" | "dans", rest) => {
+            let (delay, text) = next_word(rest);
+            (When::In(parse_delay(delay)?), text)
+        }
+        (delay, text) => (When::In(parse_delay(delay)?), text),
+    };
+    if text.is_empty() {
+        return None;
+    }
+    Some(RemindCommand::Add { when, text })
+}
+
+/// The first word, and the trimmed rest
+fn next_word(input: &str) -> (&str, &str) {
+    let input = input.trim();
+    match input.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (input, ""),
+    }
+}
+
+/// Like `45m`, `2h30m`, or `2h30` with the minutes after the hours
+pub fn parse_delay(input: &str) -> Option<Duration> {
+    if input
+        .trim_end_matches(|c: char| c.is_ascii_digit())
+        .ends_with('h')
+        && input.ends_with(|c: char| c.is_ascii_digit())
+    {
+        return parse_duration(&format!("{input}m"));
+    }
+    parse_duration(input)
+}
+
+/// Like `18:00` or `9:30`
+pub fn parse_time(input: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(input, "%H:%M").ok()
+}
+
+/// The next time it is `at` in that timezone, tomorrow when it's already
+/// past today
+pub fn next_at(tz: Tz, at: NaiveTime, now: DateTime<Utc>) -> DateTime<Utc> {
+    let today = now.with_timezone(&tz).naive_local().date();
+    let due = local(tz, today, at);
+    if due > now {
+        due
+    } else {
+        local(tz, today.succ(), at)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    fn mins(m: u64) -> Duration {
+        Duration::from_secs(m * 60)
+    }
+
+    #[test]
+    async fn test_parse_delay() {
+        for (input, expected) in [
+            ("45m", mins(45)),
+            ("2h30", mins(150)),
+            ("2h30m", mins(150)),
+            ("2h", mins(120)),
+            ("1h05", mins(65)),
+            ("90s", Duration::from_secs(90)),
+        ] {
+            assert_eq!(parse_delay(input), Some(expected), "{input}");
+        }
+        for invalid in ["", "45", "2h30x", "30m15", "h30", "0m", "demain"] {
+            assert_eq!(parse_delay(invalid), None, "{invalid:?}");
+        }
+    }
+
+    #[test]
+    async fn test_parse_time() {
+        assert_eq!(parse_time("18:00"), Some(NaiveTime::from_hms(18, 0, 0)));
+        assert_eq!(parse_time("9:30"), Some(NaiveTime::from_hms(9, 30, 0)));
+        for invalid in ["24:00", "18h00", "18", "18:60", "apéro"] {
+            assert_eq!(parse_time(invalid), None, "{invalid:?}");
+        }
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        let add = |when, text| Some(Ok::<_, String>(RemindCommand::Add { when, text }));
+        let usage = Some(Err(USAGE.to_string()));
+        for (input, expected) in [
+            ("λremind 45m stand-up", add(When::In(mins(45)), "stand-up")),
+            (
+                "λremind me in 2h30 check the oven",
+                add(When::In(mins(150)), "check the oven"),
+            ),
+            (
+                "λremind at 18:00 apéro",
+                add(When::At(NaiveTime::from_hms(18, 0, 0)), "apéro"),
+            ),
+            (
+                "λremind me à 9:30  café > bob",
+                add(When::At(NaiveTime::from_hms(9, 30, 0)), "café > bob"),
+            ),
+            ("λremind dans 10m thé", add(When::In(mins(10)), "thé")),
+            ("λremind list", Some(Ok(RemindCommand::List))),
+            ("λremind cancel #4", Some(Ok(RemindCommand::Cancel(4)))),
+            ("λremind cancel four", usage.clone()),
+            ("λremind 45m", usage.clone()),
+            ("λremind at 25:00 apéro", usage.clone()),
+            ("λremind tomorrow apéro", usage.clone()),
+            ("λremind", usage.clone()),
+            ("λreminder 45m stand-up", None),
+        ] {
+            assert_eq!(parse_command(input), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    async fn test_next_at() {
+        let paris: Tz = "Europe/Paris".parse().unwrap();
+        let at = |h, m| NaiveTime::from_hms(h, m, 0);
+        // 16:00 in Paris
+        let now = Utc.ymd(2025, 3, 1).and_hms(15, 0, 0);
+        assert_eq!(
+            next_at(paris, at(18, 0), now),
+            Utc.ymd(2025, 3, 1).and_hms(17, 0, 0)
+        );
+        assert_eq!(
+            next_at(paris, at(9, 30), now),
+            Utc.ymd(2025, 3, 2).and_hms(8, 30, 0),
+            "already past today"
+        );
+        assert_eq!(
+            next_at(paris, at(16, 0), now),
+            Utc.ymd(2025, 3, 2).and_hms(15, 0, 0),
+            "right now is past"
+        );
+        // 00:30 in Paris, still the day before in UTC
+        let now = Utc.ymd(2025, 3, 1).and_hms(23, 30, 0);
+        assert_eq!(
+            next_at(paris, at(8, 0), now),
+            Utc.ymd(2025, 3, 2).and_hms(7, 0, 0),
+            "the local day"
+        );
+        // 02:30 doesn't exist on the change to the summer time
+        let now = Utc.ymd(2025, 3, 29).and_hms(12, 0, 0);
+        assert_eq!(
+            next_at(paris, at(2, 30), now),
+            Utc.ymd(2025, 3, 30).and_hms(1, 30, 0)
+        );
+    }
+}
@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use plugin_core::{CaseMapping, Database, Delayed, Outbound};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::utils::time::local;
+
+/// The timezone of the announcement times, unless the config says otherwise
+pub const DEFAULT_TIMEZONE: &str = "Europe/Paris";
+
+/// Longest sleep between two looks at the clock, in case it jumps
+const MAX_SLEEP: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// The tables of the republican_calendar plugin in the shared database, see
+/// `plugin_core::ensure_schema`
+const MIGRATIONS: &[&str] = &[
+    // day like 2025-03-01, in the timezone of the announcements
+    "CREATE TABLE republican_calendar_announced (
+        channel TEXT PRIMARY KEY NOT NULL,
+        day TEXT NOT NULL
+    );",
+];
+
+/// An entry of the `announce` list of the republican_calendar config section
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Announce {
+    pub channel: String,
+    /// like 08:30, in the configured timezone
+    pub at: String,
+}
+
+impl Announce {
+    pub fn check(&self) -> anyhow::Result<()> {
+        self.time().map(|_| ())
+    }
+
+    fn time(&self) -> anyhow::Result<NaiveTime> {
+        NaiveTime::parse_from_str(&self.at, "%H:%M").map_err(|_| {
+            anyhow!(
+                "republican_calendar.announce: invalid time {:?} for {}, expected HH:MM like 08:30",
+                self.at,
+                self.channel
+            )
+        })
+    }
+}
+
+/// When the announcement at that local time is due next. Right away when
+/// not made yet today even though it's past the time, like after a restart.
+fn due(tz: Tz, at: NaiveTime, last: Option<NaiveDate>, now: DateTime<Utc>) -> DateTime<Utc> {
+    let today = now.with_timezone(&tz).naive_local().date();
+    if last.map_or(true, |last| last < today) {
+        return local(tz, today, at);
+    }
+    local(tz, today.succ(), at)
+}
+
+#[derive(QueryableByName)]
+struct Row {
+    #[sql_type = "Text"]
+    channel: String,
+    #[sql_type = "Text"]
+    day: String,
+}
+
+/// The last day announced in each channel, also in the database when there's
+/// one so that a restart doesn't announce it again
+pub struct Announced {
+    db: Option<Database>,
+    /// by channel, normalized with the default casemapping as the
+    /// announcements aren't tied to a network
+    last: Mutex<HashMap<String, NaiveDate>>,
+}
+
+impl Announced {
+    /// Create the table if needed, and load the days announced before the last restart
+    pub fn load(db: Option<Database>) -> plugin_core::Result<Self> {
+        let mut last = HashMap::new();
+        if let Some(db) = &db {
+            plugin_core::ensure_schema(db, "republican_calendar", MIGRATIONS)?;
+            let rows = db.with_connection(|conn| {
+                diesel::sql_query("SELECT channel, day FROM republican_calendar_announced")
+                    .load::<Row>(conn)
+            })?;
+            for row in rows {
+                match row.day.parse::<NaiveDate>() {
+                    Ok(day) => {
+                        last.insert(row.channel, day);
+                    }
+                    Err(err) => log::warn!(
+                        "Ignoring the last announcement in {} on {:?}: {err}",
+                        row.channel,
+                        row.day
+                    ),
+                }
+            }
+        }
+        Ok(Announced {
+            db,
+            last: Mutex::new(last),
+        })
+    }
+
+    fn last(&self, channel: &str) -> Option<NaiveDate> {
+        self.last
+            .lock()
+            .expect("announced lock")
+            .get(&CaseMapping::default().normalize(channel))
+            .copied()
+    }
+
+    fn record(&self, channel: &str, day: NaiveDate) -> plugin_core::Result<()> {
+        let channel = CaseMapping::default().normalize(channel);
+        if let Some(db) = &self.db {
+            db.with_connection(|conn| {
+                diesel::sql_query(
+                    "INSERT OR REPLACE INTO republican_calendar_announced (channel, day) VALUES (?, ?)",
+                )
+                .bind::<Text, _>(&channel)
+                .bind::<Text, _>(day.to_string())
+                .execute(conn)
+            })?;
+        }
+        self.last
+            .lock()
+            .expect("announced lock")
+            .insert(channel, day);
+        Ok(())
+    }
+}
+
+/// The daily announcements of the date
+pub struct Announcer {
+    /// the channels with their time, checked by the config
+    announces: Vec<(String, NaiveTime)>,
+    tz: Tz,
+    announced: Announced,
+}
+
+impl Announcer {
+    pub fn new(announces: &[Announce], tz: Tz, announced: Announced) -> anyhow::Result<Self> {
+        let announces = announces
+            .iter()
+            .map(|announce| Ok((announce.channel.clone(), announce.time()?)))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Announcer {
+            announces,
+            tz,
+            announced,
+        })
+    }
+
+    /// Announces the day with `message` of the local time in each channel
+    /// once a day at its time, forever. `now` is the wall clock.
+    pub async fn run(
+        &self,
+        bot_chan: &mpsc::Sender<Outbound>,
+        message: impl Fn(NaiveDateTime) -> String,
+        now: impl Fn() -> DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        if self.announces.is_empty() {
+            // nothing to announce
+            return futures::future::pending().await;
+        }
+        let announcements = Delayed::new();
+        loop {
+            // scheduled again after every announcement, and at least every
+            // MAX_SLEEP in case the clock jumps
+            let current = now();
+            announcements.retain(|_| false);
+            for (channel, at) in &self.announces {
+                let due_at = due(self.tz, *at, self.announced.last(channel), current);
+                let delay = (due_at - current).to_std().unwrap_or_default();
+                announcements.push(Instant::now() + delay, channel);
+            }
+            let channels = match tokio::time::timeout(MAX_SLEEP, announcements.due()).await {
+                Ok(channels) => channels,
+                Err(_) => continue,
+            };
+            let local = now().with_timezone(&self.tz).naive_local();
+            for channel in channels {
+                bot_chan
+                    .send(Outbound::reply(channel, message(local)))
+                    .await?;
+                self.announced.record(channel, local.date())?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    fn announce(channel: &str, at: &str) -> Announce {
+        Announce {
+            channel: channel.to_string(),
+            at: at.to_string(),
+        }
+    }
+
+    fn utc(month: u32, day: u32, hour: u32, min: u32) -> DateTime<Utc> {
+        Utc.ymd(2025, month, day).and_hms(hour, min, 0)
+    }
+
+    fn day(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2025, month, day)
+    }
+
+    fn paris() -> Tz {
+        chrono_tz::Europe::Paris
+    }
+
+    /// The wall clock from `start`, following the paused tokio clock
+    fn clock(start: DateTime<Utc>) -> impl Fn() -> DateTime<Utc> {
+        let started = Instant::now();
+        move || start + chrono::Duration::from_std(started.elapsed()).unwrap()
+    }
+
+    fn drain(rx: &mut mpsc::Receiver<Outbound>) -> Vec<Outbound> {
+        let mut sent = vec![];
+        while let Ok(outbound) = rx.try_recv() {
+            sent.push(outbound);
+        }
+        sent
+    }
+
+    fn minutes(m: u64) -> std::time::Duration {
+        std::time::Duration::from_secs(m * 60)
+    }
+
+    #[test]
+    async fn test_check() {
+        for at in ["08:30", "00:00", "23:59", "8:30"] {
+            assert!(announce("#rust", at).check().is_ok(), "{at}");
+        }
+        for at in ["24:00", "08h30", "08:30:00", "", "noon"] {
+            assert!(announce("#rust", at).check().is_err(), "{at}");
+        }
+    }
+
+    #[test]
+    async fn test_due() {
+        let nine = NaiveTime::from_hms(9, 0, 0);
+        // 08:00 in Paris
+        let now = utc(3, 1, 7, 0);
+        assert_eq!(due(paris(), nine, None, now), utc(3, 1, 8, 0));
+        assert_eq!(due(paris(), nine, Some(day(2, 28)), now), utc(3, 1, 8, 0));
+        assert_eq!(
+            due(paris(), nine, Some(day(3, 1)), now),
+            utc(3, 2, 8, 0),
+            "already made today"
+        );
+
+        // 11:00 in Paris
+        let now = utc(3, 1, 10, 0);
+        assert_eq!(
+            due(paris(), nine, None, now),
+            utc(3, 1, 8, 0),
+            "overdue, right away"
+        );
+        assert_eq!(due(paris(), nine, Some(day(3, 1)), now), utc(3, 2, 8, 0));
+
+        // already the 2nd in Paris
+        let now = utc(3, 1, 23, 30);
+        assert_eq!(due(paris(), nine, Some(day(3, 1)), now), utc(3, 2, 8, 0));
+
+        // in summer time
+        assert_eq!(
+            due(paris(), nine, Some(day(3, 30)), utc(3, 30, 12, 0)),
+            utc(3, 31, 7, 0)
+        );
+        // 02:30 doesn't exist on the 30th of March in Paris
+        let half_past_two = NaiveTime::from_hms(2, 30, 0);
+        assert_eq!(
+            due(paris(), half_past_two, Some(day(3, 29)), utc(3, 29, 23, 0)),
+            utc(3, 30, 1, 30)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run() {
+        let announcer = Announcer::new(
+            &[announce("#rust", "09:00"), announce("#ocaml", "12:00")],
+            paris(),
+            Announced::load(None).unwrap(),
+        )
+        .unwrap();
+        let (tx, mut rx) = mpsc::channel(10);
+        // 08:00 in Paris
+        let now = clock(utc(3, 1, 7, 0));
+        let until = |m| {
+            tokio::time::timeout(
+                minutes(m),
+                announcer.run(&tx, |now| now.date().to_string(), &now),
+            )
+        };
+
+        assert!(until(59).await.is_err(), "still running");
+        assert_eq!(drain(&mut rx), vec![]);
+
+        assert!(until(2).await.is_err());
+        assert_eq!(drain(&mut rx), vec![Outbound::reply("#rust", "2025-03-01")]);
+
+        assert!(until(24 * 60).await.is_err());
+        assert_eq!(
+            drain(&mut rx),
+            vec![
+                Outbound::reply("#ocaml", "2025-03-01"),
+                Outbound::reply("#rust", "2025-03-02"),
+            ],
+            "once a day"
+        );
+
+        let (tx, mut rx) = mpsc::channel(10);
+        let announcer = Announcer::new(&[], paris(), Announced::load(None).unwrap()).unwrap();
+        assert!(tokio::time::timeout(
+            minutes(48 * 60),
+            announcer.run(&tx, |now| now.date().to_string(), &now)
+        )
+        .await
+        .is_err());
+        assert_eq!(drain(&mut rx), vec![], "nothing to announce");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_restart() {
+        let db = Database::in_memory().unwrap();
+        let announcer = |db: &Database| {
+            Announcer::new(
+                &[announce("#rust", "09:00")],
+                paris(),
+                Announced::load(Some(db.clone())).unwrap(),
+            )
+            .unwrap()
+        };
+        let (tx, mut rx) = mpsc::channel(10);
+        // 09:30 in Paris
+        let now = clock(utc(3, 1, 8, 30));
+
+        let first = announcer(&db);
+        assert!(tokio::time::timeout(
+            minutes(1),
+            first.run(&tx, |now| now.date().to_string(), &now)
+        )
+        .await
+        .is_err());
+        assert_eq!(
+            drain(&mut rx),
+            vec![Outbound::reply("#rust", "2025-03-01")],
+            "started after the time, not announced yet today"
+        );
+        drop(first);
+
+        let second = announcer(&db);
+        assert!(tokio::time::timeout(
+            minutes(60),
+            second.run(&tx, |now| now.date().to_string(), &now)
+        )
+        .await
+        .is_err());
+        assert_eq!(drain(&mut rx), vec![], "already announced today");
+
+        let fresh = Announcer::new(
+            &[announce("#rust", "09:00")],
+            paris(),
+            Announced::load(Some(Database::in_memory().unwrap())).unwrap(),
+        )
+        .unwrap();
+        assert!(tokio::time::timeout(
+            minutes(1),
+            fresh.run(&tx, |now| now.date().to_string(), &now)
+        )
+        .await
+        .is_err());
+        assert_eq!(
+            drain(&mut rx),
+            vec![Outbound::reply("#rust", "2025-03-01")],
+            "another database"
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use chrono::{NaiveTime, Timelike};
+
+const NANOS_PER_DAY: u128 = 86_400 * 1_000_000_000;
+/// 10 hours of 100 minutes of 100 seconds
+const DECIMAL_SECONDS_PER_DAY: u128 = 100_000;
+
+/// The decimal hours, minutes and seconds at that time of the day. Truncated,
+/// for the last instants of a day to never be 10h of that same day.
+pub fn decimal_time(time: NaiveTime) -> (u32, u32, u32) {
+    let nanos = u128::from(time.num_seconds_from_midnight()) * 1_000_000_000
+        + u128::from(time.nanosecond());
+    // a leap second is counted in the last instant of the day
+    let nanos = nanos.min(NANOS_PER_DAY - 1);
+    let seconds = (nanos * DECIMAL_SECONDS_PER_DAY / NANOS_PER_DAY) as u32;
+    (seconds / 10_000, seconds / 100 % 100, seconds % 100)
+}
+
+/// Like `5h 62m 52s décimales (≙ 13:30:02)`
+pub fn format(time: NaiveTime) -> String {
+    let (hours, minutes, seconds) = decimal_time(time);
+    format!(
+        "{hours}h {minutes:02}m {seconds:02}s décimales (≙ {})",
+        time.format("%H:%M:%S")
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_decimal_time() {
+        for ((h, m, s, nano), expected) in [
+            ((0, 0, 0, 0), (0, 0, 0)),
+            ((6, 0, 0, 0), (2, 50, 0)),
+            ((12, 0, 0, 0), (5, 0, 0)),
+            ((13, 30, 2, 0), (5, 62, 52)),
+            ((18, 0, 0, 0), (7, 50, 0)),
+            ((0, 0, 0, 863_999_999), (0, 0, 0)),
+            ((0, 0, 0, 864_000_000), (0, 0, 1)),
+            ((23, 59, 59, 0), (9, 99, 98)),
+            ((23, 59, 59, 900_000_000), (9, 99, 99)),
+            ((23, 59, 59, 999_999_999), (9, 99, 99)),
+        ] {
+            let time = NaiveTime::from_hms_nano(h, m, s, nano);
+            assert_eq!(decimal_time(time), expected, "{time}");
+        }
+        let leap_second = NaiveTime::from_hms_nano(23, 59, 59, 1_500_000_000);
+        assert_eq!(decimal_time(leap_second), (9, 99, 99));
+    }
+
+    #[test]
+    async fn test_format() {
+        assert_eq!(
+            format(NaiveTime::from_hms(13, 30, 2)),
+            "5h 62m 52s décimales (≙ 13:30:02)"
+        );
+        assert_eq!(
+            format(NaiveTime::from_hms_milli(0, 2, 3, 400)),
+            "0h 01m 42s décimales (≙ 00:02:03)"
+        );
+    }
+}
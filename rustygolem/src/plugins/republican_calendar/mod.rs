@@ -0,0 +1,6 @@
+mod announce;
+mod decimal;
+mod plugin;
+mod template;
+
+pub use plugin::RepublicanCalendar;
@@ -0,0 +1,676 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use irc::proto::{Command, Message};
+use plugin_core::utils::network::set_network;
+use plugin_core::utils::parser;
+use plugin_core::{CommandHelp, Initialised, Outbound, Plugin, Result};
+use republican_calendar::SextileRule;
+use serde::Deserialize;
+use time::Date;
+use tokio::sync::mpsc;
+
+use super::announce::{self, Announce, Announced, Announcer};
+use super::decimal;
+use super::template::{self, Template};
+use crate::utils::messages::with_target;
+use crate::utils::time::parse_timezone;
+
+const USAGE: &str = "Usage: λcalendrier 2025-03-01, λcalendrier 01/03/2025, \
+     λcalendrier 9 ventôse 233, λcalendrier demain, λcalendrier -3 ou λcalendrier jour narcisse";
+
+/// λcalendrier and its aliases
+const CALENDRIER: &[&str] = &["calendrier", "cal", "jourrep"];
+
+/// Farther than any date anyway
+const MAX_OFFSET: i64 = 10_000 * 366;
+
+const MONTHS: [&str; 12] = [
+    "janvier",
+    "février",
+    "mars",
+    "avril",
+    "mai",
+    "juin",
+    "juillet",
+    "août",
+    "septembre",
+    "octobre",
+    "novembre",
+    "décembre",
+];
+
+/// The `republican_calendar` section of the golem config
+#[derive(Deserialize)]
+struct Settings {
+    /// tell the date in every channel the golem joins
+    #[serde(default)]
+    greet_on_join: bool,
+    /// which years are sextile after the year 14
+    #[serde(default)]
+    sextile_rule: SextileRule,
+    /// tell the date every day in these channels
+    #[serde(default)]
+    announce: Vec<Announce>,
+    /// of the announcement times, λheure and the date of the day
+    #[serde(default = "default_timezone")]
+    timezone: String,
+    /// with the decimal time in the daily announcements
+    #[serde(default)]
+    decimal_time: bool,
+    /// how the date of the day is told, see `Template`
+    #[serde(default = "default_template")]
+    template: String,
+}
+
+fn default_template() -> String {
+    template::DEFAULT.to_string()
+}
+
+fn default_timezone() -> String {
+    announce::DEFAULT_TIMEZONE.to_string()
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            greet_on_join: false,
+            sextile_rule: SextileRule::default(),
+            announce: vec![],
+            timezone: default_timezone(),
+            decimal_time: false,
+            template: default_template(),
+        }
+    }
+}
+
+impl Settings {
+    /// The timezone and the template are parsed by `init`, once
+    fn load(config: &plugin_core::Config) -> Result<Self> {
+        let settings: Settings = config
+            .plugin_section("republican_calendar")?
+            .unwrap_or_default();
+        for announce in &settings.announce {
+            announce.check()?;
+        }
+        Ok(settings)
+    }
+}
+
+pub struct RepublicanCalendar {
+    greet_on_join: bool,
+    sextile_rule: SextileRule,
+    tz: Tz,
+    announcer: Announcer,
+    decimal_time: bool,
+    template: Template,
+}
+
+#[async_trait]
+impl Plugin for RepublicanCalendar {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        let settings = Settings::load(config)?;
+        parse_timezone("republican_calendar", &settings.timezone)?;
+        Template::parse(&settings.template)?;
+        if !settings.announce.is_empty() {
+            config.check_database("republican_calendar")?;
+        }
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let settings = Settings::load(config)?;
+        // the days announced, to not announce them again after a restart
+        let db = if settings.announce.is_empty() {
+            None
+        } else {
+            Some(config.require_database("republican_calendar")?)
+        };
+        let tz = parse_timezone("republican_calendar", &settings.timezone)?;
+        let announcer = Announcer::new(&settings.announce, tz, Announced::load(db)?)?;
+        Ok(Initialised::from(RepublicanCalendar {
+            greet_on_join: settings.greet_on_join,
+            sextile_rule: settings.sextile_rule,
+            tz,
+            announcer,
+            decimal_time: settings.decimal_time,
+            template: Template::parse(&settings.template)?,
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "republican_calendar"
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Outbound>> {
+        in_msg(self, msg).await
+    }
+
+    async fn run(&self, bot_chan: mpsc::Sender<Outbound>) -> Result<()> {
+        Ok(self
+            .announcer
+            .run(&bot_chan, |now| announcement(self, now), Utc::now)
+            .await?)
+    }
+
+    async fn on_self_join(&self, network: &str, channel: &str) -> Result<Vec<Outbound>> {
+        if !self.greet_on_join {
+            return Ok(vec![]);
+        }
+        Ok(handle_command(self, None)
+            .map(|text| {
+                let mut msg = Message::from(Outbound::reply(channel, text));
+                set_network(&mut msg, network);
+                Outbound::Raw(msg)
+            })
+            .into_iter()
+            .collect())
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("date").description("La date du jour dans le calendrier républicain"),
+            CommandHelp::new("calendrier")
+                .usage("calendrier <date> [> nick]")
+                .description(
+                    "Convertit une date grégorienne, comme 2025-03-01, 01/03/2025, demain, \
+                     hier ou +3, ou républicaine, comme 9 ventôse 233",
+                )
+                .aliases(&CALENDRIER[1..]),
+            CommandHelp::new("heure")
+                .description("L'heure décimale, de 10 heures de 100 minutes de 100 secondes"),
+            CommandHelp::new("calendrier jour")
+                .usage("calendrier jour <plante, animal ou outil>")
+                .description("Le prochain jour dédié à une plante, un animal ou un outil"),
+        ]
+    }
+}
+
+async fn in_msg(plugin: &RepublicanCalendar, msg: &Message) -> Result<Option<Outbound>> {
+    let rule = plugin.sextile_rule;
+    let response_target = match msg.response_target() {
+        None => return Ok(None),
+        Some(target) => target,
+    };
+
+    if let Command::PRIVMSG(_source, privmsg) = &msg.command {
+        if let Some(mb_target) = parser::single_command("date", privmsg) {
+            let msg = handle_command(plugin, mb_target).context("republican calendar")?;
+
+            return Ok(Some(Outbound::reply(response_target, msg)));
+        }
+        if let Some(mb_target) = parser::single_command("heure", privmsg) {
+            let now = Utc::now().with_timezone(&plugin.tz).time();
+            let msg = with_target(&decimal::format(now), &mb_target);
+            return Ok(Some(Outbound::reply(response_target, msg)));
+        }
+        if let Ok((_, (args, mb_target))) = parser::command_in(CALENDRIER)(privmsg) {
+            let today =
+                today(plugin.tz).map_err(|e| plugin_core::Error::Synthetic(e.to_string()))?;
+            let msg = if args.is_empty() {
+                handle_command(plugin, mb_target).context("republican calendar")?
+            } else if let Some(item) = args.strip_prefix("jour ") {
+                with_target(&find_day(rule, today, item), &mb_target)
+            } else {
+                with_target(&convert(rule, today, args), &mb_target)
+            };
+            return Ok(Some(Outbound::reply(response_target, msg)));
+        }
+    }
+    Ok(None)
+}
+
+/// From the gregorian calendar to the republican one, or the other way
+/// around. The gregorian date can be relative to today, like `demain`.
+fn convert(rule: SextileRule, today: Date, input: &str) -> String {
+    let converted = match (offset(input), parse_gregorian(input)) {
+        (Some(days), _) => today
+            .checked_add(time::Duration::days(days))
+            .ok_or(republican_calendar::Error::OutOfRange)
+            .and_then(|date| from_gregorian(rule, date)),
+        (None, Some(date)) => from_gregorian(rule, date),
+        (None, None) => rule.parse(input).and_then(|rd| {
+            let date = rule.to_gregorian(&rd)?;
+            Ok(format!(
+                "Le {} correspond au {}",
+                rd.short(),
+                format_gregorian(date)
+            ))
+        }),
+    };
+    match converted {
+        Ok(msg) => msg,
+        Err(republican_calendar::Error::Unparseable) => USAGE.to_string(),
+        Err(err) => err.to_string(),
+    }
+}
+
+fn from_gregorian(
+    rule: SextileRule,
+    date: Date,
+) -> std::result::Result<String, republican_calendar::Error> {
+    rule.to_republican(date)
+        .map(|rd| format!("Le {} correspond au {rd}", format_gregorian(date)))
+}
+
+/// Days from today, like 1 for `demain` or -3 for `-3`
+fn offset(input: &str) -> Option<i64> {
+    let days = match input.to_lowercase().as_str() {
+        "aujourd'hui" => 0,
+        "demain" => 1,
+        "après-demain" => 2,
+        "hier" => -1,
+        "avant-hier" => -2,
+        other => {
+            let (sign, digits) = match (other.strip_prefix('+'), other.strip_prefix('-')) {
+                (Some(digits), _) => (1, digits),
+                (_, Some(digits)) => (-1, digits),
+                _ => return None,
+            };
+            if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            sign * digits.parse::<i64>().unwrap_or(MAX_OFFSET).min(MAX_OFFSET)
+        }
+    };
+    Some(days)
+}
+
+/// The next day dedicated to the item, from today included
+fn find_day(rule: SextileRule, today: Date, item: &str) -> String {
+    let (month, day) = match republican_calendar::find(item) {
+        Some(found) => found,
+        None => return format!("Aucun jour n'est dédié à {}", item.trim()),
+    };
+    let year = match rule.to_republican(today) {
+        Ok(rd) => rd.year(),
+        Err(err) => return err.to_string(),
+    };
+    // the 6th sans-culottide can be up to 4 years away
+    let next = (year..=year + 5)
+        .filter_map(|y| rule.date(y, month, day).ok())
+        .find_map(|rd| Some((rd, rule.to_gregorian(&rd).ok()?)).filter(|(_, d)| *d >= today));
+    match next {
+        Some((rd, date)) => format!(
+            "Le prochain jour {} est le {}, soit le {}",
+            rd.day_symbol(),
+            rd.short(),
+            format_gregorian(date)
+        ),
+        None => republican_calendar::Error::OutOfRange.to_string(),
+    }
+}
+
+/// `2025-03-01`, or the other way around like `01/03/2025`, `1-3-2025` or `01.03.2025`
+fn parse_gregorian(input: &str) -> Option<Date> {
+    let parts = input
+        .trim()
+        .split(|c| c == '-' || c == '/' || c == '.')
+        .collect::<Vec<_>>();
+    let (year, month, day) = match parts.as_slice() {
+        [year, month, day] if year.len() == 4 => (*year, *month, *day),
+        [day, month, year] if year.len() == 4 => (*year, *month, *day),
+        _ => return None,
+    };
+    let number = |part: &str| {
+        if part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        part.parse::<u16>().ok()
+    };
+    let month = time::Month::try_from(u8::try_from(number(month)?).ok()?).ok()?;
+    let day = u8::try_from(number(day)?).ok()?;
+    Date::from_calendar_date(i32::from(number(year)?), month, day).ok()
+}
+
+/// Like `1er mars 2025`
+fn format_gregorian(date: Date) -> String {
+    let day = match date.day() {
+        1 => "1er".to_string(),
+        day => day.to_string(),
+    };
+    let month = MONTHS[usize::from(u8::from(date.month())) - 1];
+    format!("{day} {month} {}", date.year())
+}
+
+/// The date of the day in the timezone of the announcements, not in UTC
+fn today(tz: Tz) -> std::result::Result<Date, republican_calendar::Error> {
+    to_date(Utc::now().with_timezone(&tz).naive_local().date())
+}
+
+fn to_date(day: NaiveDate) -> std::result::Result<Date, republican_calendar::Error> {
+    Date::from_ordinal_date(day.year(), day.ordinal() as u16)
+        .map_err(|_| republican_calendar::Error::OutOfRange)
+}
+
+/// The date of that day told every day, with the decimal time if asked for
+fn announcement(plugin: &RepublicanCalendar, now: NaiveDateTime) -> String {
+    let date = to_date(now.date()).and_then(|date| plugin.sextile_rule.to_republican(date));
+    match date {
+        Ok(rd) if plugin.decimal_time => format!(
+            "{}, il est {}",
+            plugin.template.render(&rd),
+            decimal::format(now.time())
+        ),
+        Ok(rd) => plugin.template.render(&rd),
+        Err(err) => err.to_string(),
+    }
+}
+
+pub(crate) fn handle_command(
+    plugin: &RepublicanCalendar,
+    mb_target: Option<&str>,
+) -> Option<String> {
+    let msg = match today(plugin.tz).and_then(|today| plugin.sextile_rule.to_republican(today)) {
+        Ok(rd) => crate::utils::messages::with_target(&plugin.template.render(&rd), &mb_target),
+        Err(err) => err.to_string(),
+    };
+    Some(msg)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn plugin(decimal_time: bool, template: &str) -> RepublicanCalendar {
+        let tz = parse_timezone("republican_calendar", announce::DEFAULT_TIMEZONE).unwrap();
+        RepublicanCalendar {
+            greet_on_join: false,
+            sextile_rule: SextileRule::Romme,
+            tz,
+            announcer: Announcer::new(&[], tz, Announced::load(None).unwrap()).unwrap(),
+            decimal_time,
+            template: Template::parse(template).unwrap(),
+        }
+    }
+
+    fn date(year: i32, month: time::Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    /// 11 Ventôse 233
+    fn today() -> Date {
+        date(2025, time::Month::March, 1)
+    }
+
+    #[test]
+    async fn test_today() {
+        // UTC+14 and UTC-10, a day apart whatever the time
+        let ahead = today("Pacific/Kiritimati".parse().unwrap()).unwrap();
+        let behind = today("Pacific/Honolulu".parse().unwrap()).unwrap();
+        assert_eq!(ahead, behind + time::Duration::days(1));
+    }
+
+    #[test]
+    async fn test_parse_gregorian() {
+        use time::Month::*;
+        for input in [
+            "2025-03-01",
+            "01/03/2025",
+            "1/3/2025",
+            "01-03-2025",
+            "1.03.2025",
+        ] {
+            assert_eq!(
+                parse_gregorian(input),
+                Some(date(2025, March, 1)),
+                "{input}"
+            );
+        }
+        for invalid in [
+            "2025-02-30",
+            "2025-13-01",
+            "03/01/25",
+            "2025/03",
+            "2025-3-1-1",
+            "2025-+3-01",
+            "9 ventôse 233",
+            "",
+        ] {
+            assert_eq!(parse_gregorian(invalid), None, "{invalid:?}");
+        }
+    }
+
+    #[test]
+    async fn test_convert() {
+        assert_eq!(
+            convert(SextileRule::Romme, today(), "2025-03-01"),
+            "Le 1er mars 2025 correspond au 11 Ventôse 233 − jour du narcisse − et c'est un Primedi"
+        );
+        assert_eq!(
+            convert(SextileRule::Romme, today(), "09/11/1799"),
+            "Le 9 novembre 1799 correspond au 18 Brumaire 8 − jour de la dentelaire − et c'est un Octidi"
+        );
+        assert_eq!(
+            convert(SextileRule::Romme, today(), "9 ventôse an CCXXXIII"),
+            "Le 9 Ventôse 233 correspond au 27 février 2025"
+        );
+        assert_eq!(
+            convert(SextileRule::Romme, today(), "14/07/1789"),
+            "Le calendrier républicain commence le 22 septembre 1792, le 1 Vendémiaire 1"
+        );
+        assert_eq!(
+            convert(SextileRule::Romme, today(), "6 sans-culottides 233"),
+            "Ce jour n'existe pas dans le calendrier républicain"
+        );
+        assert_eq!(
+            convert(SextileRule::Romme, today(), "demain"),
+            "Le 2 mars 2025 correspond au 12 Ventôse 233 − jour de l'orme − et c'est un Duodi"
+        );
+        for unparseable in ["18 novembre 1799", "2025-02-30"] {
+            assert_eq!(
+                convert(SextileRule::Romme, today(), unparseable),
+                USAGE,
+                "{unparseable}"
+            );
+        }
+    }
+
+    #[test]
+    async fn test_find_day() {
+        use time::Month::*;
+        let rule = SextileRule::Romme;
+        assert_eq!(
+            find_day(rule, date(2025, February, 1), "Narcisse"),
+            "Le prochain jour du narcisse est le 11 Ventôse 233, soit le 1er mars 2025"
+        );
+        assert_eq!(
+            find_day(rule, date(2025, March, 1), "narcisse"),
+            "Le prochain jour du narcisse est le 11 Ventôse 233, soit le 1er mars 2025",
+            "today"
+        );
+        assert_eq!(
+            find_day(rule, date(2025, March, 2), "narcisse"),
+            "Le prochain jour du narcisse est le 11 Ventôse 234, soit le 1er mars 2026"
+        );
+        assert_eq!(
+            find_day(rule, date(2025, March, 2), "chataigne"),
+            "Le prochain jour de la châtaigne est le 3 Vendémiaire 234, soit le 24 septembre 2025"
+        );
+        assert_eq!(
+            find_day(rule, date(2025, March, 2), "révolution"),
+            "Le prochain jour de la révolution est le 6 Sans-Culottides 236, soit le 21 septembre 2028",
+            "only in the sextile years"
+        );
+        assert_eq!(
+            find_day(rule, date(2025, March, 2), " licorne "),
+            "Aucun jour n'est dédié à licorne"
+        );
+    }
+
+    #[test]
+    async fn test_announcement() {
+        let at = |year, month, day| chrono::NaiveDate::from_ymd(year, month, day).and_hms(9, 0, 0);
+        assert_eq!(
+            announcement(&plugin(false, template::DEFAULT), at(2025, 3, 1)),
+            "Nous sommes aujourd'hui le 11 Ventôse 233 − jour du narcisse − et c'est un Primedi"
+        );
+        assert_eq!(
+            announcement(&plugin(true, template::DEFAULT), at(2025, 3, 1)),
+            "Nous sommes aujourd'hui le 11 Ventôse 233 − jour du narcisse − et c'est un Primedi, \
+             il est 3h 75m 00s décimales (≙ 09:00:00)"
+        );
+        assert_eq!(
+            announcement(&plugin(true, template::DEFAULT), at(1789, 7, 14)),
+            "Le calendrier républicain commence le 22 septembre 1792, le 1 Vendémiaire 1"
+        );
+        assert_eq!(
+            announcement(
+                &plugin(
+                    false,
+                    "Aujourd'hui : {day} {month} an {year_roman}, jour {rural_item}"
+                ),
+                at(2025, 2, 27)
+            ),
+            "Aujourd'hui : 9 Ventôse an CCXXXIII, jour du marceau"
+        );
+    }
+
+    #[test]
+    async fn test_offset() {
+        for (input, expected) in [
+            ("demain", Some(1)),
+            ("Demain", Some(1)),
+            ("hier", Some(-1)),
+            ("après-demain", Some(2)),
+            ("avant-hier", Some(-2)),
+            ("aujourd'hui", Some(0)),
+            ("+3", Some(3)),
+            ("-12", Some(-12)),
+            ("-0", Some(0)),
+            ("+99999999999999999999", Some(MAX_OFFSET)),
+            ("3", None),
+            ("+", None),
+            ("+3j", None),
+            ("+-3", None),
+            ("demain matin", None),
+        ] {
+            assert_eq!(offset(input), expected, "{input}");
+        }
+    }
+
+    #[test]
+    async fn test_convert_offsets() {
+        use time::Month::September;
+        let rule = SextileRule::Romme;
+        assert_eq!(
+            convert(rule, today(), "demain"),
+            "Le 2 mars 2025 correspond au 12 Ventôse 233 − jour de l'orme − et c'est un Duodi"
+        );
+        assert_eq!(
+            convert(rule, today(), "-1"),
+            "Le 28 février 2025 correspond au 10 Ventôse 233 − jour de la bêche − et c'est un Décadi"
+        );
+        // 233 isn't sextile
+        assert_eq!(
+            convert(rule, date(2025, September, 16), "demain"),
+            "Le 17 septembre 2025 correspond au 1 Sans-Culottides 233 − jour de la vertu − et c'est un Primedi"
+        );
+        assert_eq!(
+            convert(rule, date(2025, September, 16), "+6"),
+            "Le 22 septembre 2025 correspond au 1 Vendémiaire 234 − jour du raisin − et c'est un Primedi"
+        );
+        assert_eq!(
+            convert(rule, date(2025, September, 22), "hier"),
+            "Le 21 septembre 2025 correspond au 5 Sans-Culottides 233 − jour des récompenses − et c'est un Quintidi"
+        );
+        // 232 is sextile
+        assert_eq!(
+            convert(rule, date(2024, September, 20), "+1"),
+            "Le 21 septembre 2024 correspond au 6 Sans-Culottides 232 − jour de la révolution − et c'est un Sextidi"
+        );
+        assert_eq!(
+            convert(rule, date(2024, September, 20), "après-demain"),
+            "Le 22 septembre 2024 correspond au 1 Vendémiaire 233 − jour du raisin − et c'est un Primedi"
+        );
+        assert_eq!(
+            convert(rule, date(1792, September, 22), "hier"),
+            "Le calendrier républicain commence le 22 septembre 1792, le 1 Vendémiaire 1"
+        );
+        assert_eq!(
+            convert(rule, today(), "+99999999999999999999"),
+            "C'est bien trop loin"
+        );
+        for unparseable in ["+3j", "demain matin", "+"] {
+            assert_eq!(convert(rule, today(), unparseable), USAGE, "{unparseable}");
+        }
+    }
+
+    #[test]
+    async fn test_aliases_and_target() {
+        let plugin = &plugin(false, template::DEFAULT);
+        let said = |text: &str| {
+            let msg = Message::new(
+                Some("alice!~alice@localhost"),
+                "PRIVMSG",
+                vec!["#france", text],
+            )
+            .unwrap();
+            async move {
+                match in_msg(plugin, &msg).await.unwrap() {
+                    Some(Outbound::Reply { text, .. }) => Some(text),
+                    None => None,
+                    other => panic!("unexpected {other:?}"),
+                }
+            }
+        };
+        let converted = "Le 1er mars 2025 correspond au 11 Ventôse 233 − jour du narcisse − et c'est un Primedi";
+        for command in ["λcalendrier", "λcal", "λjourrep"] {
+            assert_eq!(
+                said(&format!("{command} 2025-03-01")).await,
+                Some(converted.to_string()),
+                "{command}"
+            );
+            assert_eq!(
+                said(&format!("{command} 2025-03-01 > bob")).await,
+                Some(format!("bob: {converted}")),
+                "{command}"
+            );
+        }
+        assert_eq!(said("λcalc 2025-03-01").await, None);
+        assert_eq!(
+            plugin
+                .commands()
+                .iter()
+                .filter(|help| help.name == "calendrier")
+                .map(|help| help.aliases)
+                .collect::<Vec<_>>(),
+            vec![&["cal", "jourrep"][..]],
+            "listed once, with the aliases"
+        );
+    }
+
+    #[test]
+    async fn test_convert_with_rule() {
+        assert_eq!(
+            convert(SextileRule::Romme, today(), "6 sans-culottides an XVI"),
+            "Le 6 Sans-Culottides 16 correspond au 22 septembre 1808"
+        );
+        assert_eq!(
+            convert(SextileRule::Continuous, today(), "6 sans-culottides an XVI"),
+            "Ce jour n'existe pas dans le calendrier républicain"
+        );
+        assert_eq!(
+            convert(SextileRule::Continuous, today(), "6 sans-culottides an XV"),
+            "Le 6 Sans-Culottides 15 correspond au 23 septembre 1807"
+        );
+    }
+
+    #[test]
+    async fn test_greet_on_join() {
+        let mut plugin = plugin(false, template::DEFAULT);
+        assert_eq!(plugin.on_self_join("oftc", "#rust").await.unwrap(), vec![]);
+        plugin.greet_on_join = true;
+        let greetings = plugin.on_self_join("oftc", "#rust").await.unwrap();
+        assert_eq!(greetings.len(), 1);
+        match &greetings[0] {
+            Outbound::Raw(msg) => {
+                assert_eq!(plugin_core::utils::network::network(msg), Some("oftc"));
+                assert!(matches!(&msg.command, Command::PRIVMSG(target, _) if target == "#rust"));
+            }
+            other => panic!("not tagged with the network {other:?}"),
+        }
+    }
+}
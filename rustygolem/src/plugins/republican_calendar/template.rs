@@ -0,0 +1,179 @@
+use republican_calendar::{to_roman, RepublicanDate};
+
+/// As told before the template was configurable
+pub const DEFAULT: &str =
+    "Nous sommes aujourd'hui le {day} {month} {year_arabic} − jour {rural_item} − et c'est un {day_name}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    /// like 9
+    Day,
+    /// like Ventôse
+    Month,
+    /// like ventôse
+    MonthLower,
+    /// like CCXXXIII
+    YearRoman,
+    /// like 233
+    YearArabic,
+    /// like du narcisse
+    RuralItem,
+    /// like Nonidi
+    DayName,
+}
+
+const PLACEHOLDERS: [(&str, Placeholder); 7] = [
+    ("day", Placeholder::Day),
+    ("month", Placeholder::Month),
+    ("month_lower", Placeholder::MonthLower),
+    ("year_roman", Placeholder::YearRoman),
+    ("year_arabic", Placeholder::YearArabic),
+    ("rural_item", Placeholder::RuralItem),
+    ("day_name", Placeholder::DayName),
+];
+
+#[derive(Debug, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+/// How the date of the day is told, like `{day} {month} an {year_roman}`
+#[derive(Debug, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> anyhow::Result<Self> {
+        let mut parts = vec![];
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(Part::Text(rest[..open].to_string()));
+            }
+            let after = &rest[open + 1..];
+            let close = after.find('}').ok_or_else(|| {
+                anyhow!(
+                    "Unclosed placeholder {} in the republican_calendar template",
+                    &rest[open..]
+                )
+            })?;
+            let name = &after[..close];
+            let (_, placeholder) = PLACEHOLDERS
+                .iter()
+                .find(|(known, _)| *known == name)
+                .ok_or_else(|| {
+                    let known = PLACEHOLDERS
+                        .iter()
+                        .map(|(known, _)| format!("{{{known}}}"))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    anyhow!(
+                        "Unknown placeholder {{{name}}} in the republican_calendar template, \
+                         expected one of {known}"
+                    )
+                })?;
+            parts.push(Part::Placeholder(*placeholder));
+            rest = &after[close + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Template { parts })
+    }
+
+    pub fn render(&self, date: &RepublicanDate) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Placeholder(Placeholder::Day) => date.day().to_string(),
+                Part::Placeholder(Placeholder::Month) => date.month_name(),
+                Part::Placeholder(Placeholder::MonthLower) => date.month_name().to_lowercase(),
+                Part::Placeholder(Placeholder::YearRoman) => match u32::try_from(date.year()) {
+                    Ok(year) => to_roman(year),
+                    Err(_) => date.year().to_string(),
+                },
+                Part::Placeholder(Placeholder::YearArabic) => date.year().to_string(),
+                Part::Placeholder(Placeholder::RuralItem) => date.day_symbol(),
+                Part::Placeholder(Placeholder::DayName) => date.day_name().to_string(),
+            })
+            .collect()
+    }
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Template::parse(DEFAULT).expect("a valid default template")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn ventose() -> RepublicanDate {
+        RepublicanDate::new(233, 6, 9).unwrap()
+    }
+
+    #[test]
+    async fn test_default() {
+        assert_eq!(
+            Template::default().render(&ventose()),
+            format!("Nous sommes aujourd'hui le {}", ventose()),
+            "as before the template"
+        );
+    }
+
+    #[test]
+    async fn test_render() {
+        for (template, expected) in [
+            ("{day} {month} an {year_roman}", "9 Ventôse an CCXXXIII"),
+            ("{day} {month_lower} {year_arabic}", "9 ventôse 233"),
+            (
+                "Aujourd'hui : {day} {month} an {year_roman}, jour {rural_item}",
+                "Aujourd'hui : 9 Ventôse an CCXXXIII, jour du marceau",
+            ),
+            ("{day_name}", "Nonidi"),
+            ("{day}{day}", "99"),
+            ("pas de date", "pas de date"),
+            ("", ""),
+        ] {
+            assert_eq!(
+                Template::parse(template).unwrap().render(&ventose()),
+                expected,
+                "{template}"
+            );
+        }
+        let sans_culottide = RepublicanDate::new(232, 13, 6).unwrap();
+        assert_eq!(
+            Template::parse("{day} {month_lower} an {year_roman}, jour {rural_item}")
+                .unwrap()
+                .render(&sans_culottide),
+            "6 sans-culottides an CCXXXII, jour de la révolution"
+        );
+    }
+
+    #[test]
+    async fn test_invalid() {
+        let err = |template| Template::parse(template).unwrap_err().to_string();
+        assert_eq!(
+            err("{day} {mois} {year_roman}"),
+            "Unknown placeholder {mois} in the republican_calendar template, expected one of \
+             {day}, {month}, {month_lower}, {year_roman}, {year_arabic}, {rural_item}, {day_name}"
+        );
+        assert_eq!(
+            err("{day} {Month}"),
+            "Unknown placeholder {Month} in the republican_calendar template, expected one of \
+             {day}, {month}, {month_lower}, {year_roman}, {year_arabic}, {rural_item}, {day_name}",
+            "case sensitive"
+        );
+        assert_eq!(
+            err("{day} {month"),
+            "Unclosed placeholder {month in the republican_calendar template"
+        );
+        assert!(Template::parse("{}").is_err());
+    }
+}
@@ -0,0 +1,269 @@
+use anyhow::{bail, Context};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// The elements of the entries, `item` in RSS and `entry` in Atom
+const ENTRIES: &[&str] = &["item", "entry"];
+
+/// An RSS 2.0 (or 1.0) or Atom feed, its entries in the order of the feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Feed {
+    pub title: String,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// the guid in RSS and the id in Atom, the link or the title otherwise
+    pub id: String,
+    pub title: String,
+    pub link: Option<String>,
+}
+
+/// The text of an entry, as it's read
+#[derive(Debug, Default)]
+struct Partial {
+    id: String,
+    title: String,
+    link: String,
+}
+
+impl Partial {
+    /// None for an entry without anything to tell it apart
+    fn finish(self) -> Option<Entry> {
+        let title = squash(&self.title);
+        let link = Some(self.link.trim().to_string()).filter(|link| !link.is_empty());
+        let id = match self.id.trim() {
+            "" => link.clone().unwrap_or_else(|| title.clone()),
+            id => id.to_string(),
+        };
+        if id.is_empty() {
+            return None;
+        }
+        Some(Entry { id, title, link })
+    }
+}
+
+/// The whitespace, newlines included, as single spaces
+fn squash(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Without its namespace prefix, like `link` for `atom:link`
+fn local_name(element: &BytesStart) -> String {
+    String::from_utf8_lossy(element.local_name()).into_owned()
+}
+
+/// The entry being read, with the depth of its element
+type Current = Option<(usize, Partial)>;
+
+pub fn parse(xml: &str) -> anyhow::Result<Feed> {
+    let mut reader = Reader::from_str(xml);
+    let mut buf = vec![];
+    // the local names of the elements being read, the root first
+    let mut open: Vec<String> = vec![];
+    let mut title = String::new();
+    let mut entries = vec![];
+    let mut current: Current = None;
+    let mut rooted = false;
+    loop {
+        let event = reader
+            .read_event(&mut buf)
+            .with_context(|| format!("Malformed feed at byte {}", reader.buffer_position()))?;
+        match event {
+            Event::Start(element) => {
+                let name = local_name(&element);
+                if !rooted && !["rss", "RDF", "feed"].contains(&name.as_str()) {
+                    bail!("Neither an RSS nor an Atom feed, but a <{name}>");
+                }
+                rooted = true;
+                alternate_link(&reader, &element, &open, &mut current)?;
+                if current.is_none() && ENTRIES.contains(&name.as_str()) {
+                    current = Some((open.len(), Partial::default()));
+                }
+                open.push(name);
+            }
+            Event::Empty(element) => alternate_link(&reader, &element, &open, &mut current)?,
+            Event::End(_) => {
+                open.pop();
+                if matches!(current, Some((depth, _)) if depth == open.len()) {
+                    let (_, partial) = current.take().expect("an entry being read");
+                    entries.extend(partial.finish());
+                }
+            }
+            Event::Text(text) => {
+                let text = text.unescape_and_decode(&reader)?;
+                on_text(&text, &open, &mut title, &mut current);
+            }
+            Event::CData(text) => {
+                let text = String::from_utf8(text.into_inner().into_owned())?;
+                on_text(&text, &open, &mut title, &mut current);
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    match open.last() {
+        _ if !rooted => bail!("Empty feed"),
+        Some(name) => bail!("Truncated feed, <{name}> isn't closed"),
+        None => Ok(Feed {
+            title: squash(&title),
+            entries,
+        }),
+    }
+}
+
+/// The text goes to the field of the entry it's in, or to the title of the
+/// feed
+fn on_text(text: &str, open: &[String], title: &mut String, current: &mut Current) {
+    match current {
+        Some((depth, partial)) => match open.get(*depth + 1).map(String::as_str) {
+            Some("title") => partial.title.push_str(text),
+            // RSS, Atom links are in an attribute
+            Some("link") => partial.link.push_str(text),
+            Some("guid") | Some("id") => partial.id.push_str(text),
+            _ => (),
+        },
+        None => {
+            // the channel in RSS, the root in Atom
+            let container = open.iter().position(|n| n == "channel" || n == "feed");
+            if let Some(container) = container {
+                if open.get(container + 1).map(String::as_str) == Some("title") {
+                    title.push_str(text);
+                }
+            }
+        }
+    }
+}
+
+/// The first `<link href="…"/>` of an Atom entry, without any rel or with
+/// rel="alternate"
+fn alternate_link(
+    reader: &Reader<&[u8]>,
+    element: &BytesStart,
+    open: &[String],
+    current: &mut Current,
+) -> anyhow::Result<()> {
+    let partial = match current {
+        Some((depth, partial)) if open.len() == *depth + 1 && partial.link.is_empty() => partial,
+        _ => return Ok(()),
+    };
+    if element.local_name() != b"link" {
+        return Ok(());
+    }
+    let mut href = None;
+    let mut alternate = true;
+    for attribute in element.attributes() {
+        let attribute = attribute?;
+        match attribute.key {
+            b"href" => href = Some(attribute.unescape_and_decode_value(reader)?),
+            b"rel" => alternate = attribute.unescape_and_decode_value(reader)? == "alternate",
+            _ => (),
+        }
+    }
+    if let Some(href) = href.filter(|_| alternate) {
+        partial.link = href;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn fixture(name: &str) -> String {
+        let path = format!("{}/fixtures/rss/{name}", env!("CARGO_MANIFEST_DIR"));
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    fn entry(id: &str, title: &str, link: Option<&str>) -> Entry {
+        Entry {
+            id: id.to_string(),
+            title: title.to_string(),
+            link: link.map(str::to_string),
+        }
+    }
+
+    #[test]
+    async fn test_parse_rss() {
+        assert_eq!(
+            parse(&fixture("rss2.xml")).unwrap(),
+            Feed {
+                title: "This Week in Rust".to_string(),
+                entries: vec![
+                    entry(
+                        "tag:this-week-in-rust.org,2025-02-26:/blog/2025/02/26/this-week-in-rust-588/",
+                        "This Week in Rust 588",
+                        Some("https://this-week-in-rust.org/blog/2025/02/26/this-week-in-rust-588/"),
+                    ),
+                    entry(
+                        "https://this-week-in-rust.org/blog/2025/02/19/this-week-in-rust-587/",
+                        "Rust & friends: <async> closures",
+                        Some("https://this-week-in-rust.org/blog/2025/02/19/this-week-in-rust-587/"),
+                    ),
+                    entry(
+                        "urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6a",
+                        "Only a guid & no link",
+                        None
+                    ),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    async fn test_parse_atom() {
+        assert_eq!(
+            parse(&fixture("atom.xml")).unwrap(),
+            Feed {
+                title: "Inside Rust Blog".to_string(),
+                entries: vec![
+                    entry(
+                        "https://blog.rust-lang.org/inside-rust/2025/02/27/leadership-council-update.html",
+                        "Leadership Council update",
+                        Some("https://blog.rust-lang.org/inside-rust/2025/02/27/leadership-council-update.html"),
+                    ),
+                    entry(
+                        "tag:blog.rust-lang.org,2025-02-20:compiler-team",
+                        "The compiler team",
+                        Some("https://blog.rust-lang.org/inside-rust/2025/02/20/compiler-team.html"),
+                    ),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    async fn test_parse_malformed() {
+        let truncated = parse(&fixture("malformed.xml")).unwrap_err();
+        assert_eq!(truncated.to_string(), "Truncated feed, <item> isn't closed");
+        let html = parse("<!DOCTYPE html><html><body>Not found</body></html>").unwrap_err();
+        assert_eq!(
+            html.to_string(),
+            "Neither an RSS nor an Atom feed, but a <html>"
+        );
+        assert_eq!(parse("").unwrap_err().to_string(), "Empty feed");
+        assert!(parse("<rss><channel><title>a</titl></channel></rss>").is_err());
+        assert!(parse("<feed><entry><title>a &bogus; b</title></entry></feed>").is_err());
+    }
+
+    #[test]
+    async fn test_parse_without_ids() {
+        let feed = parse(
+            "<rss version=\"2.0\"><channel><title> Bare\n feed </title>\
+             <item><title>Untracked</title></item><item><description>?</description></item>\
+             </channel></rss>",
+        )
+        .unwrap();
+        assert_eq!(
+            feed,
+            Feed {
+                title: "Bare feed".to_string(),
+                entries: vec![entry("Untracked", "Untracked", None)],
+            },
+            "by title, nothing without one"
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Nullable, Text};
+use plugin_core::{Database, Result};
+use std::collections::HashSet;
+
+use super::feed::Entry;
+
+/// Entries gone from a feed are forgotten after that long. Until then, an
+/// entry that comes back isn't announced again.
+const RETENTION_DAYS: i64 = 90;
+
+/// The tables of the rss plugin in the shared database, see
+/// `plugin_core::ensure_schema`
+const MIGRATIONS: &[&str] = &[
+    // a feed is there once polled, with the validators of its last response
+    "CREATE TABLE rss_feeds (
+        url TEXT NOT NULL,
+        channel TEXT NOT NULL,
+        etag TEXT,
+        last_modified TEXT,
+        PRIMARY KEY (url, channel)
+    );",
+    // entry is the id of an entry, see feed::Entry, and seen_at the last
+    // time it was in the feed, in unix seconds
+    "CREATE TABLE rss_seen (
+        url TEXT NOT NULL,
+        channel TEXT NOT NULL,
+        entry TEXT NOT NULL,
+        seen_at INTEGER NOT NULL,
+        PRIMARY KEY (url, channel, entry)
+    );",
+];
+
+/// For the HTTP conditional requests, as given by the last response
+#[derive(Debug, Clone, Default, PartialEq, QueryableByName)]
+pub struct Validators {
+    #[sql_type = "Nullable<Text>"]
+    pub etag: Option<String>,
+    #[sql_type = "Nullable<Text>"]
+    pub last_modified: Option<String>,
+}
+
+#[derive(QueryableByName)]
+struct Seen {
+    #[sql_type = "Text"]
+    entry: String,
+}
+
+/// The entries already announced, by feed and channel, so that a restart
+/// doesn't announce them again
+pub struct History {
+    db: Database,
+}
+
+impl History {
+    /// Create the tables if needed
+    pub fn load(db: Database) -> Result<Self> {
+        plugin_core::ensure_schema(&db, "rss", MIGRATIONS)?;
+        Ok(History { db })
+    }
+
+    /// None when the feed was never polled for that channel
+    pub fn validators(&self, url: &str, channel: &str) -> Result<Option<Validators>> {
+        let validators = self.db.with_connection(|conn| {
+            diesel::sql_query(
+                "SELECT etag, last_modified FROM rss_feeds WHERE url = ? AND channel = ?",
+            )
+            .bind::<Text, _>(url)
+            .bind::<Text, _>(channel)
+            .load::<Validators>(conn)
+        })?;
+        Ok(validators.into_iter().next())
+    }
+
+    /// Remembers the entries of the feed, and gives the ones never seen
+    /// before, in the order of the feed. The first time a feed is polled
+    /// nothing is new, its whole history would be announced otherwise.
+    /// Only the entries gone from the feed are forgotten, the ones still
+    /// there are seen now even when the feed wasn't modified for a while.
+    pub fn record(
+        &self,
+        url: &str,
+        channel: &str,
+        entries: &[Entry],
+        validators: &Validators,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Entry>> {
+        let polled_before = self.validators(url, channel)?.is_some();
+        let fresh = self.db.with_connection(|conn| {
+            conn.transaction(|| {
+                let seen =
+                    diesel::sql_query("SELECT entry FROM rss_seen WHERE url = ? AND channel = ?")
+                        .bind::<Text, _>(url)
+                        .bind::<Text, _>(channel)
+                        .load::<Seen>(conn)?
+                        .into_iter()
+                        .map(|seen| seen.entry)
+                        .collect::<HashSet<_>>();
+                let mut fresh = vec![];
+                for entry in entries {
+                    if !seen.contains(&entry.id) && !fresh.iter().any(|f: &Entry| f.id == entry.id)
+                    {
+                        fresh.push(entry.clone());
+                    }
+                    diesel::sql_query(
+                        "INSERT OR REPLACE INTO rss_seen (url, channel, entry, seen_at) \
+                         VALUES (?, ?, ?, ?)",
+                    )
+                    .bind::<Text, _>(url)
+                    .bind::<Text, _>(channel)
+                    .bind::<Text, _>(&entry.id)
+                    .bind::<BigInt, _>(now.timestamp())
+                    .execute(conn)?;
+                }
+                diesel::sql_query(
+                    "DELETE FROM rss_seen WHERE url = ? AND channel = ? AND seen_at < ?",
+                )
+                .bind::<Text, _>(url)
+                .bind::<Text, _>(channel)
+                .bind::<BigInt, _>((now - Duration::days(RETENTION_DAYS)).timestamp())
+                .execute(conn)?;
+                diesel::sql_query(
+                    "INSERT OR REPLACE INTO rss_feeds (url, channel, etag, last_modified) \
+                     VALUES (?, ?, ?, ?)",
+                )
+                .bind::<Text, _>(url)
+                .bind::<Text, _>(channel)
+                .bind::<Nullable<Text>, _>(&validators.etag)
+                .bind::<Nullable<Text>, _>(&validators.last_modified)
+                .execute(conn)?;
+                Ok(fresh)
+            })
+        })?;
+        Ok(if polled_before { fresh } else { vec![] })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const URL: &str = "https://blog.rust-lang.org/feed.xml";
+
+    fn entries(ids: &[&str]) -> Vec<Entry> {
+        ids.iter()
+            .map(|id| Entry {
+                id: id.to_string(),
+                title: format!("Post {id}"),
+                link: None,
+            })
+            .collect()
+    }
+
+    fn at(days: i64) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2025-03-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+            + Duration::days(days)
+    }
+
+    #[test]
+    async fn test_record() {
+        let db = Database::in_memory().unwrap();
+        let history = History::load(db.clone()).unwrap();
+        let none = Validators::default();
+        assert_eq!(history.validators(URL, "#rust").unwrap(), None);
+        assert_eq!(
+            history
+                .record(URL, "#rust", &entries(&["2", "1"]), &none, at(0))
+                .unwrap(),
+            Vec::<Entry>::new(),
+            "nothing new the first time"
+        );
+        assert_eq!(
+            history.validators(URL, "#rust").unwrap(),
+            Some(none.clone())
+        );
+        assert_eq!(
+            history
+                .record(URL, "#rust", &entries(&["4", "3", "2", "1"]), &none, at(0))
+                .unwrap(),
+            entries(&["4", "3"])
+        );
+        assert_eq!(
+            history
+                .record(
+                    URL,
+                    "#rust-fr",
+                    &entries(&["4", "3", "2", "1"]),
+                    &none,
+                    at(0)
+                )
+                .unwrap(),
+            Vec::<Entry>::new(),
+            "per channel"
+        );
+
+        // after a restart
+        let history = History::load(db).unwrap();
+        let validators = Validators {
+            etag: Some("\"abc\"".to_string()),
+            last_modified: Some("Sat, 01 Mar 2025 10:00:00 GMT".to_string()),
+        };
+        assert_eq!(
+            history
+                .record(URL, "#rust", &entries(&["5", "4", "3"]), &validators, at(1))
+                .unwrap(),
+            entries(&["5"])
+        );
+        assert_eq!(
+            history.validators(URL, "#rust").unwrap(),
+            Some(validators.clone())
+        );
+        assert_eq!(
+            history
+                .record(URL, "#rust", &entries(&["5", "1"]), &validators, at(2))
+                .unwrap(),
+            Vec::<Entry>::new(),
+            "1 is gone from the feed but not forgotten yet"
+        );
+        history
+            .record(URL, "#rust", &entries(&["5"]), &validators, at(99))
+            .unwrap();
+        assert_eq!(
+            history
+                .record(URL, "#rust", &entries(&["5", "2"]), &validators, at(100))
+                .unwrap(),
+            entries(&["2"]),
+            "2 was forgotten, 5 is still in the feed"
+        );
+        assert_eq!(
+            history
+                .record(URL, "#rust", &entries(&["5", "2"]), &validators, at(200))
+                .unwrap(),
+            Vec::<Entry>::new(),
+            "not modified for a while, but still in the feed"
+        );
+    }
+}
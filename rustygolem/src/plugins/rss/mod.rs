@@ -0,0 +1,5 @@
+mod feed;
+mod history;
+mod plugin;
+
+pub use plugin::Rss;
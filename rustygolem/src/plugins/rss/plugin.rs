@@ -0,0 +1,455 @@
+use anyhow::{anyhow, bail, Context};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use plugin_core::{Initialised, Outbound, Plugin, Requirement, Result};
+use reqwest::header::{HeaderName, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Client, Response, StatusCode, Url};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
+
+use super::feed::{self, Entry};
+use super::history::{History, Validators};
+use crate::utils::text::sanitize;
+
+/// New entries announced at once, unless the config says otherwise. A feed
+/// that suddenly gives its whole history gets a "…and N more".
+pub const DEFAULT_MAX_PER_POLL: usize = 3;
+
+/// Longer titles and links are cut, in chars
+const MAX_TITLE_LENGTH: usize = 200;
+const MAX_LINK_LENGTH: usize = 500;
+
+/// Larger feeds aren't read, in bytes
+const MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+/// An entry of the `feeds` list of the rss config section
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FeedSettings {
+    pub url: String,
+    /// where its new entries are announced
+    pub channel: String,
+    pub every_minutes: u64,
+}
+
+impl FeedSettings {
+    fn check(&self) -> anyhow::Result<()> {
+        let url = Url::parse(&self.url)
+            .with_context(|| format!("rss.feeds: invalid url {}", self.url))?;
+        if !["http", "https"].contains(&url.scheme()) {
+            bail!("rss.feeds: {} isn't an http or https url", self.url);
+        }
+        if self.every_minutes == 0 {
+            bail!(
+                "rss.feeds: every_minutes must be at least 1 for {}",
+                self.url
+            );
+        }
+        Ok(())
+    }
+
+    fn every(&self) -> Duration {
+        Duration::from_secs(self.every_minutes * 60)
+    }
+}
+
+/// The `rss` section of the golem config
+#[derive(Deserialize)]
+struct Settings {
+    #[serde(default)]
+    feeds: Vec<FeedSettings>,
+    /// new entries of a feed announced at once
+    #[serde(default = "default_max_per_poll")]
+    max_per_poll: usize,
+}
+
+fn default_max_per_poll() -> usize {
+    DEFAULT_MAX_PER_POLL
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            feeds: vec![],
+            max_per_poll: default_max_per_poll(),
+        }
+    }
+}
+
+impl Settings {
+    fn load(config: &plugin_core::Config) -> Result<Self> {
+        let settings: Settings = config.plugin_section("rss")?.unwrap_or_default();
+        if settings.max_per_poll == 0 {
+            return Err(anyhow!("rss.max_per_poll must be at least 1").into());
+        }
+        for feed in &settings.feeds {
+            feed.check()?;
+        }
+        Ok(settings)
+    }
+}
+
+/// A response to a conditional request
+enum Fetched {
+    NotModified,
+    Body {
+        body: String,
+        validators: Validators,
+    },
+}
+
+/// With the validators of the last response, so that an unchanged feed
+/// isn't sent again
+async fn fetch(client: &Client, url: &str, validators: &Validators) -> anyhow::Result<Fetched> {
+    let mut request = client.get(url);
+    if let Some(etag) = &validators.etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &validators.last_modified {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+    let response = request.send().await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Fetched::NotModified);
+    }
+    let response = response.error_for_status()?;
+    let header = |name: HeaderName| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let validators = Validators {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+    };
+    Ok(Fetched::Body {
+        body: read_body(response, MAX_BODY_SIZE).await?,
+        validators,
+    })
+}
+
+/// An error rather than a truncated feed when it's larger than `max` bytes
+async fn read_body(mut response: Response, max: usize) -> anyhow::Result<String> {
+    let too_large = || anyhow!("the feed is larger than {max} bytes");
+    if response
+        .content_length()
+        .map_or(false, |len| len > max as u64)
+    {
+        return Err(too_large());
+    }
+    let mut body = vec![];
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > max {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Like `📰 This Week in Rust: This Week in Rust 588 https://…`, in the
+/// order of the feed, at most `max` of them and then how many others
+fn announcements(feed_title: &str, fresh: &[Entry], max: usize) -> Vec<String> {
+    let feed_title = sanitize(feed_title, MAX_TITLE_LENGTH);
+    let mut lines = fresh
+        .iter()
+        .take(max)
+        .map(|entry| {
+            let title = sanitize(&entry.title, MAX_TITLE_LENGTH);
+            let link = entry.link.as_deref().map(|l| sanitize(l, MAX_LINK_LENGTH));
+            let text = [Some(title), link]
+                .into_iter()
+                .flatten()
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!("📰 {feed_title}: {text}")
+        })
+        .collect::<Vec<_>>();
+    if fresh.len() > max {
+        lines.push(format!("📰 {feed_title}: …and {} more", fresh.len() - max));
+    }
+    lines
+}
+
+pub struct Rss {
+    client: Client,
+    history: History,
+    feeds: Vec<FeedSettings>,
+    max_per_poll: usize,
+}
+
+#[async_trait]
+impl Plugin for Rss {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        Settings::load(config)?;
+        config.check_database("rss")?;
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let settings = Settings::load(config)?;
+        let db = config.require_database("rss")?;
+        Ok(Initialised::from(Rss {
+            client: config.http_client(),
+            history: History::load(db)?,
+            feeds: settings.feeds,
+            max_per_poll: settings.max_per_poll,
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "rss"
+    }
+
+    async fn run(&self, bot_chan: mpsc::Sender<Outbound>) -> Result<()> {
+        let polls = self
+            .feeds
+            .iter()
+            .map(|feed| self.poll_every(feed, &bot_chan));
+        futures::future::try_join_all(polls).await?;
+        // nothing to poll
+        futures::future::pending().await
+    }
+
+    fn requirements(&self) -> Vec<Requirement> {
+        // the entries already announced
+        vec![Requirement::Database]
+    }
+}
+
+impl Rss {
+    /// Polls the feed forever, right away and then at its own pace. A feed
+    /// that can't be fetched or parsed is skipped until the next time.
+    async fn poll_every(
+        &self,
+        feed: &FeedSettings,
+        bot_chan: &mpsc::Sender<Outbound>,
+    ) -> anyhow::Result<()> {
+        let mut interval = tokio::time::interval(feed.every());
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let lines = match self.poll(feed).await {
+                Ok(lines) => lines,
+                Err(err) => {
+                    log::warn!(
+                        "Cannot poll the feed {} for {}: {err:#}",
+                        feed.url,
+                        feed.channel
+                    );
+                    continue;
+                }
+            };
+            for line in lines {
+                bot_chan.send(Outbound::reply(&feed.channel, line)).await?;
+            }
+        }
+    }
+
+    async fn poll(&self, feed: &FeedSettings) -> anyhow::Result<Vec<String>> {
+        let validators = self
+            .history
+            .validators(&feed.url, &feed.channel)?
+            .unwrap_or_default();
+        match fetch(&self.client, &feed.url, &validators).await? {
+            Fetched::NotModified => Ok(vec![]),
+            Fetched::Body { body, validators } => {
+                self.on_body(feed, &body, &validators, Utc::now())
+            }
+        }
+    }
+
+    /// The announcements of the new entries of the feed, nothing is
+    /// remembered when it's malformed
+    fn on_body(
+        &self,
+        feed: &FeedSettings,
+        body: &str,
+        validators: &Validators,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<String>> {
+        let parsed = feed::parse(body)?;
+        let fresh =
+            self.history
+                .record(&feed.url, &feed.channel, &parsed.entries, validators, now)?;
+        let title = match parsed.title.as_str() {
+            "" => &feed.url,
+            title => title,
+        };
+        Ok(announcements(title, &fresh, self.max_per_poll))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use plugin_core::Database;
+    use pretty_assertions::assert_eq;
+
+    fn fixture(name: &str) -> String {
+        let path = format!("{}/fixtures/rss/{name}", env!("CARGO_MANIFEST_DIR"));
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    fn twir() -> FeedSettings {
+        FeedSettings {
+            url: "https://this-week-in-rust.org/rss.xml".to_string(),
+            channel: "#rust".to_string(),
+            every_minutes: 60,
+        }
+    }
+
+    fn rss(db: Database) -> Rss {
+        Rss {
+            client: Client::new(),
+            history: History::load(db).unwrap(),
+            feeds: vec![twir()],
+            max_per_poll: DEFAULT_MAX_PER_POLL,
+        }
+    }
+
+    /// The fixture with new items on top
+    fn with_items(titles: &[&str]) -> String {
+        let items = titles
+            .iter()
+            .map(|title| {
+                format!(
+                    "<item><title>{title}</title><link>https://example.com/{title}</link></item>"
+                )
+            })
+            .collect::<String>();
+        fixture("rss2.xml").replacen("<item>", &format!("{items}<item>"), 1)
+    }
+
+    #[test]
+    async fn test_check() {
+        assert!(twir().check().is_ok());
+        let every = FeedSettings {
+            every_minutes: 0,
+            ..twir()
+        };
+        assert!(every.check().is_err());
+        for url in ["this-week-in-rust.org/rss.xml", "file:///etc/passwd"] {
+            let feed = FeedSettings {
+                url: url.to_string(),
+                ..twir()
+            };
+            assert!(feed.check().is_err(), "{url}");
+        }
+    }
+
+    #[test]
+    async fn test_read_body() {
+        let response = |body: &str| Response::from(http::Response::new(body.to_string()));
+        assert_eq!(
+            read_body(response("<rss></rss>"), 11).await.unwrap(),
+            "<rss></rss>"
+        );
+        assert_eq!(
+            read_body(response("<rss></rss>"), 10)
+                .await
+                .unwrap_err()
+                .to_string(),
+            "the feed is larger than 10 bytes"
+        );
+    }
+
+    #[test]
+    async fn test_announcements() {
+        let entries = (1..=5)
+            .map(|i| Entry {
+                id: i.to_string(),
+                title: format!("Post\u{3}4 {i}"),
+                link: (i != 2).then(|| format!("https://example.com/{i}")),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            announcements("Blog", &entries, 3),
+            vec![
+                "📰 Blog: Post 1 https://example.com/1",
+                "📰 Blog: Post 2",
+                "📰 Blog: Post 3 https://example.com/3",
+                "📰 Blog: …and 2 more",
+            ]
+        );
+        assert_eq!(
+            announcements("Blog", &entries[..3], 3),
+            vec![
+                "📰 Blog: Post 1 https://example.com/1",
+                "📰 Blog: Post 2",
+                "📰 Blog: Post 3 https://example.com/3",
+            ]
+        );
+        assert_eq!(announcements("Blog", &[], 3), Vec::<String>::new());
+    }
+
+    #[test]
+    async fn test_dedup_across_restart() {
+        let db = Database::in_memory().unwrap();
+        let plugin = rss(db.clone());
+        let feed = twir();
+        let none = Validators::default();
+        let now = Utc::now();
+        assert_eq!(
+            plugin
+                .on_body(&feed, &fixture("rss2.xml"), &none, now)
+                .unwrap(),
+            Vec::<String>::new(),
+            "the history isn't announced"
+        );
+        assert_eq!(
+            plugin
+                .on_body(&feed, &with_items(&["589"]), &none, now)
+                .unwrap(),
+            vec!["📰 This Week in Rust: 589 https://example.com/589"]
+        );
+
+        let plugin = rss(db);
+        assert_eq!(
+            plugin
+                .on_body(&feed, &with_items(&["589"]), &none, now)
+                .unwrap(),
+            Vec::<String>::new(),
+            "not again after a restart"
+        );
+        assert_eq!(
+            plugin
+                .on_body(
+                    &feed,
+                    &with_items(&["594", "593", "592", "591", "590", "589"]),
+                    &none,
+                    now
+                )
+                .unwrap(),
+            vec![
+                "📰 This Week in Rust: 594 https://example.com/594",
+                "📰 This Week in Rust: 593 https://example.com/593",
+                "📰 This Week in Rust: 592 https://example.com/592",
+                "📰 This Week in Rust: …and 2 more",
+            ]
+        );
+    }
+
+    #[test]
+    async fn test_malformed() {
+        let plugin = rss(Database::in_memory().unwrap());
+        let feed = twir();
+        assert!(plugin
+            .on_body(
+                &feed,
+                &fixture("malformed.xml"),
+                &Validators::default(),
+                Utc::now()
+            )
+            .is_err());
+        assert_eq!(
+            plugin.history.validators(&feed.url, &feed.channel).unwrap(),
+            None,
+            "not polled yet"
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use regex::{NoExpand, Regex, RegexBuilder};
+
+/// Longer patterns aren't compiled, in chars
+const MAX_PATTERN_LENGTH: usize = 200;
+/// Of the compiled regex and of its lazy DFA, in bytes
+const SIZE_LIMIT: usize = 1 << 16;
+/// Of the nested groups and repetitions
+const NEST_LIMIT: u32 = 16;
+
+/// Like `s/teh/the/g`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitution {
+    /// with `\/` as `/`
+    pub pattern: String,
+    /// as is, without any `$1`, with `\/` as `/` and `\\` as `\`
+    pub replacement: String,
+    /// every match, not only the first one
+    pub global: bool,
+    pub case_insensitive: bool,
+}
+
+/// A substitution ready to apply
+#[derive(Debug)]
+pub struct Compiled {
+    regex: Regex,
+    replacement: String,
+    global: bool,
+}
+
+/// `s/pattern/replacement/flags` as the whole message, with an optional
+/// `nick: ` before it to correct someone else. None for anything else, like
+/// a path in the middle of a sentence.
+pub fn parse(text: &str) -> Option<(Option<&str>, Substitution)> {
+    let text = text.trim();
+    let (nick, expression) = match text.split_once(char::is_whitespace) {
+        Some((first, rest)) => match first.strip_suffix(&[':', ','][..]) {
+            Some(nick) if is_nick(nick) => (Some(nick), rest.trim_start()),
+            _ => (None, text),
+        },
+        None => (None, text),
+    };
+    Some((nick, parse_expression(expression)?))
+}
+
+/// Like `alice` or `[m]atrix`, not `s/foo`
+fn is_nick(word: &str) -> bool {
+    !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_[]\\`^{}|".contains(c))
+}
+
+/// The final slash is optional when there's no flag
+fn parse_expression(input: &str) -> Option<Substitution> {
+    let rest = input.strip_prefix("s/")?;
+    let (pattern, rest) = until_slash(rest)?;
+    let (replacement, flags) = until_slash(rest).unwrap_or((rest, ""));
+    if pattern.is_empty() || !flags.chars().all(|c| c == 'g' || c == 'i') {
+        return None;
+    }
+    Some(Substitution {
+        pattern: pattern.replace("\\/", "/"),
+        replacement: unescape(replacement),
+        global: flags.contains('g'),
+        case_insensitive: flags.contains('i'),
+    })
+}
+
+/// Before and after the first slash not escaped with a backslash
+fn until_slash(input: &str) -> Option<(&str, &str)> {
+    let mut chars = input.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '/' => return Some((&input[..i], &input[i + 1..])),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// `\/` as `/` and `\\` as `\`, the other backslashes as is
+fn unescape(replacement: &str) -> String {
+    let mut unescaped = String::with_capacity(replacement.len());
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some('/')) | ('\\', Some('\\')) => unescaped.extend(chars.next()),
+            (c, _) => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+impl Substitution {
+    /// Why it can't be applied, like an invalid pattern or one too large to
+    /// match quickly
+    pub fn compile(&self) -> Result<Compiled, String> {
+        if self.pattern.chars().count() > MAX_PATTERN_LENGTH {
+            return Err(format!(
+                "the pattern is longer than {MAX_PATTERN_LENGTH} chars"
+            ));
+        }
+        let regex = RegexBuilder::new(&self.pattern)
+            .case_insensitive(self.case_insensitive)
+            .size_limit(SIZE_LIMIT)
+            .dfa_size_limit(SIZE_LIMIT)
+            .nest_limit(NEST_LIMIT)
+            .build()
+            .map_err(|err| err.to_string())?;
+        Ok(Compiled {
+            regex,
+            replacement: self.replacement.clone(),
+            global: self.global,
+        })
+    }
+}
+
+impl Compiled {
+    /// None when the text doesn't match
+    pub fn apply(&self, text: &str) -> Option<String> {
+        if !self.regex.is_match(text) {
+            return None;
+        }
+        let replacement = NoExpand(&self.replacement);
+        Some(if self.global {
+            self.regex.replace_all(text, replacement).into_owned()
+        } else {
+            self.regex.replace(text, replacement).into_owned()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn s(pattern: &str, replacement: &str, global: bool, case_insensitive: bool) -> Substitution {
+        Substitution {
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            global,
+            case_insensitive,
+        }
+    }
+
+    #[test]
+    async fn test_parse() {
+        for (text, expected) in [
+            ("s/teh/the/", (None, s("teh", "the", false, false))),
+            ("s/teh/the", (None, s("teh", "the", false, false))),
+            ("  s/teh/the/  ", (None, s("teh", "the", false, false))),
+            ("s/teh/the/g", (None, s("teh", "the", true, false))),
+            ("s/teh/the/gi", (None, s("teh", "the", true, true))),
+            ("s/teh/the/ig", (None, s("teh", "the", true, true))),
+            ("s/teh/the/i", (None, s("teh", "the", false, true))),
+            ("s/teh//", (None, s("teh", "", false, false))),
+            ("s/teh/", (None, s("teh", "", false, false))),
+            ("s/a b/c d/", (None, s("a b", "c d", false, false))),
+            (
+                "s/\\/usr\\/bin/\\/opt\\/bin/",
+                (None, s("/usr/bin", "/opt/bin", false, false)),
+            ),
+            ("s/\\d+/N/g", (None, s("\\d+", "N", true, false))),
+            ("s/a\\\\/b\\\\/", (None, s("a\\\\", "b\\", false, false))),
+            ("s/\\\\\\//x/", (None, s("\\\\/", "x", false, false))),
+            ("s/a/\\n$1/", (None, s("a", "\\n$1", false, false))),
+            (
+                "alice: s/teh/the/",
+                (Some("alice"), s("teh", "the", false, false)),
+            ),
+            (
+                "[m]atrix, s/teh/the/g",
+                (Some("[m]atrix"), s("teh", "the", true, false)),
+            ),
+            (
+                "s/foo: bar/baz/",
+                (None, s("foo: bar", "baz", false, false)),
+            ),
+        ] {
+            assert_eq!(parse(text), Some(expected), "{text:?}");
+        }
+        for text in [
+            "",
+            "s",
+            "s/",
+            "s//the/",
+            "s/teh/the/x",
+            "s/teh/the/g i",
+            "s/a/b/c/d",
+            "S/teh/the/",
+            "s|teh|the|",
+            "I moved it to s/foo/bar yesterday",
+            "see s/foo/bar/",
+            "s/foo/bar/ right?",
+            "alice: see s/foo/bar/",
+            "alice bob: s/foo/bar/",
+            ": s/foo/bar/",
+        ] {
+            assert_eq!(parse(text), None, "{text:?}");
+        }
+    }
+
+    #[test]
+    async fn test_apply() {
+        let apply =
+            |substitution: Substitution, text: &str| substitution.compile().unwrap().apply(text);
+        assert_eq!(
+            apply(s("teh", "the", false, false), "teh cat and teh dog"),
+            Some("the cat and teh dog".to_string())
+        );
+        assert_eq!(
+            apply(s("teh", "the", true, false), "teh cat and teh dog"),
+            Some("the cat and the dog".to_string())
+        );
+        assert_eq!(apply(s("teh", "the", true, false), "Teh cat"), None);
+        assert_eq!(
+            apply(s("teh", "the", true, true), "Teh cat and TEH dog"),
+            Some("the cat and the dog".to_string())
+        );
+        assert_eq!(
+            apply(s("(\\w+) (\\w+)", "$2 $1", false, false), "hello world"),
+            Some("$2 $1".to_string()),
+            "the replacement as is"
+        );
+        assert_eq!(
+            apply(s("/usr/bin", "/opt/bin", false, false), "in /usr/bin/rustc"),
+            Some("in /opt/bin/rustc".to_string())
+        );
+        assert_eq!(
+            apply(s("é", "e", true, false), "café, été"),
+            Some("cafe, ete".to_string())
+        );
+        assert_eq!(
+            apply(s("o*", "-", true, false), "foo"),
+            Some("-f-".to_string()),
+            "the empty matches too"
+        );
+        assert_eq!(
+            apply(s("(a*)*b", "x", false, false), &"a".repeat(300)),
+            None,
+            "no backtracking"
+        );
+    }
+
+    #[test]
+    async fn test_compile_limits() {
+        assert!(s("(", "x", false, false).compile().is_err());
+        assert!(s("(?:x{1000}){1000}", "x", false, false).compile().is_err());
+        let nested = format!("{}a{}", "(".repeat(20), ")".repeat(20));
+        assert!(s(&nested, "x", false, false).compile().is_err());
+        assert!(s(&"a".repeat(201), "x", false, false).compile().is_err());
+        assert!(s(&"a".repeat(200), "x", false, false).compile().is_ok());
+    }
+}
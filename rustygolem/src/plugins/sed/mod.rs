@@ -0,0 +1,4 @@
+mod expression;
+mod plugin;
+
+pub use plugin::Sed;
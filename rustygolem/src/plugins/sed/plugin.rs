@@ -0,0 +1,237 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use irc::proto::{ChannelExt, Command, Message};
+use plugin_core::utils::network::network;
+use plugin_core::{Initialised, MsgCtx, Outbound, Plugin, Result};
+
+use super::expression;
+use crate::caps::CaseMapping;
+use crate::utils::backlog::Backlog;
+use crate::utils::text::sanitize;
+
+/// Longer corrections are cut, in chars
+const MAX_LENGTH: usize = 400;
+/// Beyond, the correction is given up
+const MATCH_TIMEOUT: Duration = Duration::from_millis(100);
+
+pub struct Sed {
+    /// what was said in the channels, to correct it
+    backlog: Backlog,
+}
+
+#[async_trait]
+impl Plugin for Sed {
+    async fn init(_config: &plugin_core::Config) -> Result<Initialised> {
+        Ok(Initialised::from(Sed {
+            backlog: Backlog::default(),
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "sed"
+    }
+
+    async fn in_message_ctx(&self, ctx: &MsgCtx, msg: &Message) -> Result<Option<Outbound>> {
+        self.in_msg(msg, ctx.casemapping).await
+    }
+}
+
+impl Sed {
+    async fn in_msg(&self, msg: &Message, casemapping: CaseMapping) -> Result<Option<Outbound>> {
+        let (channel, text) = match &msg.command {
+            Command::PRIVMSG(target, text) if target.is_channel_name() => (target, text),
+            _ => return Ok(None),
+        };
+        let nick = match msg.source_nickname() {
+            Some(nick) => nick,
+            None => return Ok(None),
+        };
+        let network = network(msg).unwrap_or_default();
+        let (target, substitution) = match expression::parse(text) {
+            Some(parsed) => parsed,
+            None => {
+                if !text.starts_with('\x01') {
+                    self.backlog
+                        .remember(network, casemapping, channel, nick, text);
+                }
+                return Ok(None);
+            }
+        };
+        let compiled = match substitution.compile() {
+            Ok(compiled) => compiled,
+            Err(err) => {
+                log::debug!("Not correcting with {text:?} from {nick}: {err}");
+                return Ok(None);
+            }
+        };
+        let (said_by, messages) =
+            match self
+                .backlog
+                .messages_of(network, casemapping, channel, target.unwrap_or(nick))
+            {
+                Some(said) => said,
+                None => return Ok(None),
+            };
+        // the most recent message that matches
+        let corrected = tokio::time::timeout(
+            MATCH_TIMEOUT,
+            tokio::task::spawn_blocking(move || {
+                messages.iter().rev().find_map(|text| compiled.apply(text))
+            }),
+        )
+        .await;
+        let corrected = match corrected {
+            Ok(corrected) => corrected.map_err(anyhow::Error::from)?,
+            Err(_) => {
+                log::warn!("Gave up correcting {said_by} in {channel} with {text:?}, too slow");
+                return Ok(None);
+            }
+        };
+        Ok(corrected.map(|corrected| {
+            // so that a correction can be corrected in turn
+            self.backlog
+                .remember(network, casemapping, channel, &said_by, &corrected);
+            Outbound::reply(
+                channel,
+                sanitize(&format!("{said_by} meant: {corrected}"), MAX_LENGTH),
+            )
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::backlog::NICKS_PER_CHANNEL;
+    use plugin_core::utils::network::set_network;
+    use pretty_assertions::assert_eq;
+
+    fn sed() -> Sed {
+        Sed {
+            backlog: Backlog::default(),
+        }
+    }
+
+    async fn say_in(plugin: &Sed, nick: &str, target: &str, text: &str) -> Option<String> {
+        let source = format!("{nick}!~{nick}@localhost");
+        let mut msg = Message::new(Some(&source), "PRIVMSG", vec![target, text]).unwrap();
+        set_network(&mut msg, "libera");
+        match plugin.in_msg(&msg, CaseMapping::Rfc1459).await.unwrap() {
+            Some(Outbound::Reply { target, text }) => {
+                assert_eq!(target, "#rust");
+                Some(text)
+            }
+            None => None,
+            other => panic!("unexpected reply to {text:?}: {other:?}"),
+        }
+    }
+
+    async fn say(plugin: &Sed, nick: &str, text: &str) -> Option<String> {
+        say_in(plugin, nick, "#rust", text).await
+    }
+
+    #[test]
+    async fn test_correct() {
+        let plugin = sed();
+        assert_eq!(
+            say(&plugin, "alice", "s/teh/the/").await,
+            None,
+            "nothing yet"
+        );
+        assert_eq!(say(&plugin, "alice", "teh cat and teh dog").await, None);
+        assert_eq!(
+            say(&plugin, "alice", "s/teh/the/").await.as_deref(),
+            Some("alice meant: the cat and teh dog")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "s/teh/the/g").await.as_deref(),
+            Some("alice meant: the cat and the dog"),
+            "the correction is corrected"
+        );
+        assert_eq!(
+            say(&plugin, "alice", "s/THE CAT/the bird/i")
+                .await
+                .as_deref(),
+            Some("alice meant: the bird and the dog")
+        );
+        assert_eq!(say(&plugin, "alice", "s/unicorn/horse/").await, None);
+        assert_eq!(
+            say(&plugin, "bob", "s/bird/fish/").await,
+            None,
+            "bob said nothing"
+        );
+    }
+
+    #[test]
+    async fn test_correct_someone_else() {
+        let plugin = sed();
+        say(&plugin, "alice", "rust is teh best").await;
+        say(&plugin, "bob", "teh worst").await;
+        assert_eq!(
+            say(&plugin, "bob", "alice: s/teh/the/").await.as_deref(),
+            Some("alice meant: rust is the best")
+        );
+        assert_eq!(
+            say(&plugin, "charlie", "BOB, s/teh/the/").await.as_deref(),
+            Some("bob meant: the worst")
+        );
+        assert_eq!(say(&plugin, "bob", "dave: s/teh/the/").await, None);
+    }
+
+    #[test]
+    async fn test_most_recent_match() {
+        let plugin = sed();
+        for text in ["foo one", "bar two", "\u{1}ACTION foo three\u{1}", "s/(/x/"] {
+            say(&plugin, "alice", text).await;
+        }
+        assert_eq!(
+            say(&plugin, "alice", "s/foo/baz/").await.as_deref(),
+            Some("alice meant: baz one"),
+            "neither the actions nor the expressions"
+        );
+        say(&plugin, "alice", "I moved it to s/foo/bar yesterday").await;
+        assert_eq!(
+            say(&plugin, "alice", "s/moved/copied/").await.as_deref(),
+            Some("alice meant: I copied it to s/foo/bar yesterday")
+        );
+        assert_eq!(say_in(&plugin, "alice", "golem", "s/one/two/").await, None);
+    }
+
+    #[test]
+    async fn test_bounds() {
+        let plugin = sed();
+        for i in 1..=6 {
+            say(&plugin, "alice", &format!("message {i}")).await;
+        }
+        assert_eq!(
+            say(&plugin, "alice", "s/message 2/second/")
+                .await
+                .as_deref(),
+            Some("alice meant: second")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "s/message 1/first/").await,
+            None,
+            "forgotten"
+        );
+
+        for i in 0..NICKS_PER_CHANNEL {
+            say(&plugin, &format!("nick{i}"), "hello").await;
+        }
+        assert_eq!(
+            say(&plugin, "alice", "s/message/msg/").await,
+            None,
+            "forgotten"
+        );
+        assert_eq!(
+            say(&plugin, "bob", "nick0: s/hello/bye/").await.as_deref(),
+            Some("nick0 meant: bye")
+        );
+
+        say(&plugin, "mallory", &"a".repeat(500)).await;
+        let long = say(&plugin, "mallory", "s/a/\u{3}4b/").await.unwrap();
+        assert_eq!(long.chars().count(), MAX_LENGTH);
+        assert!(long.starts_with("mallory meant: baa"), "{long}");
+    }
+}
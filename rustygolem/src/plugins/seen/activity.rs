@@ -0,0 +1,264 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use plugin_core::{Database, Result};
+
+use crate::caps::CaseMapping;
+
+/// The tables of the seen plugin in the shared database, see
+/// `plugin_core::ensure_schema`
+const MIGRATIONS: &[&str] = &[
+    // the last activity of each nick in each channel. nick and channel are
+    // normalized with the casemapping of the network, channel_name is as seen
+    // for the members of the channel. detail is the text of a message, or the
+    // other nick of a nick change.
+    "CREATE TABLE seen_activity (
+        network TEXT NOT NULL,
+        nick TEXT NOT NULL,
+        channel TEXT NOT NULL,
+        shown_nick TEXT NOT NULL,
+        channel_name TEXT NOT NULL,
+        at TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        detail TEXT NOT NULL DEFAULT '',
+        PRIMARY KEY (network, nick, channel)
+    );",
+];
+
+/// What a nick was last seen doing in a channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Message(String),
+    Join,
+    Part,
+    Quit,
+    /// now known as that nick
+    Nick(String),
+    /// previously known as that nick
+    Renamed(String),
+}
+
+impl Event {
+    fn kind(&self) -> &'static str {
+        match self {
+            Event::Message(_) => "message",
+            Event::Join => "join",
+            Event::Part => "part",
+            Event::Quit => "quit",
+            Event::Nick(_) => "nick",
+            Event::Renamed(_) => "renamed",
+        }
+    }
+
+    fn detail(&self) -> &str {
+        match self {
+            Event::Message(detail) | Event::Nick(detail) | Event::Renamed(detail) => detail,
+            Event::Join | Event::Part | Event::Quit => "",
+        }
+    }
+
+    fn from_row(kind: &str, detail: String) -> Option<Self> {
+        match kind {
+            "message" => Some(Event::Message(detail)),
+            "join" => Some(Event::Join),
+            "part" => Some(Event::Part),
+            "quit" => Some(Event::Quit),
+            "nick" => Some(Event::Nick(detail)),
+            "renamed" => Some(Event::Renamed(detail)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Activity {
+    /// as last seen, like `Charlie`
+    pub nick: String,
+    pub channel: String,
+    pub at: DateTime<Utc>,
+    pub event: Event,
+}
+
+impl Activity {
+    /// Whether the nick isn't in the channel anymore since then
+    pub fn has_left(&self) -> bool {
+        matches!(self.event, Event::Part | Event::Quit)
+    }
+}
+
+#[derive(QueryableByName)]
+struct Row {
+    #[sql_type = "Text"]
+    shown_nick: String,
+    #[sql_type = "Text"]
+    channel_name: String,
+    #[sql_type = "Text"]
+    at: String,
+    #[sql_type = "Text"]
+    kind: String,
+    #[sql_type = "Text"]
+    detail: String,
+}
+
+/// The last activity of everyone, in the database so that a restart
+/// doesn't forget it
+pub struct Activities {
+    db: Database,
+}
+
+impl Activities {
+    /// Create the table if needed
+    pub fn load(db: Database) -> Result<Self> {
+        plugin_core::ensure_schema(&db, "seen", MIGRATIONS)?;
+        Ok(Activities { db })
+    }
+
+    /// Replaces the previous activity of the nick in that channel
+    pub fn record(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        activity: &Activity,
+    ) -> Result<()> {
+        self.db.with_connection(|conn| {
+            diesel::sql_query(
+                "INSERT OR REPLACE INTO seen_activity \
+                 (network, nick, channel, shown_nick, channel_name, at, kind, detail) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind::<Text, _>(network)
+            .bind::<Text, _>(casemapping.normalize(&activity.nick))
+            .bind::<Text, _>(casemapping.normalize(&activity.channel))
+            .bind::<Text, _>(&activity.nick)
+            .bind::<Text, _>(&activity.channel)
+            .bind::<Text, _>(activity.at.to_rfc3339_opts(SecondsFormat::Secs, true))
+            .bind::<Text, _>(activity.event.kind())
+            .bind::<Text, _>(activity.event.detail())
+            .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// Every channel where the nick was seen, the last activity first
+    pub fn of(&self, network: &str, casemapping: CaseMapping, nick: &str) -> Result<Vec<Activity>> {
+        let rows = self.db.with_connection(|conn| {
+            diesel::sql_query(
+                "SELECT shown_nick, channel_name, at, kind, detail FROM seen_activity \
+                 WHERE network = ? AND nick = ? ORDER BY at DESC",
+            )
+            .bind::<Text, _>(network)
+            .bind::<Text, _>(casemapping.normalize(nick))
+            .load::<Row>(conn)
+        })?;
+        let activities = rows
+            .into_iter()
+            .filter_map(|row| {
+                let at = match DateTime::parse_from_rfc3339(&row.at) {
+                    Ok(at) => at.with_timezone(&Utc),
+                    Err(err) => {
+                        log::warn!(
+                            "Ignoring the activity of {} at {:?}: {err}",
+                            row.shown_nick,
+                            row.at
+                        );
+                        return None;
+                    }
+                };
+                let event = Event::from_row(&row.kind, row.detail)?;
+                Some(Activity {
+                    nick: row.shown_nick,
+                    channel: row.channel_name,
+                    at,
+                    event,
+                })
+            })
+            .collect();
+        Ok(activities)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    fn activity(nick: &str, channel: &str, hour: u32, event: Event) -> Activity {
+        Activity {
+            nick: nick.to_string(),
+            channel: channel.to_string(),
+            at: Utc.ymd(2025, 3, 1).and_hms(hour, 0, 0),
+            event,
+        }
+    }
+
+    #[test]
+    async fn test_round_trip() {
+        let db = Database::in_memory().unwrap();
+        let activities = Activities::load(db.clone()).unwrap();
+        let rfc1459 = CaseMapping::Rfc1459;
+        let said = activity("Charlie", "#rust", 10, Event::Message("hello".to_string()));
+        let parted = activity("charlie", "#OCaml", 12, Event::Part);
+        activities.record("libera", rfc1459, &said).unwrap();
+        activities.record("libera", rfc1459, &parted).unwrap();
+        activities
+            .record(
+                "oftc",
+                rfc1459,
+                &activity("charlie", "#rust", 13, Event::Join),
+            )
+            .unwrap();
+
+        let activities = Activities::load(db).unwrap();
+        assert_eq!(
+            activities.of("libera", rfc1459, "CHARLIE").unwrap(),
+            vec![parted.clone(), said],
+            "after a restart, the last one first, only on that network"
+        );
+
+        let joined = activity("charlie", "#Rust", 14, Event::Join);
+        activities.record("libera", rfc1459, &joined).unwrap();
+        assert_eq!(
+            activities.of("libera", rfc1459, "charlie").unwrap(),
+            vec![joined, parted],
+            "replacing the previous activity in that channel"
+        );
+        assert_eq!(activities.of("libera", rfc1459, "alice").unwrap(), vec![]);
+    }
+
+    #[test]
+    async fn test_casemapping() {
+        let activities = Activities::load(Database::in_memory().unwrap()).unwrap();
+        let said = activity(
+            "[Charlie]",
+            "#rust",
+            10,
+            Event::Message("hello".to_string()),
+        );
+        activities
+            .record("libera", CaseMapping::Rfc1459, &said)
+            .unwrap();
+        activities
+            .record("oftc", CaseMapping::Ascii, &said)
+            .unwrap();
+        assert_eq!(
+            activities
+                .of("libera", CaseMapping::Rfc1459, "{charlie}")
+                .unwrap(),
+            vec![said.clone()]
+        );
+        assert_eq!(
+            activities
+                .of("oftc", CaseMapping::Ascii, "{charlie}")
+                .unwrap(),
+            vec![],
+            "different nicks in ascii"
+        );
+        assert_eq!(
+            activities
+                .of("oftc", CaseMapping::Ascii, "[CHARLIE]")
+                .unwrap(),
+            vec![said]
+        );
+    }
+}
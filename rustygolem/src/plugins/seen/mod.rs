@@ -0,0 +1,4 @@
+mod activity;
+mod plugin;
+
+pub use plugin::Seen;
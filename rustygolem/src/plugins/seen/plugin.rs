@@ -0,0 +1,554 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use irc::proto::{ChannelExt, Command, Message};
+use plugin_core::utils::network::network;
+use plugin_core::utils::parser;
+use plugin_core::{
+    CommandHelp, Initialised, Members, MsgCtx, Outbound, Plugin, Requirement, Result,
+};
+use serde::Deserialize;
+
+use super::activity::{Activities, Activity, Event};
+use crate::caps::CaseMapping;
+use crate::utils::messages::with_target;
+use crate::utils::text::sanitize;
+use crate::utils::time::format_ago;
+
+const USAGE: &str = "Usage: λseen <nick>";
+
+/// Longest message told by λseen, in chars, unless the config says otherwise
+pub const DEFAULT_MAX_LENGTH: usize = 100;
+
+/// The `seen` section of the golem config
+#[derive(Deserialize)]
+struct Settings {
+    /// where the messages are never told, only that the nick spoke
+    #[serde(default)]
+    private_channels: Vec<String>,
+    /// longer messages are cut, in chars
+    #[serde(default = "default_max_length")]
+    max_length: usize,
+}
+
+fn default_max_length() -> usize {
+    DEFAULT_MAX_LENGTH
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            private_channels: vec![],
+            max_length: default_max_length(),
+        }
+    }
+}
+
+impl Settings {
+    fn load(config: &plugin_core::Config) -> Result<Self> {
+        Ok(config.plugin_section("seen")?.unwrap_or_default())
+    }
+}
+
+pub struct Seen {
+    activities: Activities,
+    /// only the activity in the channels of the asker is told
+    members: Arc<Members>,
+    private_channels: Vec<String>,
+    max_length: usize,
+}
+
+#[async_trait]
+impl Plugin for Seen {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        Settings::load(config)?;
+        config.check_database("seen")?;
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let settings = Settings::load(config)?;
+        let db = config.require_database("seen")?;
+        Ok(Initialised::from(Seen {
+            activities: Activities::load(db)?,
+            members: config.members(),
+            private_channels: settings.private_channels,
+            max_length: settings.max_length,
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "seen"
+    }
+
+    async fn in_message_ctx(&self, ctx: &MsgCtx, msg: &Message) -> Result<Option<Outbound>> {
+        let reply = in_msg(self, msg, ctx.casemapping, Utc::now())?;
+        if let Err(err) = record(self, msg, ctx.casemapping, Utc::now()) {
+            log::error!("Error recording the activity of {msg:?}: {err:?}");
+        }
+        Ok(reply)
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![CommandHelp::new("seen")
+            .usage("seen <nick> [> nick]")
+            .description("When the nick was last active in one of your channels, and doing what")]
+    }
+
+    fn requirements(&self) -> Vec<Requirement> {
+        // who was seen where
+        vec![Requirement::Database]
+    }
+}
+
+impl Seen {
+    fn is_private(&self, casemapping: CaseMapping, channel: &str) -> bool {
+        self.private_channels
+            .iter()
+            .any(|c| casemapping.eq_ignore_case(c, channel))
+    }
+
+    /// The last activity of the nick in the channels where the asker is
+    fn last_seen(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        asker: &str,
+        nick: &str,
+    ) -> Result<Option<Activity>> {
+        let activities = self.activities.of(network, casemapping, nick)?;
+        Ok(activities
+            .into_iter()
+            .find(|activity| self.members.is_member(network, &activity.channel, asker)))
+    }
+}
+
+fn in_msg(
+    plugin: &Seen,
+    msg: &Message,
+    casemapping: CaseMapping,
+    now: DateTime<Utc>,
+) -> Result<Option<Outbound>> {
+    let response_target = match msg.response_target() {
+        None => return Ok(None),
+        Some(target) => target,
+    };
+    let privmsg = match &msg.command {
+        Command::PRIVMSG(_source, privmsg) => privmsg,
+        _ => return Ok(None),
+    };
+    let (args, mb_target) = match parser::command("seen")(privmsg) {
+        Ok((_, parsed)) => parsed,
+        Err(_) => return Ok(None),
+    };
+    let nick = match args.split_whitespace().next() {
+        Some(nick) => nick,
+        None => return Ok(Some(Outbound::reply(response_target, USAGE))),
+    };
+    let network = network(msg).unwrap_or_default();
+    let asker = msg.source_nickname().unwrap_or_default();
+    let text = match plugin.last_seen(network, casemapping, asker, nick)? {
+        // the activity in another channel is only told to the asker, not
+        // to everyone in this one
+        Some(activity)
+            if response_target.is_channel_name()
+                && !casemapping.eq_ignore_case(&activity.channel, response_target) =>
+        {
+            let private = plugin.is_private(casemapping, &activity.channel);
+            let text = describe(&activity, now, private, plugin.max_length);
+            return Ok(Some(Outbound::notice(asker, text)));
+        }
+        Some(activity) => {
+            let private = plugin.is_private(casemapping, &activity.channel);
+            describe(&activity, now, private, plugin.max_length)
+        }
+        None => format!("I haven't seen {nick}"),
+    };
+    Ok(Some(Outbound::reply(
+        response_target,
+        with_target(&text, &mb_target),
+    )))
+}
+
+/// Remembers what the nick of the message did
+fn record(
+    plugin: &Seen,
+    msg: &Message,
+    casemapping: CaseMapping,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    let network = network(msg).unwrap_or_default();
+    let nick = match msg.source_nickname() {
+        Some(nick) => nick,
+        None => return Ok(()),
+    };
+    let record = |channel: &str, nick: &str, event: Event| {
+        let activity = Activity {
+            nick: nick.to_string(),
+            channel: channel.to_string(),
+            at: now,
+            event,
+        };
+        plugin.activities.record(network, casemapping, &activity)
+    };
+    match &msg.command {
+        Command::PRIVMSG(target, text) if target.is_channel_name() => {
+            let text = match text
+                .strip_prefix("\x01ACTION ")
+                .map(|action| action.trim_end_matches('\x01'))
+            {
+                Some(action) => format!("* {nick} {action}"),
+                None => text.clone(),
+            };
+            record(target, nick, Event::Message(text))?;
+        }
+        Command::JOIN(channels, _, _) => {
+            for channel in channels.split(',') {
+                record(channel, nick, Event::Join)?;
+            }
+        }
+        Command::PART(channels, _) => {
+            for channel in channels.split(',') {
+                record(channel, nick, Event::Part)?;
+            }
+        }
+        // the golem doesn't know the channels anymore once the nick is gone,
+        // the ones where it was seen without leaving them are good enough
+        Command::QUIT(_) => {
+            for activity in plugin.activities.of(network, casemapping, nick)? {
+                if !activity.has_left() {
+                    record(&activity.channel, nick, Event::Quit)?;
+                }
+            }
+        }
+        Command::NICK(new_nick) => {
+            let activities = plugin.activities.of(network, casemapping, nick)?;
+            for activity in activities.into_iter().filter(|a| !a.has_left()) {
+                record(&activity.channel, nick, Event::Nick(new_nick.clone()))?;
+                record(
+                    &activity.channel,
+                    new_nick,
+                    Event::Renamed(nick.to_string()),
+                )?;
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+/// Like `charlie was last seen 2h ago saying "hello" in #rust`, without the
+/// message in the private channels
+fn describe(activity: &Activity, now: DateTime<Utc>, private: bool, max_length: usize) -> String {
+    let Activity {
+        nick, channel, at, ..
+    } = activity;
+    let ago = format_ago(now - *at);
+    match &activity.event {
+        Event::Message(_) if private => format!("{nick} was last seen {ago} speaking in {channel}"),
+        Event::Message(text) => format!(
+            "{nick} was last seen {ago} saying \"{}\" in {channel}",
+            sanitize(text, max_length)
+        ),
+        Event::Join => format!("{nick} was last seen {ago} joining {channel}"),
+        Event::Part => format!("{nick} was last seen {ago} leaving {channel}"),
+        Event::Quit => format!("{nick} was last seen {ago} quitting, last in {channel}"),
+        Event::Nick(new_nick) => {
+            format!("{nick} was last seen {ago} in {channel}, and is now known as {new_nick}")
+        }
+        Event::Renamed(old_nick) => {
+            format!("{nick} was last seen {ago} changing nick from {old_nick} in {channel}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use plugin_core::utils::network::set_network;
+    use plugin_core::Database;
+    use pretty_assertions::assert_eq;
+
+    fn seen(private_channels: &[&str]) -> Seen {
+        Seen {
+            activities: Activities::load(Database::in_memory().unwrap()).unwrap(),
+            members: members(),
+            private_channels: private_channels.iter().map(|c| c.to_string()).collect(),
+            max_length: 20,
+        }
+    }
+
+    /// golem, alice and bob in #rust, golem and alice in #secret
+    fn members() -> Arc<Members> {
+        let members = Members::default();
+        for (nick, channel) in [
+            ("golem", "#rust"),
+            ("alice", "#rust"),
+            ("bob", "#rust"),
+            ("golem", "#secret"),
+            ("alice", "#secret"),
+        ] {
+            let source = format!("{nick}!~{nick}@localhost");
+            let join = Message::new(Some(&source), "JOIN", vec![channel]).unwrap();
+            members.on_message("libera", "golem", &join);
+        }
+        Arc::new(members)
+    }
+
+    fn at(hour: u32, min: u32) -> DateTime<Utc> {
+        Utc.ymd(2025, 3, 1).and_hms(hour, min, 0)
+    }
+
+    fn message(nick: &str, command: &str, args: Vec<&str>) -> Message {
+        let source = format!("{nick}!~{nick}@localhost");
+        let mut msg = Message::new(Some(&source), command, args).unwrap();
+        set_network(&mut msg, "libera");
+        msg
+    }
+
+    fn ask(plugin: &Seen, asker: &str, target: &str, text: &str, now: DateTime<Utc>) -> String {
+        match in_msg(
+            plugin,
+            &message(asker, "PRIVMSG", vec![target, text]),
+            CaseMapping::Rfc1459,
+            now,
+        )
+        .unwrap()
+        {
+            Some(Outbound::Reply { text, .. }) => text,
+            other => panic!("no reply to {text:?}: {other:?}"),
+        }
+    }
+
+    #[test]
+    async fn test_seen() {
+        let plugin = seen(&[]);
+        let said = message(
+            "Charlie",
+            "PRIVMSG",
+            vec!["#rust", "hello there, anyone using nom?"],
+        );
+        record(&plugin, &said, CaseMapping::Rfc1459, at(10, 0)).unwrap();
+        assert_eq!(
+            ask(&plugin, "bob", "#rust", "λseen charlie", at(12, 0)),
+            "Charlie was last seen 2h ago saying \"hello there, anyone…\" in #rust"
+        );
+        assert_eq!(
+            ask(&plugin, "bob", "#rust", "λseen charlie > alice", at(12, 0)),
+            "alice: Charlie was last seen 2h ago saying \"hello there, anyone…\" in #rust"
+        );
+        assert_eq!(
+            ask(&plugin, "bob", "golem", "λseen dave", at(12, 0)),
+            "I haven't seen dave"
+        );
+        assert_eq!(ask(&plugin, "bob", "#rust", "λseen", at(12, 0)), USAGE);
+
+        record(
+            &plugin,
+            &message("charlie", "PRIVMSG", vec!["#rust", "\x01ACTION waves\x01"]),
+            CaseMapping::Rfc1459,
+            at(11, 0),
+        )
+        .unwrap();
+        assert_eq!(
+            ask(&plugin, "bob", "#rust", "λseen charlie", at(12, 0)),
+            "charlie was last seen 1h ago saying \"* charlie waves\" in #rust"
+        );
+        record(
+            &plugin,
+            &message("charlie", "PART", vec!["#rust"]),
+            CaseMapping::Rfc1459,
+            at(11, 30),
+        )
+        .unwrap();
+        assert_eq!(
+            ask(&plugin, "bob", "#rust", "λseen charlie", at(12, 0)),
+            "charlie was last seen 30min ago leaving #rust"
+        );
+    }
+
+    #[test]
+    async fn test_shared_channels_only() {
+        let plugin = seen(&["#secret"]);
+        record(
+            &plugin,
+            &message("charlie", "PRIVMSG", vec!["#rust", "hello"]),
+            CaseMapping::Rfc1459,
+            at(10, 0),
+        )
+        .unwrap();
+        record(
+            &plugin,
+            &message("charlie", "PRIVMSG", vec!["#secret", "psst"]),
+            CaseMapping::Rfc1459,
+            at(11, 0),
+        )
+        .unwrap();
+        assert_eq!(
+            ask(&plugin, "alice", "golem", "λseen charlie", at(12, 0)),
+            "charlie was last seen 1h ago speaking in #secret",
+            "without the message of a private channel"
+        );
+        assert_eq!(
+            ask(&plugin, "bob", "#rust", "λseen charlie", at(12, 0)),
+            "charlie was last seen 2h ago saying \"hello\" in #rust",
+            "bob isn't in #secret"
+        );
+        record(
+            &plugin,
+            &message("dave", "JOIN", vec!["#secret"]),
+            CaseMapping::Rfc1459,
+            at(11, 0),
+        )
+        .unwrap();
+        assert_eq!(
+            ask(&plugin, "bob", "golem", "λseen dave", at(12, 0)),
+            "I haven't seen dave"
+        );
+        assert_eq!(
+            ask(&plugin, "mallory", "golem", "λseen charlie", at(12, 0)),
+            "I haven't seen charlie",
+            "in none of the channels"
+        );
+    }
+
+    #[test]
+    async fn test_nick_and_quit() {
+        let plugin = seen(&[]);
+        record(
+            &plugin,
+            &message("charlie", "JOIN", vec!["#rust"]),
+            CaseMapping::Rfc1459,
+            at(9, 0),
+        )
+        .unwrap();
+        record(
+            &plugin,
+            &message("charlie", "NICK", vec!["charlye"]),
+            CaseMapping::Rfc1459,
+            at(10, 0),
+        )
+        .unwrap();
+        assert_eq!(
+            ask(&plugin, "bob", "#rust", "λseen charlie", at(12, 0)),
+            "charlie was last seen 2h ago in #rust, and is now known as charlye"
+        );
+        assert_eq!(
+            ask(&plugin, "bob", "#rust", "λseen charlye", at(12, 0)),
+            "charlye was last seen 2h ago changing nick from charlie in #rust"
+        );
+        record(
+            &plugin,
+            &message("charlye", "QUIT", vec!["Ping timeout"]),
+            CaseMapping::Rfc1459,
+            at(11, 0),
+        )
+        .unwrap();
+        assert_eq!(
+            ask(&plugin, "bob", "#rust", "λseen charlye", at(12, 0)),
+            "charlye was last seen 1h ago quitting, last in #rust"
+        );
+    }
+
+    #[test]
+    async fn test_elsewhere() {
+        let plugin = seen(&[]);
+        record(
+            &plugin,
+            &message("charlie", "PRIVMSG", vec!["#secret", "psst"]),
+            CaseMapping::Rfc1459,
+            at(11, 0),
+        )
+        .unwrap();
+        let asked = message("alice", "PRIVMSG", vec!["#rust", "λseen charlie > bob"]);
+        assert_eq!(
+            in_msg(&plugin, &asked, CaseMapping::Rfc1459, at(12, 0)).unwrap(),
+            Some(Outbound::notice(
+                "alice",
+                "charlie was last seen 1h ago saying \"psst\" in #secret"
+            )),
+            "not told to everyone in #rust"
+        );
+        assert_eq!(
+            ask(&plugin, "alice", "#SECRET", "λseen charlie", at(12, 0)),
+            "charlie was last seen 1h ago saying \"psst\" in #secret"
+        );
+    }
+
+    #[test]
+    async fn test_left_before_quitting() {
+        let plugin = seen(&[]);
+        for (nick, command, args, hour) in [
+            ("charlie", "JOIN", vec!["#rust"], 9),
+            ("charlie", "JOIN", vec!["#secret"], 9),
+            ("charlie", "PART", vec!["#secret"], 10),
+            ("charlie", "NICK", vec!["charlye"], 11),
+            ("charlye", "QUIT", vec!["Ping timeout"], 11),
+        ] {
+            record(
+                &plugin,
+                &message(nick, command, args),
+                CaseMapping::Rfc1459,
+                at(hour, 0),
+            )
+            .unwrap();
+        }
+        let events = |nick| {
+            let activities = plugin.activities.of("libera", CaseMapping::Rfc1459, nick);
+            let activities = activities.unwrap().into_iter();
+            activities.map(|a| (a.channel, a.event)).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            events("charlie"),
+            vec![
+                ("#rust".to_string(), Event::Nick("charlye".to_string())),
+                ("#secret".to_string(), Event::Part),
+            ],
+            "not renamed in a channel already left"
+        );
+        assert_eq!(events("charlye"), vec![("#rust".to_string(), Event::Quit)]);
+    }
+
+    #[test]
+    async fn test_casemapping() {
+        let plugin = seen(&[]);
+        record(
+            &plugin,
+            &message("[charlie]", "JOIN", vec!["#rust"]),
+            CaseMapping::Rfc1459,
+            at(10, 0),
+        )
+        .unwrap();
+        assert_eq!(
+            ask(&plugin, "bob", "#rust", "λseen {CHARLIE}", at(12, 0)),
+            "[charlie] was last seen 2h ago joining #rust",
+            "in rfc1459"
+        );
+        let plugin = seen(&[]);
+        record(
+            &plugin,
+            &message("[charlie]", "JOIN", vec!["#rust"]),
+            CaseMapping::Ascii,
+            at(10, 0),
+        )
+        .unwrap();
+        let ask_ascii = |text: &str| {
+            let asked = message("bob", "PRIVMSG", vec!["#rust", text]);
+            match in_msg(&plugin, &asked, CaseMapping::Ascii, at(12, 0)).unwrap() {
+                Some(Outbound::Reply { text, .. }) => text,
+                other => panic!("no reply to {text:?}: {other:?}"),
+            }
+        };
+        assert_eq!(
+            ask_ascii("λseen {CHARLIE}"),
+            "I haven't seen {CHARLIE}",
+            "another nick in ascii"
+        );
+        assert_eq!(
+            ask_ascii("λseen [CHARLIE]"),
+            "[charlie] was last seen 2h ago joining #rust"
+        );
+    }
+}
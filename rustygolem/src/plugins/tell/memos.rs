@@ -0,0 +1,369 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+use plugin_core::{Database, Result};
+use std::sync::Mutex;
+
+use crate::caps::CaseMapping;
+
+/// The tables of the tell plugin in the shared database, see
+/// `plugin_core::ensure_schema`
+const MIGRATIONS: &[&str] = &[
+    // channel is where the memo was left, and where it is told
+    "CREATE TABLE tell_memos (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        network TEXT NOT NULL,
+        channel TEXT NOT NULL,
+        sender TEXT NOT NULL,
+        recipient TEXT NOT NULL,
+        text TEXT NOT NULL,
+        left_at TEXT NOT NULL
+    );",
+];
+
+#[derive(QueryableByName)]
+struct LastId {
+    #[sql_type = "BigInt"]
+    id: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, QueryableByName)]
+pub struct Memo {
+    #[sql_type = "BigInt"]
+    pub id: i64,
+    #[sql_type = "Text"]
+    pub network: String,
+    #[sql_type = "Text"]
+    pub channel: String,
+    #[sql_type = "Text"]
+    pub sender: String,
+    #[sql_type = "Text"]
+    pub recipient: String,
+    #[sql_type = "Text"]
+    pub text: String,
+    #[sql_type = "Text"]
+    left_at: String,
+}
+
+impl Memo {
+    pub fn new(
+        network: &str,
+        channel: &str,
+        sender: &str,
+        recipient: &str,
+        text: &str,
+        left_at: DateTime<Utc>,
+    ) -> Self {
+        Memo {
+            id: 0,
+            network: network.to_string(),
+            channel: channel.to_string(),
+            sender: sender.to_string(),
+            recipient: recipient.to_string(),
+            text: text.to_string(),
+            left_at: left_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+
+    pub fn left_at(&self) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&self.left_at)
+            .map(|at| at.with_timezone(&Utc))
+            .unwrap_or_else(|err| {
+                log::warn!(
+                    "Invalid time of memo #{} {:?}: {err}",
+                    self.id,
+                    self.left_at
+                );
+                Utc::now()
+            })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Left {
+    Memo(Memo),
+    /// the sender already has that many pending memos
+    TooMany,
+}
+
+/// The memos not told yet, persisted in the database
+pub struct Memos {
+    db: Database,
+    pending: Mutex<Vec<Memo>>,
+}
+
+impl Memos {
+    /// Create the table if needed, and load the memos from before the last restart
+    pub fn load(db: Database) -> Result<Self> {
+        plugin_core::ensure_schema(&db, "tell", MIGRATIONS)?;
+        let pending = db.with_connection(|conn| {
+            diesel::sql_query(
+                "SELECT id, network, channel, sender, recipient, text, left_at \
+                 FROM tell_memos ORDER BY id",
+            )
+            .load::<Memo>(conn)
+        })?;
+        log::info!("Loaded {} pending memos", pending.len());
+        Ok(Memos {
+            db,
+            pending: Mutex::new(pending),
+        })
+    }
+
+    /// Unless the sender already has `max_per_sender` pending memos on that network
+    pub fn add(
+        &self,
+        mut memo: Memo,
+        casemapping: CaseMapping,
+        max_per_sender: usize,
+    ) -> Result<Left> {
+        let mut pending = self.pending.lock().expect("memos lock");
+        let sent = pending
+            .iter()
+            .filter(|m| {
+                m.network == memo.network && casemapping.eq_ignore_case(&m.sender, &memo.sender)
+            })
+            .count();
+        if sent >= max_per_sender {
+            return Ok(Left::TooMany);
+        }
+        let id = self.db.with_connection(|conn| {
+            conn.transaction(|| {
+                diesel::sql_query(
+                    "INSERT INTO tell_memos (network, channel, sender, recipient, text, left_at) \
+                     VALUES (?, ?, ?, ?, ?, ?)",
+                )
+                .bind::<Text, _>(&memo.network)
+                .bind::<Text, _>(&memo.channel)
+                .bind::<Text, _>(&memo.sender)
+                .bind::<Text, _>(&memo.recipient)
+                .bind::<Text, _>(&memo.text)
+                .bind::<Text, _>(&memo.left_at)
+                .execute(conn)?;
+                diesel::sql_query("SELECT last_insert_rowid() AS id").get_result::<LastId>(conn)
+            })
+        })?;
+        memo.id = id.id;
+        pending.push(memo.clone());
+        Ok(Left::Memo(memo))
+    }
+
+    /// The pending memos left by the sender on that network
+    pub fn sent_by(&self, network: &str, casemapping: CaseMapping, sender: &str) -> Vec<Memo> {
+        self.pending
+            .lock()
+            .expect("memos lock")
+            .iter()
+            .filter(|m| m.network == network && casemapping.eq_ignore_case(&m.sender, sender))
+            .cloned()
+            .collect()
+    }
+
+    /// Only the memos of the sender can be cancelled. False when it has no such memo.
+    pub fn cancel(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        sender: &str,
+        id: i64,
+    ) -> Result<bool> {
+        let mut pending = self.pending.lock().expect("memos lock");
+        match pending.iter().position(|m| {
+            m.id == id && m.network == network && casemapping.eq_ignore_case(&m.sender, sender)
+        }) {
+            Some(idx) => {
+                self.delete(&[id])?;
+                pending.remove(idx);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// The channels where memos wait for the recipient
+    pub fn channels_of(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        recipient: &str,
+    ) -> Vec<String> {
+        let mut channels: Vec<String> = vec![];
+        for memo in self.pending.lock().expect("memos lock").iter() {
+            if memo.network == network
+                && casemapping.eq_ignore_case(&memo.recipient, recipient)
+                && !channels
+                    .iter()
+                    .any(|c| casemapping.eq_ignore_case(c, &memo.channel))
+            {
+                channels.push(memo.channel.clone());
+            }
+        }
+        channels
+    }
+
+    /// Removes and returns the memos for the recipient left in that channel,
+    /// the oldest first. They stay in the database until `told`, to be told
+    /// after a restart if they couldn't be sent.
+    pub fn take(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        recipient: &str,
+    ) -> Vec<Memo> {
+        let mut pending = self.pending.lock().expect("memos lock");
+        let (taken, kept): (Vec<_>, Vec<_>) = pending.drain(..).partition(|m| {
+            m.network == network
+                && casemapping.eq_ignore_case(&m.channel, channel)
+                && casemapping.eq_ignore_case(&m.recipient, recipient)
+        });
+        *pending = kept;
+        taken
+    }
+
+    /// Forgets a memo taken once sent
+    pub fn told(&self, id: i64) -> Result<()> {
+        self.delete(&[id])
+    }
+
+    fn delete(&self, ids: &[i64]) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        self.db.with_connection(|conn| {
+            conn.transaction(|| {
+                for id in ids {
+                    diesel::sql_query("DELETE FROM tell_memos WHERE id = ?")
+                        .bind::<BigInt, _>(id)
+                        .execute(conn)?;
+                }
+                Ok(())
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    const RFC1459: CaseMapping = CaseMapping::Rfc1459;
+
+    fn memo(channel: &str, sender: &str, recipient: &str, text: &str) -> Memo {
+        let left_at = Utc.ymd(2025, 3, 1).and_hms(10, 0, 0);
+        Memo::new("libera", channel, sender, recipient, text, left_at)
+    }
+
+    fn add(memos: &Memos, memo: Memo) -> Left {
+        memos.add(memo, RFC1459, 2).unwrap()
+    }
+
+    #[test]
+    async fn test_cap() {
+        let memos = Memos::load(Database::in_memory().unwrap()).unwrap();
+        assert!(matches!(
+            add(&memos, memo("#rust", "alice", "charlie", "one")),
+            Left::Memo(_)
+        ));
+        assert!(matches!(
+            add(&memos, memo("#ocaml", "Alice", "bob", "two")),
+            Left::Memo(_)
+        ));
+        assert_eq!(
+            add(&memos, memo("#rust", "ALICE", "dave", "three")),
+            Left::TooMany,
+            "across channels and recipients"
+        );
+        assert!(
+            matches!(
+                add(&memos, memo("#rust", "bob", "charlie", "four")),
+                Left::Memo(_)
+            ),
+            "per sender"
+        );
+        let mut oftc = memo("#rust", "alice", "charlie", "five");
+        oftc.network = "oftc".to_string();
+        assert!(matches!(add(&memos, oftc), Left::Memo(_)), "per network");
+
+        let id = memos.sent_by("libera", RFC1459, "alice")[0].id;
+        assert!(
+            !memos.cancel("libera", RFC1459, "bob", id).unwrap(),
+            "not bob's"
+        );
+        assert!(memos.cancel("libera", RFC1459, "alice", id).unwrap());
+        assert!(
+            !memos.cancel("libera", RFC1459, "alice", id).unwrap(),
+            "already cancelled"
+        );
+        assert!(
+            matches!(
+                add(&memos, memo("#rust", "alice", "dave", "three")),
+                Left::Memo(_)
+            ),
+            "room for another one once cancelled"
+        );
+    }
+
+    #[test]
+    async fn test_take() {
+        let memos = Memos::load(Database::in_memory().unwrap()).unwrap();
+        add(&memos, memo("#rust", "alice", "[charlie]", "one"));
+        add(&memos, memo("#ocaml", "alice", "charlie", "two"));
+        add(&memos, memo("#Rust", "bob", "{Charlie}", "three"));
+        assert_eq!(
+            memos.channels_of("libera", RFC1459, "{CHARLIE}"),
+            vec!["#rust".to_string()]
+        );
+        assert_eq!(memos.take("libera", RFC1459, "#rust", "dave"), vec![]);
+        let taken = memos.take("libera", RFC1459, "#RUST", "{charlie}");
+        assert_eq!(
+            taken.iter().map(|m| m.text.as_str()).collect::<Vec<_>>(),
+            vec!["one", "three"],
+            "the oldest first"
+        );
+        assert_eq!(memos.take("libera", RFC1459, "#rust", "{charlie}"), vec![]);
+        assert_eq!(
+            memos
+                .take("libera", CaseMapping::Ascii, "#ocaml", "CHARLIE")
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    async fn test_persistence() {
+        let db = Database::in_memory().unwrap();
+        let memos = Memos::load(db.clone()).unwrap();
+        let kept = match add(
+            &memos,
+            memo("#rust", "alice", "charlie", "your build is fixed"),
+        ) {
+            Left::Memo(memo) => memo,
+            Left::TooMany => panic!("a single memo"),
+        };
+        add(&memos, memo("#rust", "bob", "dave", "told"));
+        add(&memos, memo("#rust", "bob", "erin", "never sent"));
+        for memo in memos.take("libera", RFC1459, "#rust", "dave") {
+            memos.told(memo.id).unwrap();
+        }
+        memos.take("libera", RFC1459, "#rust", "erin");
+
+        let memos = Memos::load(db).unwrap();
+        assert_eq!(
+            memos.sent_by("libera", RFC1459, "alice"),
+            vec![kept.clone()]
+        );
+        assert_eq!(
+            memos
+                .sent_by("libera", RFC1459, "bob")
+                .iter()
+                .map(|m| m.text.as_str())
+                .collect::<Vec<_>>(),
+            vec!["never sent"],
+            "only the ones told are forgotten"
+        );
+        assert_eq!(kept.left_at(), Utc.ymd(2025, 3, 1).and_hms(10, 0, 0));
+    }
+}
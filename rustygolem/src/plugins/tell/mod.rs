@@ -0,0 +1,4 @@
+mod memos;
+mod plugin;
+
+pub use plugin::Tell;
@@ -0,0 +1,545 @@
+use std::result::Result as StdResult;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use irc::proto::{ChannelExt, Command, Message};
+use nom::bytes::complete::tag;
+use nom::sequence::preceded;
+use plugin_core::utils::network::{network, set_network};
+use plugin_core::utils::parser;
+use plugin_core::{
+    CommandHelp, Initialised, Members, MsgCtx, Outbound, Plugin, Requirement, Result,
+};
+use serde::Deserialize;
+use tokio::sync::{mpsc, Notify};
+
+use super::memos::{Left, Memo, Memos};
+use crate::caps::CaseMapping;
+use crate::utils::text::sanitize;
+use crate::utils::time::format_ago;
+
+const USAGE: &str = "Usage: λtell <nick> <message>, λtell list, λtell cancel <id>";
+
+/// Pending memos a nick can have on a network, unless the config says otherwise
+pub const DEFAULT_MAX_PER_SENDER: usize = 5;
+/// Longest memo, in chars, unless the config says otherwise
+pub const DEFAULT_MAX_LENGTH: usize = 300;
+
+/// The `tell` section of the golem config
+#[derive(Deserialize)]
+struct Settings {
+    /// also tell the memos when the recipient joins the channel, not only
+    /// when they speak there
+    #[serde(default)]
+    deliver_on_join: bool,
+    #[serde(default = "default_max_per_sender")]
+    max_per_sender: usize,
+    /// longer memos are cut, in chars
+    #[serde(default = "default_max_length")]
+    max_length: usize,
+}
+
+fn default_max_per_sender() -> usize {
+    DEFAULT_MAX_PER_SENDER
+}
+
+fn default_max_length() -> usize {
+    DEFAULT_MAX_LENGTH
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            deliver_on_join: false,
+            max_per_sender: default_max_per_sender(),
+            max_length: default_max_length(),
+        }
+    }
+}
+
+impl Settings {
+    fn load(config: &plugin_core::Config) -> Result<Self> {
+        Ok(config.plugin_section("tell")?.unwrap_or_default())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum TellCommand<'a> {
+    Leave { nick: &'a str, text: &'a str },
+    List,
+    Cancel(i64),
+}
+
+/// None when this isn't a tell command, an error with the usage when it
+/// is one, but malformed. The whole text is the memo, `> nick` included.
+fn parse_command(input: &str) -> Option<StdResult<TellCommand<'_>, String>> {
+    let (rest, _) = preceded(parser::command_prefix, tag("tell"))(input).ok()?;
+    if rest.starts_with(|c: char| !c.is_whitespace()) {
+        return None;
+    }
+    let (word, text) = match rest.trim().split_once(char::is_whitespace) {
+        Some((word, text)) => (word, text.trim()),
+        None => (rest.trim(), ""),
+    };
+    let command = match (word, text) {
+        ("list", "") => Some(TellCommand::List),
+        ("cancel", id) => id
+            .trim_start_matches('#')
+            .parse()
+            .ok()
+            .map(TellCommand::Cancel),
+        ("", _) | (_, "") => None,
+        (nick, text) => Some(TellCommand::Leave { nick, text }),
+    };
+    Some(command.ok_or_else(|| USAGE.to_string()))
+}
+
+pub struct Tell {
+    memos: Memos,
+    /// to tell the memos after a nick change, in the channels of the new nick
+    members: Arc<Members>,
+    /// the memos to tell with their id, sent by `run`
+    deliveries: Mutex<Vec<(i64, Outbound)>>,
+    delivered: Notify,
+    deliver_on_join: bool,
+    max_per_sender: usize,
+    max_length: usize,
+}
+
+#[async_trait]
+impl Plugin for Tell {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        Settings::load(config)?;
+        config.check_database("tell")?;
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let settings = Settings::load(config)?;
+        let db = config.require_database("tell")?;
+        Ok(Initialised::from(Tell {
+            memos: Memos::load(db)?,
+            members: config.members(),
+            deliveries: Mutex::new(vec![]),
+            delivered: Notify::new(),
+            deliver_on_join: settings.deliver_on_join,
+            max_per_sender: settings.max_per_sender,
+            max_length: settings.max_length,
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "tell"
+    }
+
+    async fn in_message_ctx(&self, ctx: &MsgCtx, msg: &Message) -> Result<Option<Outbound>> {
+        let now = Utc::now();
+        let reply = in_msg(self, msg, ctx.casemapping, now)?;
+        let deliveries = deliver(self, msg, ctx.casemapping, now);
+        if !deliveries.is_empty() {
+            self.deliveries
+                .lock()
+                .expect("deliveries lock")
+                .extend(deliveries);
+            self.delivered.notify_one();
+        }
+        Ok(reply)
+    }
+
+    /// Sends the memos queued by `in_message_ctx` once their recipient is back,
+    /// and only then forgets them
+    async fn run(&self, bot_chan: mpsc::Sender<Outbound>) -> Result<()> {
+        loop {
+            let deliveries = std::mem::take(&mut *self.deliveries.lock().expect("deliveries lock"));
+            for (id, outbound) in deliveries {
+                bot_chan.send(outbound).await.map_err(anyhow::Error::from)?;
+                if let Err(err) = self.memos.told(id) {
+                    log::warn!("Couldn't forget memo #{id} once told: {err}");
+                }
+            }
+            self.delivered.notified().await;
+        }
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("tell")
+                .usage("tell <nick> <message>")
+                .description("Tell the message here to the nick, the next time they speak here"),
+            CommandHelp::new("tell list")
+                .usage("tell list")
+                .description("The memos you left that weren't told yet"),
+            CommandHelp::new("tell cancel")
+                .usage("tell cancel <id>")
+                .description("Don't tell that memo you left"),
+        ]
+    }
+
+    fn requirements(&self) -> Vec<Requirement> {
+        // the memos waiting for their recipient
+        vec![Requirement::Database]
+    }
+}
+
+fn in_msg(
+    plugin: &Tell,
+    msg: &Message,
+    casemapping: CaseMapping,
+    now: DateTime<Utc>,
+) -> Result<Option<Outbound>> {
+    let response_target = match msg.response_target() {
+        None => return Ok(None),
+        Some(target) => target,
+    };
+    let network = network(msg).unwrap_or_default();
+    let command = match &msg.command {
+        Command::PRIVMSG(_source, privmsg) => match parse_command(privmsg) {
+            Some(command) => command,
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    let sender = msg.source_nickname().unwrap_or_default();
+    let text = match command {
+        Err(usage) => usage,
+        Ok(TellCommand::Leave { .. }) if !response_target.is_channel_name() => {
+            "Memos can only be left in a channel, to be told there".to_string()
+        }
+        Ok(TellCommand::Leave { nick, .. }) if casemapping.eq_ignore_case(nick, sender) => {
+            "You can't tell yourself".to_string()
+        }
+        Ok(TellCommand::Leave { nick, text }) => {
+            let text = sanitize(text, plugin.max_length);
+            if text.is_empty() {
+                return Ok(Some(Outbound::reply(response_target, USAGE)));
+            }
+            let memo = Memo::new(network, response_target, sender, nick, &text, now);
+            match plugin.memos.add(memo, casemapping, plugin.max_per_sender)? {
+                Left::Memo(memo) => format!(
+                    "Memo #{} for {nick} saved, told the next time they {} here",
+                    memo.id,
+                    if plugin.deliver_on_join {
+                        "speak or join"
+                    } else {
+                        "speak"
+                    }
+                ),
+                Left::TooMany => format!(
+                    "You already have {} pending memos, cancel one with λtell cancel <id>",
+                    plugin.max_per_sender
+                ),
+            }
+        }
+        Ok(TellCommand::List) => {
+            let memos = plugin.memos.sent_by(network, casemapping, sender);
+            if memos.is_empty() {
+                "You have no pending memo".to_string()
+            } else {
+                memos
+                    .iter()
+                    .map(|m| {
+                        format!(
+                            "#{} for {} in {}: {}",
+                            m.id,
+                            m.recipient,
+                            m.channel,
+                            sanitize(&m.text, 30)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        }
+        Ok(TellCommand::Cancel(id)) => {
+            if plugin.memos.cancel(network, casemapping, sender, id)? {
+                format!("Memo #{id} cancelled")
+            } else {
+                format!("You have no pending memo #{id}")
+            }
+        }
+    };
+    Ok(Some(Outbound::reply(response_target, text)))
+}
+
+/// The memos to tell now that the nick of the message is back: when it
+/// speaks in the channel of a memo, joins it if configured, or when a nick
+/// in there takes the nick of the recipient. Each with the id of its memo.
+fn deliver(
+    plugin: &Tell,
+    msg: &Message,
+    casemapping: CaseMapping,
+    now: DateTime<Utc>,
+) -> Vec<(i64, Outbound)> {
+    let network = network(msg).unwrap_or_default();
+    let nick = match msg.source_nickname() {
+        Some(nick) => nick,
+        None => return vec![],
+    };
+    let (nick, channels) = match &msg.command {
+        Command::PRIVMSG(target, _) if target.is_channel_name() => (nick, vec![target.clone()]),
+        Command::JOIN(channels, _, _) if plugin.deliver_on_join => {
+            (nick, channels.split(',').map(String::from).collect())
+        }
+        Command::NICK(new_nick) => {
+            let channels = plugin
+                .memos
+                .channels_of(network, casemapping, new_nick)
+                .into_iter()
+                .filter(|channel| plugin.members.is_member(network, channel, new_nick))
+                .collect();
+            (new_nick.as_str(), channels)
+        }
+        _ => return vec![],
+    };
+    let mut deliveries = vec![];
+    for channel in channels {
+        for memo in plugin.memos.take(network, casemapping, &channel, nick) {
+            let text = format!(
+                "{nick}: message from {} ({}): {}",
+                memo.sender,
+                format_ago(now - memo.left_at()),
+                memo.text
+            );
+            deliveries.push((memo.id, on_network(&memo.network, &memo.channel, text)));
+        }
+    }
+    deliveries
+}
+
+/// Sent by `run`, to the network of the memo rather than any with that channel
+fn on_network(network: &str, channel: &str, text: String) -> Outbound {
+    let mut msg = Message::from(Outbound::reply(channel, text));
+    set_network(&mut msg, network);
+    Outbound::Raw(msg)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use plugin_core::Database;
+    use pretty_assertions::assert_eq;
+
+    fn tell(deliver_on_join: bool) -> Tell {
+        Tell {
+            memos: Memos::load(Database::in_memory().unwrap()).unwrap(),
+            members: Arc::default(),
+            deliveries: Mutex::new(vec![]),
+            delivered: Notify::new(),
+            deliver_on_join,
+            max_per_sender: 2,
+            max_length: DEFAULT_MAX_LENGTH,
+        }
+    }
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.ymd(2025, 3, 1).and_hms(hour, 0, 0)
+    }
+
+    fn message(nick: &str, command: &str, args: Vec<&str>) -> Message {
+        let source = format!("{nick}!~{nick}@localhost");
+        let mut msg = Message::new(Some(&source), command, args).unwrap();
+        set_network(&mut msg, "libera");
+        msg
+    }
+
+    fn ask(plugin: &Tell, nick: &str, target: &str, text: &str) -> String {
+        match in_msg(
+            plugin,
+            &message(nick, "PRIVMSG", vec![target, text]),
+            CaseMapping::Rfc1459,
+            at(10),
+        )
+        .unwrap()
+        {
+            Some(Outbound::Reply { text, .. }) => text,
+            other => panic!("no reply to {text:?}: {other:?}"),
+        }
+    }
+
+    fn on_libera(channel: &str, text: &str) -> Outbound {
+        on_network("libera", channel, text.to_string())
+    }
+
+    fn delivered(plugin: &Tell, msg: &Message, now: DateTime<Utc>) -> Vec<Outbound> {
+        let deliveries = deliver(plugin, msg, CaseMapping::Rfc1459, now).into_iter();
+        deliveries.map(|(_, outbound)| outbound).collect()
+    }
+
+    fn said(plugin: &Tell, nick: &str, channel: &str, hour: u32) -> Vec<Outbound> {
+        let msg = message(nick, "PRIVMSG", vec![channel, "hi"]);
+        delivered(plugin, &msg, at(hour))
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        for (input, expected) in [
+            (
+                "λtell charlie your build is fixed",
+                Some(Ok(TellCommand::Leave {
+                    nick: "charlie",
+                    text: "your build is fixed",
+                })),
+            ),
+            (
+                "λtell charlie  ask > bob ",
+                Some(Ok(TellCommand::Leave {
+                    nick: "charlie",
+                    text: "ask > bob",
+                })),
+            ),
+            ("λtell list", Some(Ok(TellCommand::List))),
+            ("λtell cancel 3", Some(Ok(TellCommand::Cancel(3)))),
+            ("λtell cancel #3", Some(Ok(TellCommand::Cancel(3)))),
+            ("λtell cancel three", Some(Err(USAGE.to_string()))),
+            ("λtell cancel", Some(Err(USAGE.to_string()))),
+            ("λtell charlie", Some(Err(USAGE.to_string()))),
+            ("λtell", Some(Err(USAGE.to_string()))),
+            ("λtelly charlie hi", None),
+            ("tell charlie hi", None),
+        ] {
+            assert_eq!(parse_command(input), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    async fn test_leave() {
+        let plugin = tell(false);
+        assert_eq!(
+            ask(
+                &plugin,
+                "alice",
+                "#rust",
+                "λtell charlie your build is fixed"
+            ),
+            "Memo #1 for charlie saved, told the next time they speak here"
+        );
+        assert_eq!(
+            ask(&plugin, "alice", "golem", "λtell charlie hi"),
+            "Memos can only be left in a channel, to be told there"
+        );
+        assert_eq!(
+            ask(&plugin, "alice", "#rust", "λtell ALICE hi"),
+            "You can't tell yourself"
+        );
+        ask(&plugin, "alice", "#ocaml", "λtell dave the pizza is here");
+        assert_eq!(
+            ask(&plugin, "alice", "#rust", "λtell dave another one"),
+            "You already have 2 pending memos, cancel one with λtell cancel <id>"
+        );
+        assert_eq!(
+            ask(&plugin, "alice", "golem", "λtell list"),
+            "#1 for charlie in #rust: your build is fixed, #2 for dave in #ocaml: the pizza is here"
+        );
+        assert_eq!(
+            ask(&plugin, "bob", "#rust", "λtell list"),
+            "You have no pending memo"
+        );
+        assert_eq!(
+            ask(&plugin, "bob", "#rust", "λtell cancel 2"),
+            "You have no pending memo #2"
+        );
+        assert_eq!(
+            ask(&plugin, "alice", "#rust", "λtell cancel 2"),
+            "Memo #2 cancelled"
+        );
+        assert_eq!(
+            ask(&plugin, "alice", "#rust", "λtell list"),
+            "#1 for charlie in #rust: your build is fixed"
+        );
+    }
+
+    #[test]
+    async fn test_deliver_on_message() {
+        let plugin = tell(false);
+        ask(
+            &plugin,
+            "alice",
+            "#rust",
+            "λtell charlie your build is fixed",
+        );
+        ask(&plugin, "bob", "#rust", "λtell Charlie lunch?");
+        assert_eq!(
+            said(&plugin, "charlie", "#ocaml", 13),
+            vec![],
+            "only where left"
+        );
+        assert_eq!(said(&plugin, "dave", "#rust", 13), vec![]);
+        let join = message("charlie", "JOIN", vec!["#rust"]);
+        assert_eq!(delivered(&plugin, &join, at(13)), vec![], "not on join");
+        assert_eq!(
+            said(&plugin, "CHARLIE", "#rust", 13),
+            vec![
+                on_libera(
+                    "#rust",
+                    "CHARLIE: message from alice (3h ago): your build is fixed"
+                ),
+                on_libera("#rust", "CHARLIE: message from bob (3h ago): lunch?"),
+            ]
+        );
+        assert_eq!(said(&plugin, "charlie", "#rust", 14), vec![], "told once");
+    }
+
+    #[test]
+    async fn test_deliver_on_join() {
+        let plugin = tell(true);
+        ask(
+            &plugin,
+            "alice",
+            "#rust",
+            "λtell charlie your build is fixed",
+        );
+        assert_eq!(
+            delivered(
+                &plugin,
+                &message("charlie", "JOIN", vec!["#rust"]),
+                at(10) + Duration::minutes(5)
+            ),
+            vec![on_libera(
+                "#rust",
+                "charlie: message from alice (5min ago): your build is fixed"
+            )]
+        );
+    }
+
+    #[test]
+    async fn test_deliver_on_nick_change() {
+        let members = Members::default();
+        for nick in ["golem", "charlie_"] {
+            let join = message(nick, "JOIN", vec!["#rust"]);
+            members.on_message("libera", "golem", &join);
+        }
+        let plugin = Tell {
+            members: Arc::new(members),
+            ..tell(false)
+        };
+        ask(
+            &plugin,
+            "alice",
+            "#rust",
+            "λtell [charlie] your build is fixed",
+        );
+        ask(&plugin, "alice", "#ocaml", "λtell [charlie] not in there");
+        let nick = message("charlie_", "NICK", vec!["{charlie}"]);
+        // the golem follows the nick change before the plugins
+        plugin.members.on_message("libera", "golem", &nick);
+        assert_eq!(
+            delivered(&plugin, &nick, at(11)),
+            vec![on_libera(
+                "#rust",
+                "{charlie}: message from alice (1h ago): your build is fixed"
+            )],
+            "in rfc1459, only in the channels of the nick"
+        );
+        assert_eq!(
+            delivered(
+                &plugin,
+                &message("{charlie}", "NICK", vec!["charlie_"]),
+                at(11)
+            ),
+            vec![],
+            "not to the previous nick"
+        );
+    }
+}
@@ -0,0 +1,5 @@
+mod places;
+mod plugin;
+mod zones;
+
+pub use plugin::Time;
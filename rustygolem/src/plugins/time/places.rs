@@ -0,0 +1,232 @@
+use chrono_tz::{Tz, TZ_VARIANTS};
+
+/// The cities which aren't the city of a zone name, like Lyon, or which share
+/// their name with a city of another zone, like Portland. With the region
+/// telling them apart, and their zone.
+const CITIES: &[(&str, &str, &str)] = &[
+    ("Lyon", "France", "Europe/Paris"),
+    ("Marseille", "France", "Europe/Paris"),
+    ("Toulouse", "France", "Europe/Paris"),
+    ("Bordeaux", "France", "Europe/Paris"),
+    ("Lille", "France", "Europe/Paris"),
+    ("Nantes", "France", "Europe/Paris"),
+    ("Strasbourg", "France", "Europe/Paris"),
+    ("Rennes", "France", "Europe/Paris"),
+    ("Brest", "France", "Europe/Paris"),
+    ("Grenoble", "France", "Europe/Paris"),
+    ("Montpellier", "France", "Europe/Paris"),
+    ("Nice", "France", "Europe/Paris"),
+    ("Saint-Étienne", "France", "Europe/Paris"),
+    ("Genève", "Suisse", "Europe/Zurich"),
+    ("Lausanne", "Suisse", "Europe/Zurich"),
+    ("Bruxelles", "Belgique", "Europe/Brussels"),
+    ("Montréal", "Québec", "America/Toronto"),
+    ("Québec", "Québec", "America/Toronto"),
+    ("Ottawa", "Ontario", "America/Toronto"),
+    ("Munich", "Germany", "Europe/Berlin"),
+    ("Hamburg", "Germany", "Europe/Berlin"),
+    ("Barcelona", "Spain", "Europe/Madrid"),
+    ("Milan", "Italy", "Europe/Rome"),
+    ("Edinburgh", "Scotland", "Europe/London"),
+    ("Birmingham", "England", "Europe/London"),
+    ("Birmingham", "Alabama", "America/Chicago"),
+    ("Boston", "Massachusetts", "America/New_York"),
+    ("Washington", "D.C.", "America/New_York"),
+    ("Atlanta", "Georgia", "America/New_York"),
+    ("Miami", "Florida", "America/New_York"),
+    ("Portland", "Maine", "America/New_York"),
+    ("Portland", "Oregon", "America/Los_Angeles"),
+    ("Springfield", "Massachusetts", "America/New_York"),
+    ("Springfield", "Illinois", "America/Chicago"),
+    ("Austin", "Texas", "America/Chicago"),
+    ("Dallas", "Texas", "America/Chicago"),
+    ("Houston", "Texas", "America/Chicago"),
+    ("San Francisco", "California", "America/Los_Angeles"),
+    ("Seattle", "Washington", "America/Los_Angeles"),
+    ("Beijing", "China", "Asia/Shanghai"),
+    ("Mumbai", "India", "Asia/Kolkata"),
+    ("Delhi", "India", "Asia/Kolkata"),
+    ("Bangalore", "India", "Asia/Kolkata"),
+    ("Osaka", "Japan", "Asia/Tokyo"),
+    ("Kyoto", "Japan", "Asia/Tokyo"),
+    ("Wellington", "New Zealand", "Pacific/Auckland"),
+];
+
+/// The areas of the zone names of a city, not the legacy ones like
+/// `US/Pacific`
+const AREAS: &[&str] = &[
+    "Africa",
+    "America",
+    "Antarctica",
+    "Asia",
+    "Atlantic",
+    "Australia",
+    "Europe",
+    "Indian",
+    "Pacific",
+];
+
+/// A city or a zone, as told in the replies
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Place {
+    pub label: String,
+    pub tz: Tz,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Found {
+    One(Place),
+    /// the labels of the cities of that name, in different zones
+    Ambiguous(Vec<String>),
+    Unknown,
+}
+
+/// Lowercase, without the accents nor the punctuation, so that
+/// `Saint-Étienne` is `saint etienne`
+fn normalize(name: &str) -> String {
+    let folded = name
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'à' | 'â' | 'ä' | 'á' | 'ã' => 'a',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'î' | 'ï' | 'í' => 'i',
+            'ô' | 'ö' | 'ó' | 'õ' => 'o',
+            'ù' | 'û' | 'ü' | 'ú' => 'u',
+            'ç' => 'c',
+            'ñ' => 'n',
+            c if c.is_alphanumeric() => c,
+            _ => ' ',
+        })
+        .collect::<String>();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The city of a zone name, `New York` for `America/New_York`
+fn zone_city(tz: &Tz) -> Option<&'static str> {
+    let (area, city) = tz.name().split_once('/')?;
+    AREAS
+        .contains(&area)
+        .then(|| city.rsplit('/').next().unwrap_or(city))
+}
+
+/// A city of the bundled list, `Portland, Maine` or `Portland Maine` to tell
+/// which one, or else the city of a zone name like `Tokyo`
+pub fn city(name: &str) -> Found {
+    let name = normalize(name);
+    if name.is_empty() {
+        return Found::Unknown;
+    }
+    let bundled = CITIES
+        .iter()
+        .filter(|(city, region, _)| {
+            normalize(city) == name || normalize(&format!("{city} {region}")) == name
+        })
+        .collect::<Vec<_>>();
+    match bundled.as_slice() {
+        [] => (),
+        [(city, region, tz)] => {
+            // the region is only told for the cities sharing their name
+            let shared = CITIES.iter().filter(|(c, _, _)| c == city).count() > 1;
+            let label = if shared {
+                format!("{city}, {region}")
+            } else {
+                city.to_string()
+            };
+            return Found::One(Place {
+                label,
+                tz: tz.parse().expect("a valid zone in the city list"),
+            });
+        }
+        several => {
+            return Found::Ambiguous(
+                several
+                    .iter()
+                    .map(|(city, region, _)| format!("{city}, {region}"))
+                    .collect(),
+            )
+        }
+    }
+    // the shortest name when the same zone has several, like
+    // America/Indianapolis and America/Indiana/Indianapolis
+    TZ_VARIANTS
+        .iter()
+        .filter_map(|tz| Some((tz, zone_city(tz)?)))
+        .filter(|(_, city)| normalize(city) == name)
+        .min_by_key(|(tz, _)| tz.name().len())
+        .map(|(tz, city)| {
+            Found::One(Place {
+                label: city.replace('_', " "),
+                tz: *tz,
+            })
+        })
+        .unwrap_or(Found::Unknown)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn one(label: &str, tz: Tz) -> Found {
+        Found::One(Place {
+            label: label.to_string(),
+            tz,
+        })
+    }
+
+    #[test]
+    async fn test_city() {
+        assert_eq!(city("Tokyo"), one("Tokyo", chrono_tz::Asia::Tokyo));
+        assert_eq!(
+            city("new york"),
+            one("New York", chrono_tz::America::New_York)
+        );
+        assert_eq!(city("PARIS"), one("Paris", chrono_tz::Europe::Paris));
+        assert_eq!(city("lyon"), one("Lyon", chrono_tz::Europe::Paris));
+        assert_eq!(
+            city("saint etienne"),
+            one("Saint-Étienne", chrono_tz::Europe::Paris)
+        );
+        assert_eq!(
+            city("Montreal"),
+            one("Montréal", chrono_tz::America::Toronto)
+        );
+        assert_eq!(
+            city("Indianapolis"),
+            one("Indianapolis", chrono_tz::America::Indianapolis),
+            "rather than America/Indiana/Indianapolis"
+        );
+        assert_eq!(city("Atlantis"), Found::Unknown);
+        assert_eq!(city("Pacific"), Found::Unknown, "an area isn't a city");
+        assert_eq!(city(" - "), Found::Unknown);
+    }
+
+    #[test]
+    async fn test_ambiguous() {
+        assert_eq!(
+            city("Portland"),
+            Found::Ambiguous(vec![
+                "Portland, Maine".to_string(),
+                "Portland, Oregon".to_string()
+            ])
+        );
+        assert_eq!(
+            city("Portland, Oregon"),
+            one("Portland, Oregon", chrono_tz::America::Los_Angeles)
+        );
+        assert_eq!(
+            city("portland maine"),
+            one("Portland, Maine", chrono_tz::America::New_York)
+        );
+        assert!(matches!(city("Birmingham"), Found::Ambiguous(_)));
+    }
+
+    /// The list is checked here rather than when looking up the cities
+    #[test]
+    async fn test_cities_list() {
+        for (city, _, tz) in CITIES {
+            assert!(tz.parse::<Tz>().is_ok(), "{city}: {tz}");
+        }
+    }
+}
@@ -0,0 +1,636 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveTime, Offset, Utc};
+use chrono_tz::Tz;
+use irc::proto::{Command, Message};
+use plugin_core::utils::account::account;
+use plugin_core::utils::network::network;
+use plugin_core::utils::parser;
+use plugin_core::{CommandHelp, Initialised, MsgCtx, Outbound, Plugin, Requirement, Result};
+
+use super::places::{self, Found, Place};
+use super::zones::Zones;
+use crate::caps::CaseMapping;
+use crate::utils::messages::with_target;
+use crate::utils::time::{local, zone};
+
+const USAGE: &str = "Usage: λtime [city, zone or nick], λtime set <city or zone>, \
+                     λtime diff <place> <place> [HH:MM]";
+
+const NO_ZONE: &str = "No timezone for you, choose one with λtime set <city or zone>";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimeCommand<'a> {
+    /// `λtime set Europe/Paris`
+    Set(&'a str),
+    /// `λtime diff Paris Tokyo [15:00]`, both places in the same string
+    /// since they may have spaces
+    Diff {
+        places: &'a str,
+        at: Option<NaiveTime>,
+    },
+    /// `λtime [Tokyo]`, the timezone of the nick when none is given
+    Show(Option<&'a str>),
+}
+
+/// None when `set` or `diff` miss their arguments
+fn parse_command(args: &str) -> Option<TimeCommand<'_>> {
+    let args = args.trim();
+    let (first, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    let rest = rest.trim();
+    if first.eq_ignore_ascii_case("set") {
+        return Some(TimeCommand::Set(rest)).filter(|_| !rest.is_empty());
+    }
+    if first.eq_ignore_ascii_case("diff") {
+        let (places, at) = match rest.rsplit_once(char::is_whitespace) {
+            Some((places, last)) => match NaiveTime::parse_from_str(last, "%H:%M") {
+                Ok(at) => (places.trim(), Some(at)),
+                Err(_) => (rest, None),
+            },
+            None => (rest, None),
+        };
+        return Some(TimeCommand::Diff { places, at }).filter(|_| places.contains(' '));
+    }
+    Some(TimeCommand::Show(
+        Some(args).filter(|args| !args.is_empty()),
+    ))
+}
+
+/// `UTC+9`, `UTC-3:30`, or `UTC` for no offset
+fn format_offset(secs: i32) -> String {
+    let sign = if secs < 0 { '-' } else { '+' };
+    let (hours, minutes) = (secs.abs() / 3600, secs.abs() % 3600 / 60);
+    match (hours, minutes) {
+        (0, 0) => "UTC".to_string(),
+        (_, 0) => format!("UTC{sign}{hours}"),
+        _ => format!("UTC{sign}{hours}:{minutes:02}"),
+    }
+}
+
+/// `7h`, `5h30` or `45min`
+fn format_gap(secs: i32) -> String {
+    let (hours, minutes) = (secs.abs() / 3600, secs.abs() % 3600 / 60);
+    match (hours, minutes) {
+        (_, 0) => format!("{hours}h"),
+        (0, _) => format!("{minutes}min"),
+        _ => format!("{hours}h{minutes:02}"),
+    }
+}
+
+/// Of the zone at that instant, changing with the summer time
+fn offset(tz: Tz, now: DateTime<Utc>) -> i32 {
+    now.with_timezone(&tz).offset().fix().local_minus_utc()
+}
+
+/// `Tokyo: 23:15 on Thu 16 Oct (UTC+9)`
+fn show(place: &Place, now: DateTime<Utc>) -> String {
+    let time = now.with_timezone(&place.tz);
+    format!(
+        "{}: {} ({})",
+        place.label,
+        time.format("%H:%M on %a %-d %b"),
+        format_offset(offset(place.tz, now))
+    )
+}
+
+/// `Tokyo is 7h ahead of Paris: 15:00 in Paris is 22:00 in Tokyo`, with the
+/// offsets at that time today
+fn diff(from: &Place, to: &Place, at: NaiveTime, now: DateTime<Utc>) -> String {
+    let today = now.with_timezone(&from.tz).naive_local().date();
+    // an hour later when `at` is skipped by the change to the summer time
+    let instant = local(from.tz, today, at);
+    let gap = offset(to.tz, instant) - offset(from.tz, instant);
+    let relation = match gap {
+        0 => return format!("{} and {} have the same time", from.label, to.label),
+        gap if gap > 0 => format!(
+            "{} is {} ahead of {}",
+            to.label,
+            format_gap(gap),
+            from.label
+        ),
+        gap => format!("{} is {} behind {}", to.label, format_gap(gap), from.label),
+    };
+    let there = instant.with_timezone(&to.tz).naive_local();
+    let day = match there.date().cmp(&today) {
+        std::cmp::Ordering::Less => ", the day before",
+        std::cmp::Ordering::Equal => "",
+        std::cmp::Ordering::Greater => ", the next day",
+    };
+    format!(
+        "{relation}: {} in {} is {} in {}{day}",
+        instant.with_timezone(&from.tz).format("%H:%M"),
+        from.label,
+        there.format("%H:%M"),
+        to.label
+    )
+}
+
+/// `Which Portland: Portland, Maine or Portland, Oregon?`
+fn which(name: &str, labels: &[String]) -> String {
+    let (last, others) = labels.split_last().expect("ambiguous between several");
+    format!("Which {name}: {} or {last}?", others.join(", "))
+}
+
+pub struct Time {
+    zones: Zones,
+}
+
+#[async_trait]
+impl Plugin for Time {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        config.check_database("time")?;
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let db = config.require_database("time")?;
+        Ok(Initialised::from(Time {
+            zones: Zones::load(db)?,
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "time"
+    }
+
+    async fn in_message_ctx(&self, ctx: &MsgCtx, msg: &Message) -> Result<Option<Outbound>> {
+        self.in_msg(msg, ctx.casemapping, Utc::now())
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("time")
+                .usage("time [city, zone or nick] [> nick]")
+                .description(
+                    "The time and UTC offset of a city, a zone like America/New_York, \
+                     someone who set theirs, or yours",
+                ),
+            CommandHelp::new("time set")
+                .usage("time set <city or zone>")
+                .description("Your timezone, by services account when logged in"),
+            CommandHelp::new("time diff")
+                .usage("time diff <place> <place> [HH:MM]")
+                .description(
+                    "The offset between two places, and what 15:00 in one is in the other",
+                ),
+        ]
+    }
+
+    fn requirements(&self) -> Vec<Requirement> {
+        // the timezone of each user
+        vec![Requirement::Database]
+    }
+}
+
+impl Time {
+    fn in_msg(
+        &self,
+        msg: &Message,
+        casemapping: CaseMapping,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Outbound>> {
+        let response_target = match msg.response_target() {
+            Some(target) => target.to_string(),
+            None => return Ok(None),
+        };
+        let privmsg = match &msg.command {
+            Command::PRIVMSG(_, privmsg) => privmsg,
+            _ => return Ok(None),
+        };
+        let (args, mb_target) = match parser::command("time")(privmsg) {
+            Ok((_, command)) => command,
+            Err(_) => return Ok(None),
+        };
+        let network = network(msg).unwrap_or_default();
+        let nick = msg.source_nickname().unwrap_or_default();
+
+        let text = match parse_command(args) {
+            None => USAGE.to_string(),
+            Some(TimeCommand::Set(name)) => {
+                let found = match zone(name) {
+                    Some(tz) => Found::One(Place {
+                        label: tz.name().to_string(),
+                        tz,
+                    }),
+                    None => places::city(name),
+                };
+                match found {
+                    Found::One(place) => {
+                        self.zones
+                            .set(network, casemapping, account(msg), nick, place.tz)?;
+                        format!("Your timezone is now {}", place.tz.name())
+                    }
+                    Found::Ambiguous(labels) => which(name, &labels),
+                    Found::Unknown => format!("I don't know {name}"),
+                }
+            }
+            Some(TimeCommand::Diff { places, at }) => {
+                let at = at.unwrap_or_else(|| NaiveTime::from_hms(15, 0, 0));
+                match self.resolve_pair(network, casemapping, places)? {
+                    Ok((from, to)) => diff(&from, &to, at, now),
+                    Err(text) => text,
+                }
+            }
+            Some(TimeCommand::Show(None)) => {
+                match self.zones.get(network, casemapping, account(msg), nick)? {
+                    Some(tz) => show(
+                        &Place {
+                            label: format!("{nick} ({})", tz.name()),
+                            tz,
+                        },
+                        now,
+                    ),
+                    None => NO_ZONE.to_string(),
+                }
+            }
+            Some(TimeCommand::Show(Some(name))) => {
+                match self.resolve(network, casemapping, name)? {
+                    Found::One(place) => show(&place, now),
+                    Found::Ambiguous(labels) => which(name, &labels),
+                    Found::Unknown => format!("I don't know {name}"),
+                }
+            }
+        };
+        Ok(Some(Outbound::reply(
+            response_target,
+            with_target(&text, &mb_target),
+        )))
+    }
+
+    /// A zone name first, then a city, and only then a nick who set their
+    /// timezone, so that nobody can hijack `Paris` by taking that nick
+    fn resolve(&self, network: &str, casemapping: CaseMapping, name: &str) -> Result<Found> {
+        if let Some(tz) = zone(name) {
+            return Ok(Found::One(Place {
+                label: tz.name().to_string(),
+                tz,
+            }));
+        }
+        let found = places::city(name);
+        if found != Found::Unknown {
+            return Ok(found);
+        }
+        Ok(match self.zones.get(network, casemapping, None, name)? {
+            Some(tz) => Found::One(Place {
+                label: format!("{name} ({})", tz.name()),
+                tz,
+            }),
+            None => Found::Unknown,
+        })
+    }
+
+    /// The two places of `λtime diff`, split where both are known since
+    /// either may have spaces. The reply when they aren't.
+    fn resolve_pair(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        places: &str,
+    ) -> Result<std::result::Result<(Place, Place), String>> {
+        let words = places.split_whitespace().collect::<Vec<_>>();
+        let mut reply = None;
+        for i in 1..words.len() {
+            let (first, second) = (words[..i].join(" "), words[i..].join(" "));
+            let found = (
+                self.resolve(network, casemapping, &first)?,
+                self.resolve(network, casemapping, &second)?,
+            );
+            let first_reply = match found {
+                (Found::One(from), Found::One(to)) => return Ok(Ok((from, to))),
+                (Found::Ambiguous(labels), Found::One(_)) => which(&first, &labels),
+                (Found::One(_), Found::Ambiguous(labels)) => which(&second, &labels),
+                (Found::One(_), Found::Unknown) => format!("I don't know {second}"),
+                (Found::Unknown, Found::One(_)) => format!("I don't know {first}"),
+                _ => continue,
+            };
+            reply.get_or_insert(first_reply);
+        }
+        Ok(Err(
+            reply.unwrap_or_else(|| format!("I don't know {places}"))
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use plugin_core::utils::network::set_network;
+    use plugin_core::Database;
+    use pretty_assertions::assert_eq;
+
+    fn time() -> Time {
+        Time {
+            zones: Zones::load(Database::in_memory().unwrap()).unwrap(),
+        }
+    }
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.ymd(y, m, d).and_hms(h, min, 0)
+    }
+
+    fn say(plugin: &Time, nick: &str, text: &str, now: DateTime<Utc>) -> Option<String> {
+        let source = format!("{nick}!~{nick}@localhost");
+        let mut msg = Message::new(Some(&source), "PRIVMSG", vec!["#coucou", text]).unwrap();
+        set_network(&mut msg, "libera");
+        reply(plugin, msg, now)
+    }
+
+    fn say_logged_in(
+        plugin: &Time,
+        nick: &str,
+        account: &str,
+        text: &str,
+        now: DateTime<Utc>,
+    ) -> Option<String> {
+        let line =
+            format!("@account={account} :{nick}!~{nick}@localhost PRIVMSG #coucou :{text}\r\n");
+        let mut msg: Message = line.parse().unwrap();
+        set_network(&mut msg, "libera");
+        reply(plugin, msg, now)
+    }
+
+    fn reply(plugin: &Time, msg: Message, now: DateTime<Utc>) -> Option<String> {
+        match plugin.in_msg(&msg, CaseMapping::Rfc1459, now).unwrap() {
+            Some(Outbound::Reply { text, .. }) => Some(text),
+            None => None,
+            other => panic!("unexpected reply to {msg:?}: {other:?}"),
+        }
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        let t = |h, m| Some(NaiveTime::from_hms(h, m, 0));
+        for (args, expected) in [
+            ("", Some(TimeCommand::Show(None))),
+            (" Tokyo ", Some(TimeCommand::Show(Some("Tokyo")))),
+            ("New York", Some(TimeCommand::Show(Some("New York")))),
+            ("set Europe/Paris", Some(TimeCommand::Set("Europe/Paris"))),
+            (
+                "SET  Portland, Maine",
+                Some(TimeCommand::Set("Portland, Maine")),
+            ),
+            ("set", None),
+            (
+                "diff Paris Tokyo",
+                Some(TimeCommand::Diff {
+                    places: "Paris Tokyo",
+                    at: None,
+                }),
+            ),
+            (
+                "diff New York Tokyo 9:30",
+                Some(TimeCommand::Diff {
+                    places: "New York Tokyo",
+                    at: t(9, 30),
+                }),
+            ),
+            ("diff Paris", None),
+            ("diff Paris 15:00", None),
+        ] {
+            assert_eq!(parse_command(args), expected, "{args:?}");
+        }
+    }
+
+    #[test]
+    async fn test_format_offset() {
+        for (secs, expected) in [
+            (0, "UTC"),
+            (9 * 3600, "UTC+9"),
+            (-4 * 3600, "UTC-4"),
+            (5 * 3600 + 1800, "UTC+5:30"),
+            (-(3 * 3600 + 1800), "UTC-3:30"),
+            (5 * 3600 + 45 * 60, "UTC+5:45"),
+        ] {
+            assert_eq!(format_offset(secs), expected, "{secs}");
+        }
+        assert_eq!(format_gap(-7 * 3600), "7h");
+        assert_eq!(format_gap(4 * 3600 + 1800), "4h30");
+        assert_eq!(format_gap(45 * 60), "45min");
+    }
+
+    #[test]
+    async fn test_time() {
+        let plugin = time();
+        let now = at(2025, 10, 16, 14, 15);
+        assert_eq!(
+            say(&plugin, "alice", "λtime Tokyo", now).as_deref(),
+            Some("Tokyo: 23:15 on Thu 16 Oct (UTC+9)")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime america/new_york > bob", now).as_deref(),
+            Some("bob: America/New_York: 10:15 on Thu 16 Oct (UTC-4)")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime Mumbai", now).as_deref(),
+            Some("Mumbai: 19:45 on Thu 16 Oct (UTC+5:30)")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime UTC", now).as_deref(),
+            Some("UTC: 14:15 on Thu 16 Oct (UTC)")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime Auckland", now).as_deref(),
+            Some("Auckland: 03:15 on Fri 17 Oct (UTC+13)")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime Atlantis", now).as_deref(),
+            Some("I don't know Atlantis")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime Portland", now).as_deref(),
+            Some("Which Portland: Portland, Maine or Portland, Oregon?")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime Portland, Oregon", now).as_deref(),
+            Some("Portland, Oregon: 07:15 on Thu 16 Oct (UTC-7)")
+        );
+        assert_eq!(say(&plugin, "alice", "time Tokyo", now), None);
+    }
+
+    #[test]
+    async fn test_set() {
+        let plugin = time();
+        let now = at(2025, 10, 16, 14, 15);
+        assert_eq!(
+            say(&plugin, "charlie", "λtime", now).as_deref(),
+            Some(NO_ZONE)
+        );
+        assert_eq!(
+            say(&plugin, "charlie", "λtime set Europe/Paris", now).as_deref(),
+            Some("Your timezone is now Europe/Paris")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime charlie", now).as_deref(),
+            Some("charlie (Europe/Paris): 16:15 on Thu 16 Oct (UTC+2)")
+        );
+        assert_eq!(
+            say(&plugin, "Charlie", "λtime", now).as_deref(),
+            Some("Charlie (Europe/Paris): 16:15 on Thu 16 Oct (UTC+2)")
+        );
+        assert_eq!(
+            say(&plugin, "charlie", "λtime set Portland", now).as_deref(),
+            Some("Which Portland: Portland, Maine or Portland, Oregon?")
+        );
+        assert_eq!(
+            say(&plugin, "charlie", "λtime set Atlantis", now).as_deref(),
+            Some("I don't know Atlantis")
+        );
+        assert_eq!(
+            say(&plugin, "charlie", "λtime set", now).as_deref(),
+            Some(USAGE)
+        );
+
+        assert_eq!(
+            say_logged_in(&plugin, "dave", "Dave", "λtime set Seattle", now).as_deref(),
+            Some("Your timezone is now America/Los_Angeles")
+        );
+        assert_eq!(
+            say_logged_in(&plugin, "dave_away", "dave", "λtime", now).as_deref(),
+            Some("dave_away (America/Los_Angeles): 07:15 on Thu 16 Oct (UTC-7)"),
+            "by account whatever the nick"
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime dave", now).as_deref(),
+            Some("dave (America/Los_Angeles): 07:15 on Thu 16 Oct (UTC-7)")
+        );
+    }
+
+    #[test]
+    async fn test_places_before_nicks() {
+        let plugin = time();
+        let now = at(2025, 10, 16, 14, 15);
+        say(&plugin, "Tokyo", "λtime set America/New_York", now);
+        say(&plugin, "Portland", "λtime set Europe/Paris", now);
+        say(&plugin, "UTC", "λtime set Asia/Tokyo", now);
+        assert_eq!(
+            say(&plugin, "alice", "λtime Tokyo", now).as_deref(),
+            Some("Tokyo: 23:15 on Thu 16 Oct (UTC+9)"),
+            "the city, not the nick"
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime Portland", now).as_deref(),
+            Some("Which Portland: Portland, Maine or Portland, Oregon?")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime UTC", now).as_deref(),
+            Some("UTC: 14:15 on Thu 16 Oct (UTC)")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime diff Tokyo Paris", now).as_deref(),
+            Some("Paris is 7h behind Tokyo: 15:00 in Tokyo is 08:00 in Paris")
+        );
+    }
+
+    #[test]
+    async fn test_diff() {
+        let plugin = time();
+        let now = at(2025, 10, 16, 14, 15);
+        assert_eq!(
+            say(&plugin, "alice", "λtime diff Paris Tokyo", now).as_deref(),
+            Some("Tokyo is 7h ahead of Paris: 15:00 in Paris is 22:00 in Tokyo")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime diff Tokyo Paris", now).as_deref(),
+            Some("Paris is 7h behind Tokyo: 15:00 in Tokyo is 08:00 in Paris")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime diff New York Tokyo 18:30", now).as_deref(),
+            Some(
+                "Tokyo is 13h ahead of New York: 18:30 in New York is 07:30 in Tokyo, \
+                 the next day"
+            )
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime diff Paris Mumbai", now).as_deref(),
+            Some("Mumbai is 3h30 ahead of Paris: 15:00 in Paris is 18:30 in Mumbai")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime diff Auckland Honolulu 8:00", now).as_deref(),
+            Some(
+                "Honolulu is 23h behind Auckland: 08:00 in Auckland is 09:00 in Honolulu, \
+                 the day before"
+            )
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime diff Paris Lyon", now).as_deref(),
+            Some("Paris and Lyon have the same time")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime diff Paris Portland", now).as_deref(),
+            Some("Which Portland: Portland, Maine or Portland, Oregon?")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime diff Paris Atlantis", now).as_deref(),
+            Some("I don't know Atlantis")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime diff Paris", now).as_deref(),
+            Some(USAGE)
+        );
+    }
+
+    /// The offsets change with the summer time, on different days in Europe
+    /// and in America
+    #[test]
+    async fn test_dst() {
+        let plugin = time();
+        // Europe/Paris goes from UTC+1 to UTC+2 at 01:00 UTC on 2025-03-30
+        assert_eq!(
+            say(&plugin, "alice", "λtime Paris", at(2025, 3, 30, 0, 59)).as_deref(),
+            Some("Paris: 01:59 on Sun 30 Mar (UTC+1)")
+        );
+        assert_eq!(
+            say(&plugin, "alice", "λtime Paris", at(2025, 3, 30, 1, 0)).as_deref(),
+            Some("Paris: 03:00 on Sun 30 Mar (UTC+2)")
+        );
+        // and back at 01:00 UTC on 2025-10-26
+        assert_eq!(
+            say(&plugin, "alice", "λtime Paris", at(2025, 10, 26, 1, 0)).as_deref(),
+            Some("Paris: 02:00 on Sun 26 Oct (UTC+1)")
+        );
+        // New York already changed on 2025-03-09, not Paris yet
+        assert_eq!(
+            say(
+                &plugin,
+                "alice",
+                "λtime diff Paris New York",
+                at(2025, 3, 20, 12, 0)
+            )
+            .as_deref(),
+            Some("New York is 5h behind Paris: 15:00 in Paris is 10:00 in New York")
+        );
+        assert_eq!(
+            say(
+                &plugin,
+                "alice",
+                "λtime diff Paris New York",
+                at(2025, 4, 20, 12, 0)
+            )
+            .as_deref(),
+            Some("New York is 6h behind Paris: 15:00 in Paris is 09:00 in New York")
+        );
+        // 02:30 doesn't exist in Paris on 2025-03-30, it's 03:30
+        assert_eq!(
+            say(
+                &plugin,
+                "alice",
+                "λtime diff Paris Tokyo 2:30",
+                at(2025, 3, 30, 0, 0)
+            )
+            .as_deref(),
+            Some("Tokyo is 7h ahead of Paris: 03:30 in Paris is 10:30 in Tokyo")
+        );
+        // the summer time starts in Paris on that day, the offsets are the
+        // ones of 15:00
+        assert_eq!(
+            say(
+                &plugin,
+                "alice",
+                "λtime diff Paris New York",
+                at(2025, 3, 30, 0, 0)
+            )
+            .as_deref(),
+            Some("New York is 6h behind Paris: 15:00 in Paris is 09:00 in New York")
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use plugin_core::{Database, Result};
+
+use crate::caps::CaseMapping;
+
+/// The tables of the time plugin in the shared database, see
+/// `plugin_core::ensure_schema`
+const MIGRATIONS: &[&str] = &[
+    // account is empty when set by a nick not logged in, nick is normalized
+    // with the casemapping of the network
+    "CREATE TABLE time_zones (
+        network TEXT NOT NULL,
+        account TEXT NOT NULL,
+        nick TEXT NOT NULL,
+        tz TEXT NOT NULL
+    );",
+];
+
+#[derive(QueryableByName)]
+struct Zone {
+    #[sql_type = "Text"]
+    tz: String,
+}
+
+/// The timezone of each user, by services account when logged in, by nick
+/// otherwise
+pub struct Zones {
+    db: Database,
+}
+
+impl Zones {
+    /// Create the table if needed
+    pub fn load(db: Database) -> Result<Self> {
+        plugin_core::ensure_schema(&db, "time", MIGRATIONS)?;
+        Ok(Zones { db })
+    }
+
+    /// Replaces the timezone of the account, or of the nick when not logged
+    /// in. The one set by the account owning a nick isn't replaced by
+    /// someone else using that nick.
+    pub fn set(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        account: Option<&str>,
+        nick: &str,
+        tz: Tz,
+    ) -> Result<()> {
+        let account = account.map(str::to_lowercase).unwrap_or_default();
+        let nick = casemapping.normalize(nick);
+        self.db.with_connection(|conn| {
+            conn.transaction(|| {
+                diesel::sql_query(
+                    "DELETE FROM time_zones WHERE network = ? \
+                     AND ((account <> '' AND account = ?) OR (account = '' AND nick = ?))",
+                )
+                .bind::<Text, _>(network)
+                .bind::<Text, _>(&account)
+                .bind::<Text, _>(&nick)
+                .execute(conn)?;
+                diesel::sql_query(
+                    "INSERT INTO time_zones (network, account, nick, tz) VALUES (?, ?, ?, ?)",
+                )
+                .bind::<Text, _>(network)
+                .bind::<Text, _>(&account)
+                .bind::<Text, _>(&nick)
+                .bind::<Text, _>(tz.name())
+                .execute(conn)
+            })
+        })?;
+        Ok(())
+    }
+
+    /// The one of the account first, then the one of the nick, set by its
+    /// account owner over the one set when not logged in. None when never
+    /// set.
+    pub fn get(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        account: Option<&str>,
+        nick: &str,
+    ) -> Result<Option<Tz>> {
+        let account = account.map(str::to_lowercase).unwrap_or_default();
+        let zones = self.db.with_connection(|conn| {
+            diesel::sql_query(
+                "SELECT tz FROM time_zones WHERE network = ? \
+                 AND ((account <> '' AND account = ?) OR nick = ?) \
+                 ORDER BY (account <> '' AND account = ?) DESC, account = '' ASC LIMIT 1",
+            )
+            .bind::<Text, _>(network)
+            .bind::<Text, _>(&account)
+            .bind::<Text, _>(casemapping.normalize(nick))
+            .bind::<Text, _>(&account)
+            .load::<Zone>(conn)
+        })?;
+        // a zone may be gone from a newer tz database
+        Ok(zones.into_iter().next().and_then(|z| z.tz.parse().ok()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const RFC1459: CaseMapping = CaseMapping::Rfc1459;
+
+    #[test]
+    async fn test_persistence() {
+        let db = Database::in_memory().unwrap();
+        let zones = Zones::load(db.clone()).unwrap();
+        let get =
+            |zones: &Zones, account, nick| zones.get("libera", RFC1459, account, nick).unwrap();
+        assert_eq!(get(&zones, None, "alice"), None);
+        zones
+            .set("libera", RFC1459, None, "Alice", chrono_tz::Europe::Paris)
+            .unwrap();
+        zones
+            .set("libera", RFC1459, None, "[bob]", chrono_tz::Asia::Tokyo)
+            .unwrap();
+        zones
+            .set("libera", RFC1459, None, "alice", chrono_tz::Europe::Lisbon)
+            .unwrap();
+
+        let zones = Zones::load(db).unwrap();
+        assert_eq!(
+            get(&zones, None, "ALICE"),
+            Some(chrono_tz::Europe::Lisbon),
+            "the last one"
+        );
+        assert_eq!(get(&zones, None, "{bob}"), Some(chrono_tz::Asia::Tokyo));
+        assert_eq!(
+            zones.get("oftc", RFC1459, None, "alice").unwrap(),
+            None,
+            "per network"
+        );
+    }
+
+    #[test]
+    async fn test_accounts() {
+        let zones = Zones::load(Database::in_memory().unwrap()).unwrap();
+        let get = |account, nick| zones.get("libera", RFC1459, account, nick).unwrap();
+        zones
+            .set(
+                "libera",
+                RFC1459,
+                Some("Charlie"),
+                "charlie",
+                chrono_tz::Europe::Paris,
+            )
+            .unwrap();
+        assert_eq!(
+            get(Some("charlie"), "charlie_away"),
+            Some(chrono_tz::Europe::Paris),
+            "whatever the nick"
+        );
+        assert_eq!(get(None, "Charlie"), Some(chrono_tz::Europe::Paris));
+
+        zones
+            .set("libera", RFC1459, None, "charlie", chrono_tz::Asia::Tokyo)
+            .unwrap();
+        assert_eq!(
+            get(None, "charlie"),
+            Some(chrono_tz::Europe::Paris),
+            "not replaced by someone not logged in"
+        );
+
+        zones
+            .set(
+                "libera",
+                RFC1459,
+                Some("charlie"),
+                "charlie",
+                chrono_tz::America::New_York,
+            )
+            .unwrap();
+        assert_eq!(get(None, "charlie"), Some(chrono_tz::America::New_York));
+        assert_eq!(
+            get(Some("charlie"), "charlie"),
+            Some(chrono_tz::America::New_York)
+        );
+    }
+}
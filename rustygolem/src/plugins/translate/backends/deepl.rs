@@ -0,0 +1,175 @@
+use super::{Backend, Translation};
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use plugin_core::{Error, Result};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+
+/// The free keys end with `:fx` and only work with the free endpoint
+const FREE_URL: &str = "https://api-free.deepl.com/v2/translate";
+const PRO_URL: &str = "https://api.deepl.com/v2/translate";
+
+/// Not a standard status, the character quota of the month is exhausted
+const QUOTA_EXCEEDED: u16 = 456;
+
+/// https://developers.deepl.com/docs/api-reference/translate
+pub struct DeepL {
+    client: Client,
+    api_key: String,
+}
+
+impl DeepL {
+    pub fn new(client: Client, api_key: String) -> Self {
+        DeepL { client, api_key }
+    }
+
+    fn url(&self) -> &'static str {
+        if self.api_key.ends_with(":fx") {
+            FREE_URL
+        } else {
+            PRO_URL
+        }
+    }
+}
+
+/// Like `{"translations": [{"detected_source_language": "EN", "text":
+/// "Le renard"}]}`, the source is there even when it was given
+#[derive(Debug, Deserialize)]
+struct TranslateResponse {
+    translations: Vec<Translated>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Translated {
+    detected_source_language: Option<String>,
+    text: String,
+}
+
+/// Like `{"message": "Value for 'target_lang' not supported."}`
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
+
+/// DeepL wants `EN` or `PT-BR`
+fn language_code(code: &str) -> String {
+    code.to_uppercase()
+}
+
+fn parse(status: StatusCode, body: &str) -> Result<Translation> {
+    match status.as_u16() {
+        200 => {
+            let response: TranslateResponse =
+                serde_json::from_str(body).context("Unexpected DeepL response")?;
+            let translated = response
+                .translations
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow!("No translation in the DeepL response"))?;
+            Ok(Translation {
+                text: translated.text,
+                source: translated
+                    .detected_source_language
+                    .map(|source| source.to_lowercase()),
+            })
+        }
+        400 => {
+            let response: ErrorResponse =
+                serde_json::from_str(body).context("Unexpected DeepL error")?;
+            Err(Error::user_visible(response.message))
+        }
+        429 => Err(Error::RateLimited { retry_after: None }),
+        QUOTA_EXCEEDED => Err(Error::user_visible(
+            "No more translations for this month, the DeepL quota is exhausted",
+        )),
+        _ => Err(anyhow!("DeepL answered {status}: {body}").into()),
+    }
+}
+
+#[async_trait]
+impl Backend for DeepL {
+    fn name(&self) -> &'static str {
+        "deepl"
+    }
+
+    async fn translate(
+        &self,
+        text: &str,
+        source: Option<&str>,
+        target: &str,
+    ) -> Result<Translation> {
+        let target = language_code(target);
+        let mut form = vec![("text", text.to_string()), ("target_lang", target)];
+        if let Some(source) = source {
+            // no regional variants for the source languages
+            let source = source.split('-').next().unwrap_or(source);
+            form.push(("source_lang", language_code(source)));
+        }
+        let response = self
+            .client
+            .post(self.url())
+            .header("Authorization", format!("DeepL-Auth-Key {}", self.api_key))
+            .form(&form)
+            .send()
+            .await
+            .context("Cannot reach DeepL")?;
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .context("Cannot read the DeepL response")?;
+        parse(status, &body)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_parse() {
+        let body = r#"{"translations": [
+            {"detected_source_language": "EN", "text": "Le renard brun rapide"}
+        ]}"#;
+        assert_eq!(
+            parse(StatusCode::OK, body).unwrap(),
+            Translation {
+                text: "Le renard brun rapide".to_string(),
+                source: Some("en".to_string()),
+            }
+        );
+        assert!(parse(StatusCode::OK, r#"{"translations": []}"#).is_err());
+        assert!(parse(StatusCode::OK, "<html>").is_err());
+    }
+
+    #[test]
+    async fn test_parse_errors() {
+        let unsupported = r#"{"message": "Value for 'target_lang' not supported."}"#;
+        assert!(matches!(
+            parse(StatusCode::BAD_REQUEST, unsupported),
+            Err(Error::UserVisible { message }) if message == "Value for 'target_lang' not supported."
+        ));
+        assert!(matches!(
+            parse(StatusCode::TOO_MANY_REQUESTS, ""),
+            Err(Error::RateLimited { retry_after: None })
+        ));
+        let quota = StatusCode::from_u16(QUOTA_EXCEEDED).unwrap();
+        assert!(matches!(
+            parse(quota, r#"{"message": "Quota Exceeded"}"#),
+            Err(Error::UserVisible { .. })
+        ));
+        let forbidden = r#"{"message": "Wrong endpoint. Use https://api-free.deepl.com"}"#;
+        assert!(matches!(
+            parse(StatusCode::FORBIDDEN, forbidden),
+            Err(Error::Generic(_))
+        ));
+    }
+
+    #[test]
+    async fn test_url() {
+        let deepl = |key: &str| DeepL::new(Client::new(), key.to_string());
+        assert_eq!(deepl("abc-123:fx").url(), FREE_URL);
+        assert_eq!(deepl("abc-123").url(), PRO_URL);
+    }
+}
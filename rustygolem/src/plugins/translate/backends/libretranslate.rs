@@ -0,0 +1,209 @@
+use super::{Backend, Translation};
+use anyhow::{anyhow, bail, Context};
+use async_trait::async_trait;
+use plugin_core::{Error, Result};
+use reqwest::{Client, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+
+/// https://libretranslate.com/docs, usually a self-hosted instance
+pub struct LibreTranslate {
+    client: Client,
+    /// of the translate endpoint
+    url: Url,
+    api_key: Option<String>,
+}
+
+impl LibreTranslate {
+    /// The url of the instance, like `http://localhost:5000`
+    pub fn new(client: Client, url: String, api_key: Option<String>) -> anyhow::Result<Self> {
+        let base =
+            Url::parse(&url).with_context(|| format!("translate.backend: invalid url {url}"))?;
+        if !["http", "https"].contains(&base.scheme()) {
+            bail!("translate.backend: {url} isn't an http or https url");
+        }
+        let url = base
+            .join("translate")
+            .with_context(|| format!("translate.backend: invalid url {url}"))?;
+        Ok(LibreTranslate {
+            client,
+            url,
+            api_key,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct TranslateRequest<'a> {
+    q: &'a str,
+    /// `auto` to detect it
+    source: &'a str,
+    target: &'a str,
+    format: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_key: Option<&'a str>,
+}
+
+/// Like `{"translatedText": "Hello", "detectedLanguage": {"confidence": 90.0,
+/// "language": "fr"}}`, the language only when it was detected
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TranslateResponse {
+    translated_text: String,
+    #[serde(default)]
+    detected_language: Option<DetectedLanguage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DetectedLanguage {
+    language: String,
+}
+
+/// Like `{"error": "zz is not supported"}`
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/// The given source language when there was one
+fn parse(status: StatusCode, body: &str, source: Option<&str>) -> Result<Translation> {
+    let error = || -> Option<String> {
+        serde_json::from_str::<ErrorResponse>(body)
+            .ok()
+            .map(|response| response.error)
+    };
+    match status {
+        StatusCode::OK => {
+            let response: TranslateResponse =
+                serde_json::from_str(body).context("Unexpected LibreTranslate response")?;
+            let detected = response.detected_language.map(|detected| detected.language);
+            Ok(Translation {
+                text: response.translated_text,
+                source: detected
+                    .or_else(|| source.map(str::to_string))
+                    .map(|source| source.to_lowercase()),
+            })
+        }
+        StatusCode::BAD_REQUEST => match error() {
+            Some(message) => Err(Error::user_visible(message)),
+            None => Err(anyhow!("Unexpected LibreTranslate error: {body}").into()),
+        },
+        StatusCode::TOO_MANY_REQUESTS => Err(Error::RateLimited { retry_after: None }),
+        _ => Err(anyhow!(
+            "LibreTranslate answered {status}: {}",
+            error().as_deref().unwrap_or(body)
+        )
+        .into()),
+    }
+}
+
+#[async_trait]
+impl Backend for LibreTranslate {
+    fn name(&self) -> &'static str {
+        "libretranslate"
+    }
+
+    async fn translate(
+        &self,
+        text: &str,
+        source: Option<&str>,
+        target: &str,
+    ) -> Result<Translation> {
+        let request = TranslateRequest {
+            q: text,
+            source: source.unwrap_or("auto"),
+            target,
+            format: "text",
+            api_key: self.api_key.as_deref(),
+        };
+        let response = self
+            .client
+            .post(self.url.clone())
+            .json(&request)
+            .send()
+            .await
+            .with_context(|| format!("Cannot reach {}", self.url))?;
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .context("Cannot read the LibreTranslate response")?;
+        parse(status, &body, source)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn translation(text: &str, source: Option<&str>) -> Translation {
+        Translation {
+            text: text.to_string(),
+            source: source.map(str::to_string),
+        }
+    }
+
+    #[test]
+    async fn test_parse() {
+        let detected = r#"{
+            "detectedLanguage": {"confidence": 90.0, "language": "fr"},
+            "translatedText": "Hello"
+        }"#;
+        assert_eq!(
+            parse(StatusCode::OK, detected, None).unwrap(),
+            translation("Hello", Some("fr"))
+        );
+        let given = r#"{"translatedText": "Le renard brun rapide"}"#;
+        assert_eq!(
+            parse(StatusCode::OK, given, Some("en")).unwrap(),
+            translation("Le renard brun rapide", Some("en"))
+        );
+        assert_eq!(
+            parse(StatusCode::OK, given, None).unwrap(),
+            translation("Le renard brun rapide", None)
+        );
+        assert!(parse(StatusCode::OK, "<html>", None).is_err());
+    }
+
+    #[test]
+    async fn test_parse_errors() {
+        assert!(matches!(
+            parse(StatusCode::BAD_REQUEST, r#"{"error": "zz is not supported"}"#, None),
+            Err(Error::UserVisible { message }) if message == "zz is not supported"
+        ));
+        assert!(matches!(
+            parse(
+                StatusCode::TOO_MANY_REQUESTS,
+                r#"{"error": "Too many request limits violations"}"#,
+                None
+            ),
+            Err(Error::RateLimited { retry_after: None })
+        ));
+        assert!(matches!(
+            parse(
+                StatusCode::FORBIDDEN,
+                r#"{"error": "Invalid API key"}"#,
+                None
+            ),
+            Err(Error::Generic(_))
+        ));
+        assert!(matches!(
+            parse(StatusCode::BAD_GATEWAY, "<html>", None),
+            Err(Error::Generic(_))
+        ));
+    }
+
+    #[test]
+    async fn test_url() {
+        let libre = |url: &str| LibreTranslate::new(Client::new(), url.to_string(), None);
+        assert_eq!(
+            libre("http://localhost:5000").unwrap().url.as_str(),
+            "http://localhost:5000/translate"
+        );
+        assert_eq!(
+            libre("https://example.com/libre/").unwrap().url.as_str(),
+            "https://example.com/libre/translate"
+        );
+        assert!(libre("localhost:5000").is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use anyhow::bail;
+use async_trait::async_trait;
+use plugin_core::Result;
+use reqwest::Client;
+use serde::Deserialize;
+
+mod deepl;
+mod libretranslate;
+
+pub use deepl::DeepL;
+pub use libretranslate::LibreTranslate;
+
+/// The known backends, for the config errors
+const BACKENDS: &[&str] = &["deepl", "libretranslate"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Translation {
+    pub text: String,
+    /// in lowercase, as detected when it wasn't given, None when the backend
+    /// doesn't tell
+    pub source: Option<String>,
+}
+
+#[async_trait]
+pub trait Backend: Send + Sync {
+    /// Only for the logs
+    fn name(&self) -> &'static str;
+
+    /// To the target language, from the source one or the detected one when
+    /// None. The language codes are in lowercase, like `en` or `pt-br`. A
+    /// quota exhausted is a rate limit, an unsupported language a user
+    /// visible error.
+    async fn translate(
+        &self,
+        text: &str,
+        source: Option<&str>,
+        target: &str,
+    ) -> Result<Translation>;
+}
+
+/// The `backend` of the translate config section
+#[derive(Debug, Clone, Deserialize)]
+pub struct BackendSettings {
+    pub name: String,
+    /// of the LibreTranslate instance, like `http://localhost:5000`
+    #[serde(default)]
+    pub url: Option<String>,
+    /// mandatory for DeepL, only for the instances which require one with
+    /// LibreTranslate
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+pub fn build(client: &Client, settings: &BackendSettings) -> anyhow::Result<Box<dyn Backend>> {
+    match settings.name.as_str() {
+        "deepl" => match &settings.api_key {
+            Some(api_key) => Ok(Box::new(DeepL::new(client.clone(), api_key.clone()))),
+            None => bail!("translate.backend: deepl needs an api_key"),
+        },
+        "libretranslate" => match &settings.url {
+            Some(url) => Ok(Box::new(LibreTranslate::new(
+                client.clone(),
+                url.clone(),
+                settings.api_key.clone(),
+            )?)),
+            None => bail!("translate.backend: libretranslate needs the url of an instance"),
+        },
+        name => bail!(
+            "Unknown translate backend {name}, the known ones are {}",
+            BACKENDS.join(", ")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn settings(name: &str, url: Option<&str>, api_key: Option<&str>) -> BackendSettings {
+        BackendSettings {
+            name: name.to_string(),
+            url: url.map(str::to_string),
+            api_key: api_key.map(str::to_string),
+        }
+    }
+
+    #[test]
+    async fn test_build() {
+        let client = Client::new();
+        let deepl = build(&client, &settings("deepl", None, Some("abc:fx"))).unwrap();
+        assert_eq!(deepl.name(), "deepl");
+        let libre = build(
+            &client,
+            &settings("libretranslate", Some("http://localhost:5000"), None),
+        )
+        .unwrap();
+        assert_eq!(libre.name(), "libretranslate");
+        for invalid in [
+            settings("deepl", Some("https://api.deepl.com"), None),
+            settings("libretranslate", None, Some("abc")),
+            settings("libretranslate", Some("localhost:5000"), None),
+            settings("google", None, Some("abc")),
+        ] {
+            assert!(build(&client, &invalid).is_err(), "{invalid:?}");
+        }
+    }
+}
@@ -0,0 +1,4 @@
+mod backends;
+mod plugin;
+
+pub use plugin::Translate;
@@ -0,0 +1,551 @@
+use std::time::Duration;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use irc::proto::{ChannelExt, Command, Message};
+use plugin_core::utils::network::network;
+use plugin_core::utils::parser;
+use plugin_core::{CommandHelp, Cooldown, Error, Initialised, MsgCtx, Outbound, Plugin, Result};
+use serde::Deserialize;
+
+use super::backends::{self, Backend, BackendSettings, Translation};
+use crate::caps::CaseMapping;
+use crate::utils::backlog::Backlog;
+use crate::utils::messages::with_target;
+use crate::utils::text::{sanitize, strip_formatting};
+
+const USAGE: &str = "Usage: λtr [source->]<language> <text>, λtr last [language]";
+
+/// Longer translations are cut, in chars
+const MAX_REPLY_LENGTH: usize = 400;
+
+/// Longest text translated, in chars, unless the config says otherwise
+pub const DEFAULT_MAX_LENGTH: usize = 500;
+/// Between two translations asked by the same nick, unless the config says
+/// otherwise
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(10);
+
+/// The `translate` section of the golem config
+#[derive(Deserialize)]
+struct Settings {
+    backend: BackendSettings,
+    /// longer texts aren't sent to the backend, in chars
+    #[serde(default = "default_max_length")]
+    max_length: usize,
+    /// of λtr last without a language
+    #[serde(default = "default_language")]
+    default_language: String,
+    /// for each nick, the backends being billed or rate limited
+    #[serde(default = "default_cooldown_secs")]
+    cooldown_secs: u64,
+}
+
+fn default_max_length() -> usize {
+    DEFAULT_MAX_LENGTH
+}
+
+fn default_language() -> String {
+    "en".to_string()
+}
+
+fn default_cooldown_secs() -> u64 {
+    DEFAULT_COOLDOWN.as_secs()
+}
+
+impl Settings {
+    fn load(config: &plugin_core::Config) -> Result<Self> {
+        let settings: Settings = config
+            .plugin_section("translate")?
+            .ok_or_else(|| anyhow!("No translate section in the config"))?;
+        if language(&settings.default_language).is_none() {
+            return Err(anyhow!(
+                "translate.default_language: invalid language {}",
+                settings.default_language
+            )
+            .into());
+        }
+        Ok(settings)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum TrCommand<'a> {
+    /// `λtr en->fr The quick brown fox`, or `λtr fr bonjour` to detect the
+    /// source language
+    Translate {
+        source: Option<String>,
+        target: String,
+        text: &'a str,
+    },
+    /// `λtr last [fr]`, the last message of the channel
+    Last(Option<String>),
+}
+
+/// In lowercase, like `fr` or `pt-br`, None when it doesn't look like a
+/// language code
+fn language(code: &str) -> Option<String> {
+    let (base, region) = match code.split_once('-') {
+        Some((base, region)) => (base, Some(region)),
+        None => (code, None),
+    };
+    let valid_base = (2..=3).contains(&base.len()) && base.chars().all(|c| c.is_ascii_alphabetic());
+    let valid_region = region.map_or(true, |region| {
+        (2..=4).contains(&region.len()) && region.chars().all(|c| c.is_ascii_alphanumeric())
+    });
+    if valid_base && valid_region {
+        Some(code.to_ascii_lowercase())
+    } else {
+        None
+    }
+}
+
+/// `en->fr` or `en→fr`, `auto->fr` or `->fr` to detect the source language
+fn languages(input: &str) -> Option<(Option<String>, String)> {
+    let (source, target) = input.split_once("->").or_else(|| input.split_once('→'))?;
+    let source = match source {
+        "" => None,
+        source if source.eq_ignore_ascii_case("auto") => None,
+        source => Some(language(source)?),
+    };
+    Some((source, language(target)?))
+}
+
+/// None when the args aren't a translation
+fn parse_command(args: &str) -> Option<TrCommand<'_>> {
+    let args = args.trim();
+    let (first, rest) = match args.split_once(char::is_whitespace) {
+        Some((first, rest)) => (first, rest.trim()),
+        None => (args, ""),
+    };
+    if first.eq_ignore_ascii_case("last") {
+        return match rest {
+            "" => Some(TrCommand::Last(None)),
+            rest => language(rest).map(|target| TrCommand::Last(Some(target))),
+        };
+    }
+    if rest.is_empty() {
+        return None;
+    }
+    let (source, target) = match languages(first) {
+        Some(languages) => languages,
+        None => (None, language(first)?),
+    };
+    Some(TrCommand::Translate {
+        source,
+        target,
+        text: rest,
+    })
+}
+
+/// Like `[en → fr]`, or `[fr]` when the backend didn't tell the source
+fn languages_hint(translation: &Translation, target: &str) -> String {
+    match &translation.source {
+        Some(source) => format!("[{source} → {target}]"),
+        None => format!("[{target}]"),
+    }
+}
+
+pub struct Translate {
+    backend: Box<dyn Backend>,
+    /// what was said in the channels, for λtr last
+    backlog: Backlog,
+    max_length: usize,
+    default_language: String,
+    /// between two translations asked by a nick
+    cooldown: Cooldown,
+}
+
+#[async_trait]
+impl Plugin for Translate {
+    fn check_config(config: &plugin_core::Config) -> Result<()> {
+        let settings = Settings::load(config)?;
+        backends::build(&config.http_client(), &settings.backend)?;
+        Ok(())
+    }
+
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let settings = Settings::load(config)?;
+        Ok(Initialised::from(Translate {
+            backend: backends::build(&config.http_client(), &settings.backend)?,
+            backlog: Backlog::default(),
+            max_length: settings.max_length,
+            default_language: settings.default_language.to_ascii_lowercase(),
+            cooldown: Cooldown::new(Duration::from_secs(settings.cooldown_secs)),
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "translate"
+    }
+
+    async fn in_message_ctx(&self, ctx: &MsgCtx, msg: &Message) -> Result<Option<Outbound>> {
+        let reply = self.in_msg(msg, ctx.casemapping).await;
+        self.remember(msg, ctx.casemapping);
+        reply
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("tr")
+                .usage("tr [source->]<language> <text> [> nick]")
+                .description(
+                    "Translate the text, like λtr en->fr hello, the source language is \
+                     detected when not given",
+                ),
+            CommandHelp::new("tr last")
+                .usage("tr last [language] [> nick]")
+                .description("Translate the last message of the channel"),
+        ]
+    }
+}
+
+impl Translate {
+    async fn in_msg(&self, msg: &Message, casemapping: CaseMapping) -> Result<Option<Outbound>> {
+        let response_target = match msg.response_target() {
+            Some(target) => target.to_string(),
+            None => return Ok(None),
+        };
+        let privmsg = match &msg.command {
+            Command::PRIVMSG(_, privmsg) => privmsg,
+            _ => return Ok(None),
+        };
+        let (args, mb_target) = match parser::command("tr")(privmsg) {
+            Ok((_, command)) => command,
+            Err(_) => return Ok(None),
+        };
+        let command = parse_command(args);
+        if command.is_some() && !self.cooled_down(msg, casemapping) {
+            return Ok(None);
+        }
+        let text = match command {
+            None => USAGE.to_string(),
+            Some(TrCommand::Translate {
+                source,
+                target,
+                text,
+            }) => {
+                let translation = self.translate(text, source.as_deref(), &target).await?;
+                format!(
+                    "{} {}",
+                    translation.text,
+                    languages_hint(&translation, &target)
+                )
+            }
+            Some(TrCommand::Last(target)) => {
+                let target = target.as_deref().unwrap_or(&self.default_language);
+                let network = network(msg).unwrap_or_default();
+                let last = if response_target.is_channel_name() {
+                    self.backlog.last(network, casemapping, &response_target)
+                } else {
+                    None
+                };
+                match last {
+                    None => "Nothing to translate here yet".to_string(),
+                    Some((said_by, said)) => {
+                        let translation = self.translate(&said, None, target).await?;
+                        format!(
+                            "{said_by} {}: {}",
+                            languages_hint(&translation, target),
+                            translation.text
+                        )
+                    }
+                }
+            }
+        };
+        Ok(Some(Outbound::reply(
+            response_target,
+            sanitize(&with_target(&text, &mb_target), MAX_REPLY_LENGTH),
+        )))
+    }
+
+    /// Whether the cooldown of the sender of the message is over, nicks
+    /// compared with the casemapping of their network
+    fn cooled_down(&self, msg: &Message, casemapping: CaseMapping) -> bool {
+        let nick = msg.source_nickname().unwrap_or_default();
+        self.cooldown.check(&casemapping.normalize(nick))
+    }
+
+    /// Without the formatting, the failures of the backend only logged
+    async fn translate(
+        &self,
+        text: &str,
+        source: Option<&str>,
+        target: &str,
+    ) -> Result<Translation> {
+        let text = strip_formatting(text);
+        let text = text.trim();
+        if text.chars().count() > self.max_length {
+            return Err(Error::user_visible(format!(
+                "Too long to translate, {} characters at most",
+                self.max_length
+            )));
+        }
+        self.backend
+            .translate(text, source, target)
+            .await
+            .map_err(|err| match err {
+                Error::UserVisible { .. } | Error::RateLimited { .. } => err,
+                err => {
+                    log::error!(
+                        "Cannot translate to {target} with {}: {err:#}",
+                        self.backend.name()
+                    );
+                    Error::user_visible("The translation is unavailable for now")
+                }
+            })
+    }
+
+    /// Keeps the messages of the channels for λtr last, but not the commands
+    fn remember(&self, msg: &Message, casemapping: CaseMapping) {
+        let (channel, text) = match &msg.command {
+            Command::PRIVMSG(target, text) if target.is_channel_name() => (target, text),
+            _ => return,
+        };
+        let nick = match msg.source_nickname() {
+            Some(nick) => nick,
+            None => return,
+        };
+        if parser::command_prefix(text).is_ok() || text.starts_with('\x01') {
+            return;
+        }
+        let network = network(msg).unwrap_or_default();
+        self.backlog
+            .remember(network, casemapping, channel, nick, text);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use plugin_core::utils::network::set_network;
+    use pretty_assertions::assert_eq;
+    use std::sync::{Arc, Mutex};
+
+    /// Translates to `<target>:<text>`, the source is `en` unless given. The
+    /// texts starting with `!` fail
+    #[derive(Default)]
+    struct Canned {
+        /// text, source and target of every translation asked
+        asked: Arc<Mutex<Vec<(String, Option<String>, String)>>>,
+    }
+
+    #[async_trait]
+    impl Backend for Canned {
+        fn name(&self) -> &'static str {
+            "canned"
+        }
+
+        async fn translate(
+            &self,
+            text: &str,
+            source: Option<&str>,
+            target: &str,
+        ) -> Result<Translation> {
+            self.asked.lock().unwrap().push((
+                text.to_string(),
+                source.map(str::to_string),
+                target.to_string(),
+            ));
+            match text {
+                "!quota" => Err(Error::user_visible("No more translations for this month")),
+                "!limited" => Err(Error::RateLimited { retry_after: None }),
+                text if text.starts_with('!') => Err(anyhow!("boom").into()),
+                text => Ok(Translation {
+                    text: format!("{target}:{text}"),
+                    source: Some(source.unwrap_or("en").to_string()),
+                }),
+            }
+        }
+    }
+
+    fn translate() -> (Translate, Arc<Mutex<Vec<(String, Option<String>, String)>>>) {
+        let backend = Canned::default();
+        let asked = Arc::clone(&backend.asked);
+        let plugin = Translate {
+            backend: Box::new(backend),
+            backlog: Backlog::default(),
+            max_length: 20,
+            default_language: "fr".to_string(),
+            cooldown: Cooldown::new(Duration::ZERO),
+        };
+        (plugin, asked)
+    }
+
+    async fn say_in(plugin: &Translate, target: &str, text: &str) -> Result<Option<String>> {
+        say_by(plugin, "alice", target, text).await
+    }
+
+    async fn say_by(
+        plugin: &Translate,
+        nick: &str,
+        target: &str,
+        text: &str,
+    ) -> Result<Option<String>> {
+        let source = format!("{nick}!~{nick}@localhost");
+        let mut msg = Message::new(Some(&source), "PRIVMSG", vec![target, text]).unwrap();
+        set_network(&mut msg, "libera");
+        match plugin
+            .in_message_ctx(&MsgCtx::from_message(&msg), &msg)
+            .await?
+        {
+            Some(Outbound::Reply { text, .. }) => Ok(Some(text)),
+            None => Ok(None),
+            other => panic!("unexpected reply to {text:?}: {other:?}"),
+        }
+    }
+
+    async fn say(plugin: &Translate, text: &str) -> Option<String> {
+        say_in(plugin, "#rust", text).await.unwrap()
+    }
+
+    fn translation<'a>(source: Option<&str>, target: &str, text: &'a str) -> Option<TrCommand<'a>> {
+        Some(TrCommand::Translate {
+            source: source.map(str::to_string),
+            target: target.to_string(),
+            text,
+        })
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        for (args, expected) in [
+            (
+                "en->fr The quick brown fox",
+                translation(Some("en"), "fr", "The quick brown fox"),
+            ),
+            ("EN→FR  hello ", translation(Some("en"), "fr", "hello")),
+            ("fr bonjour", translation(None, "fr", "bonjour")),
+            ("auto->pt-BR hello", translation(None, "pt-br", "hello")),
+            ("->de hello", translation(None, "de", "hello")),
+            ("last", Some(TrCommand::Last(None))),
+            ("last EN", Some(TrCommand::Last(Some("en".to_string())))),
+            ("fr last", translation(None, "fr", "last")),
+            ("last week", None),
+            ("", None),
+            ("fr", None),
+            ("en->fr", None),
+            ("french bonjour", None),
+            ("en->french bonjour", None),
+            ("e1->fr bonjour", None),
+        ] {
+            assert_eq!(parse_command(args), expected, "{args:?}");
+        }
+    }
+
+    #[test]
+    async fn test_translate() {
+        let (plugin, asked) = translate();
+        assert_eq!(
+            say(&plugin, "λtr en->fr The quick fox").await.as_deref(),
+            Some("fr:The quick fox [en → fr]")
+        );
+        assert_eq!(
+            say(&plugin, "λtr de \u{2}bonjour\u{2} > bob")
+                .await
+                .as_deref(),
+            Some("bob: de:bonjour [en → de]")
+        );
+        assert_eq!(
+            *asked.lock().unwrap(),
+            vec![
+                (
+                    "The quick fox".to_string(),
+                    Some("en".to_string()),
+                    "fr".to_string()
+                ),
+                ("bonjour".to_string(), None, "de".to_string()),
+            ]
+        );
+        assert_eq!(
+            say(&plugin, "λtr french bonjour").await.as_deref(),
+            Some(USAGE)
+        );
+        assert_eq!(say(&plugin, "λtranslate fr hello").await, None);
+    }
+
+    #[test]
+    async fn test_last() {
+        let (plugin, asked) = translate();
+        assert_eq!(
+            say(&plugin, "λtr last").await.as_deref(),
+            Some("Nothing to translate here yet")
+        );
+        say(&plugin, "the weather is nice").await;
+        say(&plugin, "λmeteo Lyon").await;
+        say(&plugin, "\u{1}ACTION waves\u{1}").await;
+        say_in(&plugin, "#ocaml", "let () = ()").await.unwrap();
+        assert_eq!(
+            say(&plugin, "λtr last").await.as_deref(),
+            Some("alice [en → fr]: fr:the weather is nice"),
+            "neither the commands nor the actions, in the default language"
+        );
+        assert_eq!(
+            say(&plugin, "λtr last es").await.as_deref(),
+            Some("alice [en → es]: es:the weather is nice")
+        );
+        assert_eq!(
+            say_in(&plugin, "golem", "λtr last")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("Nothing to translate here yet")
+        );
+        assert_eq!(asked.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    async fn test_errors() {
+        let (plugin, asked) = translate();
+        assert!(matches!(
+            say_in(&plugin, "#rust", "λtr fr a text that is way too long").await,
+            Err(Error::UserVisible { message }) if message.contains("20 characters at most")
+        ));
+        assert!(asked.lock().unwrap().is_empty(), "not sent to the backend");
+        assert!(matches!(
+            say_in(&plugin, "#rust", "λtr fr !quota").await,
+            Err(Error::UserVisible { message }) if message.contains("this month")
+        ));
+        assert!(matches!(
+            say_in(&plugin, "#rust", "λtr fr !limited").await,
+            Err(Error::RateLimited { retry_after: None })
+        ));
+        assert!(matches!(
+            say_in(&plugin, "#rust", "λtr fr !boom").await,
+            Err(Error::UserVisible { message }) if message == "The translation is unavailable for now"
+        ));
+    }
+
+    #[test]
+    async fn test_cooldown() {
+        let (plugin, asked) = translate();
+        let plugin = Translate {
+            cooldown: Cooldown::new(Duration::from_secs(10)),
+            ..plugin
+        };
+        assert_eq!(
+            say(&plugin, "λtr fr hello").await.as_deref(),
+            Some("fr:hello [en → fr]")
+        );
+        assert_eq!(say(&plugin, "λtr de hello").await, None);
+        assert_eq!(say(&plugin, "λtr last").await, None, "λtr last too");
+        assert_eq!(
+            say(&plugin, "λtr french hello").await.as_deref(),
+            Some(USAGE),
+            "the usage still told"
+        );
+        assert_eq!(
+            say_by(&plugin, "bob", "#rust", "λtr de hello")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("de:hello [en → de]")
+        );
+        assert_eq!(
+            say_by(&plugin, "ALICE", "#rust", "λtr de hello")
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(asked.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,95 @@
+/// Plugins without a configured priority get this one
+pub const DEFAULT_PRIORITY: u32 = 100;
+
+/// Which replies win when several plugins answer the same message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Priority {
+    /// lower goes first
+    pub priority: u32,
+    /// when the plugin replies, the replies of plugins with a lower priority are dropped
+    pub exclusive: bool,
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority {
+            priority: DEFAULT_PRIORITY,
+            exclusive: false,
+        }
+    }
+}
+
+/// Orders the replies by priority, keeping the plugins order between equal
+/// priorities, and drops the ones overruled by an exclusive plugin
+pub fn prioritize<T>(mut replies: Vec<(Priority, Option<T>)>) -> Vec<Option<T>> {
+    // stable, so that the default priorities keep the plugins order
+    replies.sort_by_key(|(priority, _)| priority.priority);
+    let exclusive = replies
+        .iter()
+        .find(|(priority, reply)| priority.exclusive && reply.is_some())
+        .map(|(priority, _)| priority.priority);
+    replies
+        .into_iter()
+        .map(|(priority, reply)| match exclusive {
+            Some(winner) if priority.priority > winner => {
+                if reply.is_some() {
+                    log::debug!("Reply overruled by an exclusive plugin of priority {winner}");
+                }
+                None
+            }
+            _ => reply,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn priority(priority: u32, exclusive: bool) -> Priority {
+        Priority {
+            priority,
+            exclusive,
+        }
+    }
+
+    #[test]
+    async fn test_default_keeps_the_order() {
+        let replies = vec![
+            (Priority::default(), Some("a")),
+            (Priority::default(), None),
+            (Priority::default(), Some("c")),
+        ];
+        assert_eq!(prioritize(replies), vec![Some("a"), None, Some("c")]);
+    }
+
+    #[test]
+    async fn test_prioritize() {
+        let replies = vec![
+            (priority(100, false), Some("default")),
+            (priority(10, false), Some("first")),
+            (priority(50, true), Some("exclusive")),
+            (priority(50, false), Some("same priority")),
+        ];
+        assert_eq!(
+            prioritize(replies),
+            vec![
+                Some("first"),
+                Some("exclusive"),
+                Some("same priority"),
+                None
+            ]
+        );
+
+        let replies = vec![
+            (priority(100, false), Some("default")),
+            (priority(50, true), None),
+        ];
+        assert_eq!(
+            prioritize(replies),
+            vec![None, Some("default")],
+            "exclusive only when replying"
+        );
+    }
+}
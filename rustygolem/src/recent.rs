@@ -0,0 +1,239 @@
+use crate::web::is_authorized;
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing, Json, Router,
+};
+use irc::proto::{Command, Message};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+pub const DEFAULT_CAPACITY: usize = 500;
+
+const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Entry {
+    /// RFC3339 timestamp of when the message went through the golem
+    pub timestamp: String,
+    pub direction: Direction,
+    pub source: Option<String>,
+    pub target: Option<String>,
+    /// which plugin produced this message, only for outbound messages
+    pub plugin: Option<&'static str>,
+    pub body: String,
+}
+
+/// Bounded buffer of the last messages seen or sent by the golem.
+/// Useful to investigate "the bot didn't answer me" kind of reports
+/// after the fact.
+pub struct RecentMessages {
+    capacity: usize,
+    /// bodies of messages on these channels are never stored
+    private_channels: Vec<String>,
+    entries: Mutex<VecDeque<Entry>>,
+}
+
+impl RecentMessages {
+    pub fn new(capacity: usize, private_channels: Vec<String>) -> Self {
+        RecentMessages {
+            capacity,
+            private_channels: private_channels
+                .into_iter()
+                .map(|c| c.to_lowercase())
+                .collect(),
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn record_inbound(&self, msg: &Message) {
+        if matches!(msg.command, Command::PING(..) | Command::PONG(..)) {
+            return;
+        }
+        let (target, body) = self.target_and_body(msg);
+        self.push(Entry {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            direction: Direction::Inbound,
+            source: msg.source_nickname().map(String::from),
+            target,
+            plugin: None,
+            body,
+        });
+    }
+
+    pub fn record_outbound(&self, plugin: &'static str, msg: &Message) {
+        let (target, body) = self.target_and_body(msg);
+        self.push(Entry {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            direction: Direction::Outbound,
+            source: None,
+            target,
+            plugin: Some(plugin),
+            body,
+        });
+    }
+
+    /// Oldest entries first
+    pub fn entries(&self) -> Vec<Entry> {
+        self.entries
+            .lock()
+            .expect("recent messages lock")
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    fn push(&self, entry: Entry) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().expect("recent messages lock");
+        while entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    fn target_and_body(&self, msg: &Message) -> (Option<String>, String) {
+        match &msg.command {
+            Command::PRIVMSG(target, text) | Command::NOTICE(target, text) => {
+                let body = if self.is_private(target) {
+                    REDACTED.to_string()
+                } else {
+                    text.clone()
+                };
+                (Some(target.clone()), body)
+            }
+            cmd => (None, String::from(cmd)),
+        }
+    }
+
+    fn is_private(&self, target: &str) -> bool {
+        let target = target.to_lowercase();
+        self.private_channels.iter().any(|c| c == &target)
+    }
+}
+
+#[derive(Clone)]
+struct DebugState {
+    recent: Arc<RecentMessages>,
+    token: Arc<String>,
+}
+
+/// Expose the recent messages under GET /debug/recent, only for requests
+/// carrying the header `Authorization: Bearer <token>`
+pub fn router(recent: Arc<RecentMessages>, token: String) -> Router<()> {
+    let state = DebugState {
+        recent,
+        token: Arc::new(token),
+    };
+    Router::new()
+        .route("/debug/recent", routing::get(get_recent))
+        .with_state(state)
+}
+
+async fn get_recent(State(state): State<DebugState>, headers: HeaderMap) -> Response {
+    if !is_authorized(&headers, &state.token) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    Json(state.recent.entries()).into_response()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use pretty_assertions::assert_eq;
+    use tower::ServiceExt;
+
+    fn privmsg(target: &str, text: &str) -> Message {
+        Command::PRIVMSG(target.to_string(), text.to_string()).into()
+    }
+
+    #[test]
+    async fn test_eviction() {
+        let recent = RecentMessages::new(3, vec![]);
+        for i in 0..5 {
+            recent.record_inbound(&privmsg("#chan", &format!("msg {i}")));
+        }
+        let bodies = recent
+            .entries()
+            .into_iter()
+            .map(|e| e.body)
+            .collect::<Vec<_>>();
+        assert_eq!(bodies, vec!["msg 2", "msg 3", "msg 4"]);
+    }
+
+    #[test]
+    async fn test_zero_capacity() {
+        let recent = RecentMessages::new(0, vec![]);
+        recent.record_inbound(&privmsg("#chan", "coucou"));
+        assert_eq!(recent.entries(), vec![]);
+    }
+
+    #[test]
+    async fn test_outbound_plugin() {
+        let recent = RecentMessages::new(3, vec![]);
+        recent.record_outbound("url", &privmsg("#chan", "coucou"));
+        let entry = recent.entries().pop().unwrap();
+        assert_eq!(entry.direction, Direction::Outbound);
+        assert_eq!(entry.plugin, Some("url"));
+        assert_eq!(entry.target, Some("#chan".to_string()));
+    }
+
+    #[test]
+    async fn test_redact_private_channels() {
+        let recent = RecentMessages::new(3, vec!["#Secret".to_string()]);
+        recent.record_inbound(&privmsg("#secret", "my password is hunter2"));
+        recent.record_outbound("echo", &privmsg("#SECRET", "echo - hunter2"));
+        recent.record_inbound(&privmsg("#public", "coucou"));
+        let bodies = recent
+            .entries()
+            .into_iter()
+            .map(|e| e.body)
+            .collect::<Vec<_>>();
+        assert_eq!(bodies, vec![REDACTED, REDACTED, "coucou"]);
+    }
+
+    async fn get_status(app: Router<()>, auth: Option<&str>) -> StatusCode {
+        let mut req = Request::builder().uri("/debug/recent");
+        if let Some(auth) = auth {
+            req = req.header("Authorization", auth);
+        }
+        app.oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_auth() {
+        let recent = Arc::new(RecentMessages::new(3, vec![]));
+        let app = router(recent, "s3cr3t".to_string());
+
+        assert_eq!(
+            get_status(app.clone(), None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            get_status(app.clone(), Some("Bearer nope")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            get_status(app.clone(), Some("s3cr3t")).await,
+            StatusCode::UNAUTHORIZED,
+            "must use the bearer scheme"
+        );
+        assert_eq!(get_status(app, Some("Bearer s3cr3t")).await, StatusCode::OK);
+    }
+}
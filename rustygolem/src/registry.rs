@@ -0,0 +1,186 @@
+use crate::utils::text::distance;
+use anyhow::Error;
+
+/// Generates, from a list like `crypto => plugins::Crypto, url => plugin_url::UrlPlugin`,
+/// `init_plugin` to initialise a plugin from the name used in the golem
+/// config, `check_plugin_config` to only check its settings,
+/// and `known_plugin_names` listing all these names
+macro_rules! register_plugins {
+    ($($name:ident => $plugin:ty),* $(,)?) => {
+        pub fn known_plugin_names() -> &'static [&'static str] {
+            &[$(stringify!($name)),*]
+        }
+
+        pub async fn init_plugin(
+            config: &plugin_core::Config,
+            name: &str,
+        ) -> anyhow::Result<plugin_core::Initialised> {
+            use anyhow::Context;
+            let plugin = match name {
+                $(stringify!($name) => <$plugin as plugin_core::Plugin>::init(config).await,)*
+                _ => return Err($crate::registry::unknown_plugin(name, known_plugin_names())),
+            };
+            let plugin = plugin.with_context(|| format!("Cannot initalize plugin {}", name))?;
+            log::info!("Plugin initialized: {}", name);
+            Ok(plugin)
+        }
+
+        pub fn check_plugin_config(config: &plugin_core::Config, name: &str) -> anyhow::Result<()> {
+            use anyhow::Context;
+            match name {
+                $(stringify!($name) => <$plugin as plugin_core::Plugin>::check_config(config),)*
+                _ => return Err($crate::registry::unknown_plugin(name, known_plugin_names())),
+            }
+            .with_context(|| format!("Invalid config for plugin {}", name))
+        }
+    };
+}
+
+/// An error for each name which isn't a known plugin
+pub fn unknown_plugins(names: &[String], known: &[&str]) -> Vec<Error> {
+    names
+        .iter()
+        .filter(|name| !known.contains(&name.as_str()))
+        .map(|name| unknown_plugin(name, known))
+        .collect()
+}
+
+pub fn unknown_plugin(name: &str, known: &[&str]) -> Error {
+    let suggestion = match suggest(name, known) {
+        Some(s) => format!(" Did you mean {s}?"),
+        None => String::new(),
+    };
+    anyhow!(
+        "Unknown plugin name: {name}.{suggestion} Known plugins: {}",
+        known.join(", ")
+    )
+}
+
+/// The closest known name, when it's close enough to be a typo
+pub fn suggest<'a>(name: &str, known: &[&'a str]) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    known
+        .iter()
+        .map(|k| (distance(&name, k), *k))
+        .filter(|(d, k)| *d <= (k.chars().count() / 3).max(1))
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, k)| k)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use async_trait::async_trait;
+    use plugin_core::{Config, Initialised, Plugin};
+    use pretty_assertions::assert_eq;
+
+    struct Hello;
+
+    #[async_trait]
+    impl Plugin for Hello {
+        async fn init(_config: &Config) -> plugin_core::Result<Initialised> {
+            Ok(Initialised::from(Hello))
+        }
+
+        fn get_name(&self) -> &'static str {
+            "hello"
+        }
+    }
+
+    struct Broken;
+
+    #[async_trait]
+    impl Plugin for Broken {
+        fn check_config(_config: &Config) -> plugin_core::Result<()> {
+            Err(plugin_core::Error::user_visible("missing key"))
+        }
+
+        async fn init(_config: &Config) -> plugin_core::Result<Initialised> {
+            Err(plugin_core::Error::user_visible("nope"))
+        }
+
+        fn get_name(&self) -> &'static str {
+            "broken"
+        }
+    }
+
+    mod registered {
+        register_plugins! {
+            hello => super::Hello,
+            broken => super::Broken,
+        }
+    }
+
+    #[test]
+    async fn test_known_names() {
+        assert_eq!(registered::known_plugin_names(), &["hello", "broken"]);
+    }
+
+    #[tokio::test]
+    async fn test_init_plugin() {
+        let config = Config::from_dhall_str("{=}").unwrap();
+        let init = registered::init_plugin(&config, "hello").await.unwrap();
+        assert_eq!(init.plugin.get_name(), "hello");
+
+        let err = registered::init_plugin(&config, "broken")
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Cannot initalize plugin broken");
+
+        let err = registered::init_plugin(&config, "helo").await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown plugin name: helo. Did you mean hello? Known plugins: hello, broken"
+        );
+    }
+
+    #[test]
+    async fn test_check_plugin_config() {
+        let config = Config::from_dhall_str("{=}").unwrap();
+        assert!(registered::check_plugin_config(&config, "hello").is_ok());
+        let err = registered::check_plugin_config(&config, "broken").unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "Invalid config for plugin broken: missing key"
+        );
+        assert!(registered::check_plugin_config(&config, "helo").is_err());
+    }
+
+    #[test]
+    async fn test_suggest() {
+        let known = ["crypto", "ctcp", "joke", "republican_calendar", "url"];
+        assert_eq!(suggest("cryto", &known), Some("crypto"));
+        assert_eq!(suggest("URL", &known), Some("url"));
+        assert_eq!(suggest("jokes", &known), Some("joke"));
+        assert_eq!(
+            suggest("republican-calendar", &known),
+            Some("republican_calendar")
+        );
+        assert_eq!(suggest("twitch", &known), None);
+        assert_eq!(suggest("", &known), None);
+    }
+
+    #[test]
+    async fn test_unknown_plugins() {
+        let known = ["crypto", "url"];
+        let names = ["crypto".to_string(), "url".to_string()];
+        assert!(unknown_plugins(&names, &known).is_empty());
+
+        let names = [
+            "crypto".to_string(),
+            "twitch".to_string(),
+            "urll".to_string(),
+        ];
+        let errors = unknown_plugins(&names, &known)
+            .iter()
+            .map(|err| err.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                "Unknown plugin name: twitch. Known plugins: crypto, url",
+                "Unknown plugin name: urll. Did you mean url? Known plugins: crypto, url",
+            ]
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use anyhow::Result;
+use plugin_core::{Plugin, Requirement};
+
+/// What the golem actually has to offer to the plugins
+pub struct Provided<'a> {
+    pub config: &'a plugin_core::Config,
+    pub networks: usize,
+    pub web_server: bool,
+}
+
+/// The requirements of the given plugin that cannot be satisfied
+pub fn unmet(plugin: &str, requirements: &[Requirement], provided: &Provided) -> Vec<Requirement> {
+    requirements
+        .iter()
+        .filter(|req| match req {
+            Requirement::ConfigKey(key) => {
+                !provided.config.has_plugin_key(plugin, key).unwrap_or(false)
+            }
+            Requirement::Network => provided.networks == 0,
+            Requirement::Database => provided.config.database().is_none(),
+            Requirement::WebRouter => !provided.web_server,
+        })
+        .cloned()
+        .collect()
+}
+
+/// Check the requirements of all the plugins. In strict mode, any unmet
+/// requirement is an error, otherwise they are only logged.
+pub fn check(plugins: &[&dyn Plugin], provided: &Provided, strict: bool) -> Result<()> {
+    let mut problems = vec![];
+    for plugin in plugins {
+        let name = plugin.get_name();
+        for req in unmet(name, &plugin.requirements(), provided) {
+            problems.push(format!("plugin {name} requires {req}"));
+        }
+    }
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+    if strict {
+        bail!("Unmet plugin requirements: {}", problems.join(", "));
+    }
+    for problem in problems {
+        log::warn!("!!! {problem}, it will probably not work properly !!!");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use async_trait::async_trait;
+    use plugin_core::{Config, Initialised};
+    use pretty_assertions::assert_eq;
+
+    struct NeedsKey;
+
+    #[async_trait]
+    impl Plugin for NeedsKey {
+        async fn init(_config: &Config) -> plugin_core::Result<Initialised> {
+            Ok(Initialised::from(NeedsKey))
+        }
+
+        fn get_name(&self) -> &'static str {
+            "needs_key"
+        }
+
+        fn requirements(&self) -> Vec<Requirement> {
+            vec![Requirement::ConfigKey("api_key"), Requirement::Network]
+        }
+    }
+
+    fn provided(config: &Config) -> Provided {
+        Provided {
+            config,
+            networks: 1,
+            web_server: true,
+        }
+    }
+
+    #[test]
+    async fn test_unmet() {
+        let config = Config::from_dhall_str(r#"{ needs_key = { api_key = "secret" } }"#).unwrap();
+        let reqs = NeedsKey.requirements();
+        assert_eq!(unmet("needs_key", &reqs, &provided(&config)), vec![]);
+
+        let no_network = Provided {
+            networks: 0,
+            ..provided(&config)
+        };
+        assert_eq!(
+            unmet("needs_key", &reqs, &no_network),
+            vec![Requirement::Network]
+        );
+
+        let reqs = [Requirement::Database, Requirement::WebRouter];
+        assert_eq!(
+            unmet("needs_key", &reqs, &provided(&config)),
+            vec![Requirement::Database]
+        );
+    }
+
+    #[test]
+    async fn test_missing_key() {
+        for source in [
+            "{ needs_key = { api_key = None Text } }",
+            "{ needs_key = { other = 1 } }",
+            "{ plugins = [\"needs_key\"] }",
+        ] {
+            let config = Config::from_dhall_str(source).unwrap();
+            assert_eq!(
+                unmet("needs_key", &NeedsKey.requirements(), &provided(&config)),
+                vec![Requirement::ConfigKey("api_key")],
+                "{source}"
+            );
+            let plugins: [&dyn Plugin; 1] = [&NeedsKey];
+            let err = check(&plugins, &provided(&config), true).unwrap_err();
+            assert!(err.to_string().contains("config key api_key"), "{err}");
+            assert!(check(&plugins, &provided(&config), false).is_ok());
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use plugin_core::BackgroundTask;
+use tokio::sync::watch;
+
+/// Runs a background task of a plugin, restarting it according to its policy,
+/// until it's done for good or until the golem shuts down.
+/// Shutting down drops the running future, cancelling the task.
+pub async fn supervise(plugin: &str, task: BackgroundTask, mut shutdown: watch::Receiver<bool>) {
+    tokio::select! {
+        _ = run(plugin, &task) => (),
+        _ = shutdown_requested(&mut shutdown) => {
+            log::info!("Task {plugin}/{} cancelled by the shutdown", task.name);
+        }
+    }
+}
+
+async fn run(plugin: &str, task: &BackgroundTask) {
+    let name = &task.name;
+    loop {
+        log::info!("Starting task {plugin}/{name}");
+        let outcome = task.start().await;
+        match &outcome {
+            Ok(()) => log::info!("Task {plugin}/{name} completed"),
+            Err(err) => log::error!("Task {plugin}/{name} failed: {err}"),
+        }
+
+        match task.restart.after(&outcome) {
+            Some(delay) => tokio::time::sleep(delay).await,
+            None => return,
+        }
+    }
+}
+
+/// Completes once true has been sent, or once the golem is gone
+pub async fn shutdown_requested(shutdown: &mut watch::Receiver<bool>) {
+    while !*shutdown.borrow_and_update() {
+        if shutdown.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use plugin_core::{Error, Restart};
+    use pretty_assertions::assert_eq;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failing_task_is_restarted() {
+        let starts = Arc::new(AtomicUsize::new(0));
+        let task = {
+            let starts = Arc::clone(&starts);
+            BackgroundTask::new("flaky", move || {
+                let starts = Arc::clone(&starts);
+                async move {
+                    // fails twice, then succeeds
+                    if starts.fetch_add(1, Ordering::SeqCst) < 2 {
+                        Err(Error::Synthetic("boom".to_string()))
+                    } else {
+                        Ok(())
+                    }
+                }
+            })
+            .restart(Restart::OnFailure {
+                delay: Duration::from_secs(10),
+            })
+        };
+
+        let (_tx, rx) = watch::channel(false);
+        let started_at = Instant::now();
+        supervise("test", task, rx).await;
+        assert_eq!(starts.load(Ordering::SeqCst), 3);
+        assert_eq!(started_at.elapsed(), Duration::from_secs(20));
+    }
+
+    /// Sets the flag when dropped
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown_cancels_tasks() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let forever = {
+            let dropped = Arc::clone(&dropped);
+            BackgroundTask::new("forever", move || {
+                let guard = DropFlag(Arc::clone(&dropped));
+                async move {
+                    let _guard = guard;
+                    std::future::pending::<()>().await;
+                    Ok(())
+                }
+            })
+        };
+        let periodic =
+            BackgroundTask::new("periodic", || async { Ok(()) }).restart(Restart::Always {
+                delay: Duration::from_secs(60),
+            });
+
+        let (tx, rx) = watch::channel(false);
+        let handles = vec![
+            tokio::spawn(supervise("test", forever, rx.clone())),
+            tokio::spawn(supervise("test", periodic, rx)),
+        ];
+        tokio::time::sleep(Duration::from_secs(90)).await;
+        assert!(!dropped.load(Ordering::SeqCst));
+
+        tx.send_replace(true);
+        for handle in handles {
+            tokio::time::timeout(Duration::from_secs(1), handle)
+                .await
+                .expect("task stopped by the shutdown")
+                .unwrap();
+        }
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::caps::CaseMapping;
+
+/// The last messages kept of each nick in a channel
+pub const MESSAGES_PER_NICK: usize = 5;
+/// Nicks kept in a channel, the ones who spoke the least recently are
+/// forgotten first
+pub const NICKS_PER_CHANNEL: usize = 50;
+
+/// The last messages of a nick, oldest first
+struct Said {
+    /// as last seen
+    nick: String,
+    messages: VecDeque<String>,
+}
+
+/// The last messages of the channels, in memory and bounded, for the plugins
+/// working on what was just said
+#[derive(Default)]
+pub struct Backlog {
+    /// by network and normalized channel, the nicks who spoke last at the back
+    channels: Mutex<HashMap<(String, String), VecDeque<Said>>>,
+}
+
+impl Backlog {
+    pub fn remember(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        nick: &str,
+        text: &str,
+    ) {
+        let key = (network.to_string(), casemapping.normalize(channel));
+        let mut channels = self.channels.lock().expect("backlog lock");
+        let nicks = channels.entry(key).or_default();
+        let mut said = match nicks
+            .iter()
+            .position(|said| casemapping.eq_ignore_case(&said.nick, nick))
+        {
+            Some(i) => nicks.remove(i).expect("a nick of the channel"),
+            None => Said {
+                nick: nick.to_string(),
+                messages: VecDeque::new(),
+            },
+        };
+        said.nick = nick.to_string();
+        said.messages.push_back(text.to_string());
+        while said.messages.len() > MESSAGES_PER_NICK {
+            said.messages.pop_front();
+        }
+        nicks.push_back(said);
+        while nicks.len() > NICKS_PER_CHANNEL {
+            nicks.pop_front();
+        }
+    }
+
+    /// The nick as last seen in the channel, and its last messages, oldest
+    /// first
+    pub fn messages_of(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+        nick: &str,
+    ) -> Option<(String, Vec<String>)> {
+        let key = (network.to_string(), casemapping.normalize(channel));
+        let channels = self.channels.lock().expect("backlog lock");
+        let said = channels
+            .get(&key)?
+            .iter()
+            .find(|said| casemapping.eq_ignore_case(&said.nick, nick))?;
+        Some((said.nick.clone(), said.messages.iter().cloned().collect()))
+    }
+
+    /// The last message of the channel, and who said it
+    pub fn last(
+        &self,
+        network: &str,
+        casemapping: CaseMapping,
+        channel: &str,
+    ) -> Option<(String, String)> {
+        let key = (network.to_string(), casemapping.normalize(channel));
+        let channels = self.channels.lock().expect("backlog lock");
+        let said = channels.get(&key)?.back()?;
+        Some((said.nick.clone(), said.messages.back()?.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const RFC1459: CaseMapping = CaseMapping::Rfc1459;
+
+    fn messages(backlog: &Backlog, channel: &str, nick: &str) -> Option<(String, Vec<String>)> {
+        backlog.messages_of("libera", RFC1459, channel, nick)
+    }
+
+    #[test]
+    async fn test_backlog() {
+        let backlog = Backlog::default();
+        assert_eq!(backlog.last("libera", RFC1459, "#rust"), None);
+        backlog.remember("libera", RFC1459, "#rust", "alice", "hello");
+        backlog.remember("libera", RFC1459, "#Rust", "bob", "hi");
+        backlog.remember("libera", RFC1459, "#rust", "Alice", "how are you?");
+        backlog.remember("libera", RFC1459, "#ocaml", "bob", "let () = ()");
+        assert_eq!(
+            messages(&backlog, "#RUST", "ALICE"),
+            Some((
+                "Alice".to_string(),
+                vec!["hello".to_string(), "how are you?".to_string()]
+            ))
+        );
+        assert_eq!(
+            backlog.last("libera", RFC1459, "#rust"),
+            Some(("Alice".to_string(), "how are you?".to_string()))
+        );
+        assert_eq!(
+            backlog.last("libera", RFC1459, "#ocaml"),
+            Some(("bob".to_string(), "let () = ()".to_string()))
+        );
+        assert_eq!(backlog.last("oftc", RFC1459, "#rust"), None);
+        assert_eq!(messages(&backlog, "#rust", "charlie"), None);
+    }
+
+    #[test]
+    async fn test_bounds() {
+        let backlog = Backlog::default();
+        for i in 1..=MESSAGES_PER_NICK + 1 {
+            backlog.remember("libera", RFC1459, "#rust", "alice", &i.to_string());
+        }
+        let (_, said) = messages(&backlog, "#rust", "alice").unwrap();
+        assert_eq!(said.len(), MESSAGES_PER_NICK);
+        assert_eq!(said[0], "2", "the oldest one is forgotten");
+
+        for i in 0..NICKS_PER_CHANNEL {
+            backlog.remember("libera", RFC1459, "#rust", &format!("nick{i}"), "hello");
+        }
+        assert_eq!(messages(&backlog, "#rust", "alice"), None, "forgotten");
+        assert!(messages(&backlog, "#rust", "nick0").is_some());
+    }
+}
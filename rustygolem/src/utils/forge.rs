@@ -0,0 +1,440 @@
+use anyhow::anyhow;
+use axum::http::{HeaderMap, StatusCode};
+use hmac::{Hmac, Mac, NewMac};
+use plugin_core::utils::hex::decode_hex;
+use plugin_core::{Outbound, Result};
+use serde::Deserialize;
+use tokio::sync::{mpsc, Mutex as TokioMutex};
+
+use crate::utils::text::sanitize;
+
+type HmacSha256 = Hmac<sha2::Sha256>;
+
+/// The events which can be routed, as named in the `events` of the routes
+pub const KNOWN_EVENTS: &[&str] = &["push", "pull_request", "issues", "tag", "release"];
+
+/// Longer announcements are cut, in chars
+const MAX_ANNOUNCEMENT_LENGTH: usize = 400;
+
+/// The sha given by the forges as `before` for a new branch, or as `after`
+/// for a deleted one
+const NULL_SHA: &str = "0000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PullRequestAction {
+    Opened,
+    Reopened,
+    Merged,
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueAction {
+    Opened,
+    Reopened,
+    Closed,
+}
+
+/// Something worth announcing on a repository, whichever forge it comes from.
+/// The forges' payloads are mapped to it so that their announcements are the
+/// same.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RepoEvent {
+    Push {
+        /// like `CoucouInc/rustygolem`
+        repo: String,
+        pusher: String,
+        branch: String,
+        commits: usize,
+        /// of the oldest commit pushed
+        first_message: String,
+        /// comparing the branch before and after the push
+        url: String,
+    },
+    /// A merge request for GitLab
+    PullRequest {
+        repo: String,
+        actor: String,
+        action: PullRequestAction,
+        number: u64,
+        title: String,
+        url: String,
+    },
+    Issue {
+        repo: String,
+        actor: String,
+        action: IssueAction,
+        number: u64,
+        title: String,
+        url: String,
+    },
+    Tag {
+        repo: String,
+        pusher: String,
+        tag: String,
+        url: String,
+    },
+    /// Not every forge tells who published it
+    Release {
+        repo: String,
+        name: String,
+        prerelease: bool,
+        url: String,
+    },
+}
+
+/// The branch of a ref like `refs/heads/master`
+pub fn branch(git_ref: &str) -> Option<&str> {
+    git_ref.strip_prefix("refs/heads/")
+}
+
+/// The tag of a ref like `refs/tags/v0.2.0`
+pub fn tag(git_ref: &str) -> Option<&str> {
+    git_ref.strip_prefix("refs/tags/")
+}
+
+/// For the `before` of a new branch or the `after` of a deleted one
+pub fn is_null_sha(sha: &str) -> bool {
+    sha == NULL_SHA
+}
+
+/// The oldest of the commits of a push to `head`, whatever their order since
+/// some forges list them newest first
+pub fn oldest<'a, C>(commits: &'a [C], head: &str, id: impl Fn(&C) -> &str) -> Option<&'a C> {
+    match commits.first() {
+        Some(first) if id(first) == head => commits.last(),
+        first => first,
+    }
+}
+
+/// The first line, for the commit messages
+fn summary(message: &str) -> &str {
+    message.lines().next().unwrap_or_default().trim()
+}
+
+impl RepoEvent {
+    /// As in `KNOWN_EVENTS`
+    pub fn name(&self) -> &'static str {
+        match self {
+            RepoEvent::Push { .. } => "push",
+            RepoEvent::PullRequest { .. } => "pull_request",
+            RepoEvent::Issue { .. } => "issues",
+            RepoEvent::Tag { .. } => "tag",
+            RepoEvent::Release { .. } => "release",
+        }
+    }
+
+    pub fn repo(&self) -> &str {
+        match self {
+            RepoEvent::Push { repo, .. }
+            | RepoEvent::PullRequest { repo, .. }
+            | RepoEvent::Issue { repo, .. }
+            | RepoEvent::Tag { repo, .. }
+            | RepoEvent::Release { repo, .. } => repo,
+        }
+    }
+
+    /// Where to see it on the forge
+    pub fn url(&self) -> &str {
+        match self {
+            RepoEvent::Push { url, .. }
+            | RepoEvent::PullRequest { url, .. }
+            | RepoEvent::Issue { url, .. }
+            | RepoEvent::Tag { url, .. }
+            | RepoEvent::Release { url, .. } => url,
+        }
+    }
+
+    /// The line announced on IRC, like
+    /// `CoucouInc/rustygolem: geekingfrog merged PR #42: Add the dice plugin https://…`
+    pub fn announcement(&self) -> String {
+        let repo = self.repo();
+        let url = self.url();
+        let line = match self {
+            RepoEvent::Push {
+                pusher,
+                branch,
+                commits,
+                first_message,
+                ..
+            } => {
+                let plural = if *commits == 1 { "" } else { "s" };
+                format!(
+                    "{repo}: {commits} commit{plural} to {branch} by {pusher} — {} {url}",
+                    summary(first_message)
+                )
+            }
+            RepoEvent::PullRequest {
+                actor,
+                action,
+                number,
+                title,
+                ..
+            } => {
+                let action = match action {
+                    PullRequestAction::Opened => "opened",
+                    PullRequestAction::Reopened => "reopened",
+                    PullRequestAction::Merged => "merged",
+                    PullRequestAction::Closed => "closed",
+                };
+                format!("{repo}: {actor} {action} PR #{number}: {title} {url}")
+            }
+            RepoEvent::Issue {
+                actor,
+                action,
+                number,
+                title,
+                ..
+            } => {
+                let action = match action {
+                    IssueAction::Opened => "opened",
+                    IssueAction::Reopened => "reopened",
+                    IssueAction::Closed => "closed",
+                };
+                format!("{repo}: {actor} {action} issue #{number}: {title} {url}")
+            }
+            RepoEvent::Tag { pusher, tag, .. } => format!("{repo}: {pusher} tagged {tag} {url}"),
+            RepoEvent::Release {
+                name, prerelease, ..
+            } => {
+                let pre = if *prerelease { "pre-" } else { "" };
+                format!("{repo}: new {pre}release {name} {url}")
+            }
+        };
+        sanitize(&line, MAX_ANNOUNCEMENT_LENGTH)
+    }
+}
+
+/// An entry of the `routes` list of the config section of a forge
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Route {
+    /// like `CoucouInc/rustygolem`, whatever the case
+    pub repo: String,
+    /// where its events are announced
+    pub channel: String,
+    /// among `KNOWN_EVENTS`, all of them when empty
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+impl Route {
+    fn matches(&self, event: &RepoEvent) -> bool {
+        self.repo.eq_ignore_ascii_case(event.repo())
+            && (self.events.is_empty() || self.events.iter().any(|e| e == event.name()))
+    }
+}
+
+/// The channels where the event is announced
+pub fn channels<'a>(routes: &'a [Route], event: &RepoEvent) -> Vec<&'a str> {
+    routes
+        .iter()
+        .filter(|route| route.matches(event))
+        .map(|route| route.channel.as_str())
+        .collect()
+}
+
+/// The `github`, `gitea` or `gitlab` section of the golem config
+#[derive(Deserialize)]
+pub struct Settings {
+    /// of the webhook, to authenticate the deliveries
+    pub secret: String,
+    #[serde(default)]
+    pub routes: Vec<Route>,
+}
+
+impl Settings {
+    pub fn load(config: &plugin_core::Config, section: &str) -> Result<Self> {
+        let settings: Settings = config
+            .plugin_section(section)?
+            .ok_or_else(|| anyhow!("No {section} section in the config"))?;
+        if settings.secret.is_empty() {
+            return Err(anyhow!("{section}.secret cannot be empty").into());
+        }
+        for route in &settings.routes {
+            if let Some(event) = route
+                .events
+                .iter()
+                .find(|event| !KNOWN_EVENTS.contains(&event.as_str()))
+            {
+                return Err(anyhow!(
+                    "{section}.routes: unknown event {event} for {}, expected one of {}",
+                    route.repo,
+                    KNOWN_EVENTS.join(", ")
+                )
+                .into());
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// Checks a signature given as the hex HMAC-SHA256 of the body
+pub fn verify_signature(secret: &str, signature: Option<&str>, body: &[u8]) -> bool {
+    let signature = match signature.and_then(decode_hex) {
+        Some(signature) => signature,
+        None => return false,
+    };
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("hmac of any length");
+    mac.update(body);
+    mac.verify(&signature).is_ok()
+}
+
+/// The value of the header, None when it's missing or not ascii
+pub fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Sends the announcement of the event to the channels of its routes
+pub async fn announce(
+    routes: &[Route],
+    event: &RepoEvent,
+    tx: &mpsc::Sender<Outbound>,
+) -> StatusCode {
+    let announcement = event.announcement();
+    for channel in channels(routes, event) {
+        let msg = Outbound::reply(channel, announcement.clone());
+        if tx.send(msg).await.is_err() {
+            log::error!("The plugin of the webhook is gone");
+            return StatusCode::SERVICE_UNAVAILABLE;
+        }
+    }
+    StatusCode::OK
+}
+
+/// The announcements of a webhook, sent to IRC by its plugin
+pub struct Relay {
+    rx: TokioMutex<mpsc::Receiver<Outbound>>,
+}
+
+impl Relay {
+    /// With the sender given to the webhook
+    pub fn new() -> (Self, mpsc::Sender<Outbound>) {
+        let (tx, rx) = mpsc::channel(50);
+        let relay = Relay {
+            rx: TokioMutex::new(rx),
+        };
+        (relay, tx)
+    }
+
+    pub async fn run(&self, bot_chan: &mpsc::Sender<Outbound>) -> Result<()> {
+        // hold that lock forever
+        let mut rx = self.rx.lock().await;
+        while let Some(msg) = rx.recv().await {
+            bot_chan.send(msg).await.map_err(anyhow::Error::from)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn push(commits: usize) -> RepoEvent {
+        RepoEvent::Push {
+            repo: "CoucouInc/rustygolem".to_string(),
+            pusher: "geekingfrog".to_string(),
+            branch: "master".to_string(),
+            commits,
+            first_message: "Fix the casemapping\n\nThe servers announcing rfc1459…".to_string(),
+            url: "https://git.coucou.im/CoucouInc/rustygolem/compare/9049f126...0d1a26e6"
+                .to_string(),
+        }
+    }
+
+    fn route(repo: &str, channel: &str, events: &[&str]) -> Route {
+        Route {
+            repo: repo.to_string(),
+            channel: channel.to_string(),
+            events: events.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    async fn test_announcement() {
+        assert_eq!(
+            push(2).announcement(),
+            "CoucouInc/rustygolem: 2 commits to master by geekingfrog — Fix the casemapping \
+             https://git.coucou.im/CoucouInc/rustygolem/compare/9049f126...0d1a26e6"
+        );
+        assert!(push(1).announcement().contains(": 1 commit to master"));
+        let release = RepoEvent::Release {
+            repo: "CoucouInc/rustygolem".to_string(),
+            name: "v0.3.0-rc1".to_string(),
+            prerelease: true,
+            url: "https://git.coucou.im/CoucouInc/rustygolem/releases/tag/v0.3.0-rc1".to_string(),
+        };
+        assert_eq!(
+            release.announcement(),
+            "CoucouInc/rustygolem: new pre-release v0.3.0-rc1 \
+             https://git.coucou.im/CoucouInc/rustygolem/releases/tag/v0.3.0-rc1"
+        );
+        let issue = RepoEvent::Issue {
+            repo: "CoucouInc/rustygolem".to_string(),
+            actor: "Shampooing".to_string(),
+            action: IssueAction::Closed,
+            number: 57,
+            title: "Ding\u{7}\r\nPRIVMSG #coucou :injected".to_string(),
+            url: "https://git.coucou.im/CoucouInc/rustygolem/issues/57".to_string(),
+        };
+        assert!(
+            !issue.announcement().contains(['\r', '\n', '\u{7}']),
+            "{}",
+            issue.announcement()
+        );
+    }
+
+    #[test]
+    async fn test_oldest() {
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let oldest_of = |commits: &[String]| oldest(commits, "c", |c| c.as_str()).cloned();
+        assert_eq!(oldest_of(&ids(&["a", "b", "c"])).as_deref(), Some("a"));
+        assert_eq!(oldest_of(&ids(&["c", "b", "a"])).as_deref(), Some("a"));
+        assert_eq!(oldest_of(&ids(&["c"])).as_deref(), Some("c"));
+        assert_eq!(oldest_of(&[]), None);
+    }
+
+    #[test]
+    async fn test_channels() {
+        let routes = vec![
+            route("CoucouInc/rustygolem", "#coucou", &[]),
+            route("coucouinc/RUSTYGOLEM", "#coucou-dev", &["tag", "push"]),
+            route("CoucouInc/other", "#other", &[]),
+        ];
+        assert_eq!(channels(&routes, &push(2)), vec!["#coucou", "#coucou-dev"]);
+        let tag = RepoEvent::Tag {
+            repo: "CoucouInc/rustygolem".to_string(),
+            pusher: "geekingfrog".to_string(),
+            tag: "v0.2.0".to_string(),
+            url: "https://git.coucou.im/CoucouInc/rustygolem/src/tag/v0.2.0".to_string(),
+        };
+        assert_eq!(channels(&routes, &tag), vec!["#coucou", "#coucou-dev"]);
+        let release = RepoEvent::Release {
+            repo: "CoucouInc/rustygolem".to_string(),
+            name: "Printemps".to_string(),
+            prerelease: false,
+            url: "https://git.coucou.im/CoucouInc/rustygolem/releases/tag/v0.2.0".to_string(),
+        };
+        assert_eq!(channels(&routes, &release), vec!["#coucou"]);
+        assert!(channels(&routes[2..], &release).is_empty());
+    }
+
+    #[test]
+    async fn test_verify_signature() {
+        let body = br#"{"ref": "refs/heads/master"}"#;
+        let mut mac = HmacSha256::new_from_slice(b"s3cr3t").unwrap();
+        mac.update(body);
+        let hex = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+        assert!(verify_signature("s3cr3t", Some(&hex), body));
+        assert!(!verify_signature("other", Some(&hex), body));
+        assert!(!verify_signature("s3cr3t", Some(&hex), b"{}"));
+        assert!(!verify_signature("s3cr3t", None, body));
+        assert!(!verify_signature("s3cr3t", Some("zz"), body));
+    }
+}
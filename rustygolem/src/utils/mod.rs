@@ -1,2 +1,7 @@
+pub mod backlog;
+pub mod forge;
 pub mod messages;
-pub mod parser;
+pub mod numbers;
+pub mod sparkline;
+pub mod text;
+pub mod time;
@@ -0,0 +1,133 @@
+use plugin_core::utils::numbers::group_thousands;
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::str::FromStr;
+
+/// How many significant digits to keep for amounts below 1
+const SIGNIFICANT_DIGITS: u32 = 4;
+
+/// An amount of money, readable at a glance whatever its magnitude:
+/// no decimals from 1000, 2 decimals from 1, and 4 significant digits
+/// (but at least 2 decimals) below that.
+/// `64230.5` gives `64 230`, `0.061234` gives `0.06123`.
+pub fn format_amount(amount: f64) -> String {
+    match Decimal::from_f64_retain(amount) {
+        // half to even, like the float formatting always did
+        Some(amount) => format_rounded(amount, RoundingStrategy::MidpointNearestEven),
+        // NaN or infinite, better than nothing
+        None => amount.to_string(),
+    }
+}
+
+/// Through the shortest representation of the float, so that 0.06
+/// isn't 0.0599999…, None when NaN or infinite
+pub fn decimal_from_f32(value: f32) -> Option<Decimal> {
+    Decimal::from_str(&value.to_string()).ok()
+}
+
+/// Like `decimal_from_f32`, for the prices fetched as doubles
+pub fn decimal_from_f64(value: f64) -> Option<Decimal> {
+    Decimal::from_str(&value.to_string()).ok()
+}
+
+/// Like `format_amount`, without going through a float, the midpoints
+/// rounded away from zero
+pub fn format_decimal(amount: Decimal) -> String {
+    format_rounded(amount, RoundingStrategy::MidpointAwayFromZero)
+}
+
+fn format_rounded(amount: Decimal, strategy: RoundingStrategy) -> String {
+    let abs = amount.abs();
+    let decimals = if abs >= Decimal::from(1000) {
+        0
+    } else if abs >= Decimal::ONE || abs.is_zero() {
+        2
+    } else {
+        // how many zeros right after the point
+        let mut zeros = 0;
+        let mut shifted = abs;
+        while shifted < Decimal::new(1, 1) {
+            shifted *= Decimal::from(10);
+            zeros += 1;
+        }
+        (zeros + SIGNIFICANT_DIGITS).min(12)
+    };
+    let rounded = abs.round_dp_with_strategy(decimals, strategy);
+    let formatted = rounded.to_string();
+    let sign = if amount.is_sign_negative() && !rounded.is_zero() {
+        "-"
+    } else {
+        ""
+    };
+    match formatted.split_once('.') {
+        Some((integer, fraction)) => {
+            let fraction = fraction.trim_end_matches('0');
+            format!("{sign}{}.{fraction:0<2}", group_thousands(integer))
+        }
+        None if decimals > 0 => format!("{sign}{}.00", group_thousands(&formatted)),
+        None => format!("{sign}{}", group_thousands(&formatted)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_format_amount() {
+        let cases = [
+            (64230.5, "64\u{2009}230"),
+            (1234567.0, "1\u{2009}234\u{2009}567"),
+            (1000.0, "1\u{2009}000"),
+            (999.999, "1\u{2009}000.00"),
+            (412.5, "412.50"),
+            (1.0, "1.00"),
+            (0.0, "0.00"),
+            (0.5, "0.50"),
+            (0.061234, "0.06123"),
+            (0.06, "0.06"),
+            (0.000012347, "0.00001235"),
+            (-2345.6, "-2\u{2009}346"),
+            (-0.0, "0.00"),
+        ];
+        for (amount, expected) in cases {
+            assert_eq!(format_amount(amount), expected, "{amount}");
+        }
+    }
+
+    #[test]
+    async fn test_format_decimal() {
+        let cases = [
+            ("32115", "32\u{2009}115"),
+            ("32115.5", "32\u{2009}116"),
+            ("0.1", "0.10"),
+            ("2.005", "2.01"),
+            ("0.0000000000001", "0.00"),
+            ("-0.00001", "-0.00001"),
+        ];
+        for (amount, expected) in cases {
+            let amount = Decimal::from_str(amount).unwrap();
+            assert_eq!(format_decimal(amount), expected, "{amount}");
+        }
+    }
+
+    #[test]
+    async fn test_decimal_from_f32() {
+        assert_eq!(decimal_from_f32(0.06), Decimal::from_str("0.06").ok());
+        assert_eq!(
+            decimal_from_f32(64230.12),
+            Decimal::from_str("64230.12").ok()
+        );
+        assert_eq!(decimal_from_f32(f32::NAN), None);
+        assert_eq!(decimal_from_f32(f32::INFINITY), None);
+    }
+
+    #[test]
+    async fn test_decimal_from_f64() {
+        assert_eq!(
+            decimal_from_f64(64230.123456),
+            Decimal::from_str("64230.123456").ok()
+        );
+        assert_eq!(decimal_from_f64(f64::NAN), None);
+    }
+}
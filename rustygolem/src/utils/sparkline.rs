@@ -0,0 +1,63 @@
+/// From the lowest to the highest
+const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// What a missing value looks like
+const GAP: char = ' ';
+
+/// One block per value, scaled between the lowest and the highest of the
+/// series, like `▁▂▃▅▆▇`. NaN and infinities are gaps, and a flat series
+/// is drawn halfway.
+pub fn sparkline(values: &[f64]) -> String {
+    let finite = values.iter().copied().filter(|v| v.is_finite());
+    let min = finite.clone().fold(f64::INFINITY, f64::min);
+    let max = finite.fold(f64::NEG_INFINITY, f64::max);
+    let top = (BLOCKS.len() - 1) as f64;
+    values
+        .iter()
+        .map(|&v| {
+            if !v.is_finite() {
+                GAP
+            } else if max - min <= f64::EPSILON * max.abs() {
+                BLOCKS[BLOCKS.len() / 2 - 1]
+            } else {
+                let level = ((v - min) / (max - min) * top).round() as usize;
+                BLOCKS[level.min(BLOCKS.len() - 1)]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_sparkline() {
+        assert_eq!(
+            sparkline(&[0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]),
+            "▁▂▃▄▅▆▇█"
+        );
+        assert_eq!(
+            sparkline(&[64000.0, 64900.0, 63100.0, 64230.0]),
+            "▅█▁▅",
+            "scaled between the lowest and the highest"
+        );
+        assert_eq!(sparkline(&[-2.0, -1.0, 0.0]), "▁▅█", "negative values");
+    }
+
+    #[test]
+    async fn test_sparkline_flat() {
+        assert_eq!(sparkline(&[3.0, 3.0, 3.0]), "▄▄▄");
+        assert_eq!(sparkline(&[0.06]), "▄");
+        assert_eq!(sparkline(&[0.0, 0.0]), "▄▄");
+    }
+
+    #[test]
+    async fn test_sparkline_gaps() {
+        assert_eq!(sparkline(&[1.0, f64::NAN, 3.0, f64::INFINITY]), "▁ █ ");
+        assert_eq!(sparkline(&[f64::NAN, 2.0, f64::NAN]), " ▄ ");
+        assert_eq!(sparkline(&[f64::NAN, f64::NAN]), "  ");
+        assert_eq!(sparkline(&[]), "");
+    }
+}
@@ -0,0 +1,74 @@
+/// Levenshtein distance, in chars
+pub fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The nick with a zero-width joiner after its first char, for the clients
+/// not to highlight its owner when it's only mentioned
+pub fn unhighlight(nick: &str) -> String {
+    let mut chars = nick.chars();
+    match chars.next() {
+        Some(first) => format!("{first}\u{200D}{}", chars.as_str()),
+        None => String::new(),
+    }
+}
+
+/// Without the irc bold, color, italic… control codes
+pub fn strip_formatting(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // color, with up to two digits of foreground and of background
+            '\x03' => {
+                for _ in 0..2 {
+                    chars.next_if(|c| c.is_ascii_digit());
+                }
+                let mut rest = chars.clone();
+                if rest.next() == Some(',') && rest.next().map_or(false, |c| c.is_ascii_digit()) {
+                    chars.next();
+                    for _ in 0..2 {
+                        chars.next_if(|c| c.is_ascii_digit());
+                    }
+                }
+            }
+            // hex color
+            '\x04' => {
+                for _ in 0..6 {
+                    chars.next_if(|c| c.is_ascii_hexdigit());
+                }
+            }
+            '\x02' | '\x0F' | '\x11' | '\x16' | '\x1D' | '\x1E' | '\x1F' => {}
+            c => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+/// Without the formatting and control codes, CTCP included, and cut to
+/// `max_length` chars
+pub fn sanitize(text: &str, max_length: usize) -> String {
+    let text = strip_formatting(text)
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>();
+    let text = text.trim();
+    if text.chars().count() <= max_length {
+        return text.to_string();
+    }
+    let cut = text
+        .chars()
+        .take(max_length.saturating_sub(1))
+        .collect::<String>();
+    format!("{}…", cut.trim_end())
+}
@@ -0,0 +1,209 @@
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use std::time::Duration;
+
+use crate::registry;
+
+/// Like `30s`, `10m`, `2h` or `1h30m`, None when invalid or zero
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let mut total = 0u64;
+    let mut number = None;
+    for c in input.chars() {
+        match c {
+            '0'..='9' => {
+                let digit = u64::from(c.to_digit(10)?);
+                number = Some(number.unwrap_or(0u64).checked_mul(10)?.checked_add(digit)?);
+            }
+            'h' | 'm' | 's' => {
+                let unit = match c {
+                    'h' => 3600,
+                    'm' => 60,
+                    _ => 1,
+                };
+                total = total.checked_add(number.take()?.checked_mul(unit)?)?;
+            }
+            _ => return None,
+        }
+    }
+    if number.is_some() || total == 0 {
+        return None;
+    }
+    Some(Duration::from_secs(total))
+}
+
+/// The other way around from `parse_duration`, like `1h30m`
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let parts = [(secs / 3600, "h"), (secs / 60 % 60, "m"), (secs % 60, "s")];
+    let formatted = parts
+        .iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, unit)| format!("{n}{unit}"))
+        .collect::<String>();
+    if formatted.is_empty() {
+        "0s".to_string()
+    } else {
+        formatted
+    }
+}
+
+/// Like `2h ago` or `3d 4h ago`, the two largest units
+pub fn format_ago(elapsed: chrono::Duration) -> String {
+    let secs = elapsed.num_seconds().max(0);
+    let units = [
+        (secs / 86400, "d"),
+        (secs / 3600 % 24, "h"),
+        (secs / 60 % 60, "min"),
+        (secs % 60, "s"),
+    ];
+    let first = units
+        .iter()
+        .position(|(n, _)| *n > 0)
+        .unwrap_or(units.len() - 1);
+    let formatted = units[first..]
+        .iter()
+        .take(2)
+        .filter(|(n, _)| *n > 0 || secs == 0)
+        .map(|(n, unit)| format!("{n}{unit}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("{formatted} ago")
+}
+
+/// An IANA name like `America/New_York`, whatever the case
+pub fn zone(name: &str) -> Option<Tz> {
+    name.parse::<Tz>().ok().or_else(|| {
+        TZ_VARIANTS
+            .iter()
+            .find(|tz| tz.name().eq_ignore_ascii_case(name))
+            .copied()
+    })
+}
+
+/// The timezone of the config section of a plugin, with the closest name
+/// in the error when it's a typo
+pub fn parse_timezone(section: &str, name: &str) -> anyhow::Result<Tz> {
+    zone(name).ok_or_else(|| {
+        let known = TZ_VARIANTS.iter().map(|tz| tz.name()).collect::<Vec<_>>();
+        let suggestion = match registry::suggest(name, &known) {
+            Some(s) => format!(" Did you mean {s}?"),
+            None => String::new(),
+        };
+        anyhow!(
+            "Unknown timezone {name} in the {section} config, \
+             expected an IANA name like Europe/Paris.{suggestion}"
+        )
+    })
+}
+
+/// The day at that local time, an hour later when skipped by the change to
+/// the summer time
+pub fn local(tz: Tz, day: NaiveDate, at: NaiveTime) -> DateTime<Utc> {
+    let local = day.and_time(at);
+    tz.from_local_datetime(&local)
+        .earliest()
+        .or_else(|| {
+            tz.from_local_datetime(&(local + chrono::Duration::hours(1)))
+                .earliest()
+        })
+        .expect("an hour after a gap is a valid local time")
+        .with_timezone(&Utc)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    async fn test_parse_duration() {
+        for (input, expected) in [
+            ("30s", 30),
+            ("10m", 600),
+            ("2h", 7200),
+            ("1h30m", 5400),
+            ("1h30m15s", 5415),
+            ("90m", 5400),
+        ] {
+            assert_eq!(parse_duration(input), Some(secs(expected)), "{input}");
+        }
+        for invalid in [
+            "",
+            "0s",
+            "10",
+            "1h30",
+            "m",
+            "10x",
+            "-5m",
+            "1.5h",
+            "ten minutes",
+            "99999999999999999999s",
+        ] {
+            assert_eq!(parse_duration(invalid), None, "{invalid:?}");
+        }
+    }
+
+    #[test]
+    async fn test_format_duration() {
+        assert_eq!(format_duration(secs(5400)), "1h30m");
+        assert_eq!(format_duration(secs(86400)), "24h");
+        assert_eq!(format_duration(secs(61)), "1m1s");
+        assert_eq!(format_duration(secs(30)), "30s");
+    }
+
+    #[test]
+    async fn test_zone() {
+        assert_eq!(zone("Europe/Paris"), Some(chrono_tz::Europe::Paris));
+        assert_eq!(zone("america/new_york"), Some(chrono_tz::America::New_York));
+        assert_eq!(zone("UTC"), Some(chrono_tz::UTC));
+        assert_eq!(zone("Paris"), None);
+        assert_eq!(zone("Europe/Lyon"), None);
+    }
+
+    #[test]
+    async fn test_parse_timezone() {
+        assert_eq!(
+            parse_timezone("remind", "europe/paris").unwrap(),
+            chrono_tz::Europe::Paris
+        );
+        assert_eq!(
+            parse_timezone("remind", "Europe/Pari")
+                .unwrap_err()
+                .to_string(),
+            "Unknown timezone Europe/Pari in the remind config, expected an IANA name like \
+             Europe/Paris. Did you mean Europe/Paris?"
+        );
+        assert_eq!(
+            parse_timezone("ctcp", "Mars/Olympus_Mons")
+                .unwrap_err()
+                .to_string(),
+            "Unknown timezone Mars/Olympus_Mons in the ctcp config, expected an IANA name like \
+             Europe/Paris."
+        );
+    }
+
+    #[test]
+    async fn test_format_ago() {
+        for (secs, expected) in [
+            (0, "0s ago"),
+            (42, "42s ago"),
+            (60, "1min ago"),
+            (61, "1min 1s ago"),
+            (2 * 3600, "2h ago"),
+            (2 * 3600 + 15 * 60 + 12, "2h 15min ago"),
+            (3 * 86400 + 4 * 3600 + 5, "3d 4h ago"),
+            (3 * 86400 + 5, "3d ago"),
+            (-30, "0s ago"),
+        ] {
+            assert_eq!(
+                format_ago(chrono::Duration::seconds(secs)),
+                expected,
+                "{secs}"
+            );
+        }
+    }
+}
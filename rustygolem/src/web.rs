@@ -0,0 +1,138 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use std::sync::Arc;
+
+/// Mount the routes of a plugin under /{name}. The routes of `router` require
+/// the header `Authorization: Bearer <token>` when a token is given, the ones
+/// of `public_router` never do.
+pub fn plugin_router(
+    name: &str,
+    router: Option<Router<()>>,
+    public_router: Option<Router<()>>,
+    token: Option<String>,
+) -> Option<Router<()>> {
+    let router = match (router, token) {
+        (Some(r), Some(token)) => Some(r.route_layer(middleware::from_fn_with_state(
+            Arc::new(token),
+            require_bearer,
+        ))),
+        (r, _) => r,
+    };
+    let merged = match (router, public_router) {
+        (Some(r), Some(p)) => r.merge(p),
+        (Some(r), None) => r,
+        (None, Some(p)) => p,
+        (None, None) => return None,
+    };
+    Some(Router::new().nest(&format!("/{name}"), merged))
+}
+
+async fn require_bearer<B>(
+    State(token): State<Arc<String>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if is_authorized(req.headers(), &token) {
+        next.run(req).await
+    } else {
+        StatusCode::UNAUTHORIZED.into_response()
+    }
+}
+
+pub fn is_authorized(headers: &HeaderMap, token: &str) -> bool {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+        .unwrap_or(false)
+}
+
+// don't leak the length of the matching prefix through timing
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing;
+    use pretty_assertions::assert_eq;
+    use tower::ServiceExt;
+
+    async fn get_status(app: Router<()>, uri: &str, auth: Option<&str>) -> StatusCode {
+        let mut req = Request::builder().uri(uri);
+        if let Some(auth) = auth {
+            req = req.header("Authorization", auth);
+        }
+        app.oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    fn hello() -> Router<()> {
+        Router::new().route("/hello", routing::get(|| async { "hello" }))
+    }
+
+    fn webhook() -> Router<()> {
+        Router::new().route("/webhook", routing::get(|| async { "webhook" }))
+    }
+
+    #[tokio::test]
+    async fn test_nesting() {
+        let app = plugin_router("foo", Some(hello()), None, None)
+            .unwrap()
+            .merge(plugin_router("bar", Some(hello()), None, None).unwrap());
+
+        assert_eq!(
+            get_status(app.clone(), "/foo/hello", None).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            get_status(app.clone(), "/bar/hello", None).await,
+            StatusCode::OK
+        );
+        assert_eq!(get_status(app, "/hello", None).await, StatusCode::NOT_FOUND);
+
+        assert!(plugin_router("foo", None, None, Some("s3cr3t".to_string())).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_protected_routes() {
+        let app = plugin_router(
+            "foo",
+            Some(hello()),
+            Some(webhook()),
+            Some("s3cr3t".to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            get_status(app.clone(), "/foo/hello", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            get_status(app.clone(), "/foo/hello", Some("Bearer nope")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            get_status(app.clone(), "/foo/hello", Some("Bearer s3cr3t")).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            get_status(app, "/foo/webhook", None).await,
+            StatusCode::OK,
+            "public routes don't need the secret"
+        );
+    }
+}